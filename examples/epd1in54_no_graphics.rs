@@ -12,10 +12,10 @@ use linux_embedded_hal::{
 // needs to be run with sudo because of some sysfs_gpio permission problems and follow-up timing problems
 // see https://github.com/rust-embedded/rust-sysfs-gpio/issues/5 and follow-up issues
 
-fn main() -> Result<(), std::io::Error> {
+fn main() -> Result<(), epd_waveshare::Error<std::io::Error>> {
     // Configure SPI
     // SPI settings are from eink-waveshare-rs documenation
-    let mut spi = Spidev::open("/dev/spidev0.0")?;
+    let mut spi = Spidev::open("/dev/spidev0.0").expect("spidev directory");
     let options = SpidevOptions::new()
         .bits_per_word(8)
         .max_speed_hz(4_000_000)
@@ -58,7 +58,8 @@ fn main() -> Result<(), std::io::Error> {
 
     // Setup of the needed pins is finished here
     // Now the "real" usage of the eink-waveshare-rs crate begins
-    let mut epd = Epd1in54::new(&mut spi, cs_pin, busy, dc, rst, &mut delay)?;
+    let mut epd = Epd1in54::new(cs_pin, busy, dc, rst);
+    epd.init(&mut spi, &mut delay)?;
 
     // Clear the full screen
     epd.clear_frame(&mut spi, &mut delay)?;
@@ -9,7 +9,7 @@ use embedded_graphics::{
 use embedded_hal::prelude::*;
 use epd_waveshare::{
     color::*,
-    epd2in13bc::{Display2in13bc, Epd2in13bc},
+    epd2in13bc::{Display2in13bcTriColor, Epd2in13bc},
     graphics::{DisplayRotation, TriDisplay},
     prelude::*,
 };
@@ -34,7 +34,7 @@ use linux_embedded_hal::{
 //
 // after finishing, put the display to sleep
 
-fn main() -> Result<(), std::io::Error> {
+fn main() -> Result<(), epd_waveshare::Error<std::io::Error>> {
     let busy = Pin::new(24); // GPIO 24, board J-18
     busy.export().expect("busy export");
     while !busy.is_exported() {}
@@ -71,11 +71,13 @@ fn main() -> Result<(), std::io::Error> {
 
     let mut delay = Delay {};
 
-    let mut epd2in13 =
-        Epd2in13bc::new(&mut spi, cs, busy, dc, rst, &mut delay).expect("eink initalize error");
+    let mut epd2in13 = Epd2in13bc::new(cs, busy, dc, rst);
+    epd2in13
+        .init(&mut spi, &mut delay)
+        .expect("eink initalize error");
 
     println!("Test all the rotations");
-    let mut display = Display2in13bc::default();
+    let mut display = Display2in13bcTriColor::default();
     display.clear_buffer(TriColor::White);
 
     display.set_rotation(DisplayRotation::Rotate0);
@@ -94,7 +96,7 @@ fn main() -> Result<(), std::io::Error> {
     // the bw-buffer of this tri-color screen
 
     epd2in13
-        .update_and_display_frame(&mut spi, &display.bw_buffer(), &mut delay)
+        .update_and_display_frame(&mut spi, display.bw_buffer(), &mut delay)
         .expect("display frame new graphics");
 
     println!("First frame done. Waiting 5s");
@@ -154,7 +156,7 @@ fn main() -> Result<(), std::io::Error> {
     epd2in13.sleep(&mut spi, &mut delay)
 }
 
-fn draw_text(display: &mut Display2in13bc, text: &str, x: i32, y: i32) {
+fn draw_text(display: &mut Display2in13bcTriColor, text: &str, x: i32, y: i32) {
     let style = MonoTextStyleBuilder::new()
         .font(&embedded_graphics::mono_font::ascii::FONT_6X10)
         .text_color(TriColor::White)
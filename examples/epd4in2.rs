@@ -23,7 +23,7 @@ use linux_embedded_hal::{
 // needs to be run with sudo because of some sysfs_gpio permission problems and follow-up timing problems
 // see https://github.com/rust-embedded/rust-sysfs-gpio/issues/5 and follow-up issues
 
-fn main() -> Result<(), std::io::Error> {
+fn main() -> Result<(), epd_waveshare::Error<std::io::Error>> {
     // Configure SPI
     // Settings are taken from
     let mut spi = Spidev::open("/dev/spidev0.0").expect("spidev directory");
@@ -61,8 +61,10 @@ fn main() -> Result<(), std::io::Error> {
 
     let mut delay = Delay {};
 
-    let mut epd4in2 =
-        Epd4in2::new(&mut spi, cs, busy, dc, rst, &mut delay).expect("eink initalize error");
+    let mut epd4in2 = Epd4in2::new(cs, busy, dc, rst);
+    epd4in2
+        .init(&mut spi, &mut delay)
+        .expect("eink initalize error");
 
     println!("Test all the rotations");
     let mut display = Display4in2::default();
@@ -79,7 +81,7 @@ fn main() -> Result<(), std::io::Error> {
     display.set_rotation(DisplayRotation::Rotate270);
     draw_text(&mut display, "Rotate 270!", 5, 50);
 
-    epd4in2.update_frame(&mut spi, &display.buffer(), &mut delay)?;
+    epd4in2.update_frame(&mut spi, display.buffer(), &mut delay)?;
     epd4in2
         .display_frame(&mut spi, &mut delay)
         .expect("display frame new graphics");
@@ -135,7 +137,7 @@ fn main() -> Result<(), std::io::Error> {
         draw_text(&mut display, "  Hello World! ", 5 + i * 12, 50);
 
         epd4in2
-            .update_frame(&mut spi, &display.buffer(), &mut delay)
+            .update_frame(&mut spi, display.buffer(), &mut delay)
             .unwrap();
         epd4in2
             .display_frame(&mut spi, &mut delay)
@@ -0,0 +1,82 @@
+#![deny(warnings)]
+
+use epd_waveshare::{epd4in2::Epd4in2, prelude::*, BusyWait};
+use linux_embedded_hal::{
+    spidev::{self, SpidevOptions},
+    sysfs_gpio::{Direction, Edge, PinPoller},
+    Delay, Pin, Spidev,
+};
+use std::sync::Mutex;
+
+// activate spi, gpio in raspi-config
+// needs to be run with sudo because of some sysfs_gpio permission problems and follow-up timing problems
+// see https://github.com/rust-embedded/rust-sysfs-gpio/issues/5 and follow-up issues
+
+/// Sleeps in the kernel until the busy pin changes, instead of polling it
+///
+/// The sysfs gpio interface wakes up the poller on the edges of the pin, like an interrupt.
+struct EdgeWait(Mutex<PinPoller>);
+
+impl BusyWait for EdgeWait {
+    fn wait(&self, _busy_low: bool) {
+        // both edges are armed, the driver checks the level again afterwards. The timeout
+        // only guards against an edge between the driver's check and the poll.
+        self.0.lock().unwrap().poll(1000).expect("busy poll");
+    }
+}
+
+fn main() -> Result<(), epd_waveshare::Error<std::io::Error>> {
+    // Configure SPI
+    let mut spi = Spidev::open("/dev/spidev0.0").expect("spidev directory");
+    let options = SpidevOptions::new()
+        .bits_per_word(8)
+        .max_speed_hz(4_000_000)
+        .mode(spidev::SpiModeFlags::SPI_MODE_0)
+        .build();
+    spi.configure(&options).expect("spi configuration");
+
+    // Configure Digital I/O Pin to be used as Chip Select for SPI
+    let cs = Pin::new(26); //BCM7 CE0
+    cs.export().expect("cs export");
+    while !cs.is_exported() {}
+    cs.set_direction(Direction::Out).expect("CS Direction");
+    cs.set_value(1).expect("CS Value set to 1");
+
+    let busy = Pin::new(5); //pin 29
+    busy.export().expect("busy export");
+    while !busy.is_exported() {}
+    busy.set_direction(Direction::In).expect("busy Direction");
+    busy.set_edge(Edge::BothEdges).expect("busy edge");
+
+    let dc = Pin::new(6); //pin 31 //bcm6
+    dc.export().expect("dc export");
+    while !dc.is_exported() {}
+    dc.set_direction(Direction::Out).expect("dc Direction");
+    dc.set_value(1).expect("dc Value set to 1");
+
+    let rst = Pin::new(16); //pin 36 //bcm16
+    rst.export().expect("rst export");
+    while !rst.is_exported() {}
+    rst.set_direction(Direction::Out).expect("rst Direction");
+    rst.set_value(1).expect("rst Value set to 1");
+
+    // the driver keeps the wait for its whole lifetime
+    let wait = EdgeWait(Mutex::new(busy.get_poller().expect("busy poller")));
+    let wait: &'static EdgeWait = Box::leak(Box::new(wait));
+
+    let mut delay = Delay {};
+
+    let mut epd4in2 = Epd4in2::new(cs, busy, dc, rst);
+    epd4in2.set_busy_wait(wait);
+    epd4in2
+        .init(&mut spi, &mut delay)
+        .expect("eink initalize error");
+
+    println!("Clear the display while sleeping during the refresh");
+    epd4in2.clear_frame(&mut spi, &mut delay)?;
+    epd4in2.display_frame(&mut spi, &mut delay)?;
+
+    println!("Finished tests - going to sleep");
+    epd4in2.sleep(&mut spi, &mut delay)?;
+    Ok(())
+}
@@ -0,0 +1,104 @@
+#![deny(warnings)]
+
+use epd_waveshare::{
+    epd4in2::{self, Epd4in2},
+    pattern,
+    prelude::*,
+    sram::{Sram, SramError},
+};
+use linux_embedded_hal::{
+    spidev::{self, SpidevOptions},
+    sysfs_gpio::Direction,
+    Delay, Pin, Spidev,
+};
+
+// activate spi, gpio in raspi-config
+// needs to be run with sudo because of some sysfs_gpio permission problems and follow-up timing problems
+// see https://github.com/rust-embedded/rust-sysfs-gpio/issues/5 and follow-up issues
+//
+// A 23LC512 (64 KB) on the same bus as the display, with its CS on bcm25. The frame is
+// written into it in small packets like they would come in over a radio and then streamed
+// into the display, the program itself never holds more than a packet and a chunk of it.
+
+/// Size of the SRAM
+const SRAM_SIZE: u32 = 0x1_0000;
+/// Bytes of a packet, e.g. the payload of a radio packet
+const PACKET: usize = 32;
+
+fn main() -> Result<(), SramError<std::io::Error>> {
+    // Configure SPI
+    let mut spi = Spidev::open("/dev/spidev0.0").expect("spidev directory");
+    let options = SpidevOptions::new()
+        .bits_per_word(8)
+        .max_speed_hz(4_000_000)
+        .mode(spidev::SpiModeFlags::SPI_MODE_0)
+        .build();
+    spi.configure(&options).expect("spi configuration");
+
+    // Configure Digital I/O Pin to be used as Chip Select for SPI
+    let cs = Pin::new(26); //BCM7 CE0
+    cs.export().expect("cs export");
+    while !cs.is_exported() {}
+    cs.set_direction(Direction::Out).expect("CS Direction");
+    cs.set_value(1).expect("CS Value set to 1");
+
+    let sram_cs = Pin::new(25); //pin 22
+    sram_cs.export().expect("sram cs export");
+    while !sram_cs.is_exported() {}
+    sram_cs
+        .set_direction(Direction::Out)
+        .expect("sram CS Direction");
+    sram_cs.set_value(1).expect("sram CS Value set to 1");
+
+    let busy = Pin::new(5); //pin 29
+    busy.export().expect("busy export");
+    while !busy.is_exported() {}
+    busy.set_direction(Direction::In).expect("busy Direction");
+
+    let dc = Pin::new(6); //pin 31 //bcm6
+    dc.export().expect("dc export");
+    while !dc.is_exported() {}
+    dc.set_direction(Direction::Out).expect("dc Direction");
+    dc.set_value(1).expect("dc Value set to 1");
+
+    let rst = Pin::new(16); //pin 36 //bcm16
+    rst.export().expect("rst export");
+    while !rst.is_exported() {}
+    rst.set_direction(Direction::Out).expect("rst Direction");
+    rst.set_value(1).expect("rst Value set to 1");
+
+    let mut delay = Delay {};
+
+    let mut epd4in2 = Epd4in2::new(cs, busy, dc, rst);
+    epd4in2.init(&mut spi, &mut delay)?;
+
+    let mut sram = Sram::new(sram_cs, SRAM_SIZE);
+    sram.init(&mut spi)?;
+
+    println!("Staging a checkerboard in the sram");
+    let len = epd4in2::buffer_len();
+    let mut packet = [0; PACKET];
+    for start in (0..len).step_by(PACKET) {
+        // e.g. the next packet of the radio
+        let packet = &mut packet[..PACKET.min(len - start)];
+        for (i, byte) in packet.iter_mut().enumerate() {
+            *byte = pattern::frame_byte(
+                epd4in2::WIDTH,
+                epd4in2::HEIGHT,
+                0,
+                start + i,
+                pattern::checkerboard,
+            );
+        }
+        sram.write(&mut spi, start as u32, packet)?;
+    }
+
+    println!("Sending it to the display");
+    let mut chunk = [0; 256];
+    sram.send_frame(&mut epd4in2, &mut spi, &mut delay, 0, len, &mut chunk)?;
+    epd4in2.display_frame(&mut spi, &mut delay)?;
+
+    println!("Finished - going to sleep");
+    epd4in2.sleep(&mut spi, &mut delay)?;
+    Ok(())
+}
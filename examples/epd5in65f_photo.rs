@@ -0,0 +1,82 @@
+#![deny(warnings)]
+
+use epd_waveshare::{
+    dither::OCT_PALETTE,
+    epd5in65f::Epd5in65f,
+    picture::{self, Fit},
+    prelude::*,
+    stream::StreamError,
+};
+use linux_embedded_hal::{
+    spidev::{self, SpidevOptions},
+    sysfs_gpio::Direction,
+    Delay, Pin, Spidev,
+};
+
+// activate spi, gpio in raspi-config
+// needs to be run with sudo because of some sysfs_gpio permission problems and follow-up timing problems
+// see https://github.com/rust-embedded/rust-sysfs-gpio/issues/5 and follow-up issues
+//
+// Shows a png or jpeg on the 7 color display. The photo is dithered and packed row by row
+// while sending, so the 134 KB frame buffer is never needed. Run with `--features image`.
+
+fn main() -> Result<(), StreamError<std::io::Error, std::convert::Infallible>> {
+    let path = std::env::args()
+        .nth(1)
+        .expect("usage: epd5in65f_photo <photo.jpg>");
+    let image = picture::open(path).expect("open photo");
+
+    // Configure SPI
+    let mut spi = Spidev::open("/dev/spidev0.0").expect("spidev directory");
+    let options = SpidevOptions::new()
+        .bits_per_word(8)
+        .max_speed_hz(4_000_000)
+        .mode(spidev::SpiModeFlags::SPI_MODE_0)
+        .build();
+    spi.configure(&options).expect("spi configuration");
+
+    // Configure Digital I/O Pin to be used as Chip Select for SPI
+    let cs = Pin::new(26); //BCM7 CE0
+    cs.export().expect("cs export");
+    while !cs.is_exported() {}
+    cs.set_direction(Direction::Out).expect("CS Direction");
+    cs.set_value(1).expect("CS Value set to 1");
+
+    let busy = Pin::new(24); //pin 18
+    busy.export().expect("busy export");
+    while !busy.is_exported() {}
+    busy.set_direction(Direction::In).expect("busy Direction");
+
+    let dc = Pin::new(25); //pin 22
+    dc.export().expect("dc export");
+    while !dc.is_exported() {}
+    dc.set_direction(Direction::Out).expect("dc Direction");
+    dc.set_value(1).expect("dc Value set to 1");
+
+    let rst = Pin::new(17); //pin 11
+    rst.export().expect("rst export");
+    while !rst.is_exported() {}
+    rst.set_direction(Direction::Out).expect("rst Direction");
+    rst.set_value(1).expect("rst Value set to 1");
+
+    let mut delay = Delay {};
+
+    let mut epd = Epd5in65f::new(cs, busy, dc, rst);
+    epd.init(&mut spi, &mut delay)
+        .expect("eink initalize error");
+
+    // the whole photo, with white bars if it doesn't have the aspect ratio of the display
+    picture::stream(
+        &mut epd,
+        &mut spi,
+        &mut delay,
+        &image,
+        Fit::Letterbox,
+        OctColor::White,
+        &OCT_PALETTE,
+    )?;
+    epd.display_frame(&mut spi, &mut delay)?;
+
+    println!("Finished - going to sleep");
+    Ok(epd.sleep(&mut spi, &mut delay)?)
+}
@@ -0,0 +1,77 @@
+#![deny(warnings)]
+
+use epd_waveshare::{
+    dither::BW_PALETTE,
+    epd7in5_v2::{Display7in5, Epd7in5},
+    picture::{self, Fit},
+    prelude::*,
+};
+use linux_embedded_hal::{
+    spidev::{self, SpidevOptions},
+    sysfs_gpio::Direction,
+    Delay, Pin, Spidev,
+};
+
+// activate spi, gpio in raspi-config
+// needs to be run with sudo because of some sysfs_gpio permission problems and follow-up timing problems
+// see https://github.com/rust-embedded/rust-sysfs-gpio/issues/5 and follow-up issues
+//
+// Shows a png or jpeg in portrait orientation on the b/w 7.5" V2 display. The photo is
+// drawn into the display buffer, which takes care of the rotation, and the buffer is sent
+// as a whole. Run with `--features image`.
+
+fn main() -> Result<(), epd_waveshare::Error<std::io::Error>> {
+    let path = std::env::args()
+        .nth(1)
+        .expect("usage: epd7in5_v2_photo <photo.jpg>");
+    let image = picture::open(path).expect("open photo");
+
+    // Configure SPI
+    let mut spi = Spidev::open("/dev/spidev0.0").expect("spidev directory");
+    let options = SpidevOptions::new()
+        .bits_per_word(8)
+        .max_speed_hz(4_000_000)
+        .mode(spidev::SpiModeFlags::SPI_MODE_0)
+        .build();
+    spi.configure(&options).expect("spi configuration");
+
+    // Configure Digital I/O Pin to be used as Chip Select for SPI
+    let cs = Pin::new(26); //BCM7 CE0
+    cs.export().expect("cs export");
+    while !cs.is_exported() {}
+    cs.set_direction(Direction::Out).expect("CS Direction");
+    cs.set_value(1).expect("CS Value set to 1");
+
+    let busy = Pin::new(24); //pin 18
+    busy.export().expect("busy export");
+    while !busy.is_exported() {}
+    busy.set_direction(Direction::In).expect("busy Direction");
+
+    let dc = Pin::new(25); //pin 22
+    dc.export().expect("dc export");
+    while !dc.is_exported() {}
+    dc.set_direction(Direction::Out).expect("dc Direction");
+    dc.set_value(1).expect("dc Value set to 1");
+
+    let rst = Pin::new(17); //pin 11
+    rst.export().expect("rst export");
+    while !rst.is_exported() {}
+    rst.set_direction(Direction::Out).expect("rst Direction");
+    rst.set_value(1).expect("rst Value set to 1");
+
+    let mut delay = Delay {};
+
+    let mut epd = Epd7in5::new(cs, busy, dc, rst);
+    epd.init(&mut spi, &mut delay)
+        .expect("eink initalize error");
+
+    // cropped to fill the whole display
+    let mut display = Display7in5::default();
+    display.set_rotation(DisplayRotation::Rotate90);
+    let _ = picture::draw(&mut display, &image, Fit::Crop, Color::White, &BW_PALETTE);
+
+    epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay)?;
+
+    println!("Finished - going to sleep");
+    epd.sleep(&mut spi, &mut delay)
+}
@@ -0,0 +1,529 @@
+//! Drawing images with arbitrary colors onto the display buffers
+//!
+//! [draw_image] takes anything implementing [ImageDrawable] with colors convertible to
+//! [Rgb888], e.g. a `tinybmp::Bmp` (1 bpp, RGB555, RGB565 and RGB888) or an
+//! `embedded_graphics::image::ImageRaw`, and maps every pixel to the color of the display
+//! with a [ColorMatcher].
+//!
+//! With the `bmp` feature, `draw_bmp` reads bmp files with `tinybmp` and draws them the
+//! same way.
+//!
+//! Example:
+//! ```rust,no_run
+//! # use epd_waveshare::bitmap::{draw_image, Nearest};
+//! # use epd_waveshare::epd5in65f::Display5in65f;
+//! # use embedded_graphics::{image::ImageRaw, pixelcolor::Rgb888, prelude::*};
+//! // two pixels, red and orange-ish
+//! let data = [255, 0, 0, 250, 120, 20];
+//! let image = ImageRaw::<Rgb888>::new(&data, 2);
+//!
+//! let mut display = Display5in65f::default();
+//! let _ = draw_image(&mut display, &image, Point::new(10, 20), &Nearest);
+//! ```
+
+use crate::color::{lab_distance, Color, OctColor, TriColor};
+use crate::dither::{ColorDistance, Palette, BW_PALETTE, OCT_PALETTE, TRI_RED_PALETTE};
+use embedded_graphics_core::image::ImageDrawable;
+use embedded_graphics_core::pixelcolor::{BinaryColor, Gray2, Rgb888, RgbColor};
+use embedded_graphics_core::prelude::*;
+use embedded_graphics_core::primitives::Rectangle;
+
+/// Picks the display color for a pixel of an image
+///
+/// Implemented for closures, so a custom mapping is just `&|rgb: Rgb888| ...`.
+pub trait ColorMatcher<C> {
+    /// Display color for `color`
+    fn matching(&self, color: Rgb888) -> C;
+}
+
+impl<C, F: Fn(Rgb888) -> C> ColorMatcher<C> for F {
+    fn matching(&self, color: Rgb888) -> C {
+        self(color)
+    }
+}
+
+/// Matches every color with the nearest color of the display
+///
+/// Black and white are chosen by brightness. For tri-color displays the chromatic
+/// color is assumed to be red. Colors are compared with how they look on the panel,
+/// the same palettes the [dithering](crate::dither) uses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Nearest;
+
+impl ColorMatcher<BinaryColor> for Nearest {
+    fn matching(&self, color: Rgb888) -> BinaryColor {
+        BinaryColor::from(ColorMatcher::<Color>::matching(self, color))
+    }
+}
+
+/// White from a brightness of 128 on, the threshold of [Color::from_luma]
+impl ColorMatcher<Color> for Nearest {
+    fn matching(&self, color: Rgb888) -> Color {
+        Color::from_luma(luma(color) as u8, 255)
+    }
+}
+
+/// The 4 gray levels by brightness
+impl ColorMatcher<Gray2> for Nearest {
+    fn matching(&self, color: Rgb888) -> Gray2 {
+        Gray2::new(luma(color) as u8 >> 6)
+    }
+}
+
+impl ColorMatcher<TriColor> for Nearest {
+    fn matching(&self, color: Rgb888) -> TriColor {
+        nearest(color, &TRI_RED_PALETTE).0
+    }
+}
+
+impl ColorMatcher<OctColor> for Nearest {
+    fn matching(&self, color: Rgb888) -> OctColor {
+        nearest(color, &OCT_PALETTE).0
+    }
+}
+
+/// Like [Nearest], but compares how different the colors look
+///
+/// Uses the CIE76 distance in L\*a\*b\* against the same palettes, see
+/// [OctColor::nearest_perceptual]. Somewhat slower, but dark blues stay blue and browns
+/// stay orange on the 7 color displays.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Perceptual;
+
+impl ColorMatcher<Color> for Perceptual {
+    fn matching(&self, color: Rgb888) -> Color {
+        nearest_perceptual(color, &BW_PALETTE).0
+    }
+}
+
+impl ColorMatcher<TriColor> for Perceptual {
+    fn matching(&self, color: Rgb888) -> TriColor {
+        nearest_perceptual(color, &TRI_RED_PALETTE).0
+    }
+}
+
+impl ColorMatcher<OctColor> for Perceptual {
+    fn matching(&self, color: Rgb888) -> OctColor {
+        OctColor::nearest_perceptual(color.r(), color.g(), color.b())
+    }
+}
+
+/// Matches every color with the nearest color of a custom [Palette]
+///
+/// For panels whose colors differ from the palettes of the crate, e.g. another batch or
+/// another manufacturer.
+#[derive(Debug, Clone, Copy)]
+pub struct InPalette<'a, C> {
+    palette: &'a Palette<C>,
+    distance: ColorDistance,
+}
+
+impl<'a, C> InPalette<'a, C> {
+    /// Matcher for the colors of `palette` with the [ColorDistance::Rgb] distance
+    ///
+    /// # Panics
+    ///
+    /// If `palette` is empty.
+    pub fn new(palette: &'a Palette<C>) -> Self {
+        assert!(!palette.is_empty(), "palette has no colors");
+        InPalette {
+            palette,
+            distance: ColorDistance::Rgb,
+        }
+    }
+
+    /// Chooses the nearest color with `distance` instead
+    pub fn with_distance(self, distance: ColorDistance) -> Self {
+        InPalette { distance, ..self }
+    }
+}
+
+impl<C: Copy> ColorMatcher<C> for InPalette<'_, C> {
+    fn matching(&self, color: Rgb888) -> C {
+        match self.distance {
+            ColorDistance::Rgb => nearest(color, self.palette).0,
+            ColorDistance::Perceptual => nearest_perceptual(color, self.palette).0,
+        }
+    }
+}
+
+fn luma(color: Rgb888) -> u32 {
+    (u32::from(color.r()) * 299 + u32::from(color.g()) * 587 + u32::from(color.b()) * 114) / 1000
+}
+
+/// Entry of `palette` closest to `color`
+pub(crate) fn nearest<C: Copy>(color: Rgb888, palette: &Palette<C>) -> (C, Rgb888) {
+    let distance = |other: Rgb888| {
+        let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+        d(color.r(), other.r()) + d(color.g(), other.g()) + d(color.b(), other.b())
+    };
+    palette
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .copied()
+        .expect("palette isn't empty")
+}
+
+/// Entry of `palette` which looks closest to `color`
+pub(crate) fn nearest_perceptual<C: Copy>(color: Rgb888, palette: &Palette<C>) -> (C, Rgb888) {
+    let lab = |color: Rgb888| crate::color::lab(color.r(), color.g(), color.b());
+    let wanted = lab(color);
+    palette
+        .iter()
+        .min_by_key(|(_, rgb)| lab_distance(wanted, lab(*rgb)))
+        .copied()
+        .expect("palette isn't empty")
+}
+
+/// Draws `image` with its top left corner at `offset`
+///
+/// Parts of the image outside of the display are left out.
+pub fn draw_image<D, I, M>(
+    target: &mut D,
+    image: &I,
+    offset: Point,
+    matcher: &M,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget,
+    I: ImageDrawable,
+    I::Color: Into<Rgb888>,
+    M: ColorMatcher<D::Color>,
+{
+    image.draw(&mut Matched {
+        target,
+        offset,
+        matcher,
+        _color: core::marker::PhantomData,
+    })
+}
+
+/// Error of [draw_bmp]
+#[cfg(feature = "bmp")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BmpError<E> {
+    /// The data isn't a bitmap `tinybmp` can read
+    Parse(tinybmp::ParseError),
+    /// Error of the display
+    Draw(E),
+}
+
+#[cfg(feature = "bmp")]
+impl<E: core::fmt::Debug> core::fmt::Display for BmpError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BmpError::Parse(e) => write!(f, "Reading the bitmap failed: {:?}", e),
+            BmpError::Draw(e) => write!(f, "Drawing the bitmap failed: {:?}", e),
+        }
+    }
+}
+
+/// Reads the bmp file `data` and draws it with its top left corner at `offset`
+///
+/// Takes 1, 4 and 8 bpp bitmaps with a color table as well as RGB555, RGB565 and RGB888
+/// bitmaps. The colors are mapped with `matcher` and clipped like in [draw_image].
+///
+/// Example:
+/// ```rust,no_run
+/// # use epd_waveshare::bitmap::{draw_bmp, Nearest};
+/// # use epd_waveshare::epd5in65f::Display5in65f;
+/// # use embedded_graphics::prelude::*;
+/// # let data: &[u8] = &[];
+/// // e.g. `include_bytes!("photo.bmp")`
+/// let mut display = Display5in65f::default();
+/// draw_bmp(&mut display, data, Point::zero(), &Nearest).expect("valid bitmap");
+/// ```
+#[cfg(feature = "bmp")]
+pub fn draw_bmp<D, M>(
+    target: &mut D,
+    data: &[u8],
+    offset: Point,
+    matcher: &M,
+) -> Result<(), BmpError<D::Error>>
+where
+    D: DrawTarget,
+    M: ColorMatcher<D::Color>,
+{
+    let bmp = tinybmp::Bmp::<Rgb888>::from_slice(data).map_err(BmpError::Parse)?;
+    draw_image(target, &bmp, offset, matcher).map_err(BmpError::Draw)
+}
+
+/// Converts and moves the pixels of the image on their way to the display
+struct Matched<'a, D, M, C> {
+    target: &'a mut D,
+    offset: Point,
+    matcher: &'a M,
+    _color: core::marker::PhantomData<C>,
+}
+
+impl<'a, D, M, C> Dimensions for Matched<'a, D, M, C>
+where
+    D: DrawTarget,
+{
+    fn bounding_box(&self) -> Rectangle {
+        let display = self.target.bounding_box();
+        let top_left = Point::new(
+            display.top_left.x.saturating_sub(self.offset.x),
+            display.top_left.y.saturating_sub(self.offset.y),
+        );
+        Rectangle::new(top_left, display.size)
+    }
+}
+
+impl<'a, D, M, C> DrawTarget for Matched<'a, D, M, C>
+where
+    D: DrawTarget,
+    M: ColorMatcher<D::Color>,
+    C: PixelColor + Into<Rgb888>,
+{
+    type Color = C;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (offset, matcher) = (self.offset, self.matcher);
+        // pixels moved beyond the range of i32 can't be on the display anyway
+        self.target
+            .draw_iter(pixels.into_iter().filter_map(|Pixel(p, c)| {
+                let p = Point::new(p.x.checked_add(offset.x)?, p.y.checked_add(offset.y)?);
+                Some(Pixel(p, matcher.matching(c.into())))
+            }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::{OctDisplay, TriDisplay, VarOctDisplay, VarTriDisplay};
+    use embedded_graphics::image::ImageRaw;
+    use embedded_graphics::pixelcolor::GrayColor;
+
+    #[test]
+    fn nearest_colors() {
+        let m = |r, g, b| ColorMatcher::<OctColor>::matching(&Nearest, Rgb888::new(r, g, b));
+        assert_eq!(m(250, 10, 10), OctColor::Red);
+        // the panel's orange is dark, bright orange is closer to its yellow
+        assert_eq!(m(250, 140, 30), OctColor::Yellow);
+        assert_eq!(m(190, 110, 60), OctColor::Orange);
+        assert_eq!(m(20, 20, 200), OctColor::Blue);
+        assert_eq!(m(230, 230, 230), OctColor::White);
+
+        let m = |r, g, b| ColorMatcher::<TriColor>::matching(&Nearest, Rgb888::new(r, g, b));
+        assert_eq!(m(200, 30, 30), TriColor::Chromatic);
+        assert_eq!(m(40, 40, 40), TriColor::Black);
+
+        let m = |r, g, b| ColorMatcher::<Color>::matching(&Nearest, Rgb888::new(r, g, b));
+        assert_eq!(m(100, 100, 100), Color::Black);
+        assert_eq!(m(0, 255, 255), Color::White);
+        assert_eq!(m(127, 127, 127), Color::Black);
+        assert_eq!(m(128, 128, 128), Color::White);
+
+        let m = |l| ColorMatcher::<BinaryColor>::matching(&Nearest, Rgb888::new(l, l, l));
+        assert_eq!(m(127), BinaryColor::On);
+        assert_eq!(m(128), BinaryColor::Off);
+
+        let m = |l| ColorMatcher::<Gray2>::matching(&Nearest, Rgb888::new(l, l, l)).luma();
+        assert_eq!([m(0), m(63), m(64), m(150), m(255)], [0, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn perceptual_colors() {
+        let m = |r, g, b| ColorMatcher::<OctColor>::matching(&Perceptual, Rgb888::new(r, g, b));
+        assert_eq!(m(20, 30, 80), OctColor::Blue);
+        assert_eq!(m(141, 85, 36), OctColor::Orange);
+        assert_eq!(m(150, 60, 70), OctColor::Red);
+
+        let m = |r, g, b| ColorMatcher::<TriColor>::matching(&Perceptual, Rgb888::new(r, g, b));
+        assert_eq!(m(200, 30, 30), TriColor::Chromatic);
+        assert_eq!(m(40, 40, 40), TriColor::Black);
+
+        // by lightness, a saturated blue is dark and a yellow light
+        let m = |r, g, b| ColorMatcher::<Color>::matching(&Perceptual, Rgb888::new(r, g, b));
+        assert_eq!(m(0, 0, 255), Color::Black);
+        assert_eq!(m(255, 255, 0), Color::White);
+    }
+
+    #[test]
+    fn custom_palette() {
+        let palette = [
+            (TriColor::Black, Rgb888::new(30, 30, 30)),
+            (TriColor::White, Rgb888::new(220, 220, 210)),
+            (TriColor::Chromatic, Rgb888::new(200, 180, 40)),
+        ];
+        let matcher = InPalette::new(&palette);
+        assert_eq!(matcher.matching(Rgb888::YELLOW), TriColor::Chromatic);
+        assert_eq!(matcher.matching(Rgb888::new(90, 90, 90)), TriColor::Black);
+        assert_eq!(matcher.matching(Rgb888::WHITE), TriColor::White);
+
+        // the brown is closer to black in rgb, but looks more like the dark yellow
+        let brown = Rgb888::new(120, 100, 20);
+        assert_eq!(matcher.matching(brown), TriColor::Black);
+        let matcher = matcher.with_distance(ColorDistance::Perceptual);
+        assert_eq!(matcher.matching(brown), TriColor::Chromatic);
+    }
+
+    #[test]
+    fn clips_outside_of_display() {
+        #[rustfmt::skip]
+        let data = [
+            255, 0, 0,   0, 0, 255,   0, 255, 0,
+            0, 0, 0,     255, 255, 0, 255, 255, 255,
+        ];
+        let image = ImageRaw::<Rgb888>::new(&data, 3);
+        let white = OctColor::colors_byte(OctColor::White, OctColor::White);
+        let mut buffer = [white; 2 * 2];
+        let mut display = VarOctDisplay::new(4, 2, &mut buffer).unwrap();
+
+        // far outside of the display
+        draw_image(
+            &mut display,
+            &image,
+            Point::new(i32::MAX, i32::MIN),
+            &Nearest,
+        )
+        .unwrap();
+        draw_image(&mut display, &image, Point::new(i32::MIN, 0), &Nearest).unwrap();
+        assert!(display.buffer().iter().all(|&byte| byte == white));
+
+        // the first column and the last row are outside
+        draw_image(&mut display, &image, Point::new(-1, 1), &Nearest).unwrap();
+        assert_eq!(
+            display.buffer(),
+            &[
+                white,
+                white,
+                OctColor::colors_byte(OctColor::Blue, OctColor::Green),
+                white
+            ]
+        );
+    }
+
+    /// Bmp file with the rows from the bottom up and a V3 header for the color masks
+    #[cfg(feature = "bmp")]
+    fn bmp(
+        width: i32,
+        rows: &[&[u8]],
+        bpp: u16,
+        masks: Option<[u32; 4]>,
+        table: &[u8],
+    ) -> std::vec::Vec<u8> {
+        extern crate std;
+        let le = |v: u32| v.to_le_bytes();
+        let stride = rows[0].len().div_ceil(4) * 4;
+        let offset = 14 + 56 + table.len() as u32;
+        let size = offset + (stride * rows.len()) as u32;
+        let mut data = b"BM".to_vec();
+        for field in [size, 0, offset, 56, width as u32, rows.len() as u32] {
+            data.extend(le(field));
+        }
+        data.extend(1u16.to_le_bytes());
+        data.extend(bpp.to_le_bytes());
+        let compression = if masks.is_some() { 3 } else { 0 };
+        for field in [compression, (stride * rows.len()) as u32, 0, 0, 0, 0] {
+            data.extend(le(field));
+        }
+        for mask in masks.unwrap_or_default() {
+            data.extend(le(mask));
+        }
+        data.extend(table);
+        for row in rows.iter().rev() {
+            data.extend(*row);
+            data.resize(data.len() + stride - row.len(), 0);
+        }
+        data
+    }
+
+    #[cfg(feature = "bmp")]
+    #[test]
+    fn bmp_files() {
+        let white = OctColor::colors_byte(OctColor::White, OctColor::White);
+        let draw = |data: &[u8]| {
+            let mut buffer = [white; 2 * 2];
+            let mut display = VarOctDisplay::new(4, 2, &mut buffer).unwrap();
+            draw_bmp(&mut display, data, Point::new(1, 0), &Nearest).unwrap();
+            buffer
+        };
+        let oct = OctColor::colors_byte;
+        // red, blue in the first row, green, black in the second
+        let expected = [
+            oct(OctColor::White, OctColor::Red),
+            oct(OctColor::Blue, OctColor::White),
+            oct(OctColor::White, OctColor::Green),
+            oct(OctColor::Black, OctColor::White),
+        ];
+
+        // bgr
+        let rgb888 = bmp(
+            2,
+            &[&[0, 0, 255, 255, 0, 0], &[0, 255, 0, 0, 0, 0]],
+            24,
+            None,
+            &[],
+        );
+        assert_eq!(draw(&rgb888), expected);
+        // 16 bpp without masks are RGB555
+        let rgb555 = bmp(
+            2,
+            &[&[0x00, 0x7c, 0x1f, 0x00], &[0xe0, 0x03, 0, 0]],
+            16,
+            None,
+            &[],
+        );
+        assert_eq!(draw(&rgb555), expected);
+        let masks = Some([0xf800, 0x07e0, 0x001f, 0]);
+        let rgb565 = bmp(
+            2,
+            &[&[0x00, 0xf8, 0x1f, 0x00], &[0xe0, 0x07, 0, 0]],
+            16,
+            masks,
+            &[],
+        );
+        assert_eq!(draw(&rgb565), expected);
+
+        // 1 bpp with a table of white and red
+        let table = [255, 255, 255, 0, 0, 0, 255, 0];
+        let indexed = bmp(3, &[&[0b0100_0000], &[0b1010_0000]], 1, None, &table);
+        let mut buffer = [white; 2 * 2];
+        let mut display = VarOctDisplay::new(4, 2, &mut buffer).unwrap();
+        draw_bmp(&mut display, &indexed, Point::new(-1, 0), &Nearest).unwrap();
+        assert_eq!(
+            buffer,
+            [
+                oct(OctColor::Red, OctColor::White),
+                white,
+                oct(OctColor::White, OctColor::Red),
+                white
+            ]
+        );
+
+        let mut display = VarOctDisplay::new(4, 2, &mut buffer).unwrap();
+        assert_eq!(
+            draw_bmp(&mut display, b"PNG", Point::zero(), &Nearest),
+            Err(BmpError::Parse(tinybmp::ParseError::InvalidFileSignature(
+                *b"PN"
+            )))
+        );
+    }
+
+    #[test]
+    fn custom_matcher_and_binary_images() {
+        // 1 bit per pixel like a b/w bitmap, set bits are white
+        let data = [0b1000_0000, 0b0100_0000];
+        let image = ImageRaw::<BinaryColor>::new(&data, 2);
+        let mut buffer = [0xff; 2 * 2];
+        let mut display = VarTriDisplay::new(2, 2, &mut buffer).unwrap();
+
+        let chromatic_or_white = |rgb: Rgb888| {
+            if rgb == Rgb888::WHITE {
+                TriColor::White
+            } else {
+                TriColor::Chromatic
+            }
+        };
+        draw_image(&mut display, &image, Point::zero(), &chromatic_or_white).unwrap();
+        assert_eq!(display.bw_buffer(), &[0xff, 0xff]);
+        assert_eq!(display.chromatic_buffer(), &[0xbf, 0x7f]);
+    }
+}
@@ -1,9 +1,16 @@
 //! B/W Color for EPDs
 
+use crate::frame::FrameDataError;
+use crate::layout;
+use core::convert::TryFrom;
 #[cfg(feature = "graphics")]
-use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics_core::pixelcolor::raw::{RawData, RawU2, RawU4};
 #[cfg(feature = "graphics")]
-use embedded_graphics::pixelcolor::PixelColor;
+use embedded_graphics_core::pixelcolor::PixelColor;
+#[cfg(feature = "graphics")]
+use embedded_graphics_core::pixelcolor::{
+    BinaryColor, Gray2, Gray4, Gray8, GrayColor, Rgb565, Rgb888, RgbColor,
+};
 
 #[cfg(feature = "graphics")]
 pub use BinaryColor::Off as White;
@@ -25,8 +32,13 @@ impl OutOfColorRangeParseError {
     }
 }
 
+// black and white of the b/w and tri-color panels
+const PANEL_BLACK: (u8, u8, u8) = (38, 38, 38);
+const PANEL_WHITE: (u8, u8, u8) = (255, 255, 255);
+
 /// Only for the Black/White-Displays
 #[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     /// Black color
     Black,
@@ -36,6 +48,7 @@ pub enum Color {
 
 /// Only for the Black/White/Color-Displays
 #[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TriColor {
     /// Black color
     Black,
@@ -47,6 +60,7 @@ pub enum TriColor {
 
 /// For the 5in65 7 Color Display
 #[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OctColor {
     /// Black Color
     Black = 0x00,
@@ -72,9 +86,37 @@ impl From<()> for OctColor {
     }
 }
 
+/// The color of a nibble like [from_nibble](OctColor::from_nibble), but values above
+/// `0x0f` fail instead of being cut to their lower nibble
+impl TryFrom<u8> for OctColor {
+    type Error = OutOfColorRangeParseError;
+
+    fn try_from(value: u8) -> Result<OctColor, OutOfColorRangeParseError> {
+        if value > 0x0f {
+            return Err(OutOfColorRangeParseError(value));
+        }
+        OctColor::from_nibble(value)
+    }
+}
+
 #[cfg(feature = "graphics")]
 impl PixelColor for OctColor {
-    type Raw = ();
+    type Raw = RawU4;
+}
+
+/// Raw data is the nibble as sent to the display, invalid nibbles become white
+#[cfg(feature = "graphics")]
+impl From<RawU4> for OctColor {
+    fn from(raw: RawU4) -> OctColor {
+        OctColor::from_nibble(raw.into_inner()).unwrap_or(OctColor::White)
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl From<OctColor> for RawU4 {
+    fn from(color: OctColor) -> RawU4 {
+        RawU4::new(color.get_nibble())
+    }
 }
 
 impl OctColor {
@@ -103,11 +145,24 @@ impl OctColor {
     }
     ///Split the nibbles of a single byte and convert both to an OctColor if possible
     pub fn split_byte(byte: u8) -> Result<(OctColor, OctColor), OutOfColorRangeParseError> {
-        let low = OctColor::from_nibble(byte & 0xf)?;
-        let high = OctColor::from_nibble((byte >> 4) & 0xf)?;
-        Ok((high, low))
+        Ok((OctColor::high_pixel(byte)?, OctColor::low_pixel(byte)?))
+    }
+    /// The left pixel of a byte packed with [colors_byte](OctColor::colors_byte), the
+    /// upper nibble
+    pub fn high_pixel(byte: u8) -> Result<OctColor, OutOfColorRangeParseError> {
+        OctColor::from_nibble(byte >> 4)
+    }
+    /// The right pixel of a byte packed with [colors_byte](OctColor::colors_byte), the
+    /// lower nibble
+    pub fn low_pixel(byte: u8) -> Result<OctColor, OutOfColorRangeParseError> {
+        OctColor::from_nibble(byte & 0x0f)
     }
     /// Converts to limited range of RGB values.
+    ///
+    /// The ideal primaries, which the panel can't show. Nothing in the crate uses them, the
+    /// color matching, the dithering and the dumps all go by
+    /// [panel_rgb](OctColor::panel_rgb).
+    #[deprecated(since = "0.6.0", note = "use `panel_rgb`, the colors of the panel")]
     pub fn rgb(self) -> (u8, u8, u8) {
         match self {
             OctColor::White => (0xff, 0xff, 0xff),
@@ -120,26 +175,521 @@ impl OctColor {
             OctColor::HiZ => (0x80, 0x80, 0x80), /* looks greyish */
         }
     }
+
+    /// How the color looks on the panel, measured on a 5.65" ACeP display
+    ///
+    /// The one table of rgb values for the 7 colors: [nearest](OctColor::nearest),
+    /// `From<Rgb888>`, [OCT_PALETTE](crate::dither::OCT_PALETTE) of the dithering and the
+    /// `dump` files are built on it. `HiZ` isn't a real color and gets a grey.
+    pub const fn panel_rgb(self) -> (u8, u8, u8) {
+        match self {
+            OctColor::Black => (57, 48, 57),
+            OctColor::White => (255, 255, 255),
+            OctColor::Green => (58, 91, 70),
+            OctColor::Blue => (61, 59, 94),
+            OctColor::Red => (156, 72, 75),
+            OctColor::Yellow => (208, 190, 71),
+            OctColor::Orange => (177, 106, 73),
+            OctColor::HiZ => (0x80, 0x80, 0x80),
+        }
+    }
+
+    /// The color of the panel closest to an rgb color
+    ///
+    /// Compares against the [panel_rgb](OctColor::panel_rgb) of all colors but `HiZ`, like
+    /// the [dithering](crate::dither::OCT_PALETTE) does.
+    pub fn nearest(r: u8, g: u8, b: u8) -> OctColor {
+        let distance = |color: OctColor| {
+            let (pr, pg, pb) = color.panel_rgb();
+            let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+            d(r, pr) + d(g, pg) + d(b, pb)
+        };
+        PANEL_COLORS
+            .iter()
+            .copied()
+            .min_by_key(|&color| distance(color))
+            .unwrap_or(OctColor::White)
+    }
+
+    /// Like [nearest](OctColor::nearest), but compares how different the colors look
+    ///
+    /// Uses the CIE76 distance in L\*a\*b\*, so dark blues stay blue instead of becoming
+    /// black and browns end up orange instead of red.
+    pub fn nearest_perceptual(r: u8, g: u8, b: u8) -> OctColor {
+        let color = lab(r, g, b);
+        PANEL_LAB
+            .iter()
+            .min_by_key(|(_, panel)| lab_distance(color, *panel))
+            .map_or(OctColor::White, |&(color, _)| color)
+    }
+}
+
+// the colors of the panel, without HiZ
+const PANEL_COLORS: [OctColor; 7] = [
+    OctColor::Black,
+    OctColor::White,
+    OctColor::Green,
+    OctColor::Blue,
+    OctColor::Red,
+    OctColor::Yellow,
+    OctColor::Orange,
+];
+
+const PANEL_LAB: [(OctColor, [i32; 3]); 7] = {
+    let mut table = [(OctColor::White, [0; 3]); 7];
+    let mut i = 0;
+    while i < table.len() {
+        let (r, g, b) = PANEL_COLORS[i].panel_rgb();
+        table[i] = (PANEL_COLORS[i], lab(r, g, b));
+        i += 1;
+    }
+    table
+};
+
+/// sRGB to linear light, 0 to 65535
+#[rustfmt::skip]
+const LINEAR: [u16; 256] = [
+    0, 20, 40, 60, 80, 99, 119, 139, 159, 179, 199, 219,
+    241, 264, 288, 313, 340, 367, 396, 427, 458, 491, 526, 562,
+    599, 637, 677, 718, 761, 805, 851, 898, 947, 997, 1048, 1101,
+    1156, 1212, 1270, 1330, 1391, 1453, 1517, 1583, 1651, 1720, 1790, 1863,
+    1937, 2013, 2090, 2170, 2250, 2333, 2418, 2504, 2592, 2681, 2773, 2866,
+    2961, 3058, 3157, 3258, 3360, 3464, 3570, 3678, 3788, 3900, 4014, 4129,
+    4247, 4366, 4488, 4611, 4736, 4864, 4993, 5124, 5257, 5392, 5530, 5669,
+    5810, 5953, 6099, 6246, 6395, 6547, 6700, 6856, 7014, 7174, 7335, 7500,
+    7666, 7834, 8004, 8177, 8352, 8528, 8708, 8889, 9072, 9258, 9445, 9635,
+    9828, 10022, 10219, 10417, 10619, 10822, 11028, 11235, 11446, 11658, 11873, 12090,
+    12309, 12530, 12754, 12980, 13209, 13440, 13673, 13909, 14146, 14387, 14629, 14874,
+    15122, 15371, 15623, 15878, 16135, 16394, 16656, 16920, 17187, 17456, 17727, 18001,
+    18277, 18556, 18837, 19121, 19407, 19696, 19987, 20281, 20577, 20876, 21177, 21481,
+    21787, 22096, 22407, 22721, 23038, 23357, 23678, 24002, 24329, 24658, 24990, 25325,
+    25662, 26001, 26344, 26688, 27036, 27386, 27739, 28094, 28452, 28813, 29176, 29542,
+    29911, 30282, 30656, 31033, 31412, 31794, 32179, 32567, 32957, 33350, 33745, 34143,
+    34544, 34948, 35355, 35764, 36176, 36591, 37008, 37429, 37852, 38278, 38706, 39138,
+    39572, 40009, 40449, 40891, 41337, 41785, 42236, 42690, 43147, 43606, 44069, 44534,
+    45002, 45473, 45947, 46423, 46903, 47385, 47871, 48359, 48850, 49344, 49841, 50341,
+    50844, 51349, 51858, 52369, 52884, 53401, 53921, 54445, 54971, 55500, 56032, 56567,
+    57105, 57646, 58190, 58737, 59287, 59840, 60396, 60955, 61517, 62082, 62650, 63221,
+    63795, 64372, 64952, 65535,
+];
+
+/// CIE L\*a\*b\* of an sRGB color with the D65 white point, scaled by 1024
+///
+/// Integer only, so it works without floating point support.
+pub(crate) const fn lab(r: u8, g: u8, b: u8) -> [i32; 3] {
+    let r = LINEAR[r as usize] as i64;
+    let g = LINEAR[g as usize] as i64;
+    let b = LINEAR[b as usize] as i64;
+    // XYZ relative to the white point, each row sums up to 4096
+    let x = (1777 * r + 1541 * g + 778 * b) >> 12;
+    let y = (871 * r + 2929 * g + 296 * b) >> 12;
+    let z = (73 * r + 448 * g + 3575 * b) >> 12;
+    let (fx, fy, fz) = (lab_f(x), lab_f(y), lab_f(z));
+    [116 * fy - 16 * 1024, 500 * (fx - fy), 200 * (fy - fz)]
+}
+
+/// Squared CIE76 distance of two [lab] colors
+pub(crate) fn lab_distance(a: [i32; 3], b: [i32; 3]) -> i64 {
+    let d = |i: usize| (i64::from(a[i]) - i64::from(b[i])).pow(2);
+    d(0) + d(1) + d(2)
+}
+
+// the cube root of t / 65535 scaled by 1024, linear for the darkest values
+const fn lab_f(t: i64) -> i32 {
+    if t <= 580 {
+        return (t * 1024 / 8416) as i32 + 141;
+    }
+    // binary search of the cube root of t / 65535 * 2^30
+    let v = (t << 30) / 65535;
+    let (mut low, mut high) = (0i64, 1025i64);
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        if mid * mid * mid <= v {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    low as i32
+}
+
+/// The nearest color of the panel, by the rgb distance to the
+/// [panel_rgb](OctColor::panel_rgb) values like [OctColor::nearest]
+#[cfg(feature = "graphics")]
+impl From<Rgb888> for OctColor {
+    fn from(color: Rgb888) -> OctColor {
+        OctColor::nearest(color.r(), color.g(), color.b())
+    }
+}
+
+/// The nearest color of the panel, see [OctColor::nearest]
+#[cfg(feature = "graphics")]
+impl From<Rgb565> for OctColor {
+    fn from(color: Rgb565) -> OctColor {
+        OctColor::from(Rgb888::from(color))
+    }
+}
+
+/// How the color looks on the panel, see [OctColor::panel_rgb]
+#[cfg(feature = "graphics")]
+impl From<OctColor> for Rgb888 {
+    fn from(color: OctColor) -> Rgb888 {
+        let (r, g, b) = color.panel_rgb();
+        Rgb888::new(r, g, b)
+    }
+}
+
+/// `On` is black and `Off` is white, like for [Color]
+#[cfg(feature = "graphics")]
+impl From<BinaryColor> for OctColor {
+    fn from(b: BinaryColor) -> OctColor {
+        Color::from(b).into()
+    }
+}
+
+/// Black and white of the 7 colors
+impl From<Color> for OctColor {
+    fn from(color: Color) -> OctColor {
+        match color {
+            Color::Black => OctColor::Black,
+            Color::White => OctColor::White,
+        }
+    }
+}
+
+/// How the color looks on the panel, see [OctColor::panel_rgb]
+#[cfg(feature = "graphics")]
+impl From<OctColor> for Rgb565 {
+    fn from(color: OctColor) -> Rgb565 {
+        Rgb565::from(Rgb888::from(color))
+    }
 }
 //TODO: Rename get_bit_value to bit() and get_byte_value to byte() ?
 
+/// How the pixels of a color are packed into the frame buffers
+///
+/// Implemented for the colors of the graphics displays, so buffers like
+/// [FixedDisplay](crate::display::FixedDisplay) and the drawing code can be generic over them.
+/// The pixels of a plane are packed from the most significant bit on, so slot 0 of a byte
+/// is the leftmost pixel.
+pub trait ColorType: Copy + PartialEq {
+    /// Bits of one pixel, summed over all planes
+    const BITS_PER_PIXEL: usize;
+
+    /// Number of separate planes in the buffer, e.g. 2 for the b/w and the chromatic
+    /// plane of tri-color displays
+    const PLANES: usize;
+
+    /// Every byte of a white buffer
+    const WHITE_BYTE: u8;
+
+    /// Color of a new buffer
+    const BACKGROUND: Self;
+
+    /// Pixels in one byte of a plane
+    const PIXELS_PER_BYTE: u32 = (8 * Self::PLANES / Self::BITS_PER_PIXEL) as u32;
+
+    /// Frame buffer length in bytes for a `width` x `height` area, see [buffer_size]
+    fn buffer_size(width: usize, height: usize) -> usize {
+        buffer_size::<Self>(width, height)
+    }
+
+    /// A byte of `plane` with every pixel in this color
+    fn fill_byte(self, plane: usize) -> u8;
+
+    /// Puts the color into pixel `slot` of a byte of `plane`
+    fn pack(self, plane: usize, slot: u32, byte: &mut u8);
+
+    /// Color of pixel `slot` of the bytes at the same position of every plane, `None` if
+    /// the bits aren't a color
+    fn unpack(bytes: &[u8], slot: u32) -> Option<Self>;
+
+    /// How the color looks on the panel, for previews and simulators
+    #[cfg(feature = "graphics")]
+    fn preview_rgb(self) -> Rgb888;
+
+    /// Color of the pixel (x, y) of an unrotated `width` x `height` buffer
+    ///
+    /// `None` if it's outside of the buffer or the bits aren't a color.
+    fn read_pixel(buffer: &[u8], width: u32, height: u32, x: u32, y: u32) -> Option<Self> {
+        if x >= width || y >= height {
+            return None;
+        }
+        let (index, slot) = slot_position::<Self>(width, x, y);
+        let plane = plane_size::<Self>(width, height);
+        let mut bytes = [0; 2];
+        for (p, byte) in bytes.iter_mut().enumerate().take(Self::PLANES) {
+            *byte = *buffer.get(p * plane + index)?;
+        }
+        Self::unpack(&bytes[..Self::PLANES], slot)
+    }
+
+    /// Puts the color at pixel (x, y) of an unrotated `width` x `height` buffer
+    ///
+    /// Pixels outside of the buffer are left out.
+    fn write_pixel(self, buffer: &mut [u8], width: u32, height: u32, x: u32, y: u32) {
+        if x >= width || y >= height {
+            return;
+        }
+        let (index, slot) = slot_position::<Self>(width, x, y);
+        let plane = plane_size::<Self>(width, height);
+        for p in 0..Self::PLANES {
+            if let Some(byte) = buffer.get_mut(p * plane + index) {
+                self.pack(p, slot, byte);
+            }
+        }
+    }
+}
+
+/// Frame buffer length in bytes of `C` for a `width` x `height` area,
+/// each row of each plane is padded to a full byte
+pub const fn buffer_size<C: ColorType>(width: usize, height: usize) -> usize {
+    let bits_per_plane = C::BITS_PER_PIXEL / C::PLANES;
+    C::PLANES * (width * bits_per_plane).div_ceil(8) * height
+}
+
+// Index in a plane and slot in that byte of the unrotated pixel (x, y)
+pub(crate) fn slot_position<C: ColorType>(width: u32, x: u32, y: u32) -> (usize, u32) {
+    layout::slot_position(width, bits_per_plane::<C>(), x, y)
+}
+
+// Bits of one pixel in every plane of `C`
+pub(crate) const fn bits_per_plane<C: ColorType>() -> u32 {
+    (C::BITS_PER_PIXEL / C::PLANES) as u32
+}
+
+// Length of one plane of a `width` x `height` buffer
+pub(crate) fn plane_size<C: ColorType>(width: u32, height: u32) -> usize {
+    buffer_size::<C>(width as usize, height as usize) / C::PLANES
+}
+
+// Puts the lowest `bits` of `value` into pixel `slot` of `byte`
+fn pack_bits(byte: &mut u8, bits: u32, slot: u32, value: u8) {
+    let shift = layout::shift(bits, slot);
+    let mask = ((1u16 << bits) - 1) as u8;
+    *byte = (*byte & !(mask << shift)) | (value & mask) << shift;
+}
+
+// The `bits` bits of pixel `slot` of `byte`
+fn unpack_bits(byte: u8, bits: u32, slot: u32) -> u8 {
+    (byte >> layout::shift(bits, slot)) & ((1u16 << bits) - 1) as u8
+}
+
+/// Black is a cleared bit
+#[cfg(feature = "graphics")]
+impl ColorType for BinaryColor {
+    const BITS_PER_PIXEL: usize = 1;
+    const PLANES: usize = 1;
+    const WHITE_BYTE: u8 = 0xff;
+    const BACKGROUND: Self = BinaryColor::Off;
+
+    fn fill_byte(self, _plane: usize) -> u8 {
+        Color::from(self).get_byte_value()
+    }
+
+    fn pack(self, _plane: usize, slot: u32, byte: &mut u8) {
+        pack_bits(byte, 1, slot, Color::from(self).get_bit_value());
+    }
+
+    fn unpack(bytes: &[u8], slot: u32) -> Option<Self> {
+        Color::unpack(bytes, slot).map(BinaryColor::from)
+    }
+
+    fn preview_rgb(self) -> Rgb888 {
+        Color::from(self).into()
+    }
+}
+
+/// Black is a cleared bit, the same buffers as `BinaryColor`
+impl ColorType for Color {
+    const BITS_PER_PIXEL: usize = 1;
+    const PLANES: usize = 1;
+    const WHITE_BYTE: u8 = 0xff;
+    const BACKGROUND: Self = Color::White;
+
+    fn fill_byte(self, _plane: usize) -> u8 {
+        self.get_byte_value()
+    }
+
+    fn pack(self, _plane: usize, slot: u32, byte: &mut u8) {
+        pack_bits(byte, 1, slot, self.get_bit_value());
+    }
+
+    fn unpack(bytes: &[u8], slot: u32) -> Option<Self> {
+        Some(Color::from(unpack_bits(bytes[0], 1, slot)))
+    }
+
+    #[cfg(feature = "graphics")]
+    fn preview_rgb(self) -> Rgb888 {
+        self.into()
+    }
+}
+
+/// A b/w plane followed by a chromatic plane, see [TriDisplay](crate::graphics::TriDisplay)
+impl ColorType for TriColor {
+    const BITS_PER_PIXEL: usize = 2;
+    const PLANES: usize = 2;
+    const WHITE_BYTE: u8 = 0xff;
+    const BACKGROUND: Self = TriColor::White;
+
+    fn fill_byte(self, plane: usize) -> u8 {
+        let (bw, chromatic) = self.bits();
+        if [bw, chromatic][plane] {
+            0xff
+        } else {
+            0x00
+        }
+    }
+
+    fn pack(self, plane: usize, slot: u32, byte: &mut u8) {
+        let (bw, chromatic) = self.bits();
+        pack_bits(byte, 1, slot, u8::from([bw, chromatic][plane]));
+    }
+
+    fn unpack(bytes: &[u8], slot: u32) -> Option<Self> {
+        let bit = |byte| unpack_bits(byte, 1, slot) == 1;
+        Some(TriColor::from_bits(bit(bytes[0]), bit(bytes[1])))
+    }
+
+    #[cfg(feature = "graphics")]
+    fn preview_rgb(self) -> Rgb888 {
+        self.into()
+    }
+}
+
+/// Two nibbles per byte, see [OctColor::colors_byte]
+impl ColorType for OctColor {
+    const BITS_PER_PIXEL: usize = 4;
+    const PLANES: usize = 1;
+    const WHITE_BYTE: u8 = 0x11;
+    const BACKGROUND: Self = OctColor::White;
+
+    fn fill_byte(self, _plane: usize) -> u8 {
+        OctColor::colors_byte(self, self)
+    }
+
+    fn pack(self, _plane: usize, slot: u32, byte: &mut u8) {
+        pack_bits(byte, 4, slot, self.get_nibble());
+    }
+
+    fn unpack(bytes: &[u8], slot: u32) -> Option<Self> {
+        OctColor::from_nibble(unpack_bits(bytes[0], 4, slot)).ok()
+    }
+
+    #[cfg(feature = "graphics")]
+    fn preview_rgb(self) -> Rgb888 {
+        self.into()
+    }
+}
+
+/// Four pixels per byte with the luma of each, see [GrayDisplay](crate::graphics::GrayDisplay)
+#[cfg(feature = "graphics")]
+impl ColorType for Gray2 {
+    const BITS_PER_PIXEL: usize = 2;
+    const PLANES: usize = 1;
+    const WHITE_BYTE: u8 = 0xff;
+    const BACKGROUND: Self = Gray2::WHITE;
+
+    fn fill_byte(self, _plane: usize) -> u8 {
+        self.luma() * 0x55
+    }
+
+    fn pack(self, _plane: usize, slot: u32, byte: &mut u8) {
+        pack_bits(byte, 2, slot, self.luma());
+    }
+
+    fn unpack(bytes: &[u8], slot: u32) -> Option<Self> {
+        Some(Gray2::new(unpack_bits(bytes[0], 2, slot)))
+    }
+
+    fn preview_rgb(self) -> Rgb888 {
+        self.into()
+    }
+}
+
+/// `On` is black and `Off` is white, like [Black] and [White]
+#[cfg(feature = "graphics")]
+impl From<BinaryColor> for Color {
+    fn from(b: BinaryColor) -> Color {
+        match b {
+            BinaryColor::On => Color::Black,
+            BinaryColor::Off => Color::White,
+        }
+    }
+}
+
+/// Black is `On` and white is `Off`, the inverse of `From<BinaryColor>`
+#[cfg(feature = "graphics")]
+impl From<Color> for BinaryColor {
+    fn from(color: Color) -> BinaryColor {
+        match color {
+            Color::Black => BinaryColor::On,
+            Color::White => BinaryColor::Off,
+        }
+    }
+}
+
+// Thresholds a gray color of embedded-graphics, see Color::from_luma
+#[cfg(feature = "graphics")]
+macro_rules! impl_color_from_gray {
+    ($($gray:ident),*) => {
+        $(
+            /// White for `GRAY_50` and lighter, black below, see [Color::from_luma]
+            ///
+            /// Goes directly to the color: `BinaryColor::from` of a gray color gives `On`
+            /// for light grays in embedded-graphics, which is black here.
+            impl From<$gray> for Color {
+                fn from(gray: $gray) -> Color {
+                    Color::from_luma(gray.luma(), $gray::WHITE.luma())
+                }
+            }
+
+            /// Black is the darkest and white the lightest level
+            impl From<Color> for $gray {
+                fn from(color: Color) -> $gray {
+                    match color {
+                        Color::Black => $gray::BLACK,
+                        Color::White => $gray::WHITE,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "graphics")]
+impl_color_from_gray!(Gray2, Gray4, Gray8);
+
 impl Color {
     /// Get the color encoding of the color for one bit
+    ///
+    /// As stored in the buffers, see [Polarity::ClearedIsColor].
     pub fn get_bit_value(self) -> u8 {
-        match self {
-            Color::White => 1u8,
-            Color::Black => 0u8,
-        }
+        self.to_controller_bit(Polarity::ClearedIsColor)
     }
 
     /// Gets a full byte of black or white pixels
+    ///
+    /// As stored in the buffers, see [Polarity::ClearedIsColor].
     pub fn get_byte_value(self) -> u8 {
-        match self {
-            Color::White => 0xff,
-            Color::Black => 0x00,
+        self.to_controller_byte(Polarity::ClearedIsColor)
+    }
+
+    /// The bit of the color for a controller with `polarity`
+    ///
+    /// See [POLARITY](crate::traits::WaveshareDisplay::POLARITY) of the drivers.
+    pub const fn to_controller_bit(self, polarity: Polarity) -> u8 {
+        match (self, polarity) {
+            (Color::White, Polarity::ClearedIsColor) | (Color::Black, Polarity::SetIsColor) => 1,
+            (Color::Black, Polarity::ClearedIsColor) | (Color::White, Polarity::SetIsColor) => 0,
         }
     }
 
+    /// A full byte of the color for a controller with `polarity`
+    pub const fn to_controller_byte(self, polarity: Polarity) -> u8 {
+        0u8.wrapping_sub(self.to_controller_bit(polarity))
+    }
+
     /// Parses from u8 to Color
     fn from_u8(val: u8) -> Self {
         match val {
@@ -152,6 +702,19 @@ impl Color {
         }
     }
 
+    /// Thresholds a gray level from 0 (black) to `max` (white)
+    ///
+    /// Levels above the middle are white, so the upper half of the levels is white and the
+    /// lower half black, e.g. 2 and 3 of `Gray2` and 128 to 255 of `Gray8`. `max` of 0
+    /// gives black.
+    pub const fn from_luma(luma: u8, max: u8) -> Color {
+        if 2 * luma as u16 > max as u16 {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+
     /// Returns the inverse of the given color.
     ///
     /// Black returns White and White returns Black
@@ -161,6 +724,33 @@ impl Color {
             Color::Black => Color::White,
         }
     }
+
+    /// How the color looks on the panel, measured on a 2.9" b/w display
+    ///
+    /// The black of the panel is a dark grey, white is the reference.
+    pub const fn panel_rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Black => PANEL_BLACK,
+            Color::White => PANEL_WHITE,
+        }
+    }
+}
+
+/// How the color looks on the panel, see [Color::panel_rgb]
+#[cfg(feature = "graphics")]
+impl From<Color> for Rgb888 {
+    fn from(color: Color) -> Rgb888 {
+        let (r, g, b) = color.panel_rgb();
+        Rgb888::new(r, g, b)
+    }
+}
+
+/// How the color looks on the panel, see [Color::panel_rgb]
+#[cfg(feature = "graphics")]
+impl From<Color> for Rgb565 {
+    fn from(color: Color) -> Rgb565 {
+        Rgb565::from(Rgb888::from(color))
+    }
 }
 
 impl From<u8> for Color {
@@ -185,17 +775,314 @@ impl TriColor {
             TriColor::Black | TriColor::Chromatic => 0x00,
         }
     }
+
+    /// How the color looks on a panel with red as chromatic color
+    ///
+    /// Black and white are the same as for the b/w panels, see [Color::panel_rgb].
+    pub const fn panel_rgb(self) -> (u8, u8, u8) {
+        match self {
+            TriColor::Black => PANEL_BLACK,
+            TriColor::White => PANEL_WHITE,
+            TriColor::Chromatic => (173, 36, 39),
+        }
+    }
+
+    /// How the color looks on a panel with yellow as chromatic color
+    pub const fn yellow_panel_rgb(self) -> (u8, u8, u8) {
+        match self {
+            TriColor::Chromatic => (222, 190, 37),
+            color => color.panel_rgb(),
+        }
+    }
+}
+
+impl TriColor {
+    /// The color of a pixel from its bit in the b/w and in the chromatic plane
+    ///
+    /// A cleared chromatic bit wins over the b/w bit, like on the panel.
+    pub const fn from_bits(bw: bool, chromatic: bool) -> TriColor {
+        match (bw, chromatic) {
+            (_, false) => TriColor::Chromatic,
+            (true, true) => TriColor::White,
+            (false, true) => TriColor::Black,
+        }
+    }
+
+    /// The bits of the color in the b/w and in the chromatic plane
+    pub const fn bits(self) -> (bool, bool) {
+        match self {
+            TriColor::Black => (false, true),
+            TriColor::White => (true, true),
+            TriColor::Chromatic => (true, false),
+        }
+    }
+
+    /// Splits a byte of the b/w and the matching byte of the chromatic plane into their
+    /// 8 pixels, the leftmost pixel first
+    pub fn split_byte(bw: u8, chromatic: u8) -> [TriColor; 8] {
+        let mut colors = [TriColor::White; 8];
+        for (i, color) in colors.iter_mut().enumerate() {
+            let bit = 0x80 >> i;
+            *color = TriColor::from_bits(bw & bit != 0, chromatic & bit != 0);
+        }
+        colors
+    }
+
+    /// Packs 8 pixels, the leftmost pixel first, into a byte of the b/w and a byte of the
+    /// chromatic plane, the inverse of [split_byte](TriColor::split_byte)
+    pub fn merge(colors: [TriColor; 8]) -> (u8, u8) {
+        colors
+            .iter()
+            .enumerate()
+            .fold((0, 0), |(bw, chromatic), (i, color)| {
+                let bit = 0x80 >> i;
+                let (b, c) = color.bits();
+                (
+                    bw | if b { bit } else { 0 },
+                    chromatic | if c { bit } else { 0 },
+                )
+            })
+    }
+}
+
+/// Splits a tri-color buffer of a `width` x `height` frame into the b/w and the
+/// chromatic plane
+///
+/// The buffer has the layout of the [tri-color displays](crate::graphics::TriDisplay),
+/// the b/w plane followed by the chromatic plane.
+pub fn split_planes(
+    buffer: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<(&[u8], &[u8]), FrameDataError> {
+    let len = plane_len(buffer.len(), width, height)?;
+    Ok(buffer.split_at(len))
+}
+
+/// Like [split_planes], to change the planes in place
+pub fn split_planes_mut(
+    buffer: &mut [u8],
+    width: u32,
+    height: u32,
+) -> Result<(&mut [u8], &mut [u8]), FrameDataError> {
+    let len = plane_len(buffer.len(), width, height)?;
+    Ok(buffer.split_at_mut(len))
+}
+
+/// Copies separate b/w and chromatic planes into one tri-color `buffer`, the inverse of
+/// [split_planes]
+///
+/// Both planes need the same length and `buffer` twice of it.
+pub fn merge_planes(bw: &[u8], chromatic: &[u8], buffer: &mut [u8]) -> Result<(), FrameDataError> {
+    if chromatic.len() != bw.len() {
+        return Err(FrameDataError::BufferLength {
+            expected: bw.len(),
+            actual: chromatic.len(),
+        });
+    }
+    if buffer.len() != 2 * bw.len() {
+        return Err(FrameDataError::BufferLength {
+            expected: 2 * bw.len(),
+            actual: buffer.len(),
+        });
+    }
+    let (first, second) = buffer.split_at_mut(bw.len());
+    first.copy_from_slice(bw);
+    second.copy_from_slice(chromatic);
+    Ok(())
+}
+
+/// The two planes of a tri-color frame, as all tri-color drivers take them
+///
+/// Both planes have the same length and the layout of a b/w buffer, the
+/// `achromatic_buffer_len()` and `chromatic_buffer_len()` of the display modules:
+///
+/// - `black`: a cleared bit is black, a set bit is white
+/// - `chromatic`: a cleared bit is red or yellow and covers the pixel of the b/w plane
+///
+/// The drivers convert them for their controllers, so the same planes work on every
+/// tri-color display of the same size. See
+/// [update_tri_color_frame](crate::traits::WaveshareThreeColorDisplay::update_tri_color_frame).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriColorFrame<'a> {
+    /// The b/w plane
+    pub black: &'a [u8],
+    /// The chromatic plane
+    pub chromatic: &'a [u8],
+}
+
+impl<'a> TriColorFrame<'a> {
+    /// Pairs separate planes, fails if they don't have the same length
+    pub fn new(black: &'a [u8], chromatic: &'a [u8]) -> Result<Self, FrameDataError> {
+        if chromatic.len() != black.len() {
+            return Err(FrameDataError::BufferLength {
+                expected: black.len(),
+                actual: chromatic.len(),
+            });
+        }
+        Ok(TriColorFrame { black, chromatic })
+    }
+
+    /// The planes of the buffer of a `width` x `height` tri-color display, see [split_planes]
+    pub fn from_buffer(buffer: &'a [u8], width: u32, height: u32) -> Result<Self, FrameDataError> {
+        let (black, chromatic) = split_planes(buffer, width, height)?;
+        Ok(TriColorFrame { black, chromatic })
+    }
+}
+
+fn plane_len(len: usize, width: u32, height: u32) -> Result<usize, FrameDataError> {
+    let expected = buffer_size::<TriColor>(width as usize, height as usize);
+    if len != expected {
+        return Err(FrameDataError::BufferLength {
+            expected,
+            actual: len,
+        });
+    }
+    Ok(expected / 2)
+}
+
+/// Which bit of a plane is the color of the plane
+///
+/// The buffers of this crate are always [ClearedIsColor](Polarity::ClearedIsColor), the
+/// drivers convert to the [POLARITY](crate::traits::WaveshareDisplay::POLARITY) of their
+/// controller.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Polarity {
+    /// A cleared bit is the color (black or chromatic), a set bit is white
+    ClearedIsColor,
+    /// A set bit is the color (black or chromatic), a cleared bit is white
+    SetIsColor,
+}
+
+/// How a controller expects the b/w and the chromatic plane of a tri-color frame
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PlanePolarity {
+    /// Polarity of the b/w plane
+    pub bw: Polarity,
+    /// Polarity of the chromatic plane
+    pub chromatic: Polarity,
+}
+
+impl PlanePolarity {
+    /// The layout of the tri-color buffers, which every tri-color driver of this crate
+    /// (1in54b, 1in54c, 2in13bc, 2in7b and 2in9bc) sends as it is
+    pub const BUFFER: PlanePolarity = PlanePolarity {
+        bw: Polarity::ClearedIsColor,
+        chromatic: Polarity::ClearedIsColor,
+    };
+
+    /// SSD1675 and SSD1680 based tri-color displays, e.g. the 2in13b V4 and 2in9b V4,
+    /// where a set bit of the red RAM is red
+    pub const SSD1680: PlanePolarity = PlanePolarity {
+        bw: Polarity::ClearedIsColor,
+        chromatic: Polarity::SetIsColor,
+    };
+
+    /// Converts planes with the polarity of the [BUFFER](PlanePolarity::BUFFER) to this
+    /// one, or back as converting twice restores the planes
+    pub fn convert(self, bw: &mut [u8], chromatic: &mut [u8]) {
+        if self.bw != PlanePolarity::BUFFER.bw {
+            bw.iter_mut().for_each(|byte| *byte = !*byte);
+        }
+        if self.chromatic != PlanePolarity::BUFFER.chromatic {
+            chromatic.iter_mut().for_each(|byte| *byte = !*byte);
+        }
+    }
+}
+
+/// How the color looks on a panel with red as chromatic color, see [TriColor::panel_rgb]
+#[cfg(feature = "graphics")]
+impl From<TriColor> for Rgb888 {
+    fn from(color: TriColor) -> Rgb888 {
+        let (r, g, b) = color.panel_rgb();
+        Rgb888::new(r, g, b)
+    }
+}
+
+/// How the color looks on a panel with red as chromatic color, see [TriColor::panel_rgb]
+#[cfg(feature = "graphics")]
+impl From<TriColor> for Rgb565 {
+    fn from(color: TriColor) -> Rgb565 {
+        Rgb565::from(Rgb888::from(color))
+    }
+}
+
+/// Maps `On` to black and `Off` to white like [Color], so b/w assets can be reused
+#[cfg(feature = "graphics")]
+impl From<BinaryColor> for TriColor {
+    fn from(b: BinaryColor) -> TriColor {
+        match b {
+            BinaryColor::On => TriColor::Black,
+            BinaryColor::Off => TriColor::White,
+        }
+    }
+}
+
+impl From<Color> for TriColor {
+    fn from(color: Color) -> TriColor {
+        match color {
+            Color::Black => TriColor::Black,
+            Color::White => TriColor::White,
+        }
+    }
 }
 
 #[cfg(feature = "graphics")]
 impl PixelColor for TriColor {
-    type Raw = ();
+    type Raw = RawU2;
+}
+
+/// Raw data is 0 for black, 1 for white and 2 (or 3) for chromatic
+#[cfg(feature = "graphics")]
+impl From<RawU2> for TriColor {
+    fn from(raw: RawU2) -> TriColor {
+        match raw.into_inner() {
+            0 => TriColor::Black,
+            1 => TriColor::White,
+            _ => TriColor::Chromatic,
+        }
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl From<TriColor> for RawU2 {
+    fn from(color: TriColor) -> RawU2 {
+        RawU2::new(match color {
+            TriColor::Black => 0,
+            TriColor::White => 1,
+            TriColor::Chromatic => 2,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn tri_color_frames() {
+        let buffer = [0x0f, 0x1f, 0xf0, 0xf1];
+        let frame = TriColorFrame::from_buffer(&buffer, 8, 2).unwrap();
+        assert_eq!(frame.black, [0x0f, 0x1f]);
+        assert_eq!(frame.chromatic, [0xf0, 0xf1]);
+        assert_eq!(TriColorFrame::new(&buffer[..2], &buffer[2..]), Ok(frame));
+
+        assert_eq!(
+            TriColorFrame::new(&buffer[..2], &buffer[1..]),
+            Err(FrameDataError::BufferLength {
+                expected: 2,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            TriColorFrame::from_buffer(&buffer, 16, 2),
+            Err(FrameDataError::BufferLength {
+                expected: 8,
+                actual: 4
+            })
+        );
+    }
+
     #[test]
     fn from_u8() {
         assert_eq!(Color::Black, Color::from(0u8));
@@ -205,13 +1092,23 @@ mod tests {
     // test all values aside from 0 and 1 which all should panic
     #[test]
     fn from_u8_panic() {
-        for val in 2..=u8::max_value() {
+        for val in 2..=u8::MAX {
             extern crate std;
             let result = std::panic::catch_unwind(|| Color::from(val));
             assert!(result.is_err());
         }
     }
 
+    #[test]
+    fn from_color() {
+        assert_eq!(TriColor::from(Color::Black), TriColor::Black);
+        assert_eq!(TriColor::from(Color::White), TriColor::White);
+        #[cfg(feature = "graphics")]
+        for &color in [Color::Black, Color::White].iter() {
+            assert_eq!(Color::from(BinaryColor::from(color)), color);
+        }
+    }
+
     #[test]
     fn u8_conversion_black() {
         assert_eq!(Color::from(Color::Black.get_bit_value()), Color::Black);
@@ -224,6 +1121,15 @@ mod tests {
         assert_eq!(Color::from(1u8).get_bit_value(), 1u8);
     }
 
+    #[test]
+    fn buffer_sizes() {
+        assert_eq!(Color::buffer_size(200, 200), 5000);
+        assert_eq!(Color::buffer_size(122, 250), 16 * 250);
+        assert_eq!(TriColor::buffer_size(104, 212), 2 * 13 * 212);
+        assert_eq!(OctColor::buffer_size(600, 448), 600 * 448 / 2);
+        assert_eq!(OctColor::buffer_size(3, 2), 4);
+    }
+
     #[test]
     fn test_oct() {
         let left = OctColor::Red;
@@ -233,4 +1139,444 @@ mod tests {
             Ok((left, right))
         );
     }
+
+    #[test]
+    fn oct_byte_round_trip() {
+        // all pairs of the 8 encodings, HiZ included
+        for high in 0..8 {
+            for low in 0..8 {
+                let (left, right) = (
+                    OctColor::try_from(high).unwrap(),
+                    OctColor::try_from(low).unwrap(),
+                );
+                let byte = OctColor::colors_byte(left, right);
+                assert_eq!(byte, high << 4 | low);
+                assert_eq!(OctColor::split_byte(byte), Ok((left, right)));
+                assert_eq!(OctColor::high_pixel(byte), Ok(left));
+                assert_eq!(OctColor::low_pixel(byte), Ok(right));
+                assert_eq!(OctColor::try_from(left.get_nibble()), Ok(left));
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_nibbles() {
+        for value in 8..=255u8 {
+            assert_eq!(
+                OctColor::try_from(value),
+                Err(OutOfColorRangeParseError(value))
+            );
+        }
+        // from_nibble only looks at the lower nibble
+        assert_eq!(OctColor::from_nibble(0x31), Ok(OctColor::White));
+        assert!(OctColor::split_byte(0x8f).is_err());
+        assert_eq!(OctColor::high_pixel(0x1f), Ok(OctColor::White));
+        assert!(OctColor::low_pixel(0x1f).is_err());
+        assert!(OctColor::high_pixel(0xf1).is_err());
+    }
+
+    #[test]
+    fn nearest_anchors() {
+        assert_eq!(OctColor::nearest(0, 0, 0), OctColor::Black);
+        assert_eq!(OctColor::nearest(255, 255, 255), OctColor::White);
+        assert_eq!(OctColor::nearest(255, 0, 0), OctColor::Red);
+        assert_eq!(OctColor::nearest(0, 255, 0), OctColor::Green);
+        assert_eq!(OctColor::nearest(0, 0, 255), OctColor::Blue);
+        assert_eq!(OctColor::nearest(255, 255, 0), OctColor::Yellow);
+        // the orange of the panel is quite dark, so bright orange ends up yellow
+        assert_eq!(OctColor::nearest(255, 128, 0), OctColor::Yellow);
+        assert_eq!(OctColor::nearest(180, 100, 70), OctColor::Orange);
+        // mid grey is closest to the dark orange, light grey is white
+        assert_eq!(OctColor::nearest(128, 128, 128), OctColor::Orange);
+        assert_eq!(OctColor::nearest(200, 200, 200), OctColor::White);
+        assert_eq!(OctColor::nearest(40, 40, 40), OctColor::Black);
+        for nibble in 0..7 {
+            let color = OctColor::from_nibble(nibble).unwrap();
+            let (r, g, b) = color.panel_rgb();
+            assert_eq!(OctColor::nearest(r, g, b), color);
+        }
+    }
+
+    #[test]
+    fn lab_values() {
+        // white, black and the primaries are within 1 of the floating point values
+        let cases = [
+            ((255, 255, 255), [100.0, 0.0, 0.0]),
+            ((0, 0, 0), [0.0, 0.0, 0.0]),
+            ((255, 0, 0), [53.24, 80.09, 67.20]),
+            ((0, 255, 0), [87.73, -86.18, 83.18]),
+            ((0, 0, 255), [32.30, 79.19, -107.86]),
+            ((128, 128, 128), [53.59, 0.0, 0.0]),
+        ];
+        for &((r, g, b), expected) in cases.iter() {
+            let lab = lab(r, g, b);
+            for (&value, &expected) in lab.iter().zip(expected.iter()) {
+                let value = value as f32 / 1024.0;
+                assert!((value - expected).abs() < 1.0, "{:?}: {}", (r, g, b), value);
+            }
+        }
+    }
+
+    #[test]
+    fn perceptual_differs_from_rgb() {
+        // dark navy is closer to the panel's black in rgb, but looks blue
+        assert_eq!(OctColor::nearest(20, 30, 80), OctColor::Black);
+        assert_eq!(OctColor::nearest_perceptual(20, 30, 80), OctColor::Blue);
+        // brown ends up red in rgb and orange in Lab
+        assert_eq!(OctColor::nearest(141, 85, 36), OctColor::Red);
+        assert_eq!(OctColor::nearest_perceptual(141, 85, 36), OctColor::Orange);
+        // mid grey is orange in rgb, closer to the greyish green of the panel in Lab
+        assert_eq!(OctColor::nearest(128, 128, 128), OctColor::Orange);
+        assert_eq!(OctColor::nearest_perceptual(128, 128, 128), OctColor::Green);
+        // clear cases agree
+        for &(r, g, b) in [
+            (224, 172, 105),
+            (198, 134, 66),
+            (0, 100, 0),
+            (255, 192, 203),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                OctColor::nearest(r, g, b),
+                OctColor::nearest_perceptual(r, g, b)
+            );
+        }
+        for nibble in 0..7 {
+            let color = OctColor::from_nibble(nibble).unwrap();
+            let (r, g, b) = color.panel_rgb();
+            assert_eq!(OctColor::nearest_perceptual(r, g, b), color);
+        }
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn rgb_conversion() {
+        assert_eq!(OctColor::from(Rgb888::RED), OctColor::Red);
+        assert_eq!(OctColor::from(Rgb888::new(128, 128, 128)), OctColor::Orange);
+        assert_eq!(OctColor::from(Rgb565::BLUE), OctColor::Blue);
+        assert_eq!(OctColor::from(Rgb565::WHITE), OctColor::White);
+        for nibble in 0..7 {
+            let color = OctColor::from_nibble(nibble).unwrap();
+            assert_eq!(OctColor::from(Rgb888::from(color)), color);
+            assert_eq!(OctColor::from(Rgb565::from(color)), color);
+        }
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn palettes_match_panel_colors() {
+        use crate::bitmap::{ColorMatcher, Nearest};
+        use crate::dither::{BW_PALETTE, OCT_PALETTE, TRI_RED_PALETTE, TRI_YELLOW_PALETTE};
+
+        for &(color, rgb) in OCT_PALETTE.iter() {
+            assert_eq!(Rgb888::from(color), rgb);
+            assert_eq!(ColorMatcher::<OctColor>::matching(&Nearest, rgb), color);
+        }
+        for &(color, rgb) in TRI_RED_PALETTE.iter() {
+            assert_eq!(Rgb888::from(color), rgb);
+            assert_eq!(ColorMatcher::<TriColor>::matching(&Nearest, rgb), color);
+        }
+        for &(color, rgb) in BW_PALETTE.iter() {
+            assert_eq!(Rgb888::from(color), rgb);
+            assert_eq!(ColorMatcher::<Color>::matching(&Nearest, rgb), color);
+        }
+        assert_eq!(TRI_YELLOW_PALETTE[2].1, Rgb888::new(222, 190, 37));
+        assert_eq!(Rgb565::from(Color::White), Rgb565::WHITE);
+        assert_eq!(Rgb888::from(TriColor::Black), Rgb888::from(Color::Black));
+    }
+
+    #[test]
+    fn controller_polarity() {
+        let cases = [
+            (Color::White, Polarity::ClearedIsColor, 1, 0xff),
+            (Color::Black, Polarity::ClearedIsColor, 0, 0x00),
+            (Color::White, Polarity::SetIsColor, 0, 0x00),
+            (Color::Black, Polarity::SetIsColor, 1, 0xff),
+        ];
+        for &(color, polarity, bit, byte) in cases.iter() {
+            assert_eq!(color.to_controller_bit(polarity), bit);
+            assert_eq!(color.to_controller_byte(polarity), byte);
+        }
+        // the buffers
+        assert_eq!(Color::White.get_bit_value(), 1);
+        assert_eq!(Color::Black.get_byte_value(), 0x00);
+    }
+
+    #[test]
+    fn tri_color_bits() {
+        for &color in [TriColor::Black, TriColor::White, TriColor::Chromatic].iter() {
+            let (bw, chromatic) = color.bits();
+            assert_eq!(TriColor::from_bits(bw, chromatic), color);
+        }
+        // a chromatic pixel stays chromatic whatever its b/w bit is
+        assert_eq!(TriColor::from_bits(false, false), TriColor::Chromatic);
+    }
+
+    #[test]
+    fn tri_color_split_and_merge_bytes() {
+        use TriColor::{Black as B, Chromatic as C, White as W};
+        let colors = [B, W, C, C, W, B, B, W];
+        assert_eq!(TriColor::merge(colors), (0b0111_1001, 0b1100_1111));
+        assert_eq!(TriColor::split_byte(0b0111_1001, 0b1100_1111), colors);
+
+        assert_eq!(TriColor::merge([W; 8]), (0xff, 0xff));
+        assert_eq!(TriColor::merge([B; 8]), (0x00, 0xff));
+        assert_eq!(TriColor::merge([C; 8]), (0xff, 0x00));
+        assert_eq!(TriColor::split_byte(0x00, 0x00), [C; 8]);
+
+        // every combination of the 16 bits survives a round trip through the colors,
+        // up to the b/w bits of chromatic pixels which are always set
+        for bw in 0..=255u8 {
+            for chromatic in [0x00, 0x5a, 0xc3, 0xff] {
+                let (b, c) = TriColor::merge(TriColor::split_byte(bw, chromatic));
+                assert_eq!((b, c), (bw | !chromatic, chromatic));
+            }
+        }
+    }
+
+    #[test]
+    fn tri_color_planes() {
+        let mut buffer = [0xff, 0x0f, 0x00, 0xf0, 0xaa, 0x55];
+        let (bw, chromatic) = split_planes(&buffer, 8, 3).unwrap();
+        assert_eq!((bw, chromatic), (&buffer[..3], &buffer[3..]));
+        assert_eq!(
+            split_planes(&buffer, 16, 3),
+            Err(FrameDataError::BufferLength {
+                expected: 12,
+                actual: 6
+            })
+        );
+
+        // a row of 9 pixels takes 2 bytes in each plane
+        let mut rows = [0xff; 8];
+        let (bw, chromatic) = split_planes_mut(&mut rows, 9, 2).unwrap();
+        bw[2] = 0x00;
+        chromatic[1] = 0x7f;
+        assert_eq!(rows, [0xff, 0xff, 0x00, 0xff, 0xff, 0x7f, 0xff, 0xff]);
+
+        let mut merged = [0; 6];
+        merge_planes(&[1, 2, 3], &[4, 5, 6], &mut merged).unwrap();
+        assert_eq!(merged, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            merge_planes(&[1, 2, 3], &[4, 5], &mut merged),
+            Err(FrameDataError::BufferLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            merge_planes(&[1, 2], &[4, 5], &mut merged),
+            Err(FrameDataError::BufferLength {
+                expected: 4,
+                actual: 6
+            })
+        );
+
+        let (bw, chromatic) = split_planes_mut(&mut buffer, 8, 3).unwrap();
+        PlanePolarity::BUFFER.convert(bw, chromatic);
+        assert_eq!(buffer, [0xff, 0x0f, 0x00, 0xf0, 0xaa, 0x55]);
+        let (bw, chromatic) = split_planes_mut(&mut buffer, 8, 3).unwrap();
+        PlanePolarity::SSD1680.convert(bw, chromatic);
+        assert_eq!(buffer, [0xff, 0x0f, 0x00, 0x0f, 0x55, 0xaa]);
+        let (bw, chromatic) = split_planes_mut(&mut buffer, 8, 3).unwrap();
+        PlanePolarity::SSD1680.convert(bw, chromatic);
+        assert_eq!(buffer, [0xff, 0x0f, 0x00, 0xf0, 0xaa, 0x55]);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn tri_color_planes_match_display() {
+        use crate::graphics::{TriDisplay, VarTriDisplay};
+        use embedded_graphics_core::prelude::*;
+        use TriColor::{Black as B, Chromatic as C, White as W};
+
+        let colors = [C, B, W, B, C, C, W, B, W, C];
+        let mut buffer = [0; 8];
+        let mut display = VarTriDisplay::new(10, 2, &mut buffer).unwrap();
+        display.clear_buffer(W);
+        for (x, &color) in colors.iter().enumerate() {
+            Pixel(Point::new(x as i32, 1), color)
+                .draw(&mut display)
+                .unwrap();
+        }
+
+        let (bw, chromatic) = split_planes(display.buffer(), 10, 2).unwrap();
+        assert_eq!(&TriColor::split_byte(bw[2], chromatic[2]), &colors[..8]);
+        assert_eq!(TriColor::split_byte(bw[3], chromatic[3])[..2], colors[8..]);
+        let mut packed = [W; 8];
+        packed.copy_from_slice(&colors[..8]);
+        assert_eq!(TriColor::merge(packed), (bw[2], chromatic[2]));
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn raw_conversion() {
+        for nibble in 0..8 {
+            let color = OctColor::from_nibble(nibble).unwrap();
+            assert_eq!(OctColor::from(RawU4::from(color)), color);
+            assert_eq!(RawU4::from(color).into_inner(), nibble);
+        }
+        assert_eq!(OctColor::from(RawU4::new(0x0f)), OctColor::White);
+
+        for &color in [TriColor::Black, TriColor::White, TriColor::Chromatic].iter() {
+            assert_eq!(TriColor::from(RawU2::from(color)), color);
+        }
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn color_type_buffer_size() {
+        assert_eq!(
+            buffer_size::<BinaryColor>(122, 250),
+            buffer_size::<Color>(122, 250)
+        );
+        assert_eq!(buffer_size::<Gray2>(5, 2), crate::gray::buffer_size(5, 2));
+        assert_eq!(
+            OctColor::WHITE_BYTE,
+            OctColor::colors_byte(OctColor::White, OctColor::White)
+        );
+    }
+
+    // Writes a pattern of `colors` into a 5 x 3 buffer and reads it back
+    #[cfg(feature = "graphics")]
+    fn pack_round_trip<C: ColorType + core::fmt::Debug>(colors: &[C]) {
+        extern crate std;
+        let (width, height) = (5, 3);
+        let mut buffer = std::vec![C::WHITE_BYTE; buffer_size::<C>(5, 3)];
+        for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+            assert_eq!(
+                C::read_pixel(&buffer, width, height, x, y),
+                Some(C::BACKGROUND)
+            );
+        }
+        let pattern = |x: u32, y: u32| colors[(x + 2 * y) as usize % colors.len()];
+        for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+            pattern(x, y).write_pixel(&mut buffer, width, height, x, y);
+        }
+        for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+            assert_eq!(
+                C::read_pixel(&buffer, width, height, x, y),
+                Some(pattern(x, y))
+            );
+        }
+        assert_eq!(C::read_pixel(&buffer, width, height, width, 0), None);
+        assert_eq!(C::read_pixel(&buffer, width, height, 0, height), None);
+
+        // a byte filled with a color has the color in every slot of its plane
+        for &color in colors {
+            for plane in 0..C::PLANES {
+                let mut byte = !color.fill_byte(plane);
+                for slot in 0..C::PIXELS_PER_BYTE {
+                    color.pack(plane, slot, &mut byte);
+                }
+                assert_eq!(byte, color.fill_byte(plane), "{:?}", color);
+            }
+        }
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn color_type_packing() {
+        pack_round_trip(&[BinaryColor::On, BinaryColor::Off]);
+        pack_round_trip(&[Color::Black, Color::White]);
+        pack_round_trip(&[TriColor::Black, TriColor::White, TriColor::Chromatic]);
+        pack_round_trip(&PANEL_COLORS);
+        pack_round_trip(&[Gray2::new(0), Gray2::new(1), Gray2::new(2), Gray2::new(3)]);
+
+        assert_eq!(BinaryColor::PIXELS_PER_BYTE, 8);
+        assert_eq!(TriColor::PIXELS_PER_BYTE, 8);
+        assert_eq!(OctColor::PIXELS_PER_BYTE, 2);
+        assert_eq!(Gray2::PIXELS_PER_BYTE, 4);
+
+        // the slots of a byte run from the most significant bits on
+        let mut byte = 0xff;
+        BinaryColor::On.pack(0, 1, &mut byte);
+        assert_eq!(byte, 0b1011_1111);
+        let mut byte = 0x11;
+        OctColor::Red.pack(0, 1, &mut byte);
+        assert_eq!(byte, OctColor::colors_byte(OctColor::White, OctColor::Red));
+        let mut byte = 0x00;
+        Gray2::new(2).pack(0, 3, &mut byte);
+        assert_eq!(byte, 0b0000_0010);
+        assert_eq!(OctColor::unpack(&[0x1f], 1), None);
+
+        // chromatic sets the b/w bit
+        let mut buffer = [0xff, 0xff];
+        TriColor::Chromatic.write_pixel(&mut buffer, 8, 1, 7, 0);
+        assert_eq!(buffer, [0xff, 0xfe]);
+        TriColor::Black.write_pixel(&mut buffer, 8, 1, 7, 0);
+        assert_eq!(buffer, [0xfe, 0xff]);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn color_type_preview() {
+        use crate::dither::{BW_PALETTE, OCT_PALETTE, TRI_RED_PALETTE};
+        for &(color, rgb) in BW_PALETTE.iter() {
+            assert_eq!(color.preview_rgb(), rgb);
+            assert_eq!(BinaryColor::from(color).preview_rgb(), rgb);
+        }
+        for &(color, rgb) in TRI_RED_PALETTE.iter() {
+            assert_eq!(color.preview_rgb(), rgb);
+        }
+        for &(color, rgb) in OCT_PALETTE.iter() {
+            assert_eq!(color.preview_rgb(), rgb);
+        }
+        assert_eq!(Gray2::new(3).preview_rgb(), Rgb888::WHITE);
+        assert_eq!(Gray2::new(0).preview_rgb(), Rgb888::BLACK);
+    }
+
+    #[test]
+    fn luma_threshold() {
+        assert_eq!(Color::from_luma(1, 3), Color::Black);
+        assert_eq!(Color::from_luma(2, 3), Color::White);
+        assert_eq!(Color::from_luma(127, 255), Color::Black);
+        assert_eq!(Color::from_luma(128, 255), Color::White);
+        assert_eq!(Color::from_luma(255, 255), Color::White);
+        assert_eq!(Color::from_luma(0, 0), Color::Black);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn gray_conversions() {
+        let colors: [Color; 4] = [0, 1, 2, 3].map(|l| Gray2::new(l).into());
+        assert_eq!(
+            colors,
+            [Color::Black, Color::Black, Color::White, Color::White]
+        );
+        assert_eq!(Color::from(Gray4::new(7)), Color::Black);
+        assert_eq!(Color::from(Gray4::new(8)), Color::White);
+        assert_eq!(Color::from(Gray8::new(127)), Color::Black);
+        assert_eq!(Color::from(Gray8::new(128)), Color::White);
+        // the same threshold as embedded-graphics, but light is white instead of `On`
+        for l in 0..=255 {
+            let binary = BinaryColor::from(Gray8::new(l)).invert();
+            assert_eq!(Color::from(Gray8::new(l)), Color::from(binary));
+        }
+
+        for &color in [Color::Black, Color::White].iter() {
+            assert_eq!(Color::from(Gray2::from(color)), color);
+            assert_eq!(Color::from(Gray4::from(color)), color);
+            assert_eq!(Color::from(Gray8::from(color)), color);
+        }
+        assert_eq!(Gray2::from(Color::White), Gray2::WHITE);
+        assert_eq!(Gray8::from(Color::Black), Gray8::BLACK);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn oct_color_from_binary_color() {
+        assert_eq!(OctColor::from(BinaryColor::On), OctColor::Black);
+        assert_eq!(OctColor::from(BinaryColor::Off), OctColor::White);
+        assert_eq!(OctColor::from(Color::Black), OctColor::Black);
+        assert_eq!(OctColor::from(Color::White), OctColor::White);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn tri_color_from_binary_color() {
+        assert_eq!(TriColor::from(BinaryColor::On), TriColor::Black);
+        assert_eq!(TriColor::from(BinaryColor::Off), TriColor::White);
+    }
 }
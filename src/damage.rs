@@ -0,0 +1,475 @@
+//! Tracking which part of a b/w buffer was drawn since the last update
+//!
+//! [DamageTracked] wraps a b/w display buffer and grows a rectangle around everything
+//! drawn into it. [flush_partial](DamageTracked::flush_partial) then only sends that
+//! window with a partial update. Unlike
+//! [DoubleBufferedDisplay](crate::double_buffer::DoubleBufferedDisplay) it doesn't need a
+//! second buffer, but pixels drawn with the color they already had count as changed too.
+//!
+//! Buffers which aren't wrapped don't pay anything for the tracking.
+//!
+//! Example:
+//! ```rust
+//! # use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, primitives::*};
+//! # use epd_waveshare::{damage::DamageTracked, epd4in2::Display4in2, prelude::*};
+//! let mut display = DamageTracked::new(Display4in2::default());
+//! // the frame is already on the screen
+//! display.reset_damage();
+//!
+//! display.set_rotation(DisplayRotation::Rotate90);
+//! Rectangle::new(Point::new(10, 20), Size::new(30, 5))
+//!     .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+//!     .draw(&mut display)
+//!     .unwrap();
+//! // in unrotated display coordinates
+//! assert_eq!(
+//!     display.damage(),
+//!     Some(Rectangle::new(Point::new(375, 10), Size::new(5, 30)))
+//! );
+//! ```
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::graphics::{find_rect, unrotated_size, Display, DisplayMirroring, DisplayRotation};
+use crate::traits::WaveshareDisplay;
+use embedded_graphics_core::pixelcolor::BinaryColor;
+use embedded_graphics_core::prelude::*;
+use embedded_graphics_core::primitives::Rectangle;
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::*,
+};
+
+/// A b/w display buffer which remembers the area drawn since the last flush
+///
+/// Drawing works like with the wrapped buffer. Writing to the buffer directly with
+/// [get_mut_buffer](Display::get_mut_buffer) marks the whole display as damaged, because
+/// it isn't known what changed.
+pub struct DamageTracked<D> {
+    display: D,
+    // unrotated top left and bottom right pixel
+    damage: Option<(u32, u32, u32, u32)>,
+}
+
+impl<D: Display> DamageTracked<D> {
+    /// Tracks the drawing into `display`
+    ///
+    /// The whole display starts out damaged, because it isn't known whether the buffer
+    /// is on the screen. Call [reset_damage](Self::reset_damage) if it is.
+    pub fn new(display: D) -> Self {
+        let mut tracked = DamageTracked {
+            display,
+            damage: None,
+        };
+        tracked.damage_all();
+        tracked
+    }
+
+    /// The wrapped buffer
+    pub fn inner(&self) -> &D {
+        &self.display
+    }
+
+    /// Stops the tracking and returns the wrapped buffer
+    pub fn into_inner(self) -> D {
+        self.display
+    }
+
+    /// The rectangle around everything drawn since the last flush or reset
+    ///
+    /// In unrotated display coordinates like the windows of
+    /// [update_partial_frame](WaveshareDisplay::update_partial_frame), so it doesn't
+    /// depend on the rotation and mirroring used while drawing.
+    pub fn damage(&self) -> Option<Rectangle> {
+        self.damage.map(|(x0, y0, x1, y1)| {
+            Rectangle::new(
+                Point::new(x0 as i32, y0 as i32),
+                Size::new(x1 - x0 + 1, y1 - y0 + 1),
+            )
+        })
+    }
+
+    /// Forget the damage, e.g. after sending the buffer some other way
+    pub fn reset_damage(&mut self) {
+        self.damage = None;
+    }
+
+    /// Sends the damaged window with a partial update and displays it
+    ///
+    /// The window is widened to whole bytes. Unless it spans whole rows, its rows are
+    /// gathered in `scratch` first, so they are sent as one window. A `scratch` as long
+    /// as the buffer always fits; a shorter one splits the window into bands of as many
+    /// rows as it can hold, and one without room for a row sends them one by one.
+    ///
+    /// Displays without partial updates get the full frame. Doesn't talk to the display
+    /// at all if nothing was drawn.
+    pub fn flush_partial<SPI, CS, BUSY, DC, RST, DELAY, EPD>(
+        &mut self,
+        epd: &mut EPD,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        scratch: &mut [u8],
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: Write<u8>,
+        CS: OutputPin,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayMs<u8>,
+        EPD: WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>,
+    {
+        let damage = match self.damage {
+            Some(damage) => damage,
+            None => return Ok(()),
+        };
+        let (width, _) = unrotated_size(self.display.bounding_box().size, self.display.rotation());
+        update_window(
+            epd,
+            spi,
+            delay,
+            self.display.buffer(),
+            width,
+            damage,
+            scratch,
+        )?;
+        epd.display_frame(spi, delay)?;
+        self.reset_damage();
+        Ok(())
+    }
+
+    fn damage_all(&mut self) {
+        let area = self.display.bounding_box();
+        self.add_damage(&area);
+    }
+
+    // `area` in drawing coordinates
+    fn add_damage(&mut self, area: &Rectangle) {
+        let (rotation, mirroring) = (self.display.rotation(), self.display.mirroring());
+        let (width, height) = unrotated_size(self.display.bounding_box().size, rotation);
+        if let Some((x0, y0, x1, y1)) = find_rect(area, width, height, rotation, mirroring) {
+            self.damage = Some(match self.damage {
+                Some((a0, b0, a1, b1)) => (a0.min(x0), b0.min(y0), a1.max(x1), b1.max(y1)),
+                None => (x0, y0, x1, y1),
+            });
+        }
+    }
+}
+
+// Sends the window from (x0, y0) to (x1, y1) of the b/w frame `buffer`, `width` pixels wide,
+// with `update_partial_frame` as in `DamageTracked::flush_partial`, or the whole frame to
+// displays without partial updates
+pub(crate) fn update_window<SPI, CS, BUSY, DC, RST, DELAY, EPD>(
+    epd: &mut EPD,
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    buffer: &[u8],
+    width: u32,
+    window: (u32, u32, u32, u32),
+    scratch: &mut [u8],
+) -> Result<(), Error<SPI::Error>>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    EPD: WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>,
+{
+    let (x0, y0, x1, y1) = window;
+    let stride = width.div_ceil(8) as usize;
+    let (first, last) = (x0 as usize / 8, x1 as usize / 8);
+    if !EPD::SUPPORTS_PARTIAL {
+        epd.update_frame(spi, buffer, delay)?;
+    } else if first == 0 && last + 1 == stride {
+        // whole rows are one piece of the buffer
+        let rows = &buffer[y0 as usize * stride..(y1 as usize + 1) * stride];
+        epd.update_partial_frame(spi, rows, 0, y0, width, y1 - y0 + 1)?;
+    } else {
+        let x = first as u32 * 8;
+        let window_width = ((last as u32 + 1) * 8).min(width) - x;
+        let row_len = last + 1 - first;
+        let band = (scratch.len() / row_len).max(1) as u32;
+        let mut y = y0;
+        while y <= y1 {
+            let rows = band.min(y1 + 1 - y);
+            let start = y as usize * stride + first;
+            if rows == 1 {
+                let row = &buffer[start..start + row_len];
+                epd.update_partial_frame(spi, row, x, y, window_width, 1)?;
+            } else {
+                let window = &mut scratch[..rows as usize * row_len];
+                for (i, row) in window.chunks_exact_mut(row_len).enumerate() {
+                    let start = start + i * stride;
+                    row.copy_from_slice(&buffer[start..start + row_len]);
+                }
+                epd.update_partial_frame(spi, window, x, y, window_width, rows)?;
+            }
+            y += rows;
+        }
+    }
+    Ok(())
+}
+
+impl<D: Display> DrawTarget for DamageTracked<D> {
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let mut corners: Option<(Point, Point)> = None;
+        let result = self
+            .display
+            .draw_iter(pixels.into_iter().inspect(|&Pixel(p, _)| {
+                corners = Some(match corners {
+                    Some((min, max)) => (min.component_min(p), max.component_max(p)),
+                    None => (p, p),
+                });
+            }));
+        if let Some((min, max)) = corners {
+            // clipped first, the corners can be too far apart for the size of a rectangle
+            let size = self.display.bounding_box().size;
+            let bottom_right = Point::new(size.width as i32 - 1, size.height as i32 - 1);
+            let (min, max) = (
+                min.component_max(Point::zero()),
+                max.component_min(bottom_right),
+            );
+            if min.x <= max.x && min.y <= max.y {
+                self.add_damage(&Rectangle::with_corners(min, max));
+            }
+        }
+        result
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.add_damage(area);
+        self.display.fill_solid(area, color)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.add_damage(area);
+        self.display.fill_contiguous(area, colors)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.damage_all();
+        self.display.clear(color)
+    }
+}
+
+impl<D: Display> OriginDimensions for DamageTracked<D> {
+    fn size(&self) -> Size {
+        self.display.bounding_box().size
+    }
+}
+
+impl<D: Display> Display for DamageTracked<D> {
+    fn clear_buffer(&mut self, background_color: Color) {
+        self.damage_all();
+        self.display.clear_buffer(background_color);
+    }
+
+    fn buffer(&self) -> &[u8] {
+        self.display.buffer()
+    }
+
+    fn get_mut_buffer(&mut self) -> &mut [u8] {
+        self.damage_all();
+        self.display.get_mut_buffer()
+    }
+
+    fn set_rotation(&mut self, rotation: DisplayRotation) {
+        self.display.set_rotation(rotation);
+    }
+
+    fn rotation(&self) -> DisplayRotation {
+        self.display.rotation()
+    }
+
+    fn set_mirroring(&mut self, mirroring: DisplayMirroring) {
+        self.display.set_mirroring(mirroring);
+    }
+
+    fn mirroring(&self) -> DisplayMirroring {
+        self.display.mirroring()
+    }
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.display.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.display.inverted()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_len;
+    use crate::color::Black;
+    use crate::epd4in2::{command::Command, Epd4in2, HEIGHT, WIDTH};
+    use crate::epd7in5_v2::Epd7in5;
+    use crate::graphics::VarDisplay;
+    use crate::mock::{init_epd, Transfer};
+    use crate::traits::Command as _;
+    use embedded_graphics::prelude::Primitive;
+    use embedded_graphics::primitives::{Line, PrimitiveStyle};
+    extern crate std;
+    use std::vec::Vec;
+
+    const LEN: usize = buffer_len(WIDTH as usize, HEIGHT as usize);
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Option<Rectangle> {
+        Some(Rectangle::new(Point::new(x, y), Size::new(width, height)))
+    }
+
+    #[test]
+    fn damage_in_every_rotation() {
+        let mut buffer = [0xff; buffer_len(20, 10)];
+        let mut display = DamageTracked::new(VarDisplay::new(20, 10, &mut buffer).unwrap());
+        assert_eq!(display.damage(), rect(0, 0, 20, 10));
+        display.reset_damage();
+        assert_eq!(display.damage(), None);
+
+        // (2, 3) is the unrotated pixel (16, 2) when rotated by 90 degrees
+        display.set_rotation(DisplayRotation::Rotate90);
+        Pixel(Point::new(2, 3), Black).draw(&mut display).unwrap();
+        assert_eq!(display.damage(), rect(16, 2, 1, 1));
+
+        display.set_rotation(DisplayRotation::Rotate180);
+        display.set_mirroring(DisplayMirroring::MirrorX);
+        Pixel(Point::new(1, 1), Black).draw(&mut display).unwrap();
+        assert_eq!(display.damage(), rect(1, 2, 16, 7));
+
+        // outside of the display
+        display.reset_damage();
+        Line::new(Point::new(-5, 30), Point::new(100, 30))
+            .into_styled(PrimitiveStyle::with_stroke(Black, 1))
+            .draw(&mut display)
+            .unwrap();
+        Pixel(Point::new(i32::MIN, i32::MAX), Black)
+            .draw(&mut display)
+            .unwrap();
+        assert_eq!(display.damage(), None);
+    }
+
+    #[test]
+    fn fills_and_direct_writes() {
+        let mut buffer = [0xff; buffer_len(20, 10)];
+        let mut display = DamageTracked::new(VarDisplay::new(20, 10, &mut buffer).unwrap());
+        display.reset_damage();
+
+        display.set_rotation(DisplayRotation::Rotate270);
+        // reaches beyond the display, (0, 0) is the unrotated pixel (0, 9)
+        Rectangle::new(Point::new(-3, -3), Size::new(5, 6))
+            .into_styled(PrimitiveStyle::with_fill(Black))
+            .draw(&mut display)
+            .unwrap();
+        assert_eq!(display.damage(), rect(0, 8, 3, 2));
+
+        display.reset_damage();
+        display.get_mut_buffer()[3] = 0;
+        assert_eq!(display.damage(), rect(0, 0, 20, 10));
+
+        display.reset_damage();
+        display.clear(BinaryColor::Off).unwrap();
+        assert_eq!(display.damage(), rect(0, 0, 20, 10));
+        assert!(display.inner().buffer().iter().all(|&byte| byte == 0xff));
+    }
+
+    #[test]
+    fn flush_partial() {
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+
+        let mut buffer = [0xff; LEN];
+        let mut display = DamageTracked::new(VarDisplay::new(WIDTH, HEIGHT, &mut buffer).unwrap());
+        display.reset_damage();
+
+        let mut scratch = [0; LEN];
+
+        log.clear();
+        display
+            .flush_partial(&mut epd, &mut spi, &mut delay, &mut scratch)
+            .unwrap();
+        assert!(log.transfers().is_empty());
+
+        // two rows of a 16 pixel wide window in one piece
+        Pixel(Point::new(13, 1), Black).draw(&mut display).unwrap();
+        Pixel(Point::new(17, 2), Black).draw(&mut display).unwrap();
+        log.clear();
+        display
+            .flush_partial(&mut epd, &mut spi, &mut delay, &mut scratch)
+            .unwrap();
+        assert_eq!(display.damage(), None);
+        assert_eq!(log.count_command(Command::PartialWindow.address()), 1);
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 1);
+        assert_eq!(
+            log.data_after_each(Command::DataStartTransmission2.address()),
+            [[!0x04, 0xff, 0xff, !0x40]]
+        );
+
+        // bands of the rows which fit, or single rows without a scratch buffer
+        for (scratch, windows) in [(&mut scratch[..5], 2), (&mut [][..], 3)] {
+            for y in 1..4 {
+                Pixel(Point::new(13, y), Black).draw(&mut display).unwrap();
+                Pixel(Point::new(17, y), Black).draw(&mut display).unwrap();
+            }
+            log.clear();
+            display
+                .flush_partial(&mut epd, &mut spi, &mut delay, scratch)
+                .unwrap();
+            assert_eq!(log.count_command(Command::PartialWindow.address()), windows);
+            let data: Vec<Vec<u8>> = log.data_after_each(Command::DataStartTransmission2.address());
+            assert_eq!(data.concat(), [!0x04, !0x40].repeat(3));
+        }
+
+        // whole rows in one piece
+        Line::new(Point::new(0, 5), Point::new(WIDTH as i32 - 1, 5))
+            .into_styled(PrimitiveStyle::with_stroke(Black, 1))
+            .draw(&mut display)
+            .unwrap();
+        log.clear();
+        display
+            .flush_partial(&mut epd, &mut spi, &mut delay, &mut [])
+            .unwrap();
+        assert_eq!(log.count_command(Command::PartialWindow.address()), 1);
+        assert_eq!(
+            log.data_after_each(Command::DataStartTransmission2.address()),
+            [[0x00; WIDTH as usize / 8]]
+        );
+    }
+
+    #[test]
+    fn flush_without_partial_update() {
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd7in5<_, _, _, _, _, _>>();
+
+        let mut buffer = [0xff; crate::epd7in5_v2::buffer_len()];
+        let (width, height) = (crate::epd7in5_v2::WIDTH, crate::epd7in5_v2::HEIGHT);
+        let mut display = DamageTracked::new(VarDisplay::new(width, height, &mut buffer).unwrap());
+        display.reset_damage();
+        Pixel(Point::new(1, 1), Black).draw(&mut display).unwrap();
+
+        log.clear();
+        display
+            .flush_partial(&mut epd, &mut spi, &mut delay, &mut [])
+            .unwrap();
+        // DataStartTransmission2 with the full frame
+        let start = log
+            .transfers()
+            .iter()
+            .position(|t| *t == Transfer::Command(0x13))
+            .unwrap();
+        match &log.transfers()[start + 1] {
+            Transfer::Data(frame) => assert_eq!(frame.len(), buffer_len(800, 480)),
+            other => panic!("{:?} instead of the frame", other),
+        }
+        assert_eq!(display.damage(), None);
+    }
+}
@@ -0,0 +1,322 @@
+//! Drawing straight into the RAM of the controller, without a frame buffer
+//!
+//! [DirectDisplay] is a [DrawTarget] which sends whatever is drawn right away as partial
+//! windows with [update_partial_frame](WaveshareDisplay::update_partial_frame). It keeps
+//! no more than a few bytes of one row, so text, lines and rectangles can be drawn on MCUs
+//! with a couple of KB of RAM, at the cost of a lot more spi traffic. The display shows the
+//! drawing with the next [display_frame](WaveshareDisplay::display_frame) as usual.
+//!
+//! It works with all b/w displays which support partial windows
+//! ([SUPPORTS_PARTIAL](WaveshareDisplay::SUPPORTS_PARTIAL)): the 1in54, 2in13 V2, V3 & V4,
+//! 2in9, 2in9 V2 and 4in2. There is no rotation, the coordinates are the ones of the controller.
+//!
+//! The RAM can't be read back, so a byte of 8 pixels in a row is always written as a whole
+//! and the pixels of it which aren't drawn in the same run become the background color.
+//! Shapes which cover their bytes in one go work as expected: filled rectangles and
+//! everything with x and width at multiples of 8, e.g. text with a background color in a
+//! font 8 pixels wide. Everything else clears the rest of the bytes it touches, like the
+//! glyphs of a narrower font the end of the glyph before.
+//!
+//! ```rust, no_run
+//! # use embedded_hal_mock::*;
+//! # fn main() -> Result<(), epd_waveshare::Error<MockError>> {
+//! use embedded_graphics::{prelude::*, primitives::{PrimitiveStyle, Rectangle}};
+//! use epd_waveshare::{color::*, direct::DirectDisplay, epd2in9::*, prelude::*};
+//!
+//! # let expectations = [];
+//! # let mut spi = spi::Mock::new(&expectations);
+//! # let expectations = [];
+//! # let cs_pin = pin::Mock::new(&expectations);
+//! # let busy_in = pin::Mock::new(&expectations);
+//! # let dc = pin::Mock::new(&expectations);
+//! # let rst = pin::Mock::new(&expectations);
+//! # let mut delay = delay::MockNoop::new();
+//! let mut epd = Epd2in9::new(cs_pin, busy_in, dc, rst);
+//! epd.init(&mut spi, &mut delay)?;
+//!
+//! let mut display = DirectDisplay::new(&mut epd, &mut spi)?;
+//! Rectangle::new(Point::new(8, 8), Size::new(32, 16))
+//!     .into_styled(PrimitiveStyle::with_fill(Black))
+//!     .draw(&mut display)?;
+//!
+//! epd.display_frame(&mut spi, &mut delay)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::color::{Color, ColorType};
+use crate::error::Error;
+use crate::traits::WaveshareDisplay;
+use core::marker::PhantomData;
+use embedded_graphics_core::{pixelcolor::BinaryColor, prelude::*, primitives::Rectangle};
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::*,
+};
+
+/// Bytes of a row which are sent in one window
+const RUN: usize = 16;
+
+/// A [DrawTarget] writing straight into the RAM of the display, see the
+/// [module documentation](self)
+pub struct DirectDisplay<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD> {
+    epd: &'a mut EPD,
+    spi: &'a mut SPI,
+    #[allow(clippy::type_complexity)]
+    _pins: PhantomData<fn(CS, BUSY, DC, RST, DELAY)>,
+}
+
+impl<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD> DirectDisplay<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    EPD: WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY, DisplayColor = Color>,
+{
+    /// Draws on `epd` through `spi` until it's dropped
+    ///
+    /// Fails with [Error::Unsupported] on displays without partial windows.
+    pub fn new(epd: &'a mut EPD, spi: &'a mut SPI) -> Result<Self, Error<SPI::Error>> {
+        if !EPD::SUPPORTS_PARTIAL {
+            return Err(Error::Unsupported);
+        }
+        Ok(DirectDisplay {
+            epd,
+            spi,
+            _pins: PhantomData,
+        })
+    }
+
+    // Sends the bytes of `run` as one window
+    fn send(&mut self, run: &Run) -> Result<(), Error<SPI::Error>> {
+        let x = run.column * 8;
+        let width = (run.len as u32 * 8).min(self.epd.width() - x);
+        self.epd
+            .update_partial_frame(self.spi, &run.bytes[..run.len], x, run.y, width, 1)
+    }
+}
+
+// Neighbouring bytes of a row, starting at byte `column`
+struct Run {
+    column: u32,
+    y: u32,
+    len: usize,
+    bytes: [u8; RUN],
+}
+
+impl Run {
+    fn new(column: u32, y: u32, background: u8) -> Self {
+        Run {
+            column,
+            y,
+            len: 1,
+            bytes: [background; RUN],
+        }
+    }
+
+    // Puts the pixel into the run, false if it doesn't belong to it
+    fn draw(&mut self, x: u32, y: u32, color: BinaryColor) -> bool {
+        let column = x / 8;
+        if y != self.y || column < self.column || column > self.column + self.len as u32 {
+            return false;
+        }
+        if column == self.column + self.len as u32 {
+            if self.len == RUN {
+                return false;
+            }
+            self.len += 1;
+        }
+        let byte = &mut self.bytes[(column - self.column) as usize];
+        color.pack(0, x % 8, byte);
+        true
+    }
+}
+
+impl<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD> DrawTarget
+    for DirectDisplay<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    EPD: WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY, DisplayColor = Color>,
+{
+    type Color = BinaryColor;
+    type Error = Error<SPI::Error>;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let background = self.epd.background_color().fill_byte(0);
+        let bounds = self.bounding_box();
+        let mut run: Option<Run> = None;
+        for Pixel(point, color) in pixels {
+            if !bounds.contains(point) {
+                continue;
+            }
+            let (x, y) = (point.x as u32, point.y as u32);
+            if let Some(run) = run.as_mut() {
+                if run.draw(x, y, color) {
+                    continue;
+                }
+                self.send(run)?;
+            }
+            let mut next = Run::new(x / 8, y, background);
+            next.draw(x, y, color);
+            run = Some(next);
+        }
+        match run {
+            Some(run) => self.send(&run),
+            None => Ok(()),
+        }
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        let bottom_right = match area.bottom_right() {
+            Some(point) => point,
+            None => return Ok(()),
+        };
+        let background = self.epd.background_color().fill_byte(0);
+        let (left, right) = (area.top_left.x as u32, bottom_right.x as u32);
+        for y in area.top_left.y as u32..=bottom_right.y as u32 {
+            let mut x = left;
+            while x <= right {
+                let mut run = Run::new(x / 8, y, background);
+                while x <= right && run.draw(x, y, color) {
+                    x += 1;
+                }
+                self.send(&run)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD> OriginDimensions
+    for DirectDisplay<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    EPD: WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY, DisplayColor = Color>,
+{
+    fn size(&self) -> Size {
+        Size::new(self.epd.width(), self.epd.height())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Black;
+    use crate::epd2in9::Epd2in9;
+    use crate::epd2in9bc::Epd2in9bc;
+    use crate::mock::init_epd;
+    use embedded_graphics::primitives::{Primitive, PrimitiveStyle};
+
+    #[test]
+    fn rectangles_are_sent_in_rows() {
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd2in9<_, _, _, _, _, _>>();
+        log.clear();
+        epd.update_partial_frame(&mut spi, &[0x00, 0x00, 0x0f], 8, 2, 24, 1)
+            .unwrap();
+        epd.update_partial_frame(&mut spi, &[0x00, 0x00, 0x0f], 8, 3, 24, 1)
+            .unwrap();
+        let expected = log.transcript();
+
+        // 20 pixels from x = 8 on, the last byte is half background
+        log.clear();
+        let mut display = DirectDisplay::new(&mut epd, &mut spi).unwrap();
+        Rectangle::new(Point::new(8, 2), Size::new(20, 2))
+            .into_styled(PrimitiveStyle::with_fill(Black))
+            .draw(&mut display)
+            .unwrap();
+        assert_eq!(log.transcript(), expected);
+    }
+
+    #[test]
+    fn pixels_of_a_byte_are_merged() {
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd2in9<_, _, _, _, _, _>>();
+        log.clear();
+        epd.update_partial_frame(&mut spi, &[0x3f, 0xfe], 0, 0, 16, 1)
+            .unwrap();
+        epd.update_partial_frame(&mut spi, &[0xbf], 0, 1, 8, 1)
+            .unwrap();
+        let expected = log.transcript();
+
+        // pixels off the display are left out
+        log.clear();
+        let mut display = DirectDisplay::new(&mut epd, &mut spi).unwrap();
+        display
+            .draw_iter([
+                Pixel(Point::new(-4, 0), Black),
+                Pixel(Point::new(0, 0), Black),
+                Pixel(Point::new(1, 0), Black),
+                Pixel(Point::new(15, 0), Black),
+                Pixel(Point::new(1, 1), Black),
+                Pixel(Point::new(1, 400), Black),
+            ])
+            .unwrap();
+        assert_eq!(log.transcript(), expected);
+    }
+
+    #[test]
+    fn displays_without_partial_windows() {
+        let (_log, mut spi, _delay, mut epd) = init_epd::<Epd2in9bc<_, _, _, _, _, _>>();
+        assert!(matches!(
+            DirectDisplay::new(&mut epd, &mut spi),
+            Err(Error::Unsupported)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn same_as_a_frame_buffer() {
+        use crate::color::White;
+        use crate::epd2in9::{Display2in9, HEIGHT, WIDTH};
+        use crate::fake::FakeEpd;
+        use crate::graphics::Display as _;
+        use crate::mock::{BusyPin, DcPin, Delay, Pin, Spi};
+
+        use embedded_graphics::{
+            mono_font::{ascii::FONT_8X13, MonoTextStyleBuilder},
+            text::{Baseline, Text},
+        };
+
+        type Fake = FakeEpd<Spi, Pin, BusyPin, DcPin, Pin, Delay, Color, WIDTH, HEIGHT>;
+        fn draw<D: DrawTarget<Color = BinaryColor>>(target: &mut D) -> Result<(), D::Error> {
+            let style = MonoTextStyleBuilder::new()
+                .font(&FONT_8X13)
+                .text_color(Black)
+                .background_color(White)
+                .build();
+            Text::with_baseline("Hello", Point::new(8, 20), style, Baseline::Top).draw(target)?;
+            Rectangle::new(Point::new(3, 40), Size::new(50, 7))
+                .into_styled(PrimitiveStyle::with_fill(Black))
+                .draw(target)?;
+            Ok(())
+        }
+
+        let (_log, mut spi, mut delay, mut buffered) = init_epd::<Fake>();
+        let mut display = Display2in9::default();
+        draw(&mut display).unwrap();
+        buffered
+            .update_frame(&mut spi, display.buffer(), &mut delay)
+            .unwrap();
+
+        let (_log, mut spi, mut delay, mut direct) = init_epd::<Fake>();
+        direct.clear_frame(&mut spi, &mut delay).unwrap();
+        draw(&mut DirectDisplay::new(&mut direct, &mut spi).unwrap()).unwrap();
+        assert_eq!(direct.last_frame(), buffered.last_frame());
+    }
+}
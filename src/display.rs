@@ -0,0 +1,385 @@
+//! One fixed size display buffer for every panel and color
+//!
+//! [FixedDisplay] is generic over the unrotated size, the buffer length and the [ColorType].
+//! The buffers of the drivers, e.g. [Display4in2](crate::epd4in2::Display4in2), are type
+//! aliases of it, so a buffer for a partial window or a panel without a driver is just
+//! another alias:
+//!
+//! ```
+//! use embedded_graphics::pixelcolor::BinaryColor;
+//! use epd_waveshare::{buffer_len, display, prelude::*};
+//!
+//! type Window = display::FixedDisplay<64, 24, { buffer_len(64, 24) }, BinaryColor>;
+//!
+//! let mut window = Window::default();
+//! window.clear_buffer(Color::Black);
+//! assert!(window.buffer().iter().all(|&byte| byte == 0x00));
+//! ```
+//!
+//! The buffer length has to be given separately, because array lengths can't be computed
+//! from generic parameters yet. It's checked against [buffer_size] when the display is
+//! created, a wrong length fails to compile.
+//!
+//! Every size is its own type, so the drawing code is compiled for every size in use, with
+//! the size as constants. For many different sizes at runtime the
+//! [VarDisplay](crate::graphics::VarDisplay) family keeps a single copy.
+//!
+//! # DMA and memory placement
+//!
+//! The buffer is 4 byte aligned, as most DMA engines want it. [FixedDisplay::new] is a
+//! `const fn`, so a display can be a `static`, which can be put into a special RAM region
+//! with `#[link_section = ".."]` of the linker script. For a buffer which comes from
+//! somewhere else, e.g. a `&'static mut [u8]` handed out by a DMA allocator, use the
+//! [VarDisplay](crate::graphics::VarDisplay) family, which borrows its buffer.
+//!
+//! ```
+//! use epd_waveshare::{epd4in2::Display4in2, prelude::*};
+//!
+//! // e.g. with #[link_section = ".axisram"] on the static
+//! static FRAME: Display4in2 = Display4in2::new();
+//! assert_eq!(FRAME.buffer().as_ptr() as usize % 4, 0);
+//! ```
+
+use crate::color::{buffer_size, ColorType, OctColor, TriColor};
+use crate::graphics::{
+    impl_buffer_accessors, Display, DisplayMirroring, DisplayRotation, GrayDisplay, OctDisplay,
+    TriDisplay,
+};
+use core::marker::PhantomData;
+use embedded_graphics_core::pixelcolor::{BinaryColor, Gray2};
+use embedded_graphics_core::prelude::*;
+use embedded_graphics_core::primitives::Rectangle;
+
+/// A `WIDTH` x `HEIGHT` buffer of `BYTECOUNT` bytes for colors of type `C`
+///
+/// `WIDTH` and `HEIGHT` are the unrotated size like for the drivers, `BYTECOUNT` has to be
+/// [`buffer_size::<C>(WIDTH, HEIGHT)`](buffer_size). Drawing works through [Display] for
+/// `BinaryColor`, [TriDisplay] for `TriColor`, [OctDisplay] for `OctColor` and
+/// [GrayDisplay] for `Gray2`.
+pub struct FixedDisplay<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize, C: ColorType> {
+    buffer: Aligned<BYTECOUNT>,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    inverted: bool,
+    _color: PhantomData<C>,
+}
+
+// Keeps the buffer 4 byte aligned for DMA
+#[derive(Clone, Copy)]
+#[repr(C, align(4))]
+struct Aligned<const N: usize>([u8; N]);
+
+impl<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize, C: ColorType>
+    FixedDisplay<WIDTH, HEIGHT, BYTECOUNT, C>
+{
+    /// Length of the buffer in bytes, the frame length of the display
+    pub const BUFFER_LEN: usize = BYTECOUNT;
+
+    // evaluated when a display of this type is created, fails the build for a wrong length
+    const LENGTH_CHECK: () = assert!(
+        BYTECOUNT == buffer_size::<C>(WIDTH as usize, HEIGHT as usize),
+        "BYTECOUNT doesn't match the size and color of the display"
+    );
+
+    /// Create the display around an existing frame buffer
+    ///
+    /// See the [buffer layout](crate::graphics#buffer-layout) for how the pixels are stored.
+    pub const fn from_buffer(buffer: [u8; BYTECOUNT]) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::LENGTH_CHECK;
+        FixedDisplay {
+            buffer: Aligned(buffer),
+            rotation: DisplayRotation::Rotate0,
+            mirroring: DisplayMirroring::None,
+            inverted: false,
+            _color: PhantomData,
+        }
+    }
+
+    /// A white buffer, like `default()` but usable for statics
+    pub const fn new() -> Self {
+        Self::from_buffer([C::WHITE_BYTE; BYTECOUNT])
+    }
+
+    /// Consumes the display and returns the frame buffer
+    pub fn into_buffer(self) -> [u8; BYTECOUNT] {
+        self.buffer.0
+    }
+}
+
+/// A white buffer
+impl<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize, C: ColorType> Default
+    for FixedDisplay<WIDTH, HEIGHT, BYTECOUNT, C>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize, C: ColorType> OriginDimensions
+    for FixedDisplay<WIDTH, HEIGHT, BYTECOUNT, C>
+{
+    fn size(&self) -> Size {
+        self.rotation.rotated_size(WIDTH, HEIGHT)
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize> DrawTarget
+    for FixedDisplay<WIDTH, HEIGHT, BYTECOUNT, BinaryColor>
+{
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper(WIDTH, HEIGHT, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper(WIDTH, HEIGHT, area, color)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.fill_contiguous_helper(WIDTH, HEIGHT, area, colors)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color.into());
+        Ok(())
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize> Display
+    for FixedDisplay<WIDTH, HEIGHT, BYTECOUNT, BinaryColor>
+{
+    impl_buffer_accessors!(buffer.0);
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    fn inverted(&self) -> bool {
+        self.inverted
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize> DrawTarget
+    for FixedDisplay<WIDTH, HEIGHT, BYTECOUNT, TriColor>
+{
+    type Color = TriColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper_tri(WIDTH, HEIGHT, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper_tri(WIDTH, HEIGHT, area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color);
+        Ok(())
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize> TriDisplay
+    for FixedDisplay<WIDTH, HEIGHT, BYTECOUNT, TriColor>
+{
+    impl_buffer_accessors!(buffer.0);
+
+    fn chromatic_offset(&self) -> usize {
+        BYTECOUNT / 2
+    }
+
+    fn bw_buffer(&self) -> &[u8] {
+        &self.buffer.0[0..self.chromatic_offset()]
+    }
+
+    fn chromatic_buffer(&self) -> &[u8] {
+        &self.buffer.0[self.chromatic_offset()..]
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize> DrawTarget
+    for FixedDisplay<WIDTH, HEIGHT, BYTECOUNT, OctColor>
+{
+    type Color = OctColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper(WIDTH, HEIGHT, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper(WIDTH, HEIGHT, area, color)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.fill_contiguous_helper(WIDTH, HEIGHT, area, colors)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color);
+        Ok(())
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize> OctDisplay
+    for FixedDisplay<WIDTH, HEIGHT, BYTECOUNT, OctColor>
+{
+    impl_buffer_accessors!(buffer.0);
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize> DrawTarget
+    for FixedDisplay<WIDTH, HEIGHT, BYTECOUNT, Gray2>
+{
+    type Color = Gray2;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper(WIDTH, HEIGHT, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper(WIDTH, HEIGHT, area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color);
+        Ok(())
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, const BYTECOUNT: usize> GrayDisplay
+    for FixedDisplay<WIDTH, HEIGHT, BYTECOUNT, Gray2>
+{
+    impl_buffer_accessors!(buffer.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_len;
+    use crate::graphics::{VarDisplay, VarGrayDisplay, VarOctDisplay, VarTriDisplay};
+    use embedded_graphics::{
+        prelude::*,
+        primitives::{Circle, Line, PrimitiveStyle},
+    };
+
+    // a partial window which isn't a multiple of 8 wide
+    const WIDTH: u32 = 21;
+    const HEIGHT: u32 = 10;
+
+    const ROTATIONS: [DisplayRotation; 4] = [
+        DisplayRotation::Rotate0,
+        DisplayRotation::Rotate90,
+        DisplayRotation::Rotate180,
+        DisplayRotation::Rotate270,
+    ];
+
+    fn draw_scene<D: DrawTarget>(display: &mut D, a: D::Color, b: D::Color) {
+        let _ = Line::new(Point::new(-3, 1), Point::new(25, 7))
+            .into_styled(PrimitiveStyle::with_stroke(a, 1))
+            .draw(display);
+        let _ = Circle::new(Point::new(3, 2), 7)
+            .into_styled(PrimitiveStyle::with_fill(b))
+            .draw(display);
+        let _ = display.fill_solid(&Rectangle::new(Point::new(5, 4), Size::new(11, 3)), a);
+    }
+
+    #[test]
+    fn aligned_buffers() {
+        // odd lengths, which an unaligned array could start anywhere
+        let displays = [
+            FixedDisplay::<3, 1, 1, BinaryColor>::new(),
+            FixedDisplay::<3, 1, 1, BinaryColor>::new(),
+        ];
+        for display in displays.iter() {
+            assert_eq!(Display::buffer(display).as_ptr() as usize % 4, 0);
+        }
+        let display =
+            FixedDisplay::<WIDTH, HEIGHT, { buffer_size::<OctColor>(21, 10) }, OctColor>::new();
+        assert_eq!(OctDisplay::buffer(&display).as_ptr() as usize % 4, 0);
+        assert_eq!(
+            FixedDisplay::<WIDTH, HEIGHT, { buffer_size::<OctColor>(21, 10) }, OctColor>::BUFFER_LEN,
+            110
+        );
+    }
+
+    #[test]
+    fn same_as_var_displays() {
+        for &rotation in ROTATIONS.iter() {
+            let mut display =
+                FixedDisplay::<WIDTH, HEIGHT, { buffer_len(21, 10) }, BinaryColor>::default();
+            let mut buffer = [0xff; buffer_len(21, 10)];
+            let mut reference = VarDisplay::new(WIDTH, HEIGHT, &mut buffer).unwrap();
+            Display::set_rotation(&mut display, rotation);
+            reference.set_rotation(rotation);
+            draw_scene(&mut display, BinaryColor::On, BinaryColor::Off);
+            draw_scene(&mut reference, BinaryColor::On, BinaryColor::Off);
+            assert_eq!(display.size(), reference.size());
+            assert_eq!(Display::buffer(&display), reference.buffer());
+
+            const TRI: usize = buffer_size::<TriColor>(21, 10);
+            let mut display = FixedDisplay::<WIDTH, HEIGHT, TRI, TriColor>::default();
+            let mut buffer = [TriColor::WHITE_BYTE; TRI];
+            let mut reference = VarTriDisplay::new(WIDTH, HEIGHT, &mut buffer).unwrap();
+            TriDisplay::set_rotation(&mut display, rotation);
+            reference.set_rotation(rotation);
+            draw_scene(&mut display, TriColor::Chromatic, TriColor::Black);
+            draw_scene(&mut reference, TriColor::Chromatic, TriColor::Black);
+            assert_eq!(display.bw_buffer(), reference.bw_buffer());
+            assert_eq!(display.chromatic_buffer(), reference.chromatic_buffer());
+
+            const OCT: usize = buffer_size::<OctColor>(21, 10);
+            let mut display = FixedDisplay::<WIDTH, HEIGHT, OCT, OctColor>::default();
+            let mut buffer = [OctColor::WHITE_BYTE; OCT];
+            let mut reference = VarOctDisplay::new(WIDTH, HEIGHT, &mut buffer).unwrap();
+            OctDisplay::set_rotation(&mut display, rotation);
+            reference.set_rotation(rotation);
+            draw_scene(&mut display, OctColor::Red, OctColor::Blue);
+            draw_scene(&mut reference, OctColor::Red, OctColor::Blue);
+            assert_eq!(OctDisplay::buffer(&display), reference.buffer());
+
+            const GRAY: usize = crate::gray::buffer_size(21, 10);
+            let mut display = FixedDisplay::<WIDTH, HEIGHT, GRAY, Gray2>::default();
+            let mut buffer = [Gray2::WHITE_BYTE; GRAY];
+            let mut reference = VarGrayDisplay::new(WIDTH, HEIGHT, &mut buffer).unwrap();
+            GrayDisplay::set_rotation(&mut display, rotation);
+            reference.set_rotation(rotation);
+            draw_scene(&mut display, Gray2::new(1), Gray2::new(2));
+            draw_scene(&mut reference, Gray2::new(1), Gray2::new(2));
+            assert_eq!(GrayDisplay::buffer(&display), reference.buffer());
+        }
+    }
+}
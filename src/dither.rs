@@ -0,0 +1,637 @@
+//! Floyd–Steinberg dithering of rgb images for the displays with few colors
+//!
+//! Quantizing a photo to the nearest color of the display loses all gradients,
+//! spreading the error of every pixel to its neighbours keeps them.
+//! The [Ditherer] works row by row and only keeps the errors of two rows in a
+//! line buffer provided by the caller, so the image itself never has to be in memory.
+//! [DitherTarget] does the same for everything drawn onto it in rgb.
+//!
+//! Example:
+//! ```rust
+//! # use epd_waveshare::dither::{line_buffer_len, Ditherer, OCT_PALETTE};
+//! # use epd_waveshare::graphics::VarOctDisplay;
+//! # use embedded_graphics_core::pixelcolor::Rgb888;
+//! let (width, height) = (16, 8);
+//! let mut buffer = [0; 16 / 2 * 8];
+//! let mut display = VarOctDisplay::new(width, height, &mut buffer).unwrap();
+//!
+//! let mut line = [[0; 3]; line_buffer_len(16)];
+//! let mut ditherer = Ditherer::new(width, &mut line, &OCT_PALETTE).unwrap();
+//! for y in 0..height as i32 {
+//!     // e.g. read the next row of the image from a file
+//!     let row = [Rgb888::new(120, 140, 200); 16];
+//!     ditherer.draw_row(&mut display, y, &row).unwrap();
+//! }
+//! ```
+
+use crate::bitmap::{nearest, nearest_perceptual};
+use crate::color::{Color, OctColor, TriColor};
+use crate::frame::FrameDataError;
+use embedded_graphics_core::pixelcolor::{BinaryColor, Rgb888, RgbColor};
+use embedded_graphics_core::prelude::*;
+use embedded_graphics_core::primitives::Rectangle;
+
+/// The colors of a display with the rgb values they look like on the panel
+///
+/// Used for the nearest color matching of the [Ditherer], the
+/// [InPalette](crate::bitmap::InPalette) matcher and the
+/// previews of `dump::write_preview`. The crate ships the measured [OCT_PALETTE],
+/// [TRI_RED_PALETTE], [TRI_YELLOW_PALETTE] and [BW_PALETTE], panels which look different
+/// can use their own array instead:
+///
+/// ```rust
+/// # use epd_waveshare::{color::OctColor, dither::{Palette, OCT_PALETTE}};
+/// # use embedded_graphics_core::pixelcolor::Rgb888;
+/// let mut olive = OCT_PALETTE;
+/// olive[2] = (OctColor::Green, Rgb888::new(128, 128, 40));
+/// let palette: &Palette<OctColor> = &olive;
+/// ```
+pub type Palette<C> = [(C, Rgb888)];
+
+/// The rgb value of `color` in `palette`, `None` if it isn't one of the colors
+pub fn palette_rgb<C: PartialEq>(palette: &Palette<C>, color: C) -> Option<Rgb888> {
+    palette
+        .iter()
+        .find(|(entry, _)| *entry == color)
+        .map(|&(_, rgb)| rgb)
+}
+
+/// Colors of the 7 color ACeP displays as they look on the panel
+///
+/// Dithering against the colors of the panel instead of the ideal primaries keeps
+/// the picture from getting too dark and saturated. Built from [OctColor::panel_rgb].
+pub const OCT_PALETTE: [(OctColor, Rgb888); 7] = [
+    (OctColor::Black, rgb(OctColor::Black.panel_rgb())),
+    (OctColor::White, rgb(OctColor::White.panel_rgb())),
+    (OctColor::Green, rgb(OctColor::Green.panel_rgb())),
+    (OctColor::Blue, rgb(OctColor::Blue.panel_rgb())),
+    (OctColor::Red, rgb(OctColor::Red.panel_rgb())),
+    (OctColor::Yellow, rgb(OctColor::Yellow.panel_rgb())),
+    (OctColor::Orange, rgb(OctColor::Orange.panel_rgb())),
+];
+
+/// Colors of the tri-color displays with red as chromatic color, see [TriColor::panel_rgb]
+pub const TRI_RED_PALETTE: [(TriColor, Rgb888); 3] = [
+    (TriColor::Black, rgb(TriColor::Black.panel_rgb())),
+    (TriColor::White, rgb(TriColor::White.panel_rgb())),
+    (TriColor::Chromatic, rgb(TriColor::Chromatic.panel_rgb())),
+];
+
+/// Colors of the tri-color displays with yellow as chromatic color, see
+/// [TriColor::yellow_panel_rgb]
+pub const TRI_YELLOW_PALETTE: [(TriColor, Rgb888); 3] = [
+    (TriColor::Black, rgb(TriColor::Black.yellow_panel_rgb())),
+    (TriColor::White, rgb(TriColor::White.yellow_panel_rgb())),
+    (
+        TriColor::Chromatic,
+        rgb(TriColor::Chromatic.yellow_panel_rgb()),
+    ),
+];
+
+/// Colors of the b/w displays, see [Color::panel_rgb]
+pub const BW_PALETTE: [(Color, Rgb888); 2] = [
+    (Color::Black, rgb(Color::Black.panel_rgb())),
+    (Color::White, rgb(Color::White.panel_rgb())),
+];
+
+/// [BW_PALETTE] in `BinaryColor`, for the b/w [graphics displays](crate::graphics::Display)
+pub const BINARY_PALETTE: [(BinaryColor, Rgb888); 2] = [
+    (BinaryColor::On, rgb(Color::Black.panel_rgb())),
+    (BinaryColor::Off, rgb(Color::White.panel_rgb())),
+];
+
+const fn rgb((r, g, b): (u8, u8, u8)) -> Rgb888 {
+    Rgb888::new(r, g, b)
+}
+
+/// Accumulated errors of the red, green and blue channel of a pixel
+pub type ChannelErrors = [i16; 3];
+
+/// Length of the line buffer of a [Ditherer] for images `width` pixels wide
+pub const fn line_buffer_len(width: u32) -> usize {
+    2 * width as usize
+}
+
+/// How the nearest color of the palette is chosen
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum ColorDistance {
+    /// Euclidean distance of the rgb values
+    #[default]
+    Rgb,
+    /// CIE76 distance in L\*a\*b\*, closer to how different the colors look
+    ///
+    /// Converts every palette entry for every pixel, so it's slower.
+    Perceptual,
+}
+
+/// Dithers an rgb image row by row to the colors of a palette
+pub struct Ditherer<'a, C> {
+    width: u32,
+    line: &'a mut [ChannelErrors],
+    palette: &'a Palette<C>,
+    serpentine: bool,
+    distance: ColorDistance,
+    row: u32,
+}
+
+impl<'a, C: Copy> Ditherer<'a, C> {
+    /// Ditherer for images `width` pixels wide
+    ///
+    /// `line` keeps the errors for the current and the next row and needs
+    /// [line_buffer_len] entries.
+    pub fn new(
+        width: u32,
+        line: &'a mut [ChannelErrors],
+        palette: &'a Palette<C>,
+    ) -> Result<Self, FrameDataError> {
+        let expected = line_buffer_len(width);
+        if line.len() != expected {
+            return Err(FrameDataError::BufferLength {
+                expected,
+                actual: line.len(),
+            });
+        }
+        let mut ditherer = Ditherer {
+            width,
+            line,
+            palette,
+            serpentine: false,
+            distance: ColorDistance::Rgb,
+            row: 0,
+        };
+        ditherer.reset();
+        Ok(ditherer)
+    }
+
+    /// Go through every second row from right to left
+    ///
+    /// Avoids the diagonal patterns which can appear in larger areas of the same color.
+    pub fn set_serpentine(&mut self, serpentine: bool) {
+        self.serpentine = serpentine;
+    }
+
+    /// How the nearest color of the palette is chosen, [ColorDistance::Rgb] by default
+    pub fn set_distance(&mut self, distance: ColorDistance) {
+        self.distance = distance;
+    }
+
+    /// Forget the errors of the previous rows to start with a new image
+    pub fn reset(&mut self) {
+        self.line.fill([0; 3]);
+        self.row = 0;
+    }
+
+    /// Dithers the next row and hands every pixel to `put` with its x coordinate
+    ///
+    /// # Panics
+    ///
+    /// If `pixels` doesn't have exactly `width` pixels.
+    pub fn row<F: FnMut(u32, C)>(&mut self, pixels: &[Rgb888], mut put: F) {
+        let width = self.width as usize;
+        assert_eq!(pixels.len(), width, "row has to be `width` pixels long");
+
+        let reverse = self.serpentine && self.row % 2 == 1;
+        for i in 0..width {
+            let x = if reverse { width - 1 - i } else { i };
+            put(x as u32, self.pixel(x, pixels[x], reverse));
+        }
+        self.next_row();
+    }
+
+    // Dithers pixel `x` of the current row and spreads its error, `reverse` for a row
+    // going from right to left
+    fn pixel(&mut self, x: usize, pixel: Rgb888, reverse: bool) -> C {
+        let width = self.width as usize;
+        let (first, second) = self.line.split_at_mut(width);
+        let (current, next) = if self.row.is_multiple_of(2) {
+            (first, second)
+        } else {
+            (second, first)
+        };
+
+        let wanted = [
+            clamp(i16::from(pixel.r()) + current[x][0]),
+            clamp(i16::from(pixel.g()) + current[x][1]),
+            clamp(i16::from(pixel.b()) + current[x][2]),
+        ];
+        let wanted_rgb = Rgb888::new(wanted[0], wanted[1], wanted[2]);
+        let (color, rgb) = match self.distance {
+            ColorDistance::Rgb => nearest(wanted_rgb, self.palette),
+            ColorDistance::Perceptual => nearest_perceptual(wanted_rgb, self.palette),
+        };
+
+        let (ahead, behind) = if reverse {
+            (x.checked_sub(1), Some(x + 1).filter(|&x| x < width))
+        } else {
+            (Some(x + 1).filter(|&x| x < width), x.checked_sub(1))
+        };
+        let got = [rgb.r(), rgb.g(), rgb.b()];
+        for channel in 0..3 {
+            let error = i16::from(wanted[channel]) - i16::from(got[channel]);
+            if let Some(ahead) = ahead {
+                current[ahead][channel] += error * 7 / 16;
+                next[ahead][channel] += error / 16;
+            }
+            if let Some(behind) = behind {
+                next[behind][channel] += error * 3 / 16;
+            }
+            next[x][channel] += error * 5 / 16;
+        }
+        color
+    }
+
+    // Clears the errors of the finished row, it becomes the row after the next one
+    fn next_row(&mut self) {
+        let width = self.width as usize;
+        let (first, second) = self.line.split_at_mut(width);
+        if self.row.is_multiple_of(2) {
+            first.fill([0; 3]);
+        } else {
+            second.fill([0; 3]);
+        }
+        self.row += 1;
+    }
+
+    /// Dithers the next row and draws it at row `y` of `target`
+    pub fn draw_row<D>(&mut self, target: &mut D, y: i32, pixels: &[Rgb888]) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = C>,
+        C: PixelColor,
+    {
+        let mut result = Ok(());
+        self.row(pixels, |x, color| {
+            if result.is_ok() {
+                result = target.draw_iter(core::iter::once(Pixel(Point::new(x as i32, y), color)));
+            }
+        });
+        result
+    }
+}
+
+/// Dithers everything drawn in rgb into a display with few colors
+///
+/// Wraps a display buffer, e.g. a [VarOctDisplay](crate::graphics::VarOctDisplay), so
+/// gradients, photos or anti-aliased text from other crates can be drawn in [Rgb888]
+/// without converting them first:
+///
+/// ```rust
+/// # use epd_waveshare::dither::{line_buffer_len, DitherTarget, OCT_PALETTE};
+/// # use epd_waveshare::epd5in65f::{Display5in65f, WIDTH};
+/// # use embedded_graphics::{pixelcolor::Rgb888, prelude::*, primitives::Rectangle};
+/// let mut display = Display5in65f::default();
+/// let mut line = [[0; 3]; line_buffer_len(WIDTH)];
+/// let mut target = DitherTarget::new(&mut display, &mut line, &OCT_PALETTE).unwrap();
+///
+/// // a gray area becomes a pattern of black and white
+/// let area = Rectangle::new(Point::new(10, 10), Size::new(100, 50));
+/// target.fill_solid(&area, Rgb888::new(128, 128, 128)).unwrap();
+/// ```
+///
+/// Error diffusion needs the pixels in raster order, so only `fill_contiguous`,
+/// `fill_solid` and `clear` dither: each call is dithered on its own, as an image of the
+/// size of its area, starting without errors. Images drawn with embedded-graphics, e.g.
+/// `ImageRaw` or `tinybmp::Bmp`, go through `fill_contiguous`. Everything else, e.g.
+/// lines, circles or single pixels, arrives pixel by pixel in any order and gets the
+/// nearest color without dithering, as do areas wider than the display.
+pub struct DitherTarget<'a, D: DrawTarget> {
+    target: &'a mut D,
+    line: &'a mut [ChannelErrors],
+    palette: &'a Palette<D::Color>,
+    distance: ColorDistance,
+}
+
+impl<'a, D: DrawTarget> DitherTarget<'a, D> {
+    /// Dithers into `target` with the colors of `palette`
+    ///
+    /// `line` keeps the errors of two rows and needs [line_buffer_len] entries for the
+    /// width of `target`, as it's seen with its rotation.
+    pub fn new(
+        target: &'a mut D,
+        line: &'a mut [ChannelErrors],
+        palette: &'a Palette<D::Color>,
+    ) -> Result<Self, FrameDataError> {
+        let expected = line_buffer_len(target.bounding_box().size.width);
+        if line.len() != expected {
+            return Err(FrameDataError::BufferLength {
+                expected,
+                actual: line.len(),
+            });
+        }
+        Ok(DitherTarget {
+            target,
+            line,
+            palette,
+            distance: ColorDistance::Rgb,
+        })
+    }
+
+    /// How the nearest color of the palette is chosen, [ColorDistance::Rgb] by default
+    pub fn set_distance(&mut self, distance: ColorDistance) {
+        self.distance = distance;
+    }
+
+    /// The wrapped display, e.g. to draw in its own colors
+    pub fn target(&mut self) -> &mut D {
+        self.target
+    }
+}
+
+impl<'a, D: DrawTarget> Dimensions for DitherTarget<'a, D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+impl<'a, D: DrawTarget> DrawTarget for DitherTarget<'a, D> {
+    type Color = Rgb888;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (palette, distance) = (self.palette, self.distance);
+        self.target.draw_iter(
+            pixels
+                .into_iter()
+                .map(|Pixel(point, color)| Pixel(point, matching(color, palette, distance))),
+        )
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let width = area.size.width as usize;
+        let (palette, distance) = (self.palette, self.distance);
+        if width == 0 || line_buffer_len(width as u32) > self.line.len() {
+            let colors = colors.into_iter();
+            return self
+                .target
+                .fill_contiguous(area, colors.map(|color| matching(color, palette, distance)));
+        }
+        let mut ditherer = Ditherer {
+            width: width as u32,
+            line: &mut self.line[..line_buffer_len(width as u32)],
+            palette,
+            serpentine: false,
+            distance,
+            row: 0,
+        };
+        ditherer.reset();
+        let colors = colors.into_iter().enumerate().map(|(i, color)| {
+            let x = i % width;
+            let color = ditherer.pixel(x, color, false);
+            if x == width - 1 {
+                ditherer.next_row();
+            }
+            color
+        });
+        self.target.fill_contiguous(area, colors)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let pixels = area.size.width as usize * area.size.height as usize;
+        self.fill_contiguous(area, core::iter::repeat_n(color, pixels))
+    }
+}
+
+// The nearest color of `palette` without dithering
+fn matching<C: Copy>(color: Rgb888, palette: &Palette<C>, distance: ColorDistance) -> C {
+    match distance {
+        ColorDistance::Rgb => nearest(color, palette).0,
+        ColorDistance::Perceptual => nearest_perceptual(color, palette).0,
+    }
+}
+
+fn clamp(value: i16) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::graphics::{OctDisplay, VarOctDisplay};
+    use std::{string::String, vec, vec::Vec};
+
+    /// Horizontal gray and color gradients
+    fn gradient(width: u32, y: u32) -> Vec<Rgb888> {
+        (0..width)
+            .map(|x| {
+                let v = (x * 255 / (width - 1)) as u8;
+                match y % 3 {
+                    0 => Rgb888::new(v, v, v),
+                    1 => Rgb888::new(v, 255 - v, 128),
+                    _ => Rgb888::new(200, v / 2, 255 - v),
+                }
+            })
+            .collect()
+    }
+
+    /// Dithered gradients with every color as char
+    fn dither<C: Copy>(
+        palette: &[(C, Rgb888)],
+        serpentine: bool,
+        (width, height): (u32, u32),
+        name: impl Fn(C) -> char,
+    ) -> Vec<String> {
+        let mut line = vec![[0; 3]; line_buffer_len(width)];
+        let mut ditherer = Ditherer::new(width, &mut line, palette).unwrap();
+        ditherer.set_serpentine(serpentine);
+        (0..height)
+            .map(|y| {
+                let mut row = vec![' '; width as usize];
+                ditherer.row(&gradient(width, y), |x, c| row[x as usize] = name(c));
+                row.into_iter().collect()
+            })
+            .collect()
+    }
+
+    fn oct_name(color: OctColor) -> char {
+        char::from(b'0' + color.get_nibble())
+    }
+
+    fn tri_name(color: TriColor) -> char {
+        match color {
+            TriColor::Black => 'B',
+            TriColor::White => 'W',
+            TriColor::Chromatic => 'R',
+        }
+    }
+
+    fn bw_name(color: Color) -> char {
+        match color {
+            Color::Black => 'B',
+            Color::White => 'W',
+        }
+    }
+
+    // The fixtures were generated with an independent Floyd–Steinberg implementation
+    // using the same integer rounding, the oct digits are the nibbles of the colors
+
+    #[test]
+    fn oct_reference() {
+        let expected = [
+            "000000023426665161511111",
+            "222222252521333444444444",
+            "413141444444614664666666",
+            "000033333313441411111111",
+            "222222212133133444444444",
+            "413141344344441444666666",
+        ];
+        assert_eq!(dither(&OCT_PALETTE, false, (24, 6), oct_name), expected);
+    }
+
+    #[test]
+    fn oct_serpentine_reference() {
+        let expected = [
+            "000000023426665161511111",
+            "222222252213313344444444",
+            "413141441444644664666666",
+            "000033333333141411111111",
+            "222222212131334444444444",
+            "431441434444464644466666",
+        ];
+        assert_eq!(dither(&OCT_PALETTE, true, (24, 6), oct_name), expected);
+    }
+
+    #[test]
+    fn tri_and_bw_reference() {
+        let expected = ["BBBBBBRBWRWWWWWW", "BBBWBWBWBRBRRRRR", "WRWBWRWRWRWRRRRR"];
+        assert_eq!(dither(&TRI_RED_PALETTE, true, (16, 3), tri_name), expected);
+
+        let expected = ["BBBBBBBWBWBWWWWW", "BBBWBWBBWBWBBBBB", "WWBWBWBWBBBWBBWB"];
+        assert_eq!(dither(&BW_PALETTE, false, (16, 3), bw_name), expected);
+    }
+
+    #[test]
+    fn perceptual_distance() {
+        // a dark navy area is mostly black with the rgb distance and mostly blue in Lab
+        let navy = vec![Rgb888::new(20, 30, 80); 16];
+        let count = |distance| {
+            let mut line = vec![[0; 3]; line_buffer_len(16)];
+            let mut ditherer = Ditherer::new(16, &mut line, &OCT_PALETTE).unwrap();
+            ditherer.set_distance(distance);
+            let mut blue = 0;
+            for _ in 0..4 {
+                ditherer.row(&navy, |_, c| blue += (c == OctColor::Blue) as u32);
+            }
+            blue
+        };
+        assert!(count(ColorDistance::Rgb) < 32);
+        assert!(count(ColorDistance::Perceptual) > 32);
+    }
+
+    #[test]
+    fn custom_palette() {
+        // an olive green panel shows olive as green, the default palette mixes it
+        let olive = vec![Rgb888::new(128, 128, 40); 16];
+        let mut palette = OCT_PALETTE;
+        palette[2] = (OctColor::Green, Rgb888::new(128, 128, 40));
+        let green = |palette: &Palette<OctColor>| {
+            let mut line = vec![[0; 3]; line_buffer_len(16)];
+            let mut ditherer = Ditherer::new(16, &mut line, palette).unwrap();
+            let mut green = 0;
+            ditherer.row(&olive, |_, c| green += (c == OctColor::Green) as u32);
+            green
+        };
+        assert!(green(&OCT_PALETTE) < 16);
+        assert_eq!(green(&palette), 16);
+
+        assert_eq!(
+            palette_rgb(&palette, OctColor::Green),
+            Some(Rgb888::new(128, 128, 40))
+        );
+        assert_eq!(palette_rgb(&OCT_PALETTE, OctColor::HiZ), None);
+    }
+
+    #[test]
+    fn draw_rows_and_reset() {
+        let mut buffer = [0; 24 / 2 * 2];
+        let mut display = VarOctDisplay::new(24, 2, &mut buffer).unwrap();
+        let mut line = [[0; 3]; line_buffer_len(24)];
+        let mut ditherer = Ditherer::new(24, &mut line, &OCT_PALETTE).unwrap();
+
+        // the first row again after the reset
+        ditherer
+            .draw_row(&mut display, 0, &gradient(24, 0))
+            .unwrap();
+        ditherer.reset();
+        ditherer
+            .draw_row(&mut display, 1, &gradient(24, 0))
+            .unwrap();
+
+        let (first, second) = display.buffer().split_at(12);
+        assert_eq!(first, second);
+        assert_eq!(&first[..6], &[0x00, 0x00, 0x00, 0x02, 0x34, 0x26],);
+    }
+
+    #[test]
+    fn dither_target() {
+        use embedded_graphics_core::primitives::Rectangle;
+
+        // an area filled in raster order is dithered like rows of a Ditherer
+        let rows: Vec<Rgb888> = (0..4).flat_map(|y| gradient(24, y)).collect();
+        let mut expected = [0; 24 / 2 * 4];
+        let mut display = VarOctDisplay::new(24, 4, &mut expected).unwrap();
+        let mut line = [[0; 3]; line_buffer_len(24)];
+        let mut ditherer = Ditherer::new(24, &mut line, &OCT_PALETTE).unwrap();
+        for (y, row) in rows.chunks(24).enumerate() {
+            ditherer.draw_row(&mut display, y as i32, row).unwrap();
+        }
+
+        let mut buffer = [0; 24 / 2 * 4];
+        let mut display = VarOctDisplay::new(24, 4, &mut buffer).unwrap();
+        let mut target = DitherTarget::new(&mut display, &mut line, &OCT_PALETTE).unwrap();
+        let area = Rectangle::new(Point::zero(), Size::new(24, 4));
+        target.fill_contiguous(&area, rows.iter().copied()).unwrap();
+        assert_eq!(buffer, expected);
+
+        // single pixels get the nearest color, solid areas are dithered
+        // closer to the white than to the dark gray black of the panel
+        let gray = Rgb888::new(160, 160, 160);
+        let mut buffer = [0x00; 4];
+        let mut display = crate::graphics::VarDisplay::new(8, 4, &mut buffer).unwrap();
+        let mut line = [[0; 3]; line_buffer_len(8)];
+        let mut target = DitherTarget::new(&mut display, &mut line, &BINARY_PALETTE).unwrap();
+        target.draw_iter([Pixel(Point::new(0, 0), gray)]).unwrap();
+        target.draw_iter([Pixel(Point::new(1, 0), gray)]).unwrap();
+        assert_eq!(buffer[0], 0xc0);
+
+        let mut display = crate::graphics::VarDisplay::new(8, 4, &mut buffer).unwrap();
+        let mut target = DitherTarget::new(&mut display, &mut line, &BINARY_PALETTE).unwrap();
+        target.clear(gray).unwrap();
+        let white: u32 = buffer.iter().map(|byte| byte.count_ones()).sum();
+        assert!((8..24).contains(&white), "{} white pixels", white);
+
+        // areas wider than the line buffer fall back to the nearest color
+        let mut display = crate::graphics::VarDisplay::new(8, 4, &mut buffer).unwrap();
+        let mut target = DitherTarget::new(&mut display, &mut line, &BINARY_PALETTE).unwrap();
+        let area = Rectangle::new(Point::new(-4, 0), Size::new(16, 1));
+        target.fill_solid(&area, gray).unwrap();
+        assert_eq!(buffer[0], 0xff);
+
+        let mut display = crate::graphics::VarDisplay::new(8, 4, &mut buffer).unwrap();
+        let mut line = [[0; 3]; line_buffer_len(4)];
+        assert_eq!(
+            DitherTarget::new(&mut display, &mut line, &BINARY_PALETTE).err(),
+            Some(FrameDataError::BufferLength {
+                expected: 16,
+                actual: 8
+            })
+        );
+    }
+
+    #[test]
+    fn line_buffer_length() {
+        let mut line = [[0; 3]; 10];
+        assert_eq!(
+            Ditherer::new(4, &mut line, &BINARY_PALETTE).err(),
+            Some(FrameDataError::BufferLength {
+                expected: 8,
+                actual: 10
+            })
+        );
+    }
+}
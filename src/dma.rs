@@ -0,0 +1,167 @@
+//! Sending the frame data with the DMA of the spi peripheral
+//!
+//! The drivers only need [Write] from the bus. With
+//! [ChunkWriter] every write of a driver goes to a closure instead, which can hand it to the
+//! DMA api of the hal, while the driver still takes care of CS, DC and the commands. CS is
+//! asserted and DC set while the closure runs, so it has to wait for the transfer to finish
+//! before it returns.
+//!
+//! # How the frame data is sliced
+//!
+//! The buffer of [update_frame](crate::traits::WaveshareDisplay::update_frame),
+//! [StreamFrame::frame_data](crate::traits::StreamFrame::frame_data) and the other frame
+//! functions reaches the bus without a copy on the drivers whose controller takes the bits
+//! like the buffers, i.e. whose [POLARITY](crate::traits::WaveshareDisplay::POLARITY) is
+//! [ClearedIsColor](crate::color::Polarity::ClearedIsColor) and which don't expand the
+//! pixels (all but the 2in7b, 7in5 and 7in5 V2, and the SSD16xx drivers with a
+//! [HardwareFlip](crate::prelude::HardwareFlip) which mirrors x):
+//!
+//! - the writes are sub-slices of the buffer, in order and without gaps,
+//! - which are only split where [set_data_delay](crate::traits::WaveshareDisplay::set_data_delay)
+//!   pauses, [set_cs_release](crate::traits::WaveshareDisplay::set_cs_release) releases CS
+//!   or [set_progress](crate::traits::WaveshareDisplay::set_progress) reports, every
+//!   `chunk_size` bytes of these, and every 4096 bytes on linux,
+//! - so without any of these the whole buffer is a single write off linux.
+//!
+//! Everything else, e.g. the commands, the background of padded or cleared frames and the
+//! flipped or expanded data of the other drivers, is written from short buffers of the
+//! driver. [chunk_range] tells the two apart, so the buffer itself can be placed where the
+//! DMA can read it, e.g. in a `static`, and be sent from there:
+//!
+//! ```rust, ignore
+//! let frame: &'static mut [u8] = cortex_m::singleton!(: [u8; 15000] = [0xff; 15000]).unwrap();
+//! let mut display = VarDisplay::new(epd4in2::WIDTH, epd4in2::HEIGHT, frame).unwrap();
+//! // draw ...
+//! let frame: &'static [u8] = display.into_buffer();
+//!
+//! let mut spi = ChunkWriter::new(|data: &[u8]| match chunk_range(frame, data) {
+//!     // a part of the frame, sent by the dma straight from the static buffer
+//!     Some(range) => dma_write_and_wait(&frame[range]),
+//!     // a command or a few bytes of the driver
+//!     None => blocking_write(data),
+//! });
+//! epd.update_frame(&mut spi, frame, &mut delay)?;
+//! ```
+
+use core::ops::Range;
+use embedded_hal::blocking::spi::Write;
+
+/// Spi bus which hands every write to a closure, e.g. the DMA api of the hal
+///
+/// The closure gets the bytes of one write while CS is asserted and DC set, see the
+/// [module documentation](self).
+pub struct ChunkWriter<F> {
+    write: F,
+}
+
+impl<F> ChunkWriter<F> {
+    /// Sends every write of the drivers with `write`
+    pub fn new(write: F) -> Self {
+        ChunkWriter { write }
+    }
+
+    /// Returns the closure again
+    pub fn into_inner(self) -> F {
+        self.write
+    }
+}
+
+impl<F, E> Write<u8> for ChunkWriter<F>
+where
+    F: FnMut(&[u8]) -> Result<(), E>,
+{
+    type Error = E;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        (self.write)(words)
+    }
+}
+
+/// Where `chunk` lies in `buffer`, `None` if it isn't a part of it
+///
+/// Tells the writes of a frame which are slices of its buffer from the bytes of the driver.
+pub fn chunk_range(buffer: &[u8], chunk: &[u8]) -> Option<Range<usize>> {
+    let start = (chunk.as_ptr() as usize).checked_sub(buffer.as_ptr() as usize)?;
+    let end = start + chunk.len();
+    if chunk.is_empty() || end > buffer.len() {
+        return None;
+    }
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::epd2in7b::{self, Epd2in7b};
+    use crate::epd4in2::{self, Epd4in2};
+    use crate::mock::{BusyPin, Delay, Pin};
+    use crate::traits::WaveshareDisplay;
+    use core::cell::RefCell;
+    use core::convert::Infallible;
+    use std::boxed::Box;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    #[test]
+    fn ranges_in_the_buffer() {
+        let buffer = [0u8; 10];
+        assert_eq!(chunk_range(&buffer, &buffer), Some(0..10));
+        assert_eq!(chunk_range(&buffer, &buffer[3..7]), Some(3..7));
+        assert_eq!(chunk_range(&buffer, &buffer[3..3]), None);
+        let other = [0u8; 10];
+        assert_eq!(chunk_range(&buffer[2..], &buffer[..4]), None);
+        assert_eq!(chunk_range(&buffer, &other), None);
+    }
+
+    type Recorder<'b> = ChunkWriter<Box<dyn FnMut(&[u8]) -> Result<(), Infallible> + 'b>>;
+
+    // The ranges of `buffer` which update_frame of `E` writes, in order
+    fn frame_writes<'b, E>(buffer: &'b [u8], chunk_size: Option<usize>) -> Vec<Range<usize>>
+    where
+        E: WaveshareDisplay<Recorder<'b>, Pin, BusyPin, Pin, Pin, Delay>,
+    {
+        let writes = Rc::new(RefCell::new(Vec::new()));
+        let recorded = writes.clone();
+        let mut spi: Recorder<'b> = ChunkWriter::new(Box::new(move |data: &[u8]| {
+            if let Some(range) = chunk_range(buffer, data) {
+                recorded.borrow_mut().push(range);
+            }
+            Ok(())
+        }));
+        let mut delay = Delay::new();
+        let mut epd = E::new(Pin, BusyPin::default(), Pin, Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+        epd.set_cs_release(chunk_size);
+        epd.update_frame(&mut spi, buffer, &mut delay).unwrap();
+        writes.take()
+    }
+
+    #[test]
+    fn frames_are_written_from_the_buffer() {
+        let buffer = [0x0f; epd4in2::buffer_len()];
+        let single = if cfg!(target_os = "linux") {
+            (0..buffer.len())
+                .step_by(4096)
+                .map(|start| start..(start + 4096).min(buffer.len()))
+                .collect()
+        } else {
+            std::vec![0..buffer.len()]
+        };
+        assert_eq!(
+            frame_writes::<Epd4in2<_, _, _, _, _, _>>(&buffer, None),
+            single
+        );
+
+        // split into the chunks of the cs release
+        let chunks = frame_writes::<Epd4in2<_, _, _, _, _, _>>(&buffer, Some(1000));
+        assert_eq!(chunks.len(), buffer.len().div_ceil(1000));
+        for (i, range) in chunks.iter().enumerate() {
+            assert_eq!(*range, i * 1000..((i + 1) * 1000).min(buffer.len()));
+        }
+
+        // the flipped frame of the 2in7b is a copy
+        let buffer = [0x0f; epd2in7b::buffer_len()];
+        assert!(frame_writes::<Epd2in7b<_, _, _, _, _, _>>(&buffer, None).is_empty());
+    }
+}
@@ -0,0 +1,442 @@
+//! Double buffered b/w display which only sends what changed
+//!
+//! [DoubleBufferedDisplay] keeps a copy of the frame that was sent last. On
+//! [flush](DoubleBufferedDisplay::flush) only the rectangle around all changed bytes
+//! is sent with a partial update, or the full frame if that rectangle gets too big.
+
+use crate::buffer_len;
+use crate::error::Error;
+use crate::frame::FrameDataError;
+use crate::graphics::{Display, DisplayMirroring, DisplayRotation};
+use crate::traits::{QuickRefresh, WaveshareDisplay};
+use embedded_graphics_core::pixelcolor::BinaryColor;
+use embedded_graphics_core::prelude::*;
+use embedded_graphics_core::primitives::Rectangle;
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::*,
+};
+
+/// Default of [DoubleBufferedDisplay::set_full_update_threshold]
+pub const DEFAULT_FULL_UPDATE_THRESHOLD: u8 = 50;
+
+/// A b/w display with a second buffer holding the frame which is on the screen
+///
+/// Drawing works like with [VarDisplay](crate::graphics::VarDisplay). Both buffers
+/// need to be (width + 7) / 8 * height bytes long. The first flush always sends the
+/// full frame, because it isn't known what is on the screen.
+///
+/// Changed areas are in unrotated display coordinates with x and width rounded to
+/// whole bytes, so they can be sent as they are, independent of the rotation.
+pub struct DoubleBufferedDisplay<'a> {
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    inverted: bool,
+    buffer: &'a mut [u8],
+    /// Frame on the screen, only valid if `on_screen` is set
+    previous: &'a mut [u8],
+    on_screen: bool,
+    full_update_threshold: u8,
+}
+
+impl<'a> DoubleBufferedDisplay<'a> {
+    /// Create a new double buffered display, the content of `previous` doesn't matter
+    ///
+    /// Fails if one of the buffers doesn't have exactly the needed length.
+    pub fn new(
+        width: u32,
+        height: u32,
+        buffer: &'a mut [u8],
+        previous: &'a mut [u8],
+    ) -> Result<DoubleBufferedDisplay<'a>, FrameDataError> {
+        let expected = buffer_len(width as usize, height as usize);
+        for len in [buffer.len(), previous.len()].iter() {
+            if *len != expected {
+                return Err(FrameDataError::BufferLength {
+                    expected,
+                    actual: *len,
+                });
+            }
+        }
+        Ok(DoubleBufferedDisplay {
+            width,
+            height,
+            rotation: DisplayRotation::default(),
+            mirroring: DisplayMirroring::default(),
+            inverted: false,
+            buffer,
+            previous,
+            on_screen: false,
+            full_update_threshold: DEFAULT_FULL_UPDATE_THRESHOLD,
+        })
+    }
+
+    /// Send the full frame once the changed area covers more than `percent` of the display
+    ///
+    /// Many small partial updates leave ghosting behind, a full update cleans it up.
+    /// With 100 partial updates are always used, with 0 always full updates.
+    pub fn set_full_update_threshold(&mut self, percent: u8) {
+        self.full_update_threshold = percent.min(100);
+    }
+
+    /// Forget what is on the screen, so the next flush sends the full frame
+    pub fn invalidate(&mut self) {
+        self.on_screen = false;
+    }
+
+    /// The rectangle around all changes since the last flush
+    ///
+    /// `None` if nothing changed and the whole display before the first flush.
+    pub fn changed_area(&self) -> Option<Rectangle> {
+        if !self.on_screen {
+            return Some(self.full_area());
+        }
+        let stride = self.stride();
+        let mut columns = (stride, 0);
+        let mut rows = (self.height, 0);
+        for (row, (new, old)) in self
+            .buffer
+            .chunks(stride as usize)
+            .zip(self.previous.chunks(stride as usize))
+            .enumerate()
+        {
+            let changed = new.iter().zip(old.iter()).map(|(n, o)| n != o);
+            let (mut first, mut last) = (None, 0);
+            for (column, _) in changed.enumerate().filter(|&(_, c)| c) {
+                first.get_or_insert(column as u32);
+                last = column as u32;
+            }
+            if let Some(first) = first {
+                columns = (columns.0.min(first), columns.1.max(last));
+                rows = (rows.0.min(row as u32), row as u32);
+            }
+        }
+        if rows.0 > rows.1 {
+            return None;
+        }
+        let x = columns.0 * 8;
+        let width = ((columns.1 + 1) * 8).min(self.width) - x;
+        Some(Rectangle::new(
+            Point::new(x as i32, rows.0 as i32),
+            Size::new(width, rows.1 - rows.0 + 1),
+        ))
+    }
+
+    /// Send the changes since the last flush and display them
+    ///
+    /// Doesn't talk to the display at all if nothing changed.
+    pub fn flush<SPI, CS, BUSY, DC, RST, DELAY, EPD>(
+        &mut self,
+        epd: &mut EPD,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: Write<u8>,
+        CS: OutputPin,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayMs<u8>,
+        EPD: WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>,
+    {
+        let area = match self.changed_area() {
+            Some(area) => area,
+            None => return Ok(()),
+        };
+        let full_update = self.needs_full_update(&area);
+        // the previous frame gets overwritten below, so it's unknown until the end
+        self.on_screen = false;
+        if full_update {
+            epd.update_frame(spi, self.buffer, delay)?;
+        } else {
+            let len = Self::pack_window(self.buffer, self.previous, self.stride(), &area);
+            let (x, y, width, height) = window(&area);
+            epd.update_partial_frame(spi, &self.previous[..len], x, y, width, height)?;
+        }
+        epd.display_frame(spi, delay)?;
+        self.mark_on_screen();
+        Ok(())
+    }
+
+    /// Like [flush](DoubleBufferedDisplay::flush), but with the quick refresh of the display
+    ///
+    /// Sends the old content of the changed area too, which the quick refresh needs.
+    pub fn flush_quick<SPI, CS, BUSY, DC, RST, DELAY, EPD>(
+        &mut self,
+        epd: &mut EPD,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: Write<u8>,
+        CS: OutputPin,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayMs<u8>,
+        EPD: WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
+            + QuickRefresh<SPI, CS, BUSY, DC, RST, DELAY>,
+    {
+        let area = match self.changed_area() {
+            Some(area) => area,
+            None => return Ok(()),
+        };
+        let full_update = self.needs_full_update(&area);
+        self.on_screen = false;
+        if full_update {
+            epd.update_frame(spi, self.buffer, delay)?;
+            epd.display_frame(spi, delay)?;
+        } else {
+            let stride = self.stride();
+            let (x, y, width, height) = window(&area);
+            // the old window is packed in place, the new one overwrites it afterwards
+            let len = Self::pack_window_in_place(self.previous, stride, &area);
+            epd.update_partial_old_frame(spi, &self.previous[..len], x, y, width, height)?;
+            let len = Self::pack_window(self.buffer, self.previous, stride, &area);
+            epd.update_partial_new_frame(spi, &self.previous[..len], x, y, width, height)?;
+            epd.display_new_frame(spi, delay)?;
+        }
+        self.mark_on_screen();
+        Ok(())
+    }
+
+    fn stride(&self) -> u32 {
+        self.width.div_ceil(8)
+    }
+
+    fn full_area(&self) -> Rectangle {
+        Rectangle::new(Point::zero(), Size::new(self.width, self.height))
+    }
+
+    fn needs_full_update(&self, area: &Rectangle) -> bool {
+        if !self.on_screen {
+            return true;
+        }
+        let changed = area.size.width.div_ceil(8) * area.size.height;
+        changed * 100 > u32::from(self.full_update_threshold) * self.buffer.len() as u32
+    }
+
+    fn mark_on_screen(&mut self) {
+        self.previous.copy_from_slice(self.buffer);
+        self.on_screen = true;
+    }
+
+    /// Copies the bytes of `area` in `from` to the start of `to`, returns their length
+    fn pack_window(from: &[u8], to: &mut [u8], stride: u32, area: &Rectangle) -> usize {
+        let (start, row_len, rows) = window_rows(stride, area);
+        for row in 0..rows {
+            let src = start + row * stride as usize;
+            to[row * row_len..(row + 1) * row_len].copy_from_slice(&from[src..src + row_len]);
+        }
+        row_len * rows
+    }
+
+    /// Like `pack_window` with the same buffer, the window never lies before its packed copy
+    fn pack_window_in_place(buffer: &mut [u8], stride: u32, area: &Rectangle) -> usize {
+        let (start, row_len, rows) = window_rows(stride, area);
+        for row in 0..rows {
+            let src = start + row * stride as usize;
+            buffer.copy_within(src..src + row_len, row * row_len);
+        }
+        row_len * rows
+    }
+}
+
+fn window(area: &Rectangle) -> (u32, u32, u32, u32) {
+    (
+        area.top_left.x as u32,
+        area.top_left.y as u32,
+        area.size.width,
+        area.size.height,
+    )
+}
+
+// Offset of the first byte, bytes per row and number of rows of the window
+fn window_rows(stride: u32, area: &Rectangle) -> (usize, usize, usize) {
+    let (x, y, width, height) = window(area);
+    (
+        (y * stride + x / 8) as usize,
+        width.div_ceil(8) as usize,
+        height as usize,
+    )
+}
+
+impl<'a> DrawTarget for DoubleBufferedDisplay<'a> {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper(self.width, self.height, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper(self.width, self.height, area, color)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.fill_contiguous_helper(self.width, self.height, area, colors)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color.into());
+        Ok(())
+    }
+}
+
+impl<'a> OriginDimensions for DoubleBufferedDisplay<'a> {
+    fn size(&self) -> Size {
+        self.rotation.rotated_size(self.width, self.height)
+    }
+}
+
+impl<'a> Display for DoubleBufferedDisplay<'a> {
+    fn buffer(&self) -> &[u8] {
+        self.buffer
+    }
+
+    fn get_mut_buffer(&mut self) -> &mut [u8] {
+        self.buffer
+    }
+
+    fn set_rotation(&mut self, rotation: DisplayRotation) {
+        self.rotation = rotation;
+    }
+
+    fn rotation(&self) -> DisplayRotation {
+        self.rotation
+    }
+
+    fn set_mirroring(&mut self, mirroring: DisplayMirroring) {
+        self.mirroring = mirroring;
+    }
+
+    fn mirroring(&self) -> DisplayMirroring {
+        self.mirroring
+    }
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    fn inverted(&self) -> bool {
+        self.inverted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{Black, Color};
+    use crate::epd4in2::{command::Command, Epd4in2, HEIGHT, WIDTH};
+    use crate::mock::init_epd;
+    use crate::traits::Command as _;
+
+    const LEN: usize = buffer_len(WIDTH as usize, HEIGHT as usize);
+
+    #[test]
+    fn changed_area() {
+        let mut buffer = [Color::White.get_byte_value(); buffer_len(20, 10)];
+        let mut previous = [0; buffer_len(20, 10)];
+        assert!(DoubleBufferedDisplay::new(20, 10, &mut buffer, &mut previous[..29]).is_err());
+        let mut display = DoubleBufferedDisplay::new(20, 10, &mut buffer, &mut previous).unwrap();
+
+        // everything before the first flush
+        assert_eq!(
+            display.changed_area(),
+            Some(Rectangle::new(Point::zero(), Size::new(20, 10)))
+        );
+        display.mark_on_screen();
+        assert_eq!(display.changed_area(), None);
+
+        // rotated by 90 degrees (2, 3) is the unrotated pixel (16, 2), in the last byte of the row
+        display.set_rotation(DisplayRotation::Rotate90);
+        Pixel(Point::new(2, 3), Black).draw(&mut display).unwrap();
+        assert_eq!(
+            display.changed_area(),
+            Some(Rectangle::new(Point::new(16, 2), Size::new(4, 1)))
+        );
+        display.set_rotation(DisplayRotation::Rotate0);
+        Pixel(Point::new(3, 7), Black).draw(&mut display).unwrap();
+        assert_eq!(
+            display.changed_area(),
+            Some(Rectangle::new(Point::new(0, 2), Size::new(20, 6)))
+        );
+    }
+
+    #[test]
+    fn flush() {
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+
+        let mut buffer = [Color::White.get_byte_value(); LEN];
+        let mut previous = [0; LEN];
+        let mut display =
+            DoubleBufferedDisplay::new(WIDTH, HEIGHT, &mut buffer, &mut previous).unwrap();
+
+        log.clear();
+        display.flush(&mut epd, &mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PartialWindow.address()), 0);
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 1);
+
+        // nothing changed
+        log.clear();
+        display.flush(&mut epd, &mut spi, &mut delay).unwrap();
+        assert!(log.transfers().is_empty());
+
+        log.clear();
+        Pixel(Point::new(9, 1), Black).draw(&mut display).unwrap();
+        display.flush(&mut epd, &mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PartialWindow.address()), 1);
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 1);
+        assert_eq!(
+            log.data_after(Command::DataStartTransmission2.address()),
+            [!0x40]
+        );
+
+        // above the threshold it's a full update again
+        log.clear();
+        display.set_full_update_threshold(10);
+        display.clear_buffer(Color::Black);
+        display.flush(&mut epd, &mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PartialWindow.address()), 0);
+        assert_eq!(display.changed_area(), None);
+    }
+
+    #[test]
+    fn flush_quick() {
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+
+        let mut buffer = [Color::White.get_byte_value(); LEN];
+        let mut previous = [0; LEN];
+        let mut display =
+            DoubleBufferedDisplay::new(WIDTH, HEIGHT, &mut buffer, &mut previous).unwrap();
+        display.flush_quick(&mut epd, &mut spi, &mut delay).unwrap();
+
+        let rows = [(0, 20), (8, 20), (0, 21), (8, 21)];
+        for &(x, y) in rows.iter() {
+            Pixel(Point::new(x, y), Black).draw(&mut display).unwrap();
+        }
+        log.clear();
+        display.flush_quick(&mut epd, &mut spi, &mut delay).unwrap();
+
+        // old and new content of the 16x2 window
+        assert!(log
+            .data_after(Command::DataStartTransmission1.address())
+            .starts_with(&[0xff; 4]));
+        assert!(log
+            .data_after(Command::DataStartTransmission2.address())
+            .starts_with(&[0x7f; 4]));
+        assert_eq!(display.changed_area(), None);
+    }
+}
@@ -0,0 +1,591 @@
+//! Dumps of frames as Netpbm images for debugging on std targets
+//!
+//! Shows what really ends up in a buffer, independent of the display: b/w frames become
+//! PBM, gray frames PGM, tri-color and 7 color frames PPM with the colors of
+//! [TriColor::panel_rgb] and [OctColor::panel_rgb]. Most image viewers open these files and [read]
+//! loads them back into a frame, e.g. to compare against a fixture in a test.
+//!
+//! The padding bits of the rows are left out of the images and white when loading.
+//!
+//! With the `graphics` feature [write_preview] shows a frame with the colors of a
+//! [Palette], e.g. the measured colors of the panel the dithering used.
+//!
+//! Example:
+//! ```rust,no_run
+//! # use epd_waveshare::{dump, frame::FrameData};
+//! # use epd_waveshare::epd4in2::{Display4in2, HEIGHT, WIDTH};
+//! # use epd_waveshare::prelude::*;
+//! let display = Display4in2::default();
+//! let frame = FrameData::new(WIDTH, HEIGHT, 1, display.buffer()).unwrap();
+//! dump::save("frame.pbm", &frame).unwrap();
+//!
+//! let loaded = dump::load("frame.pbm", 1).unwrap();
+//! assert_eq!(loaded.data(), display.buffer());
+//! ```
+
+use crate::buffer_len;
+#[cfg(feature = "graphics")]
+use crate::color::ColorType;
+use crate::color::{OctColor, TriColor};
+#[cfg(feature = "graphics")]
+use crate::dither::{palette_rgb, Palette};
+use crate::frame::FrameData;
+use crate::gray;
+#[cfg(feature = "graphics")]
+use embedded_graphics_core::pixelcolor::RgbColor;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::vec;
+use std::vec::Vec;
+
+/// Frame loaded from a dump
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedFrame {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl LoadedFrame {
+    /// Width of the frame in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the frame in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The raw frame data in the layout of the display buffers
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Takes the raw frame data
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Writes `frame` as PBM (1 bit per pixel) or PPM (tri-color and 7 color)
+///
+/// Fails with `InvalidData` for nibbles of a 7 color frame which aren't a color.
+pub fn write<W: Write>(out: W, frame: &FrameData) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    let (width, height) = (frame.width() as usize, frame.height() as usize);
+    let data = frame.data();
+    match frame.bits_per_pixel() {
+        1 => {
+            write!(out, "P4\n{} {}\n", width, height)?;
+            // same rows, but a set bit is black in PBM and white in the buffer
+            let inverted: Vec<u8> = data.iter().map(|byte| !byte).collect();
+            out.write_all(&inverted)?;
+        }
+        2 => {
+            write!(out, "P6\n{} {}\n255\n", width, height)?;
+            let (bw, chromatic) = data.split_at(buffer_len(width, height));
+            for y in 0..height {
+                for x in 0..width {
+                    let color =
+                        TriColor::from_bits(bit(bw, width, x, y), bit(chromatic, width, x, y));
+                    let (r, g, b) = color.panel_rgb();
+                    out.write_all(&[r, g, b])?;
+                }
+            }
+        }
+        _ => {
+            write!(out, "P6\n{} {}\n255\n", width, height)?;
+            let stride = width.div_ceil(2);
+            for y in 0..height {
+                for x in 0..width {
+                    let (r, g, b) = oct_pixel(data[y * stride + x / 2], x)
+                        .ok_or_else(|| invalid("nibble isn't a color"))?
+                        .panel_rgb();
+                    out.write_all(&[r, g, b])?;
+                }
+            }
+        }
+    }
+    out.flush()
+}
+
+/// Writes `frame` as PPM with the colors of `palette`, a preview of how it looks on the panel
+///
+/// Unlike [write](fn@write) the colors can't be loaded back, but they are the same the
+/// [dithering](crate::dither::Ditherer) used, e.g. the measured colors of the panel.
+/// Fails with `InvalidInput` if `frame` doesn't have the bits per pixel of `C` and with
+/// `InvalidData` for pixels whose color isn't in `palette`.
+#[cfg(feature = "graphics")]
+pub fn write_preview<W: Write, C: ColorType>(
+    out: W,
+    frame: &FrameData,
+    palette: &Palette<C>,
+) -> io::Result<()> {
+    if usize::from(frame.bits_per_pixel()) != C::BITS_PER_PIXEL {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "frame doesn't have the bits per pixel of the color",
+        ));
+    }
+    let mut out = BufWriter::new(out);
+    let (width, height) = (frame.width(), frame.height());
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    for y in 0..height {
+        for x in 0..width {
+            let color = C::read_pixel(frame.data(), width, height, x, y)
+                .ok_or_else(|| invalid("nibble isn't a color"))?;
+            let rgb =
+                palette_rgb(palette, color).ok_or_else(|| invalid("color isn't in the palette"))?;
+            out.write_all(&[rgb.r(), rgb.g(), rgb.b()])?;
+        }
+    }
+    out.flush()
+}
+
+/// Writes a 4 level gray frame (see [gray]) as PGM
+///
+/// Fails with `InvalidInput` if `buffer` doesn't have the length of a gray frame.
+pub fn write_gray<W: Write>(out: W, width: u32, height: u32, buffer: &[u8]) -> io::Result<()> {
+    let (width, height) = (width as usize, height as usize);
+    if buffer.len() != gray::buffer_size(width, height) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "buffer doesn't have the length of a gray frame",
+        ));
+    }
+    let mut out = BufWriter::new(out);
+    write!(out, "P5\n{} {}\n3\n", width, height)?;
+    let stride = width.div_ceil(4);
+    for y in 0..height {
+        for x in 0..width {
+            out.write_all(&[(buffer[y * stride + x / 4] >> (6 - 2 * (x % 4))) & 0b11])?;
+        }
+    }
+    out.flush()
+}
+
+/// Loads a frame with `bits_per_pixel` written by [write](fn@write)
+///
+/// PBM for 1 bit per pixel, PPM with exactly the colors [write](fn@write) uses for 2 and 4.
+/// Fails with `InvalidData` for other files or colors.
+pub fn read<R: Read>(input: R, bits_per_pixel: u8) -> io::Result<LoadedFrame> {
+    let mut input = BufReader::new(input);
+    let magic = match bits_per_pixel {
+        1 => b"P4",
+        2 | 4 => b"P6",
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only 1, 2 and 4 bits per pixel are supported",
+            ))
+        }
+    };
+    let (width, height) = header(&mut input, magic)?;
+    let (w, h) = (width as usize, height as usize);
+
+    let data = match bits_per_pixel {
+        1 => {
+            let mut image = vec![0; buffer_len(w, h)];
+            input.read_exact(&mut image)?;
+            let pixels = (0..h).flat_map(|y| (0..w).map(move |x| (x, y)));
+            let white = pixels.map(|(x, y)| !bit(&image, w, x, y));
+            rows(white, w, 8, |white| white as u8)
+        }
+        _ => {
+            let scale = maxval(&mut input)?;
+            let mut image = vec![0; 3 * w * h];
+            input.read_exact(&mut image)?;
+            let colors = image
+                .chunks(3)
+                .map(|rgb| (scale(rgb[0]), scale(rgb[1]), scale(rgb[2])));
+            if bits_per_pixel == 2 {
+                let mut pixels = Vec::with_capacity(w * h);
+                for rgb in colors {
+                    pixels.push(match rgb {
+                        rgb if rgb == TriColor::Chromatic.panel_rgb() => (true, false),
+                        rgb if rgb == TriColor::White.panel_rgb() => (true, true),
+                        rgb if rgb == TriColor::Black.panel_rgb() => (false, true),
+                        _ => return Err(invalid("color isn't black, white or red")),
+                    });
+                }
+                let mut data = rows(pixels.iter().map(|p| p.0), w, 8, |set| set as u8);
+                data.extend(rows(pixels.iter().map(|p| p.1), w, 8, |set| set as u8));
+                data
+            } else {
+                let mut pixels = Vec::with_capacity(w * h);
+                for rgb in colors {
+                    let color = (0..8)
+                        .filter_map(|nibble| OctColor::from_nibble(nibble).ok())
+                        .find(|color| color.panel_rgb() == rgb)
+                        .ok_or_else(|| invalid("color isn't one of the 7 colors"))?;
+                    pixels.push(color);
+                }
+                rows(pixels.into_iter(), w, 2, OctColor::get_nibble)
+            }
+        }
+    };
+    Ok(LoadedFrame {
+        width,
+        height,
+        data,
+    })
+}
+
+/// Loads a 4 level gray frame from a PGM, the levels are scaled to 0 to 3
+pub fn read_gray<R: Read>(input: R) -> io::Result<LoadedFrame> {
+    let mut input = BufReader::new(input);
+    let (width, height) = header(&mut input, b"P5")?;
+    let max = number(&mut input)?;
+    if max == 0 || max > 255 {
+        return Err(invalid("only 8 bit samples are supported"));
+    }
+    let (w, h) = (width as usize, height as usize);
+    let mut image = vec![0; w * h];
+    input.read_exact(&mut image)?;
+    let levels = image
+        .iter()
+        .map(|&v| ((u32::from(v) * 3 + max / 2) / max).min(3) as u8);
+    Ok(LoadedFrame {
+        width,
+        height,
+        data: rows(levels, w, 4, |level| level),
+    })
+}
+
+/// Writes `frame` to the file at `path`, see [write](fn@write)
+pub fn save<P: AsRef<Path>>(path: P, frame: &FrameData) -> io::Result<()> {
+    write(File::create(path)?, frame)
+}
+
+/// Writes a preview of `frame` to the file at `path`, see [write_preview]
+#[cfg(feature = "graphics")]
+pub fn save_preview<P: AsRef<Path>, C: ColorType>(
+    path: P,
+    frame: &FrameData,
+    palette: &Palette<C>,
+) -> io::Result<()> {
+    write_preview(File::create(path)?, frame, palette)
+}
+
+/// Writes a gray frame to the file at `path`, see [write_gray]
+pub fn save_gray<P: AsRef<Path>>(
+    path: P,
+    width: u32,
+    height: u32,
+    buffer: &[u8],
+) -> io::Result<()> {
+    write_gray(File::create(path)?, width, height, buffer)
+}
+
+/// Loads a frame from the file at `path`, see [read]
+pub fn load<P: AsRef<Path>>(path: P, bits_per_pixel: u8) -> io::Result<LoadedFrame> {
+    read(File::open(path)?, bits_per_pixel)
+}
+
+/// Loads a gray frame from the file at `path`, see [read_gray]
+pub fn load_gray<P: AsRef<Path>>(path: P) -> io::Result<LoadedFrame> {
+    read_gray(File::open(path)?)
+}
+
+// Color of pixel x of a byte of a 7 color frame, the even pixel is the upper nibble
+fn oct_pixel(byte: u8, x: usize) -> Option<OctColor> {
+    if x.is_multiple_of(2) {
+        OctColor::high_pixel(byte).ok()
+    } else {
+        OctColor::low_pixel(byte).ok()
+    }
+}
+
+fn invalid(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Whether the bit of pixel (x, y) is set in a 1 bit per pixel plane `width` pixels wide
+fn bit(plane: &[u8], width: usize, x: usize, y: usize) -> bool {
+    plane[y * width.div_ceil(8) + x / 8] & (0x80 >> (x % 8)) != 0
+}
+
+// Packs the values of one row into bytes with `per_byte` pixels each, the leftmost pixel in
+// the most significant bits. Padding is filled with the value of white.
+fn pack<T: Copy + PaddedWhite>(
+    row: impl Iterator<Item = T>,
+    width: usize,
+    per_byte: usize,
+    value: impl Fn(T) -> u8,
+) -> Vec<u8> {
+    let bits = 8 / per_byte;
+    let mut bytes = vec![0; width.div_ceil(per_byte)];
+    let row = row.chain(core::iter::repeat(T::WHITE));
+    for (x, pixel) in row.take(bytes.len() * per_byte).enumerate() {
+        bytes[x / per_byte] |= value(pixel) << (8 - bits * (x % per_byte + 1));
+    }
+    bytes
+}
+
+// Packs all pixels of a frame row by row, see `pack`
+fn rows<T: Copy + PaddedWhite>(
+    pixels: impl Iterator<Item = T>,
+    width: usize,
+    per_byte: usize,
+    value: impl Fn(T) -> u8,
+) -> Vec<u8> {
+    let pixels: Vec<T> = pixels.collect();
+    let mut data = Vec::new();
+    for row in pixels.chunks(width.max(1)) {
+        data.extend(pack(row.iter().copied(), width, per_byte, &value));
+    }
+    data
+}
+
+// Value of the padding pixels
+trait PaddedWhite {
+    const WHITE: Self;
+}
+
+impl PaddedWhite for bool {
+    const WHITE: bool = true;
+}
+
+impl PaddedWhite for u8 {
+    // gray level
+    const WHITE: u8 = 3;
+}
+
+impl PaddedWhite for OctColor {
+    const WHITE: OctColor = OctColor::White;
+}
+
+// Checks the magic number and reads width and height
+fn header<R: Read>(input: &mut R, magic: &[u8; 2]) -> io::Result<(u32, u32)> {
+    let mut found = [0; 2];
+    input.read_exact(&mut found)?;
+    if &found != magic {
+        return Err(invalid("not the expected Netpbm format"));
+    }
+    Ok((number(input)?, number(input)?))
+}
+
+// Reads the maximum sample value of a PPM, gives the scaling of samples to 8 bits
+fn maxval<R: Read>(input: &mut R) -> io::Result<impl Fn(u8) -> u8> {
+    let max = number(input)?;
+    if max == 0 || max > 255 {
+        return Err(invalid("only 8 bit samples are supported"));
+    }
+    Ok(move |v: u8| ((u32::from(v) * 255 + max / 2) / max).min(255) as u8)
+}
+
+// Reads the next number of the header, skipping whitespace and comments. Consumes the
+// single whitespace after it, which separates the last field from the data.
+fn number<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut c = byte(input)?;
+    loop {
+        if c == b'#' {
+            while byte(input)? != b'\n' {}
+            c = byte(input)?;
+        } else if c.is_ascii_whitespace() {
+            c = byte(input)?;
+        } else {
+            break;
+        }
+    }
+    if !c.is_ascii_digit() {
+        return Err(invalid("expected a number in the header"));
+    }
+    let mut n: u32 = 0;
+    while c.is_ascii_digit() {
+        n = n
+            .checked_mul(10)
+            .and_then(|n| n.checked_add(u32::from(c - b'0')))
+            .ok_or_else(|| invalid("number in the header too large"))?;
+        c = byte(input)?;
+    }
+    if !c.is_ascii_whitespace() {
+        return Err(invalid("expected whitespace in the header"));
+    }
+    Ok(n)
+}
+
+fn byte<R: Read>(input: &mut R) -> io::Result<u8> {
+    let mut byte = [0];
+    input.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(frame: &FrameData) -> Vec<u8> {
+        let mut out = Vec::new();
+        write(&mut out, frame).unwrap();
+        out
+    }
+
+    #[test]
+    fn bw_round_trip() {
+        // 10 pixels wide, the first pixel of each row black, padding white
+        let data = [0x7f, 0xff, 0xff, 0x3f];
+        let frame = FrameData::new(10, 2, 1, &data).unwrap();
+        let out = written(&frame);
+        assert_eq!(&out[..8], b"P4\n10 2\n");
+        assert_eq!(&out[8..], &[0x80, 0x00, 0x00, 0xc0]);
+
+        let loaded = read(&out[..], 1).unwrap();
+        assert_eq!((loaded.width(), loaded.height()), (10, 2));
+        assert_eq!(loaded.data(), &data);
+    }
+
+    #[test]
+    fn tri_color_round_trip() {
+        // black, white and chromatic
+        let mut data = [0xff; 2 * 2];
+        data[0] = 0b0111_1111;
+        data[2] = 0b1011_1111;
+        let frame = FrameData::new(3, 2, 2, &data).unwrap();
+        let out = written(&frame);
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&out[..header.len()], header);
+        // how the colors look on the panel
+        let rgb = |color: TriColor| <[u8; 3]>::from(color.panel_rgb());
+        assert_eq!(
+            &out[header.len()..][..9],
+            [TriColor::Black, TriColor::Chromatic, TriColor::White]
+                .map(rgb)
+                .concat()
+        );
+
+        assert_eq!(read(&out[..], 2).unwrap().data(), &data);
+    }
+
+    #[test]
+    fn oct_round_trip() {
+        let data = [
+            OctColor::colors_byte(OctColor::Orange, OctColor::HiZ),
+            OctColor::colors_byte(OctColor::Blue, OctColor::White),
+        ];
+        let frame = FrameData::new(3, 1, 4, &data).unwrap();
+        let out = written(&frame);
+        let header = b"P6\n3 1\n255\n";
+        let rgb = |color: OctColor| <[u8; 3]>::from(color.panel_rgb());
+        assert_eq!(
+            &out[header.len()..],
+            [OctColor::Orange, OctColor::HiZ, OctColor::Blue]
+                .map(rgb)
+                .concat()
+        );
+        // the padding nibble is white
+        assert_eq!(read(&out[..], 4).unwrap().data(), &data);
+
+        let invalid = [0x8f];
+        let frame = FrameData::new(1, 1, 4, &invalid).unwrap();
+        let error = write(&mut Vec::new(), &frame).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn gray_round_trip() {
+        let data = [0b0001_1011, 0b1111_1111];
+        let mut out = Vec::new();
+        write_gray(&mut out, 5, 1, &data).unwrap();
+        assert_eq!(out, b"P5\n5 1\n3\n\x00\x01\x02\x03\x03");
+        assert_eq!(read_gray(&out[..]).unwrap().data(), &data);
+
+        let error = write_gray(&mut Vec::new(), 5, 2, &data).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn files_of_other_tools() {
+        // comments, other whitespace and 8 bit samples
+        let pgm = b"P5 # gray\n4\t1\n# max\n255\n\x00\x50\xaa\xff";
+        assert_eq!(read_gray(&pgm[..]).unwrap().data(), &[0b0001_1011]);
+
+        let ppm = b"P6 1 1 15 \x0f\x0f\x0f";
+        assert_eq!(read(&ppm[..], 2).unwrap().data(), &[0xff, 0xff]);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let kind = |result: io::Result<LoadedFrame>| result.unwrap_err().kind();
+        assert_eq!(
+            kind(read(&b"P5 1 1 3 \x00"[..], 1)),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(read(&b"P4 x 1 \x00"[..], 1)),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(read(&b"P6 1 1 255 \x10\x20\x30"[..], 4)),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(read(&b"P4 8 2 \x00"[..], 1)),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(
+            kind(read(&b"P4 1 1 \x00"[..], 3)),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn previews() {
+        use crate::dither::{BW_PALETTE, OCT_PALETTE, TRI_RED_PALETTE};
+        use embedded_graphics_core::pixelcolor::Rgb888;
+
+        // the pixels of a preview without the header
+        fn pixels<C: ColorType>(frame: &FrameData, palette: &Palette<C>) -> Vec<u8> {
+            let mut out = Vec::new();
+            write_preview(&mut out, frame, palette).unwrap();
+            let header = std::format!("P6\n{} {}\n255\n", frame.width(), frame.height());
+            assert!(out.starts_with(header.as_bytes()));
+            out.split_off(header.len())
+        }
+        let rgb = |colors: &[Rgb888]| -> Vec<u8> {
+            colors.iter().flat_map(|c| [c.r(), c.g(), c.b()]).collect()
+        };
+
+        // black and white
+        let frame = FrameData::new(2, 1, 1, &[0x7f]).unwrap();
+        let expected = rgb(&[BW_PALETTE[0].1, BW_PALETTE[1].1]);
+        assert_eq!(pixels(&frame, &BW_PALETTE), expected);
+
+        // white, chromatic and black
+        let frame = FrameData::new(3, 1, 2, &[0xdf, 0xbf]).unwrap();
+        let expected = rgb(&[
+            TRI_RED_PALETTE[1].1,
+            TRI_RED_PALETTE[2].1,
+            TRI_RED_PALETTE[0].1,
+        ]);
+        assert_eq!(pixels(&frame, &TRI_RED_PALETTE), expected);
+
+        // the same palette instance as the dithering, e.g. with an olive green
+        let mut olive = OCT_PALETTE;
+        olive[2] = (OctColor::Green, Rgb888::new(128, 128, 40));
+        let data = [OctColor::colors_byte(OctColor::Green, OctColor::White)];
+        let frame = FrameData::new(2, 1, 4, &data).unwrap();
+        assert_eq!(pixels(&frame, &olive), [128, 128, 40, 255, 255, 255]);
+
+        let error = write_preview(&mut Vec::new(), &frame, &BW_PALETTE).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let data = [OctColor::colors_byte(OctColor::HiZ, OctColor::White)];
+        let frame = FrameData::new(2, 1, 4, &data).unwrap();
+        let error = write_preview(&mut Vec::new(), &frame, &OCT_PALETTE).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join("epd_waveshare_dump_test.ppm");
+        let data = [OctColor::colors_byte(OctColor::Red, OctColor::Green); 4];
+        let frame = FrameData::new(4, 2, 4, &data).unwrap();
+        save(&path, &frame).unwrap();
+        let loaded = load(&path, 4).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.into_data(), data);
+    }
+}
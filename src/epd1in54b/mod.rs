@@ -1,14 +1,22 @@
 //! A simple Driver for the Waveshare 1.54" (B) E-Ink Display via SPI
+//!
+//! Polarity: the IL0373 shows set bits as white and cleared bits of the red plane as red
+//! ([Polarity::ClearedIsColor]).
+//!
 
 use embedded_hal::{
     blocking::{delay::*, spi::Write},
     digital::v2::*,
 };
 
-use crate::interface::DisplayInterface;
-use crate::traits::{
-    InternalWiAdditions, RefreshLut, WaveshareDisplay, WaveshareThreeColorDisplay,
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
 };
+use crate::pattern;
+use crate::traits::DisplayState;
+use crate::traits::{RefreshLut, WaveshareDisplay, WaveshareThreeColorDisplay};
 
 //The Lookup Tables for the Display
 mod constants;
@@ -20,9 +28,24 @@ pub const WIDTH: u32 = 200;
 pub const HEIGHT: u32 = 200;
 /// Default Background Color (white)
 pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full frame buffer for epd1in54b
+///
+/// This is the size of the b/w frame, the chromatic frame has the same size
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+/// Length in bytes of the b/w plane, see [TriColorFrame](crate::color::TriColorFrame)
+pub const fn achromatic_buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+/// Length in bytes of the chromatic plane, the same as of the b/w plane
+pub const fn chromatic_buffer_len() -> usize {
+    achromatic_buffer_len()
+}
+// The IL0373 pulls BUSY low while it is working
 const IS_BUSY_LOW: bool = true;
 
-use crate::color::Color;
+use crate::color::{buffer_size, Color, Polarity};
 
 pub(crate) mod command;
 use self::command::Command;
@@ -30,59 +53,14 @@ use self::command::Command;
 #[cfg(feature = "graphics")]
 mod graphics;
 #[cfg(feature = "graphics")]
-pub use self::graphics::Display1in54b;
+pub use self::graphics::{Display1in54b, Display1in54bTriColor};
 
 /// Epd1in54b driver
 pub struct Epd1in54b<SPI, CS, BUSY, DC, RST, DELAY> {
     interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
     color: Color,
-}
-
-impl<SPI, CS, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, CS, BUSY, DC, RST, DELAY>
-    for Epd1in54b<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.interface.reset(delay, 10);
-
-        // set the power settings
-        self.interface
-            .cmd_with_data(spi, Command::PowerSetting, &[0x07, 0x00, 0x08, 0x00])?;
-
-        // start the booster
-        self.interface
-            .cmd_with_data(spi, Command::BoosterSoftStart, &[0x07, 0x07, 0x07])?;
-
-        // power on
-        self.command(spi, Command::PowerOn)?;
-        delay.delay_ms(5);
-        self.wait_until_idle();
-
-        // set the panel settings
-        self.cmd_with_data(spi, Command::PanelSetting, &[0xCF])?;
-
-        self.cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[0x37])?;
-
-        // PLL
-        self.cmd_with_data(spi, Command::PllControl, &[0x39])?;
-
-        // set resolution
-        self.send_resolution(spi)?;
-
-        self.cmd_with_data(spi, Command::VcmDcSetting, &[0x0E])?;
-
-        self.set_lut(spi, None)?;
-
-        self.wait_until_idle();
-
-        Ok(())
-    }
+    /// Whether the panel power is on
+    powered: bool,
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareThreeColorDisplay<SPI, CS, BUSY, DC, RST, DELAY>
@@ -100,21 +78,25 @@ where
         spi: &mut SPI,
         black: &[u8],
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_achromatic_frame(spi, black)?;
         self.update_chromatic_frame(spi, chromatic)
     }
 
-    fn update_achromatic_frame(&mut self, spi: &mut SPI, black: &[u8]) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn update_achromatic_frame(
+        &mut self,
+        spi: &mut SPI,
+        black: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(black, achromatic_buffer_len())?;
+        self.wait_until_idle()?;
         self.send_resolution(spi)?;
 
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
 
-        for b in black {
-            let expanded = expand_bits(*b);
-            self.interface.data(spi, &expanded)?;
-        }
+        self.interface.data_expanded(spi, black, expand_bits)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
@@ -122,9 +104,12 @@ where
         &mut self,
         spi: &mut SPI,
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, Command::DataStartTransmission2)?;
-        self.interface.data(spi, chromatic)?;
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(chromatic, chromatic_buffer_len())?;
+        self.interface
+            .cmd_with_data(spi, Command::DataStartTransmission2, chromatic)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 }
@@ -140,26 +125,67 @@ where
     DELAY: DelayMs<u8>,
 {
     type DisplayColor = Color;
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
+    const SUPPORTS_PARTIAL: bool = false;
+    const SUPPORTS_QUICK_REFRESH: bool = false;
+    const SUPPORTS_LUT: bool = true;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 2;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
         let interface = DisplayInterface::new(cs, busy, dc, rst);
         let color = DEFAULT_BACKGROUND_COLOR;
 
-        let mut epd = Epd1in54b { interface, color };
+        Epd1in54b {
+            interface,
+            color,
+            powered: false,
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.reset(delay, 10)?;
+        // IL0373: BUSY is low until the controller is ready after the reset
+        self.wait_until_idle()?;
+        self.powered = false;
+
+        // set the power settings
+        self.interface
+            .cmd_with_data(spi, Command::PowerSetting, &[0x07, 0x00, 0x08, 0x00])?;
+
+        // start the booster
+        self.interface
+            .cmd_with_data(spi, Command::BoosterSoftStart, &[0x07, 0x07, 0x07])?;
 
-        epd.init(spi, delay)?;
+        // power on
+        self.power_on(spi, delay)?;
+
+        // set the panel settings
+        self.cmd_with_data(spi, Command::PanelSetting, &[0xCF])?;
+
+        self.cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[0x37])?;
+
+        // PLL
+        self.cmd_with_data(spi, Command::PllControl, &[0x39])?;
+
+        // set resolution
+        self.send_resolution(spi)?;
 
-        Ok(epd)
+        self.cmd_with_data(spi, Command::VcmDcSetting, &[0x0E])?;
+
+        self.set_lut(spi, None)?;
+
+        self.wait_until_idle()?;
+
+        Ok(())
+    }
+
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
     }
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         self.interface
             .cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[0x17])?; //border floating
 
@@ -169,19 +195,60 @@ where
         self.interface
             .cmd_with_data(spi, Command::PowerSetting, &[0x02, 0x00, 0x00, 0x00])?; //VG&VS to 0V fast
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
 
         //NOTE: The example code has a 1s delay here
 
-        self.command(spi, Command::PowerOff)?;
+        self.power_off(spi, delay)?;
 
+        self.interface.asleep()?;
         Ok(())
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
     fn set_background_color(&mut self, color: Color) {
         self.color = color;
     }
@@ -190,6 +257,14 @@ where
         &self.color
     }
 
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -203,86 +278,129 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
+        self.wait_until_idle()?;
         self.send_resolution(spi)?;
 
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
 
-        for b in buffer {
-            // Two bits per pixel
-            let expanded = expand_bits(*b);
-            self.interface.data(spi, &expanded)?;
-        }
+        // Two bits per pixel, the uniform background byte expands to two equal bytes
+        self.interface.data_expanded(spi, buffer, expand_bits)?;
+        let background = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
+        self.interface
+            .pad(spi, background, 2 * (buffer_len() - buffer.len()))?;
 
         //NOTE: Example code has a delay here
 
-        // Clear the read layer
-        let color = self.color.get_byte_value();
+        // Clear the chromatic layer to no color, whatever the background
+        let no_color = Color::White.to_controller_byte(Self::POLARITY);
         let nbits = WIDTH * (HEIGHT / 8);
 
         self.interface.cmd(spi, Command::DataStartTransmission2)?;
-        self.interface.data_x_times(spi, color, nbits)?;
+        self.interface.data_x_times(spi, no_color, nbits)?;
 
         //NOTE: Example code has a delay here
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
-    #[allow(unused)]
     fn update_partial_frame(
         &mut self,
-        spi: &mut SPI,
-        buffer: &[u8],
-        x: u32,
-        y: u32,
-        width: u32,
-        height: u32,
-    ) -> Result<(), SPI::Error> {
-        unimplemented!()
+        _spi: &mut SPI,
+        _buffer: &[u8],
+        _x: u32,
+        _y: u32,
+        _width: u32,
+        _height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        self.command(spi, Command::DisplayRefresh)?;
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
         Ok(())
     }
 
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         self.send_resolution(spi)?;
 
-        let color = DEFAULT_BACKGROUND_COLOR.get_byte_value();
+        let color = DEFAULT_BACKGROUND_COLOR.to_controller_byte(Self::POLARITY);
 
         // Clear the black
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
 
         // Uses 2 bits per pixel
-        self.interface
-            .data_x_times(spi, color, 2 * (WIDTH * HEIGHT / 8))?;
+        self.interface.data_x_times(
+            spi,
+            self.interface.background_byte(color),
+            2 * (WIDTH * HEIGHT / 8),
+        )?;
 
         // Clear the red
         self.interface.cmd(spi, Command::DataStartTransmission2)?;
         self.interface
             .data_x_times(spi, color, WIDTH * HEIGHT / 8)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let black = pattern::bytes(WIDTH, HEIGHT, 0, pattern::halves);
+        let chromatic = pattern::bytes(WIDTH, HEIGHT, 1, pattern::halves);
+        self.wait_until_idle()?;
+        self.send_resolution(spi)?;
+
+        self.interface.cmd(spi, Command::DataStartTransmission1)?;
+        self.interface
+            .data_generated(spi, buffer_len(), black, expand_bits)?;
+        self.interface.cmd_with_generated(
+            spi,
+            Command::DataStartTransmission2,
+            buffer_len(),
+            chromatic,
+        )?;
+        self.interface.set_state(DisplayState::FramePending);
+        self.display_frame(spi, delay)
+    }
+
     fn set_lut(
         &mut self,
         spi: &mut SPI,
-        _refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
+        refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>> {
+        // only the full tables
+        if refresh_rate == Some(RefreshLut::Quick) {
+            return Err(Error::Unsupported);
+        }
         self.interface
             .cmd_with_data(spi, Command::LutForVcom, LUT_VCOM0)?;
         self.interface
@@ -301,9 +419,13 @@ where
         Ok(())
     }
 
-    fn is_busy(&self) -> bool {
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
         self.interface.is_busy(IS_BUSY_LOW)
     }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> Epd1in54b<SPI, CS, BUSY, DC, RST, DELAY>
@@ -315,12 +437,43 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, command)
+    /// Turns on the booster and the panel power
+    ///
+    /// Does nothing if the power is already on.
+    /// As long as the power stays on, `display_frame` can refresh the panel
+    /// without going through a power cycle each time.
+    pub fn power_on(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        if !self.powered {
+            self.command(spi, Command::PowerOn)?;
+            let started = self.interface.now();
+            delay.delay_ms(5);
+            self.wait_until_idle()?;
+            self.interface.record_power_on(started);
+            self.powered = true;
+        }
+        Ok(())
     }
 
-    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
-        self.interface.data(spi, data)
+    /// Turns off the booster and the panel power, the displayed image stays
+    ///
+    /// Does nothing if the power is already off.
+    pub fn power_off(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.powered {
+            self.command(spi, Command::PowerOff)?;
+            let started = self.interface.now();
+            self.wait_until_idle()?;
+            self.interface.record_power_off(started);
+            self.powered = false;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd(spi, command)
     }
 
     fn cmd_with_data(
@@ -328,23 +481,32 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn wait_until_idle(&mut self) {
-        let _ = self.interface.wait_until_idle(IS_BUSY_LOW);
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.power_on(spi, delay)?;
+        self.wait_until_idle()?;
+        self.command(spi, Command::DisplayRefresh)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
     }
 
-    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
         let w = self.width();
         let h = self.height();
 
-        self.command(spi, Command::ResolutionSetting)?;
-
-        self.send_data(spi, &[w as u8])?;
-        self.send_data(spi, &[(h >> 8) as u8])?;
-        self.send_data(spi, &[h as u8])
+        self.cmd_with_data(
+            spi,
+            Command::ResolutionSetting,
+            &[w as u8, (h >> 8) as u8, h as u8],
+        )
     }
 }
 
@@ -368,5 +530,43 @@ mod tests {
         assert_eq!(WIDTH, 200);
         assert_eq!(HEIGHT, 200);
         assert_eq!(DEFAULT_BACKGROUND_COLOR, Color::White);
+        assert_eq!(buffer_len(), 5000);
+        assert_eq!(achromatic_buffer_len(), buffer_len());
+        assert_eq!(chromatic_buffer_len(), buffer_len());
+    }
+
+    #[test]
+    #[cfg(feature = "graphics")]
+    fn clear_and_update_tri_color() {
+        use crate::color::TriColor;
+        use crate::graphics::TriDisplay;
+        use crate::mock::init_epd;
+        use embedded_graphics_core::prelude::*;
+
+        let (_log, mut spi, mut delay, mut epd) = init_epd::<Epd1in54b<_, _, _, _, _, _>>();
+
+        let mut display = Display1in54bTriColor::default();
+        display.clear(TriColor::Chromatic).unwrap();
+        epd.clear_and_update(&mut spi, &mut display, &mut delay)
+            .unwrap();
+        // white in both planes
+        assert!(display.bw_buffer().iter().all(|&byte| byte == 0xff));
+        assert!(display.chromatic_buffer().iter().all(|&byte| byte == 0xff));
+    }
+
+    #[test]
+    fn update_frame_clears_chromatic() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd1in54b<_, _, _, _, _, _>>();
+
+        // a black background does not turn the chromatic frame on
+        epd.set_background_color(Color::Black);
+        epd.update_frame(&mut spi, &[0x00; buffer_len()], &mut delay)
+            .unwrap();
+        let chromatic = log.data_after(Command::DataStartTransmission2.address());
+        assert_eq!(chromatic.len(), buffer_len());
+        assert!(chromatic.iter().all(|&byte| byte == 0xff));
     }
 }
@@ -1,14 +1,22 @@
 //! A simple Driver for the Waveshare 1.54" (C) E-Ink Display via SPI
+//!
+//! Polarity: the IL0376F shows set bits as white and cleared bits of the yellow plane as
+//! yellow ([Polarity::ClearedIsColor]).
+//!
 
 use embedded_hal::{
     blocking::{delay::*, spi::Write},
     digital::v2::*,
 };
 
-use crate::interface::DisplayInterface;
-use crate::traits::{
-    InternalWiAdditions, RefreshLut, WaveshareDisplay, WaveshareThreeColorDisplay,
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
 };
+use crate::pattern;
+use crate::traits::DisplayState;
+use crate::traits::{WaveshareDisplay, WaveshareThreeColorDisplay};
 
 /// Width of epd1in54 in pixels
 pub const WIDTH: u32 = 152;
@@ -16,10 +24,25 @@ pub const WIDTH: u32 = 152;
 pub const HEIGHT: u32 = 152;
 /// Default Background Color (white)
 pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full frame buffer for epd1in54c
+///
+/// This is the size of the b/w frame, the chromatic frame has the same size
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+/// Length in bytes of the b/w plane, see [TriColorFrame](crate::color::TriColorFrame)
+pub const fn achromatic_buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+/// Length in bytes of the chromatic plane, the same as of the b/w plane
+pub const fn chromatic_buffer_len() -> usize {
+    achromatic_buffer_len()
+}
+// The IL0376F pulls BUSY low while it is working
 const IS_BUSY_LOW: bool = true;
 const NUM_DISPLAY_BITS: u32 = WIDTH * HEIGHT / 8;
 
-use crate::color::Color;
+use crate::color::{buffer_size, Color, Polarity};
 
 pub(crate) mod command;
 use self::command::Command;
@@ -28,49 +51,14 @@ use self::command::Command;
 mod graphics;
 
 #[cfg(feature = "graphics")]
-pub use self::graphics::Display1in54c;
+pub use self::graphics::{Display1in54c, Display1in54cTriColor};
 
 /// Epd1in54c driver
 pub struct Epd1in54c<SPI, CS, BUSY, DC, RST, DELAY> {
     interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
     color: Color,
-}
-
-impl<SPI, CS, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, CS, BUSY, DC, RST, DELAY>
-    for Epd1in54c<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        // Based on Reference Program Code from:
-        // https://www.waveshare.com/w/upload/a/ac/1.54inch_e-Paper_Module_C_Specification.pdf
-        // and:
-        // https://github.com/waveshare/e-Paper/blob/master/STM32/STM32-F103ZET6/User/e-Paper/EPD_1in54c.c
-        self.interface.reset(delay, 2);
-
-        // start the booster
-        self.cmd_with_data(spi, Command::BoosterSoftStart, &[0x17, 0x17, 0x17])?;
-
-        // power on
-        self.command(spi, Command::PowerOn)?;
-        delay.delay_ms(5);
-        self.wait_until_idle();
-
-        // set the panel settings
-        self.cmd_with_data(spi, Command::PanelSetting, &[0x0f, 0x0d])?;
-
-        // set resolution
-        self.send_resolution(spi)?;
-
-        self.cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[0x77])?;
-
-        Ok(())
-    }
+    /// Whether the panel power is on
+    powered: bool,
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareThreeColorDisplay<SPI, CS, BUSY, DC, RST, DELAY>
@@ -88,15 +76,22 @@ where
         spi: &mut SPI,
         black: &[u8],
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_achromatic_frame(spi, black)?;
         self.update_chromatic_frame(spi, chromatic)
     }
 
-    fn update_achromatic_frame(&mut self, spi: &mut SPI, black: &[u8]) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn update_achromatic_frame(
+        &mut self,
+        spi: &mut SPI,
+        black: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(black, achromatic_buffer_len())?;
+        self.wait_until_idle()?;
         self.cmd_with_data(spi, Command::DataStartTransmission1, black)?;
 
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
@@ -104,10 +99,13 @@ where
         &mut self,
         spi: &mut SPI,
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(chromatic, chromatic_buffer_len())?;
+        self.wait_until_idle()?;
         self.cmd_with_data(spi, Command::DataStartTransmission2, chromatic)?;
 
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 }
@@ -123,38 +121,109 @@ where
     DELAY: DelayMs<u8>,
 {
     type DisplayColor = Color;
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
+    const SUPPORTS_PARTIAL: bool = false;
+    const SUPPORTS_QUICK_REFRESH: bool = false;
+    const SUPPORTS_LUT: bool = false;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 2;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
         let interface = DisplayInterface::new(cs, busy, dc, rst);
         let color = DEFAULT_BACKGROUND_COLOR;
 
-        let mut epd = Epd1in54c { interface, color };
+        Epd1in54c {
+            interface,
+            color,
+            powered: false,
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        // Based on Reference Program Code from:
+        // https://www.waveshare.com/w/upload/a/ac/1.54inch_e-Paper_Module_C_Specification.pdf
+        // and:
+        // https://github.com/waveshare/e-Paper/blob/master/STM32/STM32-F103ZET6/User/e-Paper/EPD_1in54c.c
+        self.interface.reset(delay, 2)?;
+        // IL0376F: BUSY is low until the controller is ready after the reset
+        self.wait_until_idle()?;
+        self.powered = false;
+
+        // start the booster
+        self.cmd_with_data(spi, Command::BoosterSoftStart, &[0x17, 0x17, 0x17])?;
+
+        // power on
+        self.power_on(spi, delay)?;
+
+        // set the panel settings
+        self.cmd_with_data(spi, Command::PanelSetting, &[0x0f, 0x0d])?;
 
-        epd.init(spi, delay)?;
+        // set resolution
+        self.send_resolution(spi)?;
+
+        self.cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[0x77])?;
+
+        Ok(())
+    }
 
-        Ok(epd)
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
     }
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
 
-        self.command(spi, Command::PowerOff)?;
-        self.wait_until_idle();
+        self.power_off(spi, delay)?;
         self.cmd_with_data(spi, Command::DeepSleep, &[0xa5])?;
 
+        self.interface.asleep()?;
         Ok(())
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
     fn set_background_color(&mut self, color: Color) {
         self.color = color;
     }
@@ -163,6 +232,14 @@ where
         &self.color
     }
 
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -176,76 +253,121 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.update_achromatic_frame(spi, buffer)?;
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
+        self.wait_until_idle()?;
+        let background = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
+        self.command(spi, Command::DataStartTransmission1)?;
+        self.interface
+            .padded_frame_data(spi, buffer, buffer_len(), background, Self::POLARITY)?;
 
-        // Clear the chromatic layer
-        let color = self.color.get_byte_value();
+        // Clear the chromatic layer to no color, whatever the background
+        let no_color = Color::White.to_controller_byte(Self::POLARITY);
 
         self.command(spi, Command::DataStartTransmission2)?;
-        self.interface.data_x_times(spi, color, NUM_DISPLAY_BITS)?;
+        self.interface
+            .data_x_times(spi, no_color, NUM_DISPLAY_BITS)?;
 
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
-    #[allow(unused)]
     fn update_partial_frame(
         &mut self,
-        spi: &mut SPI,
-        buffer: &[u8],
-        x: u32,
-        y: u32,
-        width: u32,
-        height: u32,
-    ) -> Result<(), SPI::Error> {
-        unimplemented!()
+        _spi: &mut SPI,
+        _buffer: &[u8],
+        _x: u32,
+        _y: u32,
+        _width: u32,
+        _height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.command(spi, Command::DisplayRefresh)?;
-        self.wait_until_idle();
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
+        self.wait_until_idle()?;
 
         Ok(())
     }
 
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
 
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        let color = DEFAULT_BACKGROUND_COLOR.get_byte_value();
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        let color = DEFAULT_BACKGROUND_COLOR.to_controller_byte(Self::POLARITY);
 
         // Clear the black
         self.command(spi, Command::DataStartTransmission1)?;
-        self.interface.data_x_times(spi, color, NUM_DISPLAY_BITS)?;
+        self.interface.data_x_times(
+            spi,
+            self.interface.background_byte(color),
+            NUM_DISPLAY_BITS,
+        )?;
 
         // Clear the chromatic
         self.command(spi, Command::DataStartTransmission2)?;
         self.interface.data_x_times(spi, color, NUM_DISPLAY_BITS)?;
 
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
-    fn set_lut(
-        &mut self,
-        _spi: &mut SPI,
-        _refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
-        Ok(())
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let black = pattern::bytes(WIDTH, HEIGHT, 0, pattern::halves);
+        let chromatic = pattern::bytes(WIDTH, HEIGHT, 1, pattern::halves);
+        self.wait_until_idle()?;
+        self.interface.cmd_with_generated(
+            spi,
+            Command::DataStartTransmission1,
+            buffer_len(),
+            black,
+        )?;
+        self.wait_until_idle()?;
+        self.interface.cmd_with_generated(
+            spi,
+            Command::DataStartTransmission2,
+            buffer_len(),
+            chromatic,
+        )?;
+
+        self.interface.set_state(DisplayState::FramePending);
+        self.display_frame(spi, delay)
     }
 
-    fn is_busy(&self) -> bool {
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
         self.interface.is_busy(IS_BUSY_LOW)
     }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> Epd1in54c<SPI, CS, BUSY, DC, RST, DELAY>
@@ -257,12 +379,43 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, command)
+    /// Turns on the booster and the panel power
+    ///
+    /// Does nothing if the power is already on.
+    /// As long as the power stays on, `display_frame` can refresh the panel
+    /// without going through a power cycle each time.
+    pub fn power_on(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        if !self.powered {
+            self.command(spi, Command::PowerOn)?;
+            let started = self.interface.now();
+            delay.delay_ms(5);
+            self.wait_until_idle()?;
+            self.interface.record_power_on(started);
+            self.powered = true;
+        }
+        Ok(())
+    }
+
+    /// Turns off the booster and the panel power, the displayed image stays
+    ///
+    /// Does nothing if the power is already off.
+    pub fn power_off(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.powered {
+            self.command(spi, Command::PowerOff)?;
+            let started = self.interface.now();
+            self.wait_until_idle()?;
+            self.interface.record_power_off(started);
+            self.powered = false;
+        }
+        Ok(())
     }
 
-    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
-        self.interface.data(spi, data)
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd(spi, command)
     }
 
     fn cmd_with_data(
@@ -270,31 +423,64 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn wait_until_idle(&mut self) {
-        let _ = self.interface.wait_until_idle(IS_BUSY_LOW);
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.power_on(spi, delay)?;
+        self.command(spi, Command::DisplayRefresh)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
     }
 
-    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
+    }
+
+    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
         let w = self.width();
         let h = self.height();
 
-        self.command(spi, Command::ResolutionSetting)?;
-
-        // | D7 | D6 | D5 | D4 | D3 | D2 | D1 | D0 |
-        // |       HRES[7:3]        |  0 |  0 |  0 |
-        self.send_data(spi, &[(w as u8) & 0b1111_1000])?;
-        // | D7 | D6 | D5 | D4 | D3 | D2 | D1 |      D0 |
-        // |  - |  - |  - |  - |  - |  - |  - | VRES[8] |
-        self.send_data(spi, &[(w >> 8) as u8])?;
-        // | D7 | D6 | D5 | D4 | D3 | D2 | D1 |      D0 |
-        // |                  VRES[7:0]                 |
-        // Specification shows C/D is zero while sending the last byte,
-        // but upstream code does not implement it like that. So for now
-        // we follow upstream code.
-        self.send_data(spi, &[h as u8])
+        self.cmd_with_data(
+            spi,
+            Command::ResolutionSetting,
+            &[
+                // | D7 | D6 | D5 | D4 | D3 | D2 | D1 | D0 |
+                // |       HRES[7:3]        |  0 |  0 |  0 |
+                (w as u8) & 0b1111_1000,
+                // | D7 | D6 | D5 | D4 | D3 | D2 | D1 |      D0 |
+                // |  - |  - |  - |  - |  - |  - |  - | VRES[8] |
+                (w >> 8) as u8,
+                // | D7 | D6 | D5 | D4 | D3 | D2 | D1 |      D0 |
+                // |                  VRES[7:0]                 |
+                // Specification shows C/D is zero while sending the last byte,
+                // but upstream code does not implement it like that. So for now
+                // we follow upstream code.
+                h as u8,
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_frame_clears_chromatic() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd1in54c<_, _, _, _, _, _>>();
+
+        // a black background does not turn the chromatic frame on
+        epd.set_background_color(Color::Black);
+        epd.update_frame(&mut spi, &[0x00; buffer_len()], &mut delay)
+            .unwrap();
+        let chromatic = log.data_after(Command::DataStartTransmission2.address());
+        assert_eq!(chromatic.len(), NUM_DISPLAY_BITS as usize);
+        assert!(chromatic.iter().all(|&byte| byte == 0xff));
     }
 }
@@ -89,7 +89,6 @@ impl DriverOutput {
 ///  | | `------------- load temp
 ///  | `--------------- enable clock
 ///  `----------------- enable analog
-
 pub(crate) struct DisplayUpdateControl2(pub u8);
 #[allow(dead_code)]
 impl DisplayUpdateControl2 {
@@ -112,6 +111,12 @@ impl DisplayUpdateControl2 {
         self
     }
 
+    /// Display with mode 2, used by the partial refresh
+    pub fn display_mode_2(mut self) -> Self {
+        self.0.set_bit(3, true);
+        self
+    }
+
     pub fn load_lut(mut self) -> Self {
         self.0.set_bit(4, true);
         self
@@ -133,8 +138,7 @@ impl DisplayUpdateControl2 {
     }
 }
 
-#[allow(dead_code)]
-
+#[allow(dead_code, clippy::enum_variant_names)]
 pub(crate) enum DataEntryModeIncr {
     XDecrYDecr = 0x0,
     XIncrYDecr = 0x1,
@@ -143,7 +147,6 @@ pub(crate) enum DataEntryModeIncr {
 }
 
 #[allow(dead_code)]
-
 pub(crate) enum DataEntryModeDir {
     XDir = 0x0,
     YDir = 0x4,
@@ -189,16 +192,16 @@ impl BorderWaveForm {
     }
 }
 
-#[allow(dead_code)]
-#[derive(Copy, Clone)]
+/// Deep sleep mode of the SSD1675B, see [set_deep_sleep_mode](super::Epd2in13::set_deep_sleep_mode)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum DeepSleepMode {
-    // Sleeps and keeps access to RAM and controller
+    /// Sleeps and keeps access to RAM and controller
     Normal = 0x00,
 
-    // Sleeps without access to RAM/controller but keeps RAM content
+    /// Sleeps without access to RAM/controller but keeps RAM content
     Mode1 = 0x01,
 
-    // Same as MODE_1 but RAM content is not kept
+    /// Same as MODE_1 but RAM content is not kept
     Mode2 = 0x11,
 }
 
@@ -1,66 +1,19 @@
-use crate::buffer_len;
-use crate::epd2in13_v2::{DEFAULT_BACKGROUND_COLOR, HEIGHT, WIDTH};
-use crate::graphics::{Display, DisplayRotation};
-use embedded_graphics::pixelcolor::BinaryColor;
-use embedded_graphics_core::prelude::*;
+use crate::display::FixedDisplay;
+use crate::epd2in13_v2::{buffer_len, Epd2in13, HEIGHT, WIDTH};
+use crate::flush::impl_flushable;
+use embedded_graphics_core::pixelcolor::BinaryColor;
 
 /// Full size buffer for use with the 2in13 v2 EPD
 ///
 /// Can also be manually constructed:
-/// `buffer: [DEFAULT_BACKGROUND_COLOR.get_byte_value(); WIDTH / 8 * HEIGHT]`
-pub struct Display2in13 {
-    buffer: [u8; buffer_len(WIDTH as usize, HEIGHT as usize)],
-    rotation: DisplayRotation,
-}
-
-impl Default for Display2in13 {
-    fn default() -> Self {
-        Display2in13 {
-            buffer: [DEFAULT_BACKGROUND_COLOR.get_byte_value();
-                buffer_len(WIDTH as usize, HEIGHT as usize)],
-            rotation: DisplayRotation::default(),
-        }
-    }
-}
-
-impl DrawTarget for Display2in13 {
-    type Color = BinaryColor;
-    type Error = core::convert::Infallible;
-
-    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
-    where
-        I: IntoIterator<Item = Pixel<Self::Color>>,
-    {
-        for pixel in pixels {
-            self.draw_helper(WIDTH, HEIGHT, pixel)?;
-        }
-        Ok(())
-    }
-}
-
-impl OriginDimensions for Display2in13 {
-    fn size(&self) -> Size {
-        Size::new(WIDTH, HEIGHT)
-    }
-}
-
-impl Display for Display2in13 {
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
-    }
+/// `Display2in13::from_buffer([DEFAULT_BACKGROUND_COLOR.get_byte_value(); buffer_len()])`
+pub type Display2in13 = FixedDisplay<WIDTH, HEIGHT, { buffer_len() }, BinaryColor>;
 
-    fn get_mut_buffer(&mut self) -> &mut [u8] {
-        &mut self.buffer
-    }
-
-    fn set_rotation(&mut self, rotation: DisplayRotation) {
-        self.rotation = rotation;
-    }
+// the buffer has the frame length the driver checks for
+const _: () = assert!(Display2in13::BUFFER_LEN == buffer_len());
 
-    fn rotation(&self) -> DisplayRotation {
-        self.rotation
-    }
-}
+// the driver can `flush` these buffers directly
+impl_flushable!(Display2in13 => Epd2in13, crate::graphics::Display);
 
 #[cfg(test)]
 mod tests {
@@ -168,4 +121,39 @@ mod tests {
             assert_eq!(byte, epd2in13_v2::DEFAULT_BACKGROUND_COLOR.get_byte_value());
         }
     }
+
+    // the last byte of a row holds the columns 120 and 121, the rest of it is padding
+    #[test]
+    fn graphics_rotation_last_column() {
+        use embedded_graphics::primitives::Rectangle;
+
+        let (w, h) = (WIDTH as i32 - 1, HEIGHT as i32 - 1);
+        // the physical top right and bottom right pixel, as seen through every rotation
+        let corners = [
+            (DisplayRotation::Rotate0, [(w, 0), (w, h)]),
+            (DisplayRotation::Rotate90, [(0, 0), (h, 0)]),
+            (DisplayRotation::Rotate180, [(0, h), (0, 0)]),
+            (DisplayRotation::Rotate270, [(h, w), (0, w)]),
+        ];
+        for &(rotation, points) in corners.iter() {
+            for (&(x, y), row) in points.iter().zip([0, HEIGHT as usize - 1].iter()) {
+                let index = row * 16 + 15;
+                for fill in [false, true].iter() {
+                    let mut display = Display2in13::default();
+                    display.set_rotation(rotation);
+                    if *fill {
+                        let area = Rectangle::new(Point::new(x, y), Size::new(1, 1));
+                        display.fill_solid(&area, Black).unwrap();
+                    } else {
+                        Pixel(Point::new(x, y), Black).draw(&mut display).unwrap();
+                    }
+
+                    for (i, &byte) in display.buffer().iter().enumerate() {
+                        let expected = if i == index { !0x40 } else { 0xff };
+                        assert_eq!(byte, expected, "{:?} {:?} byte {}", rotation, (x, y), i);
+                    }
+                }
+            }
+        }
+    }
 }
@@ -1,5 +1,8 @@
 //! A Driver for the Waveshare 2.13" E-Ink Display (V2) via SPI
 //!
+//! Polarity: the SSD1675B shows a set bit as white, in both the new and the old frame
+//! ([Polarity::ClearedIsColor]).
+//!
 //! # References
 //!
 //! - [Waveshare product page](https://www.waveshare.com/wiki/2.13inch_e-Paper_HAT)
@@ -7,22 +10,53 @@
 //! - [Waveshare Python driver](https://github.com/waveshare/e-Paper/blob/master/RaspberryPi%26JetsonNano/python/lib/waveshare_epd/epd2in13_V2.py)
 //! - [Controller Datasheet SS1780](http://www.e-paper-display.com/download_detail/downloadsId=682.html)
 //!
+//! # Refresh modes
+//!
+//! The driver keeps track of the refresh mode, [set_refresh](Epd2in13::set_refresh) switches
+//! between them and sends everything the mode needs:
+//!
+//! - [RefreshLut::Full] loads the LUT of the full refresh, lets the border follow it and
+//!   writes every frame into both RAMs, so a later quick refresh compares with what is shown.
+//! - [RefreshLut::Quick] loads the partial LUT, holds the border at VCOM and keeps the
+//!   displayed frame as the old frame after every refresh.
+//!
+//! [display_frame](WaveshareDisplay::display_frame) refreshes in the current mode and
+//! [update_old_frame](QuickRefresh::update_old_frame) switches to the quick one. The mode
+//! stays over a [sleep](WaveshareDisplay::sleep) in [DeepSleepMode::Normal] or
+//! [DeepSleepMode::Mode1], the RAM keeps the old frame there. [DeepSleepMode::Mode2] loses
+//! it, so the display wakes up with full refreshes.
+//!
+//! # Panels mounted upside down
+//!
+//! There is no `new_with_flip` like on the other SSD16xx displays. The 122 pixels of a row
+//! end in 6 padding bits of the last RAM byte, a backwards x counter would put them at the
+//! start of the row and shift the image by 6 pixels, and the byte aligned windows of
+//! [update_partial_frame](WaveshareDisplay::update_partial_frame) wouldn't be byte aligned
+//! anymore once mirrored. Rotate or mirror the graphics buffer instead, e.g. with
+//! `DisplayRotation::Rotate180`.
+//!
 
 use embedded_hal::{
     blocking::{delay::*, spi::Write},
     digital::v2::{InputPin, OutputPin},
 };
 
-use crate::buffer_len;
-use crate::color::Color;
-use crate::interface::DisplayInterface;
-use crate::traits::{InternalWiAdditions, RefreshLut, WaveshareDisplay};
+use crate::color::{buffer_size, Color, Polarity};
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
+};
+use crate::pattern;
+use crate::traits::DisplayState;
+use crate::traits::{QuickRefresh, RefreshLut, WaveshareDisplay};
 
 pub(crate) mod command;
+pub use self::command::DeepSleepMode;
 use self::command::{
     BorderWaveForm, BorderWaveFormFixLevel, BorderWaveFormGs, BorderWaveFormVbd, Command,
-    DataEntryModeDir, DataEntryModeIncr, DeepSleepMode, DisplayUpdateControl2, DriverOutput,
-    GateDrivingVoltage, I32Ext, SourceDrivingVoltage, Vcom,
+    DataEntryModeDir, DataEntryModeIncr, DisplayUpdateControl2, DriverOutput, GateDrivingVoltage,
+    I32Ext, SourceDrivingVoltage, Vcom,
 };
 
 pub(crate) mod constants;
@@ -41,6 +75,11 @@ pub const HEIGHT: u32 = 250;
 
 /// Default Background Color
 pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full frame buffer for epd2in13_v2
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+// The SSD1675B pulls BUSY high while it is working
 const IS_BUSY_LOW: bool = false;
 
 /// Epd2in13 (V2) driver
@@ -50,106 +89,14 @@ pub struct Epd2in13<SPI, CS, BUSY, DC, RST, DELAY> {
     interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
 
     sleep_mode: DeepSleepMode,
+    /// Whether the controller kept its LUT over the last sleep, so waking up can skip it
+    lut_kept: bool,
 
     /// Background Color
     background_color: Color,
     refresh: RefreshLut,
 }
 
-impl<SPI, CS, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, CS, BUSY, DC, RST, DELAY>
-    for Epd2in13<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        // HW reset
-        self.interface.reset(delay, 10);
-
-        if self.refresh == RefreshLut::Quick {
-            self.set_vcom_register(spi, (-9).vcom())?;
-            self.wait_until_idle();
-
-            self.set_lut(spi, Some(self.refresh))?;
-
-            // Python code does this, not sure why
-            // self.cmd_with_data(spi, Command::WriteOtpSelection, &[0, 0, 0, 0, 0x40, 0, 0])?;
-
-            // During partial update, clock/analog are not disabled between 2
-            // updates.
-            self.set_display_update_control_2(
-                spi,
-                DisplayUpdateControl2::new().enable_analog().enable_clock(),
-            )?;
-            self.command(spi, Command::MasterActivation)?;
-            self.wait_until_idle();
-
-            self.set_border_waveform(
-                spi,
-                BorderWaveForm {
-                    vbd: BorderWaveFormVbd::Gs,
-                    fix_level: BorderWaveFormFixLevel::Vss,
-                    gs_trans: BorderWaveFormGs::Lut1,
-                },
-            )?;
-        } else {
-            self.wait_until_idle();
-            self.command(spi, Command::SwReset)?;
-            self.wait_until_idle();
-
-            self.set_driver_output(
-                spi,
-                DriverOutput {
-                    scan_is_linear: true,
-                    scan_g0_is_first: true,
-                    scan_dir_incr: true,
-                    width: (HEIGHT - 1) as u16,
-                },
-            )?;
-
-            // These 2 are the reset values
-            self.set_dummy_line_period(spi, 0x30)?;
-            self.set_gate_scan_start_position(spi, 0)?;
-
-            self.set_data_entry_mode(spi, DataEntryModeIncr::XIncrYIncr, DataEntryModeDir::XDir)?;
-
-            // Use simple X/Y auto increase
-            self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
-            self.set_ram_address_counters(spi, 0, 0)?;
-
-            self.set_border_waveform(
-                spi,
-                BorderWaveForm {
-                    vbd: BorderWaveFormVbd::Gs,
-                    fix_level: BorderWaveFormFixLevel::Vss,
-                    gs_trans: BorderWaveFormGs::Lut3,
-                },
-            )?;
-
-            self.set_vcom_register(spi, (-21).vcom())?;
-
-            self.set_gate_driving_voltage(spi, 190.gate_driving_decivolt())?;
-            self.set_source_driving_voltage(
-                spi,
-                150.source_driving_decivolt(),
-                50.source_driving_decivolt(),
-                (-150).source_driving_decivolt(),
-            )?;
-
-            self.set_gate_line_width(spi, 10)?;
-
-            self.set_lut(spi, Some(self.refresh))?;
-        }
-
-        self.wait_until_idle();
-        Ok(())
-    }
-}
-
 impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
     for Epd2in13<SPI, CS, BUSY, DC, RST, DELAY>
 where
@@ -161,31 +108,46 @@ where
     DELAY: DelayMs<u8>,
 {
     type DisplayColor = Color;
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
-        let mut epd = Epd2in13 {
+    const SUPPORTS_PARTIAL: bool = true;
+    const SUPPORTS_QUICK_REFRESH: bool = true;
+    const SUPPORTS_LUT: bool = true;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 1;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
+        Epd2in13 {
             interface: DisplayInterface::new(cs, busy, dc, rst),
             sleep_mode: DeepSleepMode::Mode1,
+            lut_kept: false,
             background_color: DEFAULT_BACKGROUND_COLOR,
             refresh: RefreshLut::Full,
-        };
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.lut_kept = false;
+        self.reset(delay)?;
+        self.configure(spi, true)
+    }
 
-        epd.init(spi, delay)?;
-        Ok(epd)
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.init(spi, delay)
+    /// Skips the software reset and the LUT upload if the display slept in a mode which keeps
+    /// them, see [set_deep_sleep_mode](Epd2in13::set_deep_sleep_mode)
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        if !self.lut_kept {
+            return self.init(spi, delay);
+        }
+        self.lut_kept = false;
+        self.reset(delay)?;
+        self.configure(spi, false)
     }
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
 
         // All sample code enables and disables analog/clocks...
         self.set_display_update_control_2(
@@ -199,6 +161,12 @@ where
         self.command(spi, Command::MasterActivation)?;
 
         self.set_sleep_mode(spi, self.sleep_mode)?;
+        self.interface.asleep()?;
+        self.lut_kept = self.sleep_mode != DeepSleepMode::Mode2;
+        if !self.lut_kept {
+            // without the old frame in the RAM a quick refresh would compare with noise
+            self.refresh = RefreshLut::Full;
+        }
         Ok(())
     }
 
@@ -207,20 +175,21 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        assert!(buffer.len() == buffer_len(WIDTH as usize, HEIGHT as usize));
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
         self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
         self.set_ram_address_counters(spi, 0, 0)?;
 
-        self.cmd_with_data(spi, Command::WriteRam, buffer)?;
+        self.write_padded(spi, Command::WriteRam, buffer, buffer_len())?;
 
         if self.refresh == RefreshLut::Full {
             // Always keep the base buffer equal to current if not doing partial refresh.
             self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
             self.set_ram_address_counters(spi, 0, 0)?;
 
-            self.cmd_with_data(spi, Command::WriteRamRed, buffer)?;
+            self.write_padded(spi, Command::WriteRamRed, buffer, buffer_len())?;
         }
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
@@ -235,61 +204,56 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        assert!((width * height / 8) as usize == buffer.len());
-
-        // This should not be used when doing partial refresh. The RAM_RED must
-        // be updated with the last buffer having been displayed. Doing partial
-        // update directly in RAM makes this update impossible (we can't read
-        // RAM content). Using this function will most probably make the actual
-        // display incorrect as the controler will compare with something
-        // incorrect.
-        assert!(self.refresh == RefreshLut::Full);
-
-        self.set_ram_area(spi, x, y, x + width, y + height)?;
-        self.set_ram_address_counters(spi, x, y)?;
-
-        self.cmd_with_data(spi, Command::WriteRam, buffer)?;
-
-        if self.refresh == RefreshLut::Full {
-            // Always keep the base buffer equals to current if not doing partial refresh.
-            self.set_ram_area(spi, x, y, x + width, y + height)?;
-            self.set_ram_address_counters(spi, x, y)?;
-
-            self.cmd_with_data(spi, Command::WriteRamRed, buffer)?;
-        }
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = (width.div_ceil(8) * height) as usize;
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.interface.check_len(buffer, len)?;
+        self.write_partial(spi, buffer, x, y, width, height)
+    }
 
-        Ok(())
+    fn clear_area(
+        &mut self,
+        spi: &mut SPI,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.write_partial(spi, &[], x, y, width, height)
     }
 
     /// Never use directly this function when using partial refresh, or also
     /// keep the base buffer in syncd using `set_partial_base_buffer` function.
-    fn display_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        if self.refresh == RefreshLut::Full {
-            self.set_display_update_control_2(
-                spi,
-                DisplayUpdateControl2::new()
-                    .enable_clock()
-                    .enable_analog()
-                    .display()
-                    .disable_analog()
-                    .disable_clock(),
-            )?;
-        } else {
-            self.set_display_update_control_2(spi, DisplayUpdateControl2::new().display())?;
-        }
-        self.command(spi, Command::MasterActivation)?;
-        self.wait_until_idle();
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
+        self.wait_until_idle()?;
 
         Ok(())
     }
 
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
 
@@ -299,18 +263,17 @@ where
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        let color = self.background_color.get_byte_value();
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let color = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
 
         self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
         self.set_ram_address_counters(spi, 0, 0)?;
 
         self.command(spi, Command::WriteRam)?;
-        self.interface.data_x_times(
-            spi,
-            color,
-            buffer_len(WIDTH as usize, HEIGHT as usize) as u32,
-        )?;
+        self.interface
+            .data_x_times(spi, color, buffer_len() as u32)?;
 
         // Always keep the base buffer equals to current if not doing partial refresh.
         if self.refresh == RefreshLut::Full {
@@ -318,15 +281,73 @@ where
             self.set_ram_address_counters(spi, 0, 0)?;
 
             self.command(spi, Command::WriteRamRed)?;
-            self.interface.data_x_times(
-                spi,
-                color,
-                buffer_len(WIDTH as usize, HEIGHT as usize) as u32,
-            )?;
+            self.interface
+                .data_x_times(spi, color, buffer_len() as u32)?;
         }
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        // Like update_frame, the base buffer only follows with full refreshes
+        let commands: &[Command] = if self.refresh == RefreshLut::Full {
+            &[Command::WriteRam, Command::WriteRamRed]
+        } else {
+            &[Command::WriteRam]
+        };
+        for &command in commands {
+            self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
+            self.set_ram_address_counters(spi, 0, 0)?;
+
+            let checkerboard = pattern::bytes(WIDTH, HEIGHT, 0, pattern::checkerboard);
+            self.interface.cmd(spi, command)?;
+            self.interface
+                .generated_frame_data(spi, buffer_len(), checkerboard, Self::POLARITY)?;
+        }
+        self.interface.set_state(DisplayState::FramePending);
+        self.display_frame(spi, delay)
+    }
+
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
     fn set_background_color(&mut self, background_color: Color) {
         self.background_color = background_color;
     }
@@ -335,6 +356,14 @@ where
         &self.background_color
     }
 
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -347,7 +376,7 @@ where
         &mut self,
         spi: &mut SPI,
         refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         let buffer = match refresh_rate {
             Some(RefreshLut::Full) | None => &LUT_FULL_UPDATE,
             Some(RefreshLut::Quick) => &LUT_PARTIAL_UPDATE,
@@ -356,9 +385,13 @@ where
         self.cmd_with_data(spi, Command::WriteLutRegister, buffer)
     }
 
-    fn is_busy(&self) -> bool {
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
         self.interface.is_busy(IS_BUSY_LOW)
     }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> Epd2in13<SPI, CS, BUSY, DC, RST, DELAY>
@@ -376,28 +409,43 @@ where
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
-    ) -> Result<(), SPI::Error> {
-        assert!(buffer_len(WIDTH as usize, HEIGHT as usize) == buffer.len());
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_exact_len(buffer, buffer_len())?;
         self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
         self.set_ram_address_counters(spi, 0, 0)?;
 
         self.cmd_with_data(spi, Command::WriteRamRed, buffer)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
     /// Selects which sleep mode will be used when triggering the deep sleep.
+    ///
+    /// Defaults to [DeepSleepMode::Mode1]. After a sleep in [DeepSleepMode::Normal] or
+    /// [DeepSleepMode::Mode1] the controller keeps its LUT, so
+    /// [wake_up](WaveshareDisplay::wake_up) only resets it and sends the panel settings again.
+    /// [DeepSleepMode::Mode2] powers it down completely and needs the full init.
     pub fn set_deep_sleep_mode(&mut self, mode: DeepSleepMode) {
         self.sleep_mode = mode;
     }
 
+    /// Makes the next [wake_up](WaveshareDisplay::wake_up) run the full init
+    ///
+    /// E.g. if the controller lost its LUT while asleep, after a brown-out of its supply.
+    pub fn force_full_init(&mut self) {
+        self.lut_kept = false;
+    }
+
     /// Sets the refresh mode. When changing mode, the screen will be
     /// re-initialized accordingly.
+    ///
+    /// See the [module documentation](self) for what each mode sets up.
     pub fn set_refresh(
         &mut self,
         spi: &mut SPI,
         delay: &mut DELAY,
         refresh: RefreshLut,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         if self.refresh != refresh {
             self.refresh = refresh;
             self.init(spi, delay)?;
@@ -405,11 +453,108 @@ where
         Ok(())
     }
 
+    // HW reset, then waits for the controller
+    fn reset(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.reset(delay, 10)?;
+        // SSD1675B: BUSY is high while the controller loads its settings after the reset
+        self.wait_until_idle()
+    }
+
+    // Sends the settings of the refresh mode, without the LUT if the controller kept it
+    fn configure(&mut self, spi: &mut SPI, with_lut: bool) -> Result<(), Error<SPI::Error>> {
+        if self.refresh == RefreshLut::Quick {
+            self.set_vcom_register(spi, (-9).vcom())?;
+            self.wait_until_idle()?;
+
+            if with_lut {
+                self.set_lut(spi, Some(self.refresh))?;
+            }
+
+            // Ping-pong for display mode 2 like the vendor drivers: after a partial refresh
+            // the controller keeps the displayed frame as the old frame to compare with
+            self.cmd_with_data(spi, Command::WriteOtpSelection, &[0, 0, 0, 0, 0x40, 0, 0])?;
+
+            // During partial update, clock/analog are not disabled between 2
+            // updates.
+            self.set_display_update_control_2(
+                spi,
+                DisplayUpdateControl2::new().enable_analog().enable_clock(),
+            )?;
+            self.command(spi, Command::MasterActivation)?;
+            self.wait_until_idle()?;
+
+            // Hold the border at VCOM, following a LUT it flashes on every partial refresh.
+            // A full init sets the LUT of the full refresh again.
+            self.set_border_waveform(
+                spi,
+                BorderWaveForm {
+                    vbd: BorderWaveFormVbd::Vcom,
+                    fix_level: BorderWaveFormFixLevel::Vss,
+                    gs_trans: BorderWaveFormGs::Lut0,
+                },
+            )?;
+        } else {
+            // the software reset loses the LUT as well
+            if with_lut {
+                self.command(spi, Command::SwReset)?;
+                self.wait_until_idle()?;
+            }
+
+            self.set_driver_output(
+                spi,
+                DriverOutput {
+                    scan_is_linear: true,
+                    scan_g0_is_first: true,
+                    scan_dir_incr: true,
+                    width: (HEIGHT - 1) as u16,
+                },
+            )?;
+
+            // These 2 are the reset values
+            self.set_dummy_line_period(spi, 0x30)?;
+            self.set_gate_scan_start_position(spi, 0)?;
+
+            self.set_data_entry_mode(spi, DataEntryModeIncr::XIncrYIncr, DataEntryModeDir::XDir)?;
+
+            // Use simple X/Y auto increase
+            self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
+            self.set_ram_address_counters(spi, 0, 0)?;
+
+            self.set_border_waveform(
+                spi,
+                BorderWaveForm {
+                    vbd: BorderWaveFormVbd::Gs,
+                    fix_level: BorderWaveFormFixLevel::Vss,
+                    gs_trans: BorderWaveFormGs::Lut3,
+                },
+            )?;
+
+            self.set_vcom_register(spi, (-21).vcom())?;
+
+            self.set_gate_driving_voltage(spi, 190.gate_driving_decivolt())?;
+            self.set_source_driving_voltage(
+                spi,
+                150.source_driving_decivolt(),
+                50.source_driving_decivolt(),
+                (-150).source_driving_decivolt(),
+            )?;
+
+            self.set_gate_line_width(spi, 10)?;
+
+            if with_lut {
+                self.set_lut(spi, Some(self.refresh))?;
+            }
+        }
+
+        self.wait_until_idle()?;
+        Ok(())
+    }
+
     fn set_gate_scan_start_position(
         &mut self,
         spi: &mut SPI,
         start: u16,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         assert!(start <= 295);
         self.cmd_with_data(
             spi,
@@ -422,7 +567,7 @@ where
         &mut self,
         spi: &mut SPI,
         borderwaveform: BorderWaveForm,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(
             spi,
             Command::BorderWaveformControl,
@@ -430,7 +575,7 @@ where
         )
     }
 
-    fn set_vcom_register(&mut self, spi: &mut SPI, vcom: Vcom) -> Result<(), SPI::Error> {
+    fn set_vcom_register(&mut self, spi: &mut SPI, vcom: Vcom) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(spi, Command::WriteVcomRegister, &[vcom.0])
     }
 
@@ -438,7 +583,7 @@ where
         &mut self,
         spi: &mut SPI,
         voltage: GateDrivingVoltage,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(spi, Command::GateDrivingVoltageCtrl, &[voltage.0])
     }
 
@@ -446,12 +591,12 @@ where
         &mut self,
         spi: &mut SPI,
         number_of_lines: u8,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         assert!(number_of_lines <= 127);
         self.cmd_with_data(spi, Command::SetDummyLinePeriod, &[number_of_lines])
     }
 
-    fn set_gate_line_width(&mut self, spi: &mut SPI, width: u8) -> Result<(), SPI::Error> {
+    fn set_gate_line_width(&mut self, spi: &mut SPI, width: u8) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(spi, Command::SetGateLineWidth, &[width & 0x0F])
     }
 
@@ -462,7 +607,7 @@ where
         vsh1: SourceDrivingVoltage,
         vsh2: SourceDrivingVoltage,
         vsl: SourceDrivingVoltage,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(
             spi,
             Command::SourceDrivingVoltageCtrl,
@@ -476,31 +621,78 @@ where
         &mut self,
         spi: &mut SPI,
         value: DisplayUpdateControl2,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(spi, Command::DisplayUpdateControl2, &[value.0])
     }
 
     /// Triggers the deep sleep mode
-    fn set_sleep_mode(&mut self, spi: &mut SPI, mode: DeepSleepMode) -> Result<(), SPI::Error> {
+    fn set_sleep_mode(
+        &mut self,
+        spi: &mut SPI,
+        mode: DeepSleepMode,
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(spi, Command::DeepSleepMode, &[mode as u8])
     }
 
-    fn set_driver_output(&mut self, spi: &mut SPI, output: DriverOutput) -> Result<(), SPI::Error> {
+    fn set_driver_output(
+        &mut self,
+        spi: &mut SPI,
+        output: DriverOutput,
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(spi, Command::DriverOutputControl, &output.to_bytes())
     }
 
     /// Sets the data entry mode (ie. how X and Y positions changes when writing
     /// data to RAM)
+    ///
+    /// The init always increments x and y, the 122 pixel rows can't be mirrored by the
+    /// counters, see the [module docs](self#panels-mounted-upside-down)
     fn set_data_entry_mode(
         &mut self,
         spi: &mut SPI,
         counter_incr_mode: DataEntryModeIncr,
         counter_direction: DataEntryModeDir,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         let mode = counter_incr_mode as u8 | counter_direction as u8;
         self.cmd_with_data(spi, Command::DataEntryModeSetting, &[mode])
     }
 
+    // Sends the window of update_partial_frame, `buffer` padded to it with the background
+    fn write_partial(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = (width.div_ceil(8) * height) as usize;
+        // This should not be used when doing partial refresh. The RAM_RED must
+        // be updated with the last buffer having been displayed. Doing partial
+        // update directly in RAM makes this update impossible (we can't read
+        // RAM content). Using this function will most probably make the actual
+        // display incorrect as the controler will compare with something
+        // incorrect.
+        assert!(self.refresh == RefreshLut::Full);
+
+        self.set_ram_area(spi, x, y, x + width - 1, y + height - 1)?;
+        self.set_ram_address_counters(spi, x, y)?;
+
+        self.write_padded(spi, Command::WriteRam, buffer, len)?;
+
+        if self.refresh == RefreshLut::Full {
+            // Always keep the base buffer equals to current if not doing partial refresh.
+            self.set_ram_area(spi, x, y, x + width - 1, y + height - 1)?;
+            self.set_ram_address_counters(spi, x, y)?;
+
+            self.write_padded(spi, Command::WriteRamRed, buffer, len)?;
+        }
+
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
     /// Sets both X and Y pixels ranges
     fn set_ram_area(
         &mut self,
@@ -509,7 +701,7 @@ where
         start_y: u32,
         end_x: u32,
         end_y: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(
             spi,
             Command::SetRamXAddressStartEndPosition,
@@ -534,8 +726,8 @@ where
         spi: &mut SPI,
         x: u32,
         y: u32,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         self.cmd_with_data(spi, Command::SetRamXAddressCounter, &[(x >> 3) as u8])?;
 
         self.cmd_with_data(
@@ -546,7 +738,7 @@ where
         Ok(())
     }
 
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd(spi, command)
     }
 
@@ -555,12 +747,180 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn wait_until_idle(&mut self) {
-        let _ = self.interface.wait_until_idle(IS_BUSY_LOW);
+    /// Writes `buffer` to the ram `command` selects, padded to `len` bytes with the background
+    fn write_padded(
+        &mut self,
+        spi: &mut SPI,
+        command: Command,
+        buffer: &[u8],
+        len: usize,
+    ) -> Result<(), Error<SPI::Error>> {
+        let background = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
+        self.interface.cmd(spi, command)?;
+        self.interface
+            .padded_frame_data(spi, buffer, len, background, Self::POLARITY)
+    }
+
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.refresh == RefreshLut::Full {
+            self.set_display_update_control_2(
+                spi,
+                DisplayUpdateControl2::new()
+                    .enable_clock()
+                    .enable_analog()
+                    .display()
+                    .disable_analog()
+                    .disable_clock(),
+            )?;
+        } else {
+            self.set_display_update_control_2(
+                spi,
+                DisplayUpdateControl2::new().display().display_mode_2(),
+            )?;
+        }
+        self.command(spi, Command::MasterActivation)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
+    }
+
+    /// Writes `buffer` to the window of the given ram
+    fn write_window(
+        &mut self,
+        spi: &mut SPI,
+        ram: Command,
+        buffer: &[u8],
+        (x, y, width, height): (u32, u32, u32, u32),
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.interface
+            .check_exact_len(buffer, (width.div_ceil(8) * height) as usize)?;
+        self.set_ram_area(spi, x, y, x + width - 1, y + height - 1)?;
+        self.set_ram_address_counters(spi, x, y)?;
+        self.cmd_with_data(spi, ram, buffer)
+    }
+}
+
+/// Switches to the quick refresh with [RefreshLut::Quick] on the first old frame, return to
+/// full refreshes with [set_refresh](Epd2in13::set_refresh).
+impl<SPI, CS, BUSY, DC, RST, DELAY> QuickRefresh<SPI, CS, BUSY, DC, RST, DELAY>
+    for Epd2in13<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    /// The frame currently displayed, to be followed by `update_new_frame`
+    fn update_old_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_exact_len(buffer, buffer_len())?;
+        self.set_refresh(spi, delay, RefreshLut::Quick)?;
+        self.write_window(spi, Command::WriteRamRed, buffer, (0, 0, WIDTH, HEIGHT))
+    }
+
+    /// To be used after `update_old_frame`
+    fn update_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.write_window(spi, Command::WriteRam, buffer, (0, 0, WIDTH, HEIGHT))?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    fn display_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.display_frame(spi, delay)
+    }
+
+    fn update_and_display_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.update_new_frame(spi, buffer, delay)?;
+        self.display_new_frame(spi, delay)
+    }
+
+    /// Needs the quick refresh, e.g. after `update_old_frame`, x and width need to be
+    /// multiples of 8
+    fn update_partial_old_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        assert!(self.refresh == RefreshLut::Quick);
+        self.write_window(spi, Command::WriteRamRed, buffer, (x, y, width, height))
+    }
+
+    /// x and width need to be multiples of 8
+    fn update_partial_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.write_window(spi, Command::WriteRam, buffer, (x, y, width, height))?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    /// x and width need to be multiples of 8
+    fn clear_partial_frame(
+        &mut self,
+        spi: &mut SPI,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let color = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.set_ram_area(spi, x, y, x + width - 1, y + height - 1)?;
+        self.set_ram_address_counters(spi, x, y)?;
+        self.command(spi, Command::WriteRam)?;
+        self.interface
+            .data_x_times(spi, color, width.div_ceil(8) * height)?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
     }
 }
 
@@ -573,5 +933,167 @@ mod tests {
         assert_eq!(WIDTH, 122);
         assert_eq!(HEIGHT, 250);
         assert_eq!(DEFAULT_BACKGROUND_COLOR, Color::White);
+        assert_eq!(buffer_len(), 16 * 250);
+    }
+
+    #[test]
+    fn quick_refresh_sequence() {
+        use crate::mock::{init_epd, Transfer};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+        use std::vec::Vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in13<_, _, _, _, _, _>>();
+
+        let frames = [vec![0x00; buffer_len()], vec![0xFF; buffer_len()]];
+        log.clear();
+        for i in 0..20 {
+            let (old, new) = (&frames[i % 2], &frames[(i + 1) % 2]);
+            epd.update_old_frame(&mut spi, old, &mut delay).unwrap();
+            epd.update_and_display_new_frame(&mut spi, new, &mut delay)
+                .unwrap();
+        }
+        let transfers = log.transfers();
+        // the quick init only happens once, with the border held at VCOM
+        let border = Transfer::Command(Command::BorderWaveformControl.address());
+        let borders: Vec<_> = transfers
+            .windows(2)
+            .filter(|pair| pair[0] == border)
+            .map(|pair| pair[1].clone())
+            .collect();
+        assert_eq!(borders, [Transfer::Data(vec![0x80])]);
+        assert_eq!(log.count_command(Command::WriteOtpSelection.address()), 1);
+
+        // the old frame goes into the red ram before the new frame, then a refresh in mode 2
+        let ram_writes: Vec<_> = transfers
+            .windows(2)
+            .filter_map(|pair| match pair {
+                [Transfer::Command(c), Transfer::Data(data)]
+                    if *c == Command::WriteRamRed.address()
+                        || *c == Command::WriteRam.address()
+                        || *c == Command::DisplayUpdateControl2.address() =>
+                {
+                    Some((*c, data.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        let mut expected = vec![(Command::DisplayUpdateControl2.address(), vec![0xC0])];
+        for i in 0..20 {
+            expected.push((Command::WriteRamRed.address(), frames[i % 2].clone()));
+            expected.push((Command::WriteRam.address(), frames[(i + 1) % 2].clone()));
+            expected.push((Command::DisplayUpdateControl2.address(), vec![0x0C]));
+        }
+        assert_eq!(ram_writes, expected);
+        assert_eq!(log.count_command(Command::MasterActivation.address()), 21);
+
+        // back to full refreshes the border follows the LUT again
+        log.clear();
+        epd.set_refresh(&mut spi, &mut delay, RefreshLut::Full)
+            .unwrap();
+        let transfers = log.transfers();
+        let position = transfers.iter().position(|t| *t == border).unwrap();
+        assert_eq!(transfers[position + 1], Transfer::Data(vec![0x03]));
+    }
+
+    #[test]
+    fn wake_up_keeps_the_lut() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in13<_, _, _, _, _, _>>();
+        let uploads = |log: &crate::mock::Log| {
+            (
+                log.count_command(Command::SwReset.address()),
+                log.count_command(Command::WriteLutRegister.address()),
+            )
+        };
+
+        // mode 1 keeps the LUT, only the panel settings are sent again
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        log.clear();
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+        assert_eq!(uploads(&log), (0, 0));
+        assert_eq!(log.count_command(Command::DriverOutputControl.address()), 1);
+        assert_eq!(epd.state(), DisplayState::Idle);
+
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        epd.force_full_init();
+        log.clear();
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+        assert_eq!(uploads(&log), (1, 1));
+
+        // mode 2 powers the controller down
+        epd.set_deep_sleep_mode(DeepSleepMode::Mode2);
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        log.clear();
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+        assert_eq!(uploads(&log), (1, 1));
+
+        // a wake up without a sleep before it runs the full init as well
+        log.clear();
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+        assert_eq!(uploads(&log), (1, 1));
+    }
+
+    #[test]
+    fn refresh_mode_over_sleep() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in13<_, _, _, _, _, _>>();
+        let frame = vec![0x00; buffer_len()];
+        let last_control = |log: &crate::mock::Log| {
+            log.data_after_each(Command::DisplayUpdateControl2.address())
+                .pop()
+                .unwrap()
+        };
+
+        // mode 1 keeps the old frame, the quick refresh goes on
+        epd.set_refresh(&mut spi, &mut delay, RefreshLut::Quick)
+            .unwrap();
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+        log.clear();
+        epd.update_and_display_new_frame(&mut spi, &frame, &mut delay)
+            .unwrap();
+        assert_eq!(last_control(&log), [0x0C]);
+
+        // mode 2 loses it, the display wakes up with full refreshes
+        epd.set_deep_sleep_mode(DeepSleepMode::Mode2);
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        log.clear();
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+        assert_eq!(
+            log.data_after(Command::BorderWaveformControl.address()),
+            [0x03]
+        );
+        epd.update_and_display_frame(&mut spi, &frame, &mut delay)
+            .unwrap();
+        assert_eq!(last_control(&log), [0xC7]);
+    }
+
+    #[test]
+    #[cfg(feature = "pad_short_buffers")]
+    fn partial_window_at_right_edge_is_padded() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd2in13<_, _, _, _, _, _>>();
+        epd.set_background_color(Color::Black);
+
+        // the last 2 columns of the 122 pixel wide panel still take a byte per row,
+        // the rows missing in the buffer are the background
+        epd.update_partial_frame(&mut spi, &[0xff; 8], WIDTH - 2, 0, 2, 16)
+            .unwrap();
+        let mut expected = vec![0xff; 8];
+        expected.resize(16, 0x00);
+        assert_eq!(log.data_after(Command::WriteRam.address()), expected);
+        assert_eq!(log.data_after(Command::WriteRamRed.address()), expected);
     }
 }
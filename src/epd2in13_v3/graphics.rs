@@ -0,0 +1,39 @@
+use crate::display::FixedDisplay;
+use crate::epd2in13_v3::{buffer_len, Epd2in13, HEIGHT, WIDTH};
+use crate::flush::impl_flushable;
+use embedded_graphics_core::pixelcolor::BinaryColor;
+
+/// Display with Fullsize buffer for use with the 2in13 EPD V3
+///
+/// Can also be manually constructed:
+/// `Display2in13::from_buffer([DEFAULT_BACKGROUND_COLOR.get_byte_value(); buffer_len()])`
+pub type Display2in13 = FixedDisplay<WIDTH, HEIGHT, { buffer_len() }, BinaryColor>;
+
+// the buffer has the frame length the driver checks for
+const _: () = assert!(Display2in13::BUFFER_LEN == buffer_len());
+
+// the driver can `flush` these buffers directly
+impl_flushable!(Display2in13 => Epd2in13, crate::graphics::Display);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epd2in13_v3::DEFAULT_BACKGROUND_COLOR;
+    use crate::graphics::Display;
+
+    // test buffer length
+    #[test]
+    fn graphics_size() {
+        let display = Display2in13::default();
+        assert_eq!(display.buffer().len(), 4000);
+    }
+
+    // test default background color on all bytes
+    #[test]
+    fn graphics_default() {
+        let display = Display2in13::default();
+        for &byte in display.buffer() {
+            assert_eq!(byte, DEFAULT_BACKGROUND_COLOR.get_byte_value());
+        }
+    }
+}
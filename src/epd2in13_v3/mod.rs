@@ -0,0 +1,850 @@
+//! A simple Driver for the Waveshare 2.13" E-Ink Display V3 via SPI
+//!
+//! The V3 of the HAT has an SSD1680 instead of the SSD1675B of the
+//! [V2](crate::epd2in13_v2), with another init and LUTs of 159 bytes, which the driver
+//! loads like the Waveshare reference driver.
+//!
+//! Polarity: the SSD1680 shows a set bit as white, in the new frame as in the old one for
+//! the quick refresh ([Polarity::ClearedIsColor]).
+//!
+//! Specification: <https://www.waveshare.com/w/upload/5/59/2.13inch_e-Paper_V3_Specificition.pdf>
+//!
+//! # Example for the 2.13 in E-Ink Display V3
+//!
+//!```rust, no_run
+//!# use embedded_hal_mock::*;
+//!# fn main() -> Result<(), epd_waveshare::Error<MockError>> {
+//!use embedded_graphics::{
+//!    pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
+//!};
+//!use epd_waveshare::{epd2in13_v3::*, prelude::*};
+//!#
+//!# let expectations = [];
+//!# let mut spi = spi::Mock::new(&expectations);
+//!# let expectations = [];
+//!# let cs_pin = pin::Mock::new(&expectations);
+//!# let busy_in = pin::Mock::new(&expectations);
+//!# let dc = pin::Mock::new(&expectations);
+//!# let rst = pin::Mock::new(&expectations);
+//!# let mut delay = delay::MockNoop::new();
+//!
+//!// Setup EPD
+//!let mut epd = Epd2in13::new(cs_pin, busy_in, dc, rst);
+//!epd.init(&mut spi, &mut delay)?;
+//!
+//!// Use display graphics from embedded-graphics
+//!let mut display = Display2in13::default();
+//!
+//!// Use embedded graphics for drawing a line
+//!let _ = Line::new(Point::new(0, 120), Point::new(0, 249))
+//!    .into_styled(PrimitiveStyle::with_stroke(Black, 1))
+//!    .draw(&mut display);
+//!
+//!// Display updated frame, in the full refresh it is the base of the quick refreshes
+//!epd.update_frame(&mut spi, &display.buffer(), &mut delay)?;
+//!epd.display_frame(&mut spi, &mut delay)?;
+//!
+//!// Update image here
+//!
+//!// quick refresh of updated pixels
+//!epd.update_and_display_new_frame(&mut spi, &display.buffer(), &mut delay)?;
+//!
+//!// Set the EPD to sleep
+//!epd.sleep(&mut spi, &mut delay)?;
+//!# Ok(())
+//!# }
+//!```
+//!
+//! # Refresh modes
+//!
+//! The driver keeps track of the refresh mode, [set_refresh](Epd2in13::set_refresh) switches
+//! between them and sends everything the mode needs:
+//!
+//! - [RefreshLut::Full] loads the full LUT in the init, the border follows it.
+//! - [RefreshLut::Quick] loads the partial LUT, keeps the displayed frame as the old frame
+//!   after every refresh and holds the border, so it doesn't flash.
+//!
+//! [display_frame](WaveshareDisplay::display_frame) refreshes in the current mode and
+//! [update_new_frame](QuickRefresh::update_new_frame) switches to the quick one. Back to
+//! [RefreshLut::Full] the controller is initialized again, which restores all registers.
+//! [init](WaveshareDisplay::init) and [wake_up](WaveshareDisplay::wake_up) always start with
+//! full refreshes.
+//!
+//! # The old frame
+//!
+//! The quick refresh only drives the pixels which differ between the new frame (RAM 0x24)
+//! and the old one (RAM 0x26), like on the [2.9" V2](crate::epd2in9_v2):
+//!
+//! - In the full refresh the frames of [update_frame](WaveshareDisplay::update_frame),
+//!   [update_partial_frame](WaveshareDisplay::update_partial_frame) and
+//!   [clear_frame](WaveshareDisplay::clear_frame) go to both RAMs.
+//! - In the quick refresh they only go to the new frame, the controller copies it into the
+//!   old one after each refresh.
+//! - The deep sleep loses the RAMs. Before the first quick refresh after waking up,
+//!   [update_old_frame](QuickRefresh::update_old_frame) writes what the panel still shows
+//!   into the old frame, otherwise it smears.
+//!
+//! # Panels mounted upside down
+//!
+//! There is no `new_with_flip` like on the other SSD16xx displays. The 122 pixels of a row
+//! end in 6 padding bits of the last RAM byte, a backwards x counter would put them at the
+//! start of the row and shift the image by 6 pixels, and the byte aligned windows of
+//! [update_partial_frame](WaveshareDisplay::update_partial_frame) wouldn't be byte aligned
+//! anymore once mirrored. Rotate or mirror the graphics buffer instead, e.g. with
+//! `DisplayRotation::Rotate180`.
+
+/// Width of the display in pixels
+pub const WIDTH: u32 = 122;
+/// Height of the display in pixels
+pub const HEIGHT: u32 = 250;
+/// Default Background Color (white)
+pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full frame buffer for epd2in13_v3
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+// The SSD1680 pulls BUSY high while it is working
+const IS_BUSY_LOW: bool = false;
+
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::*,
+};
+
+use crate::type_a::command::Command;
+
+use crate::color::{buffer_size, Color, Polarity};
+
+use crate::traits::*;
+
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
+};
+use crate::pattern;
+
+mod constants;
+use crate::epd2in13_v3::constants::{LUT_FULL_UPDATE, LUT_PARTIAL_UPDATE};
+
+#[cfg(feature = "graphics")]
+mod graphics;
+#[cfg(feature = "graphics")]
+pub use crate::epd2in13_v3::graphics::Display2in13;
+
+/// Epd2in13 (V3) driver
+///
+pub struct Epd2in13<SPI, CS, BUSY, DC, RST, DELAY> {
+    /// SPI
+    interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
+    /// Color
+    background_color: Color,
+    refresh: RefreshLut,
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
+    for Epd2in13<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    type DisplayColor = Color;
+    const SUPPORTS_PARTIAL: bool = true;
+    const SUPPORTS_QUICK_REFRESH: bool = false;
+    const SUPPORTS_LUT: bool = false;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 1;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn width(&self) -> u32 {
+        WIDTH
+    }
+
+    fn height(&self) -> u32 {
+        HEIGHT
+    }
+
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
+        let interface = DisplayInterface::new(cs, busy, dc, rst);
+
+        Epd2in13 {
+            interface,
+            background_color: DEFAULT_BACKGROUND_COLOR,
+            refresh: RefreshLut::Full,
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.refresh = RefreshLut::Full;
+        self.interface.reset(delay, 2)?;
+
+        // SSD1680: BUSY is high while the controller loads its settings after the reset
+        self.wait_until_idle()?;
+        self.interface.cmd(spi, Command::SwReset)?;
+        self.wait_until_idle()?;
+
+        // 3 Databytes: the 250 gates (A = 0xF9), B = 0x00 (GD, SM and TB=0)
+        self.interface.cmd_with_data(
+            spi,
+            Command::DriverOutputControl,
+            &[(HEIGHT - 1) as u8, ((HEIGHT - 1) >> 8) as u8, 0x00],
+        )?;
+
+        // One Databyte with default value 0x03
+        //  -> address: x increment, y increment, address counter is updated in x direction
+        self.interface
+            .cmd_with_data(spi, Command::DataEntryModeSetting, &[0x03])?;
+
+        self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
+        self.set_ram_counter(spi, 0, 0)?;
+
+        // The border follows the LUT
+        self.interface
+            .cmd_with_data(spi, Command::BorderWaveformControl, &[0x05])?;
+
+        self.interface
+            .cmd_with_data(spi, Command::DisplayUpdateControl1, &[0x00, 0x80])?;
+
+        // Internal temperature sensor
+        self.interface
+            .cmd_with_data(spi, Command::TemperatureSensorSelection, &[0x80])?;
+        self.wait_until_idle()?;
+
+        self.set_lut_helper(spi, &LUT_FULL_UPDATE)?;
+        Ok(())
+    }
+
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
+    }
+
+    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        // 0x00 for Normal mode (Power on Reset), 0x01 for Deep Sleep Mode
+        self.interface
+            .cmd_with_data(spi, Command::DeepSleepMode, &[0x01])?;
+        self.interface.asleep()?;
+        Ok(())
+    }
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.init(spi, delay)
+    }
+
+    fn update_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
+        let background = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
+        let rams = self.frame_rams();
+        self.write_rams(spi, rams, (0, 0, WIDTH, HEIGHT), |interface, spi| {
+            interface.padded_frame_data(spi, buffer, buffer_len(), background, Self::POLARITY)
+        })?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    fn update_partial_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = (width.div_ceil(8) * height) as usize;
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.interface.check_len(buffer, len)?;
+
+        let background = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
+        let rams = self.frame_rams();
+        self.write_rams(spi, rams, (x, y, width, height), |interface, spi| {
+            interface.padded_frame_data(spi, buffer, len, background, Self::POLARITY)
+        })?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
+        self.wait_until_idle()?;
+        Ok(())
+    }
+
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
+    fn update_and_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.update_frame(spi, buffer, delay)?;
+        self.display_frame(spi, delay)?;
+        Ok(())
+    }
+
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        // clear the ram with the background color
+        let color = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
+
+        let rams = self.frame_rams();
+        self.write_rams(spi, rams, (0, 0, WIDTH, HEIGHT), |interface, spi| {
+            interface.data_x_times(spi, color, buffer_len() as u32)
+        })?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let checkerboard = pattern::bytes(WIDTH, HEIGHT, 0, pattern::checkerboard);
+        let rams = self.frame_rams();
+        self.write_rams(spi, rams, (0, 0, WIDTH, HEIGHT), |interface, spi| {
+            interface.generated_frame_data(spi, buffer_len(), &checkerboard, Self::POLARITY)
+        })?;
+        self.interface.set_state(DisplayState::FramePending);
+        self.display_frame(spi, delay)
+    }
+
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
+    fn set_background_color(&mut self, background_color: Color) {
+        self.background_color = background_color;
+    }
+
+    fn background_color(&self) -> &Color {
+        &self.background_color
+    }
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(IS_BUSY_LOW)
+    }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY> Epd2in13<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    /// Sets the refresh mode, see the [module documentation](self)
+    ///
+    /// Does nothing if the display already refreshes in this mode.
+    pub fn set_refresh(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        refresh: RefreshLut,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.refresh == refresh {
+            return Ok(());
+        }
+        match refresh {
+            RefreshLut::Full => self.init(spi, delay),
+            RefreshLut::Quick => self.enter_quick_refresh(spi, delay),
+        }
+    }
+
+    // Loads the partial LUT and the settings around it like the vendor driver
+    fn enter_quick_refresh(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        self.interface.reset(delay, 1)?;
+
+        self.set_lut_helper(spi, &LUT_PARTIAL_UPDATE)?;
+        // Ping-pong for display mode 2: the displayed frame becomes the old frame
+        self.interface.cmd_with_data(
+            spi,
+            Command::WriteOtpSelection,
+            &[0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00],
+        )?;
+        // Hold the border, following the partial LUT it flashes
+        self.interface
+            .cmd_with_data(spi, Command::BorderWaveformControl, &[0x80])?;
+        self.interface
+            .cmd_with_data(spi, Command::DisplayUpdateControl2, &[0xC0])?;
+        self.interface.cmd(spi, Command::MasterActivation)?;
+
+        self.wait_until_idle()?;
+        self.refresh = RefreshLut::Quick;
+        Ok(())
+    }
+
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        let control = match self.refresh {
+            // Enable clock signal, Enable Analog, DISPLAY with DISPLAY Mode 1 and the LUT
+            // of the init, Disable Analog, Disable OSC
+            RefreshLut::Full => 0xC7,
+            // DISPLAY with DISPLAY Mode 2, clock and analog stay on between the refreshes
+            RefreshLut::Quick => 0x0F,
+        };
+        self.interface
+            .cmd_with_data(spi, Command::DisplayUpdateControl2, &[control])?;
+        self.interface.cmd(spi, Command::MasterActivation)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
+    }
+
+    /// The rams a frame goes to: in the full refresh the old frame as well, so the first
+    /// quick refresh after it starts from the displayed frame
+    fn frame_rams(&self) -> &'static [Command] {
+        match self.refresh {
+            RefreshLut::Full => &[Command::WriteRam, Command::WriteRam2],
+            RefreshLut::Quick => &[Command::WriteRam],
+        }
+    }
+
+    /// Writes the window to each of `rams`, `data` sends its bytes after the command
+    fn write_rams<F>(
+        &mut self,
+        spi: &mut SPI,
+        rams: &[Command],
+        (x, y, width, height): (u32, u32, u32, u32),
+        mut data: F,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        F: FnMut(
+            &mut DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
+            &mut SPI,
+        ) -> Result<(), Error<SPI::Error>>,
+    {
+        self.wait_until_idle()?;
+        self.set_ram_area(spi, x, y, x + width - 1, y + height - 1)?;
+        for &ram in rams {
+            self.set_ram_counter(spi, x, y)?;
+            self.interface.cmd(spi, ram)?;
+            data(&mut self.interface, spi)?;
+        }
+        Ok(())
+    }
+
+    fn set_ram_area(
+        &mut self,
+        spi: &mut SPI,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        assert!(start_x <= end_x);
+        assert!(start_y <= end_y);
+
+        // x is positioned in bytes, so the last 3 bits which show the position inside a byte in the ram
+        // aren't relevant
+        self.interface.cmd_with_data(
+            spi,
+            Command::SetRamXAddressStartEndPosition,
+            &[(start_x >> 3) as u8, (end_x >> 3) as u8],
+        )?;
+
+        // 2 Databytes: A[7:0] & 0..A[8] for each - start and end
+        self.interface.cmd_with_data(
+            spi,
+            Command::SetRamYAddressStartEndPosition,
+            &[
+                start_y as u8,
+                (start_y >> 8) as u8,
+                end_y as u8,
+                (end_y >> 8) as u8,
+            ],
+        )
+    }
+
+    fn set_ram_counter(&mut self, spi: &mut SPI, x: u32, y: u32) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        // x is positioned in bytes, so the last 3 bits which show the position inside a byte in the ram
+        // aren't relevant
+        self.interface
+            .cmd_with_data(spi, Command::SetRamXAddressCounter, &[(x >> 3) as u8])?;
+
+        // 2 Databytes: A[7:0] & 0..A[8]
+        self.interface.cmd_with_data(
+            spi,
+            Command::SetRamYAddressCounter,
+            &[y as u8, (y >> 8) as u8],
+        )?;
+        Ok(())
+    }
+
+    /// Loads the waveform of `lut` and the voltages after it, like the vendor driver
+    fn set_lut_helper(&mut self, spi: &mut SPI, lut: &[u8; 159]) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        self.interface
+            .cmd_with_data(spi, Command::WriteLutRegister, &lut[..153])?;
+        self.wait_until_idle()?;
+        self.interface
+            .cmd_with_data(spi, Command::EndOption, &lut[153..154])?;
+        self.interface
+            .cmd_with_data(spi, Command::GateDrivingVoltageControl, &lut[154..155])?;
+        self.interface
+            .cmd_with_data(spi, Command::SourceDrivingVoltageControl, &lut[155..158])?;
+        self.interface
+            .cmd_with_data(spi, Command::WriteVcomRegister, &lut[158..159])?;
+        Ok(())
+    }
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY> QuickRefresh<SPI, CS, BUSY, DC, RST, DELAY>
+    for Epd2in13<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    /// Writes the frame currently displayed into the old frame RAM (0x26)
+    ///
+    /// Only needed if the RAM lost it, e.g. after waking up from the deep sleep, see the
+    /// [module documentation](self).
+    fn update_old_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_exact_len(buffer, buffer_len())?;
+        self.write_rams(
+            spi,
+            &[Command::WriteRam2],
+            (0, 0, WIDTH, HEIGHT),
+            |interface, spi| interface.data(spi, buffer),
+        )
+    }
+
+    /// Writes the new frame RAM (0x24) and switches to the quick refresh
+    fn update_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_exact_len(buffer, buffer_len())?;
+        self.set_refresh(spi, delay, RefreshLut::Quick)?;
+        self.write_rams(
+            spi,
+            &[Command::WriteRam],
+            (0, 0, WIDTH, HEIGHT),
+            |interface, spi| interface.data(spi, buffer),
+        )?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    /// For a quick refresh of the new updated frame. To be used immediately after `update_new_frame`
+    fn display_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.display_frame(spi, delay)
+    }
+
+    /// Updates and displays the new frame.
+    fn update_and_display_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.update_new_frame(spi, buffer, delay)?;
+        self.display_new_frame(spi, delay)?;
+        Ok(())
+    }
+
+    /// Writes a window of the old frame RAM (0x26), x and width need to be multiples of 8
+    fn update_partial_old_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.interface
+            .check_exact_len(buffer, (width.div_ceil(8) * height) as usize)?;
+        self.write_rams(
+            spi,
+            &[Command::WriteRam2],
+            (x, y, width, height),
+            |interface, spi| interface.data(spi, buffer),
+        )
+    }
+
+    /// Writes a window of the new frame RAM (0x24), x and width need to be multiples of 8
+    ///
+    /// Without a delay it can't switch to the quick refresh itself, use
+    /// [set_refresh](Epd2in13::set_refresh) or `update_new_frame` before.
+    fn update_partial_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.interface
+            .check_exact_len(buffer, (width.div_ceil(8) * height) as usize)?;
+        self.write_rams(
+            spi,
+            &[Command::WriteRam],
+            (x, y, width, height),
+            |interface, spi| interface.data(spi, buffer),
+        )?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    /// Clears a window of the frame like [clear_frame](WaveshareDisplay::clear_frame), x and
+    /// width need to be multiples of 8
+    fn clear_partial_frame(
+        &mut self,
+        spi: &mut SPI,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        let color = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
+        let rams = self.frame_rams();
+        self.write_rams(spi, rams, (x, y, width, height), |interface, spi| {
+            interface.data_x_times(spi, color, width.div_ceil(8) * height)
+        })?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::init_epd;
+    use crate::traits::Command as _;
+    extern crate std;
+    use std::vec;
+
+    #[test]
+    fn epd_size() {
+        assert_eq!(WIDTH, 122);
+        assert_eq!(HEIGHT, 250);
+        assert_eq!(DEFAULT_BACKGROUND_COLOR, Color::White);
+        assert_eq!(buffer_len(), 16 * 250);
+    }
+
+    #[test]
+    fn init_sequence() {
+        let (log, _spi, _delay, _epd) = init_epd::<Epd2in13<_, _, _, _, _, _>>();
+        let commands: std::vec::Vec<std::string::String> = log
+            .transcript()
+            .into_iter()
+            .filter(|entry| !entry.starts_with("wait") && !entry.starts_with("delay"))
+            .map(|entry| entry[..2].into())
+            .collect();
+        // like the init of the reference driver, with the full LUT and its voltages
+        assert_eq!(
+            commands,
+            [
+                "12", "01", "11", "44", "45", "4E", "4F", "3C", "21", "18", "32", "3F", "03", "04",
+                "2C"
+            ]
+        );
+        assert_eq!(
+            log.data_after(Command::DriverOutputControl.address()),
+            [0xF9, 0x00, 0x00]
+        );
+        // the last byte of a row holds the columns 120 and 121
+        assert_eq!(
+            log.data_after(Command::SetRamXAddressStartEndPosition.address()),
+            [0, 15]
+        );
+        assert_eq!(
+            log.data_after(Command::SetRamYAddressStartEndPosition.address()),
+            [0, 0, 249, 0]
+        );
+        assert_eq!(
+            log.data_after(Command::WriteLutRegister.address()),
+            LUT_FULL_UPDATE[..153]
+        );
+        assert_eq!(
+            log.data_after(Command::SourceDrivingVoltageControl.address()),
+            [0x41, 0x00, 0x32]
+        );
+        assert_eq!(log.data_after(Command::WriteVcomRegister.address()), [0x36]);
+    }
+
+    #[test]
+    fn refresh_modes() {
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in13<_, _, _, _, _, _>>();
+        let controls = |log: &crate::mock::Log| {
+            log.data_after_each(Command::DisplayUpdateControl2.address())
+                .concat()
+        };
+        let frame = vec![0x00; buffer_len()];
+
+        log.clear();
+        epd.update_frame(&mut spi, &frame, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        for _ in 0..3 {
+            epd.update_and_display_new_frame(&mut spi, &frame, &mut delay)
+                .unwrap();
+        }
+        // the partial LUT is only loaded once, every refresh after it is in mode 2
+        assert_eq!(controls(&log), [0xC7, 0xC0, 0x0F, 0x0F, 0x0F]);
+        assert_eq!(log.count_command(Command::WriteLutRegister.address()), 1);
+        assert_eq!(
+            log.data_after(Command::WriteLutRegister.address()),
+            LUT_PARTIAL_UPDATE[..153]
+        );
+        assert_eq!(
+            log.data_after(Command::BorderWaveformControl.address()),
+            [0x80]
+        );
+        // in the quick refresh the frames only go to the new frame
+        assert_eq!(log.count_command(Command::WriteRam.address()), 4);
+        assert_eq!(log.count_command(Command::WriteRam2.address()), 1);
+
+        // back to full refreshes the controller is initialized again
+        log.clear();
+        epd.set_refresh(&mut spi, &mut delay, RefreshLut::Full)
+            .unwrap();
+        epd.update_and_display_frame(&mut spi, &frame, &mut delay)
+            .unwrap();
+        assert_eq!(log.count_command(Command::SwReset.address()), 1);
+        assert_eq!(
+            log.data_after(Command::WriteLutRegister.address()),
+            LUT_FULL_UPDATE[..153]
+        );
+        assert_eq!(controls(&log), [0xC7]);
+    }
+
+    #[test]
+    fn partial_window_registers() {
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd2in13<_, _, _, _, _, _>>();
+
+        // the right edge ends in the byte of the columns 120 and 121
+        log.clear();
+        epd.update_partial_frame(&mut spi, &[0x00; 2 * 8], 112, 242, 10, 8)
+            .unwrap();
+        assert_eq!(
+            log.data_after(Command::SetRamXAddressStartEndPosition.address()),
+            [14, 15]
+        );
+        assert_eq!(
+            log.data_after(Command::SetRamYAddressStartEndPosition.address()),
+            [242, 0, 249, 0]
+        );
+        assert_eq!(
+            log.data_after_each(Command::SetRamXAddressCounter.address()),
+            [[14], [14]]
+        );
+
+        assert_eq!(
+            epd.update_partial_frame(&mut spi, &[0x00; 2 * 8], 112, 243, 10, 8),
+            Err(Error::OutOfBounds)
+        );
+    }
+}
@@ -26,6 +26,9 @@ pub(crate) enum Command {
     TemperatureSensorSelection = 0x41,
     VcomAndDataIntervalSetting = 0x50,
     ResolutionSetting = 0x61,
+    PartialWindow = 0x90,
+    PartialIn = 0x91,
+    PartialOut = 0x92,
     VcmDcSetting = 0x82,
     PowerSaving = 0xE3,
 }
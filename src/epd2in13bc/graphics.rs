@@ -1,74 +1,24 @@
 use crate::color::TriColor;
-use crate::epd2in13bc::{DEFAULT_BACKGROUND_COLOR, HEIGHT, NUM_DISPLAY_BITS, WIDTH};
-use crate::graphics::{DisplayRotation, TriDisplay};
-use embedded_graphics_core::prelude::*;
+use crate::display::FixedDisplay;
+use crate::epd2in13bc::{buffer_len, Epd2in13bc, HEIGHT, NUM_DISPLAY_BITS, WIDTH};
+use crate::flush::impl_flushable;
+use embedded_graphics_core::pixelcolor::BinaryColor;
 
 /// Full size buffer for use with the 2.13" b/c EPD
 ///
 /// Can also be manually constructed and be used together with VarDisplay
-pub struct Display2in13bc {
-    // one buffer for both b/w and for chromatic:
-    // * &buffer[0..NUM_DISPLAY_BITS] for b/w buffer and
-    // * &buffer[NUM_DISPLAY_BITS..2*NUM_DISPLAY_BITS] for chromatic buffer
-    buffer: [u8; 2 * NUM_DISPLAY_BITS as usize],
-    rotation: DisplayRotation,
-}
+pub type Display2in13bc = FixedDisplay<WIDTH, HEIGHT, { NUM_DISPLAY_BITS as usize }, BinaryColor>;
 
-impl Default for Display2in13bc {
-    fn default() -> Self {
-        Display2in13bc {
-            buffer: [DEFAULT_BACKGROUND_COLOR.get_byte_value(); 2 * NUM_DISPLAY_BITS as usize],
-            rotation: DisplayRotation::default(),
-        }
-    }
-}
-
-impl DrawTarget for Display2in13bc {
-    type Color = TriColor;
-    type Error = core::convert::Infallible;
-    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
-    where
-        I: IntoIterator<Item = Pixel<Self::Color>>,
-    {
-        for pixel in pixels {
-            self.draw_helper_tri(WIDTH, HEIGHT, pixel)?;
-        }
-        Ok(())
-    }
-}
-
-impl OriginDimensions for Display2in13bc {
-    fn size(&self) -> Size {
-        Size::new(WIDTH, HEIGHT)
-    }
-}
-
-impl TriDisplay for Display2in13bc {
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
-    }
-
-    fn get_mut_buffer(&mut self) -> &mut [u8] {
-        &mut self.buffer
-    }
-
-    fn set_rotation(&mut self, rotation: DisplayRotation) {
-        self.rotation = rotation;
-    }
-
-    fn rotation(&self) -> DisplayRotation {
-        self.rotation
-    }
-
-    fn chromatic_offset(&self) -> usize {
-        NUM_DISPLAY_BITS as usize
-    }
+/// Full size buffer with both the b/w and the chromatic plane for use with the 2.13" b/c EPD
+///
+/// Send it with `update_color_frame(spi, display.bw_buffer(), display.chromatic_buffer())`
+/// or refresh with it in one go with [Flush::flush](crate::flush::Flush::flush).
+pub type Display2in13bcTriColor = FixedDisplay<WIDTH, HEIGHT, { 2 * buffer_len() }, TriColor>;
 
-    fn bw_buffer(&self) -> &[u8] {
-        &self.buffer[0..self.chromatic_offset()]
-    }
+// the buffers have the frame length the driver checks for, tri-color buffers one per plane
+const _: () = assert!(Display2in13bc::BUFFER_LEN == buffer_len());
+const _: () = assert!(Display2in13bcTriColor::BUFFER_LEN == 2 * buffer_len());
 
-    fn chromatic_buffer(&self) -> &[u8] {
-        &self.buffer[self.chromatic_offset()..]
-    }
-}
+// the driver can `flush` these buffers directly
+impl_flushable!(Display2in13bc => Epd2in13bc, crate::graphics::Display);
+impl_flushable!(Display2in13bcTriColor => Epd2in13bc, tri);
@@ -1,4 +1,8 @@
 //! A simple Driver for the Waveshare 2.13" (B/C) E-Ink Display via SPI
+//!
+//! Polarity: the IL0373 shows set bits as white and cleared bits of the chromatic plane in
+//! color ([Polarity::ClearedIsColor]).
+//!
 //! More information on this display can be found at the [Waveshare Wiki](https://www.waveshare.com/wiki/2.13inch_e-Paper_HAT_(B))
 //! This driver was build and tested for 212x104, 2.13inch E-Ink display HAT for Raspberry Pi, three-color, SPI interface
 //!
@@ -6,7 +10,7 @@
 //!
 //!```rust, no_run
 //!# use embedded_hal_mock::*;
-//!# fn main() -> Result<(), MockError> {
+//!# fn main() -> Result<(), epd_waveshare::Error<MockError>> {
 //!use embedded_graphics::{prelude::*, primitives::{Line, PrimitiveStyle, PrimitiveStyleBuilder}};
 //!use epd_waveshare::{epd2in13bc::*, prelude::*};
 //!#
@@ -20,11 +24,12 @@
 //!# let mut delay = delay::MockNoop::new();
 //!
 //!// Setup EPD
-//!let mut epd = Epd2in13bc::new(&mut spi, cs_pin, busy_in, dc, rst, &mut delay)?;
+//!let mut epd = Epd2in13bc::new(cs_pin, busy_in, dc, rst);
+//!epd.init(&mut spi, &mut delay)?;
 //!
 //!// Use display graphics from embedded-graphics
 //!// This display is for the black/white/chromatic pixels
-//!let mut tricolor_display = Display2in13bc::default();
+//!let mut tricolor_display = Display2in13bcTriColor::default();
 //!
 //!// Use embedded graphics for drawing a black line
 //!let _ = Line::new(Point::new(0, 120), Point::new(0, 200))
@@ -49,15 +54,34 @@
 //!# Ok(())
 //!# }
 //!```
+//!
+//! # Partial updates of the black plane
+//!
+//! The red plane can only be refreshed with the whole panel, but
+//! [update_partial_achromatic_frame](Epd2in13bc::update_partial_achromatic_frame) writes a
+//! window of the b/w plane and the next [display_frame](WaveshareDisplay::display_frame)
+//! refreshes only that window, e.g. for a clock on a red banner.
+//! [update_partial_frame](WaveshareDisplay::update_partial_frame) does the same. The red plane in the RAM
+//! stays as it is and the border isn't driven meanwhile. The next full update sets the
+//! display back to full refreshes.
+//!
+//! Partial refreshes sometimes leave a faint red ripple in the window. After
+//! [DEFAULT_FULL_REFRESH_INTERVAL] of them the next refresh covers the whole panel again,
+//! with the content of the RAM, see
+//! [set_full_refresh_interval](Epd2in13bc::set_full_refresh_interval).
 use embedded_hal::{
     blocking::{delay::*, spi::Write},
     digital::v2::*,
 };
 
-use crate::interface::DisplayInterface;
-use crate::traits::{
-    InternalWiAdditions, RefreshLut, WaveshareDisplay, WaveshareThreeColorDisplay,
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
 };
+use crate::pattern;
+use crate::traits::DisplayState;
+use crate::traits::{WaveshareDisplay, WaveshareThreeColorDisplay};
 
 /// Width of epd2in13bc in pixels
 pub const WIDTH: u32 = 104;
@@ -65,10 +89,28 @@ pub const WIDTH: u32 = 104;
 pub const HEIGHT: u32 = 212;
 /// Default background color (white) of epd2in13bc display
 pub const DEFAULT_BACKGROUND_COLOR: TriColor = TriColor::White;
+/// Length in bytes of a full frame buffer for epd2in13bc
+///
+/// This is the size of the b/w frame, the chromatic frame has the same size
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+/// Length in bytes of the b/w plane, see [TriColorFrame](crate::color::TriColorFrame)
+pub const fn achromatic_buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+/// Length in bytes of the chromatic plane, the same as of the b/w plane
+pub const fn chromatic_buffer_len() -> usize {
+    achromatic_buffer_len()
+}
+
+/// Partial refreshes after which the next refresh covers the whole panel
+pub const DEFAULT_FULL_REFRESH_INTERVAL: u16 = 5;
 
 /// Number of bits for b/w buffer and same for chromatic buffer
 const NUM_DISPLAY_BITS: u32 = WIDTH * HEIGHT / 8;
 
+// The IL0373 pulls BUSY low while it is working
 const IS_BUSY_LOW: bool = true;
 const VCOM_DATA_INTERVAL: u8 = 0x07;
 const WHITE_BORDER: u8 = 0x70;
@@ -76,7 +118,7 @@ const BLACK_BORDER: u8 = 0x30;
 const CHROMATIC_BORDER: u8 = 0xb0;
 const FLOATING_BORDER: u8 = 0xF0;
 
-use crate::color::TriColor;
+use crate::color::{buffer_size, Color, Polarity, TriColor};
 
 pub(crate) mod command;
 use self::command::Command;
@@ -84,56 +126,21 @@ use self::command::Command;
 #[cfg(feature = "graphics")]
 mod graphics;
 #[cfg(feature = "graphics")]
-pub use self::graphics::Display2in13bc;
+pub use self::graphics::{Display2in13bc, Display2in13bcTriColor};
 
 /// Epd2in13bc driver
 pub struct Epd2in13bc<SPI, CS, BUSY, DC, RST, DELAY> {
     interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
     color: TriColor,
-}
-
-impl<SPI, CS, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, CS, BUSY, DC, RST, DELAY>
-    for Epd2in13bc<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        // Values taken from datasheet and sample code
-
-        self.interface.reset(delay, 10);
-
-        // start the booster
-        self.interface
-            .cmd_with_data(spi, Command::BoosterSoftStart, &[0x17, 0x17, 0x17])?;
-
-        // power on
-        self.command(spi, Command::PowerOn)?;
-        delay.delay_ms(5);
-        self.wait_until_idle();
-
-        // set the panel settings
-        self.cmd_with_data(spi, Command::PanelSetting, &[0x8F])?;
-
-        self.cmd_with_data(
-            spi,
-            Command::VcomAndDataIntervalSetting,
-            &[WHITE_BORDER | VCOM_DATA_INTERVAL],
-        )?;
-
-        // set resolution
-        self.send_resolution(spi)?;
-
-        self.cmd_with_data(spi, Command::VcmDcSetting, &[0x0A])?;
-
-        self.wait_until_idle();
-
-        Ok(())
-    }
+    /// Whether the panel power is on
+    powered: bool,
+    /// Border bits of the VCOM and data interval setting outside of partial mode
+    border: u8,
+    /// Whether the controller is in partial mode for a window of the b/w plane
+    partial: bool,
+    /// Partial refreshes since the last full one
+    partial_refreshes: u16,
+    full_refresh_interval: Option<u16>,
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareThreeColorDisplay<SPI, CS, BUSY, DC, RST, DELAY>
@@ -151,7 +158,7 @@ where
         spi: &mut SPI,
         black: &[u8],
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_achromatic_frame(spi, black)?;
         self.update_chromatic_frame(spi, chromatic)
     }
@@ -159,9 +166,17 @@ where
     /// Update only the black/white data of the display.
     ///
     /// Finish by calling `update_chromatic_frame`.
-    fn update_achromatic_frame(&mut self, spi: &mut SPI, black: &[u8]) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, Command::DataStartTransmission1)?;
-        self.interface.data(spi, black)?;
+    fn update_achromatic_frame(
+        &mut self,
+        spi: &mut SPI,
+        black: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(black, achromatic_buffer_len())?;
+        self.leave_partial(spi)?;
+        self.interface
+            .cmd_with_data(spi, Command::DataStartTransmission1, black)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
@@ -172,11 +187,15 @@ where
         &mut self,
         spi: &mut SPI,
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, Command::DataStartTransmission2)?;
-        self.interface.data(spi, chromatic)?;
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(chromatic, chromatic_buffer_len())?;
+        self.leave_partial(spi)?;
+        self.interface
+            .cmd_with_data(spi, Command::DataStartTransmission2, chromatic)?;
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 }
@@ -192,25 +211,70 @@ where
     DELAY: DelayMs<u8>,
 {
     type DisplayColor = TriColor;
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
+    const SUPPORTS_PARTIAL: bool = true;
+    const SUPPORTS_QUICK_REFRESH: bool = false;
+    const SUPPORTS_LUT: bool = false;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 2;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
         let interface = DisplayInterface::new(cs, busy, dc, rst);
         let color = DEFAULT_BACKGROUND_COLOR;
 
-        let mut epd = Epd2in13bc { interface, color };
+        Epd2in13bc {
+            interface,
+            color,
+            powered: false,
+            border: WHITE_BORDER,
+            partial: false,
+            partial_refreshes: 0,
+            full_refresh_interval: Some(DEFAULT_FULL_REFRESH_INTERVAL),
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        // Values taken from datasheet and sample code
+
+        self.interface.reset(delay, 10)?;
+        // IL0373: BUSY is low until the controller is ready after the reset
+        self.wait_until_idle()?;
+        self.powered = false;
+        self.partial = false;
+        self.partial_refreshes = 0;
+
+        // start the booster
+        self.interface
+            .cmd_with_data(spi, Command::BoosterSoftStart, &[0x17, 0x17, 0x17])?;
+
+        // power on
+        self.power_on(spi, delay)?;
+
+        // set the panel settings
+        self.cmd_with_data(spi, Command::PanelSetting, &[0x8F])?;
+
+        self.cmd_with_data(
+            spi,
+            Command::VcomAndDataIntervalSetting,
+            &[self.border | VCOM_DATA_INTERVAL],
+        )?;
+
+        // set resolution
+        self.send_resolution(spi)?;
+
+        self.cmd_with_data(spi, Command::VcmDcSetting, &[0x0A])?;
 
-        epd.init(spi, delay)?;
+        self.wait_until_idle()?;
+
+        Ok(())
+    }
 
-        Ok(epd)
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
     }
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.leave_partial(spi)?;
         // Section 8.2 from datasheet
         self.interface.cmd_with_data(
             spi,
@@ -218,19 +282,59 @@ where
             &[FLOATING_BORDER | VCOM_DATA_INTERVAL],
         )?;
 
-        self.command(spi, Command::PowerOff)?;
         // The example STM code from Github has a wait after PowerOff
-        self.wait_until_idle();
+        self.power_off(spi, delay)?;
 
         self.cmd_with_data(spi, Command::DeepSleep, &[0xA5])?;
 
+        self.interface.asleep()?;
         Ok(())
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
     fn set_background_color(&mut self, color: TriColor) {
         self.color = color;
     }
@@ -239,6 +343,14 @@ where
         &self.color
     }
 
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -252,22 +364,30 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = NUM_DISPLAY_BITS as usize;
+        self.interface.check_len(buffer, len)?;
+        self.leave_partial(spi)?;
+        let background = self.interface.background_byte(self.color.get_byte_value());
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
 
-        self.interface.data(spi, &buffer)?;
+        self.interface
+            .padded_frame_data(spi, buffer, len, background, Self::POLARITY)?;
 
-        // Clear the chromatic layer
-        let color = self.color.get_byte_value();
+        // Clear the chromatic layer to no color, whatever the background
+        let no_color = Color::White.to_controller_byte(Self::POLARITY);
 
         self.interface.cmd(spi, Command::DataStartTransmission2)?;
-        self.interface.data_x_times(spi, color, NUM_DISPLAY_BITS)?;
+        self.interface
+            .data_x_times(spi, no_color, NUM_DISPLAY_BITS)?;
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
-    #[allow(unused)]
+    /// Writes a window of the b/w plane like
+    /// [update_partial_achromatic_frame](Epd2in13bc::update_partial_achromatic_frame)
     fn update_partial_frame(
         &mut self,
         spi: &mut SPI,
@@ -276,29 +396,44 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        Ok(())
+    ) -> Result<(), Error<SPI::Error>> {
+        self.update_partial_achromatic_frame(spi, buffer, x, y, width, height)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.command(spi, Command::DisplayRefresh)?;
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
         Ok(())
     }
 
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.leave_partial(spi)?;
         self.send_resolution(spi)?;
 
         let color = DEFAULT_BACKGROUND_COLOR.get_byte_value();
@@ -306,27 +441,43 @@ where
         // Clear the black
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
 
-        self.interface.data_x_times(spi, color, NUM_DISPLAY_BITS)?;
+        self.interface.data_x_times(
+            spi,
+            self.interface.background_byte(color),
+            NUM_DISPLAY_BITS,
+        )?;
 
         // Clear the chromatic
         self.interface.cmd(spi, Command::DataStartTransmission2)?;
         self.interface.data_x_times(spi, color, NUM_DISPLAY_BITS)?;
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
-    fn set_lut(
-        &mut self,
-        _spi: &mut SPI,
-        _refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
-        Ok(())
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let black = pattern::bytes(WIDTH, HEIGHT, 0, pattern::halves);
+        let chromatic = pattern::bytes(WIDTH, HEIGHT, 1, pattern::halves);
+        let len = NUM_DISPLAY_BITS as usize;
+        self.leave_partial(spi)?;
+        self.interface
+            .cmd_with_generated(spi, Command::DataStartTransmission1, len, black)?;
+        self.interface
+            .cmd_with_generated(spi, Command::DataStartTransmission2, len, chromatic)?;
+
+        self.wait_until_idle()?;
+        self.interface.set_state(DisplayState::FramePending);
+        self.display_frame(spi, delay)
     }
 
-    fn is_busy(&self) -> bool {
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
         self.interface.is_busy(IS_BUSY_LOW)
     }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> Epd2in13bc<SPI, CS, BUSY, DC, RST, DELAY>
@@ -338,12 +489,43 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, command)
+    /// Turns on the booster and the panel power
+    ///
+    /// Does nothing if the power is already on.
+    /// As long as the power stays on, `display_frame` can refresh the panel
+    /// without going through a power cycle each time.
+    pub fn power_on(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        if !self.powered {
+            self.command(spi, Command::PowerOn)?;
+            let started = self.interface.now();
+            delay.delay_ms(5);
+            self.wait_until_idle()?;
+            self.interface.record_power_on(started);
+            self.powered = true;
+        }
+        Ok(())
     }
 
-    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
-        self.interface.data(spi, data)
+    /// Turns off the booster and the panel power, the displayed image stays
+    ///
+    /// Does nothing if the power is already off.
+    pub fn power_off(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.powered {
+            self.command(spi, Command::PowerOff)?;
+            let started = self.interface.now();
+            self.wait_until_idle()?;
+            self.interface.record_power_off(started);
+            self.powered = false;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd(spi, command)
     }
 
     fn cmd_with_data(
@@ -351,36 +533,287 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn wait_until_idle(&mut self) {
-        let _ = self.interface.wait_until_idle(IS_BUSY_LOW);
+    /// Writes a window of the b/w plane, which the next refresh updates on its own
+    ///
+    /// The red plane is left as it is, see the [module documentation](self). `x` is rounded
+    /// down to a multiple of 8 and `buffer` has `width.div_ceil(8) * height` bytes like the
+    /// b/w buffers.
+    pub fn update_partial_achromatic_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.interface
+            .check_exact_len(buffer, (width.div_ceil(8) * height) as usize)?;
+        self.wait_until_idle()?;
+
+        if !self.partial {
+            // keep the border as it is while only the window is refreshed
+            self.cmd_with_data(
+                spi,
+                Command::VcomAndDataIntervalSetting,
+                &[FLOATING_BORDER | VCOM_DATA_INTERVAL],
+            )?;
+            self.command(spi, Command::PartialIn)?;
+            self.partial = true;
+        }
+        self.cmd_with_data(
+            spi,
+            Command::PartialWindow,
+            &partial_window(x, y, width, height),
+        )?;
+        self.cmd_with_data(spi, Command::DataStartTransmission1, buffer)?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    /// After `interval` partial refreshes the next refresh covers the whole panel
+    ///
+    /// Clears the red ripple partial refreshes leave behind now and then,
+    /// [DEFAULT_FULL_REFRESH_INTERVAL] by default. With `None` only full updates go back to
+    /// full refreshes.
+    pub fn set_full_refresh_interval(&mut self, interval: Option<u16>) {
+        self.full_refresh_interval = interval;
+    }
+
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.power_on(spi, delay)?;
+        let due = self
+            .full_refresh_interval
+            .is_some_and(|interval| self.partial_refreshes >= interval);
+        if self.partial && !due {
+            self.partial_refreshes += 1;
+        } else {
+            self.leave_partial(spi)?;
+            self.partial_refreshes = 0;
+        }
+        self.command(spi, Command::DisplayRefresh)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
+    }
+
+    // Goes back to full refreshes with the border of set_border_color
+    fn leave_partial(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
+        if self.partial {
+            self.command(spi, Command::PartialOut)?;
+            self.cmd_with_data(
+                spi,
+                Command::VcomAndDataIntervalSetting,
+                &[self.border | VCOM_DATA_INTERVAL],
+            )?;
+            self.partial = false;
+        }
+        Ok(())
     }
 
-    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
         let w = self.width();
         let h = self.height();
 
-        self.command(spi, Command::ResolutionSetting)?;
-
-        self.send_data(spi, &[w as u8])?;
-        self.send_data(spi, &[(h >> 8) as u8])?;
-        self.send_data(spi, &[h as u8])
+        self.cmd_with_data(
+            spi,
+            Command::ResolutionSetting,
+            &[w as u8, (h >> 8) as u8, h as u8],
+        )
     }
 
     /// Set the outer border of the display to the chosen color.
-    pub fn set_border_color(&mut self, spi: &mut SPI, color: TriColor) -> Result<(), SPI::Error> {
-        let border = match color {
+    ///
+    /// In partial mode the border is only driven again by the next full update.
+    pub fn set_border_color(
+        &mut self,
+        spi: &mut SPI,
+        color: TriColor,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.border = match color {
             TriColor::Black => BLACK_BORDER,
             TriColor::White => WHITE_BORDER,
             TriColor::Chromatic => CHROMATIC_BORDER,
         };
+        if self.partial {
+            return Ok(());
+        }
         self.cmd_with_data(
             spi,
             Command::VcomAndDataIntervalSetting,
-            &[border | VCOM_DATA_INTERVAL],
+            &[self.border | VCOM_DATA_INTERVAL],
         )
     }
 }
+
+// The data of the partial window command for a window at (x, y), x rounded down to the byte
+fn partial_window(x: u32, y: u32, width: u32, height: u32) -> [u8; 7] {
+    let x_start = x & !0x07;
+    let x_end = x_start + width - 1;
+    let y_end = y + height - 1;
+    [
+        x_start as u8,
+        (x_end | 0x07) as u8,
+        (y >> 8) as u8,
+        y as u8,
+        (y_end >> 8) as u8,
+        y_end as u8,
+        // only refresh the window
+        0x01,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_frame_clears_chromatic() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in13bc<_, _, _, _, _, _>>();
+
+        // a black background does not turn the chromatic frame on
+        epd.set_background_color(TriColor::Black);
+        epd.update_frame(&mut spi, &[0x00; NUM_DISPLAY_BITS as usize], &mut delay)
+            .unwrap();
+        let chromatic = log.data_after(Command::DataStartTransmission2.address());
+        assert_eq!(chromatic.len(), NUM_DISPLAY_BITS as usize);
+        assert!(chromatic.iter().all(|&byte| byte == 0xff));
+    }
+
+    #[test]
+    fn tri_color_frames() {
+        use crate::color::TriColorFrame;
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec::Vec;
+
+        assert_eq!(achromatic_buffer_len(), NUM_DISPLAY_BITS as usize);
+        assert_eq!(chromatic_buffer_len(), achromatic_buffer_len());
+        assert_eq!(buffer_len(), achromatic_buffer_len());
+
+        // the buffer of a tri-color display holds both planes
+        let buffer: Vec<u8> = (0..2 * buffer_len()).map(|i| i as u8).collect();
+        let frame = TriColorFrame::from_buffer(&buffer, WIDTH, HEIGHT).unwrap();
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd2in13bc<_, _, _, _, _, _>>();
+        epd.update_tri_color_frame(&mut spi, frame).unwrap();
+        assert_eq!(
+            log.data_after(Command::DataStartTransmission1.address()),
+            frame.black
+        );
+        assert_eq!(
+            log.data_after(Command::DataStartTransmission2.address()),
+            frame.chromatic
+        );
+
+        assert_eq!(
+            epd.update_tri_color_frame(&mut spi, TriColorFrame::new(&[], &[]).unwrap()),
+            Err(Error::BufferTooShort {
+                min: achromatic_buffer_len(),
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn partial_achromatic_frame() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in13bc<_, _, _, _, _, _>>();
+        log.clear();
+        // 12 pixels from x = 9 on are the bytes at 8 to 23
+        epd.update_partial_achromatic_frame(&mut spi, &[0x00; 2 * 3], 9, 20, 12, 3)
+            .unwrap();
+        assert_eq!(
+            log.data_after(Command::PartialWindow.address()),
+            [8, 23, 0, 20, 0, 22, 0x01]
+        );
+        assert_eq!(
+            log.data_after(Command::DataStartTransmission1.address()),
+            [0x00; 6]
+        );
+        // the red plane is left alone
+        assert_eq!(
+            log.count_command(Command::DataStartTransmission2.address()),
+            0
+        );
+        assert_eq!(
+            epd.update_partial_achromatic_frame(&mut spi, &[0x00; 6], 100, 0, 8, 1),
+            Err(Error::OutOfBounds)
+        );
+
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PartialIn.address()), 1);
+        assert_eq!(log.count_command(Command::PartialOut.address()), 0);
+
+        // a full update goes back to full refreshes and drives the border again
+        log.clear();
+        epd.update_frame(&mut spi, &[0xff; NUM_DISPLAY_BITS as usize], &mut delay)
+            .unwrap();
+        assert_eq!(log.count_command(Command::PartialOut.address()), 1);
+        assert_eq!(
+            log.data_after(Command::VcomAndDataIntervalSetting.address()),
+            [WHITE_BORDER | VCOM_DATA_INTERVAL]
+        );
+    }
+
+    #[test]
+    fn partial_frames_of_the_trait() {
+        use crate::mock::init_epd;
+
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd2in13bc<_, _, _, _, _, _>>();
+        log.clear();
+        epd.update_partial_achromatic_frame(&mut spi, &[0x0f; 2 * 3], 8, 20, 16, 3)
+            .unwrap();
+        let expected = log.transcript();
+
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd2in13bc<_, _, _, _, _, _>>();
+        log.clear();
+        epd.update_partial_frame(&mut spi, &[0x0f; 2 * 3], 8, 20, 16, 3)
+            .unwrap();
+        assert_eq!(log.transcript(), expected);
+        assert_eq!(epd.state(), DisplayState::FramePending);
+    }
+
+    #[test]
+    fn full_refresh_after_the_interval() {
+        extern crate std;
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in13bc<_, _, _, _, _, _>>();
+        epd.set_full_refresh_interval(Some(2));
+        let mut full_refreshes = std::vec::Vec::new();
+        for _ in 0..6 {
+            log.clear();
+            epd.update_partial_achromatic_frame(&mut spi, &[0x00; 2], 0, 0, 16, 1)
+                .unwrap();
+            epd.display_frame(&mut spi, &mut delay).unwrap();
+            full_refreshes.push(log.count_command(Command::PartialOut.address()) == 1);
+        }
+        assert_eq!(full_refreshes, [false, false, true, false, false, true]);
+
+        epd.set_full_refresh_interval(None);
+        for _ in 0..6 {
+            log.clear();
+            epd.update_partial_achromatic_frame(&mut spi, &[0x00; 2], 0, 0, 16, 1)
+                .unwrap();
+            epd.display_frame(&mut spi, &mut delay).unwrap();
+            assert_eq!(log.count_command(Command::PartialOut.address()), 0);
+        }
+    }
+}
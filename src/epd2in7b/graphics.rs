@@ -1,65 +1,33 @@
-use crate::epd2in7b::{DEFAULT_BACKGROUND_COLOR, HEIGHT, WIDTH};
-use crate::graphics::{Display, DisplayRotation};
-use embedded_graphics::pixelcolor::BinaryColor;
-use embedded_graphics_core::prelude::*;
+use crate::color::{buffer_size, TriColor};
+use crate::display::FixedDisplay;
+use crate::epd2in7b::{buffer_len, Epd2in7b, HEIGHT, WIDTH};
+use crate::flush::impl_flushable;
+use embedded_graphics_core::pixelcolor::BinaryColor;
 
 /// Full size buffer for use with the 2in7B EPD
 ///
-/// Can also be manuall constructed:
-/// `buffer: [DEFAULT_BACKGROUND_COLOR.get_byte_value(); WIDTH * HEIGHT / 8]`
-pub struct Display2in7b {
-    buffer: [u8; WIDTH as usize * HEIGHT as usize / 8],
-    rotation: DisplayRotation,
-}
-
-impl Default for Display2in7b {
-    fn default() -> Self {
-        Display2in7b {
-            buffer: [DEFAULT_BACKGROUND_COLOR.get_byte_value();
-                WIDTH as usize * HEIGHT as usize / 8],
-            rotation: DisplayRotation::default(),
-        }
-    }
-}
-
-impl DrawTarget for Display2in7b {
-    type Color = BinaryColor;
-    type Error = core::convert::Infallible;
-
-    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
-    where
-        I: IntoIterator<Item = Pixel<Self::Color>>,
-    {
-        for pixel in pixels {
-            self.draw_helper(WIDTH, HEIGHT, pixel)?;
-        }
-        Ok(())
-    }
-}
-
-impl OriginDimensions for Display2in7b {
-    fn size(&self) -> Size {
-        Size::new(WIDTH, HEIGHT)
-    }
-}
-
-impl Display for Display2in7b {
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
-    }
-
-    fn get_mut_buffer(&mut self) -> &mut [u8] {
-        &mut self.buffer
-    }
+/// Can also be manually constructed:
+/// `Display2in7b::from_buffer([DEFAULT_BACKGROUND_COLOR.get_byte_value(); buffer_len()])`
+pub type Display2in7b = FixedDisplay<WIDTH, HEIGHT, { buffer_len() }, BinaryColor>;
 
-    fn set_rotation(&mut self, rotation: DisplayRotation) {
-        self.rotation = rotation;
-    }
-
-    fn rotation(&self) -> DisplayRotation {
-        self.rotation
-    }
-}
+/// Full size buffer with both the b/w and the chromatic plane for use with the 2in7B EPD
+///
+/// Send it with `update_color_frame(spi, display.bw_buffer(), display.chromatic_buffer())`
+/// or refresh with it in one go with [Flush::flush](crate::flush::Flush::flush).
+pub type Display2in7bTriColor = FixedDisplay<
+    WIDTH,
+    HEIGHT,
+    { buffer_size::<TriColor>(WIDTH as usize, HEIGHT as usize) },
+    TriColor,
+>;
+
+// the buffers have the frame length the driver checks for, tri-color buffers one per plane
+const _: () = assert!(Display2in7b::BUFFER_LEN == buffer_len());
+const _: () = assert!(Display2in7bTriColor::BUFFER_LEN == 2 * buffer_len());
+
+// the driver can `flush` these buffers directly
+impl_flushable!(Display2in7b => Epd2in7b, crate::graphics::Display);
+impl_flushable!(Display2in7bTriColor => Epd2in7b, tri);
 
 #[cfg(test)]
 mod tests {
@@ -172,4 +140,27 @@ mod tests {
             assert_eq!(byte, epd2in7b::DEFAULT_BACKGROUND_COLOR.get_byte_value());
         }
     }
+
+    #[test]
+    fn tri_color_planes() {
+        use crate::color::TriColor;
+        use crate::graphics::TriDisplay;
+        use embedded_graphics::primitives::Rectangle;
+
+        let mut display = Display2in7bTriColor::default();
+        let _ = Rectangle::new(Point::new(0, 0), Size::new(8, 1))
+            .into_styled(PrimitiveStyle::with_fill(TriColor::Black))
+            .draw(&mut display);
+        let _ = Rectangle::new(Point::new(8, 0), Size::new(8, 1))
+            .into_styled(PrimitiveStyle::with_fill(TriColor::Chromatic))
+            .draw(&mut display);
+
+        // chromatic pixels are white in the b/w plane
+        assert_eq!(display.bw_buffer()[..2], [0x00, 0xff]);
+        assert_eq!(display.chromatic_buffer()[..2], [0xff, 0x00]);
+        assert!(display.bw_buffer()[2..].iter().all(|&byte| byte == 0xff));
+        assert!(display.chromatic_buffer()[2..]
+            .iter()
+            .all(|&byte| byte == 0xff));
+    }
 }
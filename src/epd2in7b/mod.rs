@@ -1,5 +1,8 @@
 //! A simple Driver for the Waveshare 2.7" B Tri-Color E-Ink Display via SPI
 //!
+//! Polarity: the IL91874 shows set bits as black and set bits of the red plane as red,
+//! the driver flips the buffers while sending ([Polarity::SetIsColor]).
+//!
 //! [Documentation](https://www.waveshare.com/wiki/2.7inch_e-Paper_HAT_(B))
 
 use embedded_hal::{
@@ -7,10 +10,14 @@ use embedded_hal::{
     digital::v2::*,
 };
 
-use crate::interface::DisplayInterface;
-use crate::traits::{
-    InternalWiAdditions, RefreshLut, WaveshareDisplay, WaveshareThreeColorDisplay,
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
 };
+use crate::pattern;
+use crate::traits::DisplayState;
+use crate::traits::{RefreshLut, WaveshareDisplay, WaveshareThreeColorDisplay};
 
 // The Lookup Tables for the Display
 mod constants;
@@ -22,9 +29,24 @@ pub const WIDTH: u32 = 176;
 pub const HEIGHT: u32 = 264;
 /// Default Background Color
 pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full frame buffer for epd2in7b
+///
+/// This is the size of the b/w frame, the chromatic frame has the same size
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+/// Length in bytes of the b/w plane, see [TriColorFrame](crate::color::TriColorFrame)
+pub const fn achromatic_buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+/// Length in bytes of the chromatic plane, the same as of the b/w plane
+pub const fn chromatic_buffer_len() -> usize {
+    achromatic_buffer_len()
+}
+// The IL91874 pulls BUSY low while it is working
 const IS_BUSY_LOW: bool = true;
 
-use crate::color::Color;
+use crate::color::{buffer_size, Color, Polarity};
 
 pub(crate) mod command;
 use self::command::Command;
@@ -32,7 +54,7 @@ use self::command::Command;
 #[cfg(feature = "graphics")]
 mod graphics;
 #[cfg(feature = "graphics")]
-pub use self::graphics::Display2in7b;
+pub use self::graphics::{Display2in7b, Display2in7bTriColor};
 
 /// Epd2in7b driver
 pub struct Epd2in7b<SPI, CS, BUSY, DC, RST, DELAY> {
@@ -40,9 +62,11 @@ pub struct Epd2in7b<SPI, CS, BUSY, DC, RST, DELAY> {
     interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
     /// Background Color
     color: Color,
+    /// Whether the panel power is on
+    powered: bool,
 }
 
-impl<SPI, CS, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, CS, BUSY, DC, RST, DELAY>
+impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
     for Epd2in7b<SPI, CS, BUSY, DC, RST, DELAY>
 where
     SPI: Write<u8>,
@@ -52,14 +76,34 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    type DisplayColor = Color;
+    const SUPPORTS_PARTIAL: bool = true;
+    const SUPPORTS_QUICK_REFRESH: bool = false;
+    const SUPPORTS_LUT: bool = true;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 2;
+    const POLARITY: Polarity = Polarity::SetIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
+        let interface = DisplayInterface::new(cs, busy, dc, rst);
+        let color = DEFAULT_BACKGROUND_COLOR;
+
+        Epd2in7b {
+            interface,
+            color,
+            powered: false,
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // reset the device
-        self.interface.reset(delay, 2);
+        self.interface.reset(delay, 2)?;
+        // IL91874: BUSY is low until the controller is ready after the reset
+        self.wait_until_idle()?;
+        self.powered = false;
 
         // power on
-        self.command(spi, Command::PowerOn)?;
-        delay.delay_ms(5);
-        self.wait_until_idle();
+        self.power_on(spi, delay)?;
 
         // set panel settings, 0xbf is bw, 0xaf is multi-color
         self.interface
@@ -103,53 +147,27 @@ where
         self.interface
             .cmd_with_data(spi, Command::PartialDisplayRefresh, &[0x00])?;
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
         Ok(())
     }
-}
-
-impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
-    for Epd2in7b<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    type DisplayColor = Color;
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
-        let interface = DisplayInterface::new(cs, busy, dc, rst);
-        let color = DEFAULT_BACKGROUND_COLOR;
-
-        let mut epd = Epd2in7b { interface, color };
 
-        epd.init(spi, delay)?;
-
-        Ok(epd)
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         self.interface
             .cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[0xf7])?;
 
-        self.command(spi, Command::PowerOff)?;
-        self.wait_until_idle();
+        self.power_off(spi, delay)?;
         self.interface
             .cmd_with_data(spi, Command::DeepSleep, &[0xA5])?;
+        self.interface.asleep()?;
         Ok(())
     }
 
@@ -158,16 +176,23 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
+        let background = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
-        self.send_buffer_helper(spi, buffer)?;
+        self.interface
+            .padded_frame_data(spi, buffer, buffer_len(), background, Self::POLARITY)?;
 
-        // Clear chromatic layer since we won't be using it here
+        // Clear the chromatic layer to no color, whatever the background
+        let no_color = Color::White.to_controller_byte(Self::POLARITY);
         self.interface.cmd(spi, Command::DataStartTransmission2)?;
         self.interface
-            .data_x_times(spi, !self.color.get_byte_value(), WIDTH * HEIGHT / 8)?;
+            .data_x_times(spi, no_color, WIDTH * HEIGHT / 8)?;
 
         self.interface.cmd(spi, Command::DataStop)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
@@ -179,59 +204,137 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        self.interface
-            .cmd(spi, Command::PartialDataStartTransmission1)?;
-
-        self.send_data(spi, &[(x >> 8) as u8])?;
-        self.send_data(spi, &[(x & 0xf8) as u8])?;
-        self.send_data(spi, &[(y >> 8) as u8])?;
-        self.send_data(spi, &[(y & 0xff) as u8])?;
-        self.send_data(spi, &[(width >> 8) as u8])?;
-        self.send_data(spi, &[(width & 0xf8) as u8])?;
-        self.send_data(spi, &[(height >> 8) as u8])?;
-        self.send_data(spi, &[(height & 0xff) as u8])?;
-        self.wait_until_idle();
-
-        self.send_buffer_helper(spi, buffer)?;
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = (width.div_ceil(8) * height) as usize;
+        self.interface.check_len(buffer, len)?;
+        self.write_partial(spi, buffer, x, y, width, height)
+    }
 
-        self.interface.cmd(spi, Command::DataStop)
+    fn clear_area(
+        &mut self,
+        spi: &mut SPI,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.write_partial(spi, &[], x, y, width, height)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.command(spi, Command::DisplayRefresh)?;
-        self.wait_until_idle();
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
+        self.wait_until_idle()?;
         Ok(())
     }
 
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.command(spi, Command::DisplayRefresh)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
 
-        let color_value = self.color.get_byte_value();
+        let bw_value = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
         self.interface
-            .data_x_times(spi, color_value, WIDTH * HEIGHT / 8)?;
+            .data_x_times(spi, bw_value, WIDTH * HEIGHT / 8)?;
 
         self.interface.cmd(spi, Command::DataStop)?;
 
+        // no color in the chromatic frame, whatever the background
         self.interface.cmd(spi, Command::DataStartTransmission2)?;
-        self.interface
-            .data_x_times(spi, color_value, WIDTH * HEIGHT / 8)?;
+        self.interface.data_x_times(
+            spi,
+            Color::White.to_controller_byte(Self::POLARITY),
+            WIDTH * HEIGHT / 8,
+        )?;
         self.interface.cmd(spi, Command::DataStop)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let black = pattern::bytes(WIDTH, HEIGHT, 0, pattern::halves);
+        let chromatic = pattern::bytes(WIDTH, HEIGHT, 1, pattern::halves);
+        self.interface.cmd(spi, Command::DataStartTransmission1)?;
+        self.interface
+            .generated_frame_data(spi, buffer_len(), black, Self::POLARITY)?;
+        self.interface.cmd(spi, Command::DataStop)?;
+
+        // The chromatic frame is flipped like in update_chromatic_frame
+        self.interface.cmd(spi, Command::DataStartTransmission2)?;
+        self.interface
+            .data_generated(spi, buffer_len(), chromatic, |byte| [!byte])?;
+        self.interface.cmd(spi, Command::DataStop)?;
+        self.wait_until_idle()?;
+
+        self.interface.set_state(DisplayState::FramePending);
+        self.display_frame(spi, delay)
+    }
+
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
     fn set_background_color(&mut self, color: Color) {
         self.color = color;
     }
@@ -240,6 +343,14 @@ where
         &self.color
     }
 
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -251,9 +362,13 @@ where
     fn set_lut(
         &mut self,
         spi: &mut SPI,
-        _refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+        refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>> {
+        // only the full tables
+        if refresh_rate == Some(RefreshLut::Quick) {
+            return Err(Error::Unsupported);
+        }
+        self.wait_until_idle()?;
         self.cmd_with_data(spi, Command::LutForVcom, &LUT_VCOM_DC)?;
         self.cmd_with_data(spi, Command::LutWhiteToWhite, &LUT_WW)?;
         self.cmd_with_data(spi, Command::LutBlackToWhite, &LUT_BW)?;
@@ -262,9 +377,13 @@ where
         Ok(())
     }
 
-    fn is_busy(&self) -> bool {
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
         self.interface.is_busy(IS_BUSY_LOW)
     }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareThreeColorDisplay<SPI, CS, BUSY, DC, RST, DELAY>
@@ -282,7 +401,7 @@ where
         spi: &mut SPI,
         black: &[u8],
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_achromatic_frame(spi, black)?;
         self.update_chromatic_frame(spi, chromatic)
     }
@@ -294,12 +413,16 @@ where
         &mut self,
         spi: &mut SPI,
         achromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(achromatic, achromatic_buffer_len())?;
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
 
-        self.send_buffer_helper(spi, achromatic)?;
+        self.interface.frame_data(spi, achromatic, Self::POLARITY)?;
 
-        self.interface.cmd(spi, Command::DataStop)
+        self.interface.cmd(spi, Command::DataStop)?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
     }
 
     /// Update only chromatic data of the display.
@@ -309,14 +432,17 @@ where
         &mut self,
         spi: &mut SPI,
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(chromatic, chromatic_buffer_len())?;
         self.interface.cmd(spi, Command::DataStartTransmission2)?;
 
         self.send_buffer_helper(spi, chromatic)?;
 
         self.interface.cmd(spi, Command::DataStop)?;
-        self.wait_until_idle();
+        self.wait_until_idle()?;
 
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 }
@@ -330,34 +456,130 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
+    /// Turns on the booster and the panel power
+    ///
+    /// Does nothing if the power is already on.
+    /// As long as the power stays on, `display_frame` can refresh the panel
+    /// without going through a power cycle each time.
+    pub fn power_on(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        if !self.powered {
+            self.command(spi, Command::PowerOn)?;
+            let started = self.interface.now();
+            delay.delay_ms(5);
+            self.wait_until_idle()?;
+            self.interface.record_power_on(started);
+            self.powered = true;
+        }
+        Ok(())
+    }
+
+    /// Turns off the booster and the panel power, the displayed image stays
+    ///
+    /// Does nothing if the power is already off.
+    pub fn power_off(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.powered {
+            self.command(spi, Command::PowerOff)?;
+            let started = self.interface.now();
+            self.wait_until_idle()?;
+            self.interface.record_power_off(started);
+            self.powered = false;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd(spi, command)
     }
 
-    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
+    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.interface.data(spi, data)
     }
 
-    fn send_buffer_helper(&mut self, spi: &mut SPI, buffer: &[u8]) -> Result<(), SPI::Error> {
-        // Based on the waveshare implementation, all data for color values is flipped. This helper
-        // method makes that transmission easier
-        for b in buffer.iter() {
-            self.send_data(spi, &[!b])?;
-        }
+    // Sends the window of update_partial_frame, `buffer` padded to it with the background
+    fn write_partial(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = (width.div_ceil(8) * height) as usize;
+        self.interface
+            .cmd(spi, Command::PartialDataStartTransmission1)?;
+
+        self.send_window(spi, x, y, width, height)?;
+        self.wait_until_idle()?;
+
+        let background = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
+        self.interface
+            .padded_frame_data(spi, buffer, len, background, Self::POLARITY)?;
+
+        self.interface.cmd(spi, Command::DataStop)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
+    // The position and size of a partial window, x and the width are multiples of 8
+    fn send_window(
+        &mut self,
+        spi: &mut SPI,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.send_data(
+            spi,
+            &[
+                (x >> 8) as u8,
+                (x & 0xf8) as u8,
+                (y >> 8) as u8,
+                (y & 0xff) as u8,
+                (width >> 8) as u8,
+                (width & 0xf8) as u8,
+                (height >> 8) as u8,
+                (height & 0xff) as u8,
+            ],
+        )
+    }
+
+    fn send_buffer_helper(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        // Based on the waveshare implementation the chromatic frame is flipped like the b/w
+        // frame, see POLARITY
+        self.interface.data_inverted(spi, buffer)
+    }
+
     fn cmd_with_data(
         &mut self,
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn wait_until_idle(&mut self) {
-        let _ = self.interface.wait_until_idle(IS_BUSY_LOW);
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.power_on(spi, delay)?;
+        self.command(spi, Command::DisplayRefresh)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
     }
 
     /// Refresh display for partial frame
@@ -368,17 +590,12 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
         self.command(spi, Command::PartialDisplayRefresh)?;
-        self.send_data(spi, &[(x >> 8) as u8])?;
-        self.send_data(spi, &[(x & 0xf8) as u8])?;
-        self.send_data(spi, &[(y >> 8) as u8])?;
-        self.send_data(spi, &[(y & 0xff) as u8])?;
-        self.send_data(spi, &[(width >> 8) as u8])?;
-        self.send_data(spi, &[(width & 0xf8) as u8])?;
-        self.send_data(spi, &[(height >> 8) as u8])?;
-        self.send_data(spi, &[(height & 0xff) as u8])?;
-        self.wait_until_idle();
+        self.send_window(spi, x, y, width, height)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        self.wait_until_idle()?;
         Ok(())
     }
 
@@ -391,23 +608,13 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface
             .cmd(spi, Command::PartialDataStartTransmission1)?;
-        self.send_data(spi, &[(x >> 8) as u8])?;
-        self.send_data(spi, &[(x & 0xf8) as u8])?;
-        self.send_data(spi, &[(y >> 8) as u8])?;
-        self.send_data(spi, &[(y & 0xff) as u8])?;
-        self.send_data(spi, &[(width >> 8) as u8])?;
-        self.send_data(spi, &[(width & 0xf8) as u8])?;
-        self.send_data(spi, &[(height >> 8) as u8])?;
-        self.send_data(spi, &[(height & 0xff) as u8])?;
-        self.wait_until_idle();
-
-        for b in achromatic.iter() {
-            // Flipping based on waveshare implementation
-            self.send_data(spi, &[!b])?;
-        }
+        self.send_window(spi, x, y, width, height)?;
+        self.wait_until_idle()?;
+
+        self.send_buffer_helper(spi, achromatic)?;
 
         Ok(())
     }
@@ -421,23 +628,13 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface
             .cmd(spi, Command::PartialDataStartTransmission2)?;
-        self.send_data(spi, &[(x >> 8) as u8])?;
-        self.send_data(spi, &[(x & 0xf8) as u8])?;
-        self.send_data(spi, &[(y >> 8) as u8])?;
-        self.send_data(spi, &[(y & 0xff) as u8])?;
-        self.send_data(spi, &[(width >> 8) as u8])?;
-        self.send_data(spi, &[(width & 0xf8) as u8])?;
-        self.send_data(spi, &[(height >> 8) as u8])?;
-        self.send_data(spi, &[(height & 0xff) as u8])?;
-        self.wait_until_idle();
-
-        for b in chromatic.iter() {
-            // Flipping based on waveshare implementation
-            self.send_data(spi, &[!b])?;
-        }
+        self.send_window(spi, x, y, width, height)?;
+        self.wait_until_idle()?;
+
+        self.send_buffer_helper(spi, chromatic)?;
 
         Ok(())
     }
@@ -452,5 +649,102 @@ mod tests {
         assert_eq!(WIDTH, 176);
         assert_eq!(HEIGHT, 264);
         assert_eq!(DEFAULT_BACKGROUND_COLOR, Color::White);
+        assert_eq!(buffer_len(), 176 / 8 * 264);
+        assert_eq!(achromatic_buffer_len(), buffer_len());
+        assert_eq!(chromatic_buffer_len(), buffer_len());
+    }
+
+    #[test]
+    fn capabilities() {
+        use crate::mock::{BusyPin, Delay, Pin, Spi};
+        type Epd = Epd2in7b<Spi, Pin, BusyPin, Pin, Pin, Delay>;
+
+        let capabilities = (
+            Epd::SUPPORTS_PARTIAL,
+            Epd::SUPPORTS_QUICK_REFRESH,
+            Epd::SUPPORTS_GRAYSCALE,
+            Epd::COLOR_PLANES,
+        );
+        assert_eq!(capabilities, (true, false, false, 2));
+        assert_eq!(Epd::POLARITY, Polarity::SetIsColor);
+    }
+
+    #[test]
+    fn frames_are_sent_flipped() {
+        use crate::mock::{init_epd, Transfer};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in7b<_, _, _, _, _, _>>();
+        log.clear();
+
+        // a single black pixel in the top left corner
+        let mut buffer = vec![Color::White.get_byte_value(); buffer_len()];
+        buffer[0] = 0x7f;
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        let mut expected = vec![0x00; buffer_len()];
+        expected[0] = 0x80;
+        let transfers = log.transfers();
+        assert_eq!(
+            transfers[..2],
+            [
+                Transfer::Command(Command::DataStartTransmission1.address()),
+                Transfer::Data(expected)
+            ]
+        );
+
+        // white is a cleared bit in both frames, black a set bit in the b/w frame. The
+        // chromatic frame stays without color for either background.
+        for &(color, bw, chromatic) in
+            [(Color::White, 0x00, 0x00), (Color::Black, 0xff, 0x00)].iter()
+        {
+            epd.set_background_color(color);
+            log.clear();
+            epd.clear_frame(&mut spi, &mut delay).unwrap();
+            let transfers = log.transfers();
+            let frames: std::vec::Vec<_> = transfers
+                .iter()
+                .filter(|t| matches!(t, Transfer::Data(data) if data.len() == buffer_len()))
+                .collect();
+            assert_eq!(
+                frames,
+                [
+                    &Transfer::Data(vec![bw; buffer_len()]),
+                    &Transfer::Data(vec![chromatic; buffer_len()])
+                ]
+            );
+        }
+
+        // the window in one write, the flipped frame in chunks
+        log.clear();
+        epd.update_partial_achromatic_frame(&mut spi, &[0xf0; 200], 8, 16, 80, 20)
+            .unwrap();
+        let mut expected = vec![0, 8, 0, 16, 0, 80, 0, 20];
+        expected.extend_from_slice(&[0x0f; 200]);
+        assert_eq!(
+            log.transfers(),
+            [
+                Transfer::Command(Command::PartialDataStartTransmission1.address()),
+                Transfer::Data(expected)
+            ]
+        );
+        assert_eq!(log.writes(), 1 + 1 + 2);
+    }
+
+    #[test]
+    fn update_frame_clears_chromatic() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in7b<_, _, _, _, _, _>>();
+
+        // a black background does not turn the chromatic frame on
+        epd.set_background_color(Color::Black);
+        epd.update_frame(&mut spi, &[0x00; buffer_len()], &mut delay)
+            .unwrap();
+        let chromatic = log.data_after(Command::DataStartTransmission2.address());
+        assert_eq!(chromatic.len(), buffer_len());
+        assert!(chromatic.iter().all(|&byte| byte == 0x00));
     }
 }
@@ -1,11 +1,14 @@
 //! A simple Driver for the Waveshare 2.9" E-Ink Display via SPI
 //!
+//! Polarity: the IL3820 shows a set bit as white, the buffers are sent as they are
+//! ([Polarity::ClearedIsColor]).
+//!
 //!
 //! # Example for the 2.9 in E-Ink Display
 //!
 //!```rust, no_run
 //!# use embedded_hal_mock::*;
-//!# fn main() -> Result<(), MockError> {
+//!# fn main() -> Result<(), epd_waveshare::Error<MockError>> {
 //!use embedded_graphics::{
 //!    pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
 //!};
@@ -21,7 +24,8 @@
 //!# let mut delay = delay::MockNoop::new();
 //!
 //!// Setup EPD
-//!let mut epd = Epd2in9::new(&mut spi, cs_pin, busy_in, dc, rst, &mut delay)?;
+//!let mut epd = Epd2in9::new(cs_pin, busy_in, dc, rst);
+//!epd.init(&mut spi, &mut delay)?;
 //!
 //!// Use display graphics from embedded-graphics
 //!let mut display = Display2in9::default();
@@ -40,6 +44,13 @@
 //!# Ok(())
 //!# }
 //!```
+//!
+//! # Mounted upside down
+//!
+//! [Epd2in9::new_with_flip] mirrors the image in the controller with the data entry mode,
+//! the buffers and windows stay the same. The IL3820 only runs its x counter over bytes
+//! backwards, so for [HardwareFlip::MirrorX] and [HardwareFlip::MirrorXY] the driver
+//! reverses the bits of every byte while sending, without a copy of the frame.
 
 /// Width of epd2in9 in pixels
 pub const WIDTH: u32 = 128;
@@ -47,6 +58,11 @@ pub const WIDTH: u32 = 128;
 pub const HEIGHT: u32 = 296;
 /// Default Background Color (white)
 pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full frame buffer for epd2in9
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+// The IL3820 pulls BUSY high while it is working
 const IS_BUSY_LOW: bool = false;
 
 use embedded_hal::{
@@ -57,13 +73,19 @@ use embedded_hal::{
 use crate::type_a::{
     command::Command,
     constants::{LUT_FULL_UPDATE, LUT_PARTIAL_UPDATE},
+    RamLayout,
 };
 
-use crate::color::Color;
+use crate::color::{buffer_size, Color, Polarity};
 
 use crate::traits::*;
 
-use crate::interface::DisplayInterface;
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
+};
+use crate::pattern;
 
 #[cfg(feature = "graphics")]
 mod graphics;
@@ -79,6 +101,8 @@ pub struct Epd2in9<SPI, CS, BUSY, DC, RST, DELAY> {
     background_color: Color,
     /// Refresh LUT
     refresh: RefreshLut,
+    /// Data entry mode and RAM addresses of the hardware flip
+    layout: RamLayout,
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> Epd2in9<SPI, CS, BUSY, DC, RST, DELAY>
@@ -90,10 +114,60 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.interface.reset(delay, 10);
+    /// Creates a new driver which mirrors the image in the controller
+    ///
+    /// Like [new](WaveshareDisplay::new), the coordinates of the buffers and windows stay
+    /// top-left based, see the [module documentation](self).
+    pub fn new_with_flip(cs: CS, busy: BUSY, dc: DC, rst: RST, flip: HardwareFlip) -> Self {
+        Epd2in9 {
+            layout: RamLayout::new(WIDTH, HEIGHT, flip),
+            ..Self::new(cs, busy, dc, rst)
+        }
+    }
+}
 
-        self.wait_until_idle();
+impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
+    for Epd2in9<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    type DisplayColor = Color;
+    const SUPPORTS_PARTIAL: bool = true;
+    const SUPPORTS_QUICK_REFRESH: bool = true;
+    const SUPPORTS_LUT: bool = true;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 1;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn width(&self) -> u32 {
+        WIDTH
+    }
+
+    fn height(&self) -> u32 {
+        HEIGHT
+    }
+
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
+        let interface = DisplayInterface::new(cs, busy, dc, rst);
+
+        Epd2in9 {
+            interface,
+            background_color: DEFAULT_BACKGROUND_COLOR,
+            refresh: RefreshLut::Full,
+            layout: RamLayout::new(WIDTH, HEIGHT, HardwareFlip::Normal),
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.reset(delay, 10)?;
+
+        // IL3820: BUSY is high while the controller loads its settings after the reset
+        self.wait_until_idle()?;
 
         // 3 Databytes:
         // A[7:0]
@@ -125,64 +199,32 @@ where
 
         // One Databyte with default value 0x03
         //  -> address: x increment, y increment, address counter is updated in x direction
-        self.interface
-            .cmd_with_data(spi, Command::DataEntryModeSetting, &[0x03])?;
+        //  the mirrored directions decrement
+        self.interface.cmd_with_data(
+            spi,
+            Command::DataEntryModeSetting,
+            &[self.layout.data_entry_mode()],
+        )?;
 
         self.set_lut(spi, None)
     }
-}
-
-impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
-    for Epd2in9<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    type DisplayColor = Color;
-    fn width(&self) -> u32 {
-        WIDTH
-    }
 
-    fn height(&self) -> u32 {
-        HEIGHT
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
     }
 
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
-        let interface = DisplayInterface::new(cs, busy, dc, rst);
-
-        let mut epd = Epd2in9 {
-            interface,
-            background_color: DEFAULT_BACKGROUND_COLOR,
-            refresh: RefreshLut::Full,
-        };
-
-        epd.init(spi, delay)?;
-
-        Ok(epd)
-    }
-
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         // 0x00 for Normal mode (Power on Reset), 0x01 for Deep Sleep Mode
         //TODO: is 0x00 needed here? (see also epd1in54)
         self.interface
             .cmd_with_data(spi, Command::DeepSleepMode, &[0x00])?;
+        self.interface.asleep()?;
         Ok(())
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         self.init(spi, delay)?;
         Ok(())
     }
@@ -192,12 +234,24 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
+        self.wait_until_idle()?;
         self.use_full_frame(spi)?;
 
-        self.interface
-            .cmd_with_data(spi, Command::WriteRam, buffer)?;
+        let background = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
+        self.interface.cmd(spi, Command::WriteRam)?;
+        self.interface.padded_frame_data_reversed(
+            spi,
+            buffer,
+            buffer_len(),
+            background,
+            Self::POLARITY,
+            self.layout.reverses_bits(),
+        )?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
@@ -210,54 +264,130 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        self.set_ram_area(spi, x, y, x + width, y + height)?;
-        self.set_ram_counter(spi, x, y)?;
-
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = (width.div_ceil(8) * height) as usize;
         self.interface
-            .cmd_with_data(spi, Command::WriteRam, buffer)?;
-        Ok(())
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.interface.check_len(buffer, len)?;
+        self.write_partial(spi, buffer, x, y, width, height)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        // enable clock signal, enable cp, display pattern -> 0xC4 (tested with the arduino version)
-        //TODO: test control_1 or control_2 with default value 0xFF (from the datasheet)
+    fn clear_area(
+        &mut self,
+        spi: &mut SPI,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface
-            .cmd_with_data(spi, Command::DisplayUpdateControl2, &[0xC4])?;
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.write_partial(spi, &[], x, y, width, height)
+    }
 
-        self.interface.cmd(spi, Command::MasterActivation)?;
-        // MASTER Activation should not be interupted to avoid currption of panel images
-        // therefore a terminate command is send
-        self.interface.cmd(spi, Command::Nop)?;
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
         Ok(())
     }
 
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         self.use_full_frame(spi)?;
 
         // clear the ram with the background color
-        let color = self.background_color.get_byte_value();
+        let color = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
 
         self.interface.cmd(spi, Command::WriteRam)?;
         self.interface
             .data_x_times(spi, color, WIDTH / 8 * HEIGHT)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        self.use_full_frame(spi)?;
+
+        let checkerboard = pattern::bytes(WIDTH, HEIGHT, 0, pattern::checkerboard);
+        let layout = self.layout;
+        self.interface.cmd(spi, Command::WriteRam)?;
+        self.interface.generated_frame_data(
+            spi,
+            buffer_len(),
+            |i| layout.byte(checkerboard(i)),
+            Self::POLARITY,
+        )?;
+        self.interface.set_state(DisplayState::FramePending);
+        self.display_frame(spi, delay)
+    }
+
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
     fn set_background_color(&mut self, background_color: Color) {
         self.background_color = background_color;
     }
@@ -266,11 +396,19 @@ where
         &self.background_color
     }
 
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
     fn set_lut(
         &mut self,
         spi: &mut SPI,
         refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         if let Some(refresh_lut) = refresh_rate {
             self.refresh = refresh_lut;
         }
@@ -280,9 +418,13 @@ where
         }
     }
 
-    fn is_busy(&self) -> bool {
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
         self.interface.is_busy(IS_BUSY_LOW)
     }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> Epd2in9<SPI, CS, BUSY, DC, RST, DELAY>
@@ -294,11 +436,31 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn wait_until_idle(&mut self) {
-        let _ = self.interface.wait_until_idle(IS_BUSY_LOW);
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        // enable clock signal, enable cp, display pattern -> 0xC4 (tested with the arduino version)
+        //TODO: test control_1 or control_2 with default value 0xFF (from the datasheet)
+        self.interface
+            .cmd_with_data(spi, Command::DisplayUpdateControl2, &[0xC4])?;
+
+        self.interface.cmd(spi, Command::MasterActivation)?;
+        // MASTER Activation should not be interupted to avoid currption of panel images
+        // therefore a terminate command is send
+        self.interface.cmd(spi, Command::Nop)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
     }
 
-    fn use_full_frame(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    fn use_full_frame(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
         // choose full frame/ram
         self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
 
@@ -306,6 +468,37 @@ where
         self.set_ram_counter(spi, 0, 0)
     }
 
+    // Sends the window of update_partial_frame, `buffer` padded to it with the background
+    fn write_partial(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = (width.div_ceil(8) * height) as usize;
+        self.wait_until_idle()?;
+        self.set_ram_area(spi, x, y, x + width - 1, y + height - 1)?;
+        self.set_ram_counter(spi, x, y)?;
+
+        let background = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
+        self.interface.cmd(spi, Command::WriteRam)?;
+        self.interface.padded_frame_data_reversed(
+            spi,
+            buffer,
+            len,
+            background,
+            Self::POLARITY,
+            self.layout.reverses_bits(),
+        )?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
     fn set_ram_area(
         &mut self,
         spi: &mut SPI,
@@ -313,50 +506,50 @@ where
         start_y: u32,
         end_x: u32,
         end_y: u32,
-    ) -> Result<(), SPI::Error> {
-        assert!(start_x < end_x);
-        assert!(start_y < end_y);
+    ) -> Result<(), Error<SPI::Error>> {
+        assert!(start_x <= end_x);
+        assert!(start_y <= end_y);
 
         // x is positioned in bytes, so the last 3 bits which show the position inside a byte in the ram
-        // aren't relevant
+        // aren't relevant. Mirrored the start is the higher address.
         self.interface.cmd_with_data(
             spi,
             Command::SetRamXAddressStartEndPosition,
-            &[(start_x >> 3) as u8, (end_x >> 3) as u8],
+            &[self.layout.x_address(start_x), self.layout.x_address(end_x)],
         )?;
 
         // 2 Databytes: A[7:0] & 0..A[8] for each - start and end
+        let [start_low, start_high] = self.layout.y_address(start_y);
+        let [end_low, end_high] = self.layout.y_address(end_y);
         self.interface.cmd_with_data(
             spi,
             Command::SetRamYAddressStartEndPosition,
-            &[
-                start_y as u8,
-                (start_y >> 8) as u8,
-                end_y as u8,
-                (end_y >> 8) as u8,
-            ],
+            &[start_low, start_high, end_low, end_high],
         )
     }
 
-    fn set_ram_counter(&mut self, spi: &mut SPI, x: u32, y: u32) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn set_ram_counter(&mut self, spi: &mut SPI, x: u32, y: u32) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         // x is positioned in bytes, so the last 3 bits which show the position inside a byte in the ram
         // aren't relevant
-        self.interface
-            .cmd_with_data(spi, Command::SetRamXAddressCounter, &[(x >> 3) as u8])?;
+        self.interface.cmd_with_data(
+            spi,
+            Command::SetRamXAddressCounter,
+            &[self.layout.x_address(x)],
+        )?;
 
         // 2 Databytes: A[7:0] & 0..A[8]
         self.interface.cmd_with_data(
             spi,
             Command::SetRamYAddressCounter,
-            &[y as u8, (y >> 8) as u8],
+            &self.layout.y_address(y),
         )?;
         Ok(())
     }
 
     /// Set your own LUT, this function is also used internally for set_lut
-    fn set_lut_helper(&mut self, spi: &mut SPI, buffer: &[u8]) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn set_lut_helper(&mut self, spi: &mut SPI, buffer: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         assert!(buffer.len() == 30);
         self.interface
             .cmd_with_data(spi, Command::WriteLutRegister, buffer)?;
@@ -373,5 +566,6 @@ mod tests {
         assert_eq!(WIDTH, 128);
         assert_eq!(HEIGHT, 296);
         assert_eq!(DEFAULT_BACKGROUND_COLOR, Color::White);
+        assert_eq!(buffer_len(), 128 / 8 * 296);
     }
 }
@@ -1,69 +1,25 @@
-use crate::epd2in9::{DEFAULT_BACKGROUND_COLOR, HEIGHT, WIDTH};
-use crate::graphics::{Display, DisplayRotation};
-use embedded_graphics::pixelcolor::BinaryColor;
-use embedded_graphics_core::prelude::*;
+use crate::display::FixedDisplay;
+use crate::epd2in9_v2::{buffer_len, Epd2in9, HEIGHT, WIDTH};
+use crate::flush::impl_flushable;
+use embedded_graphics_core::pixelcolor::BinaryColor;
 
 /// Display with Fullsize buffer for use with the 2in9 EPD V2
 ///
-/// Can also be manuall constructed:
-/// `buffer: [DEFAULT_BACKGROUND_COLOR.get_byte_value(); WIDTH / 8 * HEIGHT]`
-pub struct Display2in9 {
-    buffer: [u8; WIDTH as usize * HEIGHT as usize / 8],
-    rotation: DisplayRotation,
-}
-
-impl Default for Display2in9 {
-    fn default() -> Self {
-        Display2in9 {
-            buffer: [DEFAULT_BACKGROUND_COLOR.get_byte_value();
-                WIDTH as usize * HEIGHT as usize / 8],
-            rotation: DisplayRotation::default(),
-        }
-    }
-}
-
-impl DrawTarget for Display2in9 {
-    type Color = BinaryColor;
-    type Error = core::convert::Infallible;
-
-    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
-    where
-        I: IntoIterator<Item = Pixel<Self::Color>>,
-    {
-        for pixel in pixels {
-            self.draw_helper(WIDTH, HEIGHT, pixel)?;
-        }
-        Ok(())
-    }
-}
+/// Can also be manually constructed:
+/// `Display2in9::from_buffer([DEFAULT_BACKGROUND_COLOR.get_byte_value(); buffer_len()])`
+pub type Display2in9 = FixedDisplay<WIDTH, HEIGHT, { buffer_len() }, BinaryColor>;
 
-impl OriginDimensions for Display2in9 {
-    fn size(&self) -> Size {
-        Size::new(WIDTH, HEIGHT)
-    }
-}
-
-impl Display for Display2in9 {
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
-    }
-
-    fn get_mut_buffer(&mut self) -> &mut [u8] {
-        &mut self.buffer
-    }
-
-    fn set_rotation(&mut self, rotation: DisplayRotation) {
-        self.rotation = rotation;
-    }
+// the buffer has the frame length the driver checks for
+const _: () = assert!(Display2in9::BUFFER_LEN == buffer_len());
 
-    fn rotation(&self) -> DisplayRotation {
-        self.rotation
-    }
-}
+// the driver can `flush` these buffers directly
+impl_flushable!(Display2in9 => Epd2in9, crate::graphics::Display);
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::epd2in9_v2::DEFAULT_BACKGROUND_COLOR;
+    use crate::graphics::Display;
 
     // test buffer length
     #[test]
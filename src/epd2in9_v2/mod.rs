@@ -1,12 +1,15 @@
 //! A simple Driver for the Waveshare 2.9" E-Ink Display V2 via SPI
 //!
+//! Polarity: the SSD1680 shows a set bit as white, in the new frame as in the old one for
+//! the partial refresh ([Polarity::ClearedIsColor]).
+//!
 //! Specification: <https://www.waveshare.com/w/upload/7/79/2.9inch-e-paper-v2-specification.pdf>
 //!
 //! # Example for the 2.9 in E-Ink Display V2
 //!
 //!```rust, no_run
 //!# use embedded_hal_mock::*;
-//!# fn main() -> Result<(), MockError> {
+//!# fn main() -> Result<(), epd_waveshare::Error<MockError>> {
 //!use embedded_graphics::{
 //!    pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
 //!};
@@ -22,7 +25,8 @@
 //!# let mut delay = delay::MockNoop::new();
 //!
 //!// Setup EPD
-//!let mut epd = Epd2in9::new(&mut spi, cs_pin, busy_in, dc, rst, &mut delay)?;
+//!let mut epd = Epd2in9::new(cs_pin, busy_in, dc, rst);
+//!epd.init(&mut spi, &mut delay)?;
 //!
 //!// Use display graphics from embedded-graphics
 //!let mut display = Display2in9::default();
@@ -39,7 +43,7 @@
 //!// Draw something new here
 //!
 //!// Display new image as a base image for further quick refreshes
-//!epd.update_old_frame(&mut spi, &display.buffer(), &mut delay)?;
+//!epd.update_frame(&mut spi, &display.buffer(), &mut delay)?;
 //!epd.display_frame(&mut spi, &mut delay)?;
 //!
 //!// Update image here
@@ -53,6 +57,47 @@
 //!# Ok(())
 //!# }
 //!```
+//!
+//! # Refresh modes
+//!
+//! The driver keeps track of the refresh mode, [set_refresh](Epd2in9::set_refresh) switches
+//! between them and sends everything the mode needs:
+//!
+//! - [RefreshLut::Full] uses the LUT of the OTP, the border follows it.
+//! - [RefreshLut::Quick] loads the partial LUT, keeps the displayed frame as the old frame
+//!   after every refresh and holds the border, so it doesn't flash.
+//!
+//! [display_frame](WaveshareDisplay::display_frame) refreshes in the current mode and
+//! [update_new_frame](QuickRefresh::update_new_frame) switches to the quick one. Back to
+//! [RefreshLut::Full] the controller is initialized again, which restores all registers.
+//! [init](WaveshareDisplay::init) and [wake_up](WaveshareDisplay::wake_up) always start with
+//! full refreshes.
+//!
+//! # The old frame
+//!
+//! The quick refresh only drives the pixels which differ between the new frame (RAM 0x24)
+//! and the old one (RAM 0x26), so the old frame has to be the one on the panel:
+//!
+//! - In the full refresh the frames of [update_frame](WaveshareDisplay::update_frame),
+//!   [update_partial_frame](WaveshareDisplay::update_partial_frame) and
+//!   [clear_frame](WaveshareDisplay::clear_frame) go to both RAMs.
+//! - In the quick refresh they only go to the new frame, the controller copies it into the
+//!   old one after each refresh.
+//! - The deep sleep loses the RAMs. Before the first quick refresh after waking up,
+//!   [update_old_frame](QuickRefresh::update_old_frame) or
+//!   [update_partial_old_frame](QuickRefresh::update_partial_old_frame) write what the panel
+//!   still shows into the old frame, otherwise it smears.
+//!
+//! [update_new_frame](QuickRefresh::update_new_frame) and
+//! [update_partial_new_frame](QuickRefresh::update_partial_new_frame) only write the new
+//! frame.
+//!
+//! # Mounted upside down
+//!
+//! [Epd2in9::new_with_flip] mirrors the image in the controller with the data entry mode,
+//! the buffers and windows stay the same. The SSD1680 only runs its x counter over bytes
+//! backwards, so for [HardwareFlip::MirrorX] and [HardwareFlip::MirrorXY] the driver
+//! reverses the bits of every byte while sending, without a copy of the frame.
 
 /// Width of epd2in9 in pixels
 pub const WIDTH: u32 = 128;
@@ -60,6 +105,11 @@ pub const WIDTH: u32 = 128;
 pub const HEIGHT: u32 = 296;
 /// Default Background Color (white)
 pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full frame buffer for epd2in9_v2
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+// The SSD1680 pulls BUSY high while it is working
 const IS_BUSY_LOW: bool = false;
 
 const LUT_PARTIAL_2IN9: [u8; 153] = [
@@ -79,13 +129,18 @@ use embedded_hal::{
     digital::v2::*,
 };
 
-use crate::type_a::command::Command;
+use crate::type_a::{command::Command, RamLayout};
 
-use crate::color::Color;
+use crate::color::{buffer_size, Color, Polarity};
 
 use crate::traits::*;
 
-use crate::interface::DisplayInterface;
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
+};
+use crate::pattern;
 use crate::traits::QuickRefresh;
 
 #[cfg(feature = "graphics")]
@@ -100,8 +155,9 @@ pub struct Epd2in9<SPI, CS, BUSY, DC, RST, DELAY> {
     interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
     /// Color
     background_color: Color,
-    /// Refresh LUT
     refresh: RefreshLut,
+    /// Data entry mode and RAM addresses of the hardware flip
+    layout: RamLayout,
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> Epd2in9<SPI, CS, BUSY, DC, RST, DELAY>
@@ -113,35 +169,15 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.interface.reset(delay, 2);
-
-        self.wait_until_idle();
-        self.interface.cmd(spi, Command::SwReset)?;
-        self.wait_until_idle();
-
-        // 3 Databytes:
-        // A[7:0]
-        // 0.. A[8]
-        // 0.. B[2:0]
-        // Default Values: A = Height of Screen (0x127), B = 0x00 (GD, SM and TB=0?)
-        self.interface
-            .cmd_with_data(spi, Command::DriverOutputControl, &[0x27, 0x01, 0x00])?;
-
-        // One Databyte with default value 0x03
-        //  -> address: x increment, y increment, address counter is updated in x direction
-        self.interface
-            .cmd_with_data(spi, Command::DataEntryModeSetting, &[0x03])?;
-
-        self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
-
-        self.interface
-            .cmd_with_data(spi, Command::DisplayUpdateControl1, &[0x00, 0x80])?;
-
-        self.set_ram_counter(spi, 0, 0)?;
-
-        self.wait_until_idle();
-        Ok(())
+    /// Creates a new driver which mirrors the image in the controller
+    ///
+    /// Like [new](WaveshareDisplay::new), the coordinates of the buffers and windows stay
+    /// top-left based, see the [module documentation](self).
+    pub fn new_with_flip(cs: CS, busy: BUSY, dc: DC, rst: RST, flip: HardwareFlip) -> Self {
+        Epd2in9 {
+            layout: RamLayout::new(WIDTH, HEIGHT, flip),
+            ..Self::new(cs, busy, dc, rst)
+        }
     }
 }
 
@@ -156,6 +192,13 @@ where
     DELAY: DelayMs<u8>,
 {
     type DisplayColor = Color;
+    const SUPPORTS_PARTIAL: bool = true;
+    const SUPPORTS_QUICK_REFRESH: bool = false;
+    const SUPPORTS_LUT: bool = false;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 1;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -164,36 +207,68 @@ where
         HEIGHT
     }
 
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
         let interface = DisplayInterface::new(cs, busy, dc, rst);
 
-        let mut epd = Epd2in9 {
+        Epd2in9 {
             interface,
             background_color: DEFAULT_BACKGROUND_COLOR,
             refresh: RefreshLut::Full,
-        };
+            layout: RamLayout::new(WIDTH, HEIGHT, HardwareFlip::Normal),
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.refresh = RefreshLut::Full;
+        self.interface.reset(delay, 2)?;
+
+        // SSD1680: BUSY is high while the controller loads its settings after the reset
+        self.wait_until_idle()?;
+        self.interface.cmd(spi, Command::SwReset)?;
+        self.wait_until_idle()?;
+
+        // 3 Databytes:
+        // A[7:0]
+        // 0.. A[8]
+        // 0.. B[2:0]
+        // Default Values: A = Height of Screen (0x127), B = 0x00 (GD, SM and TB=0?)
+        self.interface
+            .cmd_with_data(spi, Command::DriverOutputControl, &[0x27, 0x01, 0x00])?;
+
+        // One Databyte with default value 0x03
+        //  -> address: x increment, y increment, address counter is updated in x direction
+        //  the mirrored directions decrement
+        self.interface.cmd_with_data(
+            spi,
+            Command::DataEntryModeSetting,
+            &[self.layout.data_entry_mode()],
+        )?;
+
+        self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
+
+        self.interface
+            .cmd_with_data(spi, Command::DisplayUpdateControl1, &[0x00, 0x80])?;
 
-        epd.init(spi, delay)?;
+        self.set_ram_counter(spi, 0, 0)?;
 
-        Ok(epd)
+        self.wait_until_idle()?;
+        Ok(())
     }
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
+    }
+
+    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         // 0x00 for Normal mode (Power on Reset), 0x01 for Deep Sleep Mode
         self.interface
             .cmd_with_data(spi, Command::DeepSleepMode, &[0x01])?;
+        self.interface.asleep()?;
         Ok(())
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)?;
         Ok(())
     }
@@ -203,9 +278,26 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        self.interface.cmd_with_data(spi, Command::WriteRam, buffer)
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
+        self.wait_until_idle()?;
+        let background = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
+        let rams = self.frame_rams();
+        let reverse_bits = self.layout.reverses_bits();
+        self.write_rams(spi, rams, (0, 0, WIDTH, HEIGHT), |interface, spi| {
+            interface.padded_frame_data_reversed(
+                spi,
+                buffer,
+                buffer_len(),
+                background,
+                Self::POLARITY,
+                reverse_bits,
+            )
+        })?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
     }
 
     fn update_partial_frame(
@@ -216,46 +308,130 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        //TODO This is copied from epd2in9 but it seems not working. Partial refresh supported by version 2?
-        self.wait_until_idle();
-        self.set_ram_area(spi, x, y, x + width, y + height)?;
-        self.set_ram_counter(spi, x, y)?;
-
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = (width.div_ceil(8) * height) as usize;
         self.interface
-            .cmd_with_data(spi, Command::WriteRam, buffer)?;
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.interface.check_len(buffer, len)?;
+
+        let background = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
+        let rams = self.frame_rams();
+        let reverse_bits = self.layout.reverses_bits();
+        self.write_rams(spi, rams, (x, y, width, height), |interface, spi| {
+            interface.padded_frame_data_reversed(
+                spi,
+                buffer,
+                len,
+                background,
+                Self::POLARITY,
+                reverse_bits,
+            )
+        })?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        // Enable clock signal, Enable Analog, Load temperature value, DISPLAY with DISPLAY Mode 1, Disable Analog, Disable OSC
-        self.interface
-            .cmd_with_data(spi, Command::DisplayUpdateControl2, &[0xF7])?;
-        self.interface.cmd(spi, Command::MasterActivation)?;
-        self.wait_until_idle();
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
+        self.wait_until_idle()?;
         Ok(())
     }
 
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // clear the ram with the background color
-        let color = self.background_color.get_byte_value();
+        let color = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
 
-        self.interface.cmd(spi, Command::WriteRam)?;
-        self.interface.data_x_times(spi, color, WIDTH / 8 * HEIGHT)
+        let rams = self.frame_rams();
+        self.write_rams(spi, rams, (0, 0, WIDTH, HEIGHT), |interface, spi| {
+            interface.data_x_times(spi, color, WIDTH / 8 * HEIGHT)
+        })?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let checkerboard = pattern::bytes(WIDTH, HEIGHT, 0, pattern::checkerboard);
+        let layout = self.layout;
+        let rams = self.frame_rams();
+        self.write_rams(spi, rams, (0, 0, WIDTH, HEIGHT), |interface, spi| {
+            interface.generated_frame_data(
+                spi,
+                buffer_len(),
+                |i| layout.byte(checkerboard(i)),
+                Self::POLARITY,
+            )
+        })?;
+        self.interface.set_state(DisplayState::FramePending);
+        self.display_frame(spi, delay)
+    }
+
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
     }
 
     fn set_background_color(&mut self, background_color: Color) {
@@ -266,20 +442,21 @@ where
         &self.background_color
     }
 
-    fn set_lut(
-        &mut self,
-        _spi: &mut SPI,
-        refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
-        if let Some(refresh_lut) = refresh_rate {
-            self.refresh = refresh_lut;
-        }
-        Ok(())
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
     }
 
-    fn is_busy(&self) -> bool {
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
         self.interface.is_busy(IS_BUSY_LOW)
     }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> Epd2in9<SPI, CS, BUSY, DC, RST, DELAY>
@@ -291,16 +468,113 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn wait_until_idle(&mut self) {
-        self.interface.wait_until_idle(IS_BUSY_LOW);
+    /// Sets the refresh mode, see the [module documentation](self)
+    ///
+    /// Does nothing if the display already refreshes in this mode.
+    pub fn set_refresh(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        refresh: RefreshLut,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.refresh == refresh {
+            return Ok(());
+        }
+        match refresh {
+            RefreshLut::Full => self.init(spi, delay),
+            RefreshLut::Quick => self.enter_quick_refresh(spi, delay),
+        }
     }
 
-    fn use_full_frame(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
-        // choose full frame/ram
-        self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
+    // Loads the partial LUT and the settings around it like the vendor driver
+    fn enter_quick_refresh(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        self.interface.reset(delay, 2)?;
+
+        self.set_lut_helper(spi, &LUT_PARTIAL_2IN9)?;
+        // The reset restored the default data entry mode
+        self.interface.cmd_with_data(
+            spi,
+            Command::DataEntryModeSetting,
+            &[self.layout.data_entry_mode()],
+        )?;
+        // Ping-pong for display mode 2: the displayed frame becomes the old frame
+        self.interface.cmd_with_data(
+            spi,
+            Command::WriteOtpSelection,
+            &[0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00],
+        )?;
+        // Hold the border, following the partial LUT it flashes
+        self.interface
+            .cmd_with_data(spi, Command::BorderWaveformControl, &[0x80])?;
+        self.interface
+            .cmd_with_data(spi, Command::DisplayUpdateControl2, &[0xC0])?;
+        self.interface.cmd(spi, Command::MasterActivation)?;
 
-        // start from the beginning
-        self.set_ram_counter(spi, 0, 0)
+        self.wait_until_idle()?;
+        self.refresh = RefreshLut::Quick;
+        Ok(())
+    }
+
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        let control = match self.refresh {
+            // Enable clock signal, Enable Analog, Load temperature value, DISPLAY with DISPLAY Mode 1, Disable Analog, Disable OSC
+            RefreshLut::Full => 0xF7,
+            // DISPLAY with DISPLAY Mode 2, clock and analog stay on between the refreshes
+            RefreshLut::Quick => 0x0F,
+        };
+        self.interface
+            .cmd_with_data(spi, Command::DisplayUpdateControl2, &[control])?;
+        self.interface.cmd(spi, Command::MasterActivation)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
+    }
+
+    /// The rams a frame goes to: in the full refresh the old frame as well, so the first
+    /// quick refresh after it starts from the displayed frame
+    fn frame_rams(&self) -> &'static [Command] {
+        match self.refresh {
+            RefreshLut::Full => &[Command::WriteRam, Command::WriteRam2],
+            RefreshLut::Quick => &[Command::WriteRam],
+        }
+    }
+
+    /// Writes the window to each of `rams`, `data` sends its bytes after the command
+    fn write_rams<F>(
+        &mut self,
+        spi: &mut SPI,
+        rams: &[Command],
+        (x, y, width, height): (u32, u32, u32, u32),
+        mut data: F,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        F: FnMut(
+            &mut DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
+            &mut SPI,
+        ) -> Result<(), Error<SPI::Error>>,
+    {
+        self.wait_until_idle()?;
+        self.set_ram_area(spi, x, y, x + width - 1, y + height - 1)?;
+        for &ram in rams {
+            self.set_ram_counter(spi, x, y)?;
+            self.interface.cmd(spi, ram)?;
+            data(&mut self.interface, spi)?;
+        }
+        Ok(())
     }
 
     fn set_ram_area(
@@ -310,53 +584,53 @@ where
         start_y: u32,
         end_x: u32,
         end_y: u32,
-    ) -> Result<(), SPI::Error> {
-        assert!(start_x < end_x);
-        assert!(start_y < end_y);
+    ) -> Result<(), Error<SPI::Error>> {
+        assert!(start_x <= end_x);
+        assert!(start_y <= end_y);
 
         // x is positioned in bytes, so the last 3 bits which show the position inside a byte in the ram
-        // aren't relevant
+        // aren't relevant. Mirrored the start is the higher address.
         self.interface.cmd_with_data(
             spi,
             Command::SetRamXAddressStartEndPosition,
-            &[(start_x >> 3) as u8, (end_x >> 3) as u8],
+            &[self.layout.x_address(start_x), self.layout.x_address(end_x)],
         )?;
 
         // 2 Databytes: A[7:0] & 0..A[8] for each - start and end
+        let [start_low, start_high] = self.layout.y_address(start_y);
+        let [end_low, end_high] = self.layout.y_address(end_y);
         self.interface.cmd_with_data(
             spi,
             Command::SetRamYAddressStartEndPosition,
-            &[
-                start_y as u8,
-                (start_y >> 8) as u8,
-                end_y as u8,
-                (end_y >> 8) as u8,
-            ],
+            &[start_low, start_high, end_low, end_high],
         )
     }
 
-    fn set_ram_counter(&mut self, spi: &mut SPI, x: u32, y: u32) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn set_ram_counter(&mut self, spi: &mut SPI, x: u32, y: u32) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         // x is positioned in bytes, so the last 3 bits which show the position inside a byte in the ram
         // aren't relevant
-        self.interface
-            .cmd_with_data(spi, Command::SetRamXAddressCounter, &[x as u8])?;
+        self.interface.cmd_with_data(
+            spi,
+            Command::SetRamXAddressCounter,
+            &[self.layout.x_address(x)],
+        )?;
 
         // 2 Databytes: A[7:0] & 0..A[8]
         self.interface.cmd_with_data(
             spi,
             Command::SetRamYAddressCounter,
-            &[y as u8, (y >> 8) as u8],
+            &self.layout.y_address(y),
         )?;
         Ok(())
     }
 
     /// Set your own LUT, this function is also used internally for set_lut
-    fn set_lut_helper(&mut self, spi: &mut SPI, buffer: &[u8]) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn set_lut_helper(&mut self, spi: &mut SPI, buffer: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         self.interface
             .cmd_with_data(spi, Command::WriteLutRegister, buffer)?;
-        self.wait_until_idle();
+        self.wait_until_idle()?;
         Ok(())
     }
 }
@@ -371,59 +645,71 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    /// To be followed immediately by `update_new_frame`.
+    /// Writes the frame currently displayed into the old frame RAM (0x26)
+    ///
+    /// Only needed if the RAM lost it, e.g. after waking up from the deep sleep, see the
+    /// [module documentation](self).
     fn update_old_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        self.interface
-            .cmd_with_data(spi, Command::WriteRam, buffer)?;
-        self.interface
-            .cmd_with_data(spi, Command::WriteRam2, buffer)
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_exact_len(buffer, buffer_len())?;
+        let reverse_bits = self.layout.reverses_bits();
+        self.write_rams(
+            spi,
+            &[Command::WriteRam2],
+            (0, 0, WIDTH, HEIGHT),
+            |interface, spi| {
+                interface.padded_frame_data_reversed(
+                    spi,
+                    buffer,
+                    buffer.len(),
+                    0x00,
+                    Self::POLARITY,
+                    reverse_bits,
+                )
+            },
+        )
     }
 
-    /// To be used immediately after `update_old_frame`.
+    /// Writes the new frame RAM (0x24) and switches to the quick refresh
     fn update_new_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        self.interface.reset(delay, 2);
-
-        self.set_lut_helper(spi, &LUT_PARTIAL_2IN9)?;
-        self.interface.cmd_with_data(
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_exact_len(buffer, buffer_len())?;
+        self.set_refresh(spi, delay, RefreshLut::Quick)?;
+        let reverse_bits = self.layout.reverses_bits();
+        self.write_rams(
             spi,
-            Command::WriteOtpSelection,
-            &[0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00],
+            &[Command::WriteRam],
+            (0, 0, WIDTH, HEIGHT),
+            |interface, spi| {
+                interface.padded_frame_data_reversed(
+                    spi,
+                    buffer,
+                    buffer.len(),
+                    0x00,
+                    Self::POLARITY,
+                    reverse_bits,
+                )
+            },
         )?;
-        self.interface
-            .cmd_with_data(spi, Command::BorderWaveformControl, &[0x80])?;
-        self.interface
-            .cmd_with_data(spi, Command::DisplayUpdateControl2, &[0xC0])?;
-        self.interface.cmd(spi, Command::MasterActivation)?;
-
-        self.wait_until_idle();
-
-        self.use_full_frame(spi)?;
-
-        self.interface
-            .cmd_with_data(spi, Command::WriteRam, buffer)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
     /// For a quick refresh of the new updated frame. To be used immediately after `update_new_frame`
-    fn display_new_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        self.interface
-            .cmd_with_data(spi, Command::DisplayUpdateControl2, &[0x0F])?;
-        self.interface.cmd(spi, Command::MasterActivation)?;
-        self.wait_until_idle();
-        Ok(())
+    fn display_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.display_frame(spi, delay)
     }
 
     /// Updates and displays the new frame.
@@ -432,14 +718,13 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_new_frame(spi, buffer, delay)?;
         self.display_new_frame(spi, delay)?;
         Ok(())
     }
 
-    /// Partial quick refresh not supported yet
-    #[allow(unused)]
+    /// Writes a window of the old frame RAM (0x26), x and width need to be multiples of 8
     fn update_partial_old_frame(
         &mut self,
         spi: &mut SPI,
@@ -448,13 +733,33 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        //TODO supported by display?
-        unimplemented!()
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.interface
+            .check_exact_len(buffer, (width.div_ceil(8) * height) as usize)?;
+        let reverse_bits = self.layout.reverses_bits();
+        self.write_rams(
+            spi,
+            &[Command::WriteRam2],
+            (x, y, width, height),
+            |interface, spi| {
+                interface.padded_frame_data_reversed(
+                    spi,
+                    buffer,
+                    buffer.len(),
+                    0x00,
+                    Self::POLARITY,
+                    reverse_bits,
+                )
+            },
+        )
     }
 
-    /// Partial quick refresh not supported yet
-    #[allow(unused)]
+    /// Writes a window of the new frame RAM (0x24), x and width need to be multiples of 8
+    ///
+    /// Without a delay it can't switch to the quick refresh itself, use
+    /// [set_refresh](Epd2in9::set_refresh) or `update_new_frame` before.
     fn update_partial_new_frame(
         &mut self,
         spi: &mut SPI,
@@ -463,13 +768,33 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        //TODO supported by display?
-        unimplemented!()
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.interface
+            .check_exact_len(buffer, (width.div_ceil(8) * height) as usize)?;
+        let reverse_bits = self.layout.reverses_bits();
+        self.write_rams(
+            spi,
+            &[Command::WriteRam],
+            (x, y, width, height),
+            |interface, spi| {
+                interface.padded_frame_data_reversed(
+                    spi,
+                    buffer,
+                    buffer.len(),
+                    0x00,
+                    Self::POLARITY,
+                    reverse_bits,
+                )
+            },
+        )?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
     }
 
-    /// Partial quick refresh not supported yet
-    #[allow(unused)]
+    /// Clears a window of the frame like [clear_frame](WaveshareDisplay::clear_frame), x and
+    /// width need to be multiples of 8
     fn clear_partial_frame(
         &mut self,
         spi: &mut SPI,
@@ -477,9 +802,18 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        //TODO supported by display?
-        unimplemented!()
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        let color = self
+            .interface
+            .background_byte(self.background_color.to_controller_byte(Self::POLARITY));
+        let rams = self.frame_rams();
+        self.write_rams(spi, rams, (x, y, width, height), |interface, spi| {
+            interface.data_x_times(spi, color, width.div_ceil(8) * height)
+        })?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
     }
 }
 
@@ -492,5 +826,309 @@ mod tests {
         assert_eq!(WIDTH, 128);
         assert_eq!(HEIGHT, 296);
         assert_eq!(DEFAULT_BACKGROUND_COLOR, Color::White);
+        assert_eq!(buffer_len(), 128 / 8 * 296);
+    }
+
+    #[test]
+    fn partial_window_registers() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd2in9<_, _, _, _, _, _>>();
+
+        // the x positions are in bytes, the ends are the last byte and row of the window
+        let windows = [
+            // left edge
+            ((0, 0, 16, 8), [0, 1], [0, 0, 7, 0], 0),
+            // middle
+            ((64, 100, 32, 180), [8, 11], [100, 0, 23, 1], 8),
+            // right edge
+            (
+                (WIDTH - 16, HEIGHT - 8, 16, 8),
+                [14, 15],
+                [32, 1, 39, 1],
+                14,
+            ),
+            // full width
+            ((0, 10, WIDTH, 4), [0, 15], [10, 0, 13, 0], 0),
+        ];
+        for &((x, y, width, height), x_range, y_range, x_counter) in windows.iter() {
+            log.clear();
+            let buffer = vec![0x00; (width / 8 * height) as usize];
+            epd.update_partial_frame(&mut spi, &buffer, x, y, width, height)
+                .unwrap();
+            assert_eq!(
+                log.data_after(Command::SetRamXAddressStartEndPosition.address()),
+                x_range
+            );
+            assert_eq!(
+                log.data_after(Command::SetRamYAddressStartEndPosition.address()),
+                y_range
+            );
+            assert_eq!(
+                log.data_after(Command::SetRamXAddressCounter.address()),
+                [x_counter]
+            );
+            assert_eq!(
+                log.data_after(Command::SetRamYAddressCounter.address())[..1],
+                [y_range[0]]
+            );
+        }
+
+        // windows which don't fit on the display are refused
+        for &(x, y, width, height) in
+            [(WIDTH - 8, 0, 16, 8), (0, HEIGHT, 8, 1), (0, 0, 0, 8)].iter()
+        {
+            assert_eq!(
+                epd.update_partial_frame(&mut spi, &[], x, y, width, height),
+                Err(Error::OutOfBounds)
+            );
+        }
+    }
+
+    #[test]
+    fn refresh_modes() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in9<_, _, _, _, _, _>>();
+        let controls = |log: &crate::mock::Log| {
+            log.data_after_each(Command::DisplayUpdateControl2.address())
+                .concat()
+        };
+        let frame = vec![0x00; buffer_len()];
+
+        log.clear();
+        epd.update_frame(&mut spi, &frame, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        for _ in 0..3 {
+            epd.update_and_display_new_frame(&mut spi, &frame, &mut delay)
+                .unwrap();
+        }
+        // the partial LUT is only loaded once, every refresh after it is in mode 2
+        assert_eq!(controls(&log), [0xF7, 0xC0, 0x0F, 0x0F, 0x0F]);
+        assert_eq!(log.count_command(Command::WriteLutRegister.address()), 1);
+        assert_eq!(
+            log.data_after(Command::BorderWaveformControl.address()),
+            [0x80]
+        );
+
+        // back to full refreshes the controller is initialized again
+        log.clear();
+        epd.set_refresh(&mut spi, &mut delay, RefreshLut::Full)
+            .unwrap();
+        epd.update_and_display_frame(&mut spi, &frame, &mut delay)
+            .unwrap();
+        assert_eq!(log.count_command(Command::SwReset.address()), 1);
+        assert_eq!(controls(&log), [0xF7]);
+
+        // and after waking up
+        epd.set_refresh(&mut spi, &mut delay, RefreshLut::Quick)
+            .unwrap();
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+        log.clear();
+        epd.update_and_display_frame(&mut spi, &frame, &mut delay)
+            .unwrap();
+        assert_eq!(controls(&log), [0xF7]);
+    }
+
+    #[test]
+    fn frame_rams() {
+        use crate::mock::init_epd;
+        extern crate std;
+        use std::string::String;
+        use std::vec;
+        use std::vec::Vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in9<_, _, _, _, _, _>>();
+        // the ram commands in the transcript, with the positions of the counters before them
+        let rams = |log: &crate::mock::Log| -> Vec<String> {
+            let transcript = log.transcript();
+            let mut rams = Vec::new();
+            let mut counter = String::new();
+            for entry in &transcript {
+                match &entry[..2] {
+                    "4E" => counter = entry[4..].into(),
+                    "24" | "26" => rams.push(std::format!("{} at {}", &entry[..2], counter)),
+                    _ => (),
+                }
+            }
+            log.clear();
+            rams
+        };
+        let frame = vec![0x00; buffer_len()];
+        let window = vec![0x00; 2 * 8];
+        log.clear();
+
+        // the full refresh keeps both rams in sync
+        epd.update_frame(&mut spi, &frame, &mut delay).unwrap();
+        assert_eq!(rams(&log), ["24 at 00", "26 at 00"]);
+        epd.update_partial_frame(&mut spi, &window, 16, 8, 16, 8)
+            .unwrap();
+        assert_eq!(rams(&log), ["24 at 02", "26 at 02"]);
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(rams(&log), ["24 at 00", "26 at 00"]);
+
+        // the old and new frames only write their ram
+        epd.update_old_frame(&mut spi, &frame, &mut delay).unwrap();
+        assert_eq!(rams(&log), ["26 at 00"]);
+        epd.update_partial_old_frame(&mut spi, &window, 16, 8, 16, 8)
+            .unwrap();
+        assert_eq!(rams(&log), ["26 at 02"]);
+        epd.update_new_frame(&mut spi, &frame, &mut delay).unwrap();
+        assert_eq!(rams(&log), ["24 at 00"]);
+        epd.update_partial_new_frame(&mut spi, &window, 16, 8, 16, 8)
+            .unwrap();
+        assert_eq!(rams(&log), ["24 at 02"]);
+
+        // in the quick refresh the controller copies the new frame into the old one
+        epd.update_frame(&mut spi, &frame, &mut delay).unwrap();
+        assert_eq!(rams(&log), ["24 at 00"]);
+        epd.clear_partial_frame(&mut spi, 16, 8, 16, 8).unwrap();
+        assert_eq!(rams(&log), ["24 at 02"]);
+
+        assert_eq!(
+            epd.update_partial_old_frame(&mut spi, &window, 120, 8, 16, 8),
+            Err(Error::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn hardware_flip() {
+        use crate::mock::{BusyPin, Delay, Log, Pin};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        // the data entry mode and the registers of the window at (16, 8, 16, 8) and of the
+        // full frame: the mirrored counters start at the far end and count down
+        let flips = [
+            (
+                HardwareFlip::Normal,
+                0x03,
+                [2, 3],
+                [8, 0, 15, 0],
+                [0, 15],
+                [0, 0, 39, 1],
+            ),
+            (
+                HardwareFlip::MirrorX,
+                0x02,
+                [13, 12],
+                [8, 0, 15, 0],
+                [15, 0],
+                [0, 0, 39, 1],
+            ),
+            (
+                HardwareFlip::MirrorY,
+                0x01,
+                [2, 3],
+                [31, 1, 24, 1],
+                [0, 15],
+                [39, 1, 0, 0],
+            ),
+            (
+                HardwareFlip::MirrorXY,
+                0x00,
+                [13, 12],
+                [31, 1, 24, 1],
+                [15, 0],
+                [39, 1, 0, 0],
+            ),
+        ];
+        for &(flip, mode, x_range, y_range, full_x, full_y) in flips.iter() {
+            let log = Log::default();
+            let mut spi = log.spi();
+            let mut delay = Delay::new();
+            let mut epd = Epd2in9::new_with_flip(Pin, BusyPin::default(), log.dc(), Pin, flip);
+            epd.init(&mut spi, &mut delay).unwrap();
+            assert_eq!(
+                log.data_after(Command::DataEntryModeSetting.address()),
+                [mode]
+            );
+
+            log.clear();
+            let window = [0x80, 0x01].repeat(8);
+            epd.update_partial_frame(&mut spi, &window, 16, 8, 16, 8)
+                .unwrap();
+            assert_eq!(
+                log.data_after(Command::SetRamXAddressStartEndPosition.address()),
+                x_range
+            );
+            assert_eq!(
+                log.data_after(Command::SetRamYAddressStartEndPosition.address()),
+                y_range
+            );
+            // the window is written from its top-left corner on
+            assert_eq!(
+                log.data_after(Command::SetRamXAddressCounter.address()),
+                [x_range[0]]
+            );
+            assert_eq!(
+                log.data_after(Command::SetRamYAddressCounter.address()),
+                y_range[..2]
+            );
+            // the pixels of a byte only change their order in the mirrored x
+            let sent = if flip.mirrors_x() {
+                [0x01, 0x80].repeat(8)
+            } else {
+                window.clone()
+            };
+            assert_eq!(log.data_after(Command::WriteRam.address()), sent);
+
+            log.clear();
+            epd.update_frame(&mut spi, &vec![0x00; buffer_len()], &mut delay)
+                .unwrap();
+            assert_eq!(
+                log.data_after(Command::SetRamXAddressStartEndPosition.address()),
+                full_x
+            );
+            assert_eq!(
+                log.data_after(Command::SetRamYAddressStartEndPosition.address()),
+                full_y
+            );
+
+            // the reset of the quick refresh restores the default, it is set again
+            log.clear();
+            epd.set_refresh(&mut spi, &mut delay, RefreshLut::Quick)
+                .unwrap();
+            assert_eq!(
+                log.data_after(Command::DataEntryModeSetting.address()),
+                [mode]
+            );
+        }
+    }
+
+    #[test]
+    fn direct_display() {
+        use crate::color::Black;
+        use crate::direct::DirectDisplay;
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        use embedded_graphics::{
+            prelude::*,
+            primitives::{PrimitiveStyle, Rectangle},
+        };
+
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd2in9<_, _, _, _, _, _>>();
+        log.clear();
+        epd.update_partial_frame(&mut spi, &[0x00, 0x00], 16, 4, 16, 1)
+            .unwrap();
+        let expected = log.transcript();
+
+        // the partial windows take DirectDisplay's drawing to both RAMs
+        log.clear();
+        let mut display = DirectDisplay::new(&mut epd, &mut spi).unwrap();
+        Rectangle::new(Point::new(16, 4), Size::new(16, 1))
+            .into_styled(PrimitiveStyle::with_fill(Black))
+            .draw(&mut display)
+            .unwrap();
+        assert_eq!(log.transcript(), expected);
+        assert_eq!(log.count_command(Command::WriteRam2.address()), 1);
     }
 }
@@ -1,60 +1,29 @@
-use crate::epd2in9bc::{DEFAULT_BACKGROUND_COLOR, HEIGHT, NUM_DISPLAY_BITS, WIDTH};
-use crate::graphics::{Display, DisplayRotation};
-use embedded_graphics::pixelcolor::BinaryColor;
-use embedded_graphics_core::prelude::*;
+use crate::color::{buffer_size, TriColor};
+use crate::display::FixedDisplay;
+use crate::epd2in9bc::{buffer_len, Epd2in9bc, HEIGHT, NUM_DISPLAY_BITS, WIDTH};
+use crate::flush::impl_flushable;
+use embedded_graphics_core::pixelcolor::BinaryColor;
 
 /// Full size buffer for use with the 2in9b/c EPD
 ///
 /// Can also be manually constructed and be used together with VarDisplay
-pub struct Display2in9bc {
-    buffer: [u8; NUM_DISPLAY_BITS as usize],
-    rotation: DisplayRotation,
-}
+pub type Display2in9bc = FixedDisplay<WIDTH, HEIGHT, { NUM_DISPLAY_BITS as usize }, BinaryColor>;
 
-impl Default for Display2in9bc {
-    fn default() -> Self {
-        Display2in9bc {
-            buffer: [DEFAULT_BACKGROUND_COLOR.get_byte_value(); NUM_DISPLAY_BITS as usize],
-            rotation: DisplayRotation::default(),
-        }
-    }
-}
-
-impl DrawTarget for Display2in9bc {
-    type Color = BinaryColor;
-    type Error = core::convert::Infallible;
-
-    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
-    where
-        I: IntoIterator<Item = Pixel<Self::Color>>,
-    {
-        for pixel in pixels {
-            self.draw_helper(WIDTH, HEIGHT, pixel)?;
-        }
-        Ok(())
-    }
-}
-
-impl OriginDimensions for Display2in9bc {
-    fn size(&self) -> Size {
-        Size::new(WIDTH, HEIGHT)
-    }
-}
-
-impl Display for Display2in9bc {
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
-    }
-
-    fn get_mut_buffer(&mut self) -> &mut [u8] {
-        &mut self.buffer
-    }
-
-    fn set_rotation(&mut self, rotation: DisplayRotation) {
-        self.rotation = rotation;
-    }
-
-    fn rotation(&self) -> DisplayRotation {
-        self.rotation
-    }
-}
+/// Full size buffer with both the b/w and the chromatic plane for use with the 2in9b/c EPD
+///
+/// Send it with `update_color_frame(spi, display.bw_buffer(), display.chromatic_buffer())`
+/// or refresh with it in one go with [Flush::flush](crate::flush::Flush::flush).
+pub type Display2in9bcTriColor = FixedDisplay<
+    WIDTH,
+    HEIGHT,
+    { buffer_size::<TriColor>(WIDTH as usize, HEIGHT as usize) },
+    TriColor,
+>;
+
+// the buffers have the frame length the driver checks for, tri-color buffers one per plane
+const _: () = assert!(Display2in9bc::BUFFER_LEN == buffer_len());
+const _: () = assert!(Display2in9bcTriColor::BUFFER_LEN == 2 * buffer_len());
+
+// the driver can `flush` these buffers directly
+impl_flushable!(Display2in9bc => Epd2in9bc, crate::graphics::Display);
+impl_flushable!(Display2in9bcTriColor => Epd2in9bc, tri);
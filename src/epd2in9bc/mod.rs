@@ -1,10 +1,13 @@
 //! A simple Driver for the Waveshare 2.9" (B/C) E-Ink Display via SPI
 //!
+//! Polarity: the IL0373 shows set bits as white and cleared bits of the chromatic plane in
+//! color ([Polarity::ClearedIsColor]).
+//!
 //! # Example for the 2.9" E-Ink Display
 //!
 //!```rust, no_run
 //!# use embedded_hal_mock::*;
-//!# fn main() -> Result<(), MockError> {
+//!# fn main() -> Result<(), epd_waveshare::Error<MockError>> {
 //!use embedded_graphics::{
 //!    pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
 //!};
@@ -20,7 +23,8 @@
 //!# let mut delay = delay::MockNoop::new();
 //!
 //!// Setup EPD
-//!let mut epd = Epd2in9bc::new(&mut spi, cs_pin, busy_in, dc, rst, &mut delay)?;
+//!let mut epd = Epd2in9bc::new(cs_pin, busy_in, dc, rst);
+//!epd.init(&mut spi, &mut delay)?;
 //!
 //!// Use display graphics from embedded-graphics
 //!// This display is for the black/white pixels
@@ -58,10 +62,14 @@ use embedded_hal::{
     digital::v2::*,
 };
 
-use crate::interface::DisplayInterface;
-use crate::traits::{
-    InternalWiAdditions, RefreshLut, WaveshareDisplay, WaveshareThreeColorDisplay,
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
 };
+use crate::pattern;
+use crate::traits::DisplayState;
+use crate::traits::{WaveshareDisplay, WaveshareThreeColorDisplay};
 
 /// Width of epd2in9bc in pixels
 pub const WIDTH: u32 = 128;
@@ -69,9 +77,24 @@ pub const WIDTH: u32 = 128;
 pub const HEIGHT: u32 = 296;
 /// Default background color (white) of epd2in9bc display
 pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full frame buffer for epd2in9bc
+///
+/// This is the size of the b/w frame, the chromatic frame has the same size
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+/// Length in bytes of the b/w plane, see [TriColorFrame](crate::color::TriColorFrame)
+pub const fn achromatic_buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+/// Length in bytes of the chromatic plane, the same as of the b/w plane
+pub const fn chromatic_buffer_len() -> usize {
+    achromatic_buffer_len()
+}
 
 const NUM_DISPLAY_BITS: u32 = WIDTH * HEIGHT / 8;
 
+// The IL0373 pulls BUSY low while it is working
 const IS_BUSY_LOW: bool = true;
 const VCOM_DATA_INTERVAL: u8 = 0x07;
 const WHITE_BORDER: u8 = 0x70;
@@ -79,7 +102,7 @@ const BLACK_BORDER: u8 = 0x30;
 const CHROMATIC_BORDER: u8 = 0xb0;
 const FLOATING_BORDER: u8 = 0xF0;
 
-use crate::color::{Color, TriColor};
+use crate::color::{buffer_size, Color, Polarity, TriColor};
 
 pub(crate) mod command;
 use self::command::Command;
@@ -87,56 +110,14 @@ use self::command::Command;
 #[cfg(feature = "graphics")]
 mod graphics;
 #[cfg(feature = "graphics")]
-pub use self::graphics::Display2in9bc;
+pub use self::graphics::{Display2in9bc, Display2in9bcTriColor};
 
 /// Epd2in9bc driver
 pub struct Epd2in9bc<SPI, CS, BUSY, DC, RST, DELAY> {
     interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
     color: Color,
-}
-
-impl<SPI, CS, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, CS, BUSY, DC, RST, DELAY>
-    for Epd2in9bc<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        // Values taken from datasheet and sample code
-
-        self.interface.reset(delay, 10);
-
-        // start the booster
-        self.interface
-            .cmd_with_data(spi, Command::BoosterSoftStart, &[0x17, 0x17, 0x17])?;
-
-        // power on
-        self.command(spi, Command::PowerOn)?;
-        delay.delay_ms(5);
-        self.wait_until_idle();
-
-        // set the panel settings
-        self.cmd_with_data(spi, Command::PanelSetting, &[0x8F])?;
-
-        self.cmd_with_data(
-            spi,
-            Command::VcomAndDataIntervalSetting,
-            &[WHITE_BORDER | VCOM_DATA_INTERVAL],
-        )?;
-
-        // set resolution
-        self.send_resolution(spi)?;
-
-        self.cmd_with_data(spi, Command::VcmDcSetting, &[0x0A])?;
-
-        self.wait_until_idle();
-
-        Ok(())
-    }
+    /// Whether the panel power is on
+    powered: bool,
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareThreeColorDisplay<SPI, CS, BUSY, DC, RST, DELAY>
@@ -154,7 +135,7 @@ where
         spi: &mut SPI,
         black: &[u8],
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_achromatic_frame(spi, black)?;
         self.update_chromatic_frame(spi, chromatic)
     }
@@ -162,9 +143,16 @@ where
     /// Update only the black/white data of the display.
     ///
     /// Finish by calling `update_chromatic_frame`.
-    fn update_achromatic_frame(&mut self, spi: &mut SPI, black: &[u8]) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, Command::DataStartTransmission1)?;
-        self.interface.data(spi, black)?;
+    fn update_achromatic_frame(
+        &mut self,
+        spi: &mut SPI,
+        black: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(black, achromatic_buffer_len())?;
+        self.interface
+            .cmd_with_data(spi, Command::DataStartTransmission1, black)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
@@ -175,11 +163,14 @@ where
         &mut self,
         spi: &mut SPI,
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, Command::DataStartTransmission2)?;
-        self.interface.data(spi, chromatic)?;
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(chromatic, chromatic_buffer_len())?;
+        self.interface
+            .cmd_with_data(spi, Command::DataStartTransmission2, chromatic)?;
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 }
@@ -195,25 +186,63 @@ where
     DELAY: DelayMs<u8>,
 {
     type DisplayColor = Color;
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
+    const SUPPORTS_PARTIAL: bool = false;
+    const SUPPORTS_QUICK_REFRESH: bool = false;
+    const SUPPORTS_LUT: bool = false;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 2;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
         let interface = DisplayInterface::new(cs, busy, dc, rst);
         let color = DEFAULT_BACKGROUND_COLOR;
 
-        let mut epd = Epd2in9bc { interface, color };
+        Epd2in9bc {
+            interface,
+            color,
+            powered: false,
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        // Values taken from datasheet and sample code
+
+        self.interface.reset(delay, 10)?;
+        // IL0373: BUSY is low until the controller is ready after the reset
+        self.wait_until_idle()?;
+        self.powered = false;
+
+        // start the booster
+        self.interface
+            .cmd_with_data(spi, Command::BoosterSoftStart, &[0x17, 0x17, 0x17])?;
+
+        // power on
+        self.power_on(spi, delay)?;
+
+        // set the panel settings
+        self.cmd_with_data(spi, Command::PanelSetting, &[0x8F])?;
+
+        self.cmd_with_data(
+            spi,
+            Command::VcomAndDataIntervalSetting,
+            &[WHITE_BORDER | VCOM_DATA_INTERVAL],
+        )?;
+
+        // set resolution
+        self.send_resolution(spi)?;
+
+        self.cmd_with_data(spi, Command::VcmDcSetting, &[0x0A])?;
 
-        epd.init(spi, delay)?;
+        self.wait_until_idle()?;
 
-        Ok(epd)
+        Ok(())
     }
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
+    }
+
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // Section 8.2 from datasheet
         self.interface.cmd_with_data(
             spi,
@@ -221,19 +250,59 @@ where
             &[FLOATING_BORDER | VCOM_DATA_INTERVAL],
         )?;
 
-        self.command(spi, Command::PowerOff)?;
         // The example STM code from Github has a wait after PowerOff
-        self.wait_until_idle();
+        self.power_off(spi, delay)?;
 
         self.cmd_with_data(spi, Command::DeepSleep, &[0xA5])?;
 
+        self.interface.asleep()?;
         Ok(())
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
     fn set_background_color(&mut self, color: Color) {
         self.color = color;
     }
@@ -242,6 +311,14 @@ where
         &self.color
     }
 
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -255,81 +332,117 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = NUM_DISPLAY_BITS as usize;
+        self.interface.check_len(buffer, len)?;
+        let background = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
 
-        self.interface.data(spi, &buffer)?;
+        self.interface
+            .padded_frame_data(spi, buffer, len, background, Self::POLARITY)?;
 
-        // Clear the chromatic layer
-        let color = self.color.get_byte_value();
+        // Clear the chromatic layer to no color, whatever the background
+        let no_color = Color::White.to_controller_byte(Self::POLARITY);
 
         self.interface.cmd(spi, Command::DataStartTransmission2)?;
-        self.interface.data_x_times(spi, color, NUM_DISPLAY_BITS)?;
+        self.interface
+            .data_x_times(spi, no_color, NUM_DISPLAY_BITS)?;
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
-    #[allow(unused)]
     fn update_partial_frame(
         &mut self,
-        spi: &mut SPI,
-        buffer: &[u8],
-        x: u32,
-        y: u32,
-        width: u32,
-        height: u32,
-    ) -> Result<(), SPI::Error> {
-        Ok(())
+        _spi: &mut SPI,
+        _buffer: &[u8],
+        _x: u32,
+        _y: u32,
+        _width: u32,
+        _height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.command(spi, Command::DisplayRefresh)?;
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
         Ok(())
     }
 
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.send_resolution(spi)?;
 
-        let color = DEFAULT_BACKGROUND_COLOR.get_byte_value();
+        let color = DEFAULT_BACKGROUND_COLOR.to_controller_byte(Self::POLARITY);
 
         // Clear the black
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
 
-        self.interface.data_x_times(spi, color, NUM_DISPLAY_BITS)?;
+        self.interface.data_x_times(
+            spi,
+            self.interface.background_byte(color),
+            NUM_DISPLAY_BITS,
+        )?;
 
         // Clear the chromatic
         self.interface.cmd(spi, Command::DataStartTransmission2)?;
         self.interface.data_x_times(spi, color, NUM_DISPLAY_BITS)?;
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
-    fn set_lut(
-        &mut self,
-        _spi: &mut SPI,
-        _refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
-        Ok(())
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let black = pattern::bytes(WIDTH, HEIGHT, 0, pattern::halves);
+        let chromatic = pattern::bytes(WIDTH, HEIGHT, 1, pattern::halves);
+        let len = NUM_DISPLAY_BITS as usize;
+        self.interface
+            .cmd_with_generated(spi, Command::DataStartTransmission1, len, black)?;
+        self.interface
+            .cmd_with_generated(spi, Command::DataStartTransmission2, len, chromatic)?;
+
+        self.wait_until_idle()?;
+        self.interface.set_state(DisplayState::FramePending);
+        self.display_frame(spi, delay)
     }
 
-    fn is_busy(&self) -> bool {
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
         self.interface.is_busy(IS_BUSY_LOW)
     }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> Epd2in9bc<SPI, CS, BUSY, DC, RST, DELAY>
@@ -341,12 +454,43 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, command)
+    /// Turns on the booster and the panel power
+    ///
+    /// Does nothing if the power is already on.
+    /// As long as the power stays on, `display_frame` can refresh the panel
+    /// without going through a power cycle each time.
+    pub fn power_on(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        if !self.powered {
+            self.command(spi, Command::PowerOn)?;
+            let started = self.interface.now();
+            delay.delay_ms(5);
+            self.wait_until_idle()?;
+            self.interface.record_power_on(started);
+            self.powered = true;
+        }
+        Ok(())
     }
 
-    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
-        self.interface.data(spi, data)
+    /// Turns off the booster and the panel power, the displayed image stays
+    ///
+    /// Does nothing if the power is already off.
+    pub fn power_off(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.powered {
+            self.command(spi, Command::PowerOff)?;
+            let started = self.interface.now();
+            self.wait_until_idle()?;
+            self.interface.record_power_off(started);
+            self.powered = false;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd(spi, command)
     }
 
     fn cmd_with_data(
@@ -354,27 +498,39 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn wait_until_idle(&mut self) {
-        let _ = self.interface.wait_until_idle(IS_BUSY_LOW);
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.power_on(spi, delay)?;
+        self.command(spi, Command::DisplayRefresh)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
     }
 
-    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
         let w = self.width();
         let h = self.height();
 
-        self.command(spi, Command::ResolutionSetting)?;
-
-        self.send_data(spi, &[w as u8])?;
-        self.send_data(spi, &[(h >> 8) as u8])?;
-        self.send_data(spi, &[h as u8])
+        self.cmd_with_data(
+            spi,
+            Command::ResolutionSetting,
+            &[w as u8, (h >> 8) as u8, h as u8],
+        )
     }
 
     /// Set the outer border of the display to the chosen color.
-    pub fn set_border_color(&mut self, spi: &mut SPI, color: TriColor) -> Result<(), SPI::Error> {
+    pub fn set_border_color(
+        &mut self,
+        spi: &mut SPI,
+        color: TriColor,
+    ) -> Result<(), Error<SPI::Error>> {
         let border = match color {
             TriColor::Black => BLACK_BORDER,
             TriColor::White => WHITE_BORDER,
@@ -387,3 +543,61 @@ where
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_frame_clears_chromatic() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in9bc<_, _, _, _, _, _>>();
+
+        // a black background does not turn the chromatic frame on
+        epd.set_background_color(Color::Black);
+        epd.update_frame(&mut spi, &[0x00; NUM_DISPLAY_BITS as usize], &mut delay)
+            .unwrap();
+        let chromatic = log.data_after(Command::DataStartTransmission2.address());
+        assert_eq!(chromatic.len(), NUM_DISPLAY_BITS as usize);
+        assert!(chromatic.iter().all(|&byte| byte == 0xff));
+    }
+
+    #[test]
+    fn test_pattern_is_a_generated_frame() {
+        use crate::mock::init_epd;
+        use crate::pattern::{frame_byte, halves};
+        extern crate std;
+        use std::vec::Vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in9bc<_, _, _, _, _, _>>();
+        let plane = |plane| -> Vec<u8> {
+            (0..NUM_DISPLAY_BITS as usize)
+                .map(|i| frame_byte(WIDTH, HEIGHT, plane, i, halves))
+                .collect()
+        };
+
+        log.clear();
+        epd.update_color_frame(&mut spi, &plane(0), &plane(1))
+            .unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        let expected = log.transcript();
+        log.clear();
+        epd.test_pattern(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.transcript(), expected);
+    }
+
+    #[test]
+    fn clear_area_is_unsupported() {
+        use crate::mock::init_epd;
+
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd2in9bc<_, _, _, _, _, _>>();
+        log.clear();
+        assert_eq!(
+            epd.clear_area(&mut spi, 0, 0, 8, 8),
+            Err(Error::Unsupported)
+        );
+        assert!(log.transfers().is_empty());
+    }
+}
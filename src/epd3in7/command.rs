@@ -0,0 +1,84 @@
+//! SPI Commands for the Waveshare 3.7" E-Ink Display
+
+use crate::traits;
+
+/// EPD3in7 commands
+///
+/// Should rarely (never?) be needed directly.
+///
+/// For more infos about the addresses and what they are doing look into the PDFs.
+#[allow(dead_code)]
+#[derive(Copy, Clone)]
+pub(crate) enum Command {
+    /// Set the number of gate lines and the scanning sequence
+    DriverOutputControl = 0x01,
+    /// Set gate driving voltage
+    GateDrivingVoltageControl = 0x03,
+    /// Set source driving voltage
+    SourceDrivingVoltageControl = 0x04,
+    /// Booster soft start
+    SoftStart = 0x0C,
+    /// Deep sleep mode control
+    DeepSleep = 0x10,
+    /// Define data entry sequence
+    DataEntry = 0x11,
+    /// resets the commands and parameters to their S/W Reset default values except R10h-Deep Sleep Mode.
+    /// During operation, BUSY pad will output high.
+    /// Note: RAM are unaffected by this command.
+    SwReset = 0x12,
+    /// Temperature Sensor Selection
+    TemperatureSensorControl = 0x18,
+    /// Activate Display Update Sequence
+    MasterActivation = 0x20,
+    /// RAM content option for Display Update
+    DisplayUpdateControl1 = 0x21,
+    /// Display Update Sequence Option
+    DisplayUpdateControl2 = 0x22,
+    /// After this command, data entries will be written into the BW RAM until another command is written
+    WriteRamBw = 0x24,
+    /// After this command, data entries will be written into the RED RAM until another command is written
+    WriteRamRed = 0x26,
+    /// Write VCOM register from MCU interface
+    VcomWrite = 0x2C,
+    /// Write the LUT register from MCU interface (105 bytes)
+    WriteLut = 0x32,
+    /// Write Register for Display Option
+    DisplayOptionWrite = 0x37,
+    /// Select border waveform for VBD
+    VbdControl = 0x3C,
+    /// Specify the start/end positions of the window address in the X direction by an address unit for RAM
+    SetRamXStartEnd = 0x44,
+    /// Specify the start/end positions of the window address in the Y direction by an address unit for RAM
+    SetRamYStartEnd = 0x45,
+    /// Auto write RED RAM for regular pattern
+    AutoWriteRed = 0x46,
+    /// Auto write B/W RAM for regular pattern
+    AutoWriteBw = 0x47,
+    /// Make initial settings for the RAM X address in the address counter (AC)
+    SetRamXAc = 0x4E,
+    /// Make initial settings for the RAM Y address in the address counter (AC)
+    SetRamYAc = 0x4F,
+    /// This command is an empty command; it does not have any effect on the display module.
+    /// However, it can be used to terminate Frame Memory Write or Read Commands.
+    Nop = 0x7F,
+}
+
+impl traits::Command for Command {
+    /// Returns the address of the command
+    fn address(self) -> u8 {
+        self as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::Command as CommandTrait;
+
+    #[test]
+    fn command_addr() {
+        assert_eq!(Command::MasterActivation.address(), 0x20);
+        assert_eq!(Command::WriteLut.address(), 0x32);
+        assert_eq!(Command::DisplayUpdateControl1.address(), 0x21);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::display::FixedDisplay;
+use crate::epd3in7::{gray_buffer_len, Epd3in7, HEIGHT, WIDTH};
+use crate::flush::impl_flushable;
+use embedded_graphics_core::pixelcolor::Gray2;
+
+/// Display with a fullsize 4 level grayscale buffer for use with the 3in7 EPD
+///
+/// Sent with [update_gray_frame](Epd3in7::update_gray_frame). Can also be manually
+/// constructed: `Display3in7::from_buffer([Gray2::WHITE_BYTE; gray_buffer_len()])`
+pub type Display3in7 = FixedDisplay<WIDTH, HEIGHT, { gray_buffer_len() }, Gray2>;
+
+// the buffer has the frame length the driver checks for
+const _: () = assert!(Display3in7::BUFFER_LEN == gray_buffer_len());
+
+// the driver can `flush` these buffers directly, as gray frames
+impl_flushable!(@impl Display3in7 => Epd3in7, |epd, spi, display, delay| {
+    use crate::graphics::GrayDisplay;
+    epd.update_gray_frame(spi, GrayDisplay::buffer(display), delay)
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorType;
+    use crate::graphics::GrayDisplay;
+    use embedded_graphics::{
+        prelude::*,
+        primitives::{Line, PrimitiveStyle},
+    };
+
+    // test buffer length
+    #[test]
+    fn graphics_size() {
+        let display = Display3in7::default();
+        assert_eq!(display.buffer().len(), 280 * 480 / 4);
+    }
+
+    // test default background color on all bytes
+    #[test]
+    fn graphics_default() {
+        let display = Display3in7::default();
+        for &byte in display.buffer() {
+            assert_eq!(byte, Gray2::WHITE_BYTE);
+        }
+    }
+
+    #[test]
+    fn graphics_gray_levels() {
+        let mut display = Display3in7::default();
+        for level in 0..4 {
+            let _ = Line::new(Point::new(level, 0), Point::new(level, 0))
+                .into_styled(PrimitiveStyle::with_stroke(Gray2::new(level as u8), 1))
+                .draw(&mut display);
+        }
+        assert_eq!(display.buffer()[0], 0b00_01_10_11);
+        assert_eq!(display.buffer()[1], Gray2::WHITE_BYTE);
+    }
+}
@@ -0,0 +1,684 @@
+//! A simple Driver for the Waveshare 3.7" E-Ink Display via SPI
+//!
+//! Polarity: the SSD1677 shows a set bit as white like the buffers
+//! ([Polarity::ClearedIsColor]).
+//!
+//! # References
+//!
+//! - [Datasheet](https://www.waveshare.com/w/upload/7/71/3.7inch_e-Paper_Specification.pdf)
+//! - [Waveshare C driver](https://github.com/waveshare/e-Paper/blob/master/RaspberryPi_JetsonNano/c/lib/e-Paper/EPD_3in7.c)
+//!
+//! # Example for the 3.7 in E-Ink Display with 4 gray levels
+//!
+//!```rust, no_run
+//!# use embedded_hal_mock::*;
+//!# fn main() -> Result<(), epd_waveshare::Error<MockError>> {
+//!use embedded_graphics::{
+//!    pixelcolor::Gray2, prelude::*, primitives::{Line, PrimitiveStyle},
+//!};
+//!use epd_waveshare::{epd3in7::*, graphics::GrayDisplay, prelude::*};
+//!#
+//!# let expectations = [];
+//!# let mut spi = spi::Mock::new(&expectations);
+//!# let expectations = [];
+//!# let cs_pin = pin::Mock::new(&expectations);
+//!# let busy_in = pin::Mock::new(&expectations);
+//!# let dc = pin::Mock::new(&expectations);
+//!# let rst = pin::Mock::new(&expectations);
+//!# let mut delay = delay::MockNoop::new();
+//!
+//!// Setup EPD
+//!let mut epd = Epd3in7::new(cs_pin, busy_in, dc, rst);
+//!epd.init(&mut spi, &mut delay)?;
+//!
+//!// Use display graphics from embedded-graphics, with 2 bits per pixel
+//!let mut display = Display3in7::default();
+//!
+//!// A dark and a light gray line
+//!let _ = Line::new(Point::new(0, 120), Point::new(0, 295))
+//!    .into_styled(PrimitiveStyle::with_stroke(Gray2::new(1), 1))
+//!    .draw(&mut display);
+//!let _ = Line::new(Point::new(1, 120), Point::new(1, 295))
+//!    .into_styled(PrimitiveStyle::with_stroke(Gray2::new(2), 1))
+//!    .draw(&mut display);
+//!
+//!// Display updated frame
+//!epd.update_gray_frame(&mut spi, display.buffer(), &mut delay)?;
+//!epd.display_frame(&mut spi, &mut delay)?;
+//!
+//!// Set the EPD to sleep
+//!epd.sleep(&mut spi, &mut delay)?;
+//!# Ok(())
+//!# }
+//!```
+//!
+//! # Gray levels
+//!
+//! The controller takes the gray level of a pixel as one bit in each of its RAMs, the
+//! [gray] planes: [GrayPlane::Low] goes to the b/w RAM (0x24) and
+//! [GrayPlane::High] to the red one (0x26). The waveform of the 4 gray LUT drives each of
+//! the four bit combinations to one of the levels.
+//!
+//! - [update_gray_frame](Epd3in7::update_gray_frame) sends a frame of a [GrayDisplay]
+//!   buffer, split into the planes while it is sent, and the next
+//!   [display_frame](WaveshareDisplay::display_frame) refreshes with the 4 gray LUT.
+//! - [update_frame](WaveshareDisplay::update_frame) and the other b/w frames only go to
+//!   the b/w RAM. Their refresh reads the red RAM as cleared and uses the b/w LUT of
+//!   [set_lut](WaveshareDisplay::set_lut): [RefreshLut::Full] for the full refresh,
+//!   [RefreshLut::Quick] for the fast one without flashing.
+//!
+//! [GrayDisplay]: crate::graphics::GrayDisplay
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::{InputPin, OutputPin},
+};
+
+use crate::color::{buffer_size, Color, Polarity};
+use crate::error::Error;
+use crate::gray::{self, GrayPlane};
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
+};
+use crate::pattern;
+use crate::traits::{DisplayState, RefreshLut, StreamFrame, WaveshareDisplay};
+
+pub(crate) mod command;
+use self::command::Command;
+mod constants;
+use self::constants::{LUT_1GRAY_DU, LUT_1GRAY_GC, LUT_4GRAY_GC};
+
+#[cfg(feature = "graphics")]
+mod graphics;
+#[cfg(feature = "graphics")]
+pub use self::graphics::Display3in7;
+
+/// Width of the display
+pub const WIDTH: u32 = 280;
+/// Height of the display
+pub const HEIGHT: u32 = 480;
+/// Default Background Color
+pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full b/w frame buffer for epd3in7
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+/// Length in bytes of a full 4 level grayscale frame buffer for epd3in7
+pub const fn gray_buffer_len() -> usize {
+    gray::buffer_size(WIDTH as usize, HEIGHT as usize)
+}
+// The SSD1677 pulls BUSY high while it is working
+const IS_BUSY_LOW: bool = false;
+
+// Display update control 1: the b/w frames read the red RAM as cleared, the gray ones
+// take both RAMs as they are
+const BW_RAM_OPTION: [u8; 2] = [0x40, 0x00];
+const GRAY_RAM_OPTION: [u8; 2] = [0x00, 0x00];
+
+/// EPD3in7 driver
+///
+pub struct Epd3in7<SPI, CS, BUSY, DC, RST, DELAY> {
+    /// Connection Interface
+    interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
+    /// Background Color
+    color: Color,
+    /// LUT of the b/w frames
+    refresh: RefreshLut,
+    /// Whether the frame in the RAMs is a gray frame
+    gray_frame: bool,
+    /// Whether the controller holds the 4 gray LUT instead of the one of `refresh`
+    gray_lut: bool,
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
+    for Epd3in7<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    type DisplayColor = Color;
+    const SUPPORTS_PARTIAL: bool = false;
+    const SUPPORTS_QUICK_REFRESH: bool = true;
+    const SUPPORTS_LUT: bool = true;
+    const SUPPORTS_GRAYSCALE: bool = true;
+    const COLOR_PLANES: u8 = 1;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
+        let interface = DisplayInterface::new(cs, busy, dc, rst);
+        let color = DEFAULT_BACKGROUND_COLOR;
+
+        Epd3in7 {
+            interface,
+            color,
+            refresh: RefreshLut::Full,
+            gray_frame: false,
+            gray_lut: false,
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        // The init of the Waveshare driver for 4 gray levels, which also refreshes in b/w
+        // with its own LUTs
+        self.interface.reset(delay, 3)?;
+
+        // SSD1677: BUSY is high while the controller loads its settings after the reset
+        self.wait_until_idle()?;
+        self.command(spi, Command::SwReset)?;
+        self.wait_until_idle()?;
+
+        self.cmd_with_data(spi, Command::AutoWriteRed, &[0xF7])?;
+        self.wait_until_idle()?;
+        self.cmd_with_data(spi, Command::AutoWriteBw, &[0xF7])?;
+        self.wait_until_idle()?;
+
+        // 480 gate lines
+        self.cmd_with_data(spi, Command::DriverOutputControl, &[0xDF, 0x01, 0x00])?;
+        self.cmd_with_data(spi, Command::GateDrivingVoltageControl, &[0x00])?;
+        self.cmd_with_data(
+            spi,
+            Command::SourceDrivingVoltageControl,
+            &[0x41, 0xA8, 0x32],
+        )?;
+        // x and y increment
+        self.cmd_with_data(spi, Command::DataEntry, &[0x03])?;
+        self.cmd_with_data(spi, Command::VbdControl, &[0x00])?;
+        self.cmd_with_data(spi, Command::SoftStart, &[0xAE, 0xC7, 0xC3, 0xC0, 0xC0])?;
+        // internal temperature sensor
+        self.cmd_with_data(spi, Command::TemperatureSensorControl, &[0x80])?;
+        self.cmd_with_data(spi, Command::VcomWrite, &[0x44])?;
+        self.cmd_with_data(spi, Command::DisplayOptionWrite, &[0x00; 10])?;
+
+        // the x addresses are in pixels, 0 to 279
+        self.cmd_with_data(spi, Command::SetRamXStartEnd, &[0x00, 0x00, 0x17, 0x01])?;
+        self.cmd_with_data(spi, Command::SetRamYStartEnd, &[0x00, 0x00, 0xDF, 0x01])?;
+
+        self.gray_frame = false;
+        self.set_lut(spi, None)
+    }
+
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
+    }
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.init(spi, delay)
+    }
+
+    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        self.cmd_with_data(spi, Command::DeepSleep, &[0x01])?;
+        self.interface.asleep()?;
+        Ok(())
+    }
+
+    fn update_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
+        self.begin_frame(spi, delay)?;
+        let background = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
+        self.interface
+            .padded_frame_data(spi, buffer, buffer_len(), background, Self::POLARITY)?;
+        self.end_frame(spi, delay)
+    }
+
+    fn update_partial_frame(
+        &mut self,
+        _spi: &mut SPI,
+        _buffer: &[u8],
+        _x: u32,
+        _y: u32,
+        _width: u32,
+        _height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
+    }
+
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
+        self.wait_until_idle()?;
+        Ok(())
+    }
+
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
+    fn update_and_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.update_frame(spi, buffer, delay)?;
+        self.display_frame(spi, delay)?;
+        Ok(())
+    }
+
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let background = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
+        self.begin_frame(spi, delay)?;
+        self.interface
+            .data_x_times(spi, background, buffer_len() as u32)?;
+        self.end_frame(spi, delay)
+    }
+
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let bytes = pattern::bytes(WIDTH, HEIGHT, 0, pattern::checkerboard);
+        pattern::stream::<_, CS, BUSY, DC, RST, _, _>(self, spi, delay, buffer_len(), bytes)?;
+        self.display_frame(spi, delay)
+    }
+
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
+    fn set_background_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    fn background_color(&self) -> &Color {
+        &self.color
+    }
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
+    fn width(&self) -> u32 {
+        WIDTH
+    }
+
+    fn height(&self) -> u32 {
+        HEIGHT
+    }
+
+    /// Loads the LUT of the b/w frames, the gray frames always refresh with the 4 gray one
+    fn set_lut(
+        &mut self,
+        spi: &mut SPI,
+        refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>> {
+        if let Some(refresh_lut) = refresh_rate {
+            self.refresh = refresh_lut;
+        }
+        let lut = match self.refresh {
+            RefreshLut::Full => &LUT_1GRAY_GC,
+            RefreshLut::Quick => &LUT_1GRAY_DU,
+        };
+        self.set_lut_helper(spi, lut)?;
+        self.gray_lut = false;
+        Ok(())
+    }
+
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(IS_BUSY_LOW)
+    }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY> StreamFrame<SPI, CS, BUSY, DC, RST, DELAY>
+    for Epd3in7<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    fn begin_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        self.set_ram_counter(spi)?;
+        self.command(spi, Command::WriteRamBw)
+    }
+    fn frame_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.interface.frame_data(spi, data, Self::POLARITY)
+    }
+    fn end_frame(&mut self, _spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.gray_frame = false;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY> Epd3in7<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    /// Transmits a 4 level grayscale frame to both RAMs, for the next
+    /// [display_frame](WaveshareDisplay::display_frame) with the 4 gray LUT
+    ///
+    /// `buffer` has the [gray layout](crate::graphics#buffer-layout) of a `Display3in7`
+    /// and exactly [gray_buffer_len] bytes, it is split into the [gray] planes while it is
+    /// sent.
+    pub fn update_gray_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_exact_len(buffer, gray_buffer_len())?;
+        self.wait_until_idle()?;
+        for &(ram, plane) in [
+            (Command::WriteRamBw, GrayPlane::Low),
+            (Command::WriteRamRed, GrayPlane::High),
+        ]
+        .iter()
+        {
+            self.set_ram_counter(spi)?;
+            self.command(spi, ram)?;
+            self.interface.generated_frame_data(
+                spi,
+                buffer_len(),
+                |index| gray::plane_byte(buffer, WIDTH, plane, index),
+                Self::POLARITY,
+            )?;
+        }
+        self.gray_frame = true;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd(spi, command)
+    }
+
+    fn cmd_with_data(
+        &mut self,
+        spi: &mut SPI,
+        command: Command,
+        data: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd_with_data(spi, command, data)
+    }
+
+    fn set_ram_counter(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
+        self.cmd_with_data(spi, Command::SetRamXAc, &[0x00, 0x00])?;
+        self.cmd_with_data(spi, Command::SetRamYAc, &[0x00, 0x00])
+    }
+
+    fn set_lut_helper(&mut self, spi: &mut SPI, lut: &[u8; 105]) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        self.cmd_with_data(spi, Command::WriteLut, lut)
+    }
+
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    ///
+    /// Loads the LUT of the frame if the controller holds the other one.
+    fn start_refresh(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.gray_frame {
+            if !self.gray_lut {
+                self.set_lut_helper(spi, &LUT_4GRAY_GC)?;
+                self.gray_lut = true;
+            }
+            self.cmd_with_data(spi, Command::DisplayUpdateControl1, &GRAY_RAM_OPTION)?;
+        } else {
+            if self.gray_lut {
+                self.set_lut(spi, None)?;
+            }
+            self.cmd_with_data(spi, Command::DisplayUpdateControl1, &BW_RAM_OPTION)?;
+        }
+        self.cmd_with_data(spi, Command::DisplayUpdateControl2, &[0xC7])?;
+        self.command(spi, Command::MasterActivation)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epd_size() {
+        assert_eq!(WIDTH, 280);
+        assert_eq!(HEIGHT, 480);
+        assert_eq!(DEFAULT_BACKGROUND_COLOR, Color::White);
+        assert_eq!(buffer_len(), 280 / 8 * 480);
+        assert_eq!(gray_buffer_len(), 280 / 4 * 480);
+    }
+
+    // Everything sent for init, a frame, its refresh and sleep
+    #[test]
+    fn golden_transcript() {
+        use crate::mock::{Log, Pin};
+        extern crate std;
+        use std::vec;
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = log.delay();
+        let mut epd = Epd3in7::new(Pin, log.busy(), log.dc(), Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+        let buffer = vec![0xAB; buffer_len()];
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        assert_eq!(
+            log.transcript(),
+            [
+                "delay 10ms",
+                "delay 3ms",
+                "delay 200ms",
+                "wait until low",
+                "12",
+                "wait until low",
+                "46: F7",
+                "wait until low",
+                "47: F7",
+                "wait until low",
+                "01: DF 01 00",
+                "03: 00",
+                "04: 41 A8 32",
+                "11: 03",
+                "3C: 00",
+                "0C: AE C7 C3 C0 C0",
+                "18: 80",
+                "2C: 44",
+                "37: 00 00 00 00 00 00 00 00 00 00",
+                "44: 00 00 17 01",
+                "45: 00 00 DF 01",
+                "wait until low",
+                "32: 105 bytes",
+                "wait until low",
+                "4E: 00 00",
+                "4F: 00 00",
+                "24: 16800 bytes of AB",
+                "21: 40 00",
+                "22: C7",
+                "20",
+                "wait until low",
+                "10: 01",
+            ]
+        );
+    }
+
+    #[test]
+    fn gray_frame_planes() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd3in7<_, _, _, _, _, _>>();
+        log.clear();
+
+        // the first row: black, dark gray, light gray and white, then white
+        let mut buffer = vec![0xFF; gray_buffer_len()];
+        buffer[0] = 0b00_01_10_11;
+        epd.update_gray_frame(&mut spi, &buffer, &mut delay)
+            .unwrap();
+        assert_eq!(epd.state(), DisplayState::FramePending);
+
+        let mut low = vec![0xFF; buffer_len()];
+        low[0] = 0b0101_1111;
+        let mut high = vec![0xFF; buffer_len()];
+        high[0] = 0b0011_1111;
+        assert_eq!(log.data_after(Command::WriteRamBw.address()), low);
+        assert_eq!(log.data_after(Command::WriteRamRed.address()), high);
+
+        assert!(matches!(
+            epd.update_gray_frame(&mut spi, &buffer[1..], &mut delay),
+            Err(Error::BufferTooShort { .. })
+        ));
+    }
+
+    #[test]
+    fn luts_follow_the_frames() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd3in7<_, _, _, _, _, _>>();
+        let gray = vec![0xFF; gray_buffer_len()];
+        let bw = vec![0xFF; buffer_len()];
+        let lut = Command::WriteLut.address();
+        let ram_option = Command::DisplayUpdateControl1.address();
+
+        // init loads the full b/w LUT
+        assert_eq!(log.data_after(lut), LUT_1GRAY_GC);
+        log.clear();
+        epd.update_and_display_frame(&mut spi, &bw, &mut delay)
+            .unwrap();
+        assert_eq!(log.count_command(lut), 0);
+        assert_eq!(log.data_after(ram_option), BW_RAM_OPTION);
+
+        // the gray frames load their LUT once
+        log.clear();
+        for _ in 0..2 {
+            epd.update_gray_frame(&mut spi, &gray, &mut delay).unwrap();
+            epd.display_frame(&mut spi, &mut delay).unwrap();
+        }
+        assert_eq!(log.data_after_each(lut), [LUT_4GRAY_GC.to_vec()]);
+        assert_eq!(log.data_after(ram_option), GRAY_RAM_OPTION);
+
+        // and the next b/w frame the one of set_lut again
+        epd.set_lut(&mut spi, Some(RefreshLut::Quick)).unwrap();
+        epd.update_gray_frame(&mut spi, &gray, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        log.clear();
+        epd.update_and_display_frame(&mut spi, &bw, &mut delay)
+            .unwrap();
+        assert_eq!(log.data_after(lut), LUT_1GRAY_DU);
+        assert_eq!(log.data_after(ram_option), BW_RAM_OPTION);
+    }
+
+    #[test]
+    fn clear_frame_without_refresh() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd3in7<_, _, _, _, _, _>>();
+        log.clear();
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::MasterActivation.address()), 0);
+        assert_eq!(epd.state(), DisplayState::FramePending);
+        assert_eq!(
+            log.data_after(Command::WriteRamBw.address()),
+            vec![0xFF; buffer_len()]
+        );
+
+        // the cleared frame is a b/w one
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(
+            log.data_after(Command::DisplayUpdateControl1.address()),
+            BW_RAM_OPTION
+        );
+    }
+
+    #[test]
+    fn capabilities() {
+        use crate::mock::{BusyPin, Delay, Pin, Spi};
+        type Epd = Epd3in7<Spi, Pin, BusyPin, Pin, Pin, Delay>;
+
+        let capabilities = (
+            Epd::SUPPORTS_PARTIAL,
+            Epd::SUPPORTS_QUICK_REFRESH,
+            Epd::SUPPORTS_GRAYSCALE,
+            Epd::COLOR_PLANES,
+        );
+        assert_eq!(capabilities, (false, true, true, 1));
+    }
+}
@@ -1,64 +1,19 @@
-use crate::epd4in2::{DEFAULT_BACKGROUND_COLOR, HEIGHT, WIDTH};
-use crate::graphics::{Display, DisplayRotation};
-use embedded_graphics::pixelcolor::BinaryColor;
-use embedded_graphics_core::prelude::*;
+use crate::display::FixedDisplay;
+use crate::epd4in2::{buffer_len, Epd4in2, HEIGHT, WIDTH};
+use crate::flush::impl_flushable;
+use embedded_graphics_core::pixelcolor::BinaryColor;
 
 /// Full size buffer for use with the 4in2 EPD
 ///
-/// Can also be manuall constructed:
-/// `buffer: [DEFAULT_BACKGROUND_COLOR.get_byte_value(); WIDTH / 8 * HEIGHT]`
-pub struct Display4in2 {
-    buffer: [u8; WIDTH as usize * HEIGHT as usize / 8],
-    rotation: DisplayRotation,
-}
-
-impl Default for Display4in2 {
-    fn default() -> Self {
-        Display4in2 {
-            buffer: [DEFAULT_BACKGROUND_COLOR.get_byte_value();
-                WIDTH as usize * HEIGHT as usize / 8],
-            rotation: DisplayRotation::default(),
-        }
-    }
-}
-
-impl DrawTarget for Display4in2 {
-    type Color = BinaryColor;
-    type Error = core::convert::Infallible;
-    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
-    where
-        I: IntoIterator<Item = Pixel<Self::Color>>,
-    {
-        for pixel in pixels {
-            self.draw_helper(WIDTH, HEIGHT, pixel)?;
-        }
-        Ok(())
-    }
-}
-
-impl OriginDimensions for Display4in2 {
-    fn size(&self) -> Size {
-        Size::new(WIDTH, HEIGHT)
-    }
-}
-
-impl Display for Display4in2 {
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
-    }
+/// Can also be manually constructed:
+/// `Display4in2::from_buffer([DEFAULT_BACKGROUND_COLOR.get_byte_value(); buffer_len()])`
+pub type Display4in2 = FixedDisplay<WIDTH, HEIGHT, { buffer_len() }, BinaryColor>;
 
-    fn get_mut_buffer(&mut self) -> &mut [u8] {
-        &mut self.buffer
-    }
+// the buffer has the frame length the driver checks for
+const _: () = assert!(Display4in2::BUFFER_LEN == buffer_len());
 
-    fn set_rotation(&mut self, rotation: DisplayRotation) {
-        self.rotation = rotation;
-    }
-
-    fn rotation(&self) -> DisplayRotation {
-        self.rotation
-    }
-}
+// the driver can `flush` these buffers directly
+impl_flushable!(Display4in2 => Epd4in2, crate::graphics::Display);
 
 #[cfg(test)]
 mod tests {
@@ -161,4 +116,15 @@ mod tests {
             assert_eq!(byte, epd4in2::DEFAULT_BACKGROUND_COLOR.get_byte_value());
         }
     }
+
+    #[test]
+    fn from_and_into_buffer() {
+        let mut display = Display4in2::from_buffer([0xff; buffer_len()]);
+        display.buffer_mut()[..2].copy_from_slice(&[0x00, 0x0f]);
+        assert_eq!(display.get_pixel(Point::new(7, 0)), Some(Black));
+        assert_eq!(display.get_pixel(Point::new(12, 0)), Some(BinaryColor::Off));
+
+        let buffer = display.into_buffer();
+        assert_eq!(buffer[..3], [0x00, 0x0f, 0xff]);
+    }
 }
@@ -1,5 +1,8 @@
 //! A simple Driver for the Waveshare 4.2" E-Ink Display via SPI
 //!
+//! Polarity: the IL0398 in its b/w lut mode shows a set bit as white
+//! ([Polarity::ClearedIsColor]).
+//!
 //!
 //! Build with the help of documentation/code from [Waveshare](https://www.waveshare.com/wiki/4.2inch_e-Paper_Module),
 //! [Ben Krasnows partial Refresh tips](https://benkrasnow.blogspot.de/2017/10/fast-partial-refresh-on-42-e-paper.html) and
@@ -9,7 +12,7 @@
 //!
 //!```rust, no_run
 //!# use embedded_hal_mock::*;
-//!# fn main() -> Result<(), MockError> {
+//!# fn main() -> Result<(), epd_waveshare::Error<MockError>> {
 //!use embedded_graphics::{
 //!    pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
 //!};
@@ -25,7 +28,8 @@
 //!# let mut delay = delay::MockNoop::new();
 //!
 //!// Setup EPD
-//!let mut epd = Epd4in2::new(&mut spi, cs_pin, busy_in, dc, rst, &mut delay)?;
+//!let mut epd = Epd4in2::new(cs_pin, busy_in, dc, rst);
+//!epd.init(&mut spi, &mut delay)?;
 //!
 //!// Use display graphics from embedded-graphics
 //!let mut display = Display4in2::default();
@@ -48,14 +52,25 @@
 //!
 //!
 //! BE CAREFUL! The screen can get ghosting/burn-ins through the Partial Fast Update Drawing.
+//!
+//! # Good Display panels
+//!
+//! The Good Display GDEW042T2 is the same panel with a slightly different init, use
+//! [Epd4in2::new_with_variant] with [Variant::GDEW042T2] for it.
 
 use embedded_hal::{
     blocking::{delay::*, spi::Write},
     digital::v2::*,
 };
 
-use crate::interface::DisplayInterface;
-use crate::traits::{InternalWiAdditions, QuickRefresh, RefreshLut, WaveshareDisplay};
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
+};
+use crate::pattern;
+use crate::traits::DisplayState;
+use crate::traits::{QuickRefresh, RefreshLut, StreamFrame, WaveshareDisplay};
 
 //The Lookup Tables for the Display
 mod constants;
@@ -67,9 +82,14 @@ pub const WIDTH: u32 = 400;
 pub const HEIGHT: u32 = 300;
 /// Default Background Color
 pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full frame buffer for epd4in2
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+// The IL0398 pulls BUSY low while it is working
 const IS_BUSY_LOW: bool = true;
 
-use crate::color::Color;
+use crate::color::{buffer_size, Color, Polarity};
 
 pub(crate) mod command;
 use self::command::Command;
@@ -79,6 +99,63 @@ mod graphics;
 #[cfg(feature = "graphics")]
 pub use self::graphics::Display4in2;
 
+/// Which tables the quick refresh ([RefreshLut::Quick]) uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuickLut {
+    /// The partial refresh tables of the current vendor driver, the default
+    Vendor,
+    /// The quick tables of older vendor releases, which earlier versions of this driver used
+    ///
+    /// Lower contrast on most panels, for the case a panel revision was tuned with them
+    Legacy,
+}
+
+impl QuickLut {
+    /// VCOM and data interval setting (CDI) paired with the tables
+    fn cdi(self) -> u8 {
+        match self {
+            QuickLut::Vendor => 0x47,
+            QuickLut::Legacy => FULL_CDI,
+        }
+    }
+}
+
+/// Panel the driver is set up for, see [Epd4in2::new_with_variant]
+///
+/// The clones of the Waveshare panel share the controller, the tables and the frame
+/// format, only a few bytes of the init differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// The Waveshare 4.2" module, the default
+    Waveshare,
+    /// The bare Good Display GDEW042T2 panel
+    ///
+    /// Sets the resolution with the bits of the panel setting (0xBF) as well and
+    /// uses a VCOM DC of -2.1V (0x28), like the Good Display sample code.
+    GDEW042T2,
+}
+
+impl Variant {
+    /// Panel setting (PSR) of the init
+    fn panel_setting(self) -> u8 {
+        match self {
+            Variant::Waveshare => 0x3F,
+            Variant::GDEW042T2 => 0xBF,
+        }
+    }
+
+    /// VCOM DC setting of the init
+    fn vcom_dc(self) -> u8 {
+        match self {
+            Variant::Waveshare => 0x12,
+            Variant::GDEW042T2 => 0x28,
+        }
+    }
+}
+
+//VBDF 17|D7 VBDW 97  VBDB 57  VBDF F7  VBDW 77  VBDB 37  VBDR B7
+const FULL_CDI: u8 = 0x97;
+
 /// Epd4in2 driver
 ///
 pub struct Epd4in2<SPI, CS, BUSY, DC, RST, DELAY> {
@@ -88,9 +165,15 @@ pub struct Epd4in2<SPI, CS, BUSY, DC, RST, DELAY> {
     color: Color,
     /// Refresh LUT
     refresh: RefreshLut,
+    /// Tables of the quick refresh
+    quick_lut: QuickLut,
+    /// Whether the panel power is on
+    powered: bool,
+    /// Panel the init is sent for
+    variant: Variant,
 }
 
-impl<SPI, CS, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, CS, BUSY, DC, RST, DELAY>
+impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
     for Epd4in2<SPI, CS, BUSY, DC, RST, DELAY>
 where
     SPI: Write<u8>,
@@ -100,9 +183,34 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    type DisplayColor = Color;
+    const SUPPORTS_PARTIAL: bool = true;
+    const SUPPORTS_QUICK_REFRESH: bool = true;
+    const SUPPORTS_LUT: bool = true;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 1;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
+        let interface = DisplayInterface::new(cs, busy, dc, rst);
+        let color = DEFAULT_BACKGROUND_COLOR;
+
+        Epd4in2 {
+            interface,
+            color,
+            refresh: RefreshLut::Full,
+            quick_lut: QuickLut::Vendor,
+            powered: false,
+            variant: Variant::Waveshare,
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // reset the device
-        self.interface.reset(delay, 10);
+        self.interface.reset(delay, 10)?;
+        // IL0398: BUSY is low for a few ms after the reset, commands sent before are ignored
+        self.wait_until_idle()?;
+        self.powered = false;
 
         // set the power settings
         self.interface.cmd_with_data(
@@ -116,12 +224,10 @@ where
             .cmd_with_data(spi, Command::BoosterSoftStart, &[0x17, 0x17, 0x17])?;
 
         // power on
-        self.command(spi, Command::PowerOn)?;
-        delay.delay_ms(5);
-        self.wait_until_idle();
+        self.power_on(spi, delay)?;
 
         // set the panel settings
-        self.cmd_with_data(spi, Command::PanelSetting, &[0x3F])?;
+        self.cmd_with_data(spi, Command::PanelSetting, &[self.variant.panel_setting()])?;
 
         // Set Frequency, 200 Hz didn't work on my board
         // 150Hz and 171Hz wasn't tested yet
@@ -132,72 +238,37 @@ where
         self.send_resolution(spi)?;
 
         self.interface
-            .cmd_with_data(spi, Command::VcmDcSetting, &[0x12])?;
-
-        //VBDF 17|D7 VBDW 97  VBDB 57  VBDF F7  VBDW 77  VBDB 37  VBDR B7
-        self.interface
-            .cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[0x97])?;
+            .cmd_with_data(spi, Command::VcmDcSetting, &[self.variant.vcom_dc()])?;
 
+        // also sets the vcom and data interval setting belonging to the lut
         self.set_lut(spi, None)?;
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
         Ok(())
     }
-}
-
-impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
-    for Epd4in2<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    type DisplayColor = Color;
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
-        let interface = DisplayInterface::new(cs, busy, dc, rst);
-        let color = DEFAULT_BACKGROUND_COLOR;
-
-        let mut epd = Epd4in2 {
-            interface,
-            color,
-            refresh: RefreshLut::Full,
-        };
-
-        epd.init(spi, delay)?;
 
-        Ok(epd)
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         self.interface
             .cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[0x17])?; //border floating
         self.command(spi, Command::VcmDcSetting)?; // VCOM to 0V
         self.command(spi, Command::PanelSetting)?;
 
         self.command(spi, Command::PowerSetting)?; //VG&VS to 0V fast
-        for _ in 0..4 {
-            self.send_data(spi, &[0x00])?;
-        }
+        self.interface.data_x_times(spi, 0x00, 4)?;
 
-        self.command(spi, Command::PowerOff)?;
-        self.wait_until_idle();
+        self.power_off(spi, delay)?;
         self.interface
             .cmd_with_data(spi, Command::DeepSleep, &[0xA5])?;
+        self.interface.asleep()?;
         Ok(())
     }
 
@@ -206,16 +277,21 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        let color_value = self.color.get_byte_value();
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
+        self.wait_until_idle()?;
+        let color_value = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
 
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
         self.interface
             .data_x_times(spi, color_value, WIDTH / 8 * HEIGHT)?;
 
+        self.interface.cmd(spi, Command::DataStartTransmission2)?;
         self.interface
-            .cmd_with_data(spi, Command::DataStartTransmission2, buffer)?;
+            .padded_frame_data(spi, buffer, buffer_len(), color_value, Self::POLARITY)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
@@ -227,48 +303,40 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        if buffer.len() as u32 != width / 8 * height {
-            //TODO: panic!! or sth like that
-            //return Err("Wrong buffersize");
-        }
-
-        self.command(spi, Command::PartialIn)?;
-        self.command(spi, Command::PartialWindow)?;
-        self.send_data(spi, &[(x >> 8) as u8])?;
-        let tmp = x & 0xf8;
-        self.send_data(spi, &[tmp as u8])?; // x should be the multiple of 8, the last 3 bit will always be ignored
-        let tmp = tmp + width - 1;
-        self.send_data(spi, &[(tmp >> 8) as u8])?;
-        self.send_data(spi, &[(tmp | 0x07) as u8])?;
-
-        self.send_data(spi, &[(y >> 8) as u8])?;
-        self.send_data(spi, &[y as u8])?;
-
-        self.send_data(spi, &[((y + height - 1) >> 8) as u8])?;
-        self.send_data(spi, &[(y + height - 1) as u8])?;
-
-        self.send_data(spi, &[0x01])?; // Gates scan both inside and outside of the partial window. (default)
-
-        //TODO: handle dtm somehow
-        let is_dtm1 = false;
-        if is_dtm1 {
-            self.command(spi, Command::DataStartTransmission1)? //TODO: check if data_start transmission 1 also needs "old"/background data here
-        } else {
-            self.command(spi, Command::DataStartTransmission2)?
-        }
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = (width.div_ceil(8) * height) as usize;
+        self.interface.check_len(buffer, len)?;
+        self.write_partial(spi, buffer, x, y, width, height)
+    }
 
-        self.send_data(spi, buffer)?;
+    fn clear_area(
+        &mut self,
+        spi: &mut SPI,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.write_partial(spi, &[], x, y, width, height)
+    }
 
-        self.command(spi, Command::PartialOut)?;
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
         Ok(())
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        self.command(spi, Command::DisplayRefresh)?;
-        Ok(())
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
     }
 
     fn update_and_display_frame(
@@ -276,17 +344,19 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.command(spi, Command::DisplayRefresh)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         self.send_resolution(spi)?;
 
-        let color_value = self.color.get_byte_value();
+        let color_value = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
 
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
         self.interface
@@ -295,9 +365,68 @@ where
         self.interface.cmd(spi, Command::DataStartTransmission2)?;
         self.interface
             .data_x_times(spi, color_value, WIDTH / 8 * HEIGHT)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        let color_value = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
+
+        self.interface.cmd(spi, Command::DataStartTransmission1)?;
+        self.interface
+            .data_x_times(spi, color_value, WIDTH / 8 * HEIGHT)?;
+
+        let checkerboard = pattern::bytes(WIDTH, HEIGHT, 0, pattern::checkerboard);
+        self.interface.cmd(spi, Command::DataStartTransmission2)?;
+        self.interface
+            .generated_frame_data(spi, buffer_len(), checkerboard, Self::POLARITY)?;
+        self.interface.set_state(DisplayState::FramePending);
+        self.display_frame(spi, delay)
+    }
+
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
     fn set_background_color(&mut self, color: Color) {
         self.color = color;
     }
@@ -306,6 +435,14 @@ where
         &self.color
     }
 
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -318,28 +455,78 @@ where
         &mut self,
         spi: &mut SPI,
         refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         if let Some(refresh_lut) = refresh_rate {
             self.refresh = refresh_lut;
         }
-        match self.refresh {
+        let cdi = match self.refresh {
             RefreshLut::Full => {
-                self.set_lut_helper(spi, &LUT_VCOM0, &LUT_WW, &LUT_BW, &LUT_WB, &LUT_BB)
+                self.set_lut_helper(spi, &LUT_VCOM0, &LUT_WW, &LUT_BW, &LUT_WB, &LUT_BB)?;
+                FULL_CDI
             }
-            RefreshLut::Quick => self.set_lut_helper(
-                spi,
-                &LUT_VCOM0_QUICK,
-                &LUT_WW_QUICK,
-                &LUT_BW_QUICK,
-                &LUT_WB_QUICK,
-                &LUT_BB_QUICK,
-            ),
-        }
+            RefreshLut::Quick => {
+                match self.quick_lut {
+                    QuickLut::Vendor => self.set_lut_helper(
+                        spi,
+                        &LUT_VCOM0_PARTIAL,
+                        &LUT_WW_PARTIAL,
+                        &LUT_BW_PARTIAL,
+                        &LUT_WB_PARTIAL,
+                        &LUT_BB_PARTIAL,
+                    )?,
+                    QuickLut::Legacy => self.set_lut_helper(
+                        spi,
+                        &LUT_VCOM0_QUICK,
+                        &LUT_WW_QUICK,
+                        &LUT_BW_QUICK,
+                        &LUT_WB_QUICK,
+                        &LUT_BB_QUICK,
+                    )?,
+                }
+                self.quick_lut.cdi()
+            }
+        };
+        self.interface
+            .cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[cdi])
     }
 
-    fn is_busy(&self) -> bool {
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
         self.interface.is_busy(IS_BUSY_LOW)
     }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY> StreamFrame<SPI, CS, BUSY, DC, RST, DELAY>
+    for Epd4in2<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    fn begin_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        // the old frame is the background like in update_frame
+        self.wait_until_idle()?;
+        let color_value = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
+        self.interface.cmd(spi, Command::DataStartTransmission1)?;
+        self.interface
+            .data_x_times(spi, color_value, WIDTH / 8 * HEIGHT)?;
+        self.interface.cmd(spi, Command::DataStartTransmission2)
+    }
+    fn frame_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.interface.frame_data(spi, data, Self::POLARITY)
+    }
+    fn end_frame(&mut self, _spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> Epd4in2<SPI, CS, BUSY, DC, RST, DELAY>
@@ -351,11 +538,63 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
+    /// Creates a new driver for a clone of the Waveshare panel
+    ///
+    /// Like [new](WaveshareDisplay::new), but [init](WaveshareDisplay::init) and
+    /// [wake_up](WaveshareDisplay::wake_up) send the bytes of `variant`.
+    pub fn new_with_variant(cs: CS, busy: BUSY, dc: DC, rst: RST, variant: Variant) -> Self {
+        Epd4in2 {
+            variant,
+            ..Self::new(cs, busy, dc, rst)
+        }
+    }
+
+    /// Selects the tables of the quick refresh, used from the next
+    /// [set_lut](WaveshareDisplay::set_lut) with [RefreshLut::Quick] on
+    pub fn set_quick_lut(&mut self, quick_lut: QuickLut) {
+        self.quick_lut = quick_lut;
+    }
+
+    /// Turns on the booster and the panel power
+    ///
+    /// Does nothing if the power is already on.
+    /// As long as the power stays on, `display_frame` can refresh the panel
+    /// without going through a power cycle each time.
+    pub fn power_on(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        if !self.powered {
+            self.command(spi, Command::PowerOn)?;
+            let started = self.interface.now();
+            delay.delay_ms(5);
+            self.wait_until_idle()?;
+            self.interface.record_power_on(started);
+            self.powered = true;
+        }
+        Ok(())
+    }
+
+    /// Turns off the booster and the panel power, the displayed image stays
+    ///
+    /// Does nothing if the power is already off.
+    pub fn power_off(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.powered {
+            self.command(spi, Command::PowerOff)?;
+            let started = self.interface.now();
+            self.wait_until_idle()?;
+            self.interface.record_power_off(started);
+            self.powered = false;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd(spi, command)
     }
 
-    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
+    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.interface.data(spi, data)
     }
 
@@ -364,23 +603,32 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn wait_until_idle(&mut self) {
-        let _ = self.interface.wait_until_idle(IS_BUSY_LOW);
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.power_on(spi, delay)?;
+        self.wait_until_idle()?;
+        self.command(spi, Command::DisplayRefresh)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
     }
 
-    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
         let w = self.width();
         let h = self.height();
 
-        self.command(spi, Command::ResolutionSetting)?;
-        self.send_data(spi, &[(w >> 8) as u8])?;
-        self.send_data(spi, &[w as u8])?;
-        self.send_data(spi, &[(h >> 8) as u8])?;
-        self.send_data(spi, &[h as u8])
+        self.cmd_with_data(
+            spi,
+            Command::ResolutionSetting,
+            &[(w >> 8) as u8, w as u8, (h >> 8) as u8, h as u8],
+        )
     }
 
     fn set_lut_helper(
@@ -391,8 +639,8 @@ where
         lut_bw: &[u8],
         lut_wb: &[u8],
         lut_bb: &[u8],
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         // LUT VCOM
         self.cmd_with_data(spi, Command::LutForVcom, lut_vcom)?;
 
@@ -419,24 +667,60 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        self.send_data(spi, &[(x >> 8) as u8])?;
-        let tmp = x & 0xf8;
-        self.send_data(spi, &[tmp as u8])?; // x should be the multiple of 8, the last 3 bit will always be ignored
-        let tmp = tmp + width - 1;
-        self.send_data(spi, &[(tmp >> 8) as u8])?;
-        self.send_data(spi, &[(tmp | 0x07) as u8])?;
+    ) -> Result<(), Error<SPI::Error>> {
+        self.send_data(spi, &partial_window(x, y, width, height))
+    }
 
-        self.send_data(spi, &[(y >> 8) as u8])?;
-        self.send_data(spi, &[y as u8])?;
+    // Sends the window of update_partial_frame, `buffer` padded to it with the background
+    fn write_partial(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = (width.div_ceil(8) * height) as usize;
+        self.wait_until_idle()?;
 
-        self.send_data(spi, &[((y + height - 1) >> 8) as u8])?;
-        self.send_data(spi, &[(y + height - 1) as u8])?;
+        self.command(spi, Command::PartialIn)?;
+        self.set_partial_window(spi, x, y, width, height)?;
 
-        self.send_data(spi, &[0x01])?; // Gates scan both inside and outside of the partial window. (default)
+        //TODO: handle dtm somehow
+        let is_dtm1 = false;
+        if is_dtm1 {
+            self.command(spi, Command::DataStartTransmission1)? //TODO: check if data_start transmission 1 also needs "old"/background data here
+        } else {
+            self.command(spi, Command::DataStartTransmission2)?
+        }
 
+        let background = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
+        self.interface
+            .padded_frame_data(spi, buffer, len, background, Self::POLARITY)?;
+
+        self.command(spi, Command::PartialOut)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
+
+    // Sends the partial window command together with the window of shift_display
+    fn set_partial_window(
+        &mut self,
+        spi: &mut SPI,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.cmd_with_data(
+            spi,
+            Command::PartialWindow,
+            &partial_window(x, y, width, height),
+        )
+    }
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> QuickRefresh<SPI, CS, BUSY, DC, RST, DELAY>
@@ -455,13 +739,14 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_exact_len(buffer, buffer_len())?;
+        self.wait_until_idle()?;
 
-        self.interface.cmd(spi, Command::DataStartTransmission1)?;
-
-        self.interface.data(spi, buffer)?;
+        self.interface
+            .cmd_with_data(spi, Command::DataStartTransmission1, buffer)?;
 
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
@@ -471,20 +756,25 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_exact_len(buffer, buffer_len())?;
+        self.wait_until_idle()?;
         // self.send_resolution(spi)?;
 
-        self.interface.cmd(spi, Command::DataStartTransmission2)?;
-
-        self.interface.data(spi, buffer)?;
+        self.interface
+            .cmd_with_data(spi, Command::DataStartTransmission2, buffer)?;
 
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
     /// This is a wrapper around `display_frame` for using this device as a true
     /// `QuickRefresh` device.
-    fn display_new_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn display_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
         self.display_frame(spi, delay)
     }
 
@@ -497,7 +787,7 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_new_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)
     }
@@ -510,23 +800,18 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-
-        if buffer.len() as u32 != width / 8 * height {
-            //TODO: panic!! or sth like that
-            //return Err("Wrong buffersize");
-        }
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(buffer, (width.div_ceil(8) * height) as usize)?;
+        self.wait_until_idle()?;
 
         self.interface.cmd(spi, Command::PartialIn)?;
-        self.interface.cmd(spi, Command::PartialWindow)?;
+        self.set_partial_window(spi, x, y, width, height)?;
 
-        self.shift_display(spi, x, y, width, height)?;
-
-        self.interface.cmd(spi, Command::DataStartTransmission1)?;
-
-        self.interface.data(spi, buffer)?;
+        self.interface
+            .cmd_with_data(spi, Command::DataStartTransmission1, buffer)?;
 
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
@@ -540,20 +825,18 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        if buffer.len() as u32 != width / 8 * height {
-            //TODO: panic!! or sth like that
-            //return Err("Wrong buffersize");
-        }
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_exact_len(buffer, (width.div_ceil(8) * height) as usize)?;
+        self.wait_until_idle()?;
 
         self.shift_display(spi, x, y, width, height)?;
 
-        self.interface.cmd(spi, Command::DataStartTransmission2)?;
-
-        self.interface.data(spi, buffer)?;
+        self.interface
+            .cmd_with_data(spi, Command::DataStartTransmission2, buffer)?;
 
         self.interface.cmd(spi, Command::PartialOut)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
@@ -564,16 +847,16 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         self.send_resolution(spi)?;
 
-        let color_value = self.color.get_byte_value();
+        let color_value = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
 
         self.interface.cmd(spi, Command::PartialIn)?;
-        self.interface.cmd(spi, Command::PartialWindow)?;
-
-        self.shift_display(spi, x, y, width, height)?;
+        self.set_partial_window(spi, x, y, width, height)?;
 
         self.interface.cmd(spi, Command::DataStartTransmission1)?;
         self.interface
@@ -584,10 +867,31 @@ where
             .data_x_times(spi, color_value, width / 8 * height)?;
 
         self.interface.cmd(spi, Command::PartialOut)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 }
 
+// The data of the partial window command for a window at (x, y)
+fn partial_window(x: u32, y: u32, width: u32, height: u32) -> [u8; 9] {
+    // x should be the multiple of 8, the last 3 bit will always be ignored
+    let x_start = x & 0xf8;
+    let x_end = x_start + width - 1;
+    let y_end = y + height - 1;
+    [
+        (x >> 8) as u8,
+        x_start as u8,
+        (x_end >> 8) as u8,
+        (x_end | 0x07) as u8,
+        (y >> 8) as u8,
+        y as u8,
+        (y_end >> 8) as u8,
+        y_end as u8,
+        // Gates scan both inside and outside of the partial window. (default)
+        0x01,
+    ]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -597,5 +901,772 @@ mod tests {
         assert_eq!(WIDTH, 400);
         assert_eq!(HEIGHT, 300);
         assert_eq!(DEFAULT_BACKGROUND_COLOR, Color::White);
+        assert_eq!(buffer_len(), 15000);
+    }
+
+    #[test]
+    fn power_on_only_once() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+        assert_eq!(log.count_command(Command::PowerOn.address()), 1);
+
+        epd.power_on(&mut spi, &mut delay).unwrap();
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PowerOn.address()), 1);
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 2);
+
+        epd.power_off(&mut spi, &mut delay).unwrap();
+        epd.power_off(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PowerOff.address()), 1);
+
+        // refreshing needs the power, so it is turned on again
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PowerOn.address()), 2);
+
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PowerOff.address()), 2);
+    }
+
+    #[test]
+    fn state_transitions() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+        assert_eq!(epd.state(), DisplayState::Idle);
+
+        // nothing new to show yet
+        assert_eq!(epd.display_frame(&mut spi, &mut delay), Err(Error::NoFrame));
+
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(epd.state(), DisplayState::FramePending);
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(epd.state(), DisplayState::Refreshing);
+        assert_eq!(epd.display_frame(&mut spi, &mut delay), Err(Error::NoFrame));
+
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        assert_eq!(epd.state(), DisplayState::Asleep);
+        log.clear();
+        assert_eq!(epd.clear_frame(&mut spi, &mut delay), Err(Error::Asleep));
+        assert_eq!(epd.display_frame(&mut spi, &mut delay), Err(Error::Asleep));
+        assert!(log.transfers().is_empty());
+
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+        assert_eq!(epd.state(), DisplayState::Idle);
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "graphics")]
+    fn clear_and_update() {
+        use crate::graphics::Display;
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+        epd.set_background_color(Color::Black);
+        log.clear();
+
+        let mut display = Display4in2::default();
+        epd.clear_and_update(&mut spi, &mut display, &mut delay)
+            .unwrap();
+        assert!(display.buffer().iter().all(|&byte| byte == 0x00));
+        assert_eq!(
+            log.count_command(Command::DataStartTransmission2.address()),
+            1
+        );
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 1);
+        assert_eq!(epd.state(), DisplayState::Refreshing);
+    }
+
+    #[test]
+    fn clear_frame_polarity() {
+        use crate::mock::{init_epd, BusyPin, Delay, Pin, Spi, Transfer};
+        extern crate std;
+        use std::vec;
+
+        type Epd = Epd4in2<Spi, Pin, BusyPin, Pin, Pin, Delay>;
+        assert_eq!(Epd::POLARITY, Polarity::ClearedIsColor);
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+
+        // the bytes of the buffers, sent as they are
+        for &(color, byte) in [(Color::White, 0xff), (Color::Black, 0x00)].iter() {
+            epd.set_background_color(color);
+            log.clear();
+            epd.clear_frame(&mut spi, &mut delay).unwrap();
+            let frame = Transfer::Data(vec![byte; buffer_len()]);
+            assert_eq!(log.transfers().iter().filter(|t| **t == frame).count(), 2);
+        }
+    }
+
+    #[test]
+    fn clear_blanks_both_frames() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+        epd.update_frame(&mut spi, &vec![0x00; buffer_len()], &mut delay)
+            .unwrap();
+
+        // the old frame as well, a quick refresh after the clear compares with it
+        for clear in [Epd4in2::clear_frame, Epd4in2::clear_ram].iter() {
+            log.clear();
+            clear(&mut epd, &mut spi, &mut delay).unwrap();
+            for command in [
+                Command::DataStartTransmission1,
+                Command::DataStartTransmission2,
+            ]
+            .iter()
+            {
+                assert_eq!(log.data_after(command.address()), vec![0xff; buffer_len()]);
+            }
+        }
+    }
+
+    #[test]
+    fn new_doesnt_touch_the_display() {
+        use crate::mock::{BusyPin, Delay, Log, Pin};
+        use crate::traits::Command as _;
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = Delay::new();
+        let mut epd = Epd4in2::new(Pin, BusyPin::default(), log.dc(), Pin);
+        assert_eq!(epd.state(), DisplayState::Uninitialized);
+        assert_eq!(
+            epd.clear_frame(&mut spi, &mut delay),
+            Err(Error::Uninitialized)
+        );
+        assert!(log.transfers().is_empty());
+
+        // the display was initialized before, e.g. before the mcu went to deep sleep
+        epd.assume_init();
+        assert_eq!(epd.state(), DisplayState::Idle);
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PanelSetting.address()), 0);
+    }
+
+    #[test]
+    fn power_pin() {
+        extern crate std;
+        use crate::mock::{BusyPin, Delay, LevelPin, Log, Pin};
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = Delay::new();
+        let pwr = LevelPin::default();
+        // the power pin doesn't share the type of the reset pin
+        let mut epd = Epd4in2::new(Pin, BusyPin::default(), log.dc(), Pin);
+        epd.set_power_pin(std::boxed::Box::leak(std::boxed::Box::new(pwr.clone())));
+        assert!(!pwr.is_high());
+
+        epd.init(&mut spi, &mut delay).unwrap();
+        assert!(pwr.is_high());
+
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        assert!(!pwr.is_high());
+
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+        assert!(pwr.is_high());
+    }
+
+    #[test]
+    fn reset_polarity() {
+        use crate::mock::{BusyPin, Delay, LevelPin, Log, Pin};
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = Delay::new();
+
+        // active low: new() leaves the pin alone, a low pulse resets
+        let rst = LevelPin::default();
+        let mut epd = Epd4in2::new(Pin, BusyPin::default(), log.dc(), rst.clone());
+        assert_eq!(rst.levels(), []);
+        epd.init(&mut spi, &mut delay).unwrap();
+        assert_eq!(rst.levels(), [true, false, true]);
+
+        // active high: released (low) from the construction on, a high pulse resets
+        let rst = LevelPin::default();
+        let mut epd =
+            Epd4in2::new_with_reset_polarity(Pin, BusyPin::default(), log.dc(), rst.clone(), true)
+                .unwrap();
+        assert_eq!(rst.levels(), [false]);
+        epd.init(&mut spi, &mut delay).unwrap();
+        assert_eq!(rst.levels(), [false, false, true, false]);
+
+        // the default polarity, released (high) right after the construction
+        let rst = LevelPin::default();
+        let mut epd =
+            Epd4in2::new_with_reset_polarity(Pin, BusyPin::default(), log.dc(), rst.clone(), false)
+                .unwrap();
+        assert_eq!(rst.levels(), [true]);
+        epd.init(&mut spi, &mut delay).unwrap();
+        assert_eq!(rst.levels(), [true, true, false, true]);
+    }
+
+    #[test]
+    fn pin_errors() {
+        use crate::error::PinError;
+        use crate::mock::{BusyPin, Delay, FailingPin, Log, Pin};
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = Delay::new();
+        let dc = FailingPin::default();
+        let mut epd = Epd4in2::new(Pin, BusyPin::default(), dc.clone(), Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+
+        dc.fail();
+        assert_eq!(
+            epd.clear_frame(&mut spi, &mut delay),
+            Err(Error::Pin(PinError::Dc))
+        );
+        assert_eq!(epd.state(), DisplayState::Uninitialized);
+
+        // new() doesn't touch the pins, a failing reset pin shows up in the init
+        let rst = FailingPin::default();
+        rst.fail();
+        let mut epd = Epd4in2::new(Pin, BusyPin::default(), log.dc(), rst);
+        assert_eq!(
+            epd.init(&mut spi, &mut delay),
+            Err(Error::Pin(PinError::Rst))
+        );
+    }
+
+    #[test]
+    fn display_frame_nb() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+
+        // the first call only starts the refresh
+        assert_eq!(
+            epd.display_frame_nb(&mut spi, &mut delay),
+            Err(nb::Error::WouldBlock)
+        );
+        assert_eq!(epd.state(), DisplayState::Refreshing);
+
+        nb::block!(epd.display_frame_nb(&mut spi, &mut delay)).unwrap();
+        assert_eq!(epd.state(), DisplayState::Idle);
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 1);
+
+        assert_eq!(
+            epd.display_frame_nb(&mut spi, &mut delay),
+            Err(nb::Error::Other(Error::NoFrame))
+        );
+    }
+
+    #[test]
+    fn data_delay() {
+        use crate::mock::{init_epd, Transfer};
+        use crate::DataDelay;
+        use core::sync::atomic::{AtomicU32, Ordering};
+        extern crate std;
+        use std::vec;
+
+        static PAUSED_US: AtomicU32 = AtomicU32::new(0);
+        fn pause(us: u32) {
+            PAUSED_US.fetch_add(us, Ordering::Relaxed);
+        }
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+        epd.set_data_delay(Some(DataDelay::new(1000, 5, pause)));
+
+        log.clear();
+        let buffer = vec![0xAB; buffer_len()];
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        // the old frame gets filled with the background color, 2 * 15 chunks of 1000 bytes
+        assert_eq!(PAUSED_US.load(Ordering::Relaxed), 2 * 15 * 5);
+        assert!(log.transfers().contains(&Transfer::Data(buffer)));
+    }
+
+    #[test]
+    fn cs_release() {
+        use crate::mock::{BusyPin, Delay, Log, Pin, Transfer};
+        extern crate std;
+        use std::vec;
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = Delay::new();
+        let mut epd = Epd4in2::new(log.cs(), BusyPin::default(), log.dc(), Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+        let buffer = vec![0xAB; buffer_len()];
+
+        // by default the whole frame is sent with one selection
+        log.clear();
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        assert_eq!(log.longest_selection(), buffer_len());
+        let selects = log.selects();
+
+        epd.set_cs_release(Some(1000));
+        log.clear();
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        assert_eq!(log.longest_selection(), 1000);
+        // 2 frames with 15 chunks each, one more selection for 14 of the chunks
+        assert_eq!(log.selects(), selects + 2 * 14);
+        assert!(log.transfers().contains(&Transfer::Data(buffer)));
+    }
+
+    #[test]
+    fn command_and_data_in_one_selection() {
+        use crate::mock::{BusyPin, Delay, Log, Pin};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = Delay::new();
+        let mut epd = Epd4in2::new(log.cs(), BusyPin::default(), log.dc(), Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+
+        log.clear();
+        epd.send_resolution(&mut spi).unwrap();
+        assert_eq!(
+            log.selections(),
+            [vec![
+                Command::ResolutionSetting.address(),
+                0x01,
+                0x90,
+                0x01,
+                0x2C
+            ]]
+        );
+
+        log.clear();
+        epd.update_partial_frame(&mut spi, &[0x00; 2], 8, 4, 16, 1)
+            .unwrap();
+        let window = [0x00, 0x08, 0x00, 0x17, 0x00, 0x04, 0x00, 0x04, 0x01];
+        let mut selection = vec![Command::PartialWindow.address()];
+        selection.extend_from_slice(&window);
+        assert!(log.selections().contains(&selection));
+    }
+
+    #[test]
+    fn quick_lut_upload() {
+        use crate::mock::{init_epd, Transfer};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec::Vec;
+
+        fn upload(luts: [&[u8]; 5], cdi: u8) -> Vec<Transfer> {
+            let commands = [
+                Command::LutForVcom,
+                Command::LutWhiteToWhite,
+                Command::LutBlackToWhite,
+                Command::LutWhiteToBlack,
+                Command::LutBlackToBlack,
+            ];
+            let mut transfers = Vec::new();
+            for (command, lut) in commands.iter().zip(luts.iter()) {
+                transfers.push(Transfer::Command(command.address()));
+                transfers.push(Transfer::Data(lut.to_vec()));
+            }
+            transfers.push(Transfer::Command(
+                Command::VcomAndDataIntervalSetting.address(),
+            ));
+            transfers.push(Transfer::Data([cdi].to_vec()));
+            transfers
+        }
+
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+
+        log.clear();
+        epd.set_lut(&mut spi, Some(RefreshLut::Quick)).unwrap();
+        assert_eq!(
+            log.transfers(),
+            upload(
+                [
+                    &LUT_VCOM0_PARTIAL,
+                    &LUT_WW_PARTIAL,
+                    &LUT_BW_PARTIAL,
+                    &LUT_WB_PARTIAL,
+                    &LUT_BB_PARTIAL,
+                ],
+                0x47,
+            )
+        );
+
+        log.clear();
+        epd.set_quick_lut(QuickLut::Legacy);
+        epd.set_lut(&mut spi, None).unwrap();
+        assert_eq!(
+            log.transfers(),
+            upload(
+                [
+                    &LUT_VCOM0_QUICK,
+                    &LUT_WW_QUICK,
+                    &LUT_BW_QUICK,
+                    &LUT_WB_QUICK,
+                    &LUT_BB_QUICK,
+                ],
+                0x97,
+            )
+        );
+
+        // the full refresh gets its vcom and data interval setting back
+        log.clear();
+        epd.set_lut(&mut spi, Some(RefreshLut::Full)).unwrap();
+        assert_eq!(
+            log.transfers(),
+            upload([&LUT_VCOM0, &LUT_WW, &LUT_BW, &LUT_WB, &LUT_BB], 0x97)
+        );
+    }
+
+    #[test]
+    fn variants() {
+        use crate::mock::{Log, Pin};
+        extern crate std;
+        use std::vec::Vec;
+
+        // only the panel setting and the vcom dc differ
+        let init = |variant| {
+            let log = Log::default();
+            let mut spi = log.spi();
+            let mut delay = log.delay();
+            let mut epd = Epd4in2::new_with_variant(Pin, log.busy(), log.dc(), Pin, variant);
+            epd.init(&mut spi, &mut delay).unwrap();
+            log.transcript()
+        };
+        let waveshare = init(Variant::Waveshare);
+        let gdew042t2 = init(Variant::GDEW042T2);
+        assert_eq!(waveshare.len(), gdew042t2.len());
+        let differences: Vec<_> = waveshare
+            .iter()
+            .zip(&gdew042t2)
+            .filter(|(a, b)| a != b)
+            .map(|(a, b)| (a.as_str(), b.as_str()))
+            .collect();
+        assert_eq!(differences, [("00: 3F", "00: BF"), ("82: 12", "82: 28")]);
+    }
+
+    #[test]
+    fn init_waits_after_reset() {
+        use crate::mock::{Log, Pin};
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = log.delay();
+        let mut epd = Epd4in2::new(Pin, log.busy(), log.dc(), Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+        assert_eq!(
+            log.transcript()[..5],
+            [
+                "delay 10ms",
+                "delay 10ms",
+                "delay 200ms",
+                "wait until high",
+                "01: 03 00 2B 2B FF",
+            ]
+        );
+    }
+
+    #[test]
+    fn busy_wait() {
+        use crate::mock::{BusyPin, Delay, Pin};
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        struct CountingWait(AtomicUsize);
+
+        impl BusyWait for CountingWait {
+            fn wait(&self, busy_low: bool) {
+                assert_eq!(busy_low, IS_BUSY_LOW);
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        static WAIT: CountingWait = CountingWait(AtomicUsize::new(0));
+
+        let mut spi = crate::mock::Log::default().spi();
+        let mut delay = Delay::new();
+        let mut epd = Epd4in2::new(Pin, BusyPin::default(), Pin, Pin);
+        epd.set_busy_wait(&WAIT);
+        epd.init(&mut spi, &mut delay).unwrap();
+        let waits = WAIT.0.load(Ordering::Relaxed);
+        assert!(waits > 0);
+
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert!(WAIT.0.load(Ordering::Relaxed) > waits);
+    }
+
+    #[test]
+    fn busy_debounce() {
+        use crate::mock::{Log, Pin};
+        use crate::BusyDebounce;
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        static PAUSES: AtomicU32 = AtomicU32::new(0);
+        fn pause(us: u32) {
+            assert_eq!(us, 5);
+            PAUSES.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = log.delay();
+        let mut epd = Epd4in2::new(Pin, log.busy(), Pin, Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+        epd.set_busy_debounce(Some(BusyDebounce::new(3, 5, pause)));
+
+        // busy while low, the glitch to high during the refresh doesn't end the wait
+        let reads = log.busy_reads();
+        log.script_busy(&[false, true, false, false, false, true, true, true]);
+        epd.wait_until_idle().unwrap();
+        assert_eq!(log.busy_reads() - reads, 8);
+        assert_eq!(PAUSES.load(Ordering::Relaxed), 6);
+
+        // is_busy only debounces in strict mode
+        log.script_busy(&[true]);
+        assert!(!epd.is_busy().unwrap());
+        epd.set_busy_debounce(Some(BusyDebounce::new(3, 5, pause).strict()));
+        log.script_busy(&[true, false, false, false]);
+        assert!(epd.is_busy().unwrap());
+    }
+
+    #[test]
+    fn busy_check() {
+        use crate::mock::{Log, Pin};
+        use embedded_hal::digital::v2::PinState;
+
+        let init = |script: &[bool]| {
+            let log = Log::default();
+            let mut spi = log.spi();
+            let mut delay = log.delay();
+            let mut epd = Epd4in2::new(Pin, log.busy(), log.dc(), Pin);
+            epd.set_busy_check(Some(3));
+            log.script_busy(script);
+            let result = epd.init(&mut spi, &mut delay);
+            (result, log.transcript(), epd.state())
+        };
+
+        // busy (low) in the reset and idle 2ms after it
+        let mut script = [true; 32];
+        script[..3].copy_from_slice(&[false, false, false]);
+        let (result, transcript, _) = init(&script);
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            transcript[..10],
+            [
+                "delay 10ms",
+                "delay 10ms",
+                "wait until high",
+                "delay 200ms",
+                "wait until high",
+                "delay 1ms",
+                "wait until high",
+                "delay 1ms",
+                // the check and the wait of the driver
+                "wait until high",
+                "01: 03 00 2B 2B FF",
+            ]
+        );
+
+        // still busy 3ms after the reset
+        let (result, transcript, state) = init(&[false; 5]);
+        assert_eq!(
+            result,
+            Err(Error::BusyPinStuck {
+                observed_level: PinState::Low
+            })
+        );
+        // nothing is sent after the reset and the 3 pauses of the window
+        assert_eq!(transcript.len(), 11);
+        assert_eq!(transcript[10], "wait until high");
+        assert_eq!(state, DisplayState::Uninitialized);
+
+        // never busy in the reset
+        let (result, _, _) = init(&[true]);
+        assert_eq!(
+            result,
+            Err(Error::BusyPinStuck {
+                observed_level: PinState::High
+            })
+        );
+    }
+
+    #[test]
+    fn progress() {
+        use crate::mock::{BusyPin, Delay, Pin};
+        use crate::{Progress, ProgressHook};
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        extern crate std;
+        use std::vec;
+
+        static CHUNKS: AtomicUsize = AtomicUsize::new(0);
+        static SENT: AtomicUsize = AtomicUsize::new(0);
+        static POLLS: AtomicUsize = AtomicUsize::new(0);
+        fn progress(progress: Progress) {
+            match progress {
+                Progress::Data { sent, total } => {
+                    assert!(sent <= total);
+                    CHUNKS.fetch_add(1, Ordering::Relaxed);
+                    SENT.store(sent, Ordering::Relaxed);
+                }
+                Progress::Busy { .. } => {
+                    POLLS.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        let mut spi = crate::mock::Log::default().spi();
+        let mut delay = Delay::new();
+        let mut epd = Epd4in2::new(Pin, BusyPin::default(), Pin, Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+        epd.set_progress(Some(ProgressHook::new(1000, progress)));
+
+        let buffer = vec![0xAB; buffer_len()];
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        // the old frame with the background color and the new one, 15000 bytes each
+        assert_eq!(CHUNKS.load(Ordering::Relaxed), 2 * 15);
+        assert_eq!(SENT.load(Ordering::Relaxed), buffer_len());
+
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert!(POLLS.load(Ordering::Relaxed) > 0);
+
+        // without a hook nothing gets reported
+        epd.set_progress(None);
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(CHUNKS.load(Ordering::Relaxed), 2 * 15);
+    }
+
+    #[test]
+    #[cfg(feature = "pad_short_buffers")]
+    fn short_buffers_are_padded_with_background() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+        epd.set_background_color(Color::Black);
+
+        // the lower half of a half-height buffer is the background
+        let half = buffer_len() / 2;
+        epd.update_frame(&mut spi, &vec![0xff; half], &mut delay)
+            .unwrap();
+        let mut expected = vec![0xff; half];
+        expected.resize(buffer_len(), 0x00);
+        assert_eq!(
+            log.data_after(Command::DataStartTransmission2.address()),
+            expected
+        );
+
+        // a window at the right edge with only its upper half in the buffer
+        epd.update_partial_frame(&mut spi, &[0xaa; 2 * 10], WIDTH - 16, 40, 16, 20)
+            .unwrap();
+        let mut expected = vec![0xaa; 2 * 10];
+        expected.resize(2 * 20, 0x00);
+        assert_eq!(
+            log.data_after(Command::DataStartTransmission2.address()),
+            expected
+        );
+    }
+
+    #[test]
+    fn wrong_buffer_lengths_are_refused() {
+        use crate::mock::init_epd;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+        log.clear();
+
+        // nothing is sent for a buffer that is too long or, by default, too short
+        assert_eq!(
+            epd.update_frame(&mut spi, &vec![0xff; buffer_len() + 1], &mut delay),
+            Err(Error::BufferTooLong {
+                max: buffer_len(),
+                actual: buffer_len() + 1
+            })
+        );
+        assert_eq!(
+            epd.update_partial_frame(&mut spi, &[0xaa; 2 * 20 + 1], WIDTH - 16, 40, 16, 20),
+            Err(Error::BufferTooLong {
+                max: 2 * 20,
+                actual: 2 * 20 + 1
+            })
+        );
+        if !cfg!(feature = "pad_short_buffers") {
+            assert_eq!(
+                epd.update_frame(&mut spi, &vec![0xff; buffer_len() / 2], &mut delay),
+                Err(Error::BufferTooShort {
+                    min: buffer_len(),
+                    actual: buffer_len() / 2
+                })
+            );
+            assert_eq!(
+                epd.update_partial_frame(&mut spi, &[0xaa; 2 * 10], WIDTH - 16, 40, 16, 20),
+                Err(Error::BufferTooShort {
+                    min: 2 * 20,
+                    actual: 2 * 10
+                })
+            );
+        }
+        assert!(log.transfers().is_empty());
+    }
+
+    #[test]
+    fn test_pattern_is_a_generated_frame() {
+        use crate::mock::init_epd;
+        use crate::pattern::{checkerboard, frame_byte};
+        extern crate std;
+        use std::vec::Vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+        let buffer: Vec<u8> = (0..buffer_len())
+            .map(|i| frame_byte(WIDTH, HEIGHT, 0, i, checkerboard))
+            .collect();
+
+        log.clear();
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        let expected = log.transfers();
+        log.clear();
+        epd.test_pattern(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.transfers(), expected);
+    }
+
+    #[test]
+    fn clear_area_sends_the_background() {
+        use crate::mock::init_epd;
+
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+
+        // like a white buffer for the same window, x is aligned to whole bytes
+        log.clear();
+        epd.update_partial_frame(&mut spi, &[0xff; 3 * 5], 8, 10, 20, 5)
+            .unwrap();
+        let expected = log.transcript();
+        log.clear();
+        epd.clear_area(&mut spi, 12, 10, 20, 5).unwrap();
+        assert_eq!(log.transcript(), expected);
+        assert_eq!(epd.interface.state(), DisplayState::FramePending);
+
+        // the window is filled with the background color
+        epd.set_background_color(Color::Black);
+        log.clear();
+        epd.update_partial_frame(&mut spi, &[0x00; 3 * 5], 8, 10, 20, 5)
+            .unwrap();
+        let expected = log.transcript();
+        log.clear();
+        epd.clear_area(&mut spi, 8, 10, 20, 5).unwrap();
+        assert_eq!(log.transcript(), expected);
     }
 }
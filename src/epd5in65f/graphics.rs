@@ -1,65 +1,19 @@
 use crate::color::OctColor;
-use crate::epd5in65f::{DEFAULT_BACKGROUND_COLOR, HEIGHT, WIDTH};
-use crate::graphics::{DisplayRotation, OctDisplay};
-use embedded_graphics_core::prelude::*;
+use crate::display::FixedDisplay;
+use crate::epd5in65f::{buffer_len, Epd5in65f, HEIGHT, WIDTH};
+use crate::flush::impl_flushable;
 
 /// Full size buffer for use with the 5in65f EPD
 ///
 /// Can also be manually constructed:
-/// `buffer: [DEFAULT_BACKGROUND_COLOR.get_byte_value(); WIDTH / 2 * HEIGHT]`
-pub struct Display5in65f {
-    buffer: [u8; WIDTH as usize * HEIGHT as usize / 2],
-    rotation: DisplayRotation,
-}
-
-impl Default for Display5in65f {
-    fn default() -> Self {
-        Display5in65f {
-            buffer: [OctColor::colors_byte(DEFAULT_BACKGROUND_COLOR, DEFAULT_BACKGROUND_COLOR);
-                WIDTH as usize * HEIGHT as usize / 2],
-            rotation: DisplayRotation::default(),
-        }
-    }
-}
+/// `Display5in65f::from_buffer([DEFAULT_BACKGROUND_COLOR.get_byte_value(); buffer_len()])`
+pub type Display5in65f = FixedDisplay<WIDTH, HEIGHT, { buffer_len() }, OctColor>;
 
-impl DrawTarget for Display5in65f {
-    type Color = OctColor;
-    type Error = core::convert::Infallible;
+// the buffer has the frame length the driver checks for
+const _: () = assert!(Display5in65f::BUFFER_LEN == buffer_len());
 
-    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
-    where
-        I: IntoIterator<Item = Pixel<Self::Color>>,
-    {
-        for pixel in pixels {
-            self.draw_helper(WIDTH, HEIGHT, pixel)?;
-        }
-        Ok(())
-    }
-}
-
-impl OriginDimensions for Display5in65f {
-    fn size(&self) -> Size {
-        Size::new(WIDTH, HEIGHT)
-    }
-}
-
-impl OctDisplay for Display5in65f {
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
-    }
-
-    fn get_mut_buffer(&mut self) -> &mut [u8] {
-        &mut self.buffer
-    }
-
-    fn set_rotation(&mut self, rotation: DisplayRotation) {
-        self.rotation = rotation;
-    }
-
-    fn rotation(&self) -> DisplayRotation {
-        self.rotation
-    }
-}
+// the driver can `flush` these buffers directly
+impl_flushable!(Display5in65f => Epd5in65f, crate::graphics::OctDisplay);
 
 #[cfg(test)]
 mod tests {
@@ -121,6 +75,98 @@ mod tests {
         }
     }
 
+    // Color of the unrotated pixel (x, y) in the buffer
+    fn buffer_pixel(display: &Display5in65f, x: u32, y: u32) -> OctColor {
+        let byte = display.buffer()[(y * WIDTH / 2 + x / 2) as usize];
+        let (upper, lower) = OctColor::split_byte(byte).unwrap();
+        if x.is_multiple_of(2) {
+            upper
+        } else {
+            lower
+        }
+    }
+
+    #[test]
+    fn graphics_rotation_corners() {
+        let (w, h) = (WIDTH - 1, HEIGHT - 1);
+        // unrotated positions of the top left, top right, bottom left and bottom right corner
+        let cases = [
+            (
+                DisplayRotation::Rotate0,
+                (600, 448),
+                [(0, 0), (w, 0), (0, h), (w, h)],
+            ),
+            (
+                DisplayRotation::Rotate90,
+                (448, 600),
+                [(w, 0), (w, h), (0, 0), (0, h)],
+            ),
+            (
+                DisplayRotation::Rotate180,
+                (600, 448),
+                [(w, h), (0, h), (w, 0), (0, 0)],
+            ),
+            (
+                DisplayRotation::Rotate270,
+                (448, 600),
+                [(0, h), (0, 0), (w, h), (w, 0)],
+            ),
+        ];
+        let colors = [
+            OctColor::Red,
+            OctColor::Green,
+            OctColor::Blue,
+            OctColor::Yellow,
+        ];
+        for &(rotation, (width, height), corners) in cases.iter() {
+            let mut display = Display5in65f::default();
+            display.set_rotation(rotation);
+            assert_eq!(display.size(), Size::new(width, height));
+
+            let (right, bottom) = (width as i32 - 1, height as i32 - 1);
+            let points = [
+                Point::new(0, 0),
+                Point::new(right, 0),
+                Point::new(0, bottom),
+                Point::new(right, bottom),
+            ];
+            for (&point, &color) in points.iter().zip(colors.iter()) {
+                Pixel(point, color).draw(&mut display).unwrap();
+            }
+            for (&(x, y), &color) in corners.iter().zip(colors.iter()) {
+                assert_eq!(buffer_pixel(&display, x, y), color);
+            }
+
+            // the whole top edge between the corners, e.g. a column of the buffer when rotated
+            Line::new(Point::new(1, 0), Point::new(right - 1, 0))
+                .into_styled(PrimitiveStyle::with_stroke(OctColor::Black, 1))
+                .draw(&mut display)
+                .unwrap();
+            let ((x0, y0), (x1, y1)) = (corners[0], corners[1]);
+            let steps = x0.max(x1) - x0.min(x1) + y0.max(y1) - y0.min(y1);
+            for i in 1..steps {
+                let step = |a: u32, b: u32| {
+                    if a < b {
+                        a + i
+                    } else if a > b {
+                        a - i
+                    } else {
+                        a
+                    }
+                };
+                assert_eq!(
+                    buffer_pixel(&display, step(x0, x1), step(y0, y1)),
+                    OctColor::Black
+                );
+            }
+            let black = display.buffer().iter().fold(0, |n, &byte| {
+                let (upper, lower) = OctColor::split_byte(byte).unwrap();
+                n + (upper == OctColor::Black) as u32 + (lower == OctColor::Black) as u32
+            });
+            assert_eq!(black, steps - 1);
+        }
+    }
+
     #[test]
     fn graphics_rotation_90() {
         let mut display = Display5in65f::default();
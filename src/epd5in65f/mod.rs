@@ -1,5 +1,8 @@
 //! A simple Driver for the Waveshare 6.65 inch (F) E-Ink Display via SPI
 //!
+//! Polarity: the controller takes [OctColor] nibbles, [Polarity::ClearedIsColor] only names
+//! the default.
+//!
 //! # References
 //!
 //! - [Datasheet](https://www.waveshare.com/wiki/5.65inch_e-Paper_Module_(F))
@@ -11,9 +14,15 @@ use embedded_hal::{
     digital::v2::{InputPin, OutputPin},
 };
 
-use crate::color::OctColor;
-use crate::interface::DisplayInterface;
-use crate::traits::{InternalWiAdditions, RefreshLut, WaveshareDisplay};
+use crate::color::{buffer_size, OctColor, Polarity};
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
+};
+use crate::pattern;
+use crate::traits::DisplayState;
+use crate::traits::{StreamFrame, WaveshareDisplay};
 
 pub(crate) mod command;
 use self::command::Command;
@@ -29,6 +38,11 @@ pub const WIDTH: u32 = 600;
 pub const HEIGHT: u32 = 448;
 /// Default Background Color
 pub const DEFAULT_BACKGROUND_COLOR: OctColor = OctColor::White;
+/// Length in bytes of a full frame buffer for epd5in65f
+pub const fn buffer_len() -> usize {
+    buffer_size::<OctColor>(WIDTH as usize, HEIGHT as usize)
+}
+// The controller pulls BUSY low while it is working
 const IS_BUSY_LOW: bool = true;
 
 /// Epd5in65f driver
@@ -38,9 +52,15 @@ pub struct Epd5in65f<SPI, CS, BUSY, DC, RST, DELAY> {
     interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
     /// Background Color
     color: OctColor,
+    /// Whether the panel power is on
+    powered: bool,
+    /// Whether the power is turned off again once the running refresh finished
+    power_off_after_refresh: bool,
+    /// Whether the power is turned on before the frame data instead of before the refresh
+    power_on_before_data: bool,
 }
 
-impl<SPI, CS, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, CS, BUSY, DC, RST, DELAY>
+impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
     for Epd5in65f<SPI, CS, BUSY, DC, RST, DELAY>
 where
     SPI: Write<u8>,
@@ -50,9 +70,34 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    type DisplayColor = OctColor;
+    const SUPPORTS_PARTIAL: bool = false;
+    const SUPPORTS_QUICK_REFRESH: bool = false;
+    const SUPPORTS_LUT: bool = false;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 1;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
+        let interface = DisplayInterface::new(cs, busy, dc, rst);
+        let color = DEFAULT_BACKGROUND_COLOR;
+
+        Epd5in65f {
+            interface,
+            color,
+            powered: false,
+            power_off_after_refresh: false,
+            power_on_before_data: false,
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // Reset the device
-        self.interface.reset(delay, 2);
+        self.interface.reset(delay, 2)?;
+        // BUSY is low until the controller is ready after the reset, the vendor driver waits for it
+        self.wait_busy_high()?;
+        self.powered = false;
+        self.power_off_after_refresh = false;
 
         self.cmd_with_data(spi, Command::PanelSetting, &[0xEF, 0x08])?;
         self.cmd_with_data(spi, Command::PowerSetting, &[0x37, 0x00, 0x23, 0x23])?;
@@ -71,43 +116,21 @@ where
         self.cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[0x37])?;
         Ok(())
     }
-}
 
-impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
-    for Epd5in65f<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    type DisplayColor = OctColor;
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
-        let interface = DisplayInterface::new(cs, busy, dc, rst);
-        let color = DEFAULT_BACKGROUND_COLOR;
-
-        let mut epd = Epd5in65f { interface, color };
-
-        epd.init(spi, delay)?;
-
-        Ok(epd)
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.power_off(spi, delay)?;
+        // settle before deep sleep like the vendor driver
+        delay.delay_ms(100);
         self.cmd_with_data(spi, Command::DeepSleep, &[0xA5])?;
+        self.interface.asleep()?;
         Ok(())
     }
 
@@ -115,12 +138,15 @@ where
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
-        _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.wait_busy_high();
-        self.send_resolution(spi)?;
-        self.cmd_with_data(spi, Command::DataStartTransmission1, buffer)?;
-        Ok(())
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
+        self.begin_frame(spi, delay)?;
+        self.frame_data(spi, buffer)?;
+        let background = OctColor::colors_byte(self.color, self.color);
+        self.interface
+            .pad(spi, background, buffer_len() - buffer.len())?;
+        self.end_frame(spi, delay)
     }
 
     fn update_partial_frame(
@@ -131,18 +157,29 @@ where
         _y: u32,
         _width: u32,
         _height: u32,
-    ) -> Result<(), SPI::Error> {
-        unimplemented!();
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_busy_high();
-        self.command(spi, Command::PowerOn)?;
-        self.wait_busy_high();
-        self.command(spi, Command::DisplayRefresh)?;
-        self.wait_busy_high();
-        self.command(spi, Command::PowerOff)?;
-        self.wait_busy_low();
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
+        self.wait_busy_high()?;
+        self.finish_refresh(spi, delay)
+    }
+
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(true)?;
+        self.finish_refresh(spi, delay)?;
         Ok(())
     }
 
@@ -151,22 +188,77 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    /// Unlike the other drivers this also refreshes the display, see
+    /// [clear_ram](WaveshareDisplay::clear_ram) for only clearing the frame
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.clear_ram(spi, delay)?;
+        self.display_frame(spi, delay)?;
+        Ok(())
+    }
+
+    fn clear_ram(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         let bg = OctColor::colors_byte(self.color, self.color);
-        self.wait_busy_high();
+        self.wait_for_refresh()?;
+        self.power_on_before_data(spi)?;
         self.send_resolution(spi)?;
         self.command(spi, Command::DataStartTransmission1)?;
         self.interface.data_x_times(spi, bg, WIDTH * HEIGHT / 2)?;
-        self.display_frame(spi, delay)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let bytes = pattern::bytes(WIDTH, HEIGHT, 0, pattern::color_bars);
+        pattern::stream::<_, CS, BUSY, DC, RST, _, _>(self, spi, delay, buffer_len(), bytes)?;
+        self.display_frame(spi, delay)
+    }
+
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
     fn set_background_color(&mut self, color: OctColor) {
         self.color = color;
     }
@@ -175,6 +267,14 @@ where
         &self.color
     }
 
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -183,16 +283,37 @@ where
         HEIGHT
     }
 
-    fn set_lut(
-        &mut self,
-        _spi: &mut SPI,
-        _refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
-        unimplemented!();
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(IS_BUSY_LOW)
     }
 
-    fn is_busy(&self) -> bool {
-        self.interface.is_busy(IS_BUSY_LOW)
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY> StreamFrame<SPI, CS, BUSY, DC, RST, DELAY>
+    for Epd5in65f<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    fn begin_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_for_refresh()?;
+        self.power_on_before_data(spi)?;
+        self.send_resolution(spi)?;
+        self.command(spi, Command::DataStartTransmission1)
+    }
+    fn frame_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.send_data(spi, data)
+    }
+    fn end_frame(&mut self, _spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
     }
 }
 
@@ -205,11 +326,77 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
+    /// Turns on the booster and the panel power
+    ///
+    /// Does nothing if the power is already on.
+    /// As long as the power stays on, `display_frame` can refresh the panel
+    /// without going through a power cycle each time.
+    pub fn power_on(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.power_off_after_refresh = false;
+        self.switch_on(spi)
+    }
+
+    /// Turns the power on before the frame data is sent instead of right before the refresh
+    ///
+    /// Defaults to `false` like the vendor driver. Some marginal supplies corrupt a frame which
+    /// was sent before the power came on, e.g. only the top third of it shows up right. Unless
+    /// it was turned on with [power_on](Self::power_on) the power still goes off again after
+    /// the refresh.
+    pub fn set_power_on_before_data(&mut self, power_on_before_data: bool) {
+        self.power_on_before_data = power_on_before_data;
+    }
+
+    // Sends POWER_ON and waits for it, if the power is off
+    fn switch_on(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
+        if !self.powered {
+            self.command(spi, Command::PowerOn)?;
+            let started = self.interface.now();
+            self.wait_busy_high()?;
+            self.interface.record_power_on(started);
+            self.powered = true;
+        }
+        Ok(())
+    }
+
+    // Turns the power on for the next refresh only, the refresh turns it off again
+    fn power_on_for_refresh(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
+        if !self.powered {
+            self.switch_on(spi)?;
+            self.power_off_after_refresh = true;
+        }
+        Ok(())
+    }
+
+    // Powers on ahead of the frame data if configured so
+    fn power_on_before_data(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
+        if self.power_on_before_data {
+            self.power_on_for_refresh(spi)?;
+        }
+        Ok(())
+    }
+
+    /// Turns off the booster and the panel power, the displayed image stays
+    ///
+    /// Does nothing if the power is already off.
+    pub fn power_off(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        if self.powered {
+            self.command(spi, Command::PowerOff)?;
+            let started = self.interface.now();
+            // busy goes low once the power is off, the panel needs some more time to
+            // discharge or the next refresh may show a faint band
+            self.wait_busy_low()?;
+            self.interface.record_power_off(started);
+            delay.delay_ms(200);
+            self.powered = false;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd(spi, command)
     }
 
-    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
+    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.interface.data(spi, data)
     }
 
@@ -218,25 +405,63 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn wait_busy_high(&mut self) {
-        let _ = self.interface.wait_until_idle(true);
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        // Only power cycle if the power wasn't turned on explicitly before
+        self.power_on_for_refresh(spi)?;
+        self.command(spi, Command::DisplayRefresh)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    /// Turns the power off again after the refresh if it was only turned on for it
+    fn finish_refresh(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.power_off_after_refresh {
+            self.power_off_after_refresh = false;
+            self.power_off(spi, delay)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for a refresh started with `display_frame_nb` which didn't finish yet
+    ///
+    /// BUSY only needs to be checked after POWER_ON, DISPLAY_REFRESH and POWER_OFF, like the
+    /// vendor driver does. Reading it on an idle panel can take until it settled after the
+    /// next command.
+    fn wait_for_refresh(&mut self) -> Result<(), Error<SPI::Error>> {
+        if self.interface.state() == DisplayState::Refreshing {
+            self.wait_busy_high()?;
+        }
+        Ok(())
+    }
+
+    fn wait_busy_high(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(true)
     }
-    fn wait_busy_low(&mut self) {
-        let _ = self.interface.wait_until_idle(false);
+    fn wait_busy_low(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(false)
     }
-    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
         let w = self.width();
         let h = self.height();
 
-        self.command(spi, Command::TconResolution)?;
-        self.send_data(spi, &[(w >> 8) as u8])?;
-        self.send_data(spi, &[w as u8])?;
-        self.send_data(spi, &[(h >> 8) as u8])?;
-        self.send_data(spi, &[h as u8])
+        self.cmd_with_data(
+            spi,
+            Command::TconResolution,
+            &[(w >> 8) as u8, w as u8, (h >> 8) as u8, h as u8],
+        )
     }
 }
 
@@ -249,5 +474,364 @@ mod tests {
         assert_eq!(WIDTH, 600);
         assert_eq!(HEIGHT, 448);
         assert_eq!(DEFAULT_BACKGROUND_COLOR, OctColor::White);
+        assert_eq!(buffer_len(), 600 * 448 / 2);
+    }
+
+    #[test]
+    fn refresh_and_sleep_sequence() {
+        use crate::mock::{Log, Pin, Transfer};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = log.delay();
+        let mut epd = Epd5in65f::new(Pin, log.busy(), log.dc(), Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+        let buffer = vec![0x11; buffer_len()];
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+
+        // busy is low while the controller works, except for power off, see the vendor driver
+        log.clear();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        assert_eq!(
+            log.transfers(),
+            [
+                Transfer::Command(Command::PowerOn.address()),
+                Transfer::WaitUntilHigh,
+                Transfer::Command(Command::DisplayRefresh.address()),
+                Transfer::WaitUntilHigh,
+                Transfer::Command(Command::PowerOff.address()),
+                Transfer::WaitUntilLow,
+                Transfer::Delay(200),
+                Transfer::Delay(100),
+                Transfer::Command(Command::DeepSleep.address()),
+                Transfer::Data(vec![0xA5]),
+            ]
+        );
+    }
+
+    #[test]
+    fn refresh_phases_are_measured() {
+        use crate::mock::{Log, Pin};
+        use crate::{BusyWait, Clock, RefreshStats};
+        use core::cell::Cell;
+        extern crate std;
+        use std::vec;
+
+        std::thread_local!(static NOW: Cell<u32> = const { Cell::new(7) });
+        fn now_ms() -> u32 {
+            NOW.with(Cell::get)
+        }
+        // every check of a busy display takes 100ms
+        struct Tick;
+        impl BusyWait for Tick {
+            fn wait(&self, _busy_low: bool) {
+                NOW.with(|now| now.set(now.get() + 100));
+            }
+        }
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = log.delay();
+        let mut epd = Epd5in65f::new(Pin, log.busy(), log.dc(), Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+        epd.set_busy_wait(&Tick);
+        let buffer = vec![0x11; buffer_len()];
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+
+        // nothing is measured without a clock
+        log.script_busy(&[true, true, false]);
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(epd.last_refresh(), None);
+
+        // 200ms for the power on, 400ms for the refresh and 100ms until busy goes low again
+        // after the power off
+        epd.set_clock(Some(Clock::new(now_ms)));
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        log.script_busy(&[false, false, true]);
+        log.script_busy(&[false, false, false, false, true]);
+        log.script_busy(&[true, false]);
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        let stats = RefreshStats {
+            power_on_ms: 200,
+            refresh_ms: 400,
+            power_off_ms: 100,
+        };
+        assert_eq!(epd.last_refresh(), Some(stats));
+        assert_eq!(stats.busy_wait_ms(), 700);
+
+        // the power stays on and isn't measured again
+        log.script_busy(&[true]);
+        epd.power_on(&mut spi, &mut delay).unwrap();
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        log.script_busy(&[false, true]);
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        let stats = RefreshStats {
+            power_on_ms: 0,
+            refresh_ms: 100,
+            power_off_ms: 0,
+        };
+        assert_eq!(epd.last_refresh(), Some(stats));
+    }
+
+    #[test]
+    #[cfg(feature = "graphics")]
+    fn clear_and_update_refreshes_once() {
+        use crate::graphics::OctDisplay;
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd5in65f<_, _, _, _, _, _>>();
+        epd.set_background_color(OctColor::Blue);
+        log.clear();
+
+        let mut display = Display5in65f::default();
+        epd.clear_and_update(&mut spi, &mut display, &mut delay)
+            .unwrap();
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 1);
+        let blue = OctColor::colors_byte(OctColor::Blue, OctColor::Blue);
+        assert!(display.buffer().iter().all(|&byte| byte == blue));
+        assert_eq!(
+            log.count_command(Command::DataStartTransmission1.address()),
+            1
+        );
+    }
+
+    #[test]
+    fn clear_ram_without_refresh() {
+        use crate::mock::{init_epd, Transfer};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd5in65f<_, _, _, _, _, _>>();
+
+        log.clear();
+        epd.clear_ram(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 0);
+        assert_eq!(epd.state(), DisplayState::FramePending);
+        let white = OctColor::colors_byte(OctColor::White, OctColor::White);
+        assert_eq!(
+            log.transfers().last(),
+            Some(&Transfer::Data(vec![white; buffer_len()]))
+        );
+
+        // clear_frame still refreshes
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 1);
+    }
+
+    #[test]
+    fn display_frame_power_cycle() {
+        use crate::mock::{init_epd, Transfer};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd5in65f<_, _, _, _, _, _>>();
+        assert_eq!(log.count_command(Command::PowerOn.address()), 0);
+
+        // without explicit power on, every refresh does a full power cycle
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PowerOn.address()), 1);
+        assert_eq!(log.count_command(Command::PowerOff.address()), 1);
+
+        // with explicit power on, the power stays on between refreshes
+        log.clear();
+        epd.power_on(&mut spi, &mut delay).unwrap();
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        epd.power_on(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PowerOn.address()), 1);
+        assert_eq!(log.count_command(Command::PowerOff.address()), 0);
+
+        // sleep turns the power off before going to deep sleep
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        let transfers = log.transfers();
+        let n = transfers.len();
+        assert_eq!(
+            transfers[n - 3..],
+            [
+                Transfer::Command(Command::PowerOff.address()),
+                Transfer::Command(Command::DeepSleep.address()),
+                Transfer::Data(vec![0xA5]),
+            ]
+        );
+    }
+
+    #[test]
+    fn power_on_before_data() {
+        use crate::mock::{Log, Pin, Transfer};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = log.delay();
+        let mut epd = Epd5in65f::new(Pin, log.busy(), log.dc(), Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+        epd.set_power_on_before_data(true);
+
+        let buffer = vec![0x11; buffer_len()];
+        log.clear();
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(
+            log.transfers(),
+            [
+                Transfer::Command(Command::PowerOn.address()),
+                Transfer::WaitUntilHigh,
+                Transfer::Command(Command::TconResolution.address()),
+                Transfer::Data(vec![0x02, 0x58, 0x01, 0xC0]),
+                Transfer::Command(Command::DataStartTransmission1.address()),
+                Transfer::Data(buffer),
+                Transfer::Command(Command::DisplayRefresh.address()),
+                Transfer::WaitUntilHigh,
+                Transfer::Command(Command::PowerOff.address()),
+                Transfer::WaitUntilLow,
+                Transfer::Delay(200),
+            ]
+        );
+
+        // an explicit power on keeps the power on after the refresh
+        epd.power_on(&mut spi, &mut delay).unwrap();
+        log.clear();
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::PowerOn.address()), 0);
+        assert_eq!(log.count_command(Command::PowerOff.address()), 0);
+    }
+
+    #[test]
+    fn clear_frame_writes_in_chunks() {
+        use crate::mock::{init_epd, Transfer};
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd5in65f<_, _, _, _, _, _>>();
+        log.clear();
+
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        assert!(log
+            .transfers()
+            .contains(&Transfer::Data(vec![0x11; buffer_len()])));
+        // 1050 writes of 128 bytes for the frame instead of one per byte
+        let frame_writes = buffer_len() / 128;
+        assert!(log.writes() > frame_writes);
+        assert!(log.writes() < frame_writes + 20, "{} writes", log.writes());
+    }
+
+    #[test]
+    fn display_frame_nb_powers_off() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd5in65f<_, _, _, _, _, _>>();
+        epd.update_frame(&mut spi, &vec![0x11; buffer_len()], &mut delay)
+            .unwrap();
+
+        assert_eq!(
+            epd.display_frame_nb(&mut spi, &mut delay),
+            Err(nb::Error::WouldBlock)
+        );
+        assert_eq!(log.count_command(Command::PowerOn.address()), 1);
+        assert_eq!(log.count_command(Command::PowerOff.address()), 0);
+
+        nb::block!(epd.display_frame_nb(&mut spi, &mut delay)).unwrap();
+        assert_eq!(log.count_command(Command::PowerOff.address()), 1);
+    }
+
+    #[test]
+    fn busy_is_only_checked_when_required() {
+        use crate::mock::{Log, Pin, Transfer};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = log.delay();
+        let mut epd = Epd5in65f::new(Pin, log.busy(), log.dc(), Pin);
+        // ready right after the reset
+        log.script_busy(&[true]);
+        epd.init(&mut spi, &mut delay).unwrap();
+
+        // an idle panel takes the frame without reading busy
+        log.clear();
+        epd.update_frame(&mut spi, &vec![0x11; buffer_len()], &mut delay)
+            .unwrap();
+        epd.clear_ram(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.busy_reads(), 0);
+
+        // busy after power on and during the refresh, high until the power is off
+        log.clear();
+        log.script_busy(&[false, true, false, false, true, true, true, false]);
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.busy_reads(), 8);
+        assert_eq!(
+            log.transfers(),
+            [
+                Transfer::Command(Command::PowerOn.address()),
+                Transfer::WaitUntilHigh,
+                Transfer::Command(Command::DisplayRefresh.address()),
+                Transfer::WaitUntilHigh,
+                Transfer::Command(Command::PowerOff.address()),
+                Transfer::WaitUntilLow,
+                Transfer::Delay(200),
+            ]
+        );
+
+        // a refresh which is still running is waited for before the next frame
+        epd.update_frame(&mut spi, &vec![0x11; buffer_len()], &mut delay)
+            .unwrap();
+        // ready after power on, the refresh then runs in the background
+        log.script_busy(&[true]);
+        assert_eq!(
+            epd.display_frame_nb(&mut spi, &mut delay),
+            Err(nb::Error::WouldBlock)
+        );
+        log.clear();
+        log.script_busy(&[false, false, true]);
+        epd.update_frame(&mut spi, &vec![0x11; buffer_len()], &mut delay)
+            .unwrap();
+        assert_eq!(log.busy_reads(), 3);
+        assert_eq!(log.transfers()[0], Transfer::WaitUntilHigh);
+    }
+
+    #[test]
+    fn test_pattern_is_a_generated_frame() {
+        use crate::mock::init_epd;
+        use crate::pattern::{color_bars, frame_byte};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec::Vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd5in65f<_, _, _, _, _, _>>();
+        let buffer: Vec<u8> = (0..buffer_len())
+            .map(|i| frame_byte(WIDTH, HEIGHT, 0, i, color_bars))
+            .collect();
+
+        log.clear();
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        let expected = log.transfers();
+        log.clear();
+        epd.test_pattern(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.transfers(), expected);
+        // the left bar is black, the right one the clean color
+        let frame = log.data_after(Command::DataStartTransmission1.address());
+        assert_eq!(
+            frame[0],
+            OctColor::colors_byte(OctColor::Black, OctColor::Black)
+        );
+        assert_eq!(
+            frame[frame.len() - 1],
+            OctColor::colors_byte(OctColor::HiZ, OctColor::HiZ)
+        );
     }
 }
@@ -1,19 +1,31 @@
 //! A simple Driver for the Waveshare 7.5" E-Ink Display via SPI
 //!
+//! Polarity: the IL0371 gets 4 bits per pixel, the driver expands each set bit to white
+//! ([Polarity::ClearedIsColor]).
+//!
 //! # References
 //!
 //! - [Datasheet](https://www.waveshare.com/wiki/7.5inch_e-Paper_HAT)
 //! - [Waveshare C driver](https://github.com/waveshare/e-Paper/blob/702def06bcb75983c98b0f9d25d43c552c248eb0/RaspberryPi%26JetsonNano/c/lib/e-Paper/EPD_7in5.c)
 //! - [Waveshare Python driver](https://github.com/waveshare/e-Paper/blob/702def06bcb75983c98b0f9d25d43c552c248eb0/RaspberryPi%26JetsonNano/python/lib/waveshare_epd/epd7in5.py)
+//!
+//! This is the 640×384 panel sold until 2019.11. The V2 with 800×480 pixels, a "V2" sticker
+//! on the back of the panel, has another controller and needs [epd7in5_v2](crate::epd7in5_v2).
 
 use embedded_hal::{
     blocking::{delay::*, spi::Write},
     digital::v2::{InputPin, OutputPin},
 };
 
-use crate::color::Color;
-use crate::interface::DisplayInterface;
-use crate::traits::{InternalWiAdditions, RefreshLut, WaveshareDisplay};
+use crate::color::{buffer_size, Color, Polarity};
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
+};
+use crate::pattern;
+use crate::traits::DisplayState;
+use crate::traits::{StreamFrame, WaveshareDisplay};
 
 pub(crate) mod command;
 use self::command::Command;
@@ -29,6 +41,11 @@ pub const WIDTH: u32 = 640;
 pub const HEIGHT: u32 = 384;
 /// Default Background Color
 pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full frame buffer for epd7in5
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+// The IL0371 pulls BUSY low while it is working
 const IS_BUSY_LOW: bool = true;
 
 /// Epd7in5 driver
@@ -38,9 +55,11 @@ pub struct Epd7in5<SPI, CS, BUSY, DC, RST, DELAY> {
     interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
     /// Background Color
     color: Color,
+    /// Whether the panel power is on
+    powered: bool,
 }
 
-impl<SPI, CS, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, CS, BUSY, DC, RST, DELAY>
+impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
     for Epd7in5<SPI, CS, BUSY, DC, RST, DELAY>
 where
     SPI: Write<u8>,
@@ -50,9 +69,31 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    type DisplayColor = Color;
+    const SUPPORTS_PARTIAL: bool = false;
+    const SUPPORTS_QUICK_REFRESH: bool = false;
+    const SUPPORTS_LUT: bool = false;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 1;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
+        let interface = DisplayInterface::new(cs, busy, dc, rst);
+        let color = DEFAULT_BACKGROUND_COLOR;
+
+        Epd7in5 {
+            interface,
+            color,
+            powered: false,
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // Reset the device
-        self.interface.reset(delay, 10);
+        self.interface.reset(delay, 10)?;
+        // IL0371: BUSY is low until the controller is ready after the reset
+        self.wait_until_idle()?;
+        self.powered = false;
 
         // Set the power settings
         self.cmd_with_data(spi, Command::PowerSetting, &[0x37, 0x00])?;
@@ -66,9 +107,7 @@ where
         self.cmd_with_data(spi, Command::BoosterSoftStart, &[0xC7, 0xCC, 0x28])?;
 
         // Power on
-        self.command(spi, Command::PowerOn)?;
-        delay.delay_ms(5);
-        self.wait_until_idle();
+        self.power_on(spi, delay)?;
 
         // Set the clock frequency to 50Hz (default)
         self.cmd_with_data(spi, Command::PllControl, &[0x3C])?;
@@ -91,49 +130,23 @@ where
         // This is in all the Waveshare controllers for Epd7in5
         self.cmd_with_data(spi, Command::FlashMode, &[0x03])?;
 
-        self.wait_until_idle();
+        self.wait_until_idle()?;
         Ok(())
     }
-}
-
-impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
-    for Epd7in5<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    type DisplayColor = Color;
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
-        let interface = DisplayInterface::new(cs, busy, dc, rst);
-        let color = DEFAULT_BACKGROUND_COLOR;
 
-        let mut epd = Epd7in5 { interface, color };
-
-        epd.init(spi, delay)?;
-
-        Ok(epd)
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        self.command(spi, Command::PowerOff)?;
-        self.wait_until_idle();
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        self.power_off(spi, delay)?;
         self.cmd_with_data(spi, Command::DeepSleep, &[0xA5])?;
+        self.interface.asleep()?;
         Ok(())
     }
 
@@ -141,22 +154,19 @@ where
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
-        _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        self.command(spi, Command::DataStartTransmission1)?;
-        for byte in buffer {
-            let mut temp = *byte;
-            for _ in 0..4 {
-                let mut data = if temp & 0x80 == 0 { 0x00 } else { 0x03 };
-                data <<= 4;
-                temp <<= 1;
-                data |= if temp & 0x80 == 0 { 0x00 } else { 0x03 };
-                temp <<= 1;
-                self.send_data(spi, &[data])?;
-            }
-        }
-        Ok(())
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
+        self.begin_frame(spi, delay)?;
+        self.frame_data(spi, buffer)?;
+        // 4 bits per pixel, the uniform background byte expands to 4 equal bytes
+        let background = expand_pixels(
+            self.interface
+                .background_byte(self.color.to_controller_byte(Self::POLARITY)),
+        )[0];
+        self.interface
+            .pad(spi, background, 4 * (buffer_len() - buffer.len()))?;
+        self.end_frame(spi, delay)
     }
 
     fn update_partial_frame(
@@ -167,38 +177,102 @@ where
         _y: u32,
         _width: u32,
         _height: u32,
-    ) -> Result<(), SPI::Error> {
-        unimplemented!();
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
-        self.command(spi, Command::DisplayRefresh)?;
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
         Ok(())
     }
 
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.command(spi, Command::DisplayRefresh)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle();
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
         self.send_resolution(spi)?;
 
-        // The Waveshare controllers all implement clear using 0x33
+        // The Waveshare controllers all implement clear using 0x33, white for both pixels
+        let color = match self.interface.background_byte(0xff) {
+            0xff => 0x33,
+            _ => 0x00,
+        };
         self.command(spi, Command::DataStartTransmission1)?;
         self.interface
-            .data_x_times(spi, 0x33, WIDTH / 8 * HEIGHT * 4)?;
+            .data_x_times(spi, color, WIDTH / 8 * HEIGHT * 4)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let bytes = pattern::bytes(WIDTH, HEIGHT, 0, pattern::checkerboard);
+        pattern::stream::<_, CS, BUSY, DC, RST, _, _>(self, spi, delay, buffer_len(), bytes)?;
+        self.display_frame(spi, delay)
+    }
+
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
     fn set_background_color(&mut self, color: Color) {
         self.color = color;
     }
@@ -207,6 +281,14 @@ where
         &self.color
     }
 
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -215,16 +297,36 @@ where
         HEIGHT
     }
 
-    fn set_lut(
-        &mut self,
-        _spi: &mut SPI,
-        _refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
-        unimplemented!();
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(IS_BUSY_LOW)
     }
 
-    fn is_busy(&self) -> bool {
-        self.interface.is_busy(IS_BUSY_LOW)
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY> StreamFrame<SPI, CS, BUSY, DC, RST, DELAY>
+    for Epd7in5<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    fn begin_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        self.command(spi, Command::DataStartTransmission1)
+    }
+    fn frame_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        // the display takes 4 bits per pixel
+        self.interface.data_expanded(spi, data, expand_pixels)
+    }
+    fn end_frame(&mut self, _spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
     }
 }
 
@@ -237,12 +339,43 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, command)
+    /// Turns on the booster and the panel power
+    ///
+    /// Does nothing if the power is already on.
+    /// As long as the power stays on, `display_frame` can refresh the panel
+    /// without going through a power cycle each time.
+    pub fn power_on(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        if !self.powered {
+            self.command(spi, Command::PowerOn)?;
+            let started = self.interface.now();
+            delay.delay_ms(5);
+            self.wait_until_idle()?;
+            self.interface.record_power_on(started);
+            self.powered = true;
+        }
+        Ok(())
     }
 
-    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
-        self.interface.data(spi, data)
+    /// Turns off the booster and the panel power, the displayed image stays
+    ///
+    /// Does nothing if the power is already off.
+    pub fn power_off(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.powered {
+            self.command(spi, Command::PowerOff)?;
+            let started = self.interface.now();
+            self.wait_until_idle()?;
+            self.interface.record_power_off(started);
+            self.powered = false;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd(spi, command)
     }
 
     fn cmd_with_data(
@@ -250,26 +383,49 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn wait_until_idle(&mut self) {
-        let _ = self.interface.wait_until_idle(IS_BUSY_LOW);
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.power_on(spi, delay)?;
+        self.wait_until_idle()?;
+        self.command(spi, Command::DisplayRefresh)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
     }
 
-    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(IS_BUSY_LOW)
+    }
+
+    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
         let w = self.width();
         let h = self.height();
 
-        self.command(spi, Command::TconResolution)?;
-        self.send_data(spi, &[(w >> 8) as u8])?;
-        self.send_data(spi, &[w as u8])?;
-        self.send_data(spi, &[(h >> 8) as u8])?;
-        self.send_data(spi, &[h as u8])
+        self.cmd_with_data(
+            spi,
+            Command::TconResolution,
+            &[(w >> 8) as u8, w as u8, (h >> 8) as u8, h as u8],
+        )
     }
 }
 
+// The 4 bytes with 4 bits per pixel for the 8 pixels of `byte`
+fn expand_pixels(byte: u8) -> [u8; 4] {
+    let mut expanded = [0; 4];
+    let mut temp = byte;
+    for data in expanded.iter_mut() {
+        *data = if temp & 0x80 == 0 { 0x00 } else { 0x03 };
+        *data <<= 4;
+        temp <<= 1;
+        *data |= if temp & 0x80 == 0 { 0x00 } else { 0x03 };
+        temp <<= 1;
+    }
+    expanded
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -279,5 +435,86 @@ mod tests {
         assert_eq!(WIDTH, 640);
         assert_eq!(HEIGHT, 384);
         assert_eq!(DEFAULT_BACKGROUND_COLOR, Color::White);
+        assert_eq!(buffer_len(), 640 / 8 * 384);
+    }
+
+    #[test]
+    fn frame_takes_4_bits_per_pixel() {
+        assert_eq!(expand_pixels(0xff), [0x33; 4]);
+        assert_eq!(expand_pixels(0x00), [0x00; 4]);
+        assert_eq!(expand_pixels(0b1000_0110), [0x30, 0x00, 0x03, 0x30]);
+    }
+
+    #[test]
+    fn update_frame_writes_in_chunks() {
+        use crate::mock::{init_epd, Transfer};
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd7in5<_, _, _, _, _, _>>();
+        log.clear();
+
+        let mut buffer = vec![0xff; buffer_len()];
+        buffer[1] = 0x00;
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        let mut expected = vec![0x33; 4 * buffer_len()];
+        expected[4..8].fill(0x00);
+        assert!(log.transfers().contains(&Transfer::Data(expected)));
+        // 128 expanded bytes per write
+        assert!(
+            log.writes() <= 4 * buffer_len() / 128 + 4,
+            "{} writes",
+            log.writes()
+        );
+    }
+
+    // Everything sent for init, a frame, its refresh and sleep. Update it together with the
+    // sequences of the vendor driver, so changes show up as a diff here.
+    #[test]
+    fn golden_transcript() {
+        use crate::mock::{Log, Pin};
+        extern crate std;
+        use std::vec;
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = log.delay();
+        let mut epd = Epd7in5::new(Pin, log.busy(), log.dc(), Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+        let buffer = vec![0xAB; buffer_len()];
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        // the vendor init of the discontinued V1 is unchanged
+        assert_eq!(
+            log.transcript(),
+            [
+                "delay 10ms",
+                "delay 10ms",
+                "delay 200ms",
+                "wait until high",
+                "01: 37 00",
+                "00: CF 08",
+                "06: C7 CC 28",
+                "04",
+                "delay 5ms",
+                "wait until high",
+                "30: 3C",
+                "41: 00",
+                "50: 77",
+                "60: 22",
+                "61: 02 80 01 80",
+                "82: 1E",
+                "E5: 03",
+                "wait until high",
+                "10: 122880 bytes",
+                "wait until high",
+                "12",
+                "wait until high",
+                "02",
+                "wait until high",
+                "07: A5",
+            ]
+        );
     }
 }
@@ -1,5 +1,8 @@
 //! A simple Driver for the Waveshare 7.5" E-Ink Display (V2) via SPI
 //!
+//! Polarity: the GD7965 in KW mode shows a set bit as black, the driver flips the buffers
+//! while sending ([Polarity::SetIsColor]).
+//!
 //! # References
 //!
 //! - [Datasheet](https://www.waveshare.com/wiki/7.5inch_e-Paper_HAT)
@@ -9,15 +12,26 @@
 //! Important note for V2:
 //! Revision V2 has been released on 2019.11, the resolution is upgraded to 800×480, from 640×384 of V1.
 //! The hardware and interface of V2 are compatible with V1, however, the related software should be updated.
+//!
+//! # Good Display panels
+//!
+//! The Good Display GDEY075T7 is the same panel with a slightly different init, use
+//! [Epd7in5::new_with_variant] with [Variant::GDEY075T7] for it.
 
 use embedded_hal::{
     blocking::{delay::*, spi::Write},
     digital::v2::{InputPin, OutputPin},
 };
 
-use crate::color::Color;
-use crate::interface::DisplayInterface;
-use crate::traits::{InternalWiAdditions, RefreshLut, WaveshareDisplay};
+use crate::color::{buffer_size, Color, Polarity};
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
+};
+use crate::pattern;
+use crate::traits::DisplayState;
+use crate::traits::{StreamFrame, WaveshareDisplay};
 
 pub(crate) mod command;
 use self::command::Command;
@@ -33,8 +47,38 @@ pub const WIDTH: u32 = 800;
 pub const HEIGHT: u32 = 480;
 /// Default Background Color
 pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
+/// Length in bytes of a full frame buffer for epd7in5_v2
+pub const fn buffer_len() -> usize {
+    buffer_size::<Color>(WIDTH as usize, HEIGHT as usize)
+}
+// The GD7965 pulls BUSY low while it is working
 const IS_BUSY_LOW: bool = true;
 
+/// Panel the driver is set up for, see [Epd7in5::new_with_variant]
+///
+/// The clones of the Waveshare panel share the controller and the frame format, only a
+/// few bytes of the init differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// The Waveshare 7.5" V2 module, the default
+    Waveshare,
+    /// The bare Good Display GDEY075T7 panel
+    ///
+    /// Sets the VCOM and data interval (0x29, 0x07) of the Good Display sample code, which
+    /// copies the new data into the old RAM after each refresh.
+    GDEY075T7,
+}
+
+impl Variant {
+    /// VCOM and data interval setting (CDI) of the init
+    fn cdi(self) -> [u8; 2] {
+        match self {
+            Variant::Waveshare => [0x10, 0x07],
+            Variant::GDEY075T7 => [0x29, 0x07],
+        }
+    }
+}
+
 /// Epd7in5 (V2) driver
 ///
 pub struct Epd7in5<SPI, CS, BUSY, DC, RST, DELAY> {
@@ -42,9 +86,13 @@ pub struct Epd7in5<SPI, CS, BUSY, DC, RST, DELAY> {
     interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
     /// Background Color
     color: Color,
+    /// Whether the panel power is on
+    powered: bool,
+    /// Panel the init is sent for
+    variant: Variant,
 }
 
-impl<SPI, CS, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, CS, BUSY, DC, RST, DELAY>
+impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
     for Epd7in5<SPI, CS, BUSY, DC, RST, DELAY>
 where
     SPI: Write<u8>,
@@ -54,68 +102,70 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    type DisplayColor = Color;
+    const SUPPORTS_PARTIAL: bool = false;
+    const SUPPORTS_QUICK_REFRESH: bool = false;
+    const SUPPORTS_LUT: bool = false;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 1;
+    const POLARITY: Polarity = Polarity::SetIsColor;
+    const IS_BUSY_LOW: bool = IS_BUSY_LOW;
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
+        let interface = DisplayInterface::new(cs, busy, dc, rst);
+        let color = DEFAULT_BACKGROUND_COLOR;
+
+        Epd7in5 {
+            interface,
+            color,
+            powered: false,
+            variant: Variant::Waveshare,
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // Reset the device
-        self.interface.reset(delay, 2);
+        self.interface.reset(delay, 2)?;
+        // GD7965: BUSY is low until the controller is ready after the reset
+        self.wait_until_idle(spi, delay)?;
+        self.powered = false;
 
-        // V2 procedure as described here:
-        // https://github.com/waveshare/e-Paper/blob/master/RaspberryPi%26JetsonNano/python/lib/waveshare_epd/epd7in5bc_V2.py
+        // V2 procedure as described here, with the enhanced display drive:
+        // https://github.com/waveshare/e-Paper/blob/master/RaspberryPi_JetsonNano/c/lib/e-Paper/EPD_7in5_V2.c
         // and as per specs:
         // https://www.waveshare.com/w/upload/6/60/7.5inch_e-Paper_V2_Specification.pdf
 
-        self.cmd_with_data(spi, Command::BoosterSoftStart, &[0x17, 0x17, 0x27, 0x17])?;
-        self.cmd_with_data(spi, Command::PowerSetting, &[0x07, 0x17, 0x3F, 0x3F])?;
-        self.command(spi, Command::PowerOn)?;
-        self.wait_until_idle(spi, delay)?;
+        // VGH=20V, VGL=-20V, VDH=15V, VDL=-15V
+        self.cmd_with_data(spi, Command::PowerSetting, &[0x07, 0x07, 0x3F, 0x3F])?;
+        self.cmd_with_data(spi, Command::BoosterSoftStart, &[0x17, 0x17, 0x28, 0x17])?;
+        self.power_on(spi, delay)?;
+        // KW mode, the lut comes from the otp
         self.cmd_with_data(spi, Command::PanelSetting, &[0x1F])?;
-        self.cmd_with_data(spi, Command::PllControl, &[0x06])?;
         self.cmd_with_data(spi, Command::TconResolution, &[0x03, 0x20, 0x01, 0xE0])?;
+        // single spi
         self.cmd_with_data(spi, Command::DualSpi, &[0x00])?;
+        self.cmd_with_data(
+            spi,
+            Command::VcomAndDataIntervalSetting,
+            &self.variant.cdi(),
+        )?;
         self.cmd_with_data(spi, Command::TconSetting, &[0x22])?;
-        self.cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[0x10, 0x07])?;
         self.wait_until_idle(spi, delay)?;
         Ok(())
     }
-}
 
-impl<SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
-    for Epd7in5<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    type DisplayColor = Color;
-    fn new(
-        spi: &mut SPI,
-        cs: CS,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error> {
-        let interface = DisplayInterface::new(cs, busy, dc, rst);
-        let color = DEFAULT_BACKGROUND_COLOR;
-
-        let mut epd = Epd7in5 { interface, color };
-
-        epd.init(spi, delay)?;
-
-        Ok(epd)
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
-    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle(spi, delay)?;
-        self.command(spi, Command::PowerOff)?;
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
+        self.power_off(spi, delay)?;
         self.cmd_with_data(spi, Command::DeepSleep, &[0xA5])?;
+        self.interface.asleep()?;
         Ok(())
     }
 
@@ -124,10 +174,16 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.wait_until_idle(spi, delay)?;
-        self.cmd_with_data(spi, Command::DataStartTransmission2, buffer)?;
-        Ok(())
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, buffer_len())?;
+        self.begin_frame(spi, delay)?;
+        self.frame_data(spi, buffer)?;
+        let background = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
+        self.interface
+            .pad(spi, background, buffer_len() - buffer.len())?;
+        self.end_frame(spi, delay)
     }
 
     fn update_partial_frame(
@@ -138,41 +194,114 @@ where
         _y: u32,
         _width: u32,
         _height: u32,
-    ) -> Result<(), SPI::Error> {
-        unimplemented!();
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.wait_until_idle(spi, delay)?;
-        self.command(spi, Command::DisplayRefresh)?;
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
         Ok(())
     }
 
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.cmd(spi, Command::GetStatus)?;
+        self.interface.poll_refresh(IS_BUSY_LOW)
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.command(spi, Command::DisplayRefresh)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    /// Unlike most drivers this also refreshes the display, see
+    /// [clear_ram](WaveshareDisplay::clear_ram) for only clearing the frame
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.clear_ram(spi, delay)?;
+        self.display_frame(spi, delay)
+    }
+
+    fn clear_ram(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         self.send_resolution(spi)?;
 
+        let color = self
+            .interface
+            .background_byte(self.color.to_controller_byte(Self::POLARITY));
+
         self.command(spi, Command::DataStartTransmission1)?;
-        self.interface.data_x_times(spi, 0x00, WIDTH * HEIGHT / 8)?;
+        self.interface
+            .data_x_times(spi, color, WIDTH * HEIGHT / 8)?;
 
         self.command(spi, Command::DataStartTransmission2)?;
-        self.interface.data_x_times(spi, 0x00, WIDTH * HEIGHT / 8)?;
+        self.interface
+            .data_x_times(spi, color, WIDTH * HEIGHT / 8)?;
 
-        self.command(spi, Command::DisplayRefresh)?;
+        self.interface.set_state(DisplayState::FramePending);
         Ok(())
     }
 
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let bytes = pattern::bytes(WIDTH, HEIGHT, 0, pattern::checkerboard);
+        pattern::stream::<_, CS, BUSY, DC, RST, _, _>(self, spi, delay, buffer_len(), bytes)?;
+        self.display_frame(spi, delay)
+    }
+
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
     fn set_background_color(&mut self, color: Color) {
         self.color = color;
     }
@@ -181,6 +310,14 @@ where
         &self.color
     }
 
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -189,16 +326,35 @@ where
         HEIGHT
     }
 
-    fn set_lut(
-        &mut self,
-        _spi: &mut SPI,
-        _refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
-        unimplemented!();
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(IS_BUSY_LOW)
     }
 
-    fn is_busy(&self) -> bool {
-        self.interface.is_busy(IS_BUSY_LOW)
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY> StreamFrame<SPI, CS, BUSY, DC, RST, DELAY>
+    for Epd7in5<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    fn begin_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle(spi, delay)?;
+        self.command(spi, Command::DataStartTransmission2)
+    }
+    fn frame_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.interface.frame_data(spi, data, Self::POLARITY)
+    }
+    fn end_frame(&mut self, _spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
     }
 }
 
@@ -211,12 +367,50 @@ where
     RST: OutputPin,
     DELAY: DelayMs<u8>,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, command)
+    /// Creates a new driver for a clone of the Waveshare panel
+    ///
+    /// Like [new](WaveshareDisplay::new), but [init](WaveshareDisplay::init) and
+    /// [wake_up](WaveshareDisplay::wake_up) send the bytes of `variant`.
+    pub fn new_with_variant(cs: CS, busy: BUSY, dc: DC, rst: RST, variant: Variant) -> Self {
+        Epd7in5 {
+            variant,
+            ..Self::new(cs, busy, dc, rst)
+        }
     }
 
-    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
-        self.interface.data(spi, data)
+    /// Turns on the booster and the panel power
+    ///
+    /// Does nothing if the power is already on.
+    /// As long as the power stays on, `display_frame` can refresh the panel
+    /// without going through a power cycle each time.
+    pub fn power_on(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        if !self.powered {
+            self.command(spi, Command::PowerOn)?;
+            let started = self.interface.now();
+            delay.delay_ms(100);
+            self.wait_until_idle(spi, delay)?;
+            self.interface.record_power_on(started);
+            self.powered = true;
+        }
+        Ok(())
+    }
+
+    /// Turns off the booster and the panel power, the displayed image stays
+    ///
+    /// Does nothing if the power is already off.
+    pub fn power_off(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        if self.powered {
+            self.command(spi, Command::PowerOff)?;
+            let started = self.interface.now();
+            self.wait_until_idle(spi, delay)?;
+            self.interface.record_power_off(started);
+            self.powered = false;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd(spi, command)
     }
 
     fn cmd_with_data(
@@ -224,27 +418,44 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn wait_until_idle(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        while self.interface.is_busy(IS_BUSY_LOW) {
+    /// Starts the refresh of the frame sent before, without waiting for it to finish
+    fn start_refresh(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.power_on(spi, delay)?;
+        self.wait_until_idle(spi, delay)?;
+        self.command(spi, Command::DisplayRefresh)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+
+    fn wait_until_idle(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        let mut polls = 0;
+        while self.interface.settled_busy(IS_BUSY_LOW)? {
+            polls += 1;
+            self.interface.report_busy(polls);
             self.interface.cmd(spi, Command::GetStatus)?;
             delay.delay_ms(20);
         }
+        self.interface.refresh_finished();
         Ok(())
     }
 
-    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
         let w = self.width();
         let h = self.height();
 
-        self.command(spi, Command::TconResolution)?;
-        self.send_data(spi, &[(w >> 8) as u8])?;
-        self.send_data(spi, &[w as u8])?;
-        self.send_data(spi, &[(h >> 8) as u8])?;
-        self.send_data(spi, &[h as u8])
+        self.cmd_with_data(
+            spi,
+            Command::TconResolution,
+            &[(w >> 8) as u8, w as u8, (h >> 8) as u8, h as u8],
+        )
     }
 }
 
@@ -257,5 +468,182 @@ mod tests {
         assert_eq!(WIDTH, 800);
         assert_eq!(HEIGHT, 480);
         assert_eq!(DEFAULT_BACKGROUND_COLOR, Color::White);
+        assert_eq!(buffer_len(), 800 / 8 * 480);
+    }
+
+    #[test]
+    fn partial_frames_are_unsupported() {
+        use crate::mock::init_epd;
+
+        let (log, mut spi, _delay, mut epd) = init_epd::<Epd7in5<_, _, _, _, _, _>>();
+        log.clear();
+        assert!(matches!(
+            epd.update_partial_frame(&mut spi, &[0x00; 8], 0, 0, 8, 8),
+            Err(Error::Unsupported)
+        ));
+        assert!(log.transfers().is_empty());
+    }
+
+    #[test]
+    fn frame_is_sent_inverted() {
+        use crate::mock::{init_epd, Transfer};
+        use crate::traits::Command as _;
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd7in5<_, _, _, _, _, _>>();
+        log.clear();
+
+        // a single black pixel in the top left corner
+        let mut buffer = vec![Color::White.get_byte_value(); buffer_len()];
+        buffer[0] = 0x7f;
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        let mut expected = vec![0x00; buffer_len()];
+        expected[0] = 0x80;
+        let transfers = log.transfers();
+        assert_eq!(
+            transfers[transfers.len() - 2..],
+            [
+                Transfer::Command(Command::DataStartTransmission2.address()),
+                Transfer::Data(expected)
+            ]
+        );
+    }
+
+    #[test]
+    fn clear_frame_inverted() {
+        use crate::mock::{init_epd, Transfer};
+        extern crate std;
+        use std::vec;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd7in5<_, _, _, _, _, _>>();
+
+        for &(inverted, byte) in [(false, 0x00), (true, 0xff)].iter() {
+            epd.set_inverted(inverted);
+            assert_eq!(epd.inverted(), inverted);
+            log.clear();
+            epd.clear_frame(&mut spi, &mut delay).unwrap();
+            let frame = Transfer::Data(vec![byte; buffer_len()]);
+            assert_eq!(log.transfers().iter().filter(|t| **t == frame).count(), 2);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "graphics")]
+    fn clear_and_update_refreshes_once() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd7in5<_, _, _, _, _, _>>();
+        log.clear();
+
+        let mut display = Display7in5::default();
+        epd.clear_and_update(&mut spi, &mut display, &mut delay)
+            .unwrap();
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 1);
+        assert_eq!(epd.state(), DisplayState::Refreshing);
+    }
+
+    #[test]
+    fn clear_ram_without_refresh() {
+        use crate::mock::init_epd;
+        use crate::traits::Command as _;
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd7in5<_, _, _, _, _, _>>();
+
+        log.clear();
+        epd.clear_ram(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 0);
+        assert_eq!(epd.state(), DisplayState::FramePending);
+
+        // clear_frame still refreshes, once
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.count_command(Command::DisplayRefresh.address()), 1);
+        assert_eq!(epd.state(), DisplayState::Refreshing);
+    }
+
+    // Everything sent for init, a frame, its refresh and sleep. Update it together with the
+    // sequences of the vendor driver, so changes show up as a diff here.
+    #[test]
+    fn golden_transcript() {
+        use crate::mock::{Log, Pin};
+        extern crate std;
+        use std::vec;
+
+        let log = Log::default();
+        let mut spi = log.spi();
+        let mut delay = log.delay();
+        let mut epd = Epd7in5::new(Pin, log.busy(), log.dc(), Pin);
+        epd.init(&mut spi, &mut delay).unwrap();
+        let buffer = vec![0xAB; buffer_len()];
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        // the vendor init with the enhanced display drive, busy gets polled with the status command
+        assert_eq!(
+            log.transcript(),
+            [
+                "delay 10ms",
+                "delay 2ms",
+                "delay 200ms",
+                "wait until high",
+                "71",
+                "delay 20ms",
+                "wait until high",
+                "01: 07 07 3F 3F",
+                "06: 17 17 28 17",
+                "04",
+                "delay 100ms",
+                "wait until high",
+                "71",
+                "delay 20ms",
+                "wait until high",
+                "00: 1F",
+                "61: 03 20 01 E0",
+                "15: 00",
+                "50: 10 07",
+                "60: 22",
+                "wait until high",
+                "71",
+                "delay 20ms",
+                "wait until high",
+                "71",
+                "delay 20ms",
+                "wait until high",
+                "13: 48000 bytes of 54",
+                "wait until high",
+                "71",
+                "delay 20ms",
+                "wait until high",
+                "12",
+                "wait until high",
+                "71",
+                "delay 20ms",
+                "wait until high",
+                "02",
+                "wait until high",
+                "71",
+                "delay 20ms",
+                "wait until high",
+                "07: A5",
+            ]
+        );
+    }
+
+    #[test]
+    fn variants() {
+        use crate::mock::{Log, Pin};
+        use crate::traits::Command as _;
+
+        let cdi = |variant| {
+            let log = Log::default();
+            let mut spi = log.spi();
+            let mut delay = log.delay();
+            let mut epd = Epd7in5::new_with_variant(Pin, log.busy(), log.dc(), Pin, variant);
+            epd.init(&mut spi, &mut delay).unwrap();
+            log.data_after(Command::VcomAndDataIntervalSetting.address())
+        };
+        assert_eq!(cdi(Variant::Waveshare), [0x10, 0x07]);
+        assert_eq!(cdi(Variant::GDEY075T7), [0x29, 0x07]);
     }
 }
@@ -0,0 +1,111 @@
+//! Errors returned by the drivers
+
+use embedded_hal::digital::v2::PinState;
+
+/// Errors of the display drivers
+///
+/// Wraps the error of the spi bus and adds the errors of the drivers themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<SpiError> {
+    /// Error while writing to the spi bus
+    Spi(SpiError),
+    /// Error while setting or reading one of the pins
+    ///
+    /// Only names the pin, the error value of the hal is dropped: the pins can all have
+    /// different error types and `Error` stays generic over the spi error only.
+    Pin(PinError),
+    /// The display is in deep sleep and needs to be woken up with `wake_up` first
+    Asleep,
+    /// The display wasn't initialized or a previous error left it in an unknown state,
+    /// it needs to be (re)initialized with `wake_up` first
+    Uninitialized,
+    /// `display_frame` was called without sending a new frame first
+    NoFrame,
+    /// The buffer has more bytes than the frame or window it is sent to
+    BufferTooLong {
+        /// Length of the frame or window
+        max: usize,
+        /// Length of the given buffer
+        actual: usize,
+    },
+    /// The buffer has less bytes than the frame or window it is sent to
+    ///
+    /// With the `pad_short_buffers` feature the frames of `update_frame` and
+    /// `update_partial_frame` are padded with the background color instead.
+    BufferTooShort {
+        /// Length of the frame or window
+        min: usize,
+        /// Length of the given buffer
+        actual: usize,
+    },
+    /// The window of a partial update is empty or doesn't fit on the display
+    OutOfBounds,
+    /// The display doesn't support the operation, e.g. partial windows
+    Unsupported,
+    /// The busy line didn't change its level around the reset, see
+    /// [set_busy_check](crate::traits::WaveshareDisplay::set_busy_check)
+    ///
+    /// Stuck at the busy level the display would never be waited for, stuck at the idle
+    /// level the refreshes would be cut short.
+    BusyPinStuck {
+        /// The level the line stayed at
+        observed_level: PinState,
+    },
+}
+
+impl<SpiError: core::fmt::Debug> core::fmt::Display for Error<SpiError> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Spi(e) => write!(f, "Spi error: {:?}", e),
+            Error::Pin(pin) => write!(f, "Error on the {} pin", pin),
+            Error::Asleep => write!(f, "Display is asleep"),
+            Error::Uninitialized => write!(f, "Display is not initialized"),
+            Error::NoFrame => write!(f, "No frame was sent to the display"),
+            Error::BufferTooLong { max, actual } => {
+                write!(f, "Buffer has {} bytes but the frame only {}", actual, max)
+            }
+            Error::BufferTooShort { min, actual } => {
+                write!(f, "Buffer has {} bytes but the frame {}", actual, min)
+            }
+            Error::OutOfBounds => write!(f, "Window is outside of the display"),
+            Error::Unsupported => write!(f, "Operation is not supported by the display"),
+            Error::BusyPinStuck { observed_level } => {
+                let level = match observed_level {
+                    PinState::High => "high",
+                    PinState::Low => "low",
+                };
+                write!(f, "BUSY pin is stuck {}", level)
+            }
+        }
+    }
+}
+
+/// The pin which failed
+///
+/// The errors of the pins themselves can't be kept, every pin may have a different error type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinError {
+    /// Chip select
+    Cs,
+    /// Busy input
+    Busy,
+    /// Data/Command
+    Dc,
+    /// Reset
+    Rst,
+    /// Power enable
+    Pwr,
+}
+
+impl core::fmt::Display for PinError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = match self {
+            PinError::Cs => "CS",
+            PinError::Busy => "BUSY",
+            PinError::Dc => "DC",
+            PinError::Rst => "RST",
+            PinError::Pwr => "PWR",
+        };
+        f.write_str(name)
+    }
+}
@@ -0,0 +1,642 @@
+//! An in-memory display for testing applications without hardware
+//!
+//! [FakeEpd] implements [WaveshareDisplay] (and [WaveshareThreeColorDisplay] for tri-color
+//! frames) for any size and the colors of the drivers, without talking to a bus. It keeps
+//! the RAM of the controller, records every frame sent to it as decoded pixels and logs
+//! the refreshes, so a test can check what an application drew and when it showed it
+//! instead of comparing spi bytes:
+//!
+//! ```
+//! # use embedded_hal_mock::*;
+//! # fn main() -> Result<(), epd_waveshare::Error<MockError>> {
+//! use epd_waveshare::{epd4in2::*, fake::FakeEpd, prelude::*};
+//!
+//! # let expectations = [];
+//! # let mut spi = spi::Mock::new(&expectations);
+//! # let expectations = [];
+//! # let cs_pin = pin::Mock::new(&expectations);
+//! # let busy_in = pin::Mock::new(&expectations);
+//! # let dc = pin::Mock::new(&expectations);
+//! # let rst = pin::Mock::new(&expectations);
+//! # let mut delay = delay::MockNoop::new();
+//! // the same size and color as the 4.2" display
+//! let mut epd: FakeEpd<_, _, _, _, _, _, Color, WIDTH, HEIGHT> =
+//!     FakeEpd::new(cs_pin, busy_in, dc, rst);
+//! epd.init(&mut spi, &mut delay)?;
+//!
+//! let mut buffer = [0xff; buffer_len()];
+//! buffer[0] = 0x7f;
+//! epd.update_frame(&mut spi, &buffer, &mut delay)?;
+//! assert_eq!(epd.last_frame_pixel(0, 0), Some(Color::Black));
+//! assert!(!epd.was_refreshed_after_last_update());
+//!
+//! epd.display_frame(&mut spi, &mut delay)?;
+//! assert!(epd.was_refreshed_after_last_update());
+//! assert_eq!(epd.frame_count(), 1);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The fake follows the rules of the drivers: buffers of the wrong length, windows off the
+//! display, commands to a sleeping or uninitialized display and refreshes without a new
+//! frame fail with the same errors. Partial windows are written in whole bytes like the
+//! controllers do, on b/w and tri-color frames.
+
+use crate::color::{plane_size, ColorType, Polarity, TriColor};
+use crate::error::Error;
+use crate::interface::{check_exact_len, check_len, check_window};
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, PowerPin, ProgressHook, RefreshStats,
+};
+use crate::pattern::{frame_byte, PatternColor};
+use crate::traits::{DisplayState, RefreshLut, WaveshareDisplay, WaveshareThreeColorDisplay};
+use core::marker::PhantomData;
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::*,
+};
+use std::vec::Vec;
+
+/// A frame as the fake display got it, decoded into pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame<C> {
+    width: u32,
+    height: u32,
+    pixels: Vec<Option<C>>,
+}
+
+impl<C: ColorType> Frame<C> {
+    fn decode(ram: &[u8], width: u32, height: u32) -> Self {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| C::read_pixel(ram, width, height, x, y))
+            .collect();
+        Frame {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Width of the frame in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the frame in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Color of the pixel (x, y), `None` outside of the frame or if the bits aren't a color
+    pub fn pixel(&self, x: u32, y: u32) -> Option<C> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// All pixels row by row from the top left, `None` where the bits aren't a color
+    pub fn pixels(&self) -> &[Option<C>] {
+        &self.pixels
+    }
+}
+
+/// A call to the fake display which changes what it shows or will show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Call {
+    /// [init](WaveshareDisplay::init) or [assume_init](WaveshareDisplay::assume_init)
+    Init,
+    /// A frame or a window was sent, it's the last of the [frames](FakeEpd::frames)
+    Update,
+    /// [display_frame](WaveshareDisplay::display_frame) showed the last frame
+    Refresh,
+    /// [sleep](WaveshareDisplay::sleep)
+    Sleep,
+    /// [wake_up](WaveshareDisplay::wake_up)
+    WakeUp,
+    /// [set_lut](WaveshareDisplay::set_lut)
+    SetLut(Option<RefreshLut>),
+}
+
+/// A display of `WIDTH` x `HEIGHT` pixels of `C` which only exists in memory
+///
+/// Implements [WaveshareDisplay] for the bus and pins like the drivers, they are dropped
+/// though. See the [module documentation](self).
+pub struct FakeEpd<SPI, CS, BUSY, DC, RST, DELAY, C, const WIDTH: u32, const HEIGHT: u32> {
+    #[allow(clippy::type_complexity)]
+    _bus: PhantomData<fn(SPI, CS, BUSY, DC, RST, DELAY)>,
+    ram: Vec<u8>,
+    frames: Vec<Frame<C>>,
+    calls: Vec<Call>,
+    // Number of frames when the display was refreshed the last time
+    shown: Option<usize>,
+    state: DisplayState,
+    background: C,
+    inverted: bool,
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY, C, const WIDTH: u32, const HEIGHT: u32>
+    FakeEpd<SPI, CS, BUSY, DC, RST, DELAY, C, WIDTH, HEIGHT>
+where
+    C: PatternColor,
+{
+    /// Every frame sent to the display, in order
+    ///
+    /// Full frames, windows and cleared frames all record the whole frame in the RAM
+    /// afterwards.
+    pub fn frames(&self) -> &[Frame<C>] {
+        &self.frames
+    }
+
+    /// Number of frames sent to the display
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The frame sent last, `None` before the first one
+    pub fn last_frame(&self) -> Option<&Frame<C>> {
+        self.frames.last()
+    }
+
+    /// Color of the pixel (x, y) of the frame sent last
+    pub fn last_frame_pixel(&self, x: u32, y: u32) -> Option<C> {
+        self.last_frame()?.pixel(x, y)
+    }
+
+    /// The frame the last refresh showed, `None` before the first refresh
+    pub fn shown_frame(&self) -> Option<&Frame<C>> {
+        self.frames.get(self.shown?.checked_sub(1)?)
+    }
+
+    /// Whether the frame sent last is on the display
+    ///
+    /// `false` before the first frame.
+    pub fn was_refreshed_after_last_update(&self) -> bool {
+        !self.frames.is_empty() && self.shown == Some(self.frames.len())
+    }
+
+    /// Number of refreshes by [display_frame](WaveshareDisplay::display_frame)
+    pub fn refresh_count(&self) -> usize {
+        self.calls
+            .iter()
+            .filter(|&&call| call == Call::Refresh)
+            .count()
+    }
+
+    /// Every call which changed the display, in order
+    pub fn calls(&self) -> &[Call] {
+        &self.calls
+    }
+
+    // Fails like the drivers refuse to talk to a sleeping or uninitialized display
+    fn check_awake<E>(&self) -> Result<(), Error<E>> {
+        match self.state {
+            DisplayState::Asleep => Err(Error::Asleep),
+            DisplayState::Uninitialized => Err(Error::Uninitialized),
+            _ => Ok(()),
+        }
+    }
+
+    // The byte of `plane` the background is sent as, flipped if inverted like the drivers
+    // do for the b/w frame
+    fn background_byte(&self, plane: usize) -> u8 {
+        let byte = self.background.fill_byte(plane);
+        if self.inverted && plane == 0 && C::PIXELS_PER_BYTE == 8 {
+            !byte
+        } else {
+            byte
+        }
+    }
+
+    // Writes `data` to `plane` and pads it with the background
+    fn write_plane(&mut self, plane: usize, data: &[u8]) {
+        let len = plane_size::<C>(WIDTH, HEIGHT);
+        let background = self.background_byte(plane);
+        let ram = &mut self.ram[plane * len..][..len];
+        for (i, byte) in ram.iter_mut().enumerate() {
+            *byte = data.get(i).copied().unwrap_or(background);
+        }
+    }
+
+    // Writes the rows of a window to the b/w plane in whole bytes and pads them with the
+    // background
+    fn write_window(&mut self, data: &[u8], x: u32, y: u32, width: u32, height: u32) {
+        let stride = WIDTH.div_ceil(8) as usize;
+        let row_len = width.div_ceil(8) as usize;
+        let start = (x / 8) as usize;
+        let background = self.background_byte(0);
+        for row in 0..height as usize {
+            for i in 0..row_len.min(stride - start) {
+                let byte = data.get(row * row_len + i).copied().unwrap_or(background);
+                self.ram[(y as usize + row) * stride + start + i] = byte;
+            }
+        }
+    }
+
+    // Records the frame in the RAM after it changed
+    fn record(&mut self) {
+        self.frames.push(Frame::decode(&self.ram, WIDTH, HEIGHT));
+        self.calls.push(Call::Update);
+        self.state = DisplayState::FramePending;
+    }
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY, C, const WIDTH: u32, const HEIGHT: u32>
+    WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
+    for FakeEpd<SPI, CS, BUSY, DC, RST, DELAY, C, WIDTH, HEIGHT>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    C: PatternColor,
+{
+    type DisplayColor = C;
+    const SUPPORTS_PARTIAL: bool = C::PIXELS_PER_BYTE == 8;
+    const SUPPORTS_QUICK_REFRESH: bool = true;
+    const SUPPORTS_LUT: bool = true;
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = C::PLANES as u8;
+    const POLARITY: Polarity = Polarity::ClearedIsColor;
+    const IS_BUSY_LOW: bool = false;
+
+    fn new(_cs: CS, _busy: BUSY, _dc: DC, _rst: RST) -> Self {
+        let len = plane_size::<C>(WIDTH, HEIGHT);
+        let ram = (0..C::PLANES)
+            .flat_map(|plane| core::iter::repeat_n(C::BACKGROUND.fill_byte(plane), len))
+            .collect();
+        FakeEpd {
+            _bus: PhantomData,
+            ram,
+            frames: Vec::new(),
+            calls: Vec::new(),
+            shown: None,
+            state: DisplayState::Uninitialized,
+            background: C::BACKGROUND,
+            inverted: false,
+        }
+    }
+
+    fn init(&mut self, _spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.calls.push(Call::Init);
+        self.state = DisplayState::Idle;
+        Ok(())
+    }
+
+    fn assume_init(&mut self) {
+        self.calls.push(Call::Init);
+        self.state = DisplayState::Idle;
+    }
+
+    fn sleep(&mut self, _spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.check_awake()?;
+        self.calls.push(Call::Sleep);
+        self.state = DisplayState::Asleep;
+        Ok(())
+    }
+
+    fn wake_up(&mut self, _spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.calls.push(Call::WakeUp);
+        self.state = DisplayState::Idle;
+        Ok(())
+    }
+
+    fn set_power_pin(&mut self, _pwr: &'static mut dyn PowerPin) {}
+
+    fn set_data_delay(&mut self, _data_delay: Option<DataDelay>) {}
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        assert!(chunk_size != Some(0), "chunk_size must not be 0");
+    }
+
+    fn set_busy_wait(&mut self, _busy_wait: &'static dyn BusyWait) {}
+
+    fn set_busy_debounce(&mut self, _busy_debounce: Option<BusyDebounce>) {}
+
+    fn set_busy_check(&mut self, _window_ms: Option<u16>) {}
+
+    fn set_progress(&mut self, _progress: Option<ProgressHook>) {}
+
+    fn set_clock(&mut self, _clock: Option<Clock>) {}
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        None
+    }
+
+    fn set_reset_active_high(&mut self, _active_high: bool) -> Result<(), Error<SPI::Error>> {
+        Ok(())
+    }
+
+    fn set_background_color(&mut self, color: C) {
+        self.background = color;
+    }
+
+    fn background_color(&self) -> &C {
+        &self.background
+    }
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    fn inverted(&self) -> bool {
+        self.inverted
+    }
+
+    fn width(&self) -> u32 {
+        WIDTH
+    }
+
+    fn height(&self) -> u32 {
+        HEIGHT
+    }
+
+    fn update_frame(
+        &mut self,
+        _spi: &mut SPI,
+        buffer: &[u8],
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        check_len(buffer, plane_size::<C>(WIDTH, HEIGHT))?;
+        self.check_awake()?;
+        self.write_plane(0, buffer);
+        // Like the tri-color drivers, the chromatic frame is cleared to no color
+        for plane in 1..C::PLANES {
+            let len = plane_size::<C>(WIDTH, HEIGHT);
+            self.ram[plane * len..][..len].fill(C::BACKGROUND.fill_byte(plane));
+        }
+        self.record();
+        Ok(())
+    }
+
+    fn update_partial_frame(
+        &mut self,
+        _spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        if !Self::SUPPORTS_PARTIAL {
+            return Err(Error::Unsupported);
+        }
+        check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        check_len(buffer, (width.div_ceil(8) * height) as usize)?;
+        self.check_awake()?;
+        self.write_window(buffer, x, y, width, height);
+        self.record();
+        Ok(())
+    }
+
+    fn clear_area(
+        &mut self,
+        _spi: &mut SPI,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        if !Self::SUPPORTS_PARTIAL {
+            return Err(Error::Unsupported);
+        }
+        check_window((x, y, width, height), WIDTH, HEIGHT)?;
+        self.check_awake()?;
+        self.write_window(&[], x, y, width, height);
+        self.record();
+        Ok(())
+    }
+
+    fn display_frame(
+        &mut self,
+        _spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.check_awake()?;
+        if self.state != DisplayState::FramePending {
+            return Err(Error::NoFrame);
+        }
+        self.calls.push(Call::Refresh);
+        self.shown = Some(self.frames.len());
+        self.state = DisplayState::Idle;
+        Ok(())
+    }
+
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        Ok(self.display_frame(spi, delay)?)
+    }
+
+    fn update_and_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.update_frame(spi, buffer, delay)?;
+        self.display_frame(spi, delay)
+    }
+
+    fn clear_frame(&mut self, _spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.check_awake()?;
+        for plane in 0..C::PLANES {
+            self.write_plane(plane, &[]);
+        }
+        self.record();
+        Ok(())
+    }
+
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.check_awake()?;
+        let len = plane_size::<C>(WIDTH, HEIGHT);
+        for (i, byte) in self.ram.iter_mut().enumerate() {
+            *byte = frame_byte(WIDTH, HEIGHT, i / len, i % len, C::pattern);
+        }
+        self.record();
+        self.display_frame(spi, delay)
+    }
+
+    fn set_lut(
+        &mut self,
+        _spi: &mut SPI,
+        refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.check_awake()?;
+        self.calls.push(Call::SetLut(refresh_rate));
+        Ok(())
+    }
+
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
+        Ok(false)
+    }
+
+    fn state(&self) -> DisplayState {
+        self.state
+    }
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY, const WIDTH: u32, const HEIGHT: u32>
+    WaveshareThreeColorDisplay<SPI, CS, BUSY, DC, RST, DELAY>
+    for FakeEpd<SPI, CS, BUSY, DC, RST, DELAY, TriColor, WIDTH, HEIGHT>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    fn update_color_frame(
+        &mut self,
+        _spi: &mut SPI,
+        black: &[u8],
+        chromatic: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        let len = plane_size::<TriColor>(WIDTH, HEIGHT);
+        check_exact_len(black, len)?;
+        check_exact_len(chromatic, len)?;
+        self.check_awake()?;
+        self.write_plane(0, black);
+        self.write_plane(1, chromatic);
+        self.record();
+        Ok(())
+    }
+
+    fn update_achromatic_frame(
+        &mut self,
+        _spi: &mut SPI,
+        black: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        check_exact_len(black, plane_size::<TriColor>(WIDTH, HEIGHT))?;
+        self.check_awake()?;
+        self.write_plane(0, black);
+        self.record();
+        Ok(())
+    }
+
+    fn update_chromatic_frame(
+        &mut self,
+        _spi: &mut SPI,
+        chromatic: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        check_exact_len(chromatic, plane_size::<TriColor>(WIDTH, HEIGHT))?;
+        self.check_awake()?;
+        self.write_plane(1, chromatic);
+        self.record();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::mock::{init_epd, BusyPin, DcPin, Delay, Log, Pin, Spi};
+
+    type Fake<C> = FakeEpd<Spi, Pin, BusyPin, DcPin, Pin, Delay, C, 12, 4>;
+
+    #[test]
+    fn frames_are_decoded() {
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Fake<Color>>();
+        // 12 pixels wide, the second byte of a row has 4 padding bits
+        let mut buffer = [0xff; 2 * 4];
+        buffer[0] = 0x7f;
+        buffer[7] = 0xef;
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        assert_eq!(epd.last_frame_pixel(0, 0), Some(Color::Black));
+        assert_eq!(epd.last_frame_pixel(1, 0), Some(Color::White));
+        assert_eq!(epd.last_frame_pixel(11, 3), Some(Color::Black));
+        assert_eq!(epd.last_frame_pixel(12, 3), None);
+        assert!(!epd.was_refreshed_after_last_update());
+        assert_eq!(
+            epd.update_frame(&mut spi, &[0xff; 9], &mut delay),
+            Err(Error::BufferTooLong { max: 8, actual: 9 })
+        );
+
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert!(epd.was_refreshed_after_last_update());
+        assert_eq!(epd.display_frame(&mut spi, &mut delay), Err(Error::NoFrame));
+        // nothing is sent
+        assert!(log.transfers().is_empty());
+    }
+
+    #[test]
+    fn partial_windows_in_whole_bytes() {
+        let (_log, mut spi, mut delay, mut epd) = init_epd::<Fake<Color>>();
+        epd.set_background_color(Color::Black);
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+
+        // x is rounded down to the byte, the window covers the pixels 8 to 11
+        epd.update_partial_frame(&mut spi, &[0xff, 0xff], 9, 1, 2, 2)
+            .unwrap();
+        let frame = epd.last_frame().unwrap();
+        assert_eq!(frame.pixel(7, 1), Some(Color::Black));
+        assert_eq!(frame.pixel(8, 1), Some(Color::White));
+        assert_eq!(frame.pixel(11, 2), Some(Color::White));
+        assert_eq!(frame.pixel(8, 3), Some(Color::Black));
+        assert_eq!(
+            epd.update_partial_frame(&mut spi, &[0xff], 8, 3, 8, 2),
+            Err(Error::OutOfBounds)
+        );
+
+        epd.clear_area(&mut spi, 8, 2, 1, 1).unwrap();
+        assert_eq!(epd.last_frame_pixel(11, 2), Some(Color::Black));
+        assert_eq!(epd.last_frame_pixel(11, 1), Some(Color::White));
+        assert_eq!(epd.frame_count(), 3);
+    }
+
+    #[test]
+    fn tri_color_planes() {
+        let (_log, mut spi, mut delay, mut epd) = init_epd::<Fake<TriColor>>();
+        epd.update_color_frame(&mut spi, &[0x7f; 8], &[0xbf; 8])
+            .unwrap();
+        assert_eq!(epd.last_frame_pixel(0, 0), Some(TriColor::Black));
+        assert_eq!(epd.last_frame_pixel(1, 0), Some(TriColor::Chromatic));
+        assert_eq!(epd.last_frame_pixel(2, 0), Some(TriColor::White));
+
+        // update_frame clears the chromatic frame
+        epd.update_frame(&mut spi, &[0xff; 8], &mut delay).unwrap();
+        assert_eq!(epd.last_frame_pixel(1, 0), Some(TriColor::White));
+    }
+
+    #[test]
+    fn calls_follow_the_state_of_the_display() {
+        let log = Log::default();
+        let (mut spi, mut delay) = (log.spi(), Delay::new());
+        let mut epd = Fake::<Color>::new(Pin, BusyPin::default(), log.dc(), Pin);
+        assert_eq!(
+            epd.update_frame(&mut spi, &[0xff; 8], &mut delay),
+            Err(Error::Uninitialized)
+        );
+
+        epd.init(&mut spi, &mut delay).unwrap();
+        epd.test_pattern(&mut spi, &mut delay).unwrap();
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        assert_eq!(
+            epd.update_frame(&mut spi, &[0xff; 8], &mut delay),
+            Err(Error::Asleep)
+        );
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+
+        assert_eq!(
+            epd.calls(),
+            [
+                Call::Init,
+                Call::Update,
+                Call::Refresh,
+                Call::Sleep,
+                Call::WakeUp
+            ]
+        );
+        assert_eq!(epd.refresh_count(), 1);
+        // the checkerboard of the drivers
+        assert_eq!(epd.shown_frame().unwrap().pixel(1, 1), Some(Color::Black));
+        assert_eq!(epd.shown_frame().unwrap().pixel(10, 1), Some(Color::White));
+    }
+}
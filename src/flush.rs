@@ -0,0 +1,251 @@
+//! Sending a display buffer to its driver and refreshing in one call
+//!
+//! Every `Display*` buffer of the drivers knows which drivers it fits through [Flushable],
+//! and [Flush] adds [flush](Flush::flush) and [flush_partial](Flush::flush_partial) to
+//! all drivers. A buffer of the wrong size or color doesn't compile, and the tri-color
+//! buffers are sent with both planes.
+//!
+//! ```rust, no_run
+//! # use embedded_hal_mock::*;
+//! # fn main() -> Result<(), epd_waveshare::Error<MockError>> {
+//! use embedded_graphics::{prelude::*, primitives::{Line, PrimitiveStyle}};
+//! use epd_waveshare::{epd2in9bc::*, flush::Flush, prelude::*};
+//! #
+//! # let expectations = [];
+//! # let mut spi = spi::Mock::new(&expectations);
+//! # let expectations = [];
+//! # let cs_pin = pin::Mock::new(&expectations);
+//! # let busy_in = pin::Mock::new(&expectations);
+//! # let dc = pin::Mock::new(&expectations);
+//! # let rst = pin::Mock::new(&expectations);
+//! # let mut delay = delay::MockNoop::new();
+//! let mut epd = Epd2in9bc::new(cs_pin, busy_in, dc, rst);
+//! epd.init(&mut spi, &mut delay)?;
+//!
+//! let mut display = Display2in9bcTriColor::default();
+//! let _ = Line::new(Point::new(0, 120), Point::new(0, 295))
+//!     .into_styled(PrimitiveStyle::with_stroke(TriColor::Chromatic, 1))
+//!     .draw(&mut display);
+//!
+//! // update_color_frame with both planes and display_frame
+//! epd.flush(&mut spi, &display, &mut delay)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::damage::update_window;
+use crate::error::Error;
+use crate::graphics::{find_rect, unrotated_size, Display};
+use crate::traits::WaveshareDisplay;
+use embedded_graphics_core::primitives::Rectangle;
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::*,
+};
+
+/// A display buffer which can be sent to the driver `EPD` as a whole frame
+///
+/// Implemented for the `Display*` buffers of every driver module for the drivers of the
+/// same size and color, e.g. [Display2in9](crate::epd2in9::Display2in9) for both 2.9"
+/// drivers and [Display1in54b](crate::epd1in54b::Display1in54b) as well as
+/// [Display1in54bTriColor](crate::epd1in54b::Display1in54bTriColor) for the 1.54" b.
+pub trait Flushable<EPD, SPI, DELAY>
+where
+    SPI: Write<u8>,
+{
+    /// Transmits the buffer to the SRAM of `epd` without refreshing
+    fn update(
+        &self,
+        epd: &mut EPD,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>;
+}
+
+/// Updates and refreshes a driver with a matching display buffer
+///
+/// Implemented for every driver, see the [module documentation](self).
+pub trait Flush<SPI, CS, BUSY, DC, RST, DELAY>:
+    WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY> + Sized
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    /// Sends the whole buffer and displays it
+    ///
+    /// B/w and 7 color buffers are sent with
+    /// [update_frame](WaveshareDisplay::update_frame), tri-color buffers with
+    /// [update_color_frame](crate::traits::WaveshareThreeColorDisplay::update_color_frame).
+    fn flush<F>(
+        &mut self,
+        spi: &mut SPI,
+        display: &F,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        F: Flushable<Self, SPI, DELAY>,
+    {
+        display.update(self, spi, delay)?;
+        self.display_frame(spi, delay)
+    }
+
+    /// Sends the part of a b/w buffer under `area` with a partial update and displays it
+    ///
+    /// `area` is in drawing coordinates, so it's rotated and mirrored like the drawing.
+    /// The window and `scratch` work like in
+    /// [DamageTracked::flush_partial](crate::damage::DamageTracked::flush_partial), and
+    /// displays without partial updates get the full frame. Doesn't talk to the display at
+    /// all if `area` is outside of the display.
+    fn flush_partial<D>(
+        &mut self,
+        spi: &mut SPI,
+        display: &D,
+        area: &Rectangle,
+        delay: &mut DELAY,
+        scratch: &mut [u8],
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        D: Display + Flushable<Self, SPI, DELAY>,
+    {
+        let (rotation, mirroring) = (display.rotation(), display.mirroring());
+        let (width, height) = unrotated_size(display.bounding_box().size, rotation);
+        let window = match find_rect(area, width, height, rotation, mirroring) {
+            Some(window) => window,
+            None => return Ok(()),
+        };
+        update_window(self, spi, delay, display.buffer(), width, window, scratch)?;
+        self.display_frame(spi, delay)
+    }
+}
+
+impl<SPI, CS, BUSY, DC, RST, DELAY, EPD> Flush<SPI, CS, BUSY, DC, RST, DELAY> for EPD
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    EPD: WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>,
+{
+}
+
+// Implements `Flushable` of a display buffer for a driver. Single plane buffers are sent
+// with `update_frame` through the given buffer trait, `tri` buffers with both planes.
+macro_rules! impl_flushable {
+    (@impl $display:ty => $epd:ident, |$e:ident, $s:ident, $d:ident, $delay:ident| $body:block) => {
+        impl<SPI, CS, BUSY, DC, RST, DELAY>
+            crate::flush::Flushable<$epd<SPI, CS, BUSY, DC, RST, DELAY>, SPI, DELAY> for $display
+        where
+            SPI: embedded_hal::blocking::spi::Write<u8>,
+            CS: embedded_hal::digital::v2::OutputPin,
+            BUSY: embedded_hal::digital::v2::InputPin,
+            DC: embedded_hal::digital::v2::OutputPin,
+            RST: embedded_hal::digital::v2::OutputPin,
+            DELAY: embedded_hal::blocking::delay::DelayMs<u8>,
+        {
+            fn update(
+                &self,
+                $e: &mut $epd<SPI, CS, BUSY, DC, RST, DELAY>,
+                $s: &mut SPI,
+                $delay: &mut DELAY,
+            ) -> Result<(), crate::error::Error<SPI::Error>> {
+                let $d = self;
+                $body
+            }
+        }
+    };
+    ($display:ty => $epd:ident, tri) => {
+        crate::flush::impl_flushable!(@impl $display => $epd, |epd, spi, display, _delay| {
+            use crate::graphics::TriDisplay;
+            use crate::traits::WaveshareThreeColorDisplay;
+            epd.update_color_frame(spi, display.bw_buffer(), display.chromatic_buffer())
+        });
+    };
+    ($display:ty => $epd:ident, $buffer:path) => {
+        crate::flush::impl_flushable!(@impl $display => $epd, |epd, spi, display, delay| {
+            use crate::traits::WaveshareDisplay;
+            epd.update_frame(spi, <$display as $buffer>::buffer(display), delay)
+        });
+    };
+}
+pub(crate) use impl_flushable;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{Black, TriColor};
+    use crate::epd2in9bc::{Display2in9bcTriColor, Epd2in9bc};
+    use crate::epd4in2::{command::Command, Display4in2, Epd4in2, WIDTH};
+    use crate::graphics::{DisplayRotation, TriDisplay};
+    use crate::mock::init_epd;
+    use crate::traits::{Command as _, WaveshareThreeColorDisplay};
+    use embedded_graphics::prelude::*;
+
+    #[test]
+    fn flush_sends_the_matching_planes() {
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+        let mut display = Display4in2::default();
+        Pixel(Point::new(3, 0), Black).draw(&mut display).unwrap();
+
+        log.clear();
+        epd.update_frame(&mut spi, display.buffer(), &mut delay)
+            .unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        let expected = log.transcript();
+        log.clear();
+        epd.flush(&mut spi, &display, &mut delay).unwrap();
+        assert_eq!(log.transcript(), expected);
+
+        // both planes of a tri-color buffer
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd2in9bc<_, _, _, _, _, _>>();
+        let mut display = Display2in9bcTriColor::default();
+        Pixel(Point::new(3, 0), TriColor::Chromatic)
+            .draw(&mut display)
+            .unwrap();
+
+        log.clear();
+        epd.update_color_frame(&mut spi, display.bw_buffer(), display.chromatic_buffer())
+            .unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        let expected = log.transcript();
+        log.clear();
+        epd.flush(&mut spi, &display, &mut delay).unwrap();
+        assert_eq!(log.transcript(), expected);
+    }
+
+    #[test]
+    fn flush_partial_sends_the_rotated_area() {
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+        let mut display = Display4in2::default();
+        display.set_rotation(DisplayRotation::Rotate90);
+
+        // (0, 0) of the rotated display is the pixel at the right end of the top row
+        Pixel(Point::new(0, 0), Black).draw(&mut display).unwrap();
+        log.clear();
+        epd.update_partial_frame(&mut spi, &[!0x01], WIDTH - 8, 0, 8, 1)
+            .unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        let expected = log.transcript();
+        let area = Rectangle::new(Point::zero(), Size::new(1, 1));
+        log.clear();
+        epd.flush_partial(&mut spi, &display, &area, &mut delay, &mut [])
+            .unwrap();
+        assert_eq!(log.transcript(), expected);
+        assert_eq!(
+            log.data_after(Command::DataStartTransmission2.address()),
+            [!0x01]
+        );
+
+        // nothing to send outside of the display
+        log.clear();
+        let area = Rectangle::new(Point::new(-5, 0), Size::new(5, 5));
+        epd.flush_partial(&mut spi, &display, &area, &mut delay, &mut [])
+            .unwrap();
+        assert!(log.transfers().is_empty());
+    }
+}
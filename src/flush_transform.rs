@@ -0,0 +1,407 @@
+//! B/w display which applies rotation and mirroring once when the frame is sent
+//!
+//! The other graphics displays keep their buffer in the layout of the controller, so every
+//! drawn pixel is rotated and mirrored on its own. [FlushTransformDisplay] keeps the picture
+//! just like it's drawn instead: drawing a pixel only sets a bit and `fill_solid` always
+//! writes whole bytes, no matter the rotation. The remapping happens once per frame in
+//! [iter_transmission_bytes](FlushTransformDisplay::iter_transmission_bytes) or
+//! [buffer_for_update](FlushTransformDisplay::buffer_for_update).
+//!
+//! The trade-off:
+//! - CPU: drawing gets cheaper, sending gets more expensive since every pixel of the frame
+//!   is remapped, also the ones which didn't change. It pays off for frames with a lot of
+//!   drawing, e.g. text or many primitives, and not for a few lines on an otherwise
+//!   static frame.
+//! - RAM: the drawing buffer is the rotated size rounded to whole bytes per row, which can
+//!   be up to 7 rows more than the frame (e.g. 15200 instead of 15000 bytes for the EPD 4in2
+//!   rotated by 90 degrees). Sending the frame needs either a second full frame buffer
+//!   for `buffer_for_update` or, with
+//!   [update_frame_iter](crate::traits::StreamFrame::update_frame_iter), only a small chunk.
+//!
+//! The frame is pixel for pixel the same as drawing with a [VarDisplay](crate::graphics::VarDisplay)
+//! with the same rotation and mirroring.
+//!
+//! ```
+//! use embedded_graphics::{
+//!     pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
+//! };
+//! use epd_waveshare::flush_transform::FlushTransformDisplay;
+//! use epd_waveshare::{buffer_len, graphics::DisplayRotation, prelude::*};
+//!
+//! const ROTATION: DisplayRotation = DisplayRotation::Rotate90;
+//! let mut buffer = [0; FlushTransformDisplay::buffer_len(128, 296, ROTATION)];
+//! let mut display = FlushTransformDisplay::with_flush_transform(
+//!     128,
+//!     296,
+//!     &mut buffer,
+//!     ROTATION,
+//!     DisplayMirroring::None,
+//! )
+//! .unwrap();
+//! display.clear_buffer(Color::White);
+//! let _ = Line::new(Point::new(0, 0), Point::new(295, 0))
+//!     .into_styled(PrimitiveStyle::with_stroke(Black, 1))
+//!     .draw(&mut display);
+//!
+//! let mut frame = [0; buffer_len(128, 296)];
+//! let frame = display.buffer_for_update(&mut frame).unwrap();
+//! // the top row of the rotated display is the right column of the frame
+//! assert_eq!(frame[15], 0xfe);
+//! ```
+
+use crate::buffer_len;
+use crate::color::Color;
+use crate::frame::FrameDataError;
+use crate::graphics::{fill_bits, find_rect, DisplayMirroring, DisplayRotation};
+use crate::layout;
+use embedded_graphics_core::pixelcolor::BinaryColor;
+use embedded_graphics_core::prelude::*;
+use embedded_graphics_core::primitives::Rectangle;
+
+/// A b/w display on a borrowed buffer which keeps the picture unrotated until it's sent
+///
+/// See the [module documentation](self) for when to use it instead of a
+/// [VarDisplay](crate::graphics::VarDisplay).
+pub struct FlushTransformDisplay<'a> {
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    buffer: &'a mut [u8],
+}
+
+impl<'a> FlushTransformDisplay<'a> {
+    /// Length of the drawing buffer for a `width` x `height` display seen with `rotation`
+    pub const fn buffer_len(width: u32, height: u32, rotation: DisplayRotation) -> usize {
+        match rotation {
+            DisplayRotation::Rotate0 | DisplayRotation::Rotate180 => {
+                buffer_len(width as usize, height as usize)
+            }
+            DisplayRotation::Rotate90 | DisplayRotation::Rotate270 => {
+                buffer_len(height as usize, width as usize)
+            }
+        }
+    }
+
+    /// Create a display for a `width` x `height` frame which gets rotated and mirrored
+    /// when it's sent
+    ///
+    /// `width` and `height` are the unrotated size of the display like for the drivers.
+    /// Fails if `buffer` isn't exactly [buffer_len](Self::buffer_len) bytes long.
+    pub fn with_flush_transform(
+        width: u32,
+        height: u32,
+        buffer: &'a mut [u8],
+        rotation: DisplayRotation,
+        mirroring: DisplayMirroring,
+    ) -> Result<FlushTransformDisplay<'a>, FrameDataError> {
+        let expected = Self::buffer_len(width, height, rotation);
+        if buffer.len() != expected {
+            return Err(FrameDataError::BufferLength {
+                expected,
+                actual: buffer.len(),
+            });
+        }
+        Ok(FlushTransformDisplay {
+            width,
+            height,
+            rotation,
+            mirroring,
+            buffer,
+        })
+    }
+
+    /// The picture as it's drawn, row by row of the rotated size
+    pub fn buffer(&self) -> &[u8] {
+        self.buffer
+    }
+
+    /// The rotation applied when the frame is sent
+    pub fn rotation(&self) -> DisplayRotation {
+        self.rotation
+    }
+
+    /// The mirroring applied when the frame is sent
+    pub fn mirroring(&self) -> DisplayMirroring {
+        self.mirroring
+    }
+
+    /// Clears the buffer with the chosen background color
+    pub fn clear_buffer(&mut self, background_color: Color) {
+        self.buffer.fill(background_color.get_byte_value());
+    }
+
+    /// Color of the pixel at `point`, `None` if it's outside of the display
+    pub fn get_pixel(&self, point: Point) -> Option<BinaryColor> {
+        if !self.bounding_box().contains(point) {
+            return None;
+        }
+        let (index, bit) = self.position(point.x as u32, point.y as u32);
+        if self.buffer[index] & bit == 0 {
+            Some(BinaryColor::On)
+        } else {
+            Some(BinaryColor::Off)
+        }
+    }
+
+    /// The bytes of the frame in the order they are sent to the display
+    ///
+    /// Laid out like the buffer of [update_frame](crate::traits::WaveshareDisplay::update_frame),
+    /// the padding bits at the end of the rows are white.
+    pub fn iter_transmission_bytes(&self) -> TransmissionBytes<'_> {
+        TransmissionBytes {
+            buffer: self.buffer,
+            width: self.width,
+            height: self.height,
+            rotation: self.rotation,
+            mirroring: self.mirroring,
+            drawn_width: self.size().width,
+            index: 0,
+            len: buffer_len(self.width as usize, self.height as usize),
+        }
+    }
+
+    /// Writes the frame into `frame` and returns it, ready for
+    /// [update_frame](crate::traits::WaveshareDisplay::update_frame)
+    ///
+    /// Fails if `frame` doesn't have the length of a full frame.
+    pub fn buffer_for_update<'b>(&self, frame: &'b mut [u8]) -> Result<&'b [u8], FrameDataError> {
+        let expected = buffer_len(self.width as usize, self.height as usize);
+        if frame.len() != expected {
+            return Err(FrameDataError::BufferLength {
+                expected,
+                actual: frame.len(),
+            });
+        }
+        for (byte, sent) in frame.iter_mut().zip(self.iter_transmission_bytes()) {
+            *byte = sent;
+        }
+        Ok(frame)
+    }
+
+    // index and bit of a drawn pixel, without any rotation
+    fn position(&self, x: u32, y: u32) -> (usize, u8) {
+        let (index, shift) = layout::bit_position(self.size().width, 1, x, y);
+        (index, 1 << shift)
+    }
+}
+
+impl<'a> DrawTarget for FlushTransformDisplay<'a> {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let area = self.bounding_box();
+        for Pixel(point, color) in pixels {
+            if !area.contains(point) {
+                continue;
+            }
+            let (index, bit) = self.position(point.x as u32, point.y as u32);
+            match color {
+                BinaryColor::On => self.buffer[index] &= !bit,
+                BinaryColor::Off => self.buffer[index] |= bit,
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let size = self.size();
+        let (rotation, mirroring) = (DisplayRotation::Rotate0, DisplayMirroring::None);
+        if let Some(rect) = find_rect(area, size.width, size.height, rotation, mirroring) {
+            // black is a cleared bit
+            fill_bits(self.buffer, size.width, rect, color == BinaryColor::Off);
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color.into());
+        Ok(())
+    }
+}
+
+impl<'a> OriginDimensions for FlushTransformDisplay<'a> {
+    fn size(&self) -> Size {
+        self.rotation.rotated_size(self.width, self.height)
+    }
+}
+
+/// The frame of a [FlushTransformDisplay] byte by byte, see
+/// [iter_transmission_bytes](FlushTransformDisplay::iter_transmission_bytes)
+pub struct TransmissionBytes<'b> {
+    buffer: &'b [u8],
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    // width of the drawing
+    drawn_width: u32,
+    index: usize,
+    len: usize,
+}
+
+impl<'b> TransmissionBytes<'b> {
+    // The drawn pixel which ends up at (x, y) of the frame, the inverse of the
+    // rotation and mirroring of the other displays
+    fn drawn_position(&self, x: u32, y: u32) -> (u32, u32) {
+        let (width, height) = (self.width, self.height);
+        layout::from_buffer(x, y, width, height, self.rotation, self.mirroring)
+    }
+}
+
+impl<'b> Iterator for TransmissionBytes<'b> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.index >= self.len {
+            return None;
+        }
+        let frame_stride = layout::stride(self.width, 1) as usize;
+        let y = (self.index / frame_stride) as u32;
+        let first = (self.index % frame_stride) as u32 * 8;
+        self.index += 1;
+
+        // padding bits stay white
+        let mut byte = 0xff;
+        for x in first..(first + 8).min(self.width) {
+            let (dx, dy) = self.drawn_position(x, y);
+            let (index, shift) = layout::bit_position(self.drawn_width, 1, dx, dy);
+            if self.buffer[index] & (1 << shift) == 0 {
+                byte &= !(1 << layout::shift(1, x - first));
+            }
+        }
+        Some(byte)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.len - self.index;
+        (left, Some(left))
+    }
+}
+
+impl<'b> ExactSizeIterator for TransmissionBytes<'b> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::{Display, VarDisplay};
+    use embedded_graphics::{
+        prelude::*,
+        primitives::{Circle, Line, PrimitiveStyle, Rectangle},
+    };
+    extern crate std;
+    use std::vec;
+
+    const ROTATIONS: [DisplayRotation; 4] = [
+        DisplayRotation::Rotate0,
+        DisplayRotation::Rotate90,
+        DisplayRotation::Rotate180,
+        DisplayRotation::Rotate270,
+    ];
+    const MIRRORINGS: [DisplayMirroring; 3] = [
+        DisplayMirroring::None,
+        DisplayMirroring::MirrorX,
+        DisplayMirroring::MirrorY,
+    ];
+
+    fn draw_scene<D: DrawTarget<Color = BinaryColor>>(display: &mut D) {
+        let black = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+        let _ = Line::new(Point::new(-3, 1), Point::new(25, 7))
+            .into_styled(black)
+            .draw(display);
+        let _ = Circle::new(Point::new(3, 2), 7)
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(display);
+        let _ = display.fill_solid(
+            &Rectangle::new(Point::new(5, 4), Size::new(11, 3)),
+            BinaryColor::Off,
+        );
+        let _ = display.fill_solid(
+            &Rectangle::new(Point::new(-2, 8), Size::new(40, 30)),
+            BinaryColor::On,
+        );
+        let _ = Pixel(Point::new(0, 0), BinaryColor::On).draw(display);
+        let _ = Pixel(Point::new(20, 9), BinaryColor::On).draw(display);
+    }
+
+    #[test]
+    fn pixel_identical_to_var_display() {
+        // neither width nor height a multiple of 8
+        let (width, height) = (21, 10);
+        for &rotation in ROTATIONS.iter() {
+            for &mirroring in MIRRORINGS.iter() {
+                let mut reference_buffer = [0xff; buffer_len(21, 10)];
+                let mut reference = VarDisplay::new(width, height, &mut reference_buffer).unwrap();
+                reference.set_rotation(rotation);
+                reference.set_mirroring(mirroring);
+                draw_scene(&mut reference);
+
+                let mut buffer =
+                    vec![0; FlushTransformDisplay::buffer_len(width, height, rotation)];
+                let mut display = FlushTransformDisplay::with_flush_transform(
+                    width,
+                    height,
+                    &mut buffer,
+                    rotation,
+                    mirroring,
+                )
+                .unwrap();
+                display.clear_buffer(Color::White);
+                draw_scene(&mut display);
+
+                assert_eq!(display.size(), reference.size());
+                for p in display.bounding_box().points() {
+                    assert_eq!(display.get_pixel(p), reference.get_pixel(p));
+                }
+                let mut frame = [0; buffer_len(21, 10)];
+                let frame = display.buffer_for_update(&mut frame).unwrap();
+                assert_eq!(frame, reference.buffer(), "{:?} {:?}", rotation, mirroring);
+                assert_eq!(display.iter_transmission_bytes().len(), frame.len());
+                assert!(display.iter_transmission_bytes().eq(frame.iter().copied()));
+            }
+        }
+    }
+
+    #[test]
+    fn buffer_lengths() {
+        let rotation = DisplayRotation::Rotate90;
+        assert_eq!(FlushTransformDisplay::buffer_len(400, 300, rotation), 15200);
+        let mut buffer = [0; 15000];
+        assert_eq!(
+            FlushTransformDisplay::with_flush_transform(
+                400,
+                300,
+                &mut buffer,
+                rotation,
+                DisplayMirroring::None
+            )
+            .err(),
+            Some(FrameDataError::BufferLength {
+                expected: 15200,
+                actual: 15000
+            })
+        );
+
+        let mut buffer = [0; buffer_len(21, 10)];
+        let display = FlushTransformDisplay::with_flush_transform(
+            21,
+            10,
+            &mut buffer,
+            DisplayRotation::Rotate0,
+            DisplayMirroring::None,
+        )
+        .unwrap();
+        let mut frame = [0; 3];
+        assert_eq!(
+            display.buffer_for_update(&mut frame),
+            Err(FrameDataError::BufferLength {
+                expected: buffer_len(21, 10),
+                actual: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Description of a complete frame independent of a specific display
+//!
+//! Useful when a frame is rendered somewhere else (e.g. on a server) and only the raw
+//! bytes together with their dimensions are transferred to the device.
+
+use crate::color::{buffer_size, Color, ColorType, OctColor, TriColor};
+
+/// When the data of a frame doesn't fit its description
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FrameDataError {
+    /// Only 1 (b/w), 2 (tri-color) and 4 (7 color) bits per pixel are supported
+    UnsupportedBitsPerPixel(u8),
+    /// The length of the data doesn't match width, height and bits per pixel
+    BufferLength {
+        /// Length computed from width, height and bits per pixel
+        expected: usize,
+        /// Length of the given data
+        actual: usize,
+    },
+    /// The x coordinate of a partial window has to be a multiple of 8
+    UnalignedWindow(u32),
+}
+
+impl core::fmt::Display for FrameDataError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FrameDataError::UnsupportedBitsPerPixel(bpp) => {
+                write!(f, "Unsupported bits per pixel: {}", bpp)
+            }
+            FrameDataError::BufferLength { expected, actual } => write!(
+                f,
+                "Frame data has {} bytes but {} are needed",
+                actual, expected
+            ),
+            FrameDataError::UnalignedWindow(x) => {
+                write!(f, "Window starts at x = {} which isn't a multiple of 8", x)
+            }
+        }
+    }
+}
+
+/// Borrowed frame: the raw buffer together with its dimensions and format
+///
+/// The data is always guaranteed to have the right length for the dimensions,
+/// so it can directly be passed to `update_frame` of a matching display.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FrameData<'a> {
+    width: u32,
+    height: u32,
+    bits_per_pixel: u8,
+    data: &'a [u8],
+}
+
+impl<'a> FrameData<'a> {
+    /// Describe a frame after checking that `data` fits the dimensions and bits per pixel
+    pub fn new(
+        width: u32,
+        height: u32,
+        bits_per_pixel: u8,
+        data: &'a [u8],
+    ) -> Result<FrameData<'a>, FrameDataError> {
+        let expected = FrameData::buffer_size(width, height, bits_per_pixel)?;
+        if data.len() != expected {
+            return Err(FrameDataError::BufferLength {
+                expected,
+                actual: data.len(),
+            });
+        }
+        Ok(FrameData {
+            width,
+            height,
+            bits_per_pixel,
+            data,
+        })
+    }
+
+    /// Needed length of the data of a frame
+    pub fn buffer_size(
+        width: u32,
+        height: u32,
+        bits_per_pixel: u8,
+    ) -> Result<usize, FrameDataError> {
+        let (width, height) = (width as usize, height as usize);
+        match bits_per_pixel as usize {
+            Color::BITS_PER_PIXEL => Ok(buffer_size::<Color>(width, height)),
+            TriColor::BITS_PER_PIXEL => Ok(buffer_size::<TriColor>(width, height)),
+            OctColor::BITS_PER_PIXEL => Ok(buffer_size::<OctColor>(width, height)),
+            _ => Err(FrameDataError::UnsupportedBitsPerPixel(bits_per_pixel)),
+        }
+    }
+
+    /// Width of the frame in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the frame in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Bits per pixel of the frame
+    pub fn bits_per_pixel(&self) -> u8 {
+        self.bits_per_pixel
+    }
+
+    /// The raw frame data
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+/// Serialized as a struct with the data as bytes, e.g. for postcard
+#[cfg(feature = "serde")]
+impl serde::Serialize for FrameData<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        struct Bytes<'a>(&'a [u8]);
+
+        impl serde::Serialize for Bytes<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+
+        let mut frame = serializer.serialize_struct("FrameData", 4)?;
+        frame.serialize_field("width", &self.width)?;
+        frame.serialize_field("height", &self.height)?;
+        frame.serialize_field("bits_per_pixel", &self.bits_per_pixel)?;
+        frame.serialize_field("data", &Bytes(self.data))?;
+        frame.end()
+    }
+}
+
+/// Borrows the data from the input and checks it like [FrameData::new]
+#[cfg(feature = "serde")]
+impl<'de: 'a, 'a> serde::Deserialize<'de> for FrameData<'a> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "FrameData")]
+        struct Unchecked<'a> {
+            width: u32,
+            height: u32,
+            bits_per_pixel: u8,
+            #[serde(borrow)]
+            data: &'a [u8],
+        }
+
+        let frame = Unchecked::deserialize(deserializer)?;
+        FrameData::new(frame.width, frame.height, frame.bits_per_pixel, frame.data)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_frames() {
+        let data = [0xff; 15000];
+        let frame = FrameData::new(400, 300, 1, &data).unwrap();
+        assert_eq!(frame.width(), 400);
+        assert_eq!(frame.height(), 300);
+        assert_eq!(frame.bits_per_pixel(), 1);
+        assert_eq!(frame.data().len(), 15000);
+
+        assert!(FrameData::new(200, 75, 2, &data[..200 / 8 * 75 * 2]).is_ok());
+        assert!(FrameData::new(100, 100, 4, &data[..5000]).is_ok());
+    }
+
+    #[test]
+    fn invalid_frames() {
+        let data = [0xff; 5000];
+        assert_eq!(
+            FrameData::new(200, 200, 3, &data),
+            Err(FrameDataError::UnsupportedBitsPerPixel(3))
+        );
+        assert_eq!(
+            FrameData::new(200, 200, 4, &data),
+            Err(FrameDataError::BufferLength {
+                expected: 20000,
+                actual: 5000
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_roundtrip() {
+        let data = [0xf0; 60];
+        let frame = FrameData::new(20, 20, 1, &data).unwrap();
+        let mut bytes = [0; 64];
+        let bytes = postcard::to_slice(&frame, &mut bytes).unwrap();
+        assert_eq!(postcard::from_bytes::<FrameData>(bytes), Ok(frame));
+
+        let colors = (Color::Black, TriColor::Chromatic, OctColor::Orange);
+        let mut buf = [0; 8];
+        let buf = postcard::to_slice(&colors, &mut buf).unwrap();
+        assert_eq!(postcard::from_bytes(buf), Ok(colors));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_rejects_invalid_frames() {
+        // a 20x20 2 bpp frame needs 100 bytes
+        let data = [0xf0; 60];
+        let frame = FrameData::new(20, 20, 1, &data).unwrap();
+        let mut bytes = [0; 80];
+        let bytes = postcard::to_slice(&frame, &mut bytes).unwrap();
+        // bits per pixel follows the varint width and height
+        bytes[2] = 2;
+        assert!(postcard::from_bytes::<FrameData>(bytes).is_err());
+        bytes[2] = 3;
+        assert!(postcard::from_bytes::<FrameData>(bytes).is_err());
+    }
+}
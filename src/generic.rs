@@ -0,0 +1,764 @@
+//! A driver for panels which only differ by their description
+//!
+//! Many panels of Good Display and Waveshare share a controller family with one of the
+//! drivers here and differ only in the resolution, the init commands and the LUTs. Instead
+//! of a new module, such a panel can be described with a [PanelDescription] in a `const`
+//! and driven by [GenericEpd], which implements [WaveshareDisplay] from it.
+//!
+//! The [Controller] decides how the frame is written, the description the rest:
+//!
+//! - the [init](PanelDescription::init) sequence after the hardware reset, with
+//!   [Step::Lut] where the LUT of the current refresh goes,
+//! - the optional [LUTs](PanelDescription::luts) for [set_lut](WaveshareDisplay::set_lut),
+//! - the [refresh](PanelDescription::refresh) and [sleep](PanelDescription::sleep) sequences
+//!   and the busy polarity.
+//!
+//! [Epd1in54Panel] and [Epd2in9Panel] describe the 1.54" and the 2.9" b/w display this way,
+//! their generic driver sends the same as [epd1in54](crate::epd1in54) and
+//! [epd2in9](crate::epd2in9).
+//!
+//! ```rust, no_run
+//! # use embedded_hal_mock::*;
+//! # fn main() -> Result<(), epd_waveshare::Error<MockError>> {
+//! use epd_waveshare::{color::Polarity, generic::*, prelude::*};
+//!
+//! // a made up 200x200 SSD16xx panel without loadable LUTs
+//! struct MyPanel;
+//! impl Panel for MyPanel {
+//!     const DESCRIPTION: PanelDescription = PanelDescription {
+//!         width: 200,
+//!         height: 200,
+//!         controller: Controller::Ssd16xx,
+//!         polarity: Polarity::ClearedIsColor,
+//!         is_busy_low: false,
+//!         reset_ms: 10,
+//!         init: &[
+//!             Step::WaitUntilIdle,
+//!             Step::Command(0x12, &[]),
+//!             Step::WaitUntilIdle,
+//!             Step::Command(0x01, &[0xC7, 0x00, 0x00]),
+//!             Step::Command(0x11, &[0x03]),
+//!         ],
+//!         luts: None,
+//!         refresh: &[
+//!             Step::WaitUntilIdle,
+//!             Step::Command(0x22, &[0xF7]),
+//!             Step::Command(0x20, &[]),
+//!         ],
+//!         sleep: &[Step::WaitUntilIdle, Step::Command(0x10, &[0x01])],
+//!     };
+//! }
+//!
+//! # let expectations = [];
+//! # let mut spi = spi::Mock::new(&expectations);
+//! # let expectations = [];
+//! # let cs_pin = pin::Mock::new(&expectations);
+//! # let busy_in = pin::Mock::new(&expectations);
+//! # let dc = pin::Mock::new(&expectations);
+//! # let rst = pin::Mock::new(&expectations);
+//! # let mut delay = delay::MockNoop::new();
+//! let mut epd = GenericEpd::<MyPanel, _, _, _, _, _, _>::new(cs_pin, busy_in, dc, rst);
+//! epd.init(&mut spi, &mut delay)?;
+//! epd.clear_frame(&mut spi, &mut delay)?;
+//! epd.display_frame(&mut spi, &mut delay)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::color::{buffer_size, Color, Polarity};
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, DisplayInterface, PowerPin, ProgressHook,
+    RefreshStats,
+};
+use crate::pattern;
+use crate::traits::{self, DisplayState, RefreshLut, WaveshareDisplay};
+use crate::type_a;
+use core::marker::PhantomData;
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::*,
+};
+
+/// One step of a command sequence of a [PanelDescription]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Sends the command with its data, which may be empty
+    Command(u8, &'static [u8]),
+    /// Waits for the given milliseconds
+    Delay(u8),
+    /// Waits until the controller isn't busy anymore
+    WaitUntilIdle,
+    /// Sends the LUT of the current refresh, nothing without [LUTs](PanelDescription::luts)
+    Lut,
+}
+
+/// The controller family, which decides how the frame is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controller {
+    /// SSD16xx and IL38xx: the frame goes into the RAM of write RAM (0x24) through the RAM
+    /// window (0x44, 0x45) and address counters (0x4E, 0x4F), which allows partial updates
+    Ssd16xx,
+    /// UC81xx and IL03xx in KW mode: the frame goes into the new data of data start
+    /// transmission 2 (0x13), always as a whole
+    Uc81xx,
+}
+
+/// The sequences which load the LUTs of each [RefreshLut]
+///
+/// [set_lut](WaveshareDisplay::set_lut) has no delay, so [Step::Delay] and [Step::Lut] are
+/// skipped in them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Luts {
+    /// For [RefreshLut::Full]
+    pub full: &'static [Step],
+    /// For [RefreshLut::Quick]
+    pub quick: &'static [Step],
+}
+
+/// Everything [GenericEpd] needs to know about a panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PanelDescription {
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// How the frame is written
+    pub controller: Controller,
+    /// How the controller takes the bits of the buffers
+    pub polarity: Polarity,
+    /// Whether BUSY is low while the controller is working
+    pub is_busy_low: bool,
+    /// Length of the hardware reset pulse in milliseconds
+    pub reset_ms: u8,
+    /// Sent by [init](WaveshareDisplay::init) and [wake_up](WaveshareDisplay::wake_up)
+    /// after the hardware reset
+    pub init: &'static [Step],
+    /// The loadable LUTs, `None` if the controller only uses the ones of its OTP
+    pub luts: Option<Luts>,
+    /// Starts the refresh, [display_frame](WaveshareDisplay::display_frame) waits for it
+    /// to finish afterwards
+    pub refresh: &'static [Step],
+    /// Puts the controller to sleep
+    pub sleep: &'static [Step],
+}
+
+/// A panel for [GenericEpd], see the [module documentation](self)
+pub trait Panel {
+    /// The description of the panel
+    const DESCRIPTION: PanelDescription;
+}
+
+// A command of a description
+#[derive(Clone, Copy)]
+struct Raw(u8);
+
+impl traits::Command for Raw {
+    fn address(self) -> u8 {
+        self.0
+    }
+}
+
+// The SSD16xx commands of the RAM, see Controller::Ssd16xx
+const WRITE_RAM: Raw = Raw(0x24);
+const RAM_X_RANGE: Raw = Raw(0x44);
+const RAM_Y_RANGE: Raw = Raw(0x45);
+const RAM_X_COUNTER: Raw = Raw(0x4E);
+const RAM_Y_COUNTER: Raw = Raw(0x4F);
+// The UC81xx command of the new data, see Controller::Uc81xx
+const DATA_START_TRANSMISSION_2: Raw = Raw(0x13);
+
+/// Driver for the panel `P`, whose [description](Panel::DESCRIPTION) says what it sends
+pub struct GenericEpd<P, SPI, CS, BUSY, DC, RST, DELAY> {
+    interface: DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>,
+    background_color: Color,
+    refresh: RefreshLut,
+    _panel: PhantomData<P>,
+}
+
+impl<P, SPI, CS, BUSY, DC, RST, DELAY> GenericEpd<P, SPI, CS, BUSY, DC, RST, DELAY>
+where
+    P: Panel,
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    const WIDTH: u32 = P::DESCRIPTION.width;
+    const HEIGHT: u32 = P::DESCRIPTION.height;
+    const BUFFER_LEN: usize = buffer_size::<Color>(Self::WIDTH as usize, Self::HEIGHT as usize);
+
+    // Sends the steps of an init, refresh or sleep
+    fn run(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        steps: &[Step],
+    ) -> Result<(), Error<SPI::Error>> {
+        for &step in steps {
+            match step {
+                Step::Delay(ms) => delay.delay_ms(ms),
+                Step::Lut => self.send_lut(spi)?,
+                step => self.send(spi, step)?,
+            }
+        }
+        Ok(())
+    }
+
+    // Sends the LUT of the current refresh, if there are LUTs
+    fn send_lut(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
+        let luts = match P::DESCRIPTION.luts {
+            Some(luts) => luts,
+            None => return Ok(()),
+        };
+        let steps = match self.refresh {
+            RefreshLut::Full => luts.full,
+            RefreshLut::Quick => luts.quick,
+        };
+        for &step in steps {
+            self.send(spi, step)?;
+        }
+        Ok(())
+    }
+
+    // Sends a command or waits for the controller, the other steps need run
+    fn send(&mut self, spi: &mut SPI, step: Step) -> Result<(), Error<SPI::Error>> {
+        match step {
+            Step::Command(command, &[]) => self.interface.cmd(spi, Raw(command)),
+            Step::Command(command, data) => self.interface.cmd_with_data(spi, Raw(command), data),
+            Step::WaitUntilIdle => self.wait_until_idle(),
+            Step::Delay(_) | Step::Lut => Ok(()),
+        }
+    }
+
+    fn wait_until_idle(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(P::DESCRIPTION.is_busy_low)
+    }
+
+    fn background(&self) -> u8 {
+        self.interface.background_byte(
+            self.background_color
+                .to_controller_byte(P::DESCRIPTION.polarity),
+        )
+    }
+
+    // Waits for the controller and starts writing the frame data of the window
+    fn begin_frame(
+        &mut self,
+        spi: &mut SPI,
+        (x, y, width, height): (u32, u32, u32, u32),
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle()?;
+        match P::DESCRIPTION.controller {
+            Controller::Ssd16xx => {
+                let (end_x, end_y) = (x + width - 1, y + height - 1);
+                // x is positioned in bytes
+                self.interface.cmd_with_data(
+                    spi,
+                    RAM_X_RANGE,
+                    &[(x >> 3) as u8, (end_x >> 3) as u8],
+                )?;
+                self.interface.cmd_with_data(
+                    spi,
+                    RAM_Y_RANGE,
+                    &[y as u8, (y >> 8) as u8, end_y as u8, (end_y >> 8) as u8],
+                )?;
+                self.wait_until_idle()?;
+                self.interface
+                    .cmd_with_data(spi, RAM_X_COUNTER, &[(x >> 3) as u8])?;
+                self.interface
+                    .cmd_with_data(spi, RAM_Y_COUNTER, &[y as u8, (y >> 8) as u8])?;
+                self.interface.cmd(spi, WRITE_RAM)
+            }
+            Controller::Uc81xx => self.interface.cmd(spi, DATA_START_TRANSMISSION_2),
+        }
+    }
+
+    // Sends the window of update_partial_frame, `buffer` padded to it with the background
+    fn write_window(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        (x, y, width, height): (u32, u32, u32, u32),
+    ) -> Result<(), Error<SPI::Error>> {
+        if !Self::SUPPORTS_PARTIAL {
+            return Err(Error::Unsupported);
+        }
+        let len = (width.div_ceil(8) * height) as usize;
+        self.interface
+            .check_window((x, y, width, height), Self::WIDTH, Self::HEIGHT)?;
+        self.begin_frame(spi, (x, y, width, height))?;
+        let background = self.background();
+        self.interface
+            .padded_frame_data(spi, buffer, len, background, P::DESCRIPTION.polarity)?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    fn start_refresh(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.run(spi, delay, P::DESCRIPTION.refresh)?;
+        self.interface.set_state(DisplayState::Refreshing);
+        Ok(())
+    }
+}
+
+impl<P, SPI, CS, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
+    for GenericEpd<P, SPI, CS, BUSY, DC, RST, DELAY>
+where
+    P: Panel,
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    type DisplayColor = Color;
+    const SUPPORTS_PARTIAL: bool = matches!(P::DESCRIPTION.controller, Controller::Ssd16xx);
+    const SUPPORTS_QUICK_REFRESH: bool = false;
+    const SUPPORTS_LUT: bool = P::DESCRIPTION.luts.is_some();
+    const SUPPORTS_GRAYSCALE: bool = false;
+    const COLOR_PLANES: u8 = 1;
+    const POLARITY: Polarity = P::DESCRIPTION.polarity;
+    const IS_BUSY_LOW: bool = P::DESCRIPTION.is_busy_low;
+
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
+        GenericEpd {
+            interface: DisplayInterface::new(cs, busy, dc, rst),
+            background_color: Color::White,
+            refresh: RefreshLut::Full,
+            _panel: PhantomData,
+        }
+    }
+
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.reset(delay, P::DESCRIPTION.reset_ms)?;
+        self.run(spi, delay, P::DESCRIPTION.init)
+    }
+
+    fn assume_init(&mut self) {
+        self.interface.set_state(DisplayState::Idle);
+    }
+
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.run(spi, delay, P::DESCRIPTION.sleep)?;
+        self.interface.asleep()
+    }
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.init(spi, delay)
+    }
+
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.interface.set_power_pin(pwr);
+    }
+
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.interface.set_data_delay(data_delay);
+    }
+
+    fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        self.interface.set_cs_release(chunk_size);
+    }
+
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.interface.set_busy_wait(busy_wait);
+    }
+
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.interface.set_busy_debounce(busy_debounce);
+    }
+
+    fn set_busy_check(&mut self, window_ms: Option<u16>) {
+        self.interface.set_busy_check(window_ms, Self::IS_BUSY_LOW);
+    }
+
+    fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.interface.set_progress(progress);
+    }
+
+    fn set_clock(&mut self, clock: Option<Clock>) {
+        self.interface.set_clock(clock);
+    }
+
+    fn last_refresh(&self) -> Option<RefreshStats> {
+        self.interface.last_refresh()
+    }
+
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.set_reset_active_high(active_high)
+    }
+
+    fn set_background_color(&mut self, color: Color) {
+        self.background_color = color;
+    }
+
+    fn background_color(&self) -> &Color {
+        &self.background_color
+    }
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.interface.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.interface.inverted()
+    }
+
+    fn width(&self) -> u32 {
+        Self::WIDTH
+    }
+
+    fn height(&self) -> u32 {
+        Self::HEIGHT
+    }
+
+    fn update_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_len(buffer, Self::BUFFER_LEN)?;
+        self.begin_frame(spi, (0, 0, Self::WIDTH, Self::HEIGHT))?;
+        let background = self.background();
+        self.interface.padded_frame_data(
+            spi,
+            buffer,
+            Self::BUFFER_LEN,
+            background,
+            P::DESCRIPTION.polarity,
+        )?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    /// Only on [Controller::Ssd16xx], x and width are rounded to whole bytes
+    fn update_partial_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .check_len(buffer, (width.div_ceil(8) * height) as usize)?;
+        self.write_window(spi, buffer, (x, y, width, height))
+    }
+
+    fn clear_area(
+        &mut self,
+        spi: &mut SPI,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.write_window(spi, &[], (x, y, width, height))
+    }
+
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.check_frame_pending()?;
+        self.start_refresh(spi, delay)?;
+        self.wait_until_idle()
+    }
+
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>> {
+        if self.interface.state() != DisplayState::Refreshing {
+            self.interface.check_frame_pending()?;
+            self.start_refresh(spi, delay)?;
+            return Err(nb::Error::WouldBlock);
+        }
+        self.interface.poll_refresh(P::DESCRIPTION.is_busy_low)
+    }
+
+    fn update_and_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.update_frame(spi, buffer, delay)?;
+        self.display_frame(spi, delay)
+    }
+
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.begin_frame(spi, (0, 0, Self::WIDTH, Self::HEIGHT))?;
+        let background = self.background();
+        self.interface
+            .data_x_times(spi, background, Self::BUFFER_LEN as u32)?;
+        self.interface.set_state(DisplayState::FramePending);
+        Ok(())
+    }
+
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.begin_frame(spi, (0, 0, Self::WIDTH, Self::HEIGHT))?;
+        let checkerboard = pattern::bytes(Self::WIDTH, Self::HEIGHT, 0, pattern::checkerboard);
+        self.interface.generated_frame_data(
+            spi,
+            Self::BUFFER_LEN,
+            checkerboard,
+            P::DESCRIPTION.polarity,
+        )?;
+        self.interface.set_state(DisplayState::FramePending);
+        self.display_frame(spi, delay)
+    }
+
+    /// Sends the LUT of the [description](PanelDescription::luts), [Error::Unsupported] for
+    /// a refresh without them
+    fn set_lut(
+        &mut self,
+        spi: &mut SPI,
+        refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>> {
+        if P::DESCRIPTION.luts.is_none() && refresh_rate.is_some() {
+            return Err(Error::Unsupported);
+        }
+        if let Some(refresh) = refresh_rate {
+            self.refresh = refresh;
+        }
+        self.send_lut(spi)
+    }
+
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(P::DESCRIPTION.is_busy_low)
+    }
+
+    fn state(&self) -> DisplayState {
+        self.interface.state()
+    }
+}
+
+// The LUT steps of the type A displays, like their set_lut_helper
+const TYPE_A_LUTS: Luts = Luts {
+    full: &[
+        Step::WaitUntilIdle,
+        Step::Command(
+            type_a::command::Command::WriteLutRegister as u8,
+            &type_a::constants::LUT_FULL_UPDATE,
+        ),
+    ],
+    quick: &[
+        Step::WaitUntilIdle,
+        Step::Command(
+            type_a::command::Command::WriteLutRegister as u8,
+            &type_a::constants::LUT_PARTIAL_UPDATE,
+        ),
+    ],
+};
+
+// The refresh of the type A displays, the NOP keeps the master activation from being
+// interrupted
+const TYPE_A_REFRESH: &[Step] = &[
+    Step::WaitUntilIdle,
+    Step::Command(
+        type_a::command::Command::DisplayUpdateControl2 as u8,
+        &[0xC4],
+    ),
+    Step::Command(type_a::command::Command::MasterActivation as u8, &[]),
+    Step::Command(type_a::command::Command::Nop as u8, &[]),
+];
+
+const TYPE_A_SLEEP: &[Step] = &[
+    Step::WaitUntilIdle,
+    Step::Command(type_a::command::Command::DeepSleepMode as u8, &[0x00]),
+];
+
+/// The 1.54" b/w display of [epd1in54](crate::epd1in54)
+pub struct Epd1in54Panel;
+
+impl Panel for Epd1in54Panel {
+    const DESCRIPTION: PanelDescription = PanelDescription {
+        width: crate::epd1in54::WIDTH,
+        height: crate::epd1in54::HEIGHT,
+        controller: Controller::Ssd16xx,
+        polarity: Polarity::ClearedIsColor,
+        is_busy_low: false,
+        reset_ms: 10,
+        init: &[
+            Step::WaitUntilIdle,
+            Step::Command(
+                type_a::command::Command::DriverOutputControl as u8,
+                &[
+                    crate::epd1in54::HEIGHT as u8,
+                    (crate::epd1in54::HEIGHT >> 8) as u8,
+                    0x00,
+                ],
+            ),
+            Step::Command(
+                type_a::command::Command::BoosterSoftStartControl as u8,
+                &[0xD7, 0xD6, 0x9D],
+            ),
+            Step::Command(type_a::command::Command::WriteVcomRegister as u8, &[0xA8]),
+            Step::Command(type_a::command::Command::SetDummyLinePeriod as u8, &[0x1A]),
+            Step::Command(type_a::command::Command::SetGateLineWidth as u8, &[0x08]),
+            Step::Command(
+                type_a::command::Command::DataEntryModeSetting as u8,
+                &[0x03],
+            ),
+            Step::Lut,
+            Step::WaitUntilIdle,
+        ],
+        luts: Some(TYPE_A_LUTS),
+        refresh: TYPE_A_REFRESH,
+        sleep: TYPE_A_SLEEP,
+    };
+}
+
+/// The 2.9" b/w display of [epd2in9](crate::epd2in9)
+pub struct Epd2in9Panel;
+
+impl Panel for Epd2in9Panel {
+    const DESCRIPTION: PanelDescription = PanelDescription {
+        width: crate::epd2in9::WIDTH,
+        height: crate::epd2in9::HEIGHT,
+        controller: Controller::Ssd16xx,
+        polarity: Polarity::ClearedIsColor,
+        is_busy_low: false,
+        reset_ms: 10,
+        init: &[
+            Step::WaitUntilIdle,
+            Step::Command(
+                type_a::command::Command::DriverOutputControl as u8,
+                &[0x27, 0x01, 0x00],
+            ),
+            Step::Command(
+                type_a::command::Command::BoosterSoftStartControl as u8,
+                &[0xD7, 0xD6, 0x9D],
+            ),
+            Step::Command(type_a::command::Command::WriteVcomRegister as u8, &[0xA8]),
+            Step::Command(type_a::command::Command::SetDummyLinePeriod as u8, &[0x1A]),
+            Step::Command(type_a::command::Command::SetGateLineWidth as u8, &[0x08]),
+            Step::Command(
+                type_a::command::Command::DataEntryModeSetting as u8,
+                &[0x03],
+            ),
+            Step::Lut,
+        ],
+        luts: Some(TYPE_A_LUTS),
+        refresh: TYPE_A_REFRESH,
+        sleep: TYPE_A_SLEEP,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epd1in54::Epd1in54;
+    use crate::epd2in9::Epd2in9;
+    use crate::mock::{init_epd, BusyPin, DcPin, Delay, Log, Pin, Spi};
+    extern crate std;
+    use std::string::String;
+    use std::vec;
+    use std::vec::Vec;
+
+    // Everything a driver sends for init, frames, windows, LUTs, a refresh and a sleep
+    fn transcript<EPD>() -> Vec<String>
+    where
+        EPD: WaveshareDisplay<Spi, Pin, BusyPin, DcPin, Pin, Delay, DisplayColor = Color>,
+    {
+        let (log, mut spi, mut delay, mut epd) = init_epd::<EPD>();
+        let buffer = vec![0x0f; buffer_size::<Color>(epd.width() as usize, epd.height() as usize)];
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        epd.update_partial_frame(&mut spi, &[0x00; 6], 8, 10, 24, 2)
+            .unwrap();
+        epd.clear_area(&mut spi, 16, 0, 8, 8).unwrap();
+        epd.set_lut(&mut spi, Some(RefreshLut::Quick)).unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        epd.set_background_color(Color::Black);
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+        log.transcript()
+    }
+
+    #[test]
+    fn same_as_the_drivers() {
+        assert_eq!(
+            transcript::<GenericEpd<Epd1in54Panel, _, _, _, _, _, _>>(),
+            transcript::<Epd1in54<_, _, _, _, _, _>>()
+        );
+        assert_eq!(
+            transcript::<GenericEpd<Epd2in9Panel, _, _, _, _, _, _>>(),
+            transcript::<Epd2in9<_, _, _, _, _, _>>()
+        );
+    }
+
+    struct Uc81xxPanel;
+    impl Panel for Uc81xxPanel {
+        const DESCRIPTION: PanelDescription = PanelDescription {
+            width: 16,
+            height: 2,
+            controller: Controller::Uc81xx,
+            polarity: Polarity::SetIsColor,
+            is_busy_low: true,
+            reset_ms: 2,
+            init: &[
+                Step::Command(0x01, &[0x07, 0x07]),
+                Step::Command(0x04, &[]),
+                Step::Delay(100),
+                Step::WaitUntilIdle,
+                Step::Lut,
+            ],
+            luts: None,
+            refresh: &[Step::Command(0x12, &[])],
+            sleep: &[Step::Command(0x02, &[]), Step::Command(0x07, &[0xA5])],
+        };
+    }
+
+    #[test]
+    fn uc81xx_frames() {
+        type Epd = GenericEpd<Uc81xxPanel, Spi, Pin, BusyPin, DcPin, Pin, Delay>;
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd>();
+        assert_eq!(log.transcript(), ["01: 07 07", "04"]);
+        const { assert!(!Epd::SUPPORTS_PARTIAL && !Epd::SUPPORTS_LUT && Epd::IS_BUSY_LOW) };
+
+        // the frame is flipped for the set bits of the controller
+        log.clear();
+        epd.update_frame(&mut spi, &[0x0f, 0xff, 0x00, 0x00], &mut delay)
+            .unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        assert_eq!(log.transcript(), ["13: F0 00 FF FF", "12", "02", "07: A5"]);
+
+        assert_eq!(
+            epd.update_partial_frame(&mut spi, &[0x00], 0, 0, 8, 1),
+            Err(Error::Unsupported)
+        );
+        assert_eq!(
+            epd.set_lut(&mut spi, Some(RefreshLut::Full)),
+            Err(Error::Unsupported)
+        );
+    }
+
+    #[test]
+    fn delays_and_waits_are_sent() {
+        let log = Log::default();
+        let (mut spi, mut delay) = (log.spi(), log.delay());
+        let mut epd: GenericEpd<Uc81xxPanel, _, _, _, _, _, _> =
+            GenericEpd::new(Pin, log.busy(), log.dc(), Pin);
+        log.script_busy(&[true]);
+        epd.init(&mut spi, &mut delay).unwrap();
+        assert_eq!(
+            log.transcript(),
+            [
+                // the reset
+                "delay 10ms",
+                "delay 2ms",
+                "delay 200ms",
+                "01: 07 07",
+                "04",
+                "delay 100ms",
+                "wait until high"
+            ]
+        );
+    }
+}
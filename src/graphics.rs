@@ -1,14 +1,78 @@
 //! Graphics Support for EPDs
+//!
+//! # Buffer layout
+//!
+//! All buffers store the unrotated display row by row from the top left corner,
+//! every row starts at a new byte. Rotation and mirroring only change where drawn
+//! pixels end up, not the layout.
+//!
+//! - b/w ([Display]): 1 bit per pixel, the leftmost pixel is the most significant bit.
+//!   A set bit is white, a cleared bit black. A row takes `(width + 7) / 8` bytes.
+//! - tri-color ([TriDisplay]): the b/w plane followed by the chromatic plane, both like
+//!   b/w. A cleared bit in the chromatic plane is chromatic, the b/w bit of a chromatic
+//!   pixel is set (white). [split_planes](crate::color::split_planes) and
+//!   [TriColor::split_byte] take such buffers apart.
+//! - 7 color ([OctDisplay]): 4 bits per pixel with the [OctColor] nibble, the left
+//!   pixel is the upper nibble. A row takes `(width + 1) / 2` bytes.
+//! - 4 level grayscale ([GrayDisplay]): 2 bits per pixel with the luma of [Gray2],
+//!   the leftmost pixel in the most significant bits. A row takes `(width + 3) / 4` bytes.
+//!   [gray](crate::gray) splits it into the two planes the controllers need.
+//!
+//! # Drawing outside of the display
+//!
+//! Pixels outside of the display are ignored in every rotation and mirroring, so shapes
+//! and text partly outside are clipped at the edges. Drawing never panics or fails
+//! because of a coordinate.
 
 use crate::buffer_len;
-use crate::color::{Color, OctColor, TriColor};
-use embedded_graphics::pixelcolor::BinaryColor;
+use crate::color::{bits_per_plane, buffer_size, plane_size, Color, ColorType, OctColor, TriColor};
+use crate::frame::FrameDataError;
+use crate::layout;
+use core::marker::PhantomData;
+use embedded_graphics_core::image::ImageDrawable;
+use embedded_graphics_core::pixelcolor::{BinaryColor, Gray2, Rgb888};
 use embedded_graphics_core::prelude::*;
+use embedded_graphics_core::primitives::Rectangle;
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec};
+
+// The buffer, rotation and mirroring methods of the display traits for the buffers with
+// `rotation` and `mirroring` fields, `$buffer` is the field holding the bytes
+macro_rules! impl_buffer_accessors {
+    ($($buffer:tt)+) => {
+        fn buffer(&self) -> &[u8] {
+            &self.$($buffer)+[..]
+        }
+
+        fn get_mut_buffer(&mut self) -> &mut [u8] {
+            &mut self.$($buffer)+[..]
+        }
+
+        fn set_rotation(&mut self, rotation: $crate::graphics::DisplayRotation) {
+            self.rotation = rotation;
+        }
+
+        fn rotation(&self) -> $crate::graphics::DisplayRotation {
+            self.rotation
+        }
+
+        fn set_mirroring(&mut self, mirroring: $crate::graphics::DisplayMirroring) {
+            self.mirroring = mirroring;
+        }
+
+        fn mirroring(&self) -> $crate::graphics::DisplayMirroring {
+            self.mirroring
+        }
+    };
+}
+pub(crate) use impl_buffer_accessors;
 
 /// Displayrotation
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
 pub enum DisplayRotation {
     /// No rotation
+    #[default]
     Rotate0,
     /// Rotate by 90 degrees clockwise
     Rotate90,
@@ -18,9 +82,28 @@ pub enum DisplayRotation {
     Rotate270,
 }
 
-impl Default for DisplayRotation {
-    fn default() -> Self {
-        DisplayRotation::Rotate0
+/// Mirroring of the display, applied to the picture as seen through the rotation
+///
+/// Together with [DisplayRotation] every one of the 8 orientations can be reached,
+/// mirroring along both axes is the same as [DisplayRotation::Rotate180].
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum DisplayMirroring {
+    /// No mirroring
+    #[default]
+    None,
+    /// Mirror horizontally, left becomes right
+    MirrorX,
+    /// Mirror vertically, top becomes bottom
+    MirrorY,
+}
+
+impl DisplayRotation {
+    /// Size of a `width` x `height` display as seen through this rotation
+    ///
+    /// Width and height are swapped for 90 and 270 degrees.
+    pub fn rotated_size(self, width: u32, height: u32) -> Size {
+        let (width, height) = layout::rotated_size(width, height, self);
+        Size::new(width, height)
     }
 }
 
@@ -32,10 +115,15 @@ impl Default for DisplayRotation {
 /// - Clearing
 pub trait Display: DrawTarget<Color = BinaryColor> {
     /// Clears the buffer of the display with the chosen background color
+    ///
+    /// The color is flipped as well when the buffer is [inverted](Self::set_inverted).
     fn clear_buffer(&mut self, background_color: Color) {
-        for elem in self.get_mut_buffer().iter_mut() {
-            *elem = background_color.get_byte_value();
-        }
+        let color = if self.inverted() {
+            background_color.inverse()
+        } else {
+            background_color
+        };
+        self.get_mut_buffer().fill(color.get_byte_value());
     }
 
     /// Returns the buffer
@@ -44,12 +132,111 @@ pub trait Display: DrawTarget<Color = BinaryColor> {
     /// Returns a mutable buffer
     fn get_mut_buffer(&mut self) -> &mut [u8];
 
+    /// Returns a mutable buffer, e.g. to write a frame directly into it
+    ///
+    /// Same as [get_mut_buffer](Self::get_mut_buffer), see the
+    /// [buffer layout](crate::graphics#buffer-layout).
+    fn buffer_mut(&mut self) -> &mut [u8] {
+        self.get_mut_buffer()
+    }
+
+    /// The rows of the buffer in the order they are sent to the display
+    ///
+    /// The buffer always keeps the unrotated layout, so this is the same for every rotation
+    /// and mirroring. Useful to send the buffer in smaller pieces, e.g. with
+    /// [update_frame_iter](crate::traits::StreamFrame::update_frame_iter).
+    fn rows(&self) -> core::slice::Chunks<'_, u8> {
+        buffer_rows::<Self::Color>(self.buffer(), self.bounding_box().size, self.rotation())
+    }
+
     /// Sets the rotation of the display
     fn set_rotation(&mut self, rotation: DisplayRotation);
 
     /// Get the current rotation of the display
     fn rotation(&self) -> DisplayRotation;
 
+    /// Sets the mirroring of the display, it's applied on top of the rotation
+    fn set_mirroring(&mut self, mirroring: DisplayMirroring);
+
+    /// Get the current mirroring of the display
+    fn mirroring(&self) -> DisplayMirroring;
+
+    /// Swaps black and white when the colors are packed into the buffer
+    ///
+    /// Only affects what is drawn afterwards, the pixels already in the buffer are kept.
+    /// [get_pixel](Self::get_pixel) flips them back, so drawing code doesn't need to know.
+    fn set_inverted(&mut self, inverted: bool);
+
+    /// Whether black and white are swapped in the buffer
+    fn inverted(&self) -> bool;
+
+    /// Color of the pixel at `point`, `None` if it's outside of the display
+    ///
+    /// Uses the same coordinates as drawing, so rotation, mirroring and inversion are applied.
+    fn get_pixel(&self, point: Point) -> Option<BinaryColor> {
+        let size = self.bounding_box().size;
+        let color = pixel_at(
+            self.buffer(),
+            size,
+            self.rotation(),
+            self.mirroring(),
+            point,
+        )?;
+        Some(invert_binary(color, self.inverted()))
+    }
+
+    /// The buffer as an image for another [DrawTarget], see [Preview]
+    ///
+    /// Shows the pixels like the panel, so those drawn while [inverted](Self::set_inverted)
+    /// keep their swapped colors.
+    fn preview(&self) -> Preview<'_, BinaryColor> {
+        let size = self.bounding_box().size;
+        Preview::rotated(self.buffer(), size, self.rotation(), self.mirroring())
+    }
+
+    /// Draws the pixels of `area` of another buffer with the top left corner at `position`
+    ///
+    /// `area` is in the coordinates of `source`, so its rotation and mirroring apply there
+    /// just like the own ones apply to drawing. Pixels of the `transparent` color are
+    /// skipped, pixels outside of either buffer are left out.
+    fn blit<S: Display + ?Sized>(
+        &mut self,
+        source: &S,
+        area: &Rectangle,
+        position: Point,
+        transparent: Option<BinaryColor>,
+    ) -> Result<(), Self::Error> {
+        let area = area.intersection(&source.bounding_box());
+        self.draw_iter(blit_pixels(&area, position, transparent, |p| {
+            source.get_pixel(p)
+        }))
+    }
+
+    /// Copies `area` of `source` into the same area of this buffer
+    ///
+    /// With the same size, rotation and mirroring the rows are copied byte by byte and only
+    /// the bytes at the edges pixel by pixel, otherwise every pixel is drawn on its own.
+    /// The parts of `area` outside of the display are left out.
+    fn copy_rect(&mut self, source: &Self, area: &Rectangle) -> Result<(), Self::Error>
+    where
+        Self: Sized,
+    {
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        if (source.rotation(), source.mirroring()) != (rotation, mirroring)
+            || source.bounding_box() != self.bounding_box()
+            || source.inverted() != self.inverted()
+        {
+            let area = area.intersection(&source.bounding_box());
+            let pixels = blit_pixels(&area, area.top_left, None, |p| source.get_pixel(p));
+            return self.draw_iter(pixels);
+        }
+        let (width, height) = unrotated_size(self.bounding_box().size, rotation);
+        if let Some(rect) = find_rect(area, width, height, rotation, mirroring) {
+            copy_planes::<BinaryColor>(self.get_mut_buffer(), source.buffer(), width, height, rect);
+        }
+        Ok(())
+    }
+
     /// Helperfunction for the Embedded Graphics draw trait
     ///
     /// Becomes uneccesary when const_generics become stablised
@@ -59,29 +246,53 @@ pub trait Display: DrawTarget<Color = BinaryColor> {
         height: u32,
         pixel: Pixel<BinaryColor>,
     ) -> Result<(), Self::Error> {
-        let rotation = self.rotation();
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        let Pixel(point, color) = pixel;
+        let color = invert_binary(color, self.inverted());
         let buffer = self.get_mut_buffer();
+        write_pixel(buffer, width, height, rotation, mirroring, point, color);
+        Ok(())
+    }
 
-        let Pixel(point, color) = pixel;
-        if outside_display(point, width, height, rotation) {
-            return Ok(());
+    /// Helperfunction for `fill_solid` of the Embedded Graphics draw trait
+    ///
+    /// Sets whole bytes at once where possible
+    fn fill_solid_helper(
+        &mut self,
+        width: u32,
+        height: u32,
+        area: &Rectangle,
+        color: BinaryColor,
+    ) -> Result<(), Self::Error> {
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        if let Some(rect) = find_rect(area, width, height, rotation, mirroring) {
+            let color = invert_binary(color, self.inverted());
+            fill_planes(self.get_mut_buffer(), width, height, rect, color);
         }
+        Ok(())
+    }
 
-        // Give us index inside the buffer and the bit-position in that u8 which needs to be changed
-        let (index, bit) = find_position(point.x as u32, point.y as u32, width, height, rotation);
-        let index = index as usize;
-
-        // "Draw" the Pixel on that bit
-        match color {
-            // Black
-            BinaryColor::On => {
-                buffer[index] &= !bit;
-            }
-            // White
-            BinaryColor::Off => {
-                buffer[index] |= bit;
-            }
-        }
+    /// Helperfunction for `fill_contiguous` of the Embedded Graphics draw trait
+    ///
+    /// Packs the colors into whole bytes where the rows of the area are rows of the buffer,
+    /// draws pixel by pixel otherwise
+    fn fill_contiguous_helper<I>(
+        &mut self,
+        width: u32,
+        height: u32,
+        area: &Rectangle,
+        colors: I,
+    ) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = BinaryColor>,
+    {
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        let inverted = self.inverted();
+        let colors = colors
+            .into_iter()
+            .map(|color| invert_binary(color, inverted));
+        let buffer = self.get_mut_buffer();
+        fill_runs(buffer, (width, height), rotation, mirroring, area, colors);
         Ok(())
     }
 }
@@ -95,9 +306,11 @@ pub trait Display: DrawTarget<Color = BinaryColor> {
 pub trait TriDisplay: DrawTarget<Color = TriColor> {
     /// Clears the buffer of the display with the chosen background color
     fn clear_buffer(&mut self, background_color: TriColor) {
-        for elem in self.get_mut_buffer().iter_mut() {
-            *elem = background_color.get_byte_value();
-        }
+        let offset = self.chromatic_offset();
+        let (bw, chromatic) = self.get_mut_buffer().split_at_mut(offset);
+        // chromatic is white in the b/w buffer and black is white in the chromatic buffer
+        bw.fill(background_color.fill_byte(0));
+        chromatic.fill(background_color.fill_byte(1));
     }
 
     /// Returns the buffer
@@ -106,12 +319,35 @@ pub trait TriDisplay: DrawTarget<Color = TriColor> {
     /// Returns a mutable buffer
     fn get_mut_buffer(&mut self) -> &mut [u8];
 
+    /// Returns a mutable buffer, e.g. to write a frame directly into it
+    ///
+    /// Same as [get_mut_buffer](Self::get_mut_buffer), see the
+    /// [buffer layout](crate::graphics#buffer-layout).
+    fn buffer_mut(&mut self) -> &mut [u8] {
+        self.get_mut_buffer()
+    }
+
+    /// The rows of the b/w plane followed by the rows of the chromatic plane
+    ///
+    /// The buffer always keeps the unrotated layout, so this is the same for every rotation
+    /// and mirroring. Useful to send the buffer in smaller pieces, e.g. with
+    /// [update_frame_iter](crate::traits::StreamFrame::update_frame_iter).
+    fn rows(&self) -> core::slice::Chunks<'_, u8> {
+        buffer_rows::<Self::Color>(self.buffer(), self.bounding_box().size, self.rotation())
+    }
+
     /// Sets the rotation of the display
     fn set_rotation(&mut self, rotation: DisplayRotation);
 
     /// Get the current rotation of the display
     fn rotation(&self) -> DisplayRotation;
 
+    /// Sets the mirroring of the display, it's applied on top of the rotation
+    fn set_mirroring(&mut self, mirroring: DisplayMirroring);
+
+    /// Get the current mirroring of the display
+    fn mirroring(&self) -> DisplayMirroring;
+
     /// Get the offset into buffer where chromatic data starts
     fn chromatic_offset(&self) -> usize;
 
@@ -121,6 +357,50 @@ pub trait TriDisplay: DrawTarget<Color = TriColor> {
     /// return the chromatic part of the buffer
     fn chromatic_buffer(&self) -> &[u8];
 
+    /// Color of the pixel at `point`, `None` if it's outside of the display
+    ///
+    /// Uses the same coordinates as drawing, so rotation and mirroring are applied.
+    fn get_pixel(&self, point: Point) -> Option<TriColor> {
+        let size = self.bounding_box().size;
+        pixel_at(
+            self.buffer(),
+            size,
+            self.rotation(),
+            self.mirroring(),
+            point,
+        )
+    }
+
+    /// The buffer as an image for another [DrawTarget], see [Preview]
+    fn preview(&self) -> Preview<'_, TriColor> {
+        let size = self.bounding_box().size;
+        Preview::rotated(self.buffer(), size, self.rotation(), self.mirroring())
+    }
+
+    /// Copies `area` of `source` into the same area of this buffer
+    ///
+    /// With the same size, rotation and mirroring the rows are copied byte by byte and only
+    /// the bytes at the edges pixel by pixel, otherwise every pixel is drawn on its own.
+    /// The parts of `area` outside of the display are left out.
+    fn copy_rect(&mut self, source: &Self, area: &Rectangle) -> Result<(), Self::Error>
+    where
+        Self: Sized,
+    {
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        if (source.rotation(), source.mirroring()) != (rotation, mirroring)
+            || source.bounding_box() != self.bounding_box()
+        {
+            let area = area.intersection(&source.bounding_box());
+            let pixels = blit_pixels(&area, area.top_left, None, |p| source.get_pixel(p));
+            return self.draw_iter(pixels);
+        }
+        let (width, height) = unrotated_size(self.bounding_box().size, rotation);
+        if let Some(rect) = find_rect(area, width, height, rotation, mirroring) {
+            copy_planes::<TriColor>(self.get_mut_buffer(), source.buffer(), width, height, rect);
+        }
+        Ok(())
+    }
+
     /// Helperfunction for the Embedded Graphics draw trait
     ///
     /// Becomes uneccesary when const_generics become stablised
@@ -130,40 +410,27 @@ pub trait TriDisplay: DrawTarget<Color = TriColor> {
         height: u32,
         pixel: Pixel<TriColor>,
     ) -> Result<(), Self::Error> {
-        let rotation = self.rotation();
-
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
         let Pixel(point, color) = pixel;
-        if outside_display(point, width, height, rotation) {
-            return Ok(());
-        }
-
-        // Give us index inside the buffer and the bit-position in that u8 which needs to be changed
-        let (index, bit) = find_position(point.x as u32, point.y as u32, width, height, rotation);
-        let index = index as usize;
-        let offset = self.chromatic_offset();
-
+        // sets the bits in both planes, see TriColor::bits
         let buffer = self.get_mut_buffer();
+        write_pixel(buffer, width, height, rotation, mirroring, point, color);
+        Ok(())
+    }
 
-        // "Draw" the Pixel on that bit
-        match color {
-            TriColor::Black => {
-                // clear bit in bw-buffer -> black
-                buffer[index] &= !bit;
-                // set bit in chromatic-buffer -> white
-                buffer[index + offset] |= bit;
-            }
-            TriColor::White => {
-                // set bit in bw-buffer -> white
-                buffer[index] |= bit;
-                // set bit in chromatic-buffer -> white
-                buffer[index + offset] |= bit;
-            }
-            TriColor::Chromatic => {
-                // set bit in b/w buffer (white)
-                buffer[index] |= bit;
-                // clear bit in chromatic buffer -> chromatic
-                buffer[index + offset] &= !bit;
-            }
+    /// Helperfunction for `fill_solid` of the Embedded Graphics draw trait
+    ///
+    /// Sets whole bytes at once where possible
+    fn fill_solid_helper_tri(
+        &mut self,
+        width: u32,
+        height: u32,
+        area: &Rectangle,
+        color: TriColor,
+    ) -> Result<(), Self::Error> {
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        if let Some(rect) = find_rect(area, width, height, rotation, mirroring) {
+            fill_planes(self.get_mut_buffer(), width, height, rect, color);
         }
         Ok(())
     }
@@ -178,9 +445,7 @@ pub trait TriDisplay: DrawTarget<Color = TriColor> {
 pub trait OctDisplay: DrawTarget<Color = OctColor> {
     /// Clears the buffer of the display with the chosen background color
     fn clear_buffer(&mut self, background_color: OctColor) {
-        for elem in self.get_mut_buffer().iter_mut() {
-            *elem = OctColor::colors_byte(background_color, background_color);
-        }
+        self.get_mut_buffer().fill(background_color.fill_byte(0));
     }
 
     /// Returns the buffer
@@ -189,12 +454,98 @@ pub trait OctDisplay: DrawTarget<Color = OctColor> {
     /// Returns a mutable buffer
     fn get_mut_buffer(&mut self) -> &mut [u8];
 
+    /// Returns a mutable buffer, e.g. to write a frame directly into it
+    ///
+    /// Same as [get_mut_buffer](Self::get_mut_buffer), see the
+    /// [buffer layout](crate::graphics#buffer-layout).
+    fn buffer_mut(&mut self) -> &mut [u8] {
+        self.get_mut_buffer()
+    }
+
+    /// The rows of the buffer in the order they are sent to the display
+    ///
+    /// The buffer always keeps the unrotated layout, so this is the same for every rotation
+    /// and mirroring. Useful to send the buffer in smaller pieces, e.g. with
+    /// [update_frame_iter](crate::traits::StreamFrame::update_frame_iter).
+    fn rows(&self) -> core::slice::Chunks<'_, u8> {
+        buffer_rows::<Self::Color>(self.buffer(), self.bounding_box().size, self.rotation())
+    }
+
     /// Sets the rotation of the display
     fn set_rotation(&mut self, rotation: DisplayRotation);
 
     /// Get the current rotation of the display
     fn rotation(&self) -> DisplayRotation;
 
+    /// Sets the mirroring of the display, it's applied on top of the rotation
+    fn set_mirroring(&mut self, mirroring: DisplayMirroring);
+
+    /// Get the current mirroring of the display
+    fn mirroring(&self) -> DisplayMirroring;
+
+    /// Color of the pixel at `point`, `None` if it's outside of the display
+    ///
+    /// Uses the same coordinates as drawing, so rotation and mirroring are applied.
+    /// Nibbles which aren't a valid color also give `None`.
+    fn get_pixel(&self, point: Point) -> Option<OctColor> {
+        let size = self.bounding_box().size;
+        pixel_at(
+            self.buffer(),
+            size,
+            self.rotation(),
+            self.mirroring(),
+            point,
+        )
+    }
+
+    /// The buffer as an image for another [DrawTarget], see [Preview]
+    fn preview(&self) -> Preview<'_, OctColor> {
+        let size = self.bounding_box().size;
+        Preview::rotated(self.buffer(), size, self.rotation(), self.mirroring())
+    }
+
+    /// Draws the pixels of `area` of another buffer with the top left corner at `position`
+    ///
+    /// `area` is in the coordinates of `source`, so its rotation and mirroring apply there
+    /// just like the own ones apply to drawing. Pixels of the `transparent` color are
+    /// skipped, pixels outside of either buffer are left out.
+    fn blit<S: OctDisplay + ?Sized>(
+        &mut self,
+        source: &S,
+        area: &Rectangle,
+        position: Point,
+        transparent: Option<OctColor>,
+    ) -> Result<(), Self::Error> {
+        let area = area.intersection(&source.bounding_box());
+        self.draw_iter(blit_pixels(&area, position, transparent, |p| {
+            source.get_pixel(p)
+        }))
+    }
+
+    /// Copies `area` of `source` into the same area of this buffer
+    ///
+    /// With the same size, rotation and mirroring the rows are copied byte by byte and only
+    /// the bytes at the edges pixel by pixel, otherwise every pixel is drawn on its own.
+    /// The parts of `area` outside of the display are left out.
+    fn copy_rect(&mut self, source: &Self, area: &Rectangle) -> Result<(), Self::Error>
+    where
+        Self: Sized,
+    {
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        if (source.rotation(), source.mirroring()) != (rotation, mirroring)
+            || source.bounding_box() != self.bounding_box()
+        {
+            let area = area.intersection(&source.bounding_box());
+            let pixels = blit_pixels(&area, area.top_left, None, |p| source.get_pixel(p));
+            return self.draw_iter(pixels);
+        }
+        let (width, height) = unrotated_size(self.bounding_box().size, rotation);
+        if let Some(rect) = find_rect(area, width, height, rotation, mirroring) {
+            copy_planes::<OctColor>(self.get_mut_buffer(), source.buffer(), width, height, rect);
+        }
+        Ok(())
+    }
+
     /// Helperfunction for the Embedded Graphics draw trait
     ///
     /// Becomes uneccesary when const_generics become stablised
@@ -204,26 +555,170 @@ pub trait OctDisplay: DrawTarget<Color = OctColor> {
         height: u32,
         pixel: Pixel<OctColor>,
     ) -> Result<(), Self::Error> {
-        let rotation = self.rotation();
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        let Pixel(point, color) = pixel;
         let buffer = self.get_mut_buffer();
+        write_pixel(buffer, width, height, rotation, mirroring, point, color);
+        Ok(())
+    }
 
-        let Pixel(point, color) = pixel;
-        if outside_display(point, width, height, rotation) {
-            return Ok(());
+    /// Helperfunction for `fill_solid` of the Embedded Graphics draw trait
+    ///
+    /// Sets whole bytes at once where possible
+    fn fill_solid_helper(
+        &mut self,
+        width: u32,
+        height: u32,
+        area: &Rectangle,
+        color: OctColor,
+    ) -> Result<(), Self::Error> {
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        if let Some(rect) = find_rect(area, width, height, rotation, mirroring) {
+            fill_planes(self.get_mut_buffer(), width, height, rect, color);
         }
+        Ok(())
+    }
+
+    /// Helperfunction for `fill_contiguous` of the Embedded Graphics draw trait
+    ///
+    /// Packs the colors into whole bytes where the rows of the area are rows of the buffer,
+    /// draws pixel by pixel otherwise
+    fn fill_contiguous_helper<I>(
+        &mut self,
+        width: u32,
+        height: u32,
+        area: &Rectangle,
+        colors: I,
+    ) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = OctColor>,
+    {
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        let buffer = self.get_mut_buffer();
+        fill_runs(buffer, (width, height), rotation, mirroring, area, colors);
+        Ok(())
+    }
+}
+
+/// Necessary traits for all displays with 4 level grayscale to implement for drawing
+///
+/// Adds support for:
+/// - Drawing (With the help of DrawTarget/Embedded Graphics)
+/// - Rotations
+/// - Clearing
+pub trait GrayDisplay: DrawTarget<Color = Gray2> {
+    /// Clears the buffer of the display with the chosen background color
+    fn clear_buffer(&mut self, background_color: Gray2) {
+        self.get_mut_buffer().fill(background_color.fill_byte(0));
+    }
 
-        // Give us index inside the buffer and the bit-position in that u8 which needs to be changed
-        let (index, upper) =
-            find_oct_position(point.x as u32, point.y as u32, width, height, rotation);
-        let index = index as usize;
+    /// Returns the buffer
+    fn buffer(&self) -> &[u8];
 
-        // "Draw" the Pixel on that bit
-        let (mask, color_nibble) = if upper {
-            (0x0f, color.get_nibble() << 4)
-        } else {
-            (0xf0, color.get_nibble())
-        };
-        buffer[index] = (buffer[index] & mask) | color_nibble;
+    /// Returns a mutable buffer
+    fn get_mut_buffer(&mut self) -> &mut [u8];
+
+    /// Returns a mutable buffer, e.g. to write a frame directly into it
+    ///
+    /// Same as [get_mut_buffer](Self::get_mut_buffer), see the
+    /// [buffer layout](crate::graphics#buffer-layout).
+    fn buffer_mut(&mut self) -> &mut [u8] {
+        self.get_mut_buffer()
+    }
+
+    /// The rows of the buffer in the order they are sent to the display
+    ///
+    /// The buffer always keeps the unrotated layout, so this is the same for every rotation
+    /// and mirroring. Useful to send the buffer in smaller pieces, e.g. with
+    /// [update_frame_iter](crate::traits::StreamFrame::update_frame_iter).
+    fn rows(&self) -> core::slice::Chunks<'_, u8> {
+        buffer_rows::<Self::Color>(self.buffer(), self.bounding_box().size, self.rotation())
+    }
+
+    /// Sets the rotation of the display
+    fn set_rotation(&mut self, rotation: DisplayRotation);
+
+    /// Get the current rotation of the display
+    fn rotation(&self) -> DisplayRotation;
+
+    /// Sets the mirroring of the display, it's applied on top of the rotation
+    fn set_mirroring(&mut self, mirroring: DisplayMirroring);
+
+    /// Get the current mirroring of the display
+    fn mirroring(&self) -> DisplayMirroring;
+
+    /// Color of the pixel at `point`, `None` if it's outside of the display
+    ///
+    /// Uses the same coordinates as drawing, so rotation and mirroring are applied.
+    fn get_pixel(&self, point: Point) -> Option<Gray2> {
+        let size = self.bounding_box().size;
+        pixel_at(
+            self.buffer(),
+            size,
+            self.rotation(),
+            self.mirroring(),
+            point,
+        )
+    }
+
+    /// The buffer as an image for another [DrawTarget], see [Preview]
+    fn preview(&self) -> Preview<'_, Gray2> {
+        let size = self.bounding_box().size;
+        Preview::rotated(self.buffer(), size, self.rotation(), self.mirroring())
+    }
+
+    /// Copies `area` of `source` into the same area of this buffer
+    ///
+    /// With the same size, rotation and mirroring the rows are copied byte by byte and only
+    /// the bytes at the edges pixel by pixel, otherwise every pixel is drawn on its own.
+    /// The parts of `area` outside of the display are left out.
+    fn copy_rect(&mut self, source: &Self, area: &Rectangle) -> Result<(), Self::Error>
+    where
+        Self: Sized,
+    {
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        if (source.rotation(), source.mirroring()) != (rotation, mirroring)
+            || source.bounding_box() != self.bounding_box()
+        {
+            let area = area.intersection(&source.bounding_box());
+            let pixels = blit_pixels(&area, area.top_left, None, |p| source.get_pixel(p));
+            return self.draw_iter(pixels);
+        }
+        let (width, height) = unrotated_size(self.bounding_box().size, rotation);
+        if let Some(rect) = find_rect(area, width, height, rotation, mirroring) {
+            copy_planes::<Gray2>(self.get_mut_buffer(), source.buffer(), width, height, rect);
+        }
+        Ok(())
+    }
+
+    /// Helperfunction for the Embedded Graphics draw trait
+    fn draw_helper(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixel: Pixel<Gray2>,
+    ) -> Result<(), Self::Error> {
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        let Pixel(point, color) = pixel;
+        let buffer = self.get_mut_buffer();
+        write_pixel(buffer, width, height, rotation, mirroring, point, color);
+        Ok(())
+    }
+
+    /// Helperfunction for `fill_solid` of the Embedded Graphics draw trait
+    ///
+    /// Sets whole bytes at once where possible
+    fn fill_solid_helper(
+        &mut self,
+        width: u32,
+        height: u32,
+        area: &Rectangle,
+        color: Gray2,
+    ) -> Result<(), Self::Error> {
+        let (rotation, mirroring) = (self.rotation(), self.mirroring());
+        if let Some(rect) = find_rect(area, width, height, rotation, mirroring) {
+            fill_planes(self.get_mut_buffer(), width, height, rect, color);
+        }
         Ok(())
     }
 }
@@ -246,7 +741,7 @@ pub trait OctDisplay: DrawTarget<Color = OctColor> {
 /// let height = 296;
 ///
 /// let mut buffer = [DEFAULT_BACKGROUND_COLOR.get_byte_value(); 128 / 8 * 296];
-/// let mut display = VarDisplay::new(width, height, &mut buffer);
+/// let mut display = VarDisplay::new(width, height, &mut buffer).unwrap();
 ///
 /// display.set_rotation(DisplayRotation::Rotate90);
 ///
@@ -258,23 +753,44 @@ pub struct VarDisplay<'a> {
     width: u32,
     height: u32,
     rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    inverted: bool,
     buffer: &'a mut [u8], //buffer: Box<u8>//[u8; 15000]
 }
 
 impl<'a> VarDisplay<'a> {
     /// Create a new variable sized display.
     ///
-    /// Buffersize must be at least (width + 7) / 8 * height bytes.
-    pub fn new(width: u32, height: u32, buffer: &'a mut [u8]) -> VarDisplay<'a> {
-        let len = buffer.len() as u32;
-        assert!(buffer_len(width as usize, height as usize) >= len as usize);
+    /// Fails if the buffer isn't exactly (width + 7) / 8 * height bytes long.
+    pub fn new(
+        width: u32,
+        height: u32,
+        buffer: &'a mut [u8],
+    ) -> Result<VarDisplay<'a>, FrameDataError> {
+        check_buffer_len(buffer_len(width as usize, height as usize), buffer)?;
+        Ok(VarDisplay::new_unchecked(width, height, buffer))
+    }
+
+    /// Create a new variable sized display without checking the length of the buffer
+    ///
+    /// Drawing skips the pixels past the end of a shorter buffer, but `update_frame`
+    /// refuses to send it.
+    pub fn new_unchecked(width: u32, height: u32, buffer: &'a mut [u8]) -> VarDisplay<'a> {
         VarDisplay {
             width,
             height,
             rotation: DisplayRotation::default(),
+            mirroring: DisplayMirroring::default(),
+            inverted: false,
             buffer,
         }
     }
+
+    /// Returns the buffer with the drawing, e.g. a `&'static mut` buffer to hand it to the
+    /// DMA, see [dma](crate::dma)
+    pub fn into_buffer(self) -> &'a mut [u8] {
+        self.buffer
+    }
 }
 
 impl<'a> DrawTarget for VarDisplay<'a> {
@@ -290,108 +806,1213 @@ impl<'a> DrawTarget for VarDisplay<'a> {
         }
         Ok(())
     }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper(self.width, self.height, area, color)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.fill_contiguous_helper(self.width, self.height, area, colors)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color.into());
+        Ok(())
+    }
 }
 
 impl<'a> OriginDimensions for VarDisplay<'a> {
     fn size(&self) -> Size {
-        Size::new(self.width, self.height)
+        self.rotation.rotated_size(self.width, self.height)
     }
 }
 
 impl<'a> Display for VarDisplay<'a> {
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
-    }
+    impl_buffer_accessors!(buffer);
 
-    fn get_mut_buffer(&mut self) -> &mut [u8] {
-        &mut self.buffer
+    fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
     }
 
-    fn set_rotation(&mut self, rotation: DisplayRotation) {
-        self.rotation = rotation;
-    }
-
-    fn rotation(&self) -> DisplayRotation {
-        self.rotation
+    fn inverted(&self) -> bool {
+        self.inverted
     }
 }
 
-// Checks if a pos is outside the defined display
-fn outside_display(p: Point, width: u32, height: u32, rotation: DisplayRotation) -> bool {
-    if p.x < 0 || p.y < 0 {
-        return true;
-    }
-    let (x, y) = (p.x as u32, p.y as u32);
-    match rotation {
-        DisplayRotation::Rotate0 | DisplayRotation::Rotate180 => {
-            if x >= width || y >= height {
-                return true;
-            }
-        }
-        DisplayRotation::Rotate90 | DisplayRotation::Rotate270 => {
-            if y >= width || x >= height {
-                return true;
-            }
-        }
-    }
-    false
+/// A variable tri-color Display without a predefined buffer
+///
+/// Like [VarDisplay], but the buffer holds the b/w plane followed by the chromatic plane,
+/// so it needs to be exactly `2 * (width + 7) / 8 * height` bytes long.
+/// Together with a small buffer this can be used for `update_partial_frame`.
+pub struct VarTriDisplay<'a> {
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    buffer: &'a mut [u8],
 }
 
-fn find_rotation(x: u32, y: u32, width: u32, height: u32, rotation: DisplayRotation) -> (u32, u32) {
-    let nx;
-    let ny;
-    match rotation {
-        DisplayRotation::Rotate0 => {
-            nx = x;
-            ny = y;
-        }
-        DisplayRotation::Rotate90 => {
-            nx = width - 1 - y;
-            ny = x;
-        }
-        DisplayRotation::Rotate180 => {
-            nx = width - 1 - x;
-            ny = height - 1 - y;
-        }
-        DisplayRotation::Rotate270 => {
-            nx = y;
-            ny = height - 1 - x;
-        }
+impl<'a> VarTriDisplay<'a> {
+    /// Create a new variable sized tri-color display
+    ///
+    /// Fails if the buffer doesn't have exactly the length needed for width and height.
+    pub fn new(
+        width: u32,
+        height: u32,
+        buffer: &'a mut [u8],
+    ) -> Result<VarTriDisplay<'a>, FrameDataError> {
+        check_buffer_len(
+            buffer_size::<TriColor>(width as usize, height as usize),
+            buffer,
+        )?;
+        Ok(VarTriDisplay {
+            width,
+            height,
+            rotation: DisplayRotation::default(),
+            mirroring: DisplayMirroring::default(),
+            buffer,
+        })
+    }
+}
+
+impl<'a> DrawTarget for VarTriDisplay<'a> {
+    type Color = TriColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper_tri(self.width, self.height, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper_tri(self.width, self.height, area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color);
+        Ok(())
+    }
+}
+
+impl<'a> OriginDimensions for VarTriDisplay<'a> {
+    fn size(&self) -> Size {
+        self.rotation.rotated_size(self.width, self.height)
+    }
+}
+
+impl<'a> TriDisplay for VarTriDisplay<'a> {
+    impl_buffer_accessors!(buffer);
+
+    fn chromatic_offset(&self) -> usize {
+        self.buffer.len() / 2
+    }
+
+    fn bw_buffer(&self) -> &[u8] {
+        &self.buffer[0..self.chromatic_offset()]
+    }
+
+    fn chromatic_buffer(&self) -> &[u8] {
+        &self.buffer[self.chromatic_offset()..]
+    }
+}
+
+/// A variable 7 color Display without a predefined buffer
+///
+/// Like [VarDisplay], but with two pixels per byte,
+/// so the buffer needs to be exactly `(width + 1) / 2 * height` bytes long.
+/// Together with a small buffer this can be used for `update_partial_frame`.
+pub struct VarOctDisplay<'a> {
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    buffer: &'a mut [u8],
+}
+
+impl<'a> VarOctDisplay<'a> {
+    /// Create a new variable sized 7 color display
+    ///
+    /// Fails if the buffer doesn't have exactly the length needed for width and height.
+    pub fn new(
+        width: u32,
+        height: u32,
+        buffer: &'a mut [u8],
+    ) -> Result<VarOctDisplay<'a>, FrameDataError> {
+        check_buffer_len(
+            buffer_size::<OctColor>(width as usize, height as usize),
+            buffer,
+        )?;
+        Ok(VarOctDisplay {
+            width,
+            height,
+            rotation: DisplayRotation::default(),
+            mirroring: DisplayMirroring::default(),
+            buffer,
+        })
+    }
+}
+
+impl<'a> DrawTarget for VarOctDisplay<'a> {
+    type Color = OctColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper(self.width, self.height, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper(self.width, self.height, area, color)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.fill_contiguous_helper(self.width, self.height, area, colors)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color);
+        Ok(())
+    }
+}
+
+impl<'a> OriginDimensions for VarOctDisplay<'a> {
+    fn size(&self) -> Size {
+        self.rotation.rotated_size(self.width, self.height)
+    }
+}
+
+impl<'a> OctDisplay for VarOctDisplay<'a> {
+    impl_buffer_accessors!(buffer);
+}
+
+/// A variable 4 level grayscale Display without a predefined buffer
+///
+/// Like [VarDisplay], but with four pixels per byte,
+/// so the buffer needs to be exactly `(width + 3) / 4 * height` bytes long.
+///
+/// Example:
+/// ```rust
+/// # use epd_waveshare::graphics::{GrayDisplay, VarGrayDisplay};
+/// # use epd_waveshare::gray::{buffer_size, split_planes};
+/// # use embedded_graphics::{pixelcolor::Gray2, prelude::*, primitives::*};
+/// let mut buffer = [0xff; buffer_size(64, 32)];
+/// let mut display = VarGrayDisplay::new(64, 32, &mut buffer).unwrap();
+///
+/// let _ = Circle::new(Point::new(10, 2), 28)
+///     .into_styled(PrimitiveStyle::with_fill(Gray2::new(1)))
+///     .draw(&mut display);
+///
+/// // the planes for the controller
+/// let (mut high, mut low) = ([0; 64 / 8 * 32], [0; 64 / 8 * 32]);
+/// split_planes(display.buffer(), 64, &mut high, &mut low).unwrap();
+/// ```
+pub struct VarGrayDisplay<'a> {
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    buffer: &'a mut [u8],
+}
+
+impl<'a> VarGrayDisplay<'a> {
+    /// Create a new variable sized grayscale display
+    ///
+    /// Fails if the buffer doesn't have exactly the length needed for width and height.
+    pub fn new(
+        width: u32,
+        height: u32,
+        buffer: &'a mut [u8],
+    ) -> Result<VarGrayDisplay<'a>, FrameDataError> {
+        check_buffer_len(
+            buffer_size::<Gray2>(width as usize, height as usize),
+            buffer,
+        )?;
+        Ok(VarGrayDisplay {
+            width,
+            height,
+            rotation: DisplayRotation::default(),
+            mirroring: DisplayMirroring::default(),
+            buffer,
+        })
+    }
+}
+
+impl<'a> DrawTarget for VarGrayDisplay<'a> {
+    type Color = Gray2;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper(self.width, self.height, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper(self.width, self.height, area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color);
+        Ok(())
+    }
+}
+
+impl<'a> OriginDimensions for VarGrayDisplay<'a> {
+    fn size(&self) -> Size {
+        self.rotation.rotated_size(self.width, self.height)
+    }
+}
+
+impl<'a> GrayDisplay for VarGrayDisplay<'a> {
+    impl_buffer_accessors!(buffer);
+}
+
+/// A b/w Display for a window of the screen, to be sent with `update_partial_frame`
+///
+/// Drawing uses coordinates relative to the top left corner of the window.
+/// The position and size of the window are in unrotated display coordinates,
+/// rotation and mirroring only apply to the drawing inside of the window.
+///
+/// Example:
+/// ```rust,no_run
+/// # use epd_waveshare::graphics::PartialDisplay;
+/// # use epd_waveshare::prelude::*;
+/// # use epd_waveshare::color::Black;
+/// # use embedded_graphics::prelude::*;
+/// # use embedded_graphics::primitives::{Line, PrimitiveStyle};
+/// // 120x60 pixels starting at (80, 40)
+/// let mut buffer = [Color::White.get_byte_value(); 120 / 8 * 60];
+/// let mut window = PartialDisplay::new(80, 40, 120, 60, &mut buffer).unwrap();
+///
+/// let _ = Line::new(Point::new(0, 0), Point::new(119, 59))
+///         .into_styled(PrimitiveStyle::with_stroke(Black, 1))
+///         .draw(&mut window);
+///
+/// // epd.update_partial_frame(&mut spi, window.buffer(), window.x(), window.y(), window.width(), window.height())?;
+/// ```
+pub struct PartialDisplay<'a> {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    inverted: bool,
+    buffer: &'a mut [u8],
+}
+
+impl<'a> PartialDisplay<'a> {
+    /// Create a display for the window at (x, y) with the given size
+    ///
+    /// Fails if x isn't a multiple of 8 or the buffer isn't exactly
+    /// (width + 7) / 8 * height bytes long.
+    pub fn new(
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        buffer: &'a mut [u8],
+    ) -> Result<PartialDisplay<'a>, FrameDataError> {
+        if !x.is_multiple_of(8) {
+            return Err(FrameDataError::UnalignedWindow(x));
+        }
+        check_buffer_len(buffer_len(width as usize, height as usize), buffer)?;
+        Ok(PartialDisplay {
+            x,
+            y,
+            width,
+            height,
+            rotation: DisplayRotation::default(),
+            mirroring: DisplayMirroring::default(),
+            inverted: false,
+            buffer,
+        })
+    }
+
+    /// x coordinate of the window on the display
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+
+    /// y coordinate of the window on the display
+    pub fn y(&self) -> u32 {
+        self.y
+    }
+
+    /// Width of the window
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the window
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+impl<'a> DrawTarget for PartialDisplay<'a> {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper(self.width, self.height, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper(self.width, self.height, area, color)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.fill_contiguous_helper(self.width, self.height, area, colors)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color.into());
+        Ok(())
+    }
+}
+
+impl<'a> OriginDimensions for PartialDisplay<'a> {
+    fn size(&self) -> Size {
+        self.rotation.rotated_size(self.width, self.height)
+    }
+}
+
+impl<'a> Display for PartialDisplay<'a> {
+    impl_buffer_accessors!(buffer);
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    fn inverted(&self) -> bool {
+        self.inverted
+    }
+}
+
+fn check_buffer_len(expected: usize, buffer: &[u8]) -> Result<(), FrameDataError> {
+    if buffer.len() != expected {
+        return Err(FrameDataError::BufferLength {
+            expected,
+            actual: buffer.len(),
+        });
+    }
+    Ok(())
+}
+
+/// A variable Display with a heap allocated buffer
+///
+/// Behaves exactly like the fixed size `Display*` types of the different epds,
+/// but keeps its buffer in a `Box<[u8]>` instead of embedding it.
+/// Depending on the color type it implements [`Display`], [`TriDisplay`], [`OctDisplay`]
+/// or [`GrayDisplay`].
+///
+/// Example:
+/// ```rust,no_run
+/// # use epd_waveshare::epd5in65f::{HEIGHT, WIDTH};
+/// # use epd_waveshare::prelude::*;
+/// # use epd_waveshare::graphics::BoxDisplay;
+/// # use embedded_graphics::prelude::*;
+/// # use embedded_graphics::primitives::{Line, PrimitiveStyle};
+/// let mut display = BoxDisplay::<OctColor>::new(WIDTH, HEIGHT);
+///
+/// let _ = Line::new(Point::new(0, 120), Point::new(0, 295))
+///         .into_styled(PrimitiveStyle::with_stroke(OctColor::Red, 1))
+///         .draw(&mut display);
+/// ```
+#[cfg(feature = "alloc")]
+pub struct BoxDisplay<C> {
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    inverted: bool,
+    buffer: Box<[u8]>,
+    _color: PhantomData<C>,
+}
+
+#[cfg(feature = "alloc")]
+impl<C: ColorType> BoxDisplay<C> {
+    fn with_buffer(width: u32, height: u32, buffer: Box<[u8]>) -> Self {
+        BoxDisplay {
+            width,
+            height,
+            rotation: DisplayRotation::default(),
+            mirroring: DisplayMirroring::default(),
+            inverted: false,
+            buffer,
+            _color: PhantomData,
+        }
+    }
+
+    /// Create a new display with a white heap allocated buffer
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = buffer_size::<C>(width as usize, height as usize);
+        let buffer = vec![C::WHITE_BYTE; len].into_boxed_slice();
+        Self::with_buffer(width, height, buffer)
+    }
+
+    /// Create a new display from an existing buffer
+    ///
+    /// Fails if the buffer isn't exactly [`buffer_size::<C>(width, height)`](buffer_size)
+    /// bytes long, see the [buffer layout](crate::graphics#buffer-layout).
+    pub fn from_buffer(width: u32, height: u32, buffer: Box<[u8]>) -> Result<Self, FrameDataError> {
+        check_buffer_len(buffer_size::<C>(width as usize, height as usize), &buffer)?;
+        Ok(Self::with_buffer(width, height, buffer))
+    }
+
+    /// Consumes the display and returns the underlying buffer
+    pub fn into_buffer(self) -> Box<[u8]> {
+        self.buffer
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<C> OriginDimensions for BoxDisplay<C> {
+    fn size(&self) -> Size {
+        self.rotation.rotated_size(self.width, self.height)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl DrawTarget for BoxDisplay<BinaryColor> {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper(self.width, self.height, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper(self.width, self.height, area, color)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.fill_contiguous_helper(self.width, self.height, area, colors)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color.into());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Display for BoxDisplay<BinaryColor> {
+    impl_buffer_accessors!(buffer);
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    fn inverted(&self) -> bool {
+        self.inverted
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl DrawTarget for BoxDisplay<TriColor> {
+    type Color = TriColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper_tri(self.width, self.height, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper_tri(self.width, self.height, area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl TriDisplay for BoxDisplay<TriColor> {
+    impl_buffer_accessors!(buffer);
+
+    fn chromatic_offset(&self) -> usize {
+        self.buffer.len() / 2
+    }
+
+    fn bw_buffer(&self) -> &[u8] {
+        &self.buffer[0..self.chromatic_offset()]
+    }
+
+    fn chromatic_buffer(&self) -> &[u8] {
+        &self.buffer[self.chromatic_offset()..]
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl DrawTarget for BoxDisplay<OctColor> {
+    type Color = OctColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper(self.width, self.height, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper(self.width, self.height, area, color)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.fill_contiguous_helper(self.width, self.height, area, colors)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl OctDisplay for BoxDisplay<OctColor> {
+    impl_buffer_accessors!(buffer);
+}
+
+#[cfg(feature = "alloc")]
+impl DrawTarget for BoxDisplay<Gray2> {
+    type Color = Gray2;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.draw_helper(self.width, self.height, pixel)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid_helper(self.width, self.height, area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_buffer(color);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl GrayDisplay for BoxDisplay<Gray2> {
+    impl_buffer_accessors!(buffer);
+}
+
+/// A display buffer as an [ImageDrawable], e.g. to preview a frame on another display
+///
+/// Returned by `preview` of the display traits, with the size and orientation of the
+/// display as seen through its rotation and mirroring, so the preview looks like the panel.
+/// The image has the [preview_rgb](ColorType::preview_rgb) colors of the pixels, which
+/// convert into the colors of most RGB displays, [pixels](Preview::pixels) gives the
+/// colors of the buffer itself.
+///
+/// Example:
+/// ```rust
+/// # use embedded_graphics::{
+/// #     image::Image, mock_display::MockDisplay, pixelcolor::Rgb565, prelude::*,
+/// # };
+/// # use epd_waveshare::{epd2in9::Display2in9, prelude::*};
+/// let display = Display2in9::default();
+///
+/// // an rgb565 LCD, the top left corner of the frame is drawn at (10, 0)
+/// let mut lcd = MockDisplay::<Rgb565>::new();
+/// # lcd.set_allow_out_of_bounds_drawing(true);
+/// Image::new(&display.preview(), Point::new(10, 0)).draw(&mut lcd.color_converted())?;
+/// # Ok::<(), core::convert::Infallible>(())
+/// ```
+pub struct Preview<'a, C> {
+    buffer: &'a [u8],
+    size: Size,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    color: PhantomData<C>,
+}
+
+impl<'a, C: ColorType> Preview<'a, C> {
+    /// Previews an unrotated `width` x `height` buffer, see the
+    /// [buffer layout](crate::graphics#buffer-layout)
+    pub fn new(buffer: &'a [u8], width: u32, height: u32) -> Self {
+        Preview::rotated(
+            buffer,
+            Size::new(width, height),
+            DisplayRotation::Rotate0,
+            DisplayMirroring::None,
+        )
+    }
+
+    // `size` is the size as seen through the rotation
+    fn rotated(
+        buffer: &'a [u8],
+        size: Size,
+        rotation: DisplayRotation,
+        mirroring: DisplayMirroring,
+    ) -> Self {
+        Preview {
+            buffer,
+            size,
+            rotation,
+            mirroring,
+            color: PhantomData,
+        }
+    }
+
+    /// Color of the pixel at `point`, the background for bits which aren't a color
+    fn color_at(&self, point: Point) -> C {
+        pixel_at(self.buffer, self.size, self.rotation, self.mirroring, point)
+            .unwrap_or(C::BACKGROUND)
+    }
+
+    /// Every pixel of the image with the colors of the buffer, row by row
+    pub fn pixels(&self) -> impl Iterator<Item = Pixel<C>> + '_
+    where
+        C: PixelColor,
+    {
+        self.bounding_box()
+            .points()
+            .map(move |point| Pixel(point, self.color_at(point)))
+    }
+}
+
+impl<'a, C> OriginDimensions for Preview<'a, C> {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl<'a, C: ColorType> ImageDrawable for Preview<'a, C> {
+    type Color = Rgb888;
+
+    fn draw<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+    {
+        self.draw_sub_image(target, &self.bounding_box())
+    }
+
+    fn draw_sub_image<D>(&self, target: &mut D, area: &Rectangle) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+    {
+        let area = area.intersection(&self.bounding_box());
+        let colors = area
+            .points()
+            .map(|point| self.color_at(point).preview_rgb());
+        target.fill_contiguous(&Rectangle::new(Point::zero(), area.size), colors)
+    }
+}
+
+/// Picks the display color for a `BinaryColor` of an asset
+///
+/// Implemented for closures and for `(foreground, background)` pairs, where the
+/// foreground is `BinaryColor::On`.
+pub trait BinaryColorMap<C> {
+    /// Display color for `color`
+    fn map(&self, color: BinaryColor) -> C;
+}
+
+impl<C, F: Fn(BinaryColor) -> C> BinaryColorMap<C> for F {
+    fn map(&self, color: BinaryColor) -> C {
+        self(color)
+    }
+}
+
+impl<C: Copy> BinaryColorMap<C> for (C, C) {
+    fn map(&self, color: BinaryColor) -> C {
+        match color {
+            BinaryColor::On => self.0,
+            BinaryColor::Off => self.1,
+        }
+    }
+}
+
+/// Draws `BinaryColor` fonts and images onto a display with other colors
+///
+/// Wraps any display buffer, or any other [DrawTarget], and translates the colors on the
+/// way. `fill_solid`, `fill_contiguous` and `clear` are passed on with the mapped colors,
+/// so the fast paths of the wrapped display are still used.
+///
+/// Example:
+/// ```rust
+/// # use embedded_graphics::{image::{Image, ImageRaw}, pixelcolor::BinaryColor, prelude::*};
+/// # use epd_waveshare::{epd5in65f::Display5in65f, graphics::ColorMapped, prelude::*};
+/// // a 2x2 icon with set bits in the foreground color
+/// let raw = ImageRaw::<BinaryColor>::new(&[0b1000_0000, 0b0100_0000], 2);
+///
+/// let mut display = Display5in65f::default();
+/// let mut red_on_yellow = ColorMapped::new(&mut display, (OctColor::Red, OctColor::Yellow));
+/// let _ = Image::new(&raw, Point::new(10, 10)).draw(&mut red_on_yellow);
+/// ```
+pub struct ColorMapped<'a, D, F> {
+    target: &'a mut D,
+    map: F,
+}
+
+impl<'a, D, F> ColorMapped<'a, D, F>
+where
+    D: DrawTarget,
+    F: BinaryColorMap<D::Color>,
+{
+    /// Draws onto `target` with the colors of `map`
+    pub fn new(target: &'a mut D, map: F) -> Self {
+        ColorMapped { target, map }
+    }
+}
+
+impl<'a, D, F> Dimensions for ColorMapped<'a, D, F>
+where
+    D: DrawTarget,
+{
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+impl<'a, D, F> DrawTarget for ColorMapped<'a, D, F>
+where
+    D: DrawTarget,
+    F: BinaryColorMap<D::Color>,
+{
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let map = &self.map;
+        self.target
+            .draw_iter(pixels.into_iter().map(|Pixel(p, c)| Pixel(p, map.map(c))))
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let map = &self.map;
+        self.target
+            .fill_contiguous(area, colors.into_iter().map(|c| map.map(c)))
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.target.fill_solid(area, self.map.map(color))
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.target.clear(self.map.map(color))
+    }
+}
+
+// Swaps `On` and `Off` for an inverted buffer
+fn invert_binary(color: BinaryColor, inverted: bool) -> BinaryColor {
+    if inverted {
+        color.invert()
+    } else {
+        color
+    }
+}
+
+// Width and height of the unrotated display from its `size()`
+pub(crate) fn unrotated_size(size: Size, rotation: DisplayRotation) -> (u32, u32) {
+    // swapping width and height is its own inverse
+    let size = rotation.rotated_size(size.width, size.height);
+    (size.width, size.height)
+}
+
+// Maps an area as seen through rotation and mirroring to the unrotated buffer,
+// returns the top left and bottom right pixel of the part inside of the display
+pub(crate) fn find_rect(
+    area: &Rectangle,
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+) -> Option<(u32, u32, u32, u32)> {
+    // in i64, so areas reaching beyond the range of i32 don't overflow
+    let clip = |start: i32, len: u32, max: u32| {
+        let start = i64::from(start);
+        let (first, end) = (start.max(0), (start + i64::from(len)).min(i64::from(max)));
+        if first < end {
+            Some((first as u32, (end - 1) as u32))
+        } else {
+            None
+        }
+    };
+    let size = rotation.rotated_size(width, height);
+    let (x0, x1) = clip(area.top_left.x, area.size.width, size.width)?;
+    let (y0, y1) = clip(area.top_left.y, area.size.height, size.height)?;
+    let (ax, ay) = layout::to_buffer(x0, y0, width, height, rotation, mirroring);
+    let (bx, by) = layout::to_buffer(x1, y1, width, height, rotation, mirroring);
+    Some((ax.min(bx), ay.min(by), ax.max(bx), ay.max(by)))
+}
+
+// Sets or clears the bits of all pixels from (x0, y0) to (x1, y1) in a 1 bit per pixel buffer
+pub(crate) fn fill_bits(
+    buffer: &mut [u8],
+    width: u32,
+    (x0, y0, x1, y1): (u32, u32, u32, u32),
+    set: bool,
+) {
+    let stride = width.div_ceil(8) as usize;
+    let apply = |byte: &mut u8, mask: u8| {
+        if set {
+            *byte |= mask;
+        } else {
+            *byte &= !mask;
+        }
+    };
+    let (first, last) = (x0 as usize / 8, x1 as usize / 8);
+    let head = 0xff >> (x0 % 8);
+    let tail = 0xff << (7 - x1 % 8);
+    // rows beyond the buffer are left out
+    let rows = (y1 as usize + 1).min(buffer.len() / stride);
+    for row in buffer[..stride * rows].chunks_mut(stride).skip(y0 as usize) {
+        if first == last {
+            apply(&mut row[first], head & tail);
+            continue;
+        }
+        apply(&mut row[first], head);
+        row[first + 1..last].fill(if set { 0xff } else { 0x00 });
+        apply(&mut row[last], tail);
+    }
+}
+
+// Pixels of `area` read with `get_pixel` and moved to `position`, without the transparent
+// ones and the ones moved beyond the range of i32
+fn blit_pixels<'a, C, F>(
+    area: &'a Rectangle,
+    position: Point,
+    transparent: Option<C>,
+    get_pixel: F,
+) -> impl Iterator<Item = Pixel<C>> + 'a
+where
+    C: PixelColor + 'a,
+    F: Fn(Point) -> Option<C> + 'a,
+{
+    let origin = area.top_left;
+    area.points().filter_map(move |p| {
+        let color = get_pixel(p).filter(|&color| Some(color) != transparent)?;
+        let x = position.x.checked_add(p.x - origin.x)?;
+        let y = position.y.checked_add(p.y - origin.y)?;
+        Some(Pixel(Point::new(x, y), color))
+    })
+}
+
+// Whether the rows as seen through rotation and mirroring run along the rows of the
+// buffer from left to right
+fn rows_in_buffer_order(rotation: DisplayRotation, mirroring: DisplayMirroring) -> bool {
+    matches!(
+        (rotation, mirroring),
+        (DisplayRotation::Rotate0, DisplayMirroring::None)
+            | (DisplayRotation::Rotate0, DisplayMirroring::MirrorY)
+            | (DisplayRotation::Rotate180, DisplayMirroring::MirrorX)
+    )
+}
+
+// `fill_contiguous` for a single plane buffer of `C`. Where `rows_in_buffer_order` holds,
+// every byte is read and written once per row, otherwise the pixels are drawn one by one.
+// Colors of pixels outside of the display or past the end of a short buffer are skipped.
+fn fill_runs<C, I>(
+    buffer: &mut [u8],
+    (width, height): (u32, u32),
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    area: &Rectangle,
+    colors: I,
+) where
+    C: ColorType,
+    I: IntoIterator<Item = C>,
+{
+    // false once the colors run out
+    fn skip<I: Iterator>(colors: &mut I, n: i64) -> bool {
+        colors.take(n as usize).count() == n as usize
+    }
+    let mut colors = colors.into_iter();
+    let short = buffer.len() < plane_size::<C>(width, height);
+    if short || !rows_in_buffer_order(rotation, mirroring) {
+        for (point, color) in area.points().zip(colors) {
+            write_pixel(buffer, width, height, rotation, mirroring, point, color);
+        }
+        return;
+    }
+    let pixels = C::PIXELS_PER_BYTE;
+
+    // in i64, so areas reaching beyond the range of i32 don't overflow
+    let size = rotation.rotated_size(width, height);
+    let (x, len) = (i64::from(area.top_left.x), i64::from(area.size.width));
+    let first = x.max(0);
+    let visible = ((x + len).min(i64::from(size.width)) - first).max(0);
+    let head = (first - x).min(len);
+    let tail = len - head - visible;
+    let stride = width.div_ceil(pixels) as usize;
+
+    for row in 0..i64::from(area.size.height) {
+        let y = i64::from(area.top_left.y) + row;
+        if y < 0 || y >= i64::from(size.height) {
+            if !skip(&mut colors, len) {
+                return;
+            }
+            continue;
+        }
+        if !skip(&mut colors, head) {
+            return;
+        }
+        if visible > 0 {
+            let (nx, ny) =
+                layout::to_buffer(first as u32, y as u32, width, height, rotation, mirroring);
+            let row = &mut buffer[ny as usize * stride..][..stride];
+            let (mut x, end) = (nx, nx + visible as u32);
+            while x < end {
+                let slot = x % pixels;
+                let count = (pixels - slot).min(end - x);
+                let byte = &mut row[(x / pixels) as usize];
+                let mut packed = *byte;
+                for slot in slot..slot + count {
+                    match colors.next() {
+                        Some(color) => color.pack(0, slot, &mut packed),
+                        None => {
+                            *byte = packed;
+                            return;
+                        }
+                    }
+                }
+                *byte = packed;
+                x += count;
+            }
+        }
+        if !skip(&mut colors, tail) {
+            return;
+        }
+    }
+}
+
+// Copies the pixels from (x0, y0) to (x1, y1) between buffers `width` pixels wide with
+// `bits` bits per pixel, whole bytes in the middle of the rows and masked ones at the edges
+fn copy_bits(
+    dest: &mut [u8],
+    source: &[u8],
+    width: u32,
+    bits: u32,
+    (x0, y0, x1, y1): (u32, u32, u32, u32),
+) {
+    let stride = (width * bits).div_ceil(8) as usize;
+    let (start, end) = (x0 * bits, (x1 + 1) * bits);
+    let (first, last) = ((start / 8) as usize, ((end - 1) / 8) as usize);
+    let head = 0xff >> (start % 8);
+    let tail = 0xff << (7 - (end - 1) % 8);
+    let copy = |dest: &mut u8, source: u8, mask: u8| *dest = (*dest & !mask) | (source & mask);
+    for y in y0 as usize..=y1 as usize {
+        let dest = &mut dest[y * stride..][..stride];
+        let source = &source[y * stride..][..stride];
+        if first == last {
+            copy(&mut dest[first], source[first], head & tail);
+            continue;
+        }
+        copy(&mut dest[first], source[first], head);
+        dest[first + 1..last].copy_from_slice(&source[first + 1..last]);
+        copy(&mut dest[last], source[last], tail);
+    }
+}
+
+// Copies the pixels from (x0, y0) to (x1, y1) of every plane between buffers of `C`
+fn copy_planes<C: ColorType>(
+    dest: &mut [u8],
+    source: &[u8],
+    width: u32,
+    height: u32,
+    rect: (u32, u32, u32, u32),
+) {
+    let (plane, bits) = (plane_size::<C>(width, height), bits_per_plane::<C>());
+    let planes = dest.chunks_mut(plane).zip(source.chunks(plane));
+    for (dest, source) in planes.take(C::PLANES) {
+        copy_bits(dest, source, width, bits, rect);
     }
-    (nx, ny)
 }
 
-#[rustfmt::skip]
-//returns index position in the u8-slice and the bit-position inside that u8
-fn find_oct_position(x: u32, y: u32, width: u32, height: u32, rotation: DisplayRotation) -> (u32, bool) {
-    let (nx, ny) = find_rotation(x, y, width, height, rotation);
-    (
-        /* what byte address is this? */
-        nx / 2 + (width / 2) * ny,
-        /* is this the lower nibble (within byte)? */
-        (nx & 0x1) == 0,
-    )
+// Sets all pixels from (x0, y0) to (x1, y1) of every plane of a buffer of `C`
+fn fill_planes<C: ColorType>(
+    buffer: &mut [u8],
+    width: u32,
+    height: u32,
+    rect: (u32, u32, u32, u32),
+    color: C,
+) {
+    let plane = plane_size::<C>(width, height);
+    for (index, plane) in buffer.chunks_mut(plane).take(C::PLANES).enumerate() {
+        if C::PIXELS_PER_BYTE == 8 {
+            fill_bits(plane, width, rect, color.fill_byte(index) == 0xff);
+        } else {
+            fill_slots(plane, width, rect, color);
+        }
+    }
 }
 
-#[rustfmt::skip]
-//returns index position in the u8-slice and the bit-position inside that u8
-fn find_position(x: u32, y: u32, width: u32, height: u32, rotation: DisplayRotation) -> (u32, u8) {
-    let (nx, ny) = find_rotation(x, y, width, height, rotation);
-    (
-        nx / 8 + ((width + 7) / 8) * ny,
-        0x80 >> (nx % 8),
-    )
+// The rows of a buffer of `C` as drawn with `size` and `rotation`, plane after plane
+fn buffer_rows<C: ColorType>(
+    buffer: &[u8],
+    size: Size,
+    rotation: DisplayRotation,
+) -> core::slice::Chunks<'_, u8> {
+    let (width, _) = unrotated_size(size, rotation);
+    buffer.chunks(width.div_ceil(C::PIXELS_PER_BYTE) as usize)
+}
+
+// Color of a point of a buffer of `C` as drawn with `size`, rotation and mirroring
+fn pixel_at<C: ColorType>(
+    buffer: &[u8],
+    size: Size,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    point: Point,
+) -> Option<C> {
+    let (width, height) = unrotated_size(size, rotation);
+    read_pixel(buffer, width, height, rotation, mirroring, point)
+}
+
+// Color of a point as seen through rotation and mirroring, `None` outside of the display
+fn read_pixel<C: ColorType>(
+    buffer: &[u8],
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    point: Point,
+) -> Option<C> {
+    let (x, y) = layout::buffer_point(point.x, point.y, width, height, rotation, mirroring)?;
+    C::read_pixel(buffer, width, height, x, y)
+}
+
+// Puts `color` at a point as seen through rotation and mirroring, points outside of the
+// display are left out
+fn write_pixel<C: ColorType>(
+    buffer: &mut [u8],
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+    point: Point,
+    color: C,
+) {
+    if let Some((x, y)) = layout::buffer_point(point.x, point.y, width, height, rotation, mirroring)
+    {
+        color.write_pixel(buffer, width, height, x, y);
+    }
+}
+
+// Sets all pixels from (x0, y0) to (x1, y1) of a single plane buffer, whole bytes in the
+// middle of the rows and pixel by pixel at the edges
+fn fill_slots<C: ColorType>(
+    buffer: &mut [u8],
+    width: u32,
+    (x0, y0, x1, y1): (u32, u32, u32, u32),
+    color: C,
+) {
+    let pixels = C::PIXELS_PER_BYTE;
+    let stride = width.div_ceil(pixels) as usize;
+    let byte = color.fill_byte(0);
+    let rows = (y1 as usize + 1).min(buffer.len() / stride);
+    for row in buffer[..stride * rows].chunks_mut(stride).skip(y0 as usize) {
+        let mut x = x0;
+        while x <= x1 {
+            let (index, slot) = ((x / pixels) as usize, x % pixels);
+            if slot == 0 && x + pixels - 1 <= x1 {
+                row[index] = byte;
+                x += pixels;
+            } else {
+                color.pack(0, slot, &mut row[index]);
+                x += 1;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{buffer_len, find_position, outside_display, Display, DisplayRotation, VarDisplay};
-    use crate::color::Black;
-    use crate::color::Color;
+    extern crate std;
+    use super::{
+        buffer_len, fill_bits, fill_slots, Display, DisplayMirroring, DisplayRotation, VarDisplay,
+    };
+    use crate::color::{slot_position, Color, ColorType, OctColor};
+    use crate::color::{Black, White};
+    use crate::frame::FrameDataError;
+    use crate::layout;
+    use embedded_graphics::pixelcolor::BinaryColor;
     use embedded_graphics::{
         prelude::*,
         primitives::{Line, PrimitiveStyle},
     };
+    use embedded_graphics_core::primitives::Rectangle;
+
+    // Index in a plane and slot in that byte of a point as seen through rotation and mirroring
+    fn find_slot<C: ColorType>(
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        rotation: DisplayRotation,
+        mirroring: DisplayMirroring,
+    ) -> (usize, u32) {
+        let (nx, ny) = layout::to_buffer(x, y, width, height, rotation, mirroring);
+        slot_position::<C>(width, nx, ny)
+    }
 
     #[test]
     fn buffer_clear() {
@@ -399,7 +2020,7 @@ mod tests {
 
         let mut buffer =
             [Color::Black.get_byte_value(); buffer_len(WIDTH as usize, HEIGHT as usize)];
-        let mut display = VarDisplay::new(WIDTH, HEIGHT, &mut buffer);
+        let mut display = VarDisplay::new(WIDTH, HEIGHT, &mut buffer).unwrap();
 
         for &byte in display.buffer.iter() {
             assert_eq!(byte, Color::Black.get_byte_value());
@@ -415,24 +2036,32 @@ mod tests {
     #[test]
     fn rotation_overflow() {
         use crate::epd4in2::{HEIGHT, WIDTH};
-        let width = WIDTH as u32;
-        let height = HEIGHT as u32;
-        test_rotation_overflow(width, height, DisplayRotation::Rotate0);
-        test_rotation_overflow(width, height, DisplayRotation::Rotate90);
-        test_rotation_overflow(width, height, DisplayRotation::Rotate180);
-        test_rotation_overflow(width, height, DisplayRotation::Rotate270);
+        let width = WIDTH;
+        let height = HEIGHT;
+        for &mirroring in MIRRORINGS.iter() {
+            test_rotation_overflow(width, height, DisplayRotation::Rotate0, mirroring);
+            test_rotation_overflow(width, height, DisplayRotation::Rotate90, mirroring);
+            test_rotation_overflow(width, height, DisplayRotation::Rotate180, mirroring);
+            test_rotation_overflow(width, height, DisplayRotation::Rotate270, mirroring);
+        }
     }
 
-    fn test_rotation_overflow(width: u32, height: u32, rotation2: DisplayRotation) {
+    fn test_rotation_overflow(
+        width: u32,
+        height: u32,
+        rotation2: DisplayRotation,
+        mirroring: DisplayMirroring,
+    ) {
         let max_value = width / 8 * height;
         for x in 0..(width + height) {
             //limit x because it runs too long
-            for y in 0..(u32::max_value()) {
-                if outside_display(Point::new(x as i32, y as i32), width, height, rotation2) {
+            for y in 0..(u32::MAX) {
+                if !layout::contains(x as i32, y as i32, width, height, rotation2) {
                     break;
                 } else {
-                    let (idx, _) = find_position(x, y, width, height, rotation2);
-                    assert!(idx < max_value);
+                    let (idx, _) =
+                        find_slot::<BinaryColor>(x, y, width, height, rotation2, mirroring);
+                    assert!(idx < max_value as usize);
                 }
             }
         }
@@ -445,7 +2074,7 @@ mod tests {
         let height = 296;
 
         let mut buffer = [DEFAULT_BACKGROUND_COLOR.get_byte_value(); 128 / 8 * 296];
-        let mut display = VarDisplay::new(width, height, &mut buffer);
+        let mut display = VarDisplay::new(width, height, &mut buffer).unwrap();
 
         let _ = Line::new(Point::new(0, 0), Point::new(7, 0))
             .into_styled(PrimitiveStyle::with_stroke(Black, 1))
@@ -467,7 +2096,7 @@ mod tests {
         let height = 296;
 
         let mut buffer = [DEFAULT_BACKGROUND_COLOR.get_byte_value(); 128 / 8 * 296];
-        let mut display = VarDisplay::new(width, height, &mut buffer);
+        let mut display = VarDisplay::new(width, height, &mut buffer).unwrap();
 
         display.set_rotation(DisplayRotation::Rotate90);
 
@@ -486,4 +2115,1324 @@ mod tests {
             assert_eq!(byte, DEFAULT_BACKGROUND_COLOR.get_byte_value());
         }
     }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn box_display_matches_var_display() {
+        use super::BoxDisplay;
+        let width = 122;
+        let height = 250;
+
+        let mut buffer = [Color::White.get_byte_value(); buffer_len(122, 250)];
+        let mut var_display = VarDisplay::new(width, height, &mut buffer).unwrap();
+        let mut box_display =
+            BoxDisplay::<embedded_graphics::pixelcolor::BinaryColor>::new(width, height);
+        var_display.set_rotation(DisplayRotation::Rotate90);
+        box_display.set_rotation(DisplayRotation::Rotate90);
+
+        let line = Line::new(Point::new(3, 7), Point::new(200, 90))
+            .into_styled(PrimitiveStyle::with_stroke(Black, 1));
+        let _ = line.draw(&mut var_display);
+        let _ = line.draw(&mut box_display);
+
+        assert_eq!(var_display.buffer(), box_display.buffer());
+
+        let buffer = box_display.into_buffer();
+        assert!(BoxDisplay::<BinaryColor>::from_buffer(width, height - 1, buffer.clone()).is_err());
+        let from_buffer = BoxDisplay::<BinaryColor>::from_buffer(width, height, buffer).unwrap();
+        assert_eq!(var_display.buffer(), from_buffer.buffer());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn box_display_tri_chromatic() {
+        use super::{BoxDisplay, TriDisplay};
+        use crate::color::TriColor;
+
+        let mut display = BoxDisplay::<TriColor>::new(104, 212);
+        assert_eq!(display.bw_buffer().len(), 13 * 212);
+        assert_eq!(display.chromatic_buffer().len(), 13 * 212);
+
+        let _ = Line::new(Point::new(0, 0), Point::new(7, 0))
+            .into_styled(PrimitiveStyle::with_stroke(TriColor::Chromatic, 1))
+            .draw(&mut display);
+
+        assert_eq!(display.bw_buffer()[0], Color::White.get_byte_value());
+        assert_eq!(display.chromatic_buffer()[0], Color::Black.get_byte_value());
+    }
+
+    const ROTATIONS: [DisplayRotation; 4] = [
+        DisplayRotation::Rotate0,
+        DisplayRotation::Rotate90,
+        DisplayRotation::Rotate180,
+        DisplayRotation::Rotate270,
+    ];
+
+    const MIRRORINGS: [DisplayMirroring; 3] = [
+        DisplayMirroring::None,
+        DisplayMirroring::MirrorX,
+        DisplayMirroring::MirrorY,
+    ];
+
+    // Corners top left, top right, bottom left and bottom right as seen through the rotation
+    // together with the unrotated pixel they end up at
+    fn corners(width: u32, height: u32, rotation: DisplayRotation) -> [(Point, (u32, u32)); 4] {
+        let (w, h) = (width - 1, height - 1);
+        let size = rotation.rotated_size(width, height);
+        let (right, bottom) = (size.width as i32 - 1, size.height as i32 - 1);
+        let physical = match rotation {
+            DisplayRotation::Rotate0 => [(0, 0), (w, 0), (0, h), (w, h)],
+            DisplayRotation::Rotate90 => [(w, 0), (w, h), (0, 0), (0, h)],
+            DisplayRotation::Rotate180 => [(w, h), (0, h), (w, 0), (0, 0)],
+            DisplayRotation::Rotate270 => [(0, h), (0, 0), (w, h), (w, 0)],
+        };
+        [
+            (Point::new(0, 0), physical[0]),
+            (Point::new(right, 0), physical[1]),
+            (Point::new(0, bottom), physical[2]),
+            (Point::new(right, bottom), physical[3]),
+        ]
+    }
+
+    #[test]
+    fn rotated_size() {
+        let display_size = |rotation| {
+            let mut buffer = [0u8; buffer_len(122, 250)];
+            let mut display = VarDisplay::new(122, 250, &mut buffer).unwrap();
+            display.set_rotation(rotation);
+            display.size()
+        };
+        assert_eq!(display_size(DisplayRotation::Rotate0), Size::new(122, 250));
+        assert_eq!(display_size(DisplayRotation::Rotate90), Size::new(250, 122));
+        assert_eq!(
+            display_size(DisplayRotation::Rotate180),
+            Size::new(122, 250)
+        );
+        assert_eq!(
+            display_size(DisplayRotation::Rotate270),
+            Size::new(250, 122)
+        );
+    }
+
+    #[test]
+    fn rotation_corners_bw() {
+        // 122 isn't a multiple of 8, so every row is padded to 16 bytes
+        let (width, height) = (122, 250);
+        for &rotation in ROTATIONS.iter() {
+            for &(point, (x, y)) in corners(width, height, rotation).iter() {
+                let mut buffer = [Color::White.get_byte_value(); buffer_len(122, 250)];
+                let mut display = VarDisplay::new(width, height, &mut buffer).unwrap();
+                display.set_rotation(rotation);
+                Pixel(point, Black).draw(&mut display).unwrap();
+
+                let index = (y * 16 + x / 8) as usize;
+                for (i, &byte) in display.buffer().iter().enumerate() {
+                    let expected = if i == index { !(0x80 >> (x % 8)) } else { 0xff };
+                    assert_eq!(byte, expected, "{:?} at byte {}", point, i);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn rotation_corners_tri() {
+        use super::{BoxDisplay, TriDisplay};
+        use crate::color::TriColor;
+
+        let (width, height) = (122, 250);
+        for &rotation in ROTATIONS.iter() {
+            for &(point, (x, y)) in corners(width, height, rotation).iter() {
+                let mut display = BoxDisplay::<TriColor>::new(width, height);
+                display.set_rotation(rotation);
+                Pixel(point, TriColor::Chromatic)
+                    .draw(&mut display)
+                    .unwrap();
+
+                assert!(display.bw_buffer().iter().all(|&byte| byte == 0xff));
+                let index = (y * 16 + x / 8) as usize;
+                for (i, &byte) in display.chromatic_buffer().iter().enumerate() {
+                    let expected = if i == index { !(0x80 >> (x % 8)) } else { 0xff };
+                    assert_eq!(byte, expected, "{:?} at byte {}", point, i);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn rotation_corners_oct() {
+        use super::{BoxDisplay, OctDisplay};
+        use crate::color::OctColor;
+
+        // odd width, so the last byte of every row only holds one pixel
+        let (width, height) = (7, 5);
+        let white = OctColor::colors_byte(OctColor::White, OctColor::White);
+        for &rotation in ROTATIONS.iter() {
+            for &(point, (x, y)) in corners(width, height, rotation).iter() {
+                let mut display = BoxDisplay::<OctColor>::new(width, height);
+                display.set_rotation(rotation);
+                Pixel(point, OctColor::Black).draw(&mut display).unwrap();
+
+                let index = (y * 4 + x / 2) as usize;
+                for (i, &byte) in display.buffer().iter().enumerate() {
+                    let expected = match (i == index, x % 2 == 0) {
+                        (false, _) => white,
+                        (true, true) => OctColor::colors_byte(OctColor::Black, OctColor::White),
+                        (true, false) => OctColor::colors_byte(OctColor::White, OctColor::Black),
+                    };
+                    assert_eq!(byte, expected, "{:?} at byte {}", point, i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mirroring_corners() {
+        // mirroring swaps the corners as seen through the rotation
+        for &(width, height) in [(122, 250), (7, 5)].iter() {
+            for &rotation in ROTATIONS.iter() {
+                let unmirrored = corners(width, height, rotation);
+                for &mirroring in MIRRORINGS.iter() {
+                    let swapped = match mirroring {
+                        DisplayMirroring::None => [0, 1, 2, 3],
+                        DisplayMirroring::MirrorX => [1, 0, 3, 2],
+                        DisplayMirroring::MirrorY => [2, 3, 0, 1],
+                    };
+                    for (corner, &other) in swapped.iter().enumerate() {
+                        let mut buffer = [Color::White.get_byte_value(); buffer_len(122, 250)];
+                        let len = buffer_len(width as usize, height as usize);
+                        let mut display =
+                            VarDisplay::new(width, height, &mut buffer[..len]).unwrap();
+                        display.set_rotation(rotation);
+                        display.set_mirroring(mirroring);
+                        Pixel(unmirrored[corner].0, Black)
+                            .draw(&mut display)
+                            .unwrap();
+
+                        let (x, y) = unmirrored[other].1;
+                        let index = (y * width.div_ceil(8) + x / 8) as usize;
+                        for (i, &byte) in display.buffer().iter().enumerate() {
+                            let expected = if i == index { !(0x80 >> (x % 8)) } else { 0xff };
+                            assert_eq!(byte, expected, "{:?} at byte {}", unmirrored[corner].0, i);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn var_tri_display() {
+        use super::{TriDisplay, VarTriDisplay};
+        use crate::color::TriColor;
+        use crate::frame::FrameDataError;
+
+        let mut buffer = [TriColor::White.get_byte_value(); 2 * 2 * 3];
+        assert_eq!(
+            VarTriDisplay::new(16, 4, &mut buffer).err(),
+            Some(FrameDataError::BufferLength {
+                expected: 16,
+                actual: 12
+            })
+        );
+
+        // 10 pixels wide has 2 bytes per row
+        let mut display = VarTriDisplay::new(10, 3, &mut buffer).unwrap();
+        display.set_rotation(DisplayRotation::Rotate180);
+        assert_eq!(display.size(), Size::new(10, 3));
+        Pixel(Point::new(0, 0), TriColor::Chromatic)
+            .draw(&mut display)
+            .unwrap();
+        assert_eq!(display.bw_buffer(), &[0xff; 6]);
+        assert_eq!(
+            display.chromatic_buffer(),
+            &[0xff, 0xff, 0xff, 0xff, 0xff, !0x40]
+        );
+    }
+
+    // Every visible pixel has its own nibble and the padding nibble of odd widths is never used
+    #[test]
+    fn oct_positions() {
+        for &(width, height) in [(6u32, 3u32), (7, 3), (1, 2)].iter() {
+            let stride = width.div_ceil(2);
+            for &rotation in ROTATIONS.iter() {
+                for &mirroring in MIRRORINGS.iter() {
+                    let size = rotation.rotated_size(width, height);
+                    let mut used = std::vec![false; (2 * stride * height) as usize];
+                    for y in 0..size.height {
+                        for x in 0..size.width {
+                            let (index, slot) =
+                                find_slot::<OctColor>(x, y, width, height, rotation, mirroring);
+                            let nibble = 2 * index + slot as usize;
+                            assert!(!used[nibble], "{:?} used twice", (x, y));
+                            used[nibble] = true;
+                        }
+                    }
+                    for (nibble, &used) in used.iter().enumerate() {
+                        let column = nibble as u32 % (2 * stride);
+                        assert_eq!(used, column < width, "nibble {}", nibble);
+                    }
+                }
+            }
+        }
+
+        // unrotated: the even column is the upper nibble
+        let (width, height) = (7, 3);
+        let none = DisplayMirroring::None;
+        let position = |x, y, rotation| find_slot::<OctColor>(x, y, width, height, rotation, none);
+        assert_eq!(position(0, 0, DisplayRotation::Rotate0), (0, 0));
+        assert_eq!(position(5, 1, DisplayRotation::Rotate0), (6, 1));
+        assert_eq!(position(6, 2, DisplayRotation::Rotate0), (11, 0));
+        // (x, y) rotated by 90 degrees is the unrotated pixel (width - 1 - y, x)
+        assert_eq!(position(2, 0, DisplayRotation::Rotate90), (11, 0));
+        assert_eq!(position(2, 1, DisplayRotation::Rotate90), (10, 1));
+        // by 270 degrees (y, height - 1 - x)
+        assert_eq!(position(0, 5, DisplayRotation::Rotate270), (10, 1));
+        assert_eq!(position(2, 6, DisplayRotation::Rotate270), (3, 0));
+        // by 180 degrees (width - 1 - x, height - 1 - y)
+        assert_eq!(position(0, 0, DisplayRotation::Rotate180), (11, 0));
+        assert_eq!(position(1, 0, DisplayRotation::Rotate180), (10, 1));
+    }
+
+    #[test]
+    fn var_oct_display() {
+        use super::{OctDisplay, VarOctDisplay};
+        use crate::color::OctColor;
+        use crate::frame::FrameDataError;
+
+        let white = OctColor::colors_byte(OctColor::White, OctColor::White);
+        let mut buffer = [white; 3 * 2];
+        assert_eq!(
+            VarOctDisplay::new(6, 3, &mut buffer).err(),
+            Some(FrameDataError::BufferLength {
+                expected: 9,
+                actual: 6
+            })
+        );
+
+        let mut display = VarOctDisplay::new(5, 2, &mut buffer).unwrap();
+        display.set_rotation(DisplayRotation::Rotate90);
+        assert_eq!(display.size(), Size::new(2, 5));
+        Pixel(Point::new(1, 4), OctColor::Red)
+            .draw(&mut display)
+            .unwrap();
+        assert_eq!(
+            display.buffer(),
+            &[
+                white,
+                white,
+                white,
+                OctColor::colors_byte(OctColor::Red, OctColor::White),
+                white,
+                white
+            ]
+        );
+    }
+
+    #[test]
+    fn partial_display() {
+        use super::PartialDisplay;
+        use crate::frame::FrameDataError;
+
+        let mut buffer = [Color::White.get_byte_value(); 15 * 60];
+        assert_eq!(
+            PartialDisplay::new(84, 40, 120, 60, &mut buffer).err(),
+            Some(FrameDataError::UnalignedWindow(84))
+        );
+        assert_eq!(
+            PartialDisplay::new(80, 40, 128, 60, &mut buffer).err(),
+            Some(FrameDataError::BufferLength {
+                expected: 16 * 60,
+                actual: 15 * 60
+            })
+        );
+
+        let mut window = PartialDisplay::new(80, 40, 120, 60, &mut buffer).unwrap();
+        assert_eq!(
+            (window.x(), window.y(), window.width(), window.height()),
+            (80, 40, 120, 60)
+        );
+        // window coordinates, the last pixel is in the last byte
+        Pixel(Point::new(0, 0), Black).draw(&mut window).unwrap();
+        Pixel(Point::new(119, 59), Black).draw(&mut window).unwrap();
+        let buffer = window.buffer();
+        assert_eq!(buffer[0], !0x80);
+        assert_eq!(buffer[15 * 60 - 1], !0x01);
+        assert!(buffer[1..15 * 60 - 1].iter().all(|&byte| byte == 0xff));
+    }
+
+    // Rectangles with ragged edges, inside and partly outside of a 21x10 or 7x5 display
+    const FILL_AREAS: [(i32, i32, u32, u32); 8] = [
+        (0, 0, 1, 1),
+        (8, 0, 8, 3),
+        (16, 1, 5, 2),
+        (3, 1, 2, 3),
+        (1, 2, 16, 2),
+        (-3, -2, 9, 30),
+        (6, 4, 40, 40),
+        (0, 0, 30, 30),
+    ];
+
+    // Calls `check` for every rotation, mirroring and area of `FILL_AREAS`
+    fn fill_cases<F: FnMut(DisplayRotation, DisplayMirroring, Rectangle)>(mut check: F) {
+        for &rotation in ROTATIONS.iter() {
+            for &mirroring in MIRRORINGS.iter() {
+                for &(x, y, width, height) in FILL_AREAS.iter() {
+                    check(
+                        rotation,
+                        mirroring,
+                        Rectangle::new(Point::new(x, y), Size::new(width, height)),
+                    );
+                }
+            }
+        }
+    }
+
+    // Draws the area of `fill_solid` pixel by pixel
+    fn fill_reference<D: DrawTarget + Dimensions>(
+        display: &mut D,
+        area: &Rectangle,
+        color: D::Color,
+    ) {
+        use embedded_graphics_core::primitives::PointsIter;
+
+        let visible = area.intersection(&display.bounding_box());
+        let _ = display.draw_iter(visible.points().map(|p| Pixel(p, color)));
+    }
+
+    #[test]
+    fn fill_solid_bw() {
+        fill_cases(|rotation, mirroring, area| {
+            for &color in [Black, White].iter() {
+                let mut fast_buffer = [0x5a; buffer_len(21, 10)];
+                let mut reference_buffer = [0x5a; buffer_len(21, 10)];
+                let mut fast = VarDisplay::new(21, 10, &mut fast_buffer).unwrap();
+                let mut reference = VarDisplay::new(21, 10, &mut reference_buffer).unwrap();
+                fast.set_rotation(rotation);
+                fast.set_mirroring(mirroring);
+                reference.set_rotation(rotation);
+                reference.set_mirroring(mirroring);
+
+                fast.fill_solid(&area, color).unwrap();
+                fill_reference(&mut reference, &area, color);
+                assert_eq!(fast.buffer(), reference.buffer(), "{:?}", area);
+            }
+        });
+    }
+
+    #[test]
+    fn fill_solid_tri() {
+        use super::{TriDisplay, VarTriDisplay};
+        use crate::color::TriColor;
+
+        fill_cases(|rotation, mirroring, area| {
+            for &color in [TriColor::Black, TriColor::White, TriColor::Chromatic].iter() {
+                let mut fast_buffer = [0x5a; 2 * buffer_len(21, 10)];
+                let mut reference_buffer = [0x5a; 2 * buffer_len(21, 10)];
+                let mut fast = VarTriDisplay::new(21, 10, &mut fast_buffer).unwrap();
+                let mut reference = VarTriDisplay::new(21, 10, &mut reference_buffer).unwrap();
+                fast.set_rotation(rotation);
+                fast.set_mirroring(mirroring);
+                reference.set_rotation(rotation);
+                reference.set_mirroring(mirroring);
+
+                fast.fill_solid(&area, color).unwrap();
+                fill_reference(&mut reference, &area, color);
+                assert_eq!(fast.buffer(), reference.buffer(), "{:?}", area);
+            }
+        });
+    }
+
+    #[test]
+    fn fill_solid_oct() {
+        use super::{OctDisplay, VarOctDisplay};
+        use crate::color::OctColor;
+
+        fill_cases(|rotation, mirroring, area| {
+            for &color in [OctColor::Black, OctColor::Red].iter() {
+                let mut fast_buffer = [0x5a; 4 * 5];
+                let mut reference_buffer = [0x5a; 4 * 5];
+                let mut fast = VarOctDisplay::new(7, 5, &mut fast_buffer).unwrap();
+                let mut reference = VarOctDisplay::new(7, 5, &mut reference_buffer).unwrap();
+                fast.set_rotation(rotation);
+                fast.set_mirroring(mirroring);
+                reference.set_rotation(rotation);
+                reference.set_mirroring(mirroring);
+
+                fast.fill_solid(&area, color).unwrap();
+                fill_reference(&mut reference, &area, color);
+                assert_eq!(fast.buffer(), reference.buffer(), "{:?}", area);
+            }
+        });
+    }
+
+    #[test]
+    fn fill_solid_gray() {
+        use super::{GrayDisplay, VarGrayDisplay};
+        use embedded_graphics::pixelcolor::Gray2;
+
+        fill_cases(|rotation, mirroring, area| {
+            for &color in [Gray2::new(0), Gray2::new(2)].iter() {
+                let mut fast_buffer = [0x5a; 6 * 10];
+                let mut reference_buffer = [0x5a; 6 * 10];
+                let mut fast = VarGrayDisplay::new(21, 10, &mut fast_buffer).unwrap();
+                let mut reference = VarGrayDisplay::new(21, 10, &mut reference_buffer).unwrap();
+                fast.set_rotation(rotation);
+                fast.set_mirroring(mirroring);
+                reference.set_rotation(rotation);
+                reference.set_mirroring(mirroring);
+
+                fast.fill_solid(&area, color).unwrap();
+                fill_reference(&mut reference, &area, color);
+                assert_eq!(fast.buffer(), reference.buffer(), "{:?}", area);
+            }
+        });
+    }
+
+    // xorshift, good enough for test inputs
+    struct Random(u32);
+
+    impl Random {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn range(&mut self, start: i32, end: i32) -> i32 {
+            start + (self.next() % (end - start) as u32) as i32
+        }
+    }
+
+    // Random areas partly outside of the display with random colors for every rotation and
+    // mirroring, sometimes with fewer colors than pixels
+    fn contiguous_cases<C, F>(mut color: impl FnMut(u32) -> C, mut check: F)
+    where
+        F: FnMut(DisplayRotation, DisplayMirroring, Rectangle, &[C]),
+    {
+        let mut random = Random(0x1234_5678);
+        for &rotation in ROTATIONS.iter() {
+            for &mirroring in MIRRORINGS.iter() {
+                for _ in 0..40 {
+                    let area = Rectangle::new(
+                        Point::new(random.range(-12, 25), random.range(-12, 25)),
+                        Size::new(random.range(0, 30) as u32, random.range(0, 30) as u32),
+                    );
+                    let mut len = area.size.width * area.size.height;
+                    if random.next().is_multiple_of(4) {
+                        len = random.next() % (len + 1);
+                    }
+                    let colors: std::vec::Vec<C> = (0..len).map(|_| color(random.next())).collect();
+                    check(rotation, mirroring, area, &colors);
+                }
+            }
+        }
+    }
+
+    // `fill_contiguous` pixel by pixel, like the default implementation of embedded-graphics
+    fn contiguous_reference<D: DrawTarget>(display: &mut D, area: &Rectangle, colors: &[D::Color])
+    where
+        D::Color: Copy,
+    {
+        use embedded_graphics_core::primitives::PointsIter;
+
+        let pixels = area.points().zip(colors.iter()).map(|(p, &c)| Pixel(p, c));
+        let _ = display.draw_iter(pixels);
+    }
+
+    #[test]
+    fn fill_contiguous_bw() {
+        let color = |r: u32| if r.is_multiple_of(2) { Black } else { White };
+        contiguous_cases(color, |rotation, mirroring, area, colors| {
+            let mut fast_buffer = [0x5a; buffer_len(21, 10)];
+            let mut reference_buffer = [0x5a; buffer_len(21, 10)];
+            let mut fast = VarDisplay::new(21, 10, &mut fast_buffer).unwrap();
+            let mut reference = VarDisplay::new(21, 10, &mut reference_buffer).unwrap();
+            fast.set_rotation(rotation);
+            fast.set_mirroring(mirroring);
+            reference.set_rotation(rotation);
+            reference.set_mirroring(mirroring);
+
+            fast.fill_contiguous(&area, colors.iter().copied()).unwrap();
+            contiguous_reference(&mut reference, &area, colors);
+            assert_eq!(fast.buffer(), reference.buffer(), "{:?}", area);
+        });
+    }
+
+    #[test]
+    fn fill_contiguous_oct() {
+        use super::{OctDisplay, VarOctDisplay};
+        use crate::color::OctColor;
+
+        let color = |r: u32| OctColor::from_nibble((r % 7) as u8).unwrap();
+        contiguous_cases(color, |rotation, mirroring, area, colors| {
+            let mut fast_buffer = [0x5a; 4 * 5];
+            let mut reference_buffer = [0x5a; 4 * 5];
+            let mut fast = VarOctDisplay::new(7, 5, &mut fast_buffer).unwrap();
+            let mut reference = VarOctDisplay::new(7, 5, &mut reference_buffer).unwrap();
+            fast.set_rotation(rotation);
+            fast.set_mirroring(mirroring);
+            reference.set_rotation(rotation);
+            reference.set_mirroring(mirroring);
+
+            fast.fill_contiguous(&area, colors.iter().copied()).unwrap();
+            contiguous_reference(&mut reference, &area, colors);
+            assert_eq!(fast.buffer(), reference.buffer(), "{:?}", area);
+        });
+    }
+
+    #[test]
+    fn fills_stop_at_the_end_of_the_buffer() {
+        // two of the four rows
+        let mut buffer = [0x00; 2 * 2];
+        fill_bits(&mut buffer, 10, (0, 0, 9, 3), true);
+        assert_eq!(buffer, [0xff, 0xc0, 0xff, 0xc0]);
+
+        // one and a half rows
+        let mut buffer = [0x00; 3];
+        fill_slots(&mut buffer, 4, (0, 0, 3, 3), OctColor::Red);
+        let red = OctColor::colors_byte(OctColor::Red, OctColor::Red);
+        assert_eq!(buffer, [red, red, 0x00]);
+    }
+
+    #[test]
+    fn var_display_rejects_wrong_buffer_lengths() {
+        let mut buffer = [0xff; 5];
+        for len in [3, 5] {
+            assert_eq!(
+                VarDisplay::new(10, 2, &mut buffer[..len]).err(),
+                Some(FrameDataError::BufferLength {
+                    expected: 4,
+                    actual: len
+                })
+            );
+        }
+
+        // the unchecked display skips the pixels past the end of the buffer
+        let mut display = VarDisplay::new_unchecked(10, 2, &mut buffer[..3]);
+        Pixel(Point::new(9, 1), Black).draw(&mut display).unwrap();
+        Pixel(Point::new(0, 1), Black).draw(&mut display).unwrap();
+        assert_eq!(display.buffer(), [0xff, 0xff, 0x7f]);
+        let area = Rectangle::new(Point::new(0, 1), Size::new(10, 1));
+        display.fill_solid(&area, Black).unwrap();
+        display
+            .fill_contiguous(&area, core::iter::repeat(White))
+            .unwrap();
+        assert_eq!(display.buffer(), [0xff, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn fill_contiguous_far_outside() {
+        // only the colors of the pixels inside end up in the buffer
+        let mut buffer = [0xff; buffer_len(10, 2)];
+        let mut display = VarDisplay::new(10, 2, &mut buffer).unwrap();
+        let area = Rectangle::new(Point::new(i32::MAX - 1, -1), Size::new(4, 1));
+        display
+            .fill_contiguous(&area, [Black; 4].iter().copied())
+            .unwrap();
+        let area = Rectangle::new(Point::new(-3, 1), Size::new(5, 1));
+        let colors = [White, White, White, Black, Black];
+        display
+            .fill_contiguous(&area, colors.iter().copied())
+            .unwrap();
+        assert_eq!(display.buffer(), &[0xff, 0xff, 0x3f, 0xff]);
+    }
+
+    #[test]
+    fn blit_bw() {
+        use embedded_graphics_core::primitives::PointsIter;
+
+        let mut source_buffer = [0xff; buffer_len(3, 2)];
+        let mut source = VarDisplay::new(3, 2, &mut source_buffer).unwrap();
+        source
+            .draw_iter(
+                [
+                    Pixel(Point::new(0, 0), Black),
+                    Pixel(Point::new(2, 1), Black),
+                ]
+                .iter()
+                .copied(),
+            )
+            .unwrap();
+
+        let mut buffer = [0xff; buffer_len(8, 4)];
+        let mut display = VarDisplay::new(8, 4, &mut buffer).unwrap();
+        display.set_rotation(DisplayRotation::Rotate90);
+        let with = |display: &VarDisplay, color| {
+            let area = display.bounding_box();
+            area.points()
+                .filter(|&p| display.get_pixel(p) == Some(color))
+                .collect::<std::vec::Vec<_>>()
+        };
+
+        // cut off at the right edge of the rotated display
+        let area = source.bounding_box();
+        display
+            .blit(&source, &area, Point::new(2, 6), None)
+            .unwrap();
+        assert_eq!(with(&display, Black), [Point::new(2, 6)]);
+
+        // only the white pixels of the source are copied, the top left ones are cut off
+        display.clear(Black).unwrap();
+        display
+            .blit(&source, &area, Point::new(-1, -1), Some(Black))
+            .unwrap();
+        assert_eq!(with(&display, White), [Point::new(0, 0)]);
+
+        // far outside
+        display
+            .blit(&source, &area, Point::new(i32::MAX, i32::MIN), None)
+            .unwrap();
+        assert_eq!(with(&display, White), [Point::new(0, 0)]);
+    }
+
+    #[test]
+    fn blit_oct_area() {
+        use super::{OctDisplay, VarOctDisplay};
+        use crate::color::OctColor;
+
+        let mut source_buffer = [0; 2 * 2];
+        let mut source = VarOctDisplay::new(4, 2, &mut source_buffer).unwrap();
+        #[rustfmt::skip]
+        let colors = [
+            OctColor::Red, OctColor::Green, OctColor::White, OctColor::Blue,
+            OctColor::Black, OctColor::Orange, OctColor::Yellow, OctColor::Red,
+        ];
+        source
+            .fill_contiguous(&source.bounding_box(), colors.iter().copied())
+            .unwrap();
+
+        let mut buffer = [OctColor::colors_byte(OctColor::HiZ, OctColor::HiZ); 3 * 3];
+        let mut display = VarOctDisplay::new(5, 3, &mut buffer).unwrap();
+        display.set_rotation(DisplayRotation::Rotate180);
+        // the middle two columns of the source without white
+        let area = Rectangle::new(Point::new(1, 0), Size::new(2, 2));
+        display
+            .blit(&source, &area, Point::new(3, 1), Some(OctColor::White))
+            .unwrap();
+
+        let pixel = |x, y| display.get_pixel(Point::new(x, y)).unwrap();
+        assert_eq!(pixel(3, 1), OctColor::Green);
+        assert_eq!(pixel(4, 1), OctColor::HiZ);
+        assert_eq!(pixel(3, 2), OctColor::Orange);
+        assert_eq!(pixel(4, 2), OctColor::Yellow);
+        assert_eq!(pixel(2, 1), OctColor::HiZ);
+        // rotated by 180 degrees in the buffer
+        assert_eq!(display.buffer()[0] >> 4, OctColor::Yellow.get_nibble());
+    }
+
+    // Draws random colors into the whole display
+    fn random_fill<D: DrawTarget>(display: &mut D, seed: u32, color: impl Fn(u32) -> D::Color) {
+        let mut random = Random(seed);
+        let area = display.bounding_box();
+        let colors = core::iter::repeat_with(|| color(random.next()));
+        let _ = display.fill_contiguous(&area, colors);
+    }
+
+    // `copy_rect` pixel by pixel
+    fn copy_reference<D: DrawTarget>(
+        display: &mut D,
+        area: &Rectangle,
+        get_pixel: impl Fn(Point) -> Option<D::Color>,
+    ) {
+        use embedded_graphics_core::primitives::PointsIter;
+
+        let visible = area.intersection(&display.bounding_box());
+        let _ = display.draw_iter(
+            visible
+                .points()
+                .filter_map(|p| Some(Pixel(p, get_pixel(p)?))),
+        );
+    }
+
+    #[test]
+    fn copy_rect_bw() {
+        let color = |r: u32| if r.is_multiple_of(2) { Black } else { White };
+        fill_cases(|rotation, mirroring, area| {
+            let mut source_buffer = [0; buffer_len(21, 10)];
+            let mut fast_buffer = [0; buffer_len(21, 10)];
+            let mut reference_buffer = [0; buffer_len(21, 10)];
+            let mut source = VarDisplay::new(21, 10, &mut source_buffer).unwrap();
+            let mut fast = VarDisplay::new(21, 10, &mut fast_buffer).unwrap();
+            let mut reference = VarDisplay::new(21, 10, &mut reference_buffer).unwrap();
+            for (display, seed) in [(&mut source, 1), (&mut fast, 2), (&mut reference, 2)] {
+                display.set_rotation(rotation);
+                display.set_mirroring(mirroring);
+                random_fill(display, seed, color);
+            }
+
+            fast.copy_rect(&source, &area).unwrap();
+            copy_reference(&mut reference, &area, |p| source.get_pixel(p));
+            assert_eq!(fast.buffer(), reference.buffer(), "{:?}", area);
+        });
+    }
+
+    #[test]
+    fn copy_rect_tri() {
+        use super::{TriDisplay, VarTriDisplay};
+        use crate::color::TriColor;
+
+        let colors = [TriColor::Black, TriColor::White, TriColor::Chromatic];
+        let color = |r: u32| colors[(r % 3) as usize];
+        fill_cases(|rotation, mirroring, area| {
+            let mut source_buffer = [0; 2 * buffer_len(21, 10)];
+            let mut fast_buffer = [0; 2 * buffer_len(21, 10)];
+            let mut reference_buffer = [0; 2 * buffer_len(21, 10)];
+            let mut source = VarTriDisplay::new(21, 10, &mut source_buffer).unwrap();
+            let mut fast = VarTriDisplay::new(21, 10, &mut fast_buffer).unwrap();
+            let mut reference = VarTriDisplay::new(21, 10, &mut reference_buffer).unwrap();
+            for (display, seed) in [(&mut source, 1), (&mut fast, 2), (&mut reference, 2)] {
+                display.set_rotation(rotation);
+                display.set_mirroring(mirroring);
+                random_fill(display, seed, color);
+            }
+
+            fast.copy_rect(&source, &area).unwrap();
+            copy_reference(&mut reference, &area, |p| source.get_pixel(p));
+            assert_eq!(fast.buffer(), reference.buffer(), "{:?}", area);
+        });
+    }
+
+    #[test]
+    fn copy_rect_oct() {
+        use super::{OctDisplay, VarOctDisplay};
+        use crate::color::OctColor;
+
+        let color = |r: u32| OctColor::from_nibble((r % 8) as u8).unwrap();
+        fill_cases(|rotation, mirroring, area| {
+            let mut source_buffer = [0; 4 * 5];
+            let mut fast_buffer = [0; 4 * 5];
+            let mut reference_buffer = [0; 4 * 5];
+            let mut source = VarOctDisplay::new(7, 5, &mut source_buffer).unwrap();
+            let mut fast = VarOctDisplay::new(7, 5, &mut fast_buffer).unwrap();
+            let mut reference = VarOctDisplay::new(7, 5, &mut reference_buffer).unwrap();
+            for (display, seed) in [(&mut source, 1), (&mut fast, 2), (&mut reference, 2)] {
+                display.set_rotation(rotation);
+                display.set_mirroring(mirroring);
+                random_fill(display, seed, color);
+            }
+
+            fast.copy_rect(&source, &area).unwrap();
+            copy_reference(&mut reference, &area, |p| source.get_pixel(p));
+            assert_eq!(fast.buffer(), reference.buffer(), "{:?}", area);
+        });
+    }
+
+    #[test]
+    fn copy_rect_gray() {
+        use super::{GrayDisplay, VarGrayDisplay};
+        use embedded_graphics::pixelcolor::Gray2;
+
+        let color = |r: u32| Gray2::new((r % 4) as u8);
+        fill_cases(|rotation, mirroring, area| {
+            let mut source_buffer = [0; 6 * 10];
+            let mut fast_buffer = [0; 6 * 10];
+            let mut reference_buffer = [0; 6 * 10];
+            let mut source = VarGrayDisplay::new(21, 10, &mut source_buffer).unwrap();
+            let mut fast = VarGrayDisplay::new(21, 10, &mut fast_buffer).unwrap();
+            let mut reference = VarGrayDisplay::new(21, 10, &mut reference_buffer).unwrap();
+            for (display, seed) in [(&mut source, 1), (&mut fast, 2), (&mut reference, 2)] {
+                display.set_rotation(rotation);
+                display.set_mirroring(mirroring);
+                random_fill(display, seed, color);
+            }
+
+            fast.copy_rect(&source, &area).unwrap();
+            copy_reference(&mut reference, &area, |p| source.get_pixel(p));
+            assert_eq!(fast.buffer(), reference.buffer(), "{:?}", area);
+        });
+    }
+
+    #[test]
+    fn copy_rect_between_rotations() {
+        use embedded_graphics_core::primitives::PointsIter;
+
+        let color = |r: u32| if r.is_multiple_of(3) { Black } else { White };
+        let mut source_buffer = [0; buffer_len(21, 10)];
+        let mut buffer = [0; buffer_len(21, 10)];
+        let mut source = VarDisplay::new(21, 10, &mut source_buffer).unwrap();
+        let mut display = VarDisplay::new(21, 10, &mut buffer).unwrap();
+        source.set_rotation(DisplayRotation::Rotate90);
+        display.set_mirroring(DisplayMirroring::MirrorX);
+        random_fill(&mut source, 1, color);
+        random_fill(&mut display, 2, color);
+
+        // 10 x 21 and 21 x 10 overlap in the top left 10 x 10 pixels
+        let area = Rectangle::new(Point::new(2, 3), Size::new(20, 20));
+        display.copy_rect(&source, &area).unwrap();
+        let copied = Rectangle::new(Point::new(2, 3), Size::new(8, 7));
+        for p in copied.points() {
+            assert_eq!(display.get_pixel(p), source.get_pixel(p), "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn inverted_bw() {
+        use embedded_graphics_core::primitives::PointsIter;
+
+        let color = |r: u32| if r.is_multiple_of(3) { Black } else { White };
+        fill_cases(|rotation, mirroring, area| {
+            let mut buffer = [0; buffer_len(21, 10)];
+            let mut inverted_buffer = [0; buffer_len(21, 10)];
+            let mut display = VarDisplay::new(21, 10, &mut buffer).unwrap();
+            let mut inverted = VarDisplay::new(21, 10, &mut inverted_buffer).unwrap();
+            inverted.set_inverted(true);
+            assert!(inverted.inverted() && !display.inverted());
+            for display in [&mut display, &mut inverted] {
+                display.set_rotation(rotation);
+                display.set_mirroring(mirroring);
+                display.clear_buffer(Color::White);
+                random_fill(display, 1, color);
+                display.fill_solid(&area, Black).unwrap();
+                let _ = Pixel(Point::new(3, 2), White).draw(display);
+            }
+
+            let flipped: std::vec::Vec<u8> = display.buffer().iter().map(|b| !b).collect();
+            assert_eq!(inverted.buffer(), &flipped[..], "{:?}", area);
+            for p in display.bounding_box().points() {
+                assert_eq!(inverted.get_pixel(p), display.get_pixel(p));
+            }
+        });
+    }
+
+    #[test]
+    fn var_gray_display() {
+        use super::{GrayDisplay, VarGrayDisplay};
+        use crate::frame::FrameDataError;
+        use embedded_graphics::pixelcolor::Gray2;
+
+        let mut buffer = [0xff; 2 * 3];
+        assert_eq!(
+            VarGrayDisplay::new(9, 3, &mut buffer).err(),
+            Some(FrameDataError::BufferLength {
+                expected: 9,
+                actual: 6
+            })
+        );
+
+        let mut display = VarGrayDisplay::new(6, 3, &mut buffer).unwrap();
+        display.clear(Gray2::new(1)).unwrap();
+        assert_eq!(display.buffer(), &[0x55; 6]);
+        display.set_rotation(DisplayRotation::Rotate90);
+        assert_eq!(display.size(), Size::new(3, 6));
+        Pixel(Point::new(0, 5), Gray2::new(3))
+            .draw(&mut display)
+            .unwrap();
+        assert_eq!(display.buffer(), &[0xd5, 0x55, 0x55, 0x55, 0x55, 0x55]);
+    }
+
+    // Collects the pixels of a shape, also those outside of the displays
+    #[derive(Default)]
+    struct Recorder(std::collections::HashSet<Point>);
+
+    impl DrawTarget for Recorder {
+        type Color = BinaryColor;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I: IntoIterator<Item = Pixel<BinaryColor>>>(
+            &mut self,
+            pixels: I,
+        ) -> Result<(), Self::Error> {
+            self.0.extend(pixels.into_iter().map(|Pixel(p, _)| p));
+            Ok(())
+        }
+    }
+
+    impl OriginDimensions for Recorder {
+        fn size(&self) -> Size {
+            Size::new(100, 100)
+        }
+    }
+
+    // Filled and outlined shapes around `center` and a line through the whole display
+    fn draw_shape<T: DrawTarget>(
+        target: &mut T,
+        color: T::Color,
+        shape: u8,
+        center: Point,
+        width: i32,
+    ) {
+        use embedded_graphics::primitives::{Circle, Rectangle as Rect};
+
+        let filled = PrimitiveStyle::with_fill(color);
+        let stroke = PrimitiveStyle::with_stroke(color, 2);
+        let _ = match shape {
+            0 => Circle::with_center(center, 7)
+                .into_styled(filled)
+                .draw(target),
+            1 => Rect::with_center(center, Size::new(9, 5))
+                .into_styled(filled)
+                .draw(target),
+            2 => Rect::with_center(center, Size::new(6, 8))
+                .into_styled(stroke)
+                .draw(target),
+            _ => Line::new(
+                Point::new(-5, center.y),
+                Point::new(width + 5, center.y + 3),
+            )
+            .into_styled(stroke)
+            .draw(target),
+        };
+    }
+
+    // Draws shapes across every edge and corner of `display` and checks that exactly the
+    // pixels of the shapes inside of the display were drawn
+    fn check_clipping<D, F>(display: &mut D, color: D::Color, background: D::Color, get: F)
+    where
+        D: DrawTarget<Error = core::convert::Infallible>,
+        D::Color: core::fmt::Debug,
+        F: Fn(&D, Point) -> Option<D::Color>,
+    {
+        let size = display.bounding_box().size;
+        let (w, h) = (size.width as i32, size.height as i32);
+        for &cx in [-1, 0, w / 2, w - 1, w].iter() {
+            for &cy in [-1, 0, h / 2, h - 1, h].iter() {
+                let center = Point::new(cx, cy);
+                for shape in 0..4 {
+                    let mut recorder = Recorder::default();
+                    let offset = Point::new(10, 10);
+                    draw_shape(
+                        &mut recorder.translated(offset),
+                        BinaryColor::On,
+                        shape,
+                        center,
+                        w,
+                    );
+                    display.clear(background).unwrap();
+                    draw_shape(display, color, shape, center, w);
+
+                    for y in 0..h {
+                        for x in 0..w {
+                            let p = Point::new(x, y);
+                            let expected = if recorder.0.contains(&(p + offset)) {
+                                color
+                            } else {
+                                background
+                            };
+                            assert_eq!(get(display, p), Some(expected), "{:?} {:?}", shape, p);
+                        }
+                    }
+                }
+            }
+        }
+
+        // far outside or reaching beyond the range of i32
+        display.clear(background).unwrap();
+        let far = [
+            Point::new(i32::MIN, i32::MIN),
+            Point::new(i32::MAX, i32::MAX),
+            Point::new(i32::MAX, 0),
+            Point::new(0, i32::MIN),
+        ];
+        display
+            .draw_iter(far.iter().map(|&p| Pixel(p, color)))
+            .unwrap();
+        for &top_left in far.iter() {
+            let huge = Rectangle::new(top_left, Size::new(u32::MAX, u32::MAX));
+            display.fill_solid(&huge, background).unwrap();
+        }
+        display
+            .fill_solid(
+                &Rectangle::new(Point::new(-3, -3), Size::new(u32::MAX, 4)),
+                color,
+            )
+            .unwrap();
+        for x in 0..w {
+            assert_eq!(get(display, Point::new(x, 0)), Some(color));
+            assert_eq!(get(display, Point::new(x, 1)), Some(background));
+        }
+    }
+
+    #[test]
+    fn clipping() {
+        use super::{
+            GrayDisplay, OctDisplay, TriDisplay, VarGrayDisplay, VarOctDisplay, VarTriDisplay,
+        };
+        use crate::color::{OctColor, TriColor};
+        use embedded_graphics::pixelcolor::Gray2;
+
+        for &rotation in ROTATIONS.iter() {
+            for &mirroring in MIRRORINGS.iter() {
+                let mut buffer = [0; buffer_len(21, 10)];
+                let mut bw = VarDisplay::new(21, 10, &mut buffer).unwrap();
+                bw.set_rotation(rotation);
+                bw.set_mirroring(mirroring);
+                check_clipping(&mut bw, Black, White, |d, p| d.get_pixel(p));
+
+                let mut buffer = [0; 2 * buffer_len(21, 10)];
+                let mut tri = VarTriDisplay::new(21, 10, &mut buffer).unwrap();
+                tri.set_rotation(rotation);
+                tri.set_mirroring(mirroring);
+                check_clipping(&mut tri, TriColor::Chromatic, TriColor::White, |d, p| {
+                    d.get_pixel(p)
+                });
+
+                let mut buffer = [0; 4 * 5];
+                let mut oct = VarOctDisplay::new(7, 5, &mut buffer).unwrap();
+                oct.set_rotation(rotation);
+                oct.set_mirroring(mirroring);
+                check_clipping(&mut oct, OctColor::Red, OctColor::White, |d, p| {
+                    d.get_pixel(p)
+                });
+
+                let mut buffer = [0; 6 * 10];
+                let mut gray = VarGrayDisplay::new(21, 10, &mut buffer).unwrap();
+                gray.set_rotation(rotation);
+                gray.set_mirroring(mirroring);
+                check_clipping(&mut gray, Gray2::new(1), Gray2::new(3), |d, p| {
+                    d.get_pixel(p)
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn clear() {
+        use super::{TriDisplay, VarTriDisplay};
+        use crate::color::TriColor;
+
+        let mut buffer = [0x5a; 2 * buffer_len(21, 10)];
+        let mut display = VarTriDisplay::new(21, 10, &mut buffer).unwrap();
+        display.clear(TriColor::Chromatic).unwrap();
+        assert!(display.bw_buffer().iter().all(|&byte| byte == 0xff));
+        assert!(display.chromatic_buffer().iter().all(|&byte| byte == 0x00));
+        display.clear(TriColor::Black).unwrap();
+        assert!(display.bw_buffer().iter().all(|&byte| byte == 0x00));
+        assert!(display.chromatic_buffer().iter().all(|&byte| byte == 0xff));
+    }
+
+    #[test]
+    fn color_mapped() {
+        use super::{ColorMapped, OctDisplay, TriDisplay, VarOctDisplay, VarTriDisplay};
+        use crate::color::{OctColor, TriColor};
+        use embedded_graphics::image::{Image, ImageRaw};
+
+        // a 13x4 icon, drawn with fill_contiguous
+        let data = [
+            0b1010_0110,
+            0b0110_0000,
+            0b0111_1000,
+            0b1000_0000,
+            0b0000_0011,
+            0b1111_1000,
+            0b1100_1100,
+            0b1100_1000,
+        ];
+        let raw = ImageRaw::<BinaryColor>::new(&data, 13);
+        for &rotation in ROTATIONS.iter() {
+            let mut mapped = [0x11; 11 * 20];
+            let mut mapped = VarOctDisplay::new(22, 20, &mut mapped).unwrap();
+            let mut direct = [0x11; 11 * 20];
+            let mut direct = VarOctDisplay::new(22, 20, &mut direct).unwrap();
+            mapped.set_rotation(rotation);
+            direct.set_rotation(rotation);
+
+            let colors = (OctColor::Red, OctColor::Yellow);
+            Image::new(&raw, Point::new(3, 5))
+                .draw(&mut ColorMapped::new(&mut mapped, colors))
+                .unwrap();
+            for (x, y) in (0..4).flat_map(|y| (0..13).map(move |x| (x, y))) {
+                let on = data[y * 2 + x / 8] & (0x80 >> (x % 8)) != 0;
+                let color = if on { colors.0 } else { colors.1 };
+                Pixel(Point::new(x as i32 + 3, y as i32 + 5), color)
+                    .draw(&mut direct)
+                    .unwrap();
+            }
+            assert_eq!(mapped.buffer(), direct.buffer());
+        }
+
+        // fill_solid and clear with a closure
+        let mut buffer = [0xff; 2 * buffer_len(16, 2)];
+        let mut display = VarTriDisplay::new(16, 2, &mut buffer).unwrap();
+        let mut mapped = ColorMapped::new(&mut display, |color: BinaryColor| {
+            if color.is_on() {
+                TriColor::Chromatic
+            } else {
+                TriColor::Black
+            }
+        });
+        mapped.clear(BinaryColor::Off).unwrap();
+        Rectangle::new(Point::new(0, 1), Size::new(8, 1))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(&mut mapped)
+            .unwrap();
+        assert_eq!(display.bw_buffer(), &[0x00, 0x00, 0xff, 0x00]);
+        assert_eq!(display.chromatic_buffer(), &[0xff, 0xff, 0x00, 0xff]);
+    }
+
+    // The previews have the pixels of get_pixel in every orientation
+    #[test]
+    fn previews() {
+        use super::{TriDisplay, VarTriDisplay};
+        use crate::color::TriColor;
+        use embedded_graphics::{image::Image, mock_display::MockDisplay, pixelcolor::Rgb888};
+        use embedded_graphics_core::image::ImageDrawable;
+        use std::vec::Vec;
+
+        for &rotation in ROTATIONS.iter() {
+            for &mirroring in MIRRORINGS.iter() {
+                let mut buffer = [0xff; 2 * 3];
+                let mut display = VarDisplay::new(13, 3, &mut buffer).unwrap();
+                display.set_rotation(rotation);
+                display.set_mirroring(mirroring);
+                Line::new(Point::new(0, 0), Point::new(2, 2))
+                    .into_styled(PrimitiveStyle::with_stroke(Black, 1))
+                    .draw(&mut display)
+                    .unwrap();
+                let preview = display.preview();
+                assert_eq!(preview.size(), display.size());
+                let pixels: Vec<_> = preview.pixels().collect();
+                let expected: Vec<_> = display
+                    .bounding_box()
+                    .points()
+                    .map(|p| Pixel(p, display.get_pixel(p).unwrap()))
+                    .collect();
+                assert_eq!(pixels, expected);
+            }
+        }
+
+        // drawn as an image with the panel colors
+        let mut buffer = [0xff; 2 * 2];
+        let mut display = VarTriDisplay::new(4, 2, &mut buffer).unwrap();
+        display.set_rotation(DisplayRotation::Rotate90);
+        Pixel(Point::new(0, 0), TriColor::Black)
+            .draw(&mut display)
+            .unwrap();
+        Pixel(Point::new(1, 3), TriColor::Chromatic)
+            .draw(&mut display)
+            .unwrap();
+        let mut target = MockDisplay::<Rgb888>::new();
+        Image::new(&display.preview(), Point::new(1, 1))
+            .draw(&mut target)
+            .unwrap();
+        let color = |color: TriColor| color.preview_rgb();
+        let (k, w, r) = (
+            color(TriColor::Black),
+            color(TriColor::White),
+            color(TriColor::Chromatic),
+        );
+        for y in 0..4 {
+            for x in 0..2 {
+                let expected = match (x, y) {
+                    (0, 0) => k,
+                    (1, 3) => r,
+                    _ => w,
+                };
+                assert_eq!(target.get_pixel(Point::new(x + 1, y + 1)), Some(expected));
+            }
+        }
+        assert_eq!(target.get_pixel(Point::new(0, 0)), None);
+
+        // a part of it
+        let mut target = MockDisplay::<Rgb888>::new();
+        display
+            .preview()
+            .draw_sub_image(
+                &mut target,
+                &Rectangle::new(Point::new(1, 2), Size::new(4, 4)),
+            )
+            .unwrap();
+        assert_eq!(target.affected_area().size, Size::new(1, 2));
+        assert_eq!(target.get_pixel(Point::new(0, 1)), Some(r));
+
+        // an inverted buffer keeps the swapped colors of the panel
+        let mut buffer = [0xff; 1];
+        let mut display = VarDisplay::new(8, 1, &mut buffer).unwrap();
+        display.set_inverted(true);
+        Pixel(Point::new(0, 0), Black).draw(&mut display).unwrap();
+        assert_eq!(display.get_pixel(Point::new(0, 0)), Some(Black));
+        let first = display.preview().pixels().next().unwrap();
+        assert_eq!(first, Pixel(Point::new(0, 0), White));
+    }
+
+    // Every pixel drawn in every orientation is read back with the same color
+    #[test]
+    fn get_pixel_round_trip() {
+        use super::{
+            GrayDisplay, OctDisplay, TriDisplay, VarGrayDisplay, VarOctDisplay, VarTriDisplay,
+        };
+        use crate::color::{buffer_size, OctColor, TriColor};
+        use embedded_graphics::pixelcolor::Gray2;
+
+        for &(width, height) in [(8, 3), (13, 4), (7, 5)].iter() {
+            for &rotation in ROTATIONS.iter() {
+                for &mirroring in MIRRORINGS.iter() {
+                    let mut bw = [0xff; 2 * 4];
+                    let mut bw = VarDisplay::new(
+                        width,
+                        height,
+                        &mut bw[..buffer_len(width as usize, height as usize)],
+                    )
+                    .unwrap();
+                    let mut tri = [0xff; 2 * 2 * 4];
+                    let tri_len = buffer_size::<TriColor>(width as usize, height as usize);
+                    let mut tri = VarTriDisplay::new(width, height, &mut tri[..tri_len]).unwrap();
+                    let mut oct = [0x11; 7 * 5];
+                    let oct_len = buffer_size::<OctColor>(width as usize, height as usize);
+                    let mut oct = VarOctDisplay::new(width, height, &mut oct[..oct_len]).unwrap();
+                    let mut gray = [0x00; 4 * 5];
+                    let gray_len = buffer_size::<Gray2>(width as usize, height as usize);
+                    let mut gray =
+                        VarGrayDisplay::new(width, height, &mut gray[..gray_len]).unwrap();
+                    gray.set_rotation(rotation);
+                    gray.set_mirroring(mirroring);
+                    bw.set_rotation(rotation);
+                    bw.set_mirroring(mirroring);
+                    tri.set_rotation(rotation);
+                    tri.set_mirroring(mirroring);
+                    oct.set_rotation(rotation);
+                    oct.set_mirroring(mirroring);
+
+                    let size = rotation.rotated_size(width, height);
+                    let (w, h) = (size.width as i32, size.height as i32);
+                    for (i, point) in (0..h)
+                        .flat_map(|y| (0..w).map(move |x| Point::new(x, y)))
+                        .enumerate()
+                    {
+                        let bw_color = if i % 3 == 0 { Black } else { White };
+                        let tri_color =
+                            [TriColor::Black, TriColor::White, TriColor::Chromatic][i % 3];
+                        let oct_color = OctColor::from_nibble((i % 8) as u8).unwrap();
+                        Pixel(point, bw_color).draw(&mut bw).unwrap();
+                        Pixel(point, tri_color).draw(&mut tri).unwrap();
+                        let gray_color = Gray2::new((i % 4) as u8);
+                        Pixel(point, oct_color).draw(&mut oct).unwrap();
+                        Pixel(point, gray_color).draw(&mut gray).unwrap();
+                        assert_eq!(gray.get_pixel(point), Some(gray_color));
+                        assert_eq!(bw.get_pixel(point), Some(bw_color));
+                        assert_eq!(tri.get_pixel(point), Some(tri_color));
+                        assert_eq!(oct.get_pixel(point), Some(oct_color));
+                    }
+                    // drawing later pixels didn't change earlier ones
+                    for (i, point) in (0..h)
+                        .flat_map(|y| (0..w).map(move |x| Point::new(x, y)))
+                        .enumerate()
+                    {
+                        let bw_color = if i % 3 == 0 { Black } else { White };
+                        assert_eq!(bw.get_pixel(point), Some(bw_color));
+                        assert_eq!(
+                            oct.get_pixel(point),
+                            OctColor::from_nibble((i % 8) as u8).ok()
+                        );
+                        assert_eq!(gray.get_pixel(point), Some(Gray2::new((i % 4) as u8)));
+                    }
+                    for &outside in [
+                        Point::new(-1, 0),
+                        Point::new(0, -1),
+                        Point::new(w, 0),
+                        Point::new(0, h),
+                    ]
+                    .iter()
+                    {
+                        assert_eq!(bw.get_pixel(outside), None);
+                        assert_eq!(tri.get_pixel(outside), None);
+                        assert_eq!(oct.get_pixel(outside), None);
+                        assert_eq!(gray.get_pixel(outside), None);
+                    }
+                }
+            }
+        }
+    }
 }
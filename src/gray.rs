@@ -0,0 +1,138 @@
+//! 4 level grayscale frames and the b/w planes the controllers need for them
+//!
+//! Gray frames take 2 bits per pixel with the leftmost pixel in the most significant bits,
+//! every row starts at a new byte and takes `(width + 3) / 4` bytes. 0 is black, 3 white,
+//! just like the luma of `Gray2` in embedded-graphics.
+//!
+//! The controllers don't take this format, they get the gray level as two b/w planes
+//! with one bit each. [plane] and [split_planes] produce them.
+
+use crate::buffer_len;
+use crate::frame::FrameDataError;
+
+/// Length in bytes of a gray frame
+pub const fn buffer_size(width: usize, height: usize) -> usize {
+    width.div_ceil(4) * height
+}
+
+/// One bit of the gray level as b/w plane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrayPlane {
+    /// The most significant bit, set for white and light gray
+    High,
+    /// The least significant bit, set for white and dark gray
+    Low,
+}
+
+/// The bytes of one plane of a gray frame `width` pixels wide
+///
+/// The plane has the layout of a b/w buffer: 1 bit per pixel, rows padded to whole bytes
+/// and padding bits cleared. Some controllers want the bits inverted, just invert
+/// every byte for those.
+pub fn plane(buffer: &[u8], width: u32, plane: GrayPlane) -> impl Iterator<Item = u8> + '_ {
+    let stride = (width as usize).div_ceil(4);
+    let height = buffer.len().checked_div(stride).unwrap_or(0);
+    (0..height * (width as usize).div_ceil(8))
+        .map(move |index| plane_byte(buffer, width, plane, index))
+}
+
+/// The byte at `index` of a [plane], for the drivers which send the planes byte by byte
+pub(crate) fn plane_byte(buffer: &[u8], width: u32, plane: GrayPlane, index: usize) -> u8 {
+    let width = width as usize;
+    let row = &buffer[index / width.div_ceil(8) * width.div_ceil(4)..];
+    let byte = index % width.div_ceil(8);
+    let shift = match plane {
+        GrayPlane::High => 1,
+        GrayPlane::Low => 0,
+    };
+    (byte * 8..width.min(byte * 8 + 8)).fold(0, |acc, x| {
+        let level = row[x / 4] >> (6 - 2 * (x % 4));
+        acc | ((level >> shift) & 1) << (7 - x % 8)
+    })
+}
+
+/// Splits a gray frame `width` pixels wide into its two planes
+///
+/// Both planes need exactly the length of a b/w buffer of the same size.
+pub fn split_planes(
+    buffer: &[u8],
+    width: u32,
+    high: &mut [u8],
+    low: &mut [u8],
+) -> Result<(), FrameDataError> {
+    let stride = (width as usize).div_ceil(4);
+    let height = buffer.len().checked_div(stride).unwrap_or(0);
+    if height * stride != buffer.len() {
+        return Err(FrameDataError::BufferLength {
+            expected: (height + 1) * stride,
+            actual: buffer.len(),
+        });
+    }
+    let expected = buffer_len(width as usize, height);
+    for target in [&*high, &*low].iter() {
+        if target.len() != expected {
+            return Err(FrameDataError::BufferLength {
+                expected,
+                actual: target.len(),
+            });
+        }
+    }
+    for (target, byte) in high.iter_mut().zip(plane(buffer, width, GrayPlane::High)) {
+        *target = byte;
+    }
+    for (target, byte) in low.iter_mut().zip(plane(buffer, width, GrayPlane::Low)) {
+        *target = byte;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn planes() {
+        // 10 pixels: white, light, dark, black, then again and two white ones
+        let levels = [3, 2, 1, 0, 3, 2, 1, 0, 3, 3];
+        let mut buffer = [0; 2 * 3];
+        for (x, &level) in levels.iter().enumerate() {
+            buffer[x / 4] |= level << (6 - 2 * (x % 4));
+            // second row inverted
+            buffer[3 + x / 4] |= (3 - level) << (6 - 2 * (x % 4));
+        }
+        // padding bits of the gray rows are ignored
+        buffer[2] |= 0x0f;
+
+        let high: Vec<u8> = plane(&buffer, 10, GrayPlane::High).collect();
+        let low: Vec<u8> = plane(&buffer, 10, GrayPlane::Low).collect();
+        assert_eq!(high, [0b1100_1100, 0b1100_0000, 0b0011_0011, 0b0000_0000]);
+        assert_eq!(low, [0b1010_1010, 0b1100_0000, 0b0101_0101, 0b0000_0000]);
+
+        let (mut split_high, mut split_low) = ([0; 4], [0; 4]);
+        split_planes(&buffer, 10, &mut split_high, &mut split_low).unwrap();
+        assert_eq!(split_high[..], high[..]);
+        assert_eq!(split_low[..], low[..]);
+    }
+
+    #[test]
+    fn split_planes_lengths() {
+        let buffer = [0; 6];
+        let (mut high, mut low) = ([0; 4], [0; 4]);
+        assert_eq!(
+            split_planes(&buffer, 10, &mut high, &mut low[..3]),
+            Err(FrameDataError::BufferLength {
+                expected: 4,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            split_planes(&buffer[..5], 10, &mut high, &mut low),
+            Err(FrameDataError::BufferLength {
+                expected: 6,
+                actual: 5
+            })
+        );
+    }
+}
@@ -1,10 +1,305 @@
-use crate::traits::Command;
+use crate::color::Polarity;
+use crate::error::{Error, PinError};
+use crate::traits::{Command, DisplayState};
 use core::marker::PhantomData;
 use embedded_hal::{
     blocking::{delay::*, spi::Write},
     digital::v2::*,
 };
 
+/// Time for the power rail to stabilize after switching it on with the power pin
+const POWER_SETTLE_MS: u8 = 50;
+
+/// Bytes written at once when sending a repeated byte, e.g. to clear a frame
+const REPEAT_CHUNK: usize = 128;
+
+/// Pause between chunks of data, for long cables or bit-banged spi which can't keep up
+///
+/// Only data is slowed down, commands are short anyway.
+///
+/// ```
+/// # use epd_waveshare::DataDelay;
+/// fn delay_us(us: u32) {
+///     // e.g. std::thread::sleep or a busy loop counting cpu cycles
+/// #   let _ = us;
+/// }
+///
+/// let delay = DataDelay::new(64, 10, delay_us);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DataDelay {
+    chunk_size: usize,
+    us: u32,
+    delay_us: fn(u32),
+}
+
+impl DataDelay {
+    /// Pause for `us` microseconds after every `chunk_size` bytes
+    ///
+    /// `delay_us` does the actual pause and gets called with `us`
+    pub fn new(chunk_size: usize, us: u32, delay_us: fn(u32)) -> Self {
+        assert!(chunk_size > 0);
+        DataDelay {
+            chunk_size,
+            us,
+            delay_us,
+        }
+    }
+
+    fn pause(&self) {
+        (self.delay_us)(self.us);
+    }
+}
+
+/// Reads the busy pin until it settles, for noisy lines, e.g. a long ribbon cable
+///
+/// A glitch during a refresh can look like the end of it, and the next command corrupts the
+/// frame. The waits of the drivers only take a level after `samples` identical reads in a row.
+///
+/// ```
+/// # use epd_waveshare::BusyDebounce;
+/// fn delay_us(us: u32) {
+///     // e.g. std::thread::sleep or a busy loop counting cpu cycles
+/// #   let _ = us;
+/// }
+///
+/// let debounce = BusyDebounce::new(3, 5, delay_us).strict();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct BusyDebounce {
+    samples: u8,
+    us: u32,
+    delay_us: fn(u32),
+    strict: bool,
+}
+
+impl BusyDebounce {
+    /// Take the level of the busy pin after `samples` identical reads, `us` microseconds apart
+    ///
+    /// `delay_us` does the pause between the reads and gets called with `us`
+    pub fn new(samples: u8, us: u32, delay_us: fn(u32)) -> Self {
+        assert!(samples > 0);
+        BusyDebounce {
+            samples,
+            us,
+            delay_us,
+            strict: false,
+        }
+    }
+
+    /// Debounce [is_busy](crate::traits::WaveshareDisplay::is_busy) as well, not only the
+    /// waits of the driver
+    pub fn strict(self) -> Self {
+        BusyDebounce {
+            strict: true,
+            ..self
+        }
+    }
+
+    fn pause(&self) {
+        (self.delay_us)(self.us);
+    }
+}
+
+/// Rough progress of a long transfer or refresh, passed to a [ProgressHook]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// `sent` of the `total` bytes of the current data transfer are written
+    ///
+    /// A frame may be sent in several transfers, e.g. the buffer and its padding.
+    Data {
+        /// Bytes of the transfer written so far
+        sent: usize,
+        /// Bytes of the whole transfer
+        total: usize,
+    },
+    /// The display is still busy, checked `polls` times during this wait
+    Busy {
+        /// Busy checks of the current wait so far
+        polls: u32,
+    },
+}
+
+/// Gets called during long transfers and refreshes, e.g. to feed a watchdog
+///
+/// ```
+/// # use epd_waveshare::{Progress, ProgressHook};
+/// fn progress(progress: Progress) {
+///     // e.g. pet the watchdog or toggle a led
+/// #   let _ = progress;
+/// }
+///
+/// let hook = ProgressHook::new(4096, progress);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ProgressHook {
+    chunk_size: usize,
+    callback: fn(Progress),
+}
+
+impl ProgressHook {
+    /// Call `callback` after every `chunk_size` bytes of data and every check of the busy pin
+    pub fn new(chunk_size: usize, callback: fn(Progress)) -> Self {
+        assert!(chunk_size > 0);
+        ProgressHook {
+            chunk_size,
+            callback,
+        }
+    }
+}
+
+/// How to wait while the display is busy, e.g. during a refresh
+///
+/// The drivers check the busy pin and call [wait](BusyWait::wait) as long as the display
+/// is busy. The default [BusyPoll] returns right away, so the pin is polled as fast as
+/// possible. To save energy during long refreshes an implementation can sleep until the busy
+/// line changes instead, e.g. with a pin change interrupt and `WFE` on a microcontroller:
+///
+/// ```ignore
+/// struct SleepUntilEdge;
+///
+/// impl BusyWait for SleepUntilEdge {
+///     fn wait(&self, busy_low: bool) {
+///         // the interrupt for the edge to idle was armed once at startup, `WFE` returns
+///         // after any event, e.g. the interrupt, and the driver checks the pin again
+///         cortex_m::asm::wfe();
+///     }
+/// }
+///
+/// static WAIT: SleepUntilEdge = SleepUntilEdge;
+/// epd.set_busy_wait(&WAIT);
+/// ```
+///
+/// An edge which comes before `wait` must not be lost, `WFE` has its event register for
+/// that while `WFI` would sleep until the next interrupt.
+pub trait BusyWait {
+    /// Called while the display is busy, returns when the busy line might have changed
+    ///
+    /// `busy_low` is the level of the busy line while the display is busy, an interrupt
+    /// needs to wait for the edge to the other level.
+    fn wait(&self, busy_low: bool);
+}
+
+/// Polls the busy pin as fast as possible, the default [BusyWait]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BusyPoll;
+
+impl BusyWait for BusyPoll {
+    fn wait(&self, _busy_low: bool) {}
+}
+
+/// A pin which switches the power rail of the panel, see
+/// [set_power_pin](crate::prelude::WaveshareDisplay::set_power_pin)
+///
+/// Implemented for every [OutputPin], so the pin doesn't need the type of any other pin of
+/// the display.
+pub trait PowerPin {
+    /// Drives the pin high to switch the rail on and low to switch it off
+    ///
+    /// Fails with [PinError::Pwr], the error of the pin itself isn't kept.
+    fn set_power(&mut self, on: bool) -> Result<(), PinError>;
+}
+
+impl<P: OutputPin> PowerPin for P {
+    fn set_power(&mut self, on: bool) -> Result<(), PinError> {
+        let level = if on { self.set_high() } else { self.set_low() };
+        level.map_err(|_| PinError::Pwr)
+    }
+}
+
+/// A millisecond clock to measure how long the display is busy, see [RefreshStats]
+///
+/// ```
+/// # use epd_waveshare::Clock;
+/// fn now_ms() -> u32 {
+///     // e.g. a timer of the hal or the uptime of the board, wrapping around is fine
+/// #   0
+/// }
+///
+/// let clock = Clock::new(now_ms);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    now_ms: fn() -> u32,
+}
+
+impl Clock {
+    /// Take the time from `now_ms`, the milliseconds since any fixed point in time
+    pub fn new(now_ms: fn() -> u32) -> Self {
+        Clock { now_ms }
+    }
+
+    fn now(&self) -> u32 {
+        (self.now_ms)()
+    }
+}
+
+/// How long the display was busy in the phases of a refresh, measured with a [Clock]
+///
+/// The power phases only exist on the drivers which switch the power on and off around the
+/// refresh, the others keep them at 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RefreshStats {
+    /// Waiting for the power to come up since the refresh before, 0 if it stayed on
+    pub power_on_ms: u32,
+    /// From the start of the refresh until the driver saw the display idle again
+    ///
+    /// This is only as exact as the busy pin is checked, e.g. by polling
+    /// [display_frame_nb](crate::traits::WaveshareDisplay::display_frame_nb) or by the next
+    /// command on the displays whose `display_frame` doesn't wait for the refresh.
+    pub refresh_ms: u32,
+    /// Waiting for the power to go down after the refresh, 0 as long as it stays on
+    pub power_off_ms: u32,
+}
+
+impl RefreshStats {
+    /// Time the display was busy in all phases together
+    pub fn busy_wait_ms(&self) -> u32 {
+        self.power_on_ms + self.refresh_ms + self.power_off_ms
+    }
+}
+
+/// Fails like [DisplayInterface::check_len], for code without an interface
+pub(crate) fn check_len<E>(data: &[u8], len: usize) -> Result<(), Error<E>> {
+    if cfg!(feature = "pad_short_buffers") && data.len() < len {
+        return Ok(());
+    }
+    check_exact_len(data, len)
+}
+
+/// Fails like [DisplayInterface::check_exact_len], for code without an interface
+pub(crate) fn check_exact_len<E>(data: &[u8], len: usize) -> Result<(), Error<E>> {
+    if data.len() > len {
+        return Err(Error::BufferTooLong {
+            max: len,
+            actual: data.len(),
+        });
+    }
+    if data.len() < len {
+        return Err(Error::BufferTooShort {
+            min: len,
+            actual: data.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Fails like [DisplayInterface::check_window], for code without an interface
+pub(crate) fn check_window<E>(
+    (x, y, width, height): (u32, u32, u32, u32),
+    panel_width: u32,
+    panel_height: u32,
+) -> Result<(), Error<E>> {
+    let fits = |start: u32, len: u32, max: u32| {
+        len > 0 && start.checked_add(len).is_some_and(|end| end <= max)
+    };
+    if fits(x, width, panel_width) && fits(y, height, panel_height) {
+        Ok(())
+    } else {
+        Err(Error::OutOfBounds)
+    }
+}
+
 /// The Connection Interface of all (?) Waveshare EPD-Devices
 ///
 pub(crate) struct DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY> {
@@ -20,6 +315,42 @@ pub(crate) struct DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY> {
     dc: DC,
     /// Pin for Resetting
     rst: RST,
+    /// Whether a high level on `rst` resets the controller, e.g. behind an inverting transistor
+    rst_active_high: bool,
+    /// What the display is currently doing
+    state: DisplayState,
+    /// Optional pin enabling the power rail of the panel (e.g. PWR on rev 2.3 of the driver hat)
+    pwr: Option<&'static mut dyn PowerPin>,
+    /// Whether the power rail was enabled with `pwr`
+    rail_on: bool,
+    /// Optional pause between chunks of data
+    data_delay: Option<DataDelay>,
+    /// Optional number of data bytes after which CS is released and asserted again
+    cs_release: Option<usize>,
+    /// Data bytes written since CS was asserted
+    selected_bytes: usize,
+    /// How to wait while the display is busy
+    busy_wait: &'static dyn BusyWait,
+    /// Optional debouncing of the busy pin
+    busy_debounce: Option<BusyDebounce>,
+    /// Optional check of the busy pin around the reset, the window in ms and the busy level
+    busy_check: Option<(u16, bool)>,
+    /// Whether black and white of the background are swapped
+    inverted: bool,
+    /// Optional callback for the progress of long transfers and refreshes
+    progress: Option<ProgressHook>,
+    /// Data bytes written since the last call of the progress hook
+    unreported: usize,
+    /// Bytes written and total bytes of the current data transfer
+    transfer: (usize, usize),
+    /// Optional clock to measure the refreshes
+    clock: Option<Clock>,
+    /// Time the running refresh started
+    refresh_started: u32,
+    /// Power on wait of the next refresh
+    power_on_ms: u32,
+    /// Phases of the last finished refresh
+    last_refresh: Option<RefreshStats>,
 }
 
 impl<SPI, CS, BUSY, DC, RST, DELAY> DisplayInterface<SPI, CS, BUSY, DC, RST, DELAY>
@@ -33,73 +364,576 @@ where
 {
     pub fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
         DisplayInterface {
-            _spi: PhantomData::default(),
-            _delay: PhantomData::default(),
+            _spi: PhantomData,
+            _delay: PhantomData,
             cs,
             busy,
             dc,
             rst,
+            rst_active_high: false,
+            state: DisplayState::Uninitialized,
+            pwr: None,
+            rail_on: false,
+            data_delay: None,
+            cs_release: None,
+            selected_bytes: 0,
+            busy_wait: &BusyPoll,
+            busy_debounce: None,
+            busy_check: None,
+            inverted: false,
+            progress: None,
+            unreported: 0,
+            transfer: (0, 0),
+            clock: None,
+            refresh_started: 0,
+            power_on_ms: 0,
+            last_refresh: None,
+        }
+    }
+
+    /// Slow down sending data, `None` sends it as fast as the spi bus allows
+    pub(crate) fn set_data_delay(&mut self, data_delay: Option<DataDelay>) {
+        self.data_delay = data_delay;
+    }
+
+    /// Release CS after every `chunk_size` bytes of data, `None` keeps it asserted for the
+    /// whole transfer
+    pub(crate) fn set_cs_release(&mut self, chunk_size: Option<usize>) {
+        assert!(chunk_size != Some(0));
+        self.cs_release = chunk_size;
+    }
+
+    /// Replace the polling of the busy pin, e.g. by sleeping until an interrupt
+    pub(crate) fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait) {
+        self.busy_wait = busy_wait;
+    }
+
+    /// Read the busy pin until it settles, `None` reads it once
+    pub(crate) fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>) {
+        self.busy_debounce = busy_debounce;
+    }
+
+    /// Check in every reset that the busy pin follows it, see
+    /// [set_busy_check](crate::traits::WaveshareDisplay::set_busy_check)
+    pub(crate) fn set_busy_check(&mut self, window_ms: Option<u16>, is_busy_low: bool) {
+        self.busy_check = window_ms.map(|window_ms| (window_ms, is_busy_low));
+    }
+
+    /// Report the progress of long transfers and refreshes, `None` reports nothing
+    pub(crate) fn set_progress(&mut self, progress: Option<ProgressHook>) {
+        self.progress = progress;
+        self.unreported = 0;
+    }
+
+    /// Reset the controller with a high level on the reset pin instead of a low one
+    ///
+    /// Drives the pin to the new idle level right away.
+    pub(crate) fn set_reset_active_high(
+        &mut self,
+        active_high: bool,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.rst_active_high = active_high;
+        self.set_reset(false)
+    }
+
+    // Asserts or releases the reset of the controller, as the polarity of the pin requires
+    fn set_reset(&mut self, asserted: bool) -> Result<(), Error<SPI::Error>> {
+        let high = asserted == self.rst_active_high;
+        let result = if high {
+            self.rst.set_high()
+        } else {
+            self.rst.set_low()
+        };
+        result.map_err(|_| self.pin_failed(PinError::Rst))
+    }
+
+    /// Swap black and white of the background, to match an inverted graphics buffer
+    pub(crate) fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    /// Whether black and white of the background are swapped
+    pub(crate) fn inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// The b/w byte of the background, flipped if the background is inverted
+    pub(crate) fn background_byte(&self, byte: u8) -> u8 {
+        if self.inverted {
+            !byte
+        } else {
+            byte
+        }
+    }
+
+    /// Use a pin to switch the power rail of the panel
+    ///
+    /// The rail stays off until the next reset
+    pub(crate) fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin) {
+        self.pwr = Some(pwr);
+        self.rail_on = false;
+    }
+
+    /// Current state of the display
+    pub(crate) fn state(&self) -> DisplayState {
+        self.state
+    }
+
+    /// Measure the refreshes with `clock`, `None` measures nothing
+    pub(crate) fn set_clock(&mut self, clock: Option<Clock>) {
+        self.clock = clock;
+        self.power_on_ms = 0;
+        self.last_refresh = None;
+    }
+
+    /// Phases of the last refresh which finished since the clock was set
+    pub(crate) fn last_refresh(&self) -> Option<RefreshStats> {
+        self.last_refresh
+    }
+
+    /// Set the state after a command sequence of the driver finished successfully
+    ///
+    /// [DisplayState::Refreshing] starts measuring the refresh if there is a clock.
+    pub(crate) fn set_state(&mut self, state: DisplayState) {
+        if let (DisplayState::Refreshing, Some(clock)) = (state, self.clock) {
+            self.refresh_started = clock.now();
+        }
+        self.state = state;
+    }
+
+    /// Marks the display as asleep after the deep sleep command was sent
+    ///
+    /// Also switches off the power rail if there is a power pin
+    pub(crate) fn asleep(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.state = DisplayState::Asleep;
+        if let Some(pwr) = self.pwr.as_mut() {
+            pwr.set_power(false).map_err(Error::Pin)?;
+            self.rail_on = false;
+        }
+        Ok(())
+    }
+
+    /// Returns the error for a failed pin, afterwards the display is in an unknown state
+    fn pin_failed(&mut self, pin: PinError) -> Error<SPI::Error> {
+        self.state = DisplayState::Uninitialized;
+        Error::Pin(pin)
+    }
+
+    /// Fails if there is no new frame waiting to be displayed
+    pub(crate) fn check_frame_pending(&self) -> Result<(), Error<SPI::Error>> {
+        match self.state {
+            DisplayState::FramePending => Ok(()),
+            DisplayState::Asleep => Err(Error::Asleep),
+            DisplayState::Uninitialized => Err(Error::Uninitialized),
+            DisplayState::Idle | DisplayState::Refreshing => Err(Error::NoFrame),
         }
     }
 
     /// Basic function for sending [Commands](Command).
     ///
     /// Enables direct interaction with the device with the help of [data()](DisplayInterface::data())
-    pub(crate) fn cmd<T: Command>(&mut self, spi: &mut SPI, command: T) -> Result<(), SPI::Error> {
+    pub(crate) fn cmd<T: Command>(
+        &mut self,
+        spi: &mut SPI,
+        command: T,
+    ) -> Result<(), Error<SPI::Error>> {
         // low for commands
-        let _ = self.dc.set_low();
+        self.dc
+            .set_low()
+            .map_err(|_| self.pin_failed(PinError::Dc))?;
 
         // Transfer the command over spi
-        self.write(spi, &[command.address()])
+        self.select()?;
+        self.write(spi, &[command.address()])?;
+        self.deselect()
     }
 
     /// Basic function for sending an array of u8-values of data over spi
     ///
     /// Enables direct interaction with the device with the help of [command()](Epd4in2::command())
-    pub(crate) fn data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
+    pub(crate) fn data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         // high for data
-        let _ = self.dc.set_high();
+        self.dc
+            .set_high()
+            .map_err(|_| self.pin_failed(PinError::Dc))?;
 
         // Transfer data (u8-array) over spi
-        self.write(spi, data)
+        self.transfer = (0, data.len());
+        self.select()?;
+        self.delayed_data(spi, data)?;
+        self.deselect()
+    }
+
+    // Writes data while CS is asserted, pausing after every chunk of the data delay
+    fn delayed_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        match self.data_delay {
+            Some(data_delay) => {
+                for chunk in data.chunks(data_delay.chunk_size) {
+                    self.write_data(spi, chunk)?;
+                    data_delay.pause();
+                }
+                Ok(())
+            }
+            None => self.write_data(spi, data),
+        }
+    }
+
+    /// Sends a b/w frame of the buffers to a controller with `polarity`
+    ///
+    /// Flips the bits for [Polarity::SetIsColor], see
+    /// [POLARITY](crate::traits::WaveshareDisplay::POLARITY).
+    pub(crate) fn frame_data(
+        &mut self,
+        spi: &mut SPI,
+        data: &[u8],
+        polarity: Polarity,
+    ) -> Result<(), Error<SPI::Error>> {
+        match polarity {
+            Polarity::ClearedIsColor => self.data(spi, data),
+            Polarity::SetIsColor => self.data_inverted(spi, data),
+        }
+    }
+
+    /// Fails with [Error::BufferTooLong] if `data` is longer than the `len` bytes of a frame
+    ///
+    /// Shorter data fails with [Error::BufferTooShort], with the `pad_short_buffers` feature
+    /// it is fine and the drivers pad it with the background.
+    pub(crate) fn check_len(&self, data: &[u8], len: usize) -> Result<(), Error<SPI::Error>> {
+        check_len(data, len)
+    }
+
+    /// Fails unless `data` has the `len` bytes of a frame, for the frames which aren't padded
+    pub(crate) fn check_exact_len(&self, data: &[u8], len: usize) -> Result<(), Error<SPI::Error>> {
+        check_exact_len(data, len)
+    }
+
+    /// Fails with [Error::OutOfBounds] if the window at (x, y) is empty or doesn't fit on a
+    /// display of `panel_width` x `panel_height` pixels
+    pub(crate) fn check_window(
+        &self,
+        (x, y, width, height): (u32, u32, u32, u32),
+        panel_width: u32,
+        panel_height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        check_window((x, y, width, height), panel_width, panel_height)
+    }
+
+    /// Sends `data` like [frame_data](Self::frame_data) and pads it to `len` bytes with
+    /// `background`, the byte for the controller as `clear_frame` sends it
+    ///
+    /// Empty `data` sends only the background.
+    pub(crate) fn padded_frame_data(
+        &mut self,
+        spi: &mut SPI,
+        data: &[u8],
+        len: usize,
+        background: u8,
+        polarity: Polarity,
+    ) -> Result<(), Error<SPI::Error>> {
+        if !data.is_empty() {
+            self.frame_data(spi, data, polarity)?;
+        }
+        self.pad(spi, background, len.saturating_sub(data.len()))
+    }
+
+    /// Sends `data` like [padded_frame_data](Self::padded_frame_data), with the bits of
+    /// every byte in reverse order if `reverse_bits`
+    ///
+    /// For the controllers which mirror the bytes of a row in hardware but not the pixels
+    /// in them, see [RamLayout](crate::type_a::RamLayout).
+    pub(crate) fn padded_frame_data_reversed(
+        &mut self,
+        spi: &mut SPI,
+        data: &[u8],
+        len: usize,
+        background: u8,
+        polarity: Polarity,
+        reverse_bits: bool,
+    ) -> Result<(), Error<SPI::Error>> {
+        if !reverse_bits {
+            return self.padded_frame_data(spi, data, len, background, polarity);
+        }
+        if !data.is_empty() {
+            match polarity {
+                Polarity::ClearedIsColor => {
+                    self.data_expanded(spi, data, |byte| [byte.reverse_bits()])?
+                }
+                Polarity::SetIsColor => {
+                    self.data_expanded(spi, data, |byte| [!byte.reverse_bits()])?
+                }
+            }
+        }
+        self.pad(
+            spi,
+            background.reverse_bits(),
+            len.saturating_sub(data.len()),
+        )
+    }
+
+    /// Sends `count` bytes of `background` after a short buffer, nothing for a full one
+    pub(crate) fn pad(
+        &mut self,
+        spi: &mut SPI,
+        background: u8,
+        count: usize,
+    ) -> Result<(), Error<SPI::Error>> {
+        if count == 0 {
+            return Ok(());
+        }
+        self.data_x_times(spi, background, count as u32)
+    }
+
+    /// Sends every byte of `data` with its bits flipped
+    pub(crate) fn data_inverted(
+        &mut self,
+        spi: &mut SPI,
+        data: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.data_expanded(spi, data, |byte| [!byte])
+    }
+
+    /// Sends the `N` bytes `expand` makes of every byte of `data`
+    ///
+    /// E.g. for controllers which take more bits per pixel than the buffer has. The
+    /// expanded bytes are collected and written up to [REPEAT_CHUNK] bytes at once.
+    pub(crate) fn data_expanded<const N: usize>(
+        &mut self,
+        spi: &mut SPI,
+        data: &[u8],
+        expand: impl Fn(u8) -> [u8; N],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.data_generated(spi, data.len(), |i| data[i], expand)
+    }
+
+    /// Sends the `N` bytes `expand` makes of each of the `len` bytes `byte_at` gives for
+    /// the indices `0..len`
+    ///
+    /// For frames which are generated while they are sent, like the
+    /// [test patterns](crate::pattern). The bytes are collected and written up to
+    /// [REPEAT_CHUNK] bytes at once.
+    pub(crate) fn data_generated<const N: usize>(
+        &mut self,
+        spi: &mut SPI,
+        len: usize,
+        byte_at: impl Fn(usize) -> u8,
+        expand: impl Fn(u8) -> [u8; N],
+    ) -> Result<(), Error<SPI::Error>> {
+        // high for data
+        self.dc
+            .set_high()
+            .map_err(|_| self.pin_failed(PinError::Dc))?;
+
+        self.select()?;
+        self.write_generated(spi, len, byte_at, expand)?;
+        self.deselect()
+    }
+
+    /// Sends the `len` bytes `byte_at` gives like [frame_data](Self::frame_data)
+    pub(crate) fn generated_frame_data(
+        &mut self,
+        spi: &mut SPI,
+        len: usize,
+        byte_at: impl Fn(usize) -> u8,
+        polarity: Polarity,
+    ) -> Result<(), Error<SPI::Error>> {
+        match polarity {
+            Polarity::ClearedIsColor => self.data_generated(spi, len, byte_at, |byte| [byte]),
+            Polarity::SetIsColor => self.data_generated(spi, len, byte_at, |byte| [!byte]),
+        }
+    }
+
+    /// Sends the command and the `len` bytes `byte_at` gives in one selection like
+    /// [cmd_with_data](Self::cmd_with_data)
+    pub(crate) fn cmd_with_generated<T: Command>(
+        &mut self,
+        spi: &mut SPI,
+        command: T,
+        len: usize,
+        byte_at: impl Fn(usize) -> u8,
+    ) -> Result<(), Error<SPI::Error>> {
+        // low for commands
+        self.dc
+            .set_low()
+            .map_err(|_| self.pin_failed(PinError::Dc))?;
+
+        self.select()?;
+        self.write(spi, &[command.address()])?;
+
+        // high for data
+        self.dc
+            .set_high()
+            .map_err(|_| self.pin_failed(PinError::Dc))?;
+
+        self.write_generated(spi, len, byte_at, |byte| [byte])?;
+        self.deselect()
+    }
+
+    // Writes the generated and expanded bytes while CS is asserted
+    fn write_generated<const N: usize>(
+        &mut self,
+        spi: &mut SPI,
+        len: usize,
+        byte_at: impl Fn(usize) -> u8,
+        expand: impl Fn(u8) -> [u8; N],
+    ) -> Result<(), Error<SPI::Error>> {
+        let mut expanded = [0u8; REPEAT_CHUNK];
+        self.transfer = (0, len * N);
+        let mut start = 0;
+        while start < len {
+            let end = len.min(start + REPEAT_CHUNK / N);
+            for (dst, i) in expanded.chunks_exact_mut(N).zip(start..end) {
+                dst.copy_from_slice(&expand(byte_at(i)));
+            }
+            self.delayed_data(spi, &expanded[..(end - start) * N])?;
+            start = end;
+        }
+        Ok(())
     }
 
     /// Basic function for sending [Commands](Command) and the data belonging to it.
     ///
-    /// TODO: directly use ::write? cs wouldn't needed to be changed twice than
+    /// CS stays asserted from the command to the last byte of the data, only DC changes in
+    /// between. Some controllers take a release of CS as the end of the command.
     pub(crate) fn cmd_with_data<T: Command>(
         &mut self,
         spi: &mut SPI,
         command: T,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
-        self.cmd(spi, command)?;
-        self.data(spi, data)
+    ) -> Result<(), Error<SPI::Error>> {
+        // low for commands
+        self.dc
+            .set_low()
+            .map_err(|_| self.pin_failed(PinError::Dc))?;
+
+        self.select()?;
+        self.write(spi, &[command.address()])?;
+
+        // high for data
+        self.dc
+            .set_high()
+            .map_err(|_| self.pin_failed(PinError::Dc))?;
+
+        self.transfer = (0, data.len());
+        self.delayed_data(spi, data)?;
+        self.deselect()
     }
 
     /// Basic function for sending the same byte of data (one u8) multiple times over spi
     ///
+    /// Writes up to [REPEAT_CHUNK] bytes at once, the [DataDelay] still pauses after every
+    /// `chunk_size` bytes.
+    ///
     /// Enables direct interaction with the device with the help of [command()](ConnectionInterface::command())
     pub(crate) fn data_x_times(
         &mut self,
         spi: &mut SPI,
         val: u8,
         repetitions: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         // high for data
-        let _ = self.dc.set_high();
-        // Transfer data (u8) over spi
-        for _ in 0..repetitions {
-            self.write(spi, &[val])?;
+        self.dc
+            .set_high()
+            .map_err(|_| self.pin_failed(PinError::Dc))?;
+        // Transfer the data in chunks of a repeated buffer, one write per byte is slow
+        // (on linux every write is a syscall)
+        let chunk = [val; REPEAT_CHUNK];
+        self.transfer = (0, repetitions as usize);
+        self.select()?;
+        let mut remaining = repetitions as usize;
+        // bytes sent since the last pause of the data delay
+        let mut unpaused = 0;
+        while remaining > 0 {
+            let mut len = remaining.min(REPEAT_CHUNK);
+            if let Some(data_delay) = self.data_delay {
+                len = len.min(data_delay.chunk_size - unpaused);
+            }
+            self.write_data(spi, &chunk[..len])?;
+            remaining -= len;
+            if let Some(data_delay) = self.data_delay {
+                unpaused += len;
+                if unpaused == data_delay.chunk_size {
+                    data_delay.pause();
+                    unpaused = 0;
+                }
+            }
+        }
+        self.deselect()
+    }
+
+    // activate spi with cs low
+    //
+    // Refuses to talk to a sleeping or uninitialized display
+    fn select(&mut self) -> Result<(), Error<SPI::Error>> {
+        match self.state {
+            DisplayState::Asleep => return Err(Error::Asleep),
+            DisplayState::Uninitialized => return Err(Error::Uninitialized),
+            _ => {}
+        }
+        self.selected_bytes = 0;
+        self.cs.set_low().map_err(|_| self.pin_failed(PinError::Cs))
+    }
+
+    // deactivate spi with cs high
+    fn deselect(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.cs
+            .set_high()
+            .map_err(|_| self.pin_failed(PinError::Cs))
+    }
+
+    // Writes data while CS is asserted, calling the progress hook after every chunk
+    fn write_data(&mut self, spi: &mut SPI, mut data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        let hook = match self.progress {
+            Some(hook) => hook,
+            None => return self.write_released(spi, data),
+        };
+        while !data.is_empty() {
+            let (chunk, rest) = data.split_at(data.len().min(hook.chunk_size - self.unreported));
+            self.write_released(spi, chunk)?;
+            self.transfer.0 += chunk.len();
+            self.unreported += chunk.len();
+            if self.unreported == hook.chunk_size {
+                self.unreported = 0;
+                (hook.callback)(Progress::Data {
+                    sent: self.transfer.0,
+                    total: self.transfer.1,
+                });
+            }
+            data = rest;
         }
         Ok(())
     }
 
-    // spi write helper/abstraction function
-    fn write(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
-        // activate spi with cs low
-        let _ = self.cs.set_low();
+    // Writes data while CS is asserted, releasing CS in between if the
+    // cs release chunk size is reached, so other devices can use the bus
+    fn write_released(&mut self, spi: &mut SPI, mut data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        let chunk_size = match self.cs_release {
+            Some(chunk_size) => chunk_size,
+            None => return self.write(spi, data),
+        };
+        while !data.is_empty() {
+            if self.selected_bytes == chunk_size {
+                self.deselect()?;
+                self.select()?;
+            }
+            let (chunk, rest) = data.split_at(data.len().min(chunk_size - self.selected_bytes));
+            self.write(spi, chunk)?;
+            self.selected_bytes += chunk.len();
+            data = rest;
+        }
+        Ok(())
+    }
 
+    // spi write helper/abstraction function, CS needs to be asserted
+    //
+    // Marks the display as uninitialized if the transfer fails
+    fn write(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        Self::write_spi(spi, data).map_err(|e| {
+            self.state = DisplayState::Uninitialized;
+            Error::Spi(e)
+        })
+    }
+
+    fn write_spi(spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
         // transfer spi data
         // Be careful!! Linux has a default limit of 4096 bytes per spi transfer
         // see https://raspberrypi.stackexchange.com/questions/65595/spi-transfer-fails-with-buffer-size-greater-than-4096
@@ -111,32 +945,91 @@ where
             spi.write(data)?;
         }
 
-        // deactivate spi with cs high
-        let _ = self.cs.set_high();
-
         Ok(())
     }
 
-    /// Waits until device isn't busy anymore (busy == HIGH)
+    /// Waits until device isn't busy anymore
     ///
     /// This is normally handled by the more complicated commands themselves,
     /// but in the case you send data and commands directly you might need to check
     /// if the device is still busy
     ///
-    /// is_busy_low
+    /// `is_busy_low` is the level the controller drives while it works, the `IS_BUSY_LOW`
+    /// of the driver: true for the IL03xx/UC81xx style controllers, false for the SSD16xx ones
     ///
-    ///  - TRUE for epd4in2, epd2in13, epd2in7, epd5in83, epd7in5
-    ///  - FALSE for epd2in9, epd1in54 (for all Display Type A ones?)
-    ///
-    /// Most likely there was a mistake with the 2in9 busy connection
-    /// //TODO: use the #cfg feature to make this compile the right way for the certain types
-    pub(crate) fn wait_until_idle(&mut self, is_busy_low: bool) {
+    /// A sleeping display isn't waited for, the following command fails anyway
+    pub(crate) fn wait_until_idle(&mut self, is_busy_low: bool) -> Result<(), Error<SPI::Error>> {
+        if self.state == DisplayState::Asleep {
+            return Ok(());
+        }
         // //tested: worked without the delay for all tested devices
         // //self.delay_ms(1);
-        while self.is_busy(is_busy_low) {
+        let mut polls = 0;
+        while self.settled_busy(is_busy_low)? {
+            polls += 1;
+            self.report_busy(polls);
             // //tested: REMOVAL of DELAY: it's only waiting for the signal anyway and should continue work asap
             // //old: shorten the time? it was 100 in the beginning
             // //self.delay_ms(5);
+            self.busy_wait.wait(is_busy_low);
+        }
+        self.refresh_finished();
+        Ok(())
+    }
+
+    /// Tells the progress hook that the display was still busy at the `polls`th check
+    pub(crate) fn report_busy(&self, polls: u32) {
+        if let Some(hook) = self.progress {
+            (hook.callback)(Progress::Busy { polls });
+        }
+    }
+
+    /// Polls a running refresh, `WouldBlock` until the display isn't busy anymore
+    pub(crate) fn poll_refresh(&mut self, is_busy_low: bool) -> nb::Result<(), Error<SPI::Error>> {
+        if self.settled_busy(is_busy_low)? {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.refresh_finished();
+        Ok(())
+    }
+
+    /// The time of the clock to measure a power phase from, `None` without a clock
+    pub(crate) fn now(&self) -> Option<u32> {
+        self.clock.map(|clock| clock.now())
+    }
+
+    /// Records the time since `started` of [now](Self::now) as the power on of the next
+    /// refresh, call once POWER_ON finished
+    pub(crate) fn record_power_on(&mut self, started: Option<u32>) {
+        if let Some(ms) = self.elapsed(started) {
+            self.power_on_ms = ms;
+        }
+    }
+
+    /// Records the time since `started` of [now](Self::now) as the power off of the last
+    /// refresh, call once POWER_OFF finished
+    pub(crate) fn record_power_off(&mut self, started: Option<u32>) {
+        if let (Some(ms), Some(last)) = (self.elapsed(started), self.last_refresh.as_mut()) {
+            last.power_off_ms = ms;
+        }
+    }
+
+    // Milliseconds since `started` of the clock
+    fn elapsed(&self, started: Option<u32>) -> Option<u32> {
+        Some(self.now()?.wrapping_sub(started?))
+    }
+
+    /// Marks a running refresh as done, call after the busy pin was released
+    pub(crate) fn refresh_finished(&mut self) {
+        if self.state == DisplayState::Refreshing {
+            self.state = DisplayState::Idle;
+            if let Some(refresh_ms) = self.elapsed(Some(self.refresh_started)) {
+                self.last_refresh = Some(RefreshStats {
+                    power_on_ms: core::mem::take(&mut self.power_on_ms),
+                    refresh_ms,
+                    power_off_ms: 0,
+                });
+            }
         }
     }
 
@@ -146,34 +1039,129 @@ where
     /// but in the case you send data and commands directly you might need to check
     /// if the device is still busy
     ///
-    /// is_busy_low
-    ///
-    ///  - TRUE for epd4in2, epd2in13, epd2in7, epd5in83, epd7in5
-    ///  - FALSE for epd2in9, epd1in54 (for all Display Type A ones?)
+    /// `is_busy_low` is the level the controller drives while it works, the `IS_BUSY_LOW`
+    /// of the driver: true for the IL03xx/UC81xx style controllers, false for the SSD16xx ones
     ///
-    /// Most likely there was a mistake with the 2in9 busy connection
-    /// //TODO: use the #cfg feature to make this compile the right way for the certain types
-    pub(crate) fn is_busy(&self, is_busy_low: bool) -> bool {
-        (is_busy_low && self.busy.is_low().unwrap_or(false))
-            || (!is_busy_low && self.busy.is_high().unwrap_or(false))
+    /// Only debounced by a strict [BusyDebounce], the waits of the drivers use
+    /// [settled_busy](Self::settled_busy).
+    pub(crate) fn is_busy(&self, is_busy_low: bool) -> Result<bool, Error<SPI::Error>> {
+        match self.busy_debounce {
+            Some(debounce) if debounce.strict => self.settled_busy(is_busy_low),
+            _ => self.read_busy(is_busy_low),
+        }
+    }
+
+    /// Checks if device is still busy, with `samples` identical reads if there is a
+    /// [BusyDebounce]
+    pub(crate) fn settled_busy(&self, is_busy_low: bool) -> Result<bool, Error<SPI::Error>> {
+        let debounce = match self.busy_debounce {
+            Some(debounce) => debounce,
+            None => return self.read_busy(is_busy_low),
+        };
+        let mut busy = self.read_busy(is_busy_low)?;
+        let mut same = 1;
+        while same < debounce.samples {
+            debounce.pause();
+            let next = self.read_busy(is_busy_low)?;
+            if next == busy {
+                same += 1;
+            } else {
+                busy = next;
+                same = 1;
+            }
+        }
+        Ok(busy)
+    }
+
+    // A single read of the busy pin
+    fn read_busy(&self, is_busy_low: bool) -> Result<bool, Error<SPI::Error>> {
+        let busy = if is_busy_low {
+            self.busy.is_low()
+        } else {
+            self.busy.is_high()
+        };
+        busy.map_err(|_| Error::Pin(PinError::Busy))
     }
 
     /// Resets the device.
     ///
     /// Often used to awake the module from deep sleep. See [Epd4in2::sleep()](Epd4in2::sleep())
     ///
-    /// The timing of keeping the reset pin low seems to be important and different per device.
-    /// Most displays seem to require keeping it low for 10ms, but the 7in5_v2 only seems to reset
-    /// properly with 2ms
-    pub(crate) fn reset(&mut self, delay: &mut DELAY, duration: u8) {
-        let _ = self.rst.set_high();
+    /// The timing of keeping the reset asserted (the pin low, unless it is active high) seems
+    /// to be important and different per device. Most displays seem to require keeping it
+    /// asserted for 10ms, but the 7in5_v2 only seems to reset properly with 2ms
+    ///
+    /// Afterwards the display accepts commands again, even if it was asleep before, once the
+    /// controller released BUSY: the drivers wait for it right after the reset.
+    /// A power rail switched off by a power pin is enabled again first.
+    pub(crate) fn reset(
+        &mut self,
+        delay: &mut DELAY,
+        duration: u8,
+    ) -> Result<(), Error<SPI::Error>> {
+        if let Some(pwr) = self.pwr.as_mut() {
+            if !self.rail_on {
+                if let Err(pin) = pwr.set_power(true) {
+                    return Err(self.pin_failed(pin));
+                }
+                // let the power rail stabilize before the reset pulse
+                delay.delay_ms(POWER_SETTLE_MS);
+                self.rail_on = true;
+            }
+        }
+
+        self.set_reset(false)?;
         delay.delay_ms(10);
 
-        let _ = self.rst.set_low();
+        self.set_reset(true)?;
         delay.delay_ms(duration);
-        let _ = self.rst.set_high();
+        // the controller reports busy while it is held in reset
+        let held_busy = match self.busy_check {
+            Some((_, is_busy_low)) => self.read_busy(is_busy_low)?,
+            None => true,
+        };
+        self.set_reset(false)?;
         //TODO: the upstream libraries always sleep for 200ms here
         // 10ms works fine with just for the 7in5_v2 but this needs to be validated for other devices
+        // This also keeps the first busy poll of the drivers away from the first microseconds
+        // after the reset, in which BUSY may not be driven yet.
         delay.delay_ms(200);
+        if let Some((window_ms, is_busy_low)) = self.busy_check {
+            self.check_busy_released(delay, held_busy, window_ms, is_busy_low)?;
+        }
+        self.state = DisplayState::Idle;
+        Ok(())
+    }
+
+    // Fails with `BusyPinStuck` if the busy pin wasn't busy in the reset or stays busy
+    // for more than `window_ms` after it
+    fn check_busy_released(
+        &mut self,
+        delay: &mut DELAY,
+        held_busy: bool,
+        window_ms: u16,
+        is_busy_low: bool,
+    ) -> Result<(), Error<SPI::Error>> {
+        let level = |busy: bool| {
+            if busy == is_busy_low {
+                PinState::Low
+            } else {
+                PinState::High
+            }
+        };
+        if held_busy {
+            for waited in 0..=window_ms {
+                if !self.read_busy(is_busy_low)? {
+                    return Ok(());
+                }
+                if waited < window_ms {
+                    delay.delay_ms(1);
+                }
+            }
+        }
+        self.state = DisplayState::Uninitialized;
+        Err(Error::BusyPinStuck {
+            observed_level: level(held_busy),
+        })
     }
 }
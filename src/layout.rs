@@ -0,0 +1,325 @@
+//! Where the pixels are in the buffers: rotation, mirroring, row padding and packing
+//!
+//! Pure functions without any io, shared by the display buffers, the drivers and the frame
+//! tools. Positions as seen through rotation and mirroring are "drawn", positions in the
+//! unrotated buffer as the controllers get it are "buffer" positions.
+//!
+//! The rows of a buffer start at a full byte, the pixels are packed from the most
+//! significant bits down with `bits` bits each (1 for b/w and every plane of tri-color,
+//! 2 for gray, 4 for 7 color buffers) and the unused bits at the end of a row are padding.
+
+#[cfg(feature = "graphics")]
+use crate::graphics::{DisplayMirroring, DisplayRotation};
+
+/// Bytes of a row of `width` pixels with `bits` bits per pixel
+pub(crate) const fn stride(width: u32, bits: u32) -> u32 {
+    (width * bits).div_ceil(8)
+}
+
+/// Index of the byte and slot in that byte of the buffer pixel (x, y)
+pub(crate) const fn slot_position(width: u32, bits: u32, x: u32, y: u32) -> (usize, u32) {
+    let pixels = 8 / bits;
+    ((y * stride(width, bits) + x / pixels) as usize, x % pixels)
+}
+
+/// Shift of the bits of pixel `slot` in a byte with `bits` bits per pixel
+pub(crate) const fn shift(bits: u32, slot: u32) -> u32 {
+    8 - bits * (slot + 1)
+}
+
+/// Index of the byte and shift of the bits of the buffer pixel (x, y)
+#[cfg(feature = "graphics")]
+pub(crate) const fn bit_position(width: u32, bits: u32, x: u32, y: u32) -> (usize, u32) {
+    let (index, slot) = slot_position(width, bits, x, y);
+    (index, shift(bits, slot))
+}
+
+/// Size of a `width` x `height` buffer as drawn with `rotation`
+#[cfg(feature = "graphics")]
+pub(crate) const fn rotated_size(width: u32, height: u32, rotation: DisplayRotation) -> (u32, u32) {
+    match rotation {
+        DisplayRotation::Rotate0 | DisplayRotation::Rotate180 => (width, height),
+        DisplayRotation::Rotate90 | DisplayRotation::Rotate270 => (height, width),
+    }
+}
+
+/// Whether the drawn point (x, y) is on a `width` x `height` buffer drawn with `rotation`
+#[cfg(feature = "graphics")]
+pub(crate) fn contains(x: i32, y: i32, width: u32, height: u32, rotation: DisplayRotation) -> bool {
+    let (drawn_width, drawn_height) = rotated_size(width, height, rotation);
+    x >= 0 && y >= 0 && (x as u32) < drawn_width && (y as u32) < drawn_height
+}
+
+/// Buffer position of the drawn pixel (x, y), which needs to be on the display
+///
+/// Mirrors first, in drawn coordinates, then rotates.
+#[cfg(feature = "graphics")]
+pub(crate) const fn to_buffer(
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+) -> (u32, u32) {
+    let (drawn_width, drawn_height) = rotated_size(width, height, rotation);
+    let (x, y) = match mirroring {
+        DisplayMirroring::None => (x, y),
+        DisplayMirroring::MirrorX => (drawn_width - 1 - x, y),
+        DisplayMirroring::MirrorY => (x, drawn_height - 1 - y),
+    };
+    match rotation {
+        DisplayRotation::Rotate0 => (x, y),
+        DisplayRotation::Rotate90 => (width - 1 - y, x),
+        DisplayRotation::Rotate180 => (width - 1 - x, height - 1 - y),
+        DisplayRotation::Rotate270 => (y, height - 1 - x),
+    }
+}
+
+/// Drawn position of the buffer pixel (x, y), the inverse of [to_buffer]
+#[cfg(feature = "graphics")]
+pub(crate) const fn from_buffer(
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+) -> (u32, u32) {
+    let (x, y) = match rotation {
+        DisplayRotation::Rotate0 => (x, y),
+        DisplayRotation::Rotate90 => (y, width - 1 - x),
+        DisplayRotation::Rotate180 => (width - 1 - x, height - 1 - y),
+        DisplayRotation::Rotate270 => (height - 1 - y, x),
+    };
+    let (drawn_width, drawn_height) = rotated_size(width, height, rotation);
+    match mirroring {
+        DisplayMirroring::None => (x, y),
+        DisplayMirroring::MirrorX => (drawn_width - 1 - x, y),
+        DisplayMirroring::MirrorY => (x, drawn_height - 1 - y),
+    }
+}
+
+/// Buffer position of the drawn point (x, y), `None` if it isn't on the display
+#[cfg(feature = "graphics")]
+pub(crate) fn buffer_point(
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirroring: DisplayMirroring,
+) -> Option<(u32, u32)> {
+    if !contains(x, y, width, height, rotation) {
+        return None;
+    }
+    Some(to_buffer(
+        x as u32, y as u32, width, height, rotation, mirroring,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Small geometries: widths around every multiple of 8 and of every pixels per byte
+    const WIDTHS: core::ops::RangeInclusive<u32> = 1..=17;
+    const HEIGHTS: core::ops::RangeInclusive<u32> = 1..=5;
+    const BITS: [u32; 3] = [1, 2, 4];
+
+    #[test]
+    fn every_pixel_has_its_own_bits() {
+        extern crate std;
+        use std::vec;
+
+        for bits in BITS {
+            for width in WIDTHS {
+                for height in HEIGHTS {
+                    let stride = stride(width, bits);
+                    // one for every bit of the buffer
+                    let mut used = vec![false; (stride * height * 8) as usize];
+                    for y in 0..height {
+                        for x in 0..width {
+                            let (index, shift) = bit_position(width, bits, x, y);
+                            assert!(index < (stride * height) as usize);
+                            assert_eq!(shift % bits, 0);
+                            assert!(shift + bits <= 8);
+                            // the bits are in reading order from the first bit of the row
+                            let first = index * 8 + (8 - bits - shift) as usize;
+                            assert_eq!(first, (y * stride * 8 + x * bits) as usize);
+                            for bit in used.iter_mut().skip(first).take(bits as usize) {
+                                assert!(!*bit, "{} bits, {}x{}", bits, width, height);
+                                *bit = true;
+                            }
+                        }
+                    }
+                    // only the padding at the end of every row is left
+                    for (bit, &used) in used.iter().enumerate() {
+                        let in_row = bit as u32 % (stride * 8);
+                        assert_eq!(used, in_row < width * bits);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rows_start_at_full_bytes() {
+        assert_eq!(stride(12, 1), 2);
+        assert_eq!(stride(16, 1), 2);
+        assert_eq!(stride(17, 1), 3);
+        assert_eq!(stride(5, 2), 2);
+        assert_eq!(stride(3, 4), 2);
+        // the first pixels of a row are in the most significant bits
+        assert_eq!(bit_position(12, 1, 0, 1), (2, 7));
+        assert_eq!(bit_position(12, 1, 11, 1), (3, 4));
+        assert_eq!(bit_position(3, 4, 1, 0), (0, 0));
+        assert_eq!(bit_position(3, 4, 2, 0), (1, 4));
+        assert_eq!(bit_position(5, 2, 4, 2), (5, 6));
+    }
+
+    #[cfg(feature = "graphics")]
+    const ROTATIONS: [DisplayRotation; 4] = [
+        DisplayRotation::Rotate0,
+        DisplayRotation::Rotate90,
+        DisplayRotation::Rotate180,
+        DisplayRotation::Rotate270,
+    ];
+
+    #[cfg(feature = "graphics")]
+    const MIRRORINGS: [DisplayMirroring; 3] = [
+        DisplayMirroring::None,
+        DisplayMirroring::MirrorX,
+        DisplayMirroring::MirrorY,
+    ];
+
+    // Every geometry as (width, height, rotation, mirroring)
+    #[cfg(feature = "graphics")]
+    fn geometries() -> impl Iterator<Item = (u32, u32, DisplayRotation, DisplayMirroring)> {
+        WIDTHS.flat_map(|width| {
+            HEIGHTS.flat_map(move |height| {
+                ROTATIONS.iter().flat_map(move |&rotation| {
+                    MIRRORINGS
+                        .iter()
+                        .map(move |&mirroring| (width, height, rotation, mirroring))
+                })
+            })
+        })
+    }
+
+    #[test]
+    #[cfg(feature = "graphics")]
+    fn rotation_and_mirroring_are_bijections() {
+        extern crate std;
+        use std::vec;
+
+        for (width, height, rotation, mirroring) in geometries() {
+            let (drawn_width, drawn_height) = rotated_size(width, height, rotation);
+            assert_eq!(drawn_width * drawn_height, width * height);
+            let mut hit = vec![false; (width * height) as usize];
+            for y in 0..drawn_height {
+                for x in 0..drawn_width {
+                    let (bx, by) = to_buffer(x, y, width, height, rotation, mirroring);
+                    assert!(bx < width && by < height);
+                    assert!(!hit[(by * width + bx) as usize]);
+                    hit[(by * width + bx) as usize] = true;
+                    // round trip through the inverse
+                    let back = from_buffer(bx, by, width, height, rotation, mirroring);
+                    assert_eq!(
+                        back,
+                        (x, y),
+                        "{:?} {:?} {}x{}",
+                        rotation,
+                        mirroring,
+                        width,
+                        height
+                    );
+                }
+            }
+            assert!(hit.iter().all(|&hit| hit));
+            for by in 0..height {
+                for bx in 0..width {
+                    let (x, y) = from_buffer(bx, by, width, height, rotation, mirroring);
+                    assert_eq!(
+                        to_buffer(x, y, width, height, rotation, mirroring),
+                        (bx, by)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "graphics")]
+    fn corners_of_the_drawing() {
+        for (width, height, rotation, mirroring) in geometries() {
+            let (w, h) = (width - 1, height - 1);
+            // buffer position of the top left corner of the drawing
+            let top_left = match (rotation, mirroring) {
+                (DisplayRotation::Rotate0, DisplayMirroring::None) => (0, 0),
+                (DisplayRotation::Rotate0, DisplayMirroring::MirrorX) => (w, 0),
+                (DisplayRotation::Rotate0, DisplayMirroring::MirrorY) => (0, h),
+                (DisplayRotation::Rotate90, DisplayMirroring::None) => (w, 0),
+                (DisplayRotation::Rotate90, DisplayMirroring::MirrorX) => (w, h),
+                (DisplayRotation::Rotate90, DisplayMirroring::MirrorY) => (0, 0),
+                (DisplayRotation::Rotate180, DisplayMirroring::None) => (w, h),
+                (DisplayRotation::Rotate180, DisplayMirroring::MirrorX) => (0, h),
+                (DisplayRotation::Rotate180, DisplayMirroring::MirrorY) => (w, 0),
+                (DisplayRotation::Rotate270, DisplayMirroring::None) => (0, h),
+                (DisplayRotation::Rotate270, DisplayMirroring::MirrorX) => (0, 0),
+                (DisplayRotation::Rotate270, DisplayMirroring::MirrorY) => (w, h),
+            };
+            assert_eq!(
+                to_buffer(0, 0, width, height, rotation, mirroring),
+                top_left
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "graphics")]
+    fn points_off_the_display() {
+        for (width, height, rotation, mirroring) in geometries() {
+            let (drawn_width, drawn_height) = rotated_size(width, height, rotation);
+            let (dw, dh) = (drawn_width as i32, drawn_height as i32);
+            for (x, y) in [(-1, 0), (0, -1), (dw, 0), (0, dh), (i32::MIN, i32::MAX)] {
+                assert!(!contains(x, y, width, height, rotation));
+                assert_eq!(buffer_point(x, y, width, height, rotation, mirroring), None);
+            }
+            for (x, y) in [(0, 0), (dw - 1, dh - 1)] {
+                assert!(contains(x, y, width, height, rotation));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "graphics")]
+    fn drawn_pixels_cover_the_buffer_once() {
+        extern crate std;
+        use std::vec;
+
+        for (width, height, rotation, mirroring) in geometries() {
+            let (drawn_width, drawn_height) = rotated_size(width, height, rotation);
+            for bits in BITS {
+                let stride = stride(width, bits);
+                let mut used = vec![0u8; (stride * height) as usize];
+                for y in 0..drawn_height as i32 {
+                    for x in 0..drawn_width as i32 {
+                        let (bx, by) =
+                            buffer_point(x, y, width, height, rotation, mirroring).unwrap();
+                        let (index, shift) = bit_position(width, bits, bx, by);
+                        let mask = ((1u16 << bits) - 1) as u8;
+                        assert_eq!(used[index] & (mask << shift), 0);
+                        used[index] |= mask << shift;
+                    }
+                }
+                // every byte is full but the last one of a row, which has the padding
+                let padding = stride * 8 - width * bits;
+                for row in used.chunks(stride as usize) {
+                    assert!(row[..row.len() - 1].iter().all(|&byte| byte == 0xff));
+                    assert_eq!(row[row.len() - 1], 0xff << padding);
+                }
+            }
+        }
+    }
+}
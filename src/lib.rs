@@ -12,7 +12,7 @@
 //!
 //!```rust, no_run
 //!# use embedded_hal_mock::*;
-//!# fn main() -> Result<(), MockError> {
+//!# fn main() -> Result<(), epd_waveshare::Error<MockError>> {
 //!use embedded_graphics::{
 //!    pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
 //!};
@@ -28,7 +28,8 @@
 //!# let mut delay = delay::MockNoop::new();
 //!
 //!// Setup EPD
-//!let mut epd = Epd1in54::new(&mut spi, cs_pin, busy_in, dc, rst, &mut delay)?;
+//!let mut epd = Epd1in54::new(cs_pin, busy_in, dc, rst);
+//!epd.init(&mut spi, &mut delay)?;
 //!
 //!// Use display graphics from embedded-graphics
 //!let mut display = Display1in54::default();
@@ -63,25 +64,95 @@
 #![no_std]
 #![deny(missing_docs)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
+
 #[cfg(feature = "graphics")]
 pub mod graphics;
 
+#[cfg(feature = "graphics")]
+pub mod display;
+
 mod traits;
 
+mod layout;
+
 pub mod color;
 
+pub mod frame;
+
+pub mod gray;
+
+pub mod error;
+pub use error::{Error, PinError};
+pub use interface::{
+    BusyDebounce, BusyPoll, BusyWait, Clock, DataDelay, PowerPin, Progress, ProgressHook,
+    RefreshStats,
+};
+
+pub mod powered;
+
+pub mod stream;
+
+pub mod sram;
+
+pub mod dma;
+
+pub mod generic;
+
+pub mod pattern;
+
+#[cfg(feature = "std")]
+pub mod dump;
+
+#[cfg(feature = "std")]
+pub mod fake;
+
+#[cfg(feature = "image")]
+pub mod picture;
+
+#[cfg(feature = "graphics")]
+pub mod double_buffer;
+
+#[cfg(feature = "graphics")]
+pub mod damage;
+
+#[cfg(feature = "graphics")]
+pub mod flush;
+
+#[cfg(feature = "graphics")]
+pub mod flush_transform;
+
+#[cfg(feature = "graphics")]
+pub mod bitmap;
+
+#[cfg(feature = "graphics")]
+pub mod dither;
+
+#[cfg(feature = "graphics")]
+pub mod direct;
+
 /// Interface for the physical connection between display and the controlling device
 mod interface;
 
+#[cfg(test)]
+pub(crate) mod mock;
+
 pub mod epd1in54;
 pub mod epd1in54b;
 pub mod epd1in54c;
 pub mod epd2in13_v2;
+pub mod epd2in13_v3;
+pub mod epd2in13_v4;
 pub mod epd2in13bc;
 pub mod epd2in7b;
 pub mod epd2in9;
 pub mod epd2in9_v2;
 pub mod epd2in9bc;
+pub mod epd3in7;
 pub mod epd4in2;
 pub mod epd5in65f;
 pub mod epd7in5;
@@ -92,15 +163,24 @@ pub(crate) mod type_a;
 
 /// Includes everything important besides the chosen Display
 pub mod prelude {
-    pub use crate::color::{Color, OctColor, TriColor};
+    pub use crate::color::{Color, OctColor, TriColor, TriColorFrame};
     pub use crate::traits::{
-        QuickRefresh, RefreshLut, WaveshareDisplay, WaveshareThreeColorDisplay,
+        DisplayState, HardwareFlip, QuickRefresh, RefreshLut, StreamFrame, WaveshareDisplay,
+        WaveshareThreeColorDisplay,
     };
 
+    pub use crate::powered::PoweredEpd;
     pub use crate::SPI_MODE;
 
     #[cfg(feature = "graphics")]
-    pub use crate::graphics::{Display, DisplayRotation, OctDisplay, TriDisplay};
+    pub use crate::flush::Flush;
+    #[cfg(feature = "graphics")]
+    pub use crate::graphics::{
+        Display, DisplayMirroring, DisplayRotation, GrayDisplay, OctDisplay, TriDisplay,
+    };
+
+    #[cfg(feature = "alloc")]
+    pub use crate::graphics::BoxDisplay;
 }
 
 /// Computes the needed buffer length. Takes care of rounding up in case width
@@ -113,7 +193,7 @@ pub mod prelude {
 /// \[XXXXX210\]\[76543210\]...\[76543210\] | height
 /// \[XXXXX210\]\[76543210\]...\[76543210\] v
 pub const fn buffer_len(width: usize, height: usize) -> usize {
-    (width + 7) / 8 * height
+    width.div_ceil(8) * height
 }
 
 use embedded_hal::spi::{Mode, Phase, Polarity};
@@ -0,0 +1,430 @@
+//! Test doubles which record everything the drivers send to the display
+
+extern crate std;
+
+use crate::traits::WaveshareDisplay;
+use core::cell::{Cell, RefCell};
+use core::convert::Infallible;
+use embedded_hal::{
+    blocking::{delay::DelayMs, spi::Write},
+    digital::v2::{InputPin, OutputPin},
+};
+use std::collections::VecDeque;
+use std::format;
+use std::rc::Rc;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+/// One command or a block of data as seen by the display
+///
+/// Waits for the busy pin and delays only get logged with [`Log::busy`] and [`Log::delay`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) enum Transfer {
+    Command(u8),
+    Data(Vec<u8>),
+    /// The driver waited for the busy pin to go high
+    WaitUntilHigh,
+    /// The driver waited for the busy pin to go low
+    WaitUntilLow,
+    /// Delay in ms
+    Delay(u32),
+}
+
+#[derive(Default)]
+struct Bus {
+    data_mode: bool,
+    transfers: Vec<Transfer>,
+    writes: usize,
+    selects: usize,
+    selected_bytes: usize,
+    longest_selection: usize,
+    selected: bool,
+    selections: Vec<Vec<u8>>,
+    busy_script: Option<VecDeque<bool>>,
+    busy_reads: usize,
+}
+
+/// Shared log of all transfers, hand out the spi bus and dc pin with [`Log::spi`] and [`Log::dc`]
+#[derive(Default, Clone)]
+pub(crate) struct Log(Rc<RefCell<Bus>>);
+
+impl Log {
+    pub fn spi(&self) -> Spi {
+        Spi(self.clone())
+    }
+
+    pub fn dc(&self) -> DcPin {
+        DcPin(self.clone())
+    }
+
+    pub fn cs(&self) -> CsPin {
+        CsPin(self.clone())
+    }
+
+    pub fn busy(&self) -> LogBusyPin {
+        LogBusyPin(self.clone(), Cell::new(false))
+    }
+
+    /// Let the [`LogBusyPin`] read `levels` (`true` for high) instead of toggling
+    ///
+    /// Appends to the levels not read yet. A read after the script ran out panics, so a
+    /// wait the test didn't expect fails instead of looping forever.
+    pub fn script_busy(&self, levels: &[bool]) {
+        self.0
+            .borrow_mut()
+            .busy_script
+            .get_or_insert_with(VecDeque::new)
+            .extend(levels);
+    }
+
+    /// How often the busy pin was read
+    pub fn busy_reads(&self) -> usize {
+        self.0.borrow().busy_reads
+    }
+
+    pub fn delay(&self) -> LogDelay {
+        LogDelay(self.clone())
+    }
+
+    /// All transfers so far, consecutive data writes are merged into one
+    pub fn transfers(&self) -> Vec<Transfer> {
+        self.0.borrow().transfers.clone()
+    }
+
+    /// The transfers as text, one line per command with its data, e.g. `06: 17 17 28 17`
+    ///
+    /// Data of more than 16 bytes is summarized, e.g. `10: 4000 bytes of FF`, which keeps
+    /// golden transcripts of whole sequences readable.
+    pub fn transcript(&self) -> Vec<String> {
+        fn bytes(data: &[u8]) -> String {
+            if data.len() <= 16 {
+                let hex: Vec<String> = data.iter().map(|b| format!("{:02X}", b)).collect();
+                hex.join(" ")
+            } else if data.iter().all(|&b| b == data[0]) {
+                format!("{} bytes of {:02X}", data.len(), data[0])
+            } else {
+                format!("{} bytes", data.len())
+            }
+        }
+
+        let mut lines: Vec<String> = Vec::new();
+        let mut after_command = false;
+        for transfer in self.0.borrow().transfers.iter() {
+            let line = match transfer {
+                Transfer::Command(c) => format!("{:02X}", c),
+                Transfer::Data(data) if after_command => {
+                    let line = lines.last_mut().unwrap();
+                    *line = format!("{}: {}", line, bytes(data));
+                    after_command = false;
+                    continue;
+                }
+                Transfer::Data(data) => format!("data {}", bytes(data)),
+                Transfer::WaitUntilHigh => "wait until high".to_string(),
+                Transfer::WaitUntilLow => "wait until low".to_string(),
+                Transfer::Delay(ms) => format!("delay {}ms", ms),
+            };
+            after_command = matches!(transfer, Transfer::Command(_));
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// How often `command` was sent
+    pub fn count_command(&self, command: u8) -> usize {
+        self.0
+            .borrow()
+            .transfers
+            .iter()
+            .filter(|t| **t == Transfer::Command(command))
+            .count()
+    }
+
+    /// The data sent right after the last `command`, empty if there was none
+    pub fn data_after(&self, command: u8) -> Vec<u8> {
+        let log = self.0.borrow();
+        let transfers = &log.transfers;
+        let next = transfers
+            .iter()
+            .rposition(|t| *t == Transfer::Command(command))
+            .and_then(|i| transfers.get(i + 1));
+        match next {
+            Some(Transfer::Data(data)) => data.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The data sent right after every `command`, in the order they were sent
+    pub fn data_after_each(&self, command: u8) -> Vec<Vec<u8>> {
+        self.0
+            .borrow()
+            .transfers
+            .windows(2)
+            .filter_map(|pair| match pair {
+                [Transfer::Command(c), Transfer::Data(data)] if *c == command => Some(data.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// How many spi writes there were, commands and data
+    pub fn writes(&self) -> usize {
+        self.0.borrow().writes
+    }
+
+    /// How often the cs pin was asserted
+    pub fn selects(&self) -> usize {
+        self.0.borrow().selects
+    }
+
+    /// Most bytes written while the cs pin was asserted once
+    pub fn longest_selection(&self) -> usize {
+        self.0.borrow().longest_selection
+    }
+
+    /// The bytes written while the cs pin was asserted, commands and data, one entry per
+    /// assertion
+    pub fn selections(&self) -> Vec<Vec<u8>> {
+        self.0.borrow().selections.clone()
+    }
+
+    pub fn clear(&self) {
+        let mut bus = self.0.borrow_mut();
+        bus.transfers.clear();
+        bus.writes = 0;
+        bus.selects = 0;
+        bus.longest_selection = 0;
+        bus.selections.clear();
+        bus.busy_reads = 0;
+    }
+}
+
+pub(crate) struct Spi(Log);
+
+impl Write<u8> for Spi {
+    type Error = Infallible;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        let mut bus = (self.0).0.borrow_mut();
+        bus.writes += 1;
+        bus.selected_bytes += words.len();
+        bus.longest_selection = bus.longest_selection.max(bus.selected_bytes);
+        if bus.selected {
+            bus.selections.last_mut().unwrap().extend_from_slice(words);
+        }
+        if bus.data_mode {
+            if let Some(Transfer::Data(data)) = bus.transfers.last_mut() {
+                data.extend_from_slice(words);
+                return Ok(());
+            }
+            bus.transfers.push(Transfer::Data(words.to_vec()));
+        } else {
+            for &command in words {
+                bus.transfers.push(Transfer::Command(command));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Data/Command pin, decides how the next spi writes get logged
+pub(crate) struct DcPin(Log);
+
+impl OutputPin for DcPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        (self.0).0.borrow_mut().data_mode = false;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        (self.0).0.borrow_mut().data_mode = true;
+        Ok(())
+    }
+}
+
+/// Chip select pin, counts how often and for how many bytes the display got selected
+pub(crate) struct CsPin(Log);
+
+impl OutputPin for CsPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        let mut bus = (self.0).0.borrow_mut();
+        bus.selects += 1;
+        bus.selected_bytes = 0;
+        bus.selected = true;
+        bus.selections.push(Vec::new());
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        (self.0).0.borrow_mut().selected = false;
+        Ok(())
+    }
+}
+
+/// Busy pin which toggles on every read like [`BusyPin`] and logs what the driver waits for
+///
+/// Consecutive reads for the same level are logged as one wait. With [`Log::script_busy`]
+/// it reads the scripted levels instead of toggling.
+pub(crate) struct LogBusyPin(Log, Cell<bool>);
+
+impl LogBusyPin {
+    fn read(&self, wait: Transfer) -> bool {
+        let mut bus = (self.0).0.borrow_mut();
+        if bus.transfers.last() != Some(&wait) {
+            bus.transfers.push(wait);
+        }
+        bus.busy_reads += 1;
+        match bus.busy_script.as_mut() {
+            Some(script) => script
+                .pop_front()
+                .expect("busy read after the script ran out"),
+            None => {
+                let high = self.1.get();
+                self.1.set(!high);
+                high
+            }
+        }
+    }
+}
+
+impl InputPin for LogBusyPin {
+    type Error = Infallible;
+
+    // busy while high, so the driver waits for low
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.read(Transfer::WaitUntilLow))
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.read(Transfer::WaitUntilHigh))
+    }
+}
+
+/// Delay which returns immediately and logs how long it should have been
+pub(crate) struct LogDelay(Log);
+
+impl DelayMs<u8> for LogDelay {
+    fn delay_ms(&mut self, ms: u8) {
+        (self.0)
+            .0
+            .borrow_mut()
+            .transfers
+            .push(Transfer::Delay(ms.into()));
+    }
+}
+
+/// Output pin which ignores everything
+#[derive(Default)]
+pub(crate) struct Pin;
+
+impl OutputPin for Pin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Output pin which remembers every level it was set to, clones share the levels
+#[derive(Default, Clone)]
+pub(crate) struct LevelPin(Rc<RefCell<Vec<bool>>>);
+
+impl LevelPin {
+    pub fn is_high(&self) -> bool {
+        self.0.borrow().last() == Some(&true)
+    }
+
+    /// All levels so far, `true` for high
+    pub fn levels(&self) -> Vec<bool> {
+        self.0.borrow().clone()
+    }
+}
+
+impl OutputPin for LevelPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.borrow_mut().push(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.borrow_mut().push(true);
+        Ok(())
+    }
+}
+
+/// Output pin which fails once `fail` was set, clones share the flag
+#[derive(Default, Clone)]
+pub(crate) struct FailingPin(Rc<Cell<bool>>);
+
+impl FailingPin {
+    pub fn fail(&self) {
+        self.0.set(true);
+    }
+
+    fn result(&self) -> Result<(), ()> {
+        if self.0.get() {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl OutputPin for FailingPin {
+    type Error = ();
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.result()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.result()
+    }
+}
+
+/// Busy pin which toggles on every read, so every busy wait ends
+/// independent of the polarity the driver waits for
+#[derive(Default)]
+pub(crate) struct BusyPin(Cell<bool>);
+
+impl InputPin for BusyPin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        let high = self.0.get();
+        self.0.set(!high);
+        Ok(high)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+/// Delay which returns immediately
+pub(crate) type Delay = embedded_hal_mock::delay::MockNoop;
+
+/// A driver on a fresh [Log] after its [init](WaveshareDisplay::init)
+///
+/// The driver gets the dc pin of the log and a toggling [BusyPin]. The init stays in the
+/// log, tests which only look at what comes after it [clear](Log::clear) it first.
+pub(crate) fn init_epd<EPD>() -> (Log, Spi, Delay, EPD)
+where
+    EPD: WaveshareDisplay<Spi, Pin, BusyPin, DcPin, Pin, Delay>,
+{
+    let log = Log::default();
+    let mut spi = log.spi();
+    let mut delay = Delay::new();
+    let mut epd = EPD::new(Pin, BusyPin::default(), log.dc(), Pin);
+    epd.init(&mut spi, &mut delay).unwrap();
+    (log, spi, delay, epd)
+}
@@ -0,0 +1,227 @@
+//! Test patterns which are generated while they are sent
+//!
+//! [test_pattern](crate::traits::WaveshareDisplay::test_pattern) of every driver sends one
+//! of these patterns over the whole display, without a frame buffer and without the
+//! `graphics` feature. That rules out the drawing code when bringing up new hardware:
+//!
+//! - b/w panels get a [checkerboard] inside of a one pixel border,
+//! - tri-color panels black and chromatic [halves] inside of a black border,
+//! - 7 color panels vertical [color_bars] of every [OctColor].
+//!
+//! All of them cover every pixel, so a wrong width or a packing problem shows up as slanted
+//! or broken edges, and a frame which is cut off or shifted by a missing border.
+//!
+//! [frame_byte] gives the bytes like the buffers of the displays for these or any other
+//! pattern, e.g. to compare them with a frame sent by other means:
+//!
+//! ```
+//! use epd_waveshare::{color::Color, pattern};
+//!
+//! // the top left byte is the border
+//! assert_eq!(pattern::frame_byte(16, 16, 0, 0, pattern::checkerboard), 0x00);
+//! // the second row starts with the border and the black square in the corner
+//! assert_eq!(pattern::frame_byte(16, 16, 0, 2, pattern::checkerboard), 0x00);
+//! assert_eq!(pattern::checkerboard(16, 16, 8, 1), Color::White);
+//! ```
+
+use crate::color::{Color, ColorType, OctColor, TriColor};
+use crate::error::Error;
+use crate::traits::StreamFrame;
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::*,
+};
+
+/// Edge length of the squares of the [checkerboard] in pixels
+pub const SQUARE: u32 = 8;
+
+/// All 7 colors and the clean color of the 7 color panels, in the order of their nibbles
+const BARS: [OctColor; 8] = [
+    OctColor::Black,
+    OctColor::White,
+    OctColor::Green,
+    OctColor::Blue,
+    OctColor::Red,
+    OctColor::Yellow,
+    OctColor::Orange,
+    OctColor::HiZ,
+];
+
+fn on_border(width: u32, height: u32, x: u32, y: u32) -> bool {
+    x == 0 || y == 0 || x + 1 == width || y + 1 == height
+}
+
+/// Black and white squares of [SQUARE] pixels inside of a black border one pixel wide
+///
+/// The square in the top left corner is black.
+pub fn checkerboard(width: u32, height: u32, x: u32, y: u32) -> Color {
+    if on_border(width, height, x, y) || (x / SQUARE + y / SQUARE).is_multiple_of(2) {
+        Color::Black
+    } else {
+        Color::White
+    }
+}
+
+/// The left half black and the right half chromatic, inside of a black border one pixel wide
+pub fn halves(width: u32, height: u32, x: u32, y: u32) -> TriColor {
+    if on_border(width, height, x, y) || x < width / 2 {
+        TriColor::Black
+    } else {
+        TriColor::Chromatic
+    }
+}
+
+/// Eight vertical bars of equal width, from the left: every [OctColor] in the order of
+/// their nibbles
+pub fn color_bars(width: u32, _height: u32, x: u32, _y: u32) -> OctColor {
+    BARS[(x as usize * BARS.len()) / width as usize]
+}
+
+/// The colors of the drivers with the pattern their
+/// [test_pattern](crate::traits::WaveshareDisplay::test_pattern) sends
+pub trait PatternColor: ColorType {
+    /// Color of the pixel (x, y) of the pattern on a `width` x `height` display
+    fn pattern(width: u32, height: u32, x: u32, y: u32) -> Self;
+}
+
+impl PatternColor for Color {
+    fn pattern(width: u32, height: u32, x: u32, y: u32) -> Self {
+        checkerboard(width, height, x, y)
+    }
+}
+
+impl PatternColor for TriColor {
+    fn pattern(width: u32, height: u32, x: u32, y: u32) -> Self {
+        halves(width, height, x, y)
+    }
+}
+
+impl PatternColor for OctColor {
+    fn pattern(width: u32, height: u32, x: u32, y: u32) -> Self {
+        color_bars(width, height, x, y)
+    }
+}
+
+/// Byte `index` of `plane` of a `width` x `height` frame of `C` like the buffers of the
+/// displays, with the pixel (x, y) in the color `pattern(width, height, x, y)`
+///
+/// The padding bits at the end of a row are in the background color.
+pub fn frame_byte<C: ColorType>(
+    width: u32,
+    height: u32,
+    plane: usize,
+    index: usize,
+    pattern: impl Fn(u32, u32, u32, u32) -> C,
+) -> u8 {
+    let pixels = C::PIXELS_PER_BYTE;
+    let stride = width.div_ceil(pixels) as usize;
+    let (y, first) = ((index / stride) as u32, (index % stride) as u32 * pixels);
+    let mut byte = C::BACKGROUND.fill_byte(plane);
+    for slot in 0..pixels {
+        let x = first + slot;
+        if x < width {
+            pattern(width, height, x, y).pack(plane, slot, &mut byte);
+        }
+    }
+    byte
+}
+
+// The bytes of `plane` of a whole `width` x `height` frame of `pattern`, by index
+pub(crate) fn bytes<C: ColorType>(
+    width: u32,
+    height: u32,
+    plane: usize,
+    pattern: fn(u32, u32, u32, u32) -> C,
+) -> impl Fn(usize) -> u8 {
+    move |index| frame_byte(width, height, plane, index, pattern)
+}
+
+// Sends the `len` bytes of `byte_at` as a frame through `StreamFrame`, a chunk at a time
+pub(crate) fn stream<SPI, CS, BUSY, DC, RST, DELAY, EPD>(
+    epd: &mut EPD,
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    len: usize,
+    byte_at: impl Fn(usize) -> u8,
+) -> Result<(), Error<SPI::Error>>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    EPD: StreamFrame<SPI, CS, BUSY, DC, RST, DELAY>,
+{
+    let mut chunk = [0; 128];
+    epd.begin_frame(spi, delay)?;
+    let mut start = 0;
+    while start < len {
+        let end = len.min(start + chunk.len());
+        for (byte, i) in chunk.iter_mut().zip(start..end) {
+            *byte = byte_at(i);
+        }
+        epd.frame_data(spi, &chunk[..end - start])?;
+        start = end;
+    }
+    epd.end_frame(spi, delay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkerboard_with_border() {
+        // 12 pixels wide, so the second byte of a row has 4 padding bits
+        let rows: [[u8; 2]; 3] = [
+            [
+                frame_byte(12, 10, 0, 0, checkerboard),
+                frame_byte(12, 10, 0, 1, checkerboard),
+            ],
+            [
+                frame_byte(12, 10, 0, 2, checkerboard),
+                frame_byte(12, 10, 0, 3, checkerboard),
+            ],
+            [
+                frame_byte(12, 10, 0, 18, checkerboard),
+                frame_byte(12, 10, 0, 19, checkerboard),
+            ],
+        ];
+        assert_eq!(rows[0], [0x00, 0x0f]);
+        // border, black square, white square, border and padding
+        assert_eq!(rows[1], [0x00, 0b1110_1111]);
+        // the last row is the border
+        assert_eq!(rows[2], [0x00, 0x0f]);
+        // the second row of squares starts white
+        assert_eq!(frame_byte(12, 10, 0, 2 * 8, checkerboard), 0b0111_1111);
+    }
+
+    #[test]
+    fn halves_on_both_planes() {
+        // a row in the middle: the border and the left half are black, the right half
+        // chromatic, cleared bits are the color on both planes
+        let bw: [u8; 2] = [
+            frame_byte(16, 4, 0, 2, halves),
+            frame_byte(16, 4, 0, 3, halves),
+        ];
+        let chromatic: [u8; 2] = [
+            frame_byte(16, 4, 1, 2, halves),
+            frame_byte(16, 4, 1, 3, halves),
+        ];
+        assert_eq!(bw, [0x00, 0xfe]);
+        assert_eq!(chromatic, [0xff, 0x01]);
+    }
+
+    #[test]
+    fn bars_of_every_color() {
+        // two pixels per byte, one byte per bar
+        let row: [u8; 8] = core::array::from_fn(|i| frame_byte(16, 2, 0, i, color_bars));
+        let expected: [u8; 8] = core::array::from_fn(|i| {
+            let color = BARS[i];
+            OctColor::colors_byte(color, color)
+        });
+        assert_eq!(row, expected);
+        assert_eq!(frame_byte(16, 2, 0, 8, color_bars), row[0]);
+    }
+}
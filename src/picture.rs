@@ -0,0 +1,395 @@
+//! Photos from png and jpeg files on std targets
+//!
+//! Opens images with the `image` crate, scales them to the display and dithers them with
+//! the [Ditherer] to the colors of a palette. [open] and
+//! [load_from_memory] turn jpegs upright according to their exif orientation, like image
+//! viewers do for photos of phones and cameras.
+//!
+//! The picture can either be drawn into a display buffer with [draw], which follows the
+//! rotation of the buffer, or be sent straight to the display row by row with [stream],
+//! without a frame buffer.
+//!
+//! Images which don't have the aspect ratio of the display are either letterboxed with the
+//! background color or cropped, see [Fit]. Transparent pixels are blended with the
+//! background color.
+//!
+//! Example:
+//! ```rust,no_run
+//! # use epd_waveshare::{dither::BW_PALETTE, epd7in5_v2::Display7in5, picture, prelude::*};
+//! let image = picture::open("photo.jpg").unwrap();
+//!
+//! let mut display = Display7in5::default();
+//! display.set_rotation(DisplayRotation::Rotate90);
+//! let _ = picture::draw(&mut display, &image, picture::Fit::Crop, Color::White, &BW_PALETTE);
+//! ```
+
+use crate::dither::{line_buffer_len, palette_rgb, Ditherer, Palette};
+use crate::stream::{stream_frame, StreamColor, StreamError};
+use crate::traits::StreamFrame;
+use ::image::{
+    imageops::FilterType, DynamicImage, ImageDecoder, ImageReader, ImageResult, RgbaImage,
+};
+use embedded_graphics_core::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics_core::prelude::*;
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::*,
+};
+use std::io::{BufRead, Cursor, Seek};
+use std::path::Path;
+use std::vec;
+use std::vec::Vec;
+
+/// How an image with a different aspect ratio is fitted into the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fit {
+    /// Scales the whole image into the display and fills the bars on the sides with the
+    /// background color
+    Letterbox,
+    /// Scales the image to cover the whole display and cuts off the same amount on both
+    /// sides of the direction which is too long
+    Crop,
+}
+
+/// Opens an image file and turns it upright according to its orientation metadata
+///
+/// The format is guessed from the content, png and jpeg are supported.
+pub fn open<P: AsRef<Path>>(path: P) -> ImageResult<DynamicImage> {
+    decode(ImageReader::open(path)?.with_guessed_format()?)
+}
+
+/// Like [open] for an image in memory, e.g. downloaded over http
+pub fn load_from_memory(data: &[u8]) -> ImageResult<DynamicImage> {
+    decode(ImageReader::new(Cursor::new(data)).with_guessed_format()?)
+}
+
+fn decode<R: BufRead + Seek>(reader: ImageReader<R>) -> ImageResult<DynamicImage> {
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// An image scaled to the size of a display
+struct Fitted {
+    image: RgbaImage,
+    offset: Point,
+    background: Rgb888,
+}
+
+impl Fitted {
+    fn new<C: PartialEq>(
+        image: &DynamicImage,
+        size: Size,
+        fit: Fit,
+        background: C,
+        palette: &Palette<C>,
+    ) -> Self {
+        let background = palette_rgb(palette, background)
+            .expect("the background color has to be in the palette");
+        let (width, height) = (size.width, size.height);
+        let image = match fit {
+            Fit::Letterbox => image.resize(width, height, FilterType::Triangle),
+            Fit::Crop => image.resize_to_fill(width, height, FilterType::Triangle),
+        }
+        .into_rgba8();
+        let offset = Point::new(
+            (width.saturating_sub(image.width()) / 2) as i32,
+            (height.saturating_sub(image.height()) / 2) as i32,
+        );
+        Fitted {
+            image,
+            offset,
+            background,
+        }
+    }
+
+    /// Row `y` of the image blended with the background
+    fn row(&self, y: u32, row: &mut Vec<Rgb888>) {
+        let blend = |value: u8, background: u8, alpha: u8| {
+            let (value, background, alpha) =
+                (u32::from(value), u32::from(background), u32::from(alpha));
+            ((value * alpha + background * (255 - alpha) + 127) / 255) as u8
+        };
+        let bg = self.background;
+        row.clear();
+        row.extend((0..self.image.width()).map(|x| {
+            let [r, g, b, a] = self.image.get_pixel(x, y).0;
+            Rgb888::new(
+                blend(r, bg.r(), a),
+                blend(g, bg.g(), a),
+                blend(b, bg.b(), a),
+            )
+        }));
+    }
+
+    fn ditherer<'a, C: Copy>(
+        &self,
+        line: &'a mut Vec<[i16; 3]>,
+        palette: &'a Palette<C>,
+    ) -> Ditherer<'a, C> {
+        line.resize(line_buffer_len(self.image.width()), [0; 3]);
+        Ditherer::new(self.image.width(), line, palette).expect("line buffer has the right length")
+    }
+}
+
+/// Draws `image` dithered to the colors of `palette` onto the whole `target`
+///
+/// The image is scaled to the size of `target`, so it follows the rotation of a display
+/// buffer. The rest of `target` is cleared to `background`.
+///
+/// # Panics
+///
+/// If `background` isn't one of the colors of `palette`.
+pub fn draw<D, C>(
+    target: &mut D,
+    image: &DynamicImage,
+    fit: Fit,
+    background: C,
+    palette: &Palette<C>,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget,
+    C: Copy + PartialEq + Into<D::Color>,
+{
+    let area = target.bounding_box();
+    let fitted = Fitted::new(image, area.size, fit, background, palette);
+    target.clear(background.into())?;
+
+    let mut line = Vec::new();
+    let mut ditherer = fitted.ditherer(&mut line, palette);
+    let mut row = Vec::new();
+    let origin = area.top_left + fitted.offset;
+    for y in 0..fitted.image.height() {
+        fitted.row(y, &mut row);
+        let mut result = Ok(());
+        ditherer.row(&row, |x, color| {
+            if result.is_ok() {
+                let point = origin + Point::new(x as i32, y as i32);
+                result = target.draw_iter(core::iter::once(Pixel(point, color.into())));
+            }
+        });
+        result?;
+    }
+    Ok(())
+}
+
+/// Sends `image` dithered to the colors of `palette` as a full frame with [stream_frame]
+///
+/// The image is scaled to the unrotated size of the display, the rest of the frame is
+/// `background`. Only the scaled image and a few rows are kept in memory. Afterwards the
+/// frame can be shown with
+/// [display_frame](crate::traits::WaveshareDisplay::display_frame).
+///
+/// # Panics
+///
+/// If `background` isn't one of the colors of `palette`.
+#[allow(clippy::too_many_arguments)]
+pub fn stream<SPI, CS, BUSY, DC, RST, DELAY, EPD>(
+    epd: &mut EPD,
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    image: &DynamicImage,
+    fit: Fit,
+    background: EPD::DisplayColor,
+    palette: &Palette<EPD::DisplayColor>,
+) -> Result<(), StreamError<SPI::Error, core::convert::Infallible>>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    EPD: StreamFrame<SPI, CS, BUSY, DC, RST, DELAY>,
+    EPD::DisplayColor: StreamColor + PartialEq,
+{
+    let size = Size::new(epd.width(), epd.height());
+    let fitted = Fitted::new(image, size, fit, background, palette);
+
+    let mut line = Vec::new();
+    let mut ditherer = fitted.ditherer(&mut line, palette);
+    let mut pixels = Vec::new();
+    let mut row = vec![background; size.width as usize];
+    let row_bytes = (size.width as usize * EPD::DisplayColor::BITS_PER_PIXEL).div_ceil(8);
+    let mut chunk = vec![0; row_bytes * 8];
+    let (left, top) = (fitted.offset.x as u32, fitted.offset.y as u32);
+    stream_frame(epd, spi, delay, &mut row, &mut chunk, |y, row| {
+        row.fill(background);
+        if let Some(y) = y.checked_sub(top).filter(|&y| y < fitted.image.height()) {
+            fitted.row(y, &mut pixels);
+            ditherer.row(&pixels, |x, color| row[(left + x) as usize] = color);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{Color, OctColor};
+    use crate::dither::{BW_PALETTE, OCT_PALETTE};
+    use crate::epd7in5_v2::{self, Epd7in5};
+    use crate::graphics::{DisplayRotation, OctDisplay, VarOctDisplay};
+    use crate::mock::{init_epd, Transfer};
+    use ::image::codecs::jpeg::JpegEncoder;
+    use ::image::{ExtendedColorType, ImageEncoder, ImageFormat, Rgb, RgbImage, Rgba};
+
+    fn oct_pixel(display: &VarOctDisplay, x: u32, y: u32) -> OctColor {
+        display.get_pixel(Point::new(x as i32, y as i32)).unwrap()
+    }
+
+    #[test]
+    fn letterbox() {
+        // wider than the display: bars at the top and the bottom
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(40, 10, Rgb([0, 0, 0])));
+        let mut buffer = [0; 20 / 2 * 20];
+        let mut display = VarOctDisplay::new(20, 20, &mut buffer).unwrap();
+        draw(
+            &mut display,
+            &image,
+            Fit::Letterbox,
+            OctColor::White,
+            &OCT_PALETTE,
+        )
+        .unwrap();
+        for y in 0..20 {
+            let expected = if (7..7 + 5).contains(&y) {
+                OctColor::Black
+            } else {
+                OctColor::White
+            };
+            for x in 0..20 {
+                assert_eq!(oct_pixel(&display, x, y), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn crop_follows_rotation() {
+        // left half red, right half green, the outer quarters are cut off
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(40, 10, |x, _| {
+            if x < 20 {
+                Rgb([156, 72, 75])
+            } else {
+                Rgb([58, 91, 70])
+            }
+        }));
+        let mut buffer = [0; 10 / 2 * 20];
+        let mut display = VarOctDisplay::new(10, 20, &mut buffer).unwrap();
+        display.set_rotation(DisplayRotation::Rotate90);
+        draw(
+            &mut display,
+            &image,
+            Fit::Crop,
+            OctColor::White,
+            &OCT_PALETTE,
+        )
+        .unwrap();
+        for y in 0..10 {
+            for x in 1..9 {
+                assert_eq!(oct_pixel(&display, x, y), OctColor::Red);
+                assert_eq!(oct_pixel(&display, x + 10, y), OctColor::Green);
+            }
+        }
+    }
+
+    #[test]
+    fn transparency_is_background() {
+        let mut image = RgbaImage::from_pixel(8, 8, Rgba([255, 255, 255, 255]));
+        image.put_pixel(3, 4, Rgba([255, 255, 255, 0]));
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let image = load_from_memory(&png).unwrap();
+
+        let mut buffer = [0; 8 / 2 * 8];
+        let mut display = VarOctDisplay::new(8, 8, &mut buffer).unwrap();
+        draw(
+            &mut display,
+            &image,
+            Fit::Crop,
+            OctColor::Yellow,
+            &OCT_PALETTE,
+        )
+        .unwrap();
+        for y in 0..8 {
+            for x in 0..8 {
+                let expected = if (x, y) == (3, 4) {
+                    OctColor::Yellow
+                } else {
+                    OctColor::White
+                };
+                assert_eq!(oct_pixel(&display, x, y), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn exif_orientation() {
+        // little endian tiff header with a single entry: orientation 6, rotated by 90 degrees
+        let exif = vec![
+            b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, 0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0,
+        ];
+        let mut jpeg = Vec::new();
+        let mut encoder = JpegEncoder::new(&mut jpeg);
+        encoder.set_exif_metadata(exif).unwrap();
+        encoder
+            .write_image(&[0; 16 * 8 * 3], 16, 8, ExtendedColorType::Rgb8)
+            .unwrap();
+
+        let image = load_from_memory(&jpeg).unwrap();
+        assert_eq!((image.width(), image.height()), (8, 16));
+    }
+
+    #[test]
+    #[should_panic(expected = "palette")]
+    fn background_outside_of_palette() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(4, 4));
+        let mut buffer = [0; 4 / 2 * 4];
+        let mut display = VarOctDisplay::new(4, 4, &mut buffer).unwrap();
+        let _ = draw(&mut display, &image, Fit::Crop, OctColor::HiZ, &OCT_PALETTE);
+    }
+
+    #[test]
+    fn stream_letterboxed_frame() {
+        let (width, height) = (epd7in5_v2::WIDTH, epd7in5_v2::HEIGHT);
+        // as high as the display, bars on the left and the right
+        let image =
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(height / 2, height / 2, Rgb([0, 0, 0])));
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd7in5<_, _, _, _, _, _>>();
+        log.clear();
+
+        stream(
+            &mut epd,
+            &mut spi,
+            &mut delay,
+            &image,
+            Fit::Letterbox,
+            Color::White,
+            &BW_PALETTE,
+        )
+        .unwrap();
+
+        let transfers = log.transfers();
+        // after the status checks of wait_until_idle
+        let start = transfers
+            .iter()
+            .position(|transfer| *transfer == Transfer::Command(0x13))
+            .expect("DataStartTransmission2");
+        let frame = match &transfers[start + 1..] {
+            [Transfer::Data(frame)] => frame,
+            other => panic!("unexpected transfers {:?}", other),
+        };
+        assert_eq!(frame.len(), epd7in5_v2::buffer_len());
+        let left = ((width - height) / 2 / 8) as usize;
+        for row in frame.chunks((width / 8) as usize) {
+            // set bits are black on this controller
+            assert!(row[..left].iter().all(|&byte| byte == 0x00));
+            assert!(row[left..row.len() - left].iter().all(|&byte| byte == 0xff));
+            assert!(row[row.len() - left..].iter().all(|&byte| byte == 0x00));
+        }
+    }
+}
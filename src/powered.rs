@@ -0,0 +1,161 @@
+//! Guard which makes sure the display is put to sleep again
+//!
+//! Leaving a display powered for a long time can damage it permanently, so
+//! [PoweredEpd] sends it to deep sleep once it isn't needed anymore.
+
+use crate::error::Error;
+use crate::traits::{DisplayState, WaveshareDisplay};
+use core::marker::PhantomData;
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::*,
+};
+
+/// Borrows the display together with the spi bus and delay while it is awake
+///
+/// Get it from [powered](WaveshareDisplay::powered). The display is put to sleep
+/// with [finish](PoweredEpd::finish) or at the latest when the guard is dropped.
+/// Errors while going to sleep can only be seen with [finish](PoweredEpd::finish),
+/// dropping the guard ignores them.
+#[must_use = "the display should be put to sleep with `finish`"]
+pub struct PoweredEpd<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    EPD: WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>,
+{
+    epd: &'a mut EPD,
+    spi: &'a mut SPI,
+    delay: &'a mut DELAY,
+    /// Whether sleep was already sent
+    finished: bool,
+    _pins: PhantomData<(CS, BUSY, DC, RST)>,
+}
+
+impl<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD> PoweredEpd<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    EPD: WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>,
+{
+    /// Wakes the display up if it is asleep
+    pub(crate) fn new(
+        epd: &'a mut EPD,
+        spi: &'a mut SPI,
+        delay: &'a mut DELAY,
+    ) -> Result<Self, Error<SPI::Error>> {
+        if epd.state() == DisplayState::Asleep {
+            epd.wake_up(spi, delay)?;
+        }
+        Ok(PoweredEpd {
+            epd,
+            spi,
+            delay,
+            finished: false,
+            _pins: PhantomData,
+        })
+    }
+
+    /// The display itself, e.g. for functions which don't need the spi bus
+    pub fn epd(&mut self) -> &mut EPD {
+        self.epd
+    }
+
+    /// See [WaveshareDisplay::update_frame]
+    pub fn update_frame(&mut self, buffer: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.epd.update_frame(self.spi, buffer, self.delay)
+    }
+
+    /// See [WaveshareDisplay::update_partial_frame]
+    pub fn update_partial_frame(
+        &mut self,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.epd
+            .update_partial_frame(self.spi, buffer, x, y, width, height)
+    }
+
+    /// See [WaveshareDisplay::display_frame]
+    pub fn display_frame(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.epd.display_frame(self.spi, self.delay)
+    }
+
+    /// See [WaveshareDisplay::update_and_display_frame]
+    pub fn update_and_display_frame(&mut self, buffer: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.epd
+            .update_and_display_frame(self.spi, buffer, self.delay)
+    }
+
+    /// See [WaveshareDisplay::clear_frame]
+    pub fn clear_frame(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.epd.clear_frame(self.spi, self.delay)
+    }
+
+    /// See [WaveshareDisplay::clear_ram]
+    pub fn clear_ram(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.epd.clear_ram(self.spi, self.delay)
+    }
+
+    /// Puts the display to sleep and returns the error if that failed
+    pub fn finish(mut self) -> Result<(), Error<SPI::Error>> {
+        self.finished = true;
+        self.epd.sleep(self.spi, self.delay)
+    }
+}
+
+impl<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD> Drop
+    for PoweredEpd<'a, SPI, CS, BUSY, DC, RST, DELAY, EPD>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    EPD: WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>,
+{
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.epd.sleep(self.spi, self.delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::epd4in2::{command::Command, Epd4in2};
+    use crate::mock::init_epd;
+    use crate::traits::{Command as _, DisplayState, WaveshareDisplay};
+
+    #[test]
+    fn sleeps_on_finish_and_drop() {
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd4in2<_, _, _, _, _, _>>();
+
+        let mut powered = epd.powered(&mut spi, &mut delay).unwrap();
+        powered.clear_frame().unwrap();
+        powered.display_frame().unwrap();
+        powered.finish().unwrap();
+        assert_eq!(epd.state(), DisplayState::Asleep);
+        assert_eq!(log.count_command(Command::DeepSleep.address()), 1);
+
+        // wakes the display up again and sleeps once it goes out of scope
+        {
+            let mut powered = epd.powered(&mut spi, &mut delay).unwrap();
+            powered.clear_frame().unwrap();
+        }
+        assert_eq!(epd.state(), DisplayState::Asleep);
+        assert_eq!(log.count_command(Command::DeepSleep.address()), 2);
+    }
+}
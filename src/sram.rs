@@ -0,0 +1,451 @@
+//! Staging frames in an external SPI SRAM, like the 23K640 or 23LC1024 on some breakouts
+//!
+//! Microcontrollers without the RAM for a whole frame can keep it in a serial SRAM on the
+//! same bus as the display instead. [Sram] drives the memory with its own CS pin: a frame is
+//! written into it in pieces with [write](Sram::write), e.g. as the packets of a radio come
+//! in, and [send_frame](Sram::send_frame) streams it into the display through a small chunk
+//! buffer with [StreamFrame]. The frame never has to be in the memory of the MCU.
+//!
+//! The frame is laid out like the buffer of
+//! [update_frame](crate::traits::WaveshareDisplay::update_frame), only its length matters,
+//! e.g. [epd4in2::buffer_len](crate::epd4in2::buffer_len).
+//!
+//! ```rust, no_run
+//!# use embedded_hal_mock::*;
+//!# fn main() -> Result<(), epd_waveshare::sram::SramError<MockError>> {
+//!# use epd_waveshare::{epd4in2::*, prelude::*, sram::Sram};
+//!#
+//!# let expectations = [];
+//!# let mut spi = spi::Mock::new(&expectations);
+//!# let expectations = [];
+//!# let cs_pin = pin::Mock::new(&expectations);
+//!# let sram_cs = pin::Mock::new(&expectations);
+//!# let busy_in = pin::Mock::new(&expectations);
+//!# let dc = pin::Mock::new(&expectations);
+//!# let rst = pin::Mock::new(&expectations);
+//!# let mut delay = delay::MockNoop::new();
+//!# let packets: [&[u8]; 0] = [];
+//!let mut epd = Epd4in2::new(cs_pin, busy_in, dc, rst);
+//!epd.init(&mut spi, &mut delay)?;
+//!
+//!// a 23LC512 with 64 KB
+//!let mut sram = Sram::new(sram_cs, 0x1_0000);
+//!sram.init(&mut spi)?;
+//!
+//!let mut offset = 0;
+//!for packet in packets {
+//!    sram.write(&mut spi, offset, packet)?;
+//!    offset += packet.len() as u32;
+//!}
+//!
+//!let mut chunk = [0; 256];
+//!sram.send_frame(&mut epd, &mut spi, &mut delay, 0, buffer_len(), &mut chunk)?;
+//!epd.display_frame(&mut spi, &mut delay)?;
+//!# Ok(())
+//!# }
+//! ```
+
+use crate::error::Error;
+use crate::traits::StreamFrame;
+use embedded_hal::{
+    blocking::{
+        delay::*,
+        spi::{Transfer, Write},
+    },
+    digital::v2::*,
+};
+
+/// Reads the memory from the given address on
+const READ: u8 = 0x03;
+/// Writes the memory from the given address on
+const WRITE: u8 = 0x02;
+/// Writes the mode register, the status register of the 23K640 and 23K256
+const WRITE_MODE: u8 = 0x01;
+/// Mode of the transfers, the address counts up over page boundaries
+const SEQUENTIAL_MODE: u8 = 0x40;
+
+/// Errors of the SRAM and of the display while streaming a frame from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SramError<SpiError> {
+    /// Error of the display
+    Epd(Error<SpiError>),
+    /// Error of the spi bus while talking to the SRAM
+    Spi(SpiError),
+    /// Setting the CS pin of the SRAM failed
+    Cs,
+    /// The bytes don't fit into the SRAM at the address
+    OutOfRange {
+        /// First byte of the access
+        address: u32,
+        /// Number of bytes of the access
+        len: usize,
+        /// Size of the SRAM
+        capacity: u32,
+    },
+    /// The chunk buffer of [send_frame](Sram::send_frame) is empty
+    EmptyChunk,
+}
+
+impl<SpiError> From<Error<SpiError>> for SramError<SpiError> {
+    fn from(error: Error<SpiError>) -> Self {
+        SramError::Epd(error)
+    }
+}
+
+impl<SpiError: core::fmt::Debug> core::fmt::Display for SramError<SpiError> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SramError::Epd(e) => write!(f, "{}", e),
+            SramError::Spi(e) => write!(f, "Spi error of the sram: {:?}", e),
+            SramError::Cs => write!(f, "Error on the cs pin of the sram"),
+            SramError::OutOfRange {
+                address,
+                len,
+                capacity,
+            } => write!(
+                f,
+                "{} bytes at {:#x} don't fit into the sram of {} bytes",
+                len, address, capacity
+            ),
+            SramError::EmptyChunk => write!(f, "The chunk buffer for the sram is empty"),
+        }
+    }
+}
+
+/// A serial SRAM of the 23K640 / 23LC1024 kind on the bus of the display
+///
+/// Memories up to 64 KB, e.g. the 23K640 (8 KB), 23K256 (32 KB) and 23LC512 (64 KB), take
+/// two address bytes, bigger ones like the 23LC1024 (128 KB) three.
+pub struct Sram<CS> {
+    cs: CS,
+    capacity: u32,
+}
+
+impl<SCS: OutputPin> Sram<SCS> {
+    /// Creates the driver for an SRAM of `capacity` bytes selected with `cs`
+    ///
+    /// Doesn't talk to the memory yet, call [init](Sram::init) afterwards.
+    pub fn new(cs: SCS, capacity: u32) -> Self {
+        Sram { cs, capacity }
+    }
+
+    /// Size of the SRAM in bytes
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Switches the SRAM to sequential mode, so accesses can be as long as the memory
+    pub fn init<SPI, E>(&mut self, spi: &mut SPI) -> Result<(), SramError<E>>
+    where
+        SPI: Write<u8, Error = E>,
+    {
+        self.select()?;
+        let result = spi.write(&[WRITE_MODE, SEQUENTIAL_MODE]);
+        self.deselect()?;
+        result.map_err(SramError::Spi)
+    }
+
+    /// Writes `data` from `address` on
+    pub fn write<SPI, E>(
+        &mut self,
+        spi: &mut SPI,
+        address: u32,
+        data: &[u8],
+    ) -> Result<(), SramError<E>>
+    where
+        SPI: Write<u8, Error = E>,
+    {
+        self.check_range(address, data.len())?;
+        self.select()?;
+        let result = self
+            .send_header(spi, WRITE, address)
+            .and_then(|_| spi.write(data));
+        self.deselect()?;
+        result.map_err(SramError::Spi)
+    }
+
+    /// Reads `buffer.len()` bytes from `address` on into `buffer`
+    pub fn read<SPI, E>(
+        &mut self,
+        spi: &mut SPI,
+        address: u32,
+        buffer: &mut [u8],
+    ) -> Result<(), SramError<E>>
+    where
+        SPI: Write<u8, Error = E> + Transfer<u8, Error = E>,
+    {
+        self.check_range(address, buffer.len())?;
+        self.select()?;
+        let result = self
+            .send_header(spi, READ, address)
+            .and_then(|_| spi.transfer(buffer).map(|_| ()));
+        self.deselect()?;
+        result.map_err(SramError::Spi)
+    }
+
+    /// Sends the frame of `len` bytes at `address` to `epd`, `chunk.len()` bytes at a time
+    ///
+    /// Every chunk is read from the SRAM and sent to the display before the next one, so
+    /// both share the bus. Afterwards the frame can be shown with
+    /// [display_frame](crate::traits::WaveshareDisplay::display_frame). An empty `chunk`
+    /// fails with [SramError::EmptyChunk] before anything is sent.
+    pub fn send_frame<SPI, CS, BUSY, DC, RST, DELAY, EPD, E>(
+        &mut self,
+        epd: &mut EPD,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        address: u32,
+        len: usize,
+        chunk: &mut [u8],
+    ) -> Result<(), SramError<E>>
+    where
+        SPI: Write<u8, Error = E> + Transfer<u8, Error = E>,
+        CS: OutputPin,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayMs<u8>,
+        EPD: StreamFrame<SPI, CS, BUSY, DC, RST, DELAY>,
+    {
+        if chunk.is_empty() {
+            return Err(SramError::EmptyChunk);
+        }
+        self.check_range(address, len)?;
+        epd.begin_frame(spi, delay)?;
+        let mut sent = 0;
+        while sent < len {
+            let piece_len = (len - sent).min(chunk.len());
+            let piece = &mut chunk[..piece_len];
+            self.read(spi, address + sent as u32, piece)?;
+            epd.frame_data(spi, piece)?;
+            sent += piece.len();
+        }
+        epd.end_frame(spi, delay)?;
+        Ok(())
+    }
+
+    // Fails unless `len` bytes from `address` on are inside of the SRAM
+    fn check_range<E>(&self, address: u32, len: usize) -> Result<(), SramError<E>> {
+        let end = address as u64 + len as u64;
+        if end > self.capacity as u64 {
+            return Err(SramError::OutOfRange {
+                address,
+                len,
+                capacity: self.capacity,
+            });
+        }
+        Ok(())
+    }
+
+    // Sends the instruction and the address with as many bytes as the capacity needs
+    fn send_header<SPI, E>(&mut self, spi: &mut SPI, instruction: u8, address: u32) -> Result<(), E>
+    where
+        SPI: Write<u8, Error = E>,
+    {
+        let [_, high, middle, low] = address.to_be_bytes();
+        if self.capacity > 0x1_0000 {
+            spi.write(&[instruction, high, middle, low])
+        } else {
+            spi.write(&[instruction, middle, low])
+        }
+    }
+
+    fn select<E>(&mut self) -> Result<(), SramError<E>> {
+        self.cs.set_low().map_err(|_| SramError::Cs)
+    }
+
+    fn deselect<E>(&mut self) -> Result<(), SramError<E>> {
+        self.cs.set_high().map_err(|_| SramError::Cs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::epd4in2::{self, Epd4in2};
+    use crate::mock::{BusyPin, Log, Pin, Spi};
+    use crate::traits::{DisplayState, WaveshareDisplay};
+    use core::cell::RefCell;
+    use core::convert::Infallible;
+    use std::rc::Rc;
+    use std::vec;
+    use std::vec::Vec;
+
+    // A model of the SRAM, selected with its own CS pin
+    #[derive(Default)]
+    struct Chip {
+        selected: bool,
+        memory: Vec<u8>,
+        address_bytes: usize,
+        // instruction and address of the current selection, then the address of the next byte
+        header: Vec<u8>,
+        address: usize,
+        mode: Option<u8>,
+    }
+
+    impl Chip {
+        fn new(capacity: usize, address_bytes: usize) -> Rc<RefCell<Self>> {
+            Rc::new(RefCell::new(Chip {
+                memory: vec![0; capacity],
+                address_bytes,
+                ..Chip::default()
+            }))
+        }
+
+        // Clocks `word` in and out
+        fn clock(&mut self, word: u8) -> u8 {
+            let header_len = 1 + self.address_bytes;
+            match self.header.first() {
+                Some(&WRITE_MODE) => {
+                    self.mode = Some(word);
+                    0
+                }
+                Some(&instruction) if self.header.len() == header_len => {
+                    let address = self.address;
+                    self.address += 1;
+                    if instruction == WRITE {
+                        self.memory[address] = word;
+                        0
+                    } else {
+                        self.memory[address]
+                    }
+                }
+                _ => {
+                    self.header.push(word);
+                    if self.header.len() == header_len {
+                        self.address = self.header[1..]
+                            .iter()
+                            .fold(0, |address, &byte| address << 8 | byte as usize);
+                    }
+                    0
+                }
+            }
+        }
+    }
+
+    struct SramCs(Rc<RefCell<Chip>>);
+
+    impl OutputPin for SramCs {
+        type Error = Infallible;
+
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            let mut chip = self.0.borrow_mut();
+            chip.selected = true;
+            chip.header.clear();
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.0.borrow_mut().selected = false;
+            Ok(())
+        }
+    }
+
+    // The bus shared by the SRAM and the display, the display only sees the transfers
+    // while the SRAM isn't selected
+    struct Bus(Spi, Rc<RefCell<Chip>>);
+
+    impl Write<u8> for Bus {
+        type Error = Infallible;
+
+        fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+            let mut chip = self.1.borrow_mut();
+            if chip.selected {
+                for &word in words {
+                    chip.clock(word);
+                }
+                Ok(())
+            } else {
+                self.0.write(words)
+            }
+        }
+    }
+
+    impl Transfer<u8> for Bus {
+        type Error = Infallible;
+
+        fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+            let mut chip = self.1.borrow_mut();
+            assert!(chip.selected);
+            for word in words.iter_mut() {
+                *word = chip.clock(*word);
+            }
+            Ok(words)
+        }
+    }
+
+    #[test]
+    fn addresses_of_small_and_big_chips() {
+        for &(capacity, address_bytes) in [(0x2000, 2), (0x1_0000, 2), (0x2_0000, 3)].iter() {
+            let chip = Chip::new(capacity as usize, address_bytes);
+            let mut bus = Bus(Log::default().spi(), chip.clone());
+            let mut sram = Sram::new(SramCs(chip.clone()), capacity);
+            sram.init(&mut bus).unwrap();
+            assert_eq!(chip.borrow().mode, Some(SEQUENTIAL_MODE));
+
+            let end = capacity - 3;
+            sram.write(&mut bus, end, &[1, 2, 3]).unwrap();
+            assert_eq!(chip.borrow().memory[end as usize..], [1, 2, 3]);
+            let mut read = [0; 2];
+            sram.read(&mut bus, end + 1, &mut read).unwrap();
+            assert_eq!(read, [2, 3]);
+
+            // past the end
+            assert_eq!(
+                sram.write(&mut bus, end, &[0; 4]),
+                Err(SramError::OutOfRange {
+                    address: end,
+                    len: 4,
+                    capacity
+                })
+            );
+            assert!(!chip.borrow().selected);
+        }
+    }
+
+    #[test]
+    fn frame_from_sram_is_the_same_as_update_frame() {
+        let buffer: Vec<u8> = (0..epd4in2::buffer_len()).map(|i| (i * 7) as u8).collect();
+        let chip = Chip::new(0x1_0000, 2);
+        let log = Log::default();
+        let mut bus = Bus(log.spi(), chip.clone());
+        let mut delay = crate::mock::Delay::new();
+        let mut epd = Epd4in2::new(Pin, BusyPin::default(), log.dc(), Pin);
+        epd.init(&mut bus, &mut delay).unwrap();
+
+        log.clear();
+        epd.update_frame(&mut bus, &buffer, &mut delay).unwrap();
+        let expected = log.transfers();
+
+        // written in uneven packets behind some other data
+        let mut sram = Sram::new(SramCs(chip.clone()), 0x1_0000);
+        sram.init(&mut bus).unwrap();
+        let start = 100;
+        for (i, packet) in buffer.chunks(61).enumerate() {
+            sram.write(&mut bus, start + (i * 61) as u32, packet)
+                .unwrap();
+        }
+        log.clear();
+        let mut chunk = [0; 256];
+        sram.send_frame(
+            &mut epd,
+            &mut bus,
+            &mut delay,
+            start,
+            buffer.len(),
+            &mut chunk,
+        )
+        .unwrap();
+        assert_eq!(log.transfers(), expected);
+        assert_eq!(epd.state(), DisplayState::FramePending);
+
+        // without a chunk to read into
+        log.clear();
+        assert_eq!(
+            sram.send_frame(&mut epd, &mut bus, &mut delay, start, buffer.len(), &mut []),
+            Err(SramError::EmptyChunk)
+        );
+        assert!(log.transfers().is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+//! Sending frames row by row for MCUs without the RAM for a full frame
+//!
+//! The 7 color frame of the 5.65" display alone takes 134 KB. [stream_frame] asks for one
+//! row of colors at a time, packs the rows into a small chunk buffer and sends every full
+//! chunk with [StreamFrame], so only one row of colors and the chunk buffer are in memory.
+//!
+//! Rgb images can be converted while filling the rows, e.g. with
+//! [Ditherer](crate::dither::Ditherer) and a second row buffer for the rgb pixels.
+//!
+//! Example:
+//! ```rust, no_run
+//!# use embedded_hal_mock::*;
+//!# fn main() -> Result<(), epd_waveshare::stream::StreamError<MockError, ()>> {
+//!# use epd_waveshare::{epd5in65f::*, prelude::*, stream::stream_frame};
+//!#
+//!# let expectations = [];
+//!# let mut spi = spi::Mock::new(&expectations);
+//!# let expectations = [];
+//!# let cs_pin = pin::Mock::new(&expectations);
+//!# let busy_in = pin::Mock::new(&expectations);
+//!# let dc = pin::Mock::new(&expectations);
+//!# let rst = pin::Mock::new(&expectations);
+//!# let mut delay = delay::MockNoop::new();
+//!#
+//!# let mut epd = Epd5in65f::new(cs_pin, busy_in, dc, rst);
+//!# epd.init(&mut spi, &mut delay)?;
+//!let mut row = [OctColor::White; WIDTH as usize];
+//!let mut chunk = [0; 1024];
+//!stream_frame(&mut epd, &mut spi, &mut delay, &mut row, &mut chunk, |y, row| {
+//!    // e.g. read the row from an sd card
+//!    for (x, color) in row.iter_mut().enumerate() {
+//!        *color = if (x as u32 + y) % 2 == 0 { OctColor::Red } else { OctColor::Blue };
+//!    }
+//!    Ok(())
+//!})?;
+//!epd.display_frame(&mut spi, &mut delay)?;
+//!# Ok(())
+//!# }
+//! ```
+
+use crate::color::{Color, OctColor};
+use crate::error::Error;
+use crate::frame::FrameDataError;
+use crate::traits::StreamFrame;
+use embedded_hal::{
+    blocking::{delay::*, spi::Write},
+    digital::v2::*,
+};
+
+/// Colors which can be packed into the frame data of a display
+pub trait StreamColor: Copy {
+    /// Bits of one pixel in the frame data
+    const BITS_PER_PIXEL: usize;
+
+    /// Bits of this color in the frame data
+    fn bits(self) -> u8;
+}
+
+impl StreamColor for Color {
+    const BITS_PER_PIXEL: usize = 1;
+
+    fn bits(self) -> u8 {
+        self.get_bit_value()
+    }
+}
+
+impl StreamColor for OctColor {
+    const BITS_PER_PIXEL: usize = 4;
+
+    fn bits(self) -> u8 {
+        self.get_nibble()
+    }
+}
+
+/// Errors while streaming a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamError<SpiError, E> {
+    /// Error of the display
+    Epd(Error<SpiError>),
+    /// Error returned while filling a row
+    Row(E),
+    /// The row buffer doesn't match the width of the display or the chunk buffer
+    /// can't hold a row
+    Buffer(FrameDataError),
+}
+
+impl<SpiError, E> From<Error<SpiError>> for StreamError<SpiError, E> {
+    fn from(error: Error<SpiError>) -> Self {
+        StreamError::Epd(error)
+    }
+}
+
+impl<SpiError: core::fmt::Debug, E: core::fmt::Debug> core::fmt::Display
+    for StreamError<SpiError, E>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            StreamError::Epd(e) => write!(f, "{}", e),
+            StreamError::Row(e) => write!(f, "Filling a row failed: {:?}", e),
+            StreamError::Buffer(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Sends a full frame which is built row by row with `fill_row`
+///
+/// `fill_row` gets the y coordinate and the row buffer to fill, `row` needs exactly as many
+/// colors as the display is wide. The rows are packed like the buffers of the display
+/// into `chunk`, which has to hold at least one packed row. Larger chunks mean fewer
+/// but longer transfers.
+///
+/// Afterwards the frame can be shown with
+/// [display_frame](crate::traits::WaveshareDisplay::display_frame). If filling a row fails,
+/// the frame is left incomplete and isn't displayed.
+pub fn stream_frame<SPI, CS, BUSY, DC, RST, DELAY, EPD, E, F>(
+    epd: &mut EPD,
+    spi: &mut SPI,
+    delay: &mut DELAY,
+    row: &mut [EPD::DisplayColor],
+    chunk: &mut [u8],
+    mut fill_row: F,
+) -> Result<(), StreamError<SPI::Error, E>>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+    EPD: StreamFrame<SPI, CS, BUSY, DC, RST, DELAY>,
+    EPD::DisplayColor: StreamColor,
+    F: FnMut(u32, &mut [EPD::DisplayColor]) -> Result<(), E>,
+{
+    let (width, height) = (epd.width(), epd.height());
+    if row.len() != width as usize {
+        return Err(StreamError::Buffer(FrameDataError::BufferLength {
+            expected: width as usize,
+            actual: row.len(),
+        }));
+    }
+    let bits_per_pixel = EPD::DisplayColor::BITS_PER_PIXEL;
+    let row_bytes = (width as usize * bits_per_pixel).div_ceil(8);
+    if chunk.len() < row_bytes {
+        return Err(StreamError::Buffer(FrameDataError::BufferLength {
+            expected: row_bytes,
+            actual: chunk.len(),
+        }));
+    }
+    let rows_per_chunk = chunk.len() / row_bytes;
+
+    epd.begin_frame(spi, delay)?;
+    let mut filled = 0;
+    for y in 0..height {
+        fill_row(y, row).map_err(StreamError::Row)?;
+
+        let packed = &mut chunk[filled * row_bytes..(filled + 1) * row_bytes];
+        packed.fill(0);
+        for (x, color) in row.iter().enumerate() {
+            let bit = x * bits_per_pixel;
+            packed[bit / 8] |= color.bits() << (8 - bits_per_pixel - bit % 8);
+        }
+        filled += 1;
+
+        if filled == rows_per_chunk || y + 1 == height {
+            epd.frame_data(spi, &chunk[..filled * row_bytes])?;
+            filled = 0;
+        }
+    }
+    epd.end_frame(spi, delay)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::epd5in65f::{self, Epd5in65f};
+    use crate::epd7in5::{self, Epd7in5};
+    use crate::mock::{init_epd, Transfer};
+    use crate::traits::{DisplayState, WaveshareDisplay};
+    use std::vec::Vec;
+
+    fn oct_pattern(x: u32, y: u32) -> OctColor {
+        OctColor::from_nibble(((x + 3 * y) % 7) as u8).unwrap()
+    }
+
+    fn bw_pattern(x: u32, y: u32) -> Color {
+        if (x / 3 + y).is_multiple_of(2) {
+            Color::Black
+        } else {
+            Color::White
+        }
+    }
+
+    fn fill<C>(pattern: fn(u32, u32) -> C) -> impl FnMut(u32, &mut [C]) -> Result<(), ()> {
+        move |y, row| {
+            for (x, color) in row.iter_mut().enumerate() {
+                *color = pattern(x as u32, y);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn same_as_update_frame_oct() {
+        let (width, height) = (epd5in65f::WIDTH, epd5in65f::HEIGHT);
+        let buffer: Vec<u8> = (0..height)
+            .flat_map(|y| {
+                (0..width / 2).map(move |x| {
+                    OctColor::colors_byte(oct_pattern(2 * x, y), oct_pattern(2 * x + 1, y))
+                })
+            })
+            .collect();
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd5in65f<_, _, _, _, _, _>>();
+
+        log.clear();
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        let expected = log.transfers();
+
+        log.clear();
+        let mut row = [OctColor::White; epd5in65f::WIDTH as usize];
+        let mut chunk = [0; 1000];
+        stream_frame(
+            &mut epd,
+            &mut spi,
+            &mut delay,
+            &mut row,
+            &mut chunk,
+            fill(oct_pattern),
+        )
+        .unwrap();
+        assert_eq!(log.transfers(), expected);
+        assert_eq!(epd.state(), DisplayState::FramePending);
+    }
+
+    #[test]
+    fn same_as_update_frame_bw() {
+        let (width, height) = (epd7in5::WIDTH, epd7in5::HEIGHT);
+        let buffer: Vec<u8> = (0..height)
+            .flat_map(|y| {
+                (0..width / 8).map(move |byte| {
+                    (0..8).fold(0, |acc, bit| {
+                        acc << 1 | bw_pattern(byte * 8 + bit, y).get_bit_value()
+                    })
+                })
+            })
+            .collect();
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd7in5<_, _, _, _, _, _>>();
+
+        log.clear();
+        epd.update_frame(&mut spi, &buffer, &mut delay).unwrap();
+        let expected = log.transfers();
+
+        // a chunk which doesn't end on a row
+        log.clear();
+        let mut row = [Color::White; epd7in5::WIDTH as usize];
+        let mut chunk = [0; 250];
+        stream_frame(
+            &mut epd,
+            &mut spi,
+            &mut delay,
+            &mut row,
+            &mut chunk,
+            fill(bw_pattern),
+        )
+        .unwrap();
+        assert_eq!(log.transfers(), expected);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn update_frame_iter_with_rows() {
+        use crate::graphics::{DisplayRotation, OctDisplay};
+        use crate::traits::StreamFrame;
+        use embedded_graphics_core::prelude::*;
+
+        let mut display = epd5in65f::Display5in65f::default();
+        display.set_rotation(DisplayRotation::Rotate90);
+        Pixel(Point::new(3, 5), OctColor::Red)
+            .draw(&mut display)
+            .unwrap();
+        assert_eq!(display.rows().count(), epd5in65f::HEIGHT as usize);
+
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd5in65f<_, _, _, _, _, _>>();
+
+        log.clear();
+        epd.update_frame(&mut spi, display.buffer(), &mut delay)
+            .unwrap();
+        let expected = log.transfers();
+        log.clear();
+        epd.update_frame_iter(&mut spi, display.rows(), &mut delay)
+            .unwrap();
+        assert_eq!(log.transfers(), expected);
+        assert_eq!(epd.state(), DisplayState::FramePending);
+    }
+
+    #[test]
+    fn errors() {
+        let (log, mut spi, mut delay, mut epd) = init_epd::<Epd5in65f<_, _, _, _, _, _>>();
+        let mut row = [OctColor::White; epd5in65f::WIDTH as usize];
+
+        let mut chunk = [0; 299];
+        assert_eq!(
+            stream_frame(
+                &mut epd,
+                &mut spi,
+                &mut delay,
+                &mut row,
+                &mut chunk,
+                |_, _| { Ok::<_, ()>(()) }
+            ),
+            Err(StreamError::Buffer(FrameDataError::BufferLength {
+                expected: 300,
+                actual: 299
+            }))
+        );
+
+        // nothing is left to display after a failed row
+        let mut chunk = [0; 300];
+        log.clear();
+        assert_eq!(
+            stream_frame(
+                &mut epd,
+                &mut spi,
+                &mut delay,
+                &mut row,
+                &mut chunk,
+                |y, _| {
+                    if y < 10 {
+                        Ok(())
+                    } else {
+                        Err("sd card removed")
+                    }
+                }
+            ),
+            Err(StreamError::Row("sd card removed"))
+        );
+        let data_sent: usize = log
+            .transfers()
+            .iter()
+            .map(|t| match t {
+                Transfer::Data(data) => data.len(),
+                _ => 0,
+            })
+            .sum();
+        assert!(data_sent >= 10 * 300);
+        assert_eq!(
+            epd.display_frame(&mut spi, &mut delay),
+            Err(crate::error::Error::NoFrame)
+        );
+    }
+}
@@ -1,3 +1,9 @@
+use crate::color::{Polarity, TriColorFrame};
+use crate::error::Error;
+use crate::interface::{
+    BusyDebounce, BusyWait, Clock, DataDelay, PowerPin, ProgressHook, RefreshStats,
+};
+use crate::powered::PoweredEpd;
 use core::marker::Sized;
 use embedded_hal::{
     blocking::{delay::*, spi::Write},
@@ -11,41 +17,64 @@ pub(crate) trait Command {
 }
 
 /// Seperates the different LUT for the Display Refresh process
-#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Default)]
 pub enum RefreshLut {
     /// The "normal" full Lookuptable for the Refresh-Sequence
+    #[default]
     Full,
     /// The quick LUT where not the full refresh sequence is followed.
     /// This might lead to some
     Quick,
 }
 
-impl Default for RefreshLut {
-    fn default() -> Self {
-        RefreshLut::Full
-    }
+/// What the display is currently doing, as far as the driver knows
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+pub enum DisplayState {
+    /// Not initialized yet or an error left the display in an unknown state
+    Uninitialized,
+    /// Initialized and ready for a new frame
+    Idle,
+    /// In deep sleep, only `wake_up` is possible
+    Asleep,
+    /// A frame was sent and waits to be displayed
+    FramePending,
+    /// A refresh was started and might still be running
+    Refreshing,
 }
 
-pub(crate) trait InternalWiAdditions<SPI, CS, BUSY, DC, RST, DELAY>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BUSY: InputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    DELAY: DelayMs<u8>,
-{
-    /// This initialises the EPD and powers it up
-    ///
-    /// This function is already called from
-    ///  - [new()](WaveshareDisplay::new())
-    ///  - [`wake_up`]
-    ///
-    ///
-    /// This function calls [reset](WaveshareDisplay::reset),
-    /// so you don't need to call reset your self when trying to wake your device up
-    /// after setting it to sleep.
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error>;
+/// Mirroring of the image by the controller, for panels which are mounted upside down
+///
+/// The SSD16xx drivers take it at construction, e.g.
+/// [Epd2in9::new_with_flip](crate::epd2in9_v2::Epd2in9::new_with_flip). The controller
+/// writes its RAM backwards in the mirrored directions, so the buffers, the windows of the
+/// partial updates and the coordinates of the graphics stay top-left based and nothing has
+/// to be transformed in the buffer.
+///
+/// This needs rows of whole bytes, so the 2.13" displays with their 122 pixel rows
+/// (`epd2in13_v2`, `epd2in13_v3` and `epd2in13_v4`) don't take one.
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Default)]
+pub enum HardwareFlip {
+    /// The image as the controller shows it by default
+    #[default]
+    Normal,
+    /// Mirrored left to right
+    MirrorX,
+    /// Mirrored top to bottom
+    MirrorY,
+    /// Mirrored in both directions, i.e. rotated by 180°
+    MirrorXY,
+}
+
+impl HardwareFlip {
+    /// Whether the image is mirrored left to right
+    pub const fn mirrors_x(self) -> bool {
+        matches!(self, HardwareFlip::MirrorX | HardwareFlip::MirrorXY)
+    }
+
+    /// Whether the image is mirrored top to bottom
+    pub const fn mirrors_y(self) -> bool {
+        matches!(self, HardwareFlip::MirrorY | HardwareFlip::MirrorXY)
+    }
 }
 
 /// Functions to interact with three color panels
@@ -61,25 +90,44 @@ where
 {
     /// Transmit data to the SRAM of the EPD
     ///
-    /// Updates both the black and the secondary color layers
+    /// Updates both the black and the secondary color layers. The planes are separate
+    /// slices of the same length, on every tri-color display in the layout and with the
+    /// colors of a [TriColorFrame], fails with [Error::BufferTooShort] or
+    /// [Error::BufferTooLong] if one doesn't have the length of the display.
     fn update_color_frame(
         &mut self,
         spi: &mut SPI,
         black: &[u8],
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Update only the black/white data of the display.
     ///
     /// This must be finished by calling `update_chromatic_frame`.
-    fn update_achromatic_frame(&mut self, spi: &mut SPI, black: &[u8]) -> Result<(), SPI::Error>;
+    fn update_achromatic_frame(
+        &mut self,
+        spi: &mut SPI,
+        black: &[u8],
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Update only the chromatic data of the display.
     ///
     /// This should be preceded by a call to `update_achromatic_frame`.
     /// This data takes precedence over the black/white data.
-    fn update_chromatic_frame(&mut self, spi: &mut SPI, chromatic: &[u8])
-        -> Result<(), SPI::Error>;
+    fn update_chromatic_frame(
+        &mut self,
+        spi: &mut SPI,
+        chromatic: &[u8],
+    ) -> Result<(), Error<SPI::Error>>;
+
+    /// Transmits both planes of `frame`, the same as `update_color_frame`
+    fn update_tri_color_frame(
+        &mut self,
+        spi: &mut SPI,
+        frame: TriColorFrame<'_>,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.update_color_frame(spi, frame.black, frame.chromatic)
+    }
 }
 
 /// All the functions to interact with the EPDs
@@ -90,7 +138,7 @@ where
 ///
 ///```rust, no_run
 ///# use embedded_hal_mock::*;
-///# fn main() -> Result<(), MockError> {
+///# fn main() -> Result<(), epd_waveshare::Error<MockError>> {
 ///use embedded_graphics::{
 ///    pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
 ///};
@@ -106,7 +154,8 @@ where
 ///# let mut delay = delay::MockNoop::new();
 ///
 ///// Setup EPD
-///let mut epd = Epd4in2::new(&mut spi, cs_pin, busy_in, dc, rst, &mut delay)?;
+///let mut epd = Epd4in2::new(cs_pin, busy_in, dc, rst);
+///epd.init(&mut spi, &mut delay)?;
 ///
 ///// Use display graphics from embedded-graphics
 ///let mut display = Display4in2::default();
@@ -137,29 +186,174 @@ where
 {
     /// The Color Type used by the Display
     type DisplayColor;
-    /// Creates a new driver from a SPI peripheral, CS Pin, Busy InputPin, DC
+
+    /// Whether [update_partial_frame](WaveshareDisplay::update_partial_frame) is supported
+    const SUPPORTS_PARTIAL: bool;
+    /// Whether [set_lut](WaveshareDisplay::set_lut) supports [RefreshLut::Quick]
     ///
-    /// This already initialises the device.
-    fn new(
-        spi: &mut SPI,
+    /// This is independent of [QuickRefresh] which some displays implement instead.
+    const SUPPORTS_QUICK_REFRESH: bool;
+    /// Whether [set_lut](WaveshareDisplay::set_lut) loads lookup tables into the controller
+    ///
+    /// Displays without it always refresh with the waveforms of their OTP.
+    const SUPPORTS_LUT: bool;
+    /// Whether the display can show gray levels
+    const SUPPORTS_GRAYSCALE: bool;
+    /// Number of separate frames for the colors: 2 for black/white/chromatic displays
+    /// with an extra chromatic frame, 1 for everything else
+    const COLOR_PLANES: u8;
+    /// Which bit the controller takes as black in the b/w frame
+    ///
+    /// The buffers always store white as a set bit ([Polarity::ClearedIsColor], see
+    /// [Color::to_controller_bit](crate::color::Color::to_controller_bit)). Drivers for
+    /// controllers with [Polarity::SetIsColor] flip the bytes while sending, so the same
+    /// buffer shows the same colors on every display.
+    const POLARITY: Polarity;
+    /// Whether the controller pulls the busy pin low while it is working
+    ///
+    /// [is_busy](WaveshareDisplay::is_busy) already takes care of this, it is only needed
+    /// when the pin is read directly, e.g. to wait for the right edge of an interrupt.
+    const IS_BUSY_LOW: bool;
+    /// Creates a new driver from a CS Pin, Busy InputPin, DC and RST Pin
+    ///
+    /// This doesn't talk to the device yet, so the spi bus doesn't need to be ready.
+    /// Call [init](WaveshareDisplay::init) before using it, or
+    /// [assume_init](WaveshareDisplay::assume_init) if the display is already initialized.
+    fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self
+    where
+        Self: Sized;
+
+    /// Like [new](WaveshareDisplay::new), but takes the polarity of the reset pin and drives
+    /// the pin to its released level right away
+    ///
+    /// Use it on boards which invert the reset line (`reset_active_high`), or to release a
+    /// reset pin the HAL configures as asserted. The first level on the pin is the released
+    /// one, the panel is never held in reset before the [init](WaveshareDisplay::init).
+    fn new_with_reset_polarity(
         cs: CS,
         busy: BUSY,
         dc: DC,
         rst: RST,
-        delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error>
+        reset_active_high: bool,
+    ) -> Result<Self, Error<SPI::Error>>
     where
-        Self: Sized;
+        Self: Sized,
+    {
+        let mut epd = Self::new(cs, busy, dc, rst);
+        epd.set_reset_active_high(reset_active_high)?;
+        Ok(epd)
+    }
+
+    /// This initialises the EPD and powers it up
+    ///
+    /// This function is also called from [wake_up](WaveshareDisplay::wake_up).
+    ///
+    /// It starts with a hardware reset, so you don't need to reset the device yourself
+    /// when trying to wake it up after setting it to sleep.
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+
+    /// Treat the display as initialized without talking to it
+    ///
+    /// Meant for an MCU waking up from deep sleep while the display was initialized
+    /// before and still shows its image, so running [init](WaveshareDisplay::init)
+    /// again would only waste time.
+    ///
+    /// The driver can't check the state of the display, so this is only correct if
+    /// - the display was initialized by the same driver and wasn't reset since
+    /// - the display wasn't put to [sleep](WaveshareDisplay::sleep), use
+    ///   [wake_up](WaveshareDisplay::wake_up) in that case
+    /// - settings changed after init (e.g. the refresh lut) are applied again, the driver
+    ///   starts with its defaults
+    fn assume_init(&mut self);
 
     /// Let the device enter deep-sleep mode to save power.
     ///
     /// The deep sleep mode returns to standby with a hardware reset.
-    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error>;
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
 
     /// Wakes the device up from sleep
     ///
     /// Also reintialises the device if necessary.
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error>;
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+
+    /// Use a pin to switch the power rail of the panel, e.g. PWR on rev 2.3 of the driver hat
+    ///
+    /// It is driven high before the reset in [init](WaveshareDisplay::init) and
+    /// [wake_up](WaveshareDisplay::wake_up) and low again after [sleep](WaveshareDisplay::sleep).
+    /// Call it before [init](WaveshareDisplay::init).
+    ///
+    /// Any [OutputPin] can be the [PowerPin], independent of the types of the
+    /// other pins. It is borrowed for `'static` like the [BusyWait], e.g. from a `static_cell`
+    /// or `cortex_m::singleton!`.
+    fn set_power_pin(&mut self, pwr: &'static mut dyn PowerPin);
+
+    /// Pause between chunks of data for setups which corrupt frames when sending too fast
+    ///
+    /// Defaults to `None`, sending the data as fast as the spi bus allows.
+    fn set_data_delay(&mut self, data_delay: Option<DataDelay>);
+
+    /// Release CS after every `chunk_size` bytes of data during long transfers
+    ///
+    /// Lets a bus sharing layer interleave the traffic of other devices on the same bus
+    /// with a frame, the controllers continue the data after CS is asserted again.
+    /// Defaults to `None`, keeping CS asserted for the whole transfer.
+    ///
+    /// # Panics
+    ///
+    /// If `chunk_size` is `Some(0)`
+    fn set_cs_release(&mut self, chunk_size: Option<usize>);
+
+    /// Replace how the driver waits while the display is busy, see [BusyWait]
+    ///
+    /// Defaults to [BusyPoll](crate::BusyPoll), polling the busy pin. The 7in5 v2 asks the
+    /// controller for its status every 20ms while waiting instead.
+    fn set_busy_wait(&mut self, busy_wait: &'static dyn BusyWait);
+
+    /// Read the busy pin several times until it settles, for noisy lines, see [BusyDebounce]
+    ///
+    /// Defaults to `None`, a single read per check.
+    fn set_busy_debounce(&mut self, busy_debounce: Option<BusyDebounce>);
+
+    /// Check in every reset, e.g. of [init](WaveshareDisplay::init), that the busy line is
+    /// really connected to the controller
+    ///
+    /// A controller which is held in reset reports busy and is idle again shortly after the
+    /// reset. If the line isn't busy in the reset or still busy `window_ms` after the usual
+    /// wait following it, the reset fails with [Error::BusyPinStuck] instead of waiting
+    /// forever or sending to a display which isn't ready. Stuck at high and stuck at low
+    /// point to different mistakes, e.g. a loose wire pulled to one level by the board or the
+    /// wrong pin. Defaults to `None`, no check.
+    fn set_busy_check(&mut self, window_ms: Option<u16>);
+
+    /// Call a hook during long transfers and refreshes, e.g. to feed a watchdog
+    ///
+    /// The hook gets called after every `chunk_size` bytes of data, e.g. during
+    /// [update_frame](WaveshareDisplay::update_frame), and every check of the busy pin, e.g.
+    /// during [display_frame](WaveshareDisplay::display_frame). Defaults to `None`, which
+    /// costs nothing.
+    fn set_progress(&mut self, progress: Option<ProgressHook>);
+
+    /// Measure how long the refreshes take with `clock`, see
+    /// [last_refresh](WaveshareDisplay::last_refresh)
+    ///
+    /// Defaults to `None`, which costs nothing. Setting it forgets the last refresh.
+    fn set_clock(&mut self, clock: Option<Clock>);
+
+    /// How long the display was busy during the last refresh which finished
+    ///
+    /// The power on before and the power off after the refresh are measured separately on
+    /// the drivers which switch the power for every refresh, a later
+    /// [sleep](WaveshareDisplay::sleep) powering off counts as the power off as well.
+    /// `None` without a [clock](WaveshareDisplay::set_clock) or before a refresh finished.
+    fn last_refresh(&self) -> Option<RefreshStats>;
+
+    /// Whether a high level on the reset pin resets the controller
+    ///
+    /// Defaults to `false`, the panels reset on a low level. Boards which invert the reset
+    /// line, e.g. with a transistor, need `true`. The pin is driven to the released level
+    /// right away, [new_with_reset_polarity](WaveshareDisplay::new_with_reset_polarity)
+    /// sets it while constructing the driver.
+    fn set_reset_active_high(&mut self, active_high: bool) -> Result<(), Error<SPI::Error>>;
 
     /// Sets the backgroundcolor for various commands like [clear_frame](WaveshareDisplay::clear_frame)
     fn set_background_color(&mut self, color: Self::DisplayColor);
@@ -167,6 +361,15 @@ where
     /// Get current background color
     fn background_color(&self) -> &Self::DisplayColor;
 
+    /// Swaps black and white of the background, e.g. in [clear_frame](WaveshareDisplay::clear_frame)
+    ///
+    /// Use it together with an [inverted](crate::graphics::Display::set_inverted) buffer so the
+    /// backgrounds match. Only the b/w frame is affected, 7 color displays ignore it.
+    fn set_inverted(&mut self, inverted: bool);
+
+    /// Whether black and white of the background are swapped
+    fn inverted(&self) -> bool;
+
     /// Get the width of the display
     fn width(&self) -> u32;
 
@@ -174,18 +377,34 @@ where
     fn height(&self) -> u32;
 
     /// Transmit a full frame to the SRAM of the EPD
+    ///
+    /// The buffer must hold exactly one frame, a longer one fails with [Error::BufferTooLong]
+    /// and a shorter one with [Error::BufferTooShort] before anything is sent. With the
+    /// `pad_short_buffers` feature a shorter buffer is padded with the
+    /// [background color](WaveshareDisplay::set_background_color) instead, e.g. a half-height
+    /// buffer leaves the lower half in the background color.
+    ///
+    /// On tri-color displays the chromatic frame is cleared to no color, whatever the
+    /// background color is. Use
+    /// [update_color_frame](WaveshareThreeColorDisplay::update_color_frame) to send both.
+    ///
+    /// How the buffer is split into spi writes, e.g. to send it with DMA, is described in
+    /// [dma](crate::dma).
     fn update_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Transmits partial data to the SRAM of the EPD
     ///
     /// (x,y) is the top left corner
     ///
-    /// BUFFER needs to be of size: width / 8 * height !
+    /// The window takes `width.div_ceil(8) * height` bytes, the controllers write whole bytes
+    /// of a row. Buffers of another length fail like in
+    /// [update_frame](WaveshareDisplay::update_frame), or are padded with the background
+    /// color if shorter and the `pad_short_buffers` feature is enabled.
     fn update_partial_frame(
         &mut self,
         spi: &mut SPI,
@@ -194,12 +413,48 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
+
+    /// Fills a window with the [background color](WaveshareDisplay::set_background_color)
+    /// without refreshing
+    ///
+    /// The window is aligned and checked like in
+    /// [update_partial_frame](WaveshareDisplay::update_partial_frame) and the rest of the
+    /// frame stays as it is, display it with [display_frame](WaveshareDisplay::display_frame)
+    /// whenever it suits. Displays without partial updates return [Error::Unsupported]
+    /// instead of clearing the whole frame.
+    fn clear_area(
+        &mut self,
+        _spi: &mut SPI,
+        _x: u32,
+        _y: u32,
+        _width: u32,
+        _height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
+    }
 
     /// Displays the frame data from SRAM
     ///
     /// This function waits until the device isn`t busy anymore
-    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error>;
+    ///
+    /// A new frame needs to be sent first, otherwise [`Error::NoFrame`] is returned
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+
+    /// Non-blocking variant of [display_frame](WaveshareDisplay::display_frame)
+    ///
+    /// The first call starts the refresh and returns `WouldBlock`, then every call returns
+    /// `WouldBlock` until the display isn't busy anymore. The last call finishes the refresh,
+    /// e.g. powers the display off again where [display_frame](WaveshareDisplay::display_frame)
+    /// would do that. Use it with `nb::block!` or poll it from a main loop.
+    ///
+    /// Only the wait for the refresh itself is non-blocking, a display still busy with a
+    /// previous command is waited for.
+    fn display_frame_nb(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> nb::Result<(), Error<SPI::Error>>;
 
     /// Provide a combined update&display and save some time (skipping a busy check in between)
     fn update_and_display_frame(
@@ -207,12 +462,51 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Clears the frame buffer on the EPD with the declared background color
     ///
     /// The background color can be changed with [`WaveshareDisplay::set_background_color`]
-    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error>;
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+
+    /// Fills the frame buffer on the EPD with the background color, without a refresh
+    ///
+    /// E.g. so an interrupted update never shows the old frame, or to draw on a cleared
+    /// frame and refresh once at the end with [display_frame](WaveshareDisplay::display_frame).
+    /// The same as [clear_frame](WaveshareDisplay::clear_frame) for all displays but the
+    /// 5.65" 7 color and the 7.5" V2 and HD, whose `clear_frame` refreshes as well.
+    fn clear_ram(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.clear_frame(spi, delay)
+    }
+
+    /// Sends a [test pattern](crate::pattern) over the whole display and shows it
+    ///
+    /// The pattern is generated while it's sent, so it needs neither a frame buffer nor the
+    /// `graphics` feature: a checkerboard inside of a border on b/w displays, black and
+    /// chromatic halves on tri-color displays and bars of every color on the 7 color
+    /// display. Meant for bringing up hardware, it rules out the drawing code.
+    fn test_pattern(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+
+    /// Clears `display` and the panel to the background color and shows the empty frame
+    ///
+    /// `display` is any graphics buffer, e.g. the display buffer of this driver. Tri-color
+    /// buffers get both planes cleared. The panel is refreshed once, also on the displays
+    /// whose [clear_frame](WaveshareDisplay::clear_frame) refreshes by itself.
+    #[cfg(feature = "graphics")]
+    fn clear_and_update<D>(
+        &mut self,
+        spi: &mut SPI,
+        display: &mut D,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        D: embedded_graphics_core::draw_target::DrawTarget<Error = core::convert::Infallible>,
+        Self::DisplayColor: Copy + Into<D::Color>,
+    {
+        let _ = display.clear((*self.background_color()).into());
+        self.clear_ram(spi, delay)?;
+        self.display_frame(spi, delay)
+    }
 
     /// Trait for using various Waveforms from different LUTs
     /// E.g. for partial refreshes
@@ -222,18 +516,55 @@ where
     /// WARNING: Quick Refresh might lead to ghosting-effects/problems with your display. Especially for the 4.2in Display!
     ///
     /// If None is used the old value will be loaded on the LUTs once more
+    ///
+    /// Never panics: displays without [SUPPORTS_LUT](WaveshareDisplay::SUPPORTS_LUT) take
+    /// `None` as a no-op and return [Error::Unsupported] for any LUT, and
+    /// [RefreshLut::Quick] returns it without
+    /// [SUPPORTS_QUICK_REFRESH](WaveshareDisplay::SUPPORTS_QUICK_REFRESH). Nothing is sent
+    /// to the display in these cases.
     fn set_lut(
         &mut self,
-        spi: &mut SPI,
+        _spi: &mut SPI,
         refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>> {
+        match refresh_rate {
+            None => Ok(()),
+            Some(_) => Err(Error::Unsupported),
+        }
+    }
 
-    /// Checks if the display is busy transmitting data
+    /// Checks if the display is still working on a command or refresh
+    ///
+    /// `true` means busy on every display, independent of the level the controller
+    /// drives the busy pin to (see [IS_BUSY_LOW](WaveshareDisplay::IS_BUSY_LOW)).
     ///
     /// This is normally handled by the more complicated commands themselves,
     /// but in the case you send data and commands directly you might need to check
     /// if the device is still busy
-    fn is_busy(&self) -> bool;
+    fn is_busy(&self) -> Result<bool, Error<SPI::Error>>;
+
+    /// What the display is currently doing, as far as the driver knows
+    ///
+    /// Commands are refused with [`Error::Asleep`] or [`Error::Uninitialized`]
+    /// in the corresponding states and [display_frame](WaveshareDisplay::display_frame)
+    /// fails with [`Error::NoFrame`] unless a new frame was sent after the last refresh.
+    fn state(&self) -> DisplayState;
+
+    /// Borrows the display until it is put to sleep again
+    ///
+    /// Wakes the display up if it is asleep. The returned [PoweredEpd] sends the display
+    /// to sleep with [finish](PoweredEpd::finish) or when it is dropped.
+    #[allow(clippy::type_complexity)]
+    fn powered<'a>(
+        &'a mut self,
+        spi: &'a mut SPI,
+        delay: &'a mut DELAY,
+    ) -> Result<PoweredEpd<'a, SPI, CS, BUSY, DC, RST, DELAY, Self>, Error<SPI::Error>>
+    where
+        Self: Sized,
+    {
+        PoweredEpd::new(self, spi, delay)
+    }
 }
 
 /// Allows quick refresh support for displays that support it; lets you send both
@@ -247,7 +578,7 @@ where
 /// Example:
 ///```rust, no_run
 ///# use embedded_hal_mock::*;
-///# fn main() -> Result<(), MockError> {
+///# fn main() -> Result<(), epd_waveshare::Error<MockError>> {
 ///# use embedded_graphics::{
 ///#   pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
 ///# };
@@ -264,11 +595,12 @@ where
 ///# let mut delay = delay::MockNoop::new();
 ///#
 ///# // Setup EPD
-///# let mut epd = Epd4in2::new(&mut spi, cs_pin, busy_in, dc, rst, &mut delay)?;
+///# let mut epd = Epd4in2::new(cs_pin, busy_in, dc, rst);
+///# epd.init(&mut spi, &mut delay)?;
 ///let (x, y, frame_width, frame_height) = (20, 40, 80,80);
 ///
 ///let mut buffer = [DEFAULT_BACKGROUND_COLOR.get_byte_value(); 80 / 8 * 80];
-///let mut display = VarDisplay::new(frame_width, frame_height, &mut buffer);
+///let mut display = VarDisplay::new(frame_width, frame_height, &mut buffer).unwrap();
 ///
 ///epd.update_partial_old_frame(&mut spi, display.buffer(), x, y, frame_width, frame_height)
 ///  .ok();
@@ -296,7 +628,7 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Updates the new frame.
     fn update_new_frame(
@@ -304,10 +636,14 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Displays the new frame
-    fn display_new_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error>;
+    fn display_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Updates and displays the new frame.
     fn update_and_display_new_frame(
@@ -315,7 +651,7 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Updates the old frame for a portion of the display.
     fn update_partial_old_frame(
@@ -326,7 +662,7 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Updates the new frame for a portion of the display.
     fn update_partial_new_frame(
@@ -337,7 +673,7 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Clears the partial frame buffer on the EPD with the declared background color
     /// The background color can be changed with [`WaveshareDisplay::set_background_color`]
@@ -348,5 +684,204 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
+}
+
+/// Transmits a full frame in pieces instead of one buffer
+///
+/// For displays whose frame doesn't fit into the RAM of the MCU.
+/// Start the frame with [begin_frame](StreamFrame::begin_frame), send its data in order
+/// and in as many pieces as needed with [frame_data](StreamFrame::frame_data) and finish it
+/// with [end_frame](StreamFrame::end_frame). The data is laid out exactly like the buffer
+/// of [update_frame](WaveshareDisplay::update_frame). Nothing else may be sent to the
+/// display before the frame is finished.
+///
+/// [stream_frame](crate::stream::stream_frame) builds the frame row by row on top of this.
+pub trait StreamFrame<SPI, CS, BUSY, DC, RST, DELAY>:
+    WaveshareDisplay<SPI, CS, BUSY, DC, RST, DELAY>
+where
+    SPI: Write<u8>,
+    CS: OutputPin,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayMs<u8>,
+{
+    /// Starts a new frame
+    fn begin_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+
+    /// Sends the next piece of the frame
+    fn frame_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>>;
+
+    /// Finishes the frame, afterwards it can be shown with
+    /// [display_frame](WaveshareDisplay::display_frame)
+    fn end_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+
+    /// Transmits a full frame given as consecutive pieces, e.g. the `rows()` of a display
+    fn update_frame_iter<'a, I>(
+        &mut self,
+        spi: &mut SPI,
+        data: I,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        self.begin_frame(spi, delay)?;
+        for piece in data {
+            self.frame_data(spi, piece)?;
+        }
+        self.end_frame(spi, delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{init_epd, BusyPin, DcPin, Delay, Pin, Spi};
+
+    #[test]
+    fn busy_polarity() {
+        // the level each controller drives while it works, from the datasheets. is_busy()
+        // has to report busy at that level: the mock BusyPin reads low first.
+        macro_rules! check {
+            ($($module:ident::$epd:ident: $busy_low:expr,)*) => {$({
+                type Epd = crate::$module::$epd<Spi, Pin, BusyPin, Pin, Pin, Delay>;
+                assert_eq!(Epd::IS_BUSY_LOW, $busy_low, stringify!($module));
+                let epd = Epd::new(Pin, BusyPin::default(), Pin, Pin);
+                assert_eq!(epd.is_busy().unwrap(), $busy_low, stringify!($module));
+            })*};
+        }
+        check! {
+            epd1in54::Epd1in54: false,
+            epd1in54b::Epd1in54b: true,
+            epd1in54c::Epd1in54c: true,
+            epd2in13_v2::Epd2in13: false,
+            epd2in13_v3::Epd2in13: false,
+            epd2in13_v4::Epd2in13: false,
+            epd2in13bc::Epd2in13bc: true,
+            epd2in7b::Epd2in7b: true,
+            epd2in9::Epd2in9: false,
+            epd2in9_v2::Epd2in9: false,
+            epd2in9bc::Epd2in9bc: true,
+            epd3in7::Epd3in7: false,
+            epd4in2::Epd4in2: true,
+            epd5in65f::Epd5in65f: true,
+            epd7in5::Epd7in5: true,
+            epd7in5_hd::Epd7in5: false,
+            epd7in5_v2::Epd7in5: true,
+        }
+    }
+
+    // update_partial_frame takes a window on the displays with partial windows and refuses
+    // it without sending anything on the others
+    fn check_update_partial_frame<EPD>(name: &str)
+    where
+        EPD: WaveshareDisplay<Spi, Pin, BusyPin, DcPin, Pin, Delay>,
+    {
+        let (log, mut spi, _delay, mut epd) = init_epd::<EPD>();
+        log.clear();
+        let result = epd.update_partial_frame(&mut spi, &[0xFF; 8], 8, 8, 8, 8);
+        if EPD::SUPPORTS_PARTIAL {
+            assert!(result.is_ok(), "{}", name);
+        } else {
+            assert!(matches!(result, Err(Error::Unsupported)), "{}", name);
+            assert!(log.transfers().is_empty(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn update_partial_frame_follows_the_capabilities() {
+        macro_rules! check {
+            ($($module:ident::$epd:ident: $partial:expr,)*) => {$({
+                type Epd<SPI, CS, BUSY, DC, RST, DELAY> =
+                    crate::$module::$epd<SPI, CS, BUSY, DC, RST, DELAY>;
+                assert_eq!(
+                    <Epd<Spi, Pin, BusyPin, DcPin, Pin, Delay>>::SUPPORTS_PARTIAL,
+                    $partial,
+                    stringify!($module)
+                );
+                check_update_partial_frame::<Epd<_, _, _, _, _, _>>(stringify!($module));
+            })*};
+        }
+        check! {
+            epd1in54::Epd1in54: true,
+            epd1in54b::Epd1in54b: false,
+            epd1in54c::Epd1in54c: false,
+            epd2in13_v2::Epd2in13: true,
+            epd2in13_v3::Epd2in13: true,
+            epd2in13_v4::Epd2in13: true,
+            epd2in13bc::Epd2in13bc: true,
+            epd2in7b::Epd2in7b: true,
+            epd2in9::Epd2in9: true,
+            epd2in9_v2::Epd2in9: true,
+            epd2in9bc::Epd2in9bc: false,
+            epd3in7::Epd3in7: false,
+            epd4in2::Epd4in2: true,
+            epd5in65f::Epd5in65f: false,
+            epd7in5::Epd7in5: false,
+            epd7in5_hd::Epd7in5: false,
+            epd7in5_v2::Epd7in5: false,
+        }
+    }
+
+    // set_lut loads the tables the capabilities promise and refuses the others without
+    // sending anything
+    fn check_set_lut<EPD>(name: &str)
+    where
+        EPD: WaveshareDisplay<Spi, Pin, BusyPin, DcPin, Pin, Delay>,
+    {
+        let (log, mut spi, _delay, mut epd) = init_epd::<EPD>();
+        assert!(epd.set_lut(&mut spi, None).is_ok(), "{}", name);
+        for &lut in [RefreshLut::Full, RefreshLut::Quick].iter() {
+            log.clear();
+            let result = epd.set_lut(&mut spi, Some(lut));
+            if EPD::SUPPORTS_LUT && (lut == RefreshLut::Full || EPD::SUPPORTS_QUICK_REFRESH) {
+                assert!(result.is_ok(), "{} {:?}", name, lut);
+            } else {
+                assert!(
+                    matches!(result, Err(Error::Unsupported)),
+                    "{} {:?}",
+                    name,
+                    lut
+                );
+                assert!(log.transfers().is_empty(), "{} {:?}", name, lut);
+            }
+        }
+    }
+
+    #[test]
+    fn set_lut_follows_the_capabilities() {
+        macro_rules! check {
+            ($($module:ident::$epd:ident: $lut:expr,)*) => {$({
+                type Epd<SPI, CS, BUSY, DC, RST, DELAY> =
+                    crate::$module::$epd<SPI, CS, BUSY, DC, RST, DELAY>;
+                assert_eq!(
+                    <Epd<Spi, Pin, BusyPin, DcPin, Pin, Delay>>::SUPPORTS_LUT,
+                    $lut,
+                    stringify!($module)
+                );
+                check_set_lut::<Epd<_, _, _, _, _, _>>(stringify!($module));
+            })*};
+        }
+        check! {
+            epd1in54::Epd1in54: true,
+            epd1in54b::Epd1in54b: true,
+            epd1in54c::Epd1in54c: false,
+            epd2in13_v2::Epd2in13: true,
+            epd2in13_v3::Epd2in13: false,
+            epd2in13_v4::Epd2in13: false,
+            epd2in13bc::Epd2in13bc: false,
+            epd2in7b::Epd2in7b: true,
+            epd2in9::Epd2in9: true,
+            epd2in9_v2::Epd2in9: false,
+            epd2in9bc::Epd2in9bc: false,
+            epd3in7::Epd3in7: true,
+            epd4in2::Epd4in2: true,
+            epd5in65f::Epd5in65f: false,
+            epd7in5::Epd7in5: false,
+            epd7in5_hd::Epd7in5: false,
+            epd7in5_v2::Epd7in5: false,
+        }
+    }
 }
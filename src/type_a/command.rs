@@ -1,8 +1,8 @@
-//! SPI Commands for the Waveshare 2.9" and 1.54" E-Ink Display
+//! SPI Commands for the Waveshare 2.9", 1.54" and 2.13" V3 E-Ink Displays
 
 use crate::traits;
 
-/// Epd1in54 and EPD2IN9 commands
+/// Epd1in54, EPD2IN9 and Epd2in13 V3 commands
 ///
 /// Should rarely (never?) be needed directly.
 ///
@@ -17,6 +17,10 @@ pub(crate) enum Command {
     ///     0.. B[2:0]
     ///     Default: Set A[8:0] = 0x127 and B[2:0] = 0x0
     DriverOutputControl = 0x01,
+    /// Gate driving voltage (VGH), 1 Databyte
+    GateDrivingVoltageControl = 0x03,
+    /// Source driving voltages (VSH1, VSH2, VSL), 3 Databytes
+    SourceDrivingVoltageControl = 0x04,
     /// Booster Soft start control
     ///     3 Databytes:
     ///     1.. A[6:0]
@@ -39,6 +43,9 @@ pub(crate) enum Command {
 
     SwReset = 0x12,
 
+    /// Selects the internal (0x80) or an external temperature sensor
+    TemperatureSensorSelection = 0x18,
+
     TemperatureSensorControl = 0x1A,
 
     MasterActivation = 0x20,
@@ -63,6 +70,9 @@ pub(crate) enum Command {
 
     BorderWaveformControl = 0x3C,
 
+    /// End option (EOPT) after the waveform of the LUT
+    EndOption = 0x3F,
+
     SetRamXAddressStartEndPosition = 0x44,
 
     SetRamYAddressStartEndPosition = 0x45,
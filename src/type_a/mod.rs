@@ -1,2 +1,69 @@
 pub(crate) mod command;
 pub(crate) mod constants;
+
+use crate::traits::HardwareFlip;
+
+/// The data entry mode and the RAM addresses of the SSD16xx controllers for a [HardwareFlip]
+///
+/// The controller mirrors by running its address counters backwards, so a window in the
+/// coordinates of the buffer starts at its mirrored corner and ends at the opposite one.
+/// The x addresses are bytes of 8 pixels whose order the counter doesn't change, for the
+/// mirrored x the bits of every byte are reversed while they are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RamLayout {
+    width: u32,
+    height: u32,
+    flip: HardwareFlip,
+}
+
+impl RamLayout {
+    /// The layout of a panel of `width` x `height` pixels, `width` a multiple of 8
+    pub(crate) const fn new(width: u32, height: u32, flip: HardwareFlip) -> Self {
+        RamLayout {
+            width,
+            height,
+            flip,
+        }
+    }
+
+    /// The byte of the data entry mode (0x11): x and y increment unless they are mirrored,
+    /// the counter is updated in x direction
+    pub(crate) fn data_entry_mode(self) -> u8 {
+        let x = if self.flip.mirrors_x() { 0x00 } else { 0x01 };
+        let y = if self.flip.mirrors_y() { 0x00 } else { 0x02 };
+        x | y
+    }
+
+    /// The x address of the byte with the pixel at `x`
+    pub(crate) fn x_address(self, x: u32) -> u8 {
+        if self.flip.mirrors_x() {
+            (self.width / 8 - 1 - x / 8) as u8
+        } else {
+            (x / 8) as u8
+        }
+    }
+
+    /// The y address of the row `y`: A[7:0] & 0..A[8]
+    pub(crate) fn y_address(self, y: u32) -> [u8; 2] {
+        let y = if self.flip.mirrors_y() {
+            self.height - 1 - y
+        } else {
+            y
+        };
+        [y as u8, (y >> 8) as u8]
+    }
+
+    /// Whether the bits of every byte of a frame are sent in reverse order
+    pub(crate) fn reverses_bits(self) -> bool {
+        self.flip.mirrors_x()
+    }
+
+    /// A byte of a frame as it is sent
+    pub(crate) fn byte(self, byte: u8) -> u8 {
+        if self.reverses_bits() {
+            byte.reverse_bits()
+        } else {
+            byte
+        }
+    }
+}